@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use raycasting_engine::assets;
+
+// Corrupt and oversized images must fail to decode, not panic or run out of memory
+fuzz_target!(|data: &[u8]| {
+    if let Ok(image) = assets::decode_image(data) {
+        assert!(image.width() <= assets::MAX_IMAGE_SIZE);
+        assert!(image.height() <= assets::MAX_IMAGE_SIZE);
+    }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use raycasting_engine::map::{Map, Migrations};
+
+// Whatever decodes must be a valid map, and must survive encoding again
+fuzz_target!(|data: &[u8]| {
+    // Only a few paths, so decoding doesn't leak new ones each run
+    let migrations = Migrations::new().with_allowed_paths(["a", "b"]);
+    if let Ok(map) = Map::from_bytes_with_migrations(data, &migrations) {
+        map.validate().expect("decoded an invalid map");
+        let again = Map::from_bytes_with_migrations(&map.to_bytes(), &migrations)
+            .expect("re-encoded map didn't decode");
+        assert_eq!(again.size().cols(), map.size().cols());
+        assert_eq!(again.size().rows(), map.size().rows());
+    }
+});
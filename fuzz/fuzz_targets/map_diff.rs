@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use raycasting_engine::map::{Exploration, MapDiff, Migrations};
+
+fuzz_target!(|data: &[u8]| {
+    // Only a few paths, as for diffs from other players, so decoding doesn't leak new ones
+    let migrations = Migrations::new().with_allowed_paths(["a", "b"]);
+    let _ = MapDiff::from_bytes_with_migrations(data, &migrations);
+    let _ = Exploration::from_bytes(data);
+});
@@ -0,0 +1,29 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use raycasting_engine::{
+    map::{Map, TileData, TileType, TileTypes},
+    prelude::vec2,
+};
+
+// Grids built in code, ragged or empty, must be refused by validate rather than panic later
+fuzz_target!(|tiles: Vec<Vec<u8>>| {
+    let tile_types = TileTypes::from([(1, TileType::Wall(TileData::new("wall.png")))]);
+    let map = Map::new(tiles, tile_types);
+    if map.validate().is_err() {
+        return;
+    }
+
+    let size = map.size();
+    assert_eq!(map.cells().count(), size.cols() * size.rows());
+    for (row, col, _) in map.walls() {
+        assert_eq!(
+            map.world_to_cell(map.cell_to_world_center(row, col)),
+            Some((row, col))
+        );
+    }
+    assert_eq!(map.world_to_cell(vec2(-1.0, 0.0)), None);
+
+    let decoded = Map::from_bytes(&map.to_bytes()).expect("valid map didn't round trip");
+    assert!(decoded.cells().eq(map.cells()));
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use raycasting_engine::wad::{Wad, WadImport, WadTextures};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(wad) = Wad::from_bytes(data.to_vec()) else {
+        return;
+    };
+    let import = WadImport::new(WadTextures::new("wall.png", "floor.png"));
+    for name in wad.map_names() {
+        if let Ok(level) = import.import(&wad, name) {
+            level.map.validate().expect("imported an invalid map");
+        }
+    }
+});
@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use glam::Vec2;
+
+use crate::entity::Body;
+
+/// What an entity with an [`Ai`] is doing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AiState {
+    /// Standing still, with no patrol path to follow
+    #[default]
+    Idle,
+    /// Walking to the next waypoint of its patrol path
+    Patrolling,
+    /// Waiting at a waypoint before walking to the next
+    Pausing,
+    /// Walking to where it heard a noise
+    Investigating,
+    /// Running at a hostile body it can see
+    Chasing,
+    /// Walking around where it last saw what it was chasing, until it loses interest
+    Searching,
+}
+
+/// Enemy behavior the engine runs each step by setting an entity's velocity. The entity walks
+/// a [`PatrolPath`](crate::map::PatrolPath) of the map, pausing at each waypoint, and chases
+/// the nearest body it has a clear line of sight to within its sight range whose faction is
+/// hostile to its own, such as the player. Losing sight sends it to search around where it
+/// last saw its target for a while, then back to its patrol, with an
+/// [`AiEvent`](crate::engine::AiEvent) each time. Noises it hears, such as from
+/// [`Engine::make_noise`](crate::engine::Engine::make_noise), draw it to where they were made
+/// before it goes back. It walks to waypoints and noises by the cheapest route around walls,
+/// which [`Map::with_path_cost`](crate::map::Map::with_path_cost) and
+/// [`Engine::stamp_path_cost`](crate::engine::Engine::stamp_path_cost) steer. Shut
+/// [doors](crate::map::DoorData) are in its way unless it [can open](Ai::with_door_opening)
+/// them, in which case it routes through them and opens them as it gets there.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use glam::vec2;
+/// use raycasting_engine::{ai::Ai, entity::Entity};
+///
+/// let guard = Entity::new(vec2(160.0, 96.0))
+///     .with_sprite("sprites/guard.png")
+///     .with_ai(
+///         Ai::new(96.0)
+///             .with_sight_range(640.0)
+///             .with_memory(Duration::from_secs(8))
+///             .with_patrol("courtyard", Duration::from_secs(2)),
+///     );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ai {
+    /// World units per second
+    pub(crate) speed: f32,
+    pub(crate) sight_range: f32,
+    /// The name of the map's patrol path and how long to wait at each of its waypoints
+    pub(crate) patrol: Option<(String, Duration)>,
+    pub(crate) opens_doors: bool,
+    /// Seconds it searches for a target it lost sight of
+    pub(crate) memory: f32,
+    /// How far from where it last saw its target it searches, in world units
+    pub(crate) search_radius: f32,
+    pub(crate) state: AiState,
+    /// Index of the waypoint being walked to or waited at
+    pub(crate) waypoint: usize,
+    /// Seconds left to wait at the waypoint
+    pub(crate) pause_left: f32,
+    /// Where the last noise it heard was made, until it gets there
+    pub(crate) heard: Option<Vec2>,
+    /// The body being chased
+    pub(crate) target: Option<Body>,
+    /// Where the body being chased was last seen, until it gives up searching
+    pub(crate) last_seen: Option<Vec2>,
+    /// Seconds left searching
+    pub(crate) search_left: f32,
+    /// The spot near `last_seen` being walked to while searching
+    pub(crate) search_point: Option<Vec2>,
+    /// Waypoints left on the way to `route_goal`, the next one last
+    pub(crate) route: Vec<Vec2>,
+    pub(crate) route_goal: Option<Vec2>,
+    /// Seconds since the route was planned
+    pub(crate) route_age: f32,
+}
+
+impl Ai {
+    /// Moves at `speed` world units per second and sees 512 world units, with no patrol.
+    /// Searches for 5 seconds within 128 world units of where it lost its target.
+    pub fn new(speed: f32) -> Self {
+        Self {
+            speed: speed.max(0.0),
+            sight_range: 512.0,
+            patrol: None,
+            opens_doors: false,
+            memory: 5.0,
+            search_radius: 128.0,
+            state: AiState::Idle,
+            waypoint: 0,
+            pause_left: 0.0,
+            heard: None,
+            target: None,
+            last_seen: None,
+            search_left: 0.0,
+            search_point: None,
+            route: Vec::new(),
+            route_goal: None,
+            route_age: 0.0,
+        }
+    }
+
+    pub fn with_sight_range(mut self, sight_range: f32) -> Self {
+        self.sight_range = sight_range.max(0.0);
+        self
+    }
+    /// Follows the map's patrol path named `path`, waiting `pause` at each waypoint.
+    pub fn with_patrol(mut self, path: impl Into<String>, pause: Duration) -> Self {
+        self.patrol = Some((path.into(), pause));
+        self
+    }
+    /// How long it searches for a target it lost sight of before giving up. Zero sends it
+    /// straight back to what it was doing.
+    pub fn with_memory(mut self, memory: Duration) -> Self {
+        self.memory = memory.as_secs_f32();
+        self
+    }
+    /// How far from where it last saw its target it wanders while searching, in world units.
+    pub fn with_search_radius(mut self, search_radius: f32) -> Self {
+        self.search_radius = search_radius.max(0.0);
+        self
+    }
+    /// Whether it can open doors, so it plans routes through shut ones instead of around.
+    pub fn with_door_opening(mut self, opens_doors: bool) -> Self {
+        self.opens_doors = opens_doors;
+        self
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+    pub fn sight_range(&self) -> f32 {
+        self.sight_range
+    }
+    pub fn patrol_path(&self) -> Option<&str> {
+        self.patrol.as_ref().map(|(path, _)| path.as_str())
+    }
+    pub fn pause(&self) -> Duration {
+        self.patrol
+            .as_ref()
+            .map_or(Duration::ZERO, |(_, pause)| *pause)
+    }
+    pub fn memory(&self) -> Duration {
+        Duration::from_secs_f32(self.memory)
+    }
+    pub fn search_radius(&self) -> f32 {
+        self.search_radius
+    }
+    pub fn opens_doors(&self) -> bool {
+        self.opens_doors
+    }
+    pub fn state(&self) -> AiState {
+        self.state
+    }
+    /// The body it's chasing, if any.
+    pub fn target(&self) -> Option<Body> {
+        self.target
+    }
+    /// Where it last saw what it was chasing, while it's chasing or searching.
+    pub fn last_seen(&self) -> Option<Vec2> {
+        self.last_seen
+    }
+}
@@ -0,0 +1,306 @@
+use std::{collections::HashMap, fmt, future::Future, io::Cursor, pin::Pin, sync::Arc};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+#[cfg(feature = "zip")]
+use std::{io::Read, sync::Mutex};
+
+use anyhow::Context;
+use image::{DynamicImage, ImageReader, Limits};
+
+pub type AssetFuture<'a> = Pin<Box<dyn Future<Output = anyhow::Result<Vec<u8>>> + 'a>>;
+
+/// Where the engine reads asset bytes from. Paths are relative, e.g. `"walls/wall1.png"`.
+///
+/// Loading is async so sources like `fetch` on the web can be used; sources that read
+/// synchronously can return a ready future. Sources are shared with the texture loader's
+/// worker threads, so they must be `Send + Sync`.
+///
+/// The engine reads from [`EngineConfig::assets`](crate::config::EngineConfig), so a game can
+/// bundle its assets however it likes.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use raycasting_engine::{
+///     assets::{AssetFuture, AssetSource},
+///     config::EngineConfig,
+/// };
+///
+/// /// Every asset is the same blank file.
+/// struct Blank;
+///
+/// impl AssetSource for Blank {
+///     fn load<'a>(&'a self, _rel_path: &'a str) -> AssetFuture<'a> {
+///         Box::pin(std::future::ready(Ok(Vec::new())))
+///     }
+/// }
+///
+/// let config = EngineConfig {
+///     assets: Arc::new(Blank),
+///     ..Default::default()
+/// };
+/// ```
+pub trait AssetSource: Send + Sync {
+    fn load<'a>(&'a self, rel_path: &'a str) -> AssetFuture<'a>;
+}
+
+impl fmt::Debug for dyn AssetSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AssetSource")
+    }
+}
+
+/// Reads assets from directories on disk, trying each root in order. [`new`](Self::new) uses
+/// the `res` directory next to the crate, the executable or the working directory. Paths that
+/// escape their root with `..` are refused.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug)]
+pub struct FileSystemAssets {
+    roots: Vec<PathBuf>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileSystemAssets {
+    pub fn new() -> Self {
+        // ASSETS_DIR from build.rs, then project res, exe-res, cwd/res
+        let roots = [
+            option_env!("ASSETS_DIR").map(PathBuf::from),
+            Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("res")),
+            std::env::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(|p| p.join("res"))),
+            std::env::current_dir().ok().map(|cwd| cwd.join("res")),
+        ];
+        Self {
+            roots: roots.into_iter().flatten().collect(),
+        }
+    }
+
+    /// Reads from these directories instead, in order.
+    pub fn with_roots(roots: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        Self {
+            roots: roots.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for FileSystemAssets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AssetSource for FileSystemAssets {
+    fn load<'a>(&'a self, rel_path: &'a str) -> AssetFuture<'a> {
+        Box::pin(std::future::ready(load_asset(&self.roots, rel_path)))
+    }
+}
+
+/// Serves assets compiled into the program, usually with `include_bytes!`, so it runs without
+/// any files beside it. Works on every target.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::assets::{AssetSource, EmbeddedAssets};
+///
+/// let assets = EmbeddedAssets::new()
+///     .with_file("walls/wall1.png", include_bytes!("../res/walls/wall1.png"));
+///
+/// assert!(pollster::block_on(assets.load("walls/wall1.png")).is_ok());
+/// assert!(pollster::block_on(assets.load("walls/wall2.png")).is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct EmbeddedAssets {
+    files: HashMap<String, &'static [u8]>,
+}
+
+impl EmbeddedAssets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_file(mut self, rel_path: impl Into<String>, bytes: &'static [u8]) -> Self {
+        self.files.insert(rel_path.into(), bytes);
+        self
+    }
+}
+
+impl AssetSource for EmbeddedAssets {
+    fn load<'a>(&'a self, rel_path: &'a str) -> AssetFuture<'a> {
+        let bytes = self
+            .files
+            .get(rel_path)
+            .map(|bytes| bytes.to_vec())
+            .with_context(|| format!("asset not embedded: {rel_path}"));
+        Box::pin(std::future::ready(bytes))
+    }
+}
+
+/// Reads assets from a zip archive, such as a game's `res` directory zipped into one file.
+/// Paths are relative to the archive's root. Needs the `zip` feature.
+#[cfg(feature = "zip")]
+pub struct ZipAssets {
+    /// Behind a mutex since reading an entry seeks the archive
+    archive: Mutex<zip::ZipArchive<Cursor<Vec<u8>>>>,
+}
+
+#[cfg(feature = "zip")]
+impl ZipAssets {
+    pub fn from_bytes(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let archive = zip::ZipArchive::new(Cursor::new(bytes)).context("not a zip archive")?;
+        Ok(Self {
+            archive: Mutex::new(archive),
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::from_bytes(bytes).with_context(|| format!("failed to open {}", path.display()))
+    }
+
+    fn read(&self, rel_path: &str) -> anyhow::Result<Vec<u8>> {
+        let mut archive = self.archive.lock().unwrap();
+        let mut file = archive
+            .by_name(rel_path)
+            .with_context(|| format!("asset not in the archive: {rel_path}"))?;
+        let mut bytes = Vec::with_capacity(file.size().min(MAX_PREALLOCATION) as usize);
+        file.read_to_end(&mut bytes)
+            .with_context(|| format!("failed to unzip asset {rel_path}"))?;
+        Ok(bytes)
+    }
+}
+
+/// The most read ahead of time from a zip entry's claimed size, which a corrupt archive can
+/// set to anything.
+#[cfg(feature = "zip")]
+const MAX_PREALLOCATION: u64 = 64 * 1024 * 1024;
+
+#[cfg(feature = "zip")]
+impl AssetSource for ZipAssets {
+    fn load<'a>(&'a self, rel_path: &'a str) -> AssetFuture<'a> {
+        Box::pin(std::future::ready(self.read(rel_path)))
+    }
+}
+
+/// Fetches assets over HTTP, relative to `base_url` (usually the page's `res` directory).
+#[cfg(target_arch = "wasm32")]
+pub struct FetchAssets {
+    base_url: String,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl FetchAssets {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl AssetSource for FetchAssets {
+    fn load<'a>(&'a self, rel_path: &'a str) -> AssetFuture<'a> {
+        use wasm_bindgen::JsCast;
+        use wasm_bindgen_futures::JsFuture;
+
+        fn js_err(err: wasm_bindgen::JsValue) -> anyhow::Error {
+            anyhow::anyhow!("{err:?}")
+        }
+
+        Box::pin(async move {
+            let url = format!("{}/{}", self.base_url.trim_end_matches('/'), rel_path);
+            let window = web_sys::window().context("no global window to fetch assets with")?;
+
+            let response: web_sys::Response = JsFuture::from(window.fetch_with_str(&url))
+                .await
+                .map_err(js_err)?
+                .dyn_into()
+                .map_err(js_err)?;
+            anyhow::ensure!(
+                response.ok(),
+                "failed to fetch asset {url}: HTTP {}",
+                response.status()
+            );
+
+            let buffer = JsFuture::from(response.array_buffer().map_err(js_err)?)
+                .await
+                .map_err(js_err)?;
+            Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+        })
+    }
+}
+
+/// The widest or tallest image the engine decodes, in pixels. No GPU takes textures larger.
+pub const MAX_IMAGE_SIZE: u32 = 16384;
+
+/// Decodes PNG or JPEG bytes as textures are. Images wider or taller than [`MAX_IMAGE_SIZE`]
+/// are refused before anything is allocated for them, so a corrupt or hostile file fails
+/// instead of exhausting memory.
+pub fn decode_image(bytes: &[u8]) -> anyhow::Result<DynamicImage> {
+    let mut reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_SIZE);
+    limits.max_image_height = Some(MAX_IMAGE_SIZE);
+    reader.limits(limits);
+    Ok(reader.decode()?)
+}
+
+/// The source used when the game doesn't provide one.
+pub(crate) fn default_source() -> Arc<dyn AssetSource> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return Arc::new(FileSystemAssets::new());
+    #[cfg(target_arch = "wasm32")]
+    return Arc::new(FetchAssets::new("res"));
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_asset(roots: &[PathBuf], rel_path: &str) -> anyhow::Result<Vec<u8>> {
+    // Reject absolute paths to enforce assets rooted under `res/` by default.
+    let rel_path = Path::new(rel_path);
+    if rel_path.is_absolute() {
+        anyhow::bail!(
+            "expected relative asset path, got absolute: {}",
+            rel_path.display()
+        );
+    }
+
+    for root in roots {
+        let full = root.join(rel_path);
+
+        // Skip if file doesn't exist at this root
+        if !full.exists() {
+            continue;
+        }
+
+        // canonicalize both root and file to protect against path traversal (..)
+        let canon_root = root
+            .canonicalize()
+            .with_context(|| format!("failed to canonicalize assets root {}", root.display()))?;
+        let canon_full = full
+            .canonicalize()
+            .with_context(|| format!("failed to canonicalize asset path {}", full.display()))?;
+
+        // Ensure the final path is inside the assets root
+        if !canon_full.starts_with(&canon_root) {
+            // This means rel_path tried to escape the assets dir.
+            continue;
+        }
+
+        // Finally read and return the bytes
+        let bytes = fs::read(&canon_full)
+            .with_context(|| format!("failed to read asset {}", canon_full.display()))?;
+        return Ok(bytes);
+    }
+
+    anyhow::bail!("asset not found: {}", rel_path.display());
+}
@@ -0,0 +1,489 @@
+mod reverb;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    collections::HashMap,
+    f32::consts::FRAC_PI_4,
+    io::Cursor,
+    sync::{Arc, Mutex, mpsc},
+};
+
+use anyhow::Context;
+use glam::Vec2;
+
+use crate::{
+    assets::AssetSource, audio::reverb::ReverbMix, config::AudioConfig, engine::AssetEvent,
+    map::Map, sequence::Step,
+};
+
+pub use reverb::{ReverbPreset, ReverbZone};
+
+/// A group of sounds sharing a volume, set in [`AudioConfig`]. Sounds on the music bus are
+/// left out of reverb.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioBus {
+    /// Only the master volume applies
+    Master,
+    Music,
+    Sfx,
+}
+
+/// A sound placed in a map, started when the map loads. Positional emitters get quieter with
+/// distance and pan toward the side they're on; ambient ones play at the same volume
+/// everywhere.
+///
+/// Sounds are WAV files, mixed down to mono.
+///
+/// # Example
+///
+/// ```
+/// use glam::vec2;
+/// use raycasting_engine::audio::{AudioBus, SoundEmitter};
+///
+/// let drip = SoundEmitter::new(vec2(160.0, 96.0), "sounds/drip.wav").with_radius(192.0);
+/// let hum = SoundEmitter::ambient("sounds/hum.wav").with_volume(0.3);
+/// let theme = SoundEmitter::ambient("music/theme.wav").with_bus(AudioBus::Music);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct SoundEmitter {
+    pub(crate) position: Vec2,
+    pub(crate) sound_path: &'static str,
+    /// Distance at which the sound fades out completely, or `None` for ambient sounds
+    pub(crate) radius: Option<f32>,
+    pub(crate) looping: bool,
+    pub(crate) volume: f32,
+    pub(crate) bus: AudioBus,
+}
+
+impl SoundEmitter {
+    /// A looping sound at `position`, audible within 256 world units.
+    pub fn new(position: Vec2, sound_path: &'static str) -> Self {
+        Self {
+            position,
+            sound_path,
+            radius: Some(256.0),
+            looping: true,
+            volume: 1.0,
+            bus: AudioBus::Sfx,
+        }
+    }
+
+    /// A looping sound heard the same everywhere in the map.
+    pub fn ambient(sound_path: &'static str) -> Self {
+        Self {
+            radius: None,
+            ..Self::new(Vec2::ZERO, sound_path)
+        }
+    }
+
+    /// How far away the sound can be heard, in world units.
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = Some(radius.max(1.0));
+        self
+    }
+
+    /// Plays the sound once instead of looping it.
+    pub fn once(mut self) -> Self {
+        self.looping = false;
+        self
+    }
+
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume.max(0.0);
+        self
+    }
+
+    /// Which bus's volume the sound follows. Defaults to [`AudioBus::Sfx`].
+    pub fn with_bus(mut self, bus: AudioBus) -> Self {
+        self.bus = bus;
+        self
+    }
+
+    pub fn position(&self) -> Vec2 {
+        self.position
+    }
+}
+
+/// A decoded sound, shared between everything playing it.
+#[derive(Clone)]
+pub(crate) struct Sound {
+    samples: Arc<[f32]>,
+    sample_rate: u32,
+}
+
+impl Sound {
+    pub fn from_wav(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = hound::WavReader::new(Cursor::new(bytes))?;
+        let spec = reader.spec();
+        let interleaved: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1_i64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|sample| sample.map(|sample| sample as f32 * scale))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+
+        let channels = spec.channels.max(1) as usize;
+        let samples = interleaved
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+
+        Ok(Self {
+            samples,
+            sample_rate: spec.sample_rate,
+        })
+    }
+}
+
+/// One emitter's sound playing.
+struct Voice {
+    sound: Sound,
+    emitter: SoundEmitter,
+    /// Position in the sound, in its own samples
+    cursor: f64,
+}
+
+/// Mixes the playing sounds for the listener. Shared between the engine, which moves the
+/// listener and starts sounds, and the audio output pulling samples.
+#[derive(Default)]
+struct Mixer {
+    voices: Vec<Voice>,
+    listener_position: Vec2,
+    /// Radians, with the listener's right at a quarter turn clockwise
+    listener_rotation: f32,
+    reverb: ReverbMix,
+    volumes: AudioConfig,
+}
+
+impl Mixer {
+    /// Left and right gains for an emitter heard from the listener's position.
+    fn gains(&self, emitter: &SoundEmitter) -> (f32, f32) {
+        let volume = emitter.volume * self.volumes.gain(emitter.bus);
+        let Some(radius) = emitter.radius else {
+            let gain = volume * FRAC_PI_4.cos();
+            return (gain, gain);
+        };
+
+        let offset = emitter.position - self.listener_position;
+        let falloff = (1.0 - offset.length() / radius).max(0.0);
+        let gain = volume * falloff * falloff;
+        // Equal power panning, from -1 on the left to 1 on the right
+        let pan = if offset == Vec2::ZERO {
+            0.0
+        } else {
+            (offset.y.atan2(offset.x) - self.listener_rotation).sin()
+        };
+        let angle = (pan + 1.0) * FRAC_PI_4;
+        (gain * angle.cos(), gain * angle.sin())
+    }
+
+    fn mix(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        out.fill(0.0);
+        if channels == 0 || sample_rate == 0 {
+            return;
+        }
+
+        // Music is added after the reverb so it stays dry
+        let mut finished = Vec::new();
+        for music in [false, true] {
+            for index in 0..self.voices.len() {
+                if (self.voices[index].emitter.bus == AudioBus::Music) == music
+                    && self.mix_voice(index, out, channels, sample_rate)
+                {
+                    finished.push(index);
+                }
+            }
+            if !music {
+                self.reverb.process(out, channels, sample_rate);
+            }
+        }
+
+        finished.sort_unstable();
+        for index in finished.into_iter().rev() {
+            self.voices.swap_remove(index);
+        }
+    }
+
+    /// Adds one voice into `out`. Returns whether it has finished playing.
+    fn mix_voice(
+        &mut self,
+        index: usize,
+        out: &mut [f32],
+        channels: usize,
+        sample_rate: u32,
+    ) -> bool {
+        let (left, right) = self.gains(&self.voices[index].emitter);
+        let voice = &mut self.voices[index];
+        let samples = &voice.sound.samples;
+        if samples.is_empty() {
+            return true;
+        }
+        let len = samples.len() as f64;
+        let step = voice.sound.sample_rate as f64 / sample_rate as f64;
+
+        for frame in out.chunks_exact_mut(channels) {
+            if voice.cursor >= len {
+                if !voice.emitter.looping {
+                    return true;
+                }
+                // A loop shorter than the step wraps more than once
+                voice.cursor %= len;
+            }
+
+            let sample = samples[voice.cursor as usize];
+            if channels == 1 {
+                frame[0] += sample * (left + right) * 0.5;
+            } else {
+                frame[0] += sample * left;
+                frame[1] += sample * right;
+            }
+            voice.cursor += step;
+        }
+
+        false
+    }
+}
+
+/// The engine's mixed sound, for playing through whatever audio output the game uses, such as
+/// cpal, rodio or Web Audio. The engine doesn't open an output itself. Clones share the same
+/// mix and can be moved to the audio thread.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn example(engine: &raycasting_engine::engine::Engine) {
+/// let output = engine.audio_output();
+///
+/// // In the output's callback, with interleaved stereo at 48 kHz
+/// let mut buffer = vec![0.0; 1024];
+/// output.fill(&mut buffer, 2, 48_000);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AudioOutput {
+    mixer: Arc<Mutex<Mixer>>,
+}
+
+impl AudioOutput {
+    /// Mixes the next `out.len() / channels` frames into `out`, interleaved. Mono output gets
+    /// both sides mixed together; past two channels the rest are left silent.
+    pub fn fill(&self, out: &mut [f32], channels: u16, sample_rate: u32) {
+        self.mixer
+            .lock()
+            .unwrap()
+            .mix(out, channels as usize, sample_rate);
+    }
+}
+
+/// A map's sound by index into its paths, or why it couldn't be loaded.
+type LoadedSound = (usize, anyhow::Result<Sound>);
+
+/// Loads a map's sounds and starts its emitters as they arrive.
+pub(crate) struct Audio {
+    mixer: Arc<Mutex<Mixer>>,
+    /// The current map's emitters, started by sound path as each one loads
+    emitters: Vec<SoundEmitter>,
+    reverb_zones: Vec<ReverbZone>,
+    /// Sounds loaded with every map, for playing with [`play`](Self::play)
+    preload: Vec<&'static str>,
+    paths: Vec<&'static str>,
+    /// The sounds of the current map that have arrived, by path
+    loaded: HashMap<&'static str, Sound>,
+    receiver: Option<mpsc::Receiver<LoadedSound>>,
+    /// Sounds that failed to load since they were last taken
+    failed: Vec<AssetEvent>,
+}
+
+impl Audio {
+    pub fn new() -> Self {
+        Self {
+            mixer: Arc::default(),
+            emitters: Vec::new(),
+            reverb_zones: Vec::new(),
+            preload: Vec::new(),
+            paths: Vec::new(),
+            loaded: HashMap::new(),
+            receiver: None,
+            failed: Vec::new(),
+        }
+    }
+
+    pub fn output(&self) -> AudioOutput {
+        AudioOutput {
+            mixer: self.mixer.clone(),
+        }
+    }
+
+    /// Stops the previous map's sounds and starts loading this one's.
+    pub fn load_map(&mut self, map: &Map, assets: Arc<dyn AssetSource>) {
+        self.mixer.lock().unwrap().voices.clear();
+        self.emitters = map.sound_emitters().to_vec();
+        if let Some(music_path) = map.metadata().music_path() {
+            self.emitters
+                .push(SoundEmitter::ambient(music_path).with_bus(AudioBus::Music));
+        }
+        // Silent until the player is out where the weather can be heard
+        if let Some(sound_path) = map
+            .metadata()
+            .weather()
+            .and_then(|weather| weather.sound_path)
+        {
+            self.emitters
+                .push(SoundEmitter::ambient(sound_path).with_volume(0.0));
+        }
+        self.reverb_zones = map.reverb_zones().to_vec();
+
+        self.paths = Vec::new();
+        self.loaded.clear();
+        let emitter_paths = self.emitters.iter().map(|emitter| emitter.sound_path);
+        let thunder_paths = map
+            .sequences()
+            .iter()
+            .flat_map(|sequence| &sequence.steps)
+            .filter_map(|step| match step {
+                Step::Lightning(lightning) => lightning.thunder_path,
+                _ => None,
+            });
+        for path in emitter_paths
+            .chain(thunder_paths)
+            .chain(self.preload.iter().copied())
+        {
+            if !self.paths.contains(&path) {
+                self.paths.push(path);
+            }
+        }
+
+        // Replacing the receiver stops the old map's loading, which has nobody to send to
+        let (sender, receiver) = mpsc::channel();
+        self.receiver = (!self.paths.is_empty()).then_some(receiver);
+        if !self.paths.is_empty() {
+            spawn_loading(self.paths.clone(), assets, sender);
+        }
+    }
+
+    /// Loads these sounds with every map from the next one on.
+    pub fn set_preload(&mut self, preload: Vec<&'static str>) {
+        self.preload = preload;
+    }
+
+    /// Starts a sound that isn't placed in the map, if it's loaded. Returns whether it was.
+    pub fn play(&mut self, emitter: SoundEmitter) -> bool {
+        let Some(sound) = self.loaded.get(emitter.sound_path) else {
+            return false;
+        };
+        self.mixer.lock().unwrap().voices.push(Voice {
+            sound: sound.clone(),
+            emitter,
+            cursor: 0.0,
+        });
+        true
+    }
+
+    /// Changes the volume of the map's ambient sounds playing `sound_path`, including those
+    /// still waiting to load.
+    pub fn set_ambient_volume(&mut self, sound_path: &str, volume: f32) {
+        let ambient =
+            |emitter: &SoundEmitter| emitter.radius.is_none() && emitter.sound_path == sound_path;
+        for emitter in self.emitters.iter_mut().filter(|emitter| ambient(emitter)) {
+            emitter.volume = volume;
+        }
+        let mut mixer = self.mixer.lock().unwrap();
+        for voice in mixer
+            .voices
+            .iter_mut()
+            .filter(|voice| ambient(&voice.emitter))
+        {
+            voice.emitter.volume = volume;
+        }
+    }
+
+    pub fn volumes(&self) -> AudioConfig {
+        self.mixer.lock().unwrap().volumes
+    }
+
+    pub fn set_volumes(&mut self, volumes: AudioConfig) {
+        self.mixer.lock().unwrap().volumes = volumes;
+    }
+
+    /// Starts the emitters whose sounds have loaded, and moves the listener. Emitters whose
+    /// sound failed to load stay silent.
+    pub fn update(&mut self, listener_position: Vec2, listener_rotation: f32) {
+        let mut mixer = self.mixer.lock().unwrap();
+        mixer.listener_position = listener_position;
+        mixer.listener_rotation = listener_rotation.to_radians();
+        mixer.reverb.target = reverb::zone_at(&self.reverb_zones, listener_position);
+
+        let Some(receiver) = &self.receiver else {
+            return;
+        };
+        while let Ok((index, sound)) = receiver.try_recv() {
+            let path = self.paths[index];
+            let sound = match sound.with_context(|| format!("failed to load sound {path}")) {
+                Ok(sound) => sound,
+                Err(error) => {
+                    println!("{error:#}, leaving it silent");
+                    self.failed.push(AssetEvent {
+                        path,
+                        error: format!("{error:#}"),
+                    });
+                    continue;
+                }
+            };
+            for emitter in self.emitters.iter().filter(|e| e.sound_path == path) {
+                mixer.voices.push(Voice {
+                    sound: sound.clone(),
+                    emitter: *emitter,
+                    cursor: 0.0,
+                });
+            }
+            self.loaded.insert(path, sound);
+        }
+    }
+
+    /// Sounds that failed to load since this was last called.
+    pub fn take_failed(&mut self) -> Vec<AssetEvent> {
+        std::mem::take(&mut self.failed)
+    }
+}
+
+async fn load_sound(assets: &dyn AssetSource, path: &str) -> anyhow::Result<Sound> {
+    let bytes = assets.load(path).await?;
+    Sound::from_wav(&bytes)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_loading(
+    paths: Vec<&'static str>,
+    assets: Arc<dyn AssetSource>,
+    sender: mpsc::Sender<LoadedSound>,
+) {
+    std::thread::spawn(move || {
+        for (index, path) in paths.into_iter().enumerate() {
+            let sound = pollster::block_on(load_sound(assets.as_ref(), path));
+            if sender.send((index, sound)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+fn spawn_loading(
+    paths: Vec<&'static str>,
+    assets: Arc<dyn AssetSource>,
+    sender: mpsc::Sender<LoadedSound>,
+) {
+    wasm_bindgen_futures::spawn_local(async move {
+        for (index, path) in paths.into_iter().enumerate() {
+            let sound = load_sound(assets.as_ref(), path).await;
+            if sender.send((index, sound)).is_err() {
+                break;
+            }
+        }
+    });
+}
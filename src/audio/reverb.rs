@@ -0,0 +1,227 @@
+use crate::map::Region;
+
+/// How a space echoes. See [`ReverbZone`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReverbPreset {
+    /// A short, tight reflection, like a closet or a corridor
+    SmallRoom,
+    /// A long, dense tail
+    Cavern,
+    /// Sparse, distant echoes off faraway walls
+    Outdoors,
+}
+
+impl ReverbPreset {
+    fn params(self) -> ReverbParams {
+        match self {
+            ReverbPreset::SmallRoom => ReverbParams {
+                size: 0.5,
+                feedback: 0.7,
+                damping: 0.4,
+                wet: 0.25,
+            },
+            ReverbPreset::Cavern => ReverbParams {
+                size: 1.6,
+                feedback: 0.88,
+                damping: 0.2,
+                wet: 0.45,
+            },
+            ReverbPreset::Outdoors => ReverbParams {
+                size: 6.0,
+                feedback: 0.3,
+                damping: 0.5,
+                wet: 0.2,
+            },
+        }
+    }
+}
+
+/// A part of a map where sounds echo with a preset while the player is inside. Where zones
+/// overlap, the first one listed wins. Moving between zones crossfades the echo.
+///
+/// # Example
+///
+/// ```
+/// use glam::vec2;
+/// use raycasting_engine::{
+///     audio::{ReverbPreset, ReverbZone},
+///     map::Region,
+/// };
+///
+/// let cave = ReverbZone::new(
+///     Region::Rect {
+///         min: vec2(0.0, 0.0),
+///         max: vec2(512.0, 256.0),
+///     },
+///     ReverbPreset::Cavern,
+/// );
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ReverbZone {
+    pub(crate) region: Region,
+    pub(crate) preset: ReverbPreset,
+}
+
+impl ReverbZone {
+    pub fn new(region: Region, preset: ReverbPreset) -> Self {
+        Self { region, preset }
+    }
+}
+
+struct ReverbParams {
+    /// Scales the comb filters' delays, so larger spaces echo later
+    size: f32,
+    /// How much of each echo comes back around, setting how long the tail lasts
+    feedback: f32,
+    /// How much high frequencies are absorbed on each echo
+    damping: f32,
+    /// How loud the echo is against the dry sound
+    wet: f32,
+}
+
+/// Comb filter delays at a size of 1, in milliseconds. Freeverb's tunings.
+const COMB_DELAYS_MS: [f32; 4] = [25.3, 26.9, 29.0, 30.7];
+/// All-pass filter delays, in milliseconds. These diffuse the echoes, independent of size.
+const ALL_PASS_DELAYS_MS: [f32; 2] = [12.6, 10.0];
+/// Seconds to fade an echo in or out when the player crosses between zones.
+const CROSSFADE_SECONDS: f32 = 0.5;
+
+fn delay_line(milliseconds: f32, sample_rate: u32) -> Vec<f32> {
+    let len = (milliseconds * 0.001 * sample_rate as f32) as usize;
+    vec![0.0; len.max(1)]
+}
+
+struct Comb {
+    buffer: Vec<f32>,
+    index: usize,
+    /// The low-passed output fed back in
+    filtered: f32,
+}
+
+impl Comb {
+    fn process(&mut self, input: f32, params: &ReverbParams) -> f32 {
+        let output = self.buffer[self.index];
+        self.filtered = output * (1.0 - params.damping) + self.filtered * params.damping;
+        self.buffer[self.index] = input + self.filtered * params.feedback;
+        self.index = (self.index + 1) % self.buffer.len();
+        output
+    }
+}
+
+struct AllPass {
+    buffer: Vec<f32>,
+    index: usize,
+}
+
+impl AllPass {
+    fn process(&mut self, input: f32) -> f32 {
+        let buffered = self.buffer[self.index];
+        self.buffer[self.index] = input + buffered * 0.5;
+        self.index = (self.index + 1) % self.buffer.len();
+        buffered - input
+    }
+}
+
+/// A Schroeder reverb for one preset: parallel combs for the echoes, then all-passes to smear
+/// them together.
+struct Reverb {
+    preset: ReverbPreset,
+    params: ReverbParams,
+    combs: Vec<Comb>,
+    all_passes: Vec<AllPass>,
+    /// How much of the dry sound is fed in, faded toward 1 while its zone is current
+    level: f32,
+}
+
+impl Reverb {
+    fn new(preset: ReverbPreset, sample_rate: u32) -> Self {
+        let params = preset.params();
+        Self {
+            combs: COMB_DELAYS_MS
+                .iter()
+                .map(|ms| Comb {
+                    buffer: delay_line(ms * params.size, sample_rate),
+                    index: 0,
+                    filtered: 0.0,
+                })
+                .collect(),
+            all_passes: ALL_PASS_DELAYS_MS
+                .iter()
+                .map(|ms| AllPass {
+                    buffer: delay_line(*ms, sample_rate),
+                    index: 0,
+                })
+                .collect(),
+            preset,
+            params,
+            level: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let input = input * self.level;
+        let mut output = 0.0;
+        for comb in &mut self.combs {
+            output += comb.process(input, &self.params);
+        }
+        for all_pass in &mut self.all_passes {
+            output = all_pass.process(output);
+        }
+        output * self.params.wet / COMB_DELAYS_MS.len() as f32
+    }
+}
+
+/// The echo added over the mixed sound, following the zone the player is in.
+#[derive(Default)]
+pub(crate) struct ReverbMix {
+    /// One reverb per preset used so far, so a zone being left keeps ringing out while the
+    /// next fades in
+    reverbs: Vec<Reverb>,
+    sample_rate: u32,
+    pub target: Option<ReverbPreset>,
+}
+
+impl ReverbMix {
+    /// Adds the echo of the interleaved `out` on top of it.
+    pub fn process(&mut self, out: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate != self.sample_rate {
+            // Delay lengths are in samples, so start over at the new rate
+            self.reverbs.clear();
+            self.sample_rate = sample_rate;
+        }
+        if let Some(target) = self.target
+            && !self.reverbs.iter().any(|reverb| reverb.preset == target)
+        {
+            self.reverbs.push(Reverb::new(target, sample_rate));
+        }
+        if self.reverbs.is_empty() {
+            return;
+        }
+
+        let fade_step = 1.0 / (CROSSFADE_SECONDS * sample_rate as f32);
+        for frame in out.chunks_exact_mut(channels) {
+            let dry = frame.iter().take(2).sum::<f32>() / channels.min(2) as f32;
+            let mut wet = 0.0;
+            for reverb in &mut self.reverbs {
+                let target = if Some(reverb.preset) == self.target {
+                    1.0
+                } else {
+                    0.0
+                };
+                reverb.level += (target - reverb.level).clamp(-fade_step, fade_step);
+                wet += reverb.process(dry);
+            }
+            for sample in frame.iter_mut().take(2) {
+                *sample += wet;
+            }
+        }
+    }
+}
+
+/// The preset of the first zone containing `position`.
+pub(crate) fn zone_at(zones: &[ReverbZone], position: glam::Vec2) -> Option<ReverbPreset> {
+    zones
+        .iter()
+        .find(|zone| zone.region.contains(position))
+        .map(|zone| zone.preset)
+}
@@ -0,0 +1,30 @@
+use super::*;
+
+/// A looping ambient voice playing `samples` recorded at `sample_rate`.
+fn looping_voice(samples: &[f32], sample_rate: u32) -> Voice {
+    Voice {
+        sound: Sound {
+            samples: samples.into(),
+            sample_rate,
+        },
+        emitter: SoundEmitter::ambient("sounds/loop.wav"),
+        cursor: 0.0,
+    }
+}
+
+#[test]
+fn a_loop_shorter_than_the_step_keeps_wrapping() {
+    let mut mixer = Mixer::default();
+    // Over two of the sound's samples go by per output frame
+    mixer.voices.push(looping_voice(&[0.5], 96_000));
+    mixer.voices.push(looping_voice(&[0.25, -0.25], 96_000));
+
+    let mut out = vec![0.0; 64];
+    mixer.mix(&mut out, 2, 44_100);
+
+    assert_eq!(mixer.voices.len(), 2, "looping voices never finish");
+    assert!(out.iter().all(|sample| sample.is_finite()));
+    for voice in &mixer.voices {
+        assert!(voice.cursor < voice.sound.samples.len() as f64 + 96_000.0 / 44_100.0);
+    }
+}
@@ -0,0 +1,54 @@
+use glam::Vec2;
+
+/// A point of view into the world. The player's view is driven by one, and so is every
+/// render-to-texture surface (see [`MonitorData`](crate::map::MonitorData)).
+///
+/// * position - world coordinates, where one tile is 64 units wide.
+/// * rotation - heading in degrees. 0 faces along +x and angles increase clockwise on the map.
+///
+/// # Example
+///
+/// ```
+/// use glam::vec2;
+/// use raycasting_engine::camera::Camera;
+///
+/// let camera = Camera::new(vec2(96.0, 96.0), 90.0).with_fov(75.0);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub(crate) position: Vec2,
+    pub(crate) rotation: f32,
+    pub(crate) height: u16,
+    pub(crate) fov: f32,
+    /// Vertical offset of the horizon from the center of the view, in pixels.
+    pub(crate) pitch: f32,
+}
+
+impl Camera {
+    pub fn new(position: Vec2, rotation: f32) -> Self {
+        Self {
+            position,
+            rotation,
+            height: 32,
+            fov: 60.0,
+            pitch: 0.0,
+        }
+    }
+    pub fn with_fov(mut self, fov: f32) -> Self {
+        self.fov = fov;
+        self
+    }
+    pub fn with_height(mut self, height: u16) -> Self {
+        self.height = height;
+        self
+    }
+    pub fn position(&self) -> Vec2 {
+        self.position
+    }
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
+    pub fn fov(&self) -> f32 {
+        self.fov
+    }
+}
@@ -0,0 +1,918 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use glam::Mat3;
+use serde::{Deserialize, Serialize};
+
+#[cfg(debug_assertions)]
+use crate::debug::DebugFlags;
+use crate::{
+    assets::{self, AssetSource},
+    audio::AudioBus,
+    entity::{EntityCulling, EntityTypes, Hostility},
+    hud::StatusBar,
+    inventory::{Inventory, Items},
+    menu::Menus,
+    weapon::Weapon,
+};
+
+/// Settings the engine is started with. See [`run_with_config`](crate::run_with_config).
+///
+/// * fullscreen - whether to open a window_width x window_height window or go fullscreen, and on which monitor. See [`Fullscreen`].
+/// * start_map - the key of the map to start on, overriding the one passed to `run_with_config`.
+/// * pause_when_unfocused - stops the simulation while the window doesn't have focus. The cursor is always released.
+/// * background_frame_cap - the maximum frames per second while the window doesn't have focus, or `None` to keep the usual rate.
+/// * simulation - whether runs are repeatable. See [`SimulationMode`].
+/// * time_scale - how fast the simulation runs relative to real time. 0.5 is half speed slow motion and 0.0 freezes it. Looking around with the mouse isn't affected.
+/// * audio - starting volumes. See [`AudioConfig`].
+/// * preload_sounds - sounds loaded with every map, for sounds no map places, such as thunder from [`Lightning`](crate::sequence::Lightning) the game strikes itself. Weapons' sounds are added automatically.
+/// * entity_types - builds the entities maps place by type name. See [`EntityDef`](crate::entity::EntityDef).
+/// * hostility - which factions fight each other. See [`Hostility`].
+/// * entity_culling - stops updating entities far from the player. See [`EntityCulling`].
+/// * items - the items pickups can put in the player's inventory. See [`Items`].
+/// * inventory - what the player starts with and how many slots they have. See [`Inventory`].
+/// * weapons - what the player can fire, the first one held to start with. The number keys select them. See [`Weapon`].
+/// * accessibility - field of view, head bob, screen shake, flash strength, automap contrast and how crouch and sprint keys work. See [`AccessibilityConfig`].
+/// * menus - a main menu shown on starting and a pause menu opened with Escape, which otherwise quits. See [`Menus`].
+/// * editor - opens the level editor on the start map instead of playing it. F8 switches between the two.
+/// * windows - more windows opened alongside the main one, sharing its game. See [`SecondaryWindow`].
+/// * assets - where textures, sounds and sprite sheets are read from. Defaults to the `res` directory on disk, or fetched from `res` beside the page on the web. See [`AssetSource`].
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::config::{EngineConfig, RendererConfig};
+///
+/// let config = EngineConfig {
+///     renderer: RendererConfig::low_power(),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone, Debug)]
+pub struct EngineConfig {
+    pub window_width: u32,
+    pub window_height: u32,
+    pub fullscreen: Fullscreen,
+    pub start_map: Option<String>,
+    pub pause_when_unfocused: bool,
+    pub background_frame_cap: Option<u32>,
+    pub simulation: SimulationMode,
+    pub time_scale: f32,
+    pub renderer: RendererConfig,
+    pub audio: AudioConfig,
+    pub preload_sounds: Vec<&'static str>,
+    pub accessibility: AccessibilityConfig,
+    pub entity_types: EntityTypes,
+    pub hostility: Hostility,
+    pub entity_culling: Option<EntityCulling>,
+    pub items: Items,
+    pub inventory: Inventory,
+    pub weapons: Vec<Weapon>,
+    pub menus: Option<Menus>,
+    pub editor: bool,
+    pub windows: Vec<SecondaryWindow>,
+    pub assets: Arc<dyn AssetSource>,
+    #[cfg(debug_assertions)]
+    pub debug: DebugFlags,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            window_width: 1280,
+            window_height: 720,
+            fullscreen: Fullscreen::Windowed,
+            start_map: None,
+            pause_when_unfocused: true,
+            background_frame_cap: Some(10),
+            simulation: SimulationMode::Realtime,
+            time_scale: 1.0,
+            renderer: RendererConfig::default(),
+            audio: AudioConfig::default(),
+            preload_sounds: Vec::new(),
+            accessibility: AccessibilityConfig::default(),
+            entity_types: EntityTypes::default(),
+            hostility: Hostility::default(),
+            entity_culling: None,
+            items: Items::default(),
+            inventory: Inventory::default(),
+            weapons: Vec::new(),
+            menus: None,
+            editor: false,
+            windows: Vec::new(),
+            assets: assets::default_source(),
+            #[cfg(debug_assertions)]
+            debug: DebugFlags::default(),
+        }
+    }
+}
+
+const USAGE: &str = "options: --width <pixels> --height <pixels> --fullscreen --monitor <index> \
+                     --mode <width>x<height>[@<hz>] --map <key> --vsync <on|off> \
+                     --backend <vulkan|metal|dx12|gl|webgpu> --editor";
+
+impl EngineConfig {
+    /// Overrides settings with command line flags, so maps and GPU issues can be tried
+    /// without recompiling. It's opt-in, as games may take arguments of their own: pass it the
+    /// process's arguments and start with [`run_with_config`](crate::run_with_config) or
+    /// [`run_on_game_thread`](crate::run_on_game_thread).
+    ///
+    /// Understands `--width <pixels>`, `--height <pixels>`, `--fullscreen`, `--map <key>`,
+    /// `--vsync <on|off>` and `--backend <vulkan|metal|dx12|gl|webgpu>`. `--monitor <index>`
+    /// goes fullscreen on another monitor and `--mode <width>x<height>[@<hz>]` switches it to
+    /// that video mode. `--editor` starts in the level editor. Values may also be given as
+    /// `--flag=value`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use raycasting_engine::config::{Backend, EngineConfig, PresentMode};
+    ///
+    /// let args = ["--width", "800", "--vsync", "off", "--backend=gl"].map(String::from);
+    /// let config = EngineConfig::default().with_args(args).unwrap();
+    ///
+    /// assert_eq!(config.window_width, 800);
+    /// assert_eq!(config.renderer.present_mode, PresentMode::Immediate);
+    /// assert_eq!(config.renderer.backend, Backend::Gl);
+    /// ```
+    pub fn with_args(mut self, args: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        let mut args = args.into_iter();
+        let mut fullscreen = false;
+        let mut monitor = None;
+        let mut mode = None;
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| anyhow::anyhow!("{flag} needs a value; {USAGE}"))
+            };
+
+            match flag.as_str() {
+                "--width" => self.window_width = value()?.parse()?,
+                "--height" => self.window_height = value()?.parse()?,
+                "--fullscreen" => fullscreen = true,
+                "--monitor" => monitor = Some(value()?.parse()?),
+                "--mode" => mode = Some(parse_video_mode(&value()?)?),
+                "--map" => self.start_map = Some(value()?),
+                "--editor" => self.editor = true,
+                "--vsync" => {
+                    self.renderer.present_mode = match value()?.as_str() {
+                        "on" => PresentMode::Fifo,
+                        "off" => PresentMode::Immediate,
+                        other => anyhow::bail!("unknown vsync setting {other}; {USAGE}"),
+                    }
+                }
+                "--backend" => {
+                    self.renderer.backend = match value()?.as_str() {
+                        "vulkan" => Backend::Vulkan,
+                        "metal" => Backend::Metal,
+                        "dx12" => Backend::Dx12,
+                        "gl" => Backend::Gl,
+                        "webgpu" => Backend::BrowserWebGpu,
+                        other => anyhow::bail!("unknown backend {other}; {USAGE}"),
+                    }
+                }
+                _ => anyhow::bail!("unknown argument {flag}; {USAGE}"),
+            }
+        }
+
+        if let Some((width, height, refresh_rate_millihertz)) = mode {
+            self.fullscreen = Fullscreen::Exclusive {
+                monitor,
+                width,
+                height,
+                refresh_rate_millihertz,
+            };
+        } else if fullscreen || monitor.is_some() {
+            self.fullscreen = Fullscreen::Borderless { monitor };
+        }
+
+        Ok(self)
+    }
+
+    /// The renderer settings with the weapons' view and projectile sprites added to those
+    /// loaded with every map.
+    pub(crate) fn renderer_with_preloads(&self) -> RendererConfig {
+        let mut renderer = self.renderer.clone();
+        for path in self.weapons.iter().flat_map(Weapon::sprites) {
+            if !renderer.preload_sprites.contains(&path) {
+                renderer.preload_sprites.push(path);
+            }
+        }
+        renderer
+    }
+}
+
+/// Parses `<width>x<height>` with an optional `@<hz>`.
+fn parse_video_mode(mode: &str) -> anyhow::Result<(u32, u32, Option<u32>)> {
+    let (size, refresh_rate) = match mode.split_once('@') {
+        Some((size, hz)) => (size, Some((hz.parse::<f32>()? * 1000.0).round() as u32)),
+        None => (mode, None),
+    };
+    let (width, height) = size
+        .split_once('x')
+        .ok_or_else(|| anyhow::anyhow!("video mode {mode} should look like 1920x1080@60"))?;
+
+    Ok((width.parse()?, height.parse()?, refresh_rate))
+}
+
+/// Whether the window is fullscreen. Monitors are numbered in the order
+/// [`available_monitors`](crate::display::available_monitors) lists them, and `None` means
+/// the primary one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fullscreen {
+    Windowed,
+    /// A borderless window covering the monitor at its current resolution.
+    Borderless {
+        monitor: Option<usize>,
+    },
+    /// Switches the monitor to the video mode closest to the given one. Falls back to
+    /// borderless where video modes can't be changed.
+    Exclusive {
+        monitor: Option<usize>,
+        width: u32,
+        height: u32,
+        refresh_rate_millihertz: Option<u32>,
+    },
+}
+
+/// A window opened alongside the main one, such as an automap on a second monitor or a
+/// palette drawn by the game. Its contents are scaled to fit with their aspect ratio kept.
+/// Keys pressed in it reach the game as in the main window. Closing it leaves the game
+/// running.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::config::{EngineConfig, SecondaryWindow, WindowContent};
+///
+/// let config = EngineConfig {
+///     windows: vec![SecondaryWindow::new("Map", 512, 512, WindowContent::Automap)],
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SecondaryWindow {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub content: WindowContent,
+}
+
+impl SecondaryWindow {
+    pub fn new(title: impl Into<String>, width: u32, height: u32, content: WindowContent) -> Self {
+        Self {
+            title: title.into(),
+            width,
+            height,
+            content,
+        }
+    }
+}
+
+/// What a [`SecondaryWindow`] shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowContent {
+    /// The explored parts of the current map with the player on them, kept up to date.
+    Automap,
+    /// Whatever the game last gave
+    /// [`Engine::set_window_image`](crate::engine::Engine::set_window_image). Blank until then.
+    Image,
+}
+
+/// How the simulation advances and where its randomness comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimulationMode {
+    /// Steps by however long each frame took, with randomness seeded from the clock.
+    Realtime,
+    /// Steps in fixed increments of [`FIXED_TIMESTEP`] seconds, with all randomness drawn from
+    /// one [`Rng`](crate::rng::Rng) created from `seed`. The same seed and the same input on
+    /// each step give an identical run, whatever the frame rate, which replays and lockstep
+    /// networking rely on.
+    Deterministic { seed: u64 },
+}
+
+/// Seconds simulated per step in [`SimulationMode::Deterministic`].
+pub const FIXED_TIMESTEP: f32 = 1.0 / 60.0;
+
+/// GPU and presentation settings.
+///
+/// * render_scale - the internal resolution relative to the window. One ray is cast per internal column, so values below 1.0 also reduce CPU work.
+/// * frame_cap - the maximum frames per second, or `None` to render as fast as the present mode allows.
+/// * surface_format - which kind of surface format to prefer. The shaders adapt to whichever format is chosen.
+/// * anti_aliasing - smoothing for column and sprite edges, which crawl most at low render scales.
+/// * texture_budget - how many bytes of texture arrays to keep resident. A map's textures load the first time it's entered. Textures of the current map always stay loaded; those of previously visited maps are dropped, least recently used first, when over budget.
+/// * backend - which graphics API to use.
+/// * capture - records frames for video. See [`FrameCapture`].
+/// * status_bar - a bar docked to the bottom of the window. The 3D view is drawn above it, at a resolution matching the space left.
+/// * preload_sprites - sprite images loaded with every map, for images no map places, such as item icons drawn by the game. Weapons' view and projectile sprites are added automatically.
+/// * gpu_profiling - measures how long the GPU spends on the floor, walls, sprites and post-processing each frame with timestamp queries. Read the results with [`Engine::gpu_timings`](crate::engine::Engine::gpu_timings). Ignored on adapters without timestamp queries inside passes, which includes the web.
+/// * performance_overlay - shows frame and GPU timings in the corner of the view. F9 toggles it.
+/// * dynamic_resolution - casts fewer rays while frames take too long. See [`DynamicResolution`].
+/// * stereo - draws the view side by side for each eye, for 3D TVs and viewers. See [`Stereo`].
+/// * top_down - starts with the map drawn from above in place of the first-person view. See [`TopDown`].
+/// * rear_view - shows what's behind the player in a small area over the view. See [`RearView`].
+/// * dither - offsets colors by a blue noise pattern before they're stored at 8 bits per channel, so fog, distance shading and lighting gradients don't show as bands. Has no effect on float surface formats.
+/// * color_space - how the colors in texture images and map colors like fog are encoded. See [`ColorSpace`].
+/// * ambient_occlusion - how much walls darken towards their top and bottom, and floors and ceilings towards the walls they meet, from 0 to 1. Fakes the contact shadows there for a stronger sense of depth. Around 0.3 is subtle.
+/// * column_interleaving - casts the main view's even columns on one frame and odd columns on the next, reprojecting the others from the previous frame by how far the camera turned. Roughly halves the CPU cost of ray casting at the price of slight smearing during fast movement. Not applied while dynamic resolution has lowered the ray count.
+#[derive(Clone, Debug)]
+pub struct RendererConfig {
+    pub power_preference: PowerPreference,
+    pub backend: Backend,
+    pub present_mode: PresentMode,
+    pub render_scale: f32,
+    pub frame_cap: Option<u32>,
+    pub surface_format: SurfaceFormatPreference,
+    pub anti_aliasing: AntiAliasing,
+    pub texture_budget: u64,
+    pub status_bar: Option<StatusBar>,
+    pub preload_sprites: Vec<&'static str>,
+    pub capture: Option<FrameCapture>,
+    pub gpu_profiling: bool,
+    pub performance_overlay: bool,
+    pub dynamic_resolution: Option<DynamicResolution>,
+    pub column_interleaving: bool,
+    pub dither: bool,
+    pub color_space: ColorSpace,
+    pub ambient_occlusion: f32,
+    pub stereo: Option<Stereo>,
+    pub top_down: Option<TopDown>,
+    pub rear_view: Option<RearView>,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            power_preference: PowerPreference::HighPerformance,
+            backend: Backend::Auto,
+            present_mode: PresentMode::Auto,
+            render_scale: 1.0,
+            frame_cap: None,
+            surface_format: SurfaceFormatPreference::Srgb,
+            anti_aliasing: AntiAliasing::None,
+            texture_budget: 256 * 1024 * 1024,
+            status_bar: None,
+            preload_sprites: Vec::new(),
+            capture: None,
+            gpu_profiling: false,
+            performance_overlay: false,
+            dynamic_resolution: None,
+            column_interleaving: false,
+            dither: false,
+            color_space: ColorSpace::Srgb,
+            ambient_occlusion: 0.0,
+            stereo: None,
+            top_down: None,
+            rear_view: None,
+        }
+    }
+}
+
+impl RendererConfig {
+    /// A preset for laptops and integrated GPUs: prefers the low power adapter, waits for
+    /// vsync, renders at half resolution, caps the frame rate at 60 and keeps fewer maps'
+    /// textures resident.
+    pub fn low_power() -> Self {
+        Self {
+            power_preference: PowerPreference::LowPower,
+            present_mode: PresentMode::Fifo,
+            render_scale: 0.5,
+            frame_cap: Some(60),
+            texture_budget: 64 * 1024 * 1024,
+            ..Default::default()
+        }
+    }
+}
+
+/// How colors are encoded in texture images and in the colors maps give, like fog. Shading
+/// is always done in linear space, so colors are decoded to it first where needed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Gamma encoded, as most image editors save images and pick colors.
+    #[default]
+    Srgb,
+    /// Already linear, for assets authored or exported that way.
+    Linear,
+}
+
+/// Volumes of the audio buses, from 0.0 (silent) to 1.0. Every sound is scaled by `master` and
+/// by the volume of the bus it's on. Change them while running with
+/// [`Engine::set_volume`](crate::engine::Engine::set_volume).
+///
+/// Serializable, so an options menu can save the player's choices and restore them at startup.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::{audio::AudioBus, config::AudioConfig};
+///
+/// let mut audio = AudioConfig::default();
+/// audio.set_volume(AudioBus::Music, 0.4);
+///
+/// let saved = audio.to_ron().unwrap();
+/// assert_eq!(AudioConfig::from_ron(&saved).unwrap(), audio);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            music: 1.0,
+            sfx: 1.0,
+        }
+    }
+}
+
+impl AudioConfig {
+    pub fn volume(&self, bus: AudioBus) -> f32 {
+        match bus {
+            AudioBus::Master => self.master,
+            AudioBus::Music => self.music,
+            AudioBus::Sfx => self.sfx,
+        }
+    }
+
+    pub fn set_volume(&mut self, bus: AudioBus, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        match bus {
+            AudioBus::Master => self.master = volume,
+            AudioBus::Music => self.music = volume,
+            AudioBus::Sfx => self.sfx = volume,
+        }
+    }
+
+    /// The overall volume of a sound on `bus`.
+    pub(crate) fn gain(&self, bus: AudioBus) -> f32 {
+        match bus {
+            AudioBus::Master => self.master,
+            bus => self.master * self.volume(bus),
+        }
+    }
+
+    pub fn from_ron(source: &str) -> anyhow::Result<Self> {
+        Ok(ron::from_str(source)?)
+    }
+
+    pub fn to_ron(&self) -> anyhow::Result<String> {
+        Ok(ron::ser::to_string_pretty(self, Default::default())?)
+    }
+}
+
+/// Options for players who find the defaults uncomfortable or hard to use. Change them while
+/// running with [`Engine::set_accessibility`](crate::engine::Engine::set_accessibility), or
+/// let the player change them from the [menus](crate::menu::Menus).
+///
+/// * fov - the player's horizontal field of view in degrees, from 40 to 120.
+/// * head_bob - whether the view bobs up and down while walking.
+/// * screen_shake - how strongly [`Engine::shake`](crate::engine::Engine::shake) shakes the view, from 0.0 (not at all) to 1.0.
+/// * flash_reduction - how much weaker damage and lightning flashes are, from 0.0 to 1.0 (none at all).
+/// * high_contrast_automap - draws the automap with white walls on black and the player in yellow.
+/// * toggle_crouch - crouch (C or left Ctrl) on each press rather than while held.
+/// * toggle_sprint - sprint (left Shift) on each press rather than while held.
+/// * photosensitive_safe - keeps every flash of the view dim and slow, whatever the game or map asks for. Applied to the whole post-processing pass, so it also covers flashes added later, and to [`Lightning`](crate::sequence::Lightning).
+/// * color_filter - shifts colors so color-blind players can tell them apart, or shows the view as they see it. See [`ColorFilter`].
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::config::{AccessibilityConfig, AccessibilityOption};
+///
+/// let mut accessibility = AccessibilityConfig::default();
+/// accessibility.adjust(AccessibilityOption::Fov, 3);
+/// accessibility.adjust(AccessibilityOption::HeadBob, 1);
+///
+/// assert_eq!(accessibility.fov, 75.0);
+/// assert!(!accessibility.head_bob);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilityConfig {
+    pub fov: f32,
+    pub head_bob: bool,
+    pub screen_shake: f32,
+    pub flash_reduction: f32,
+    pub high_contrast_automap: bool,
+    pub toggle_crouch: bool,
+    pub toggle_sprint: bool,
+    pub photosensitive_safe: bool,
+    pub color_filter: ColorFilter,
+}
+
+impl Default for AccessibilityConfig {
+    fn default() -> Self {
+        Self {
+            fov: 60.0,
+            head_bob: true,
+            screen_shake: 1.0,
+            flash_reduction: 0.0,
+            high_contrast_automap: false,
+            toggle_crouch: false,
+            toggle_sprint: false,
+            photosensitive_safe: false,
+            color_filter: ColorFilter::None,
+        }
+    }
+}
+
+/// A kind of color blindness, each missing one of the three types of cone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorBlindness {
+    /// Red-blind
+    Protanopia,
+    /// Green-blind
+    Deuteranopia,
+    /// Blue-blind
+    Tritanopia,
+}
+
+/// A full-screen color filter for color blindness, applied in post-processing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorFilter {
+    #[default]
+    None,
+    /// Shows the view as someone with the color blindness sees it, for checking a game's
+    /// colors can be told apart
+    Simulate(ColorBlindness),
+    /// Moves the colors someone with the color blindness confuses towards ones they can see
+    Compensate(ColorBlindness),
+}
+
+impl ColorFilter {
+    /// Every filter, in the order menus step through them.
+    const ALL: [Self; 7] = [
+        Self::None,
+        Self::Compensate(ColorBlindness::Protanopia),
+        Self::Compensate(ColorBlindness::Deuteranopia),
+        Self::Compensate(ColorBlindness::Tritanopia),
+        Self::Simulate(ColorBlindness::Protanopia),
+        Self::Simulate(ColorBlindness::Deuteranopia),
+        Self::Simulate(ColorBlindness::Tritanopia),
+    ];
+
+    /// The linear color matrix the filter applies. `None` for no filter.
+    pub(crate) fn matrix(self) -> Option<Mat3> {
+        let (simulate, kind) = match self {
+            Self::None => return None,
+            Self::Simulate(kind) => (true, kind),
+            Self::Compensate(kind) => (false, kind),
+        };
+        // Machado, Oliveira and Fernandes (2009) at full severity
+        let simulation = Mat3::from_cols_array_2d(&match kind {
+            ColorBlindness::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            ColorBlindness::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            ColorBlindness::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        })
+        .transpose();
+        if simulate {
+            return Some(simulation);
+        }
+
+        // Daltonization: the difference the color blindness loses is shifted into channels
+        // that are still seen
+        let shift = Mat3::from_cols_array_2d(&match kind {
+            ColorBlindness::Protanopia | ColorBlindness::Deuteranopia => {
+                [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]]
+            }
+            ColorBlindness::Tritanopia => [[1.0, 0.0, 0.7], [0.0, 1.0, 0.7], [0.0, 0.0, 0.0]],
+        })
+        .transpose();
+        Some(Mat3::IDENTITY + shift * (Mat3::IDENTITY - simulation))
+    }
+}
+
+/// One of the settings in [`AccessibilityConfig`], for menus to change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessibilityOption {
+    Fov,
+    HeadBob,
+    ScreenShake,
+    FlashReduction,
+    HighContrastAutomap,
+    ToggleCrouch,
+    ToggleSprint,
+    PhotosensitiveSafe,
+    ColorFilter,
+}
+
+impl AccessibilityOption {
+    /// Whether the setting is a number rather than on or off.
+    pub(crate) fn is_number(self) -> bool {
+        matches!(self, Self::Fov | Self::ScreenShake | Self::FlashReduction)
+    }
+}
+
+impl AccessibilityConfig {
+    /// Steps a setting up or down: the field of view by 5 degrees, strengths by a tenth and
+    /// switches on or off whichever way.
+    pub fn adjust(&mut self, option: AccessibilityOption, steps: i32) {
+        // Rounded so repeated steps land back on whole tenths
+        let step_tenth = |value: f32| ((value + steps as f32 * 0.1) * 10.0).round() / 10.0;
+        match option {
+            AccessibilityOption::Fov => self.fov += steps as f32 * 5.0,
+            AccessibilityOption::HeadBob => self.head_bob = !self.head_bob,
+            AccessibilityOption::ScreenShake => self.screen_shake = step_tenth(self.screen_shake),
+            AccessibilityOption::FlashReduction => {
+                self.flash_reduction = step_tenth(self.flash_reduction)
+            }
+            AccessibilityOption::HighContrastAutomap => {
+                self.high_contrast_automap = !self.high_contrast_automap
+            }
+            AccessibilityOption::ToggleCrouch => self.toggle_crouch = !self.toggle_crouch,
+            AccessibilityOption::ToggleSprint => self.toggle_sprint = !self.toggle_sprint,
+            AccessibilityOption::PhotosensitiveSafe => {
+                self.photosensitive_safe = !self.photosensitive_safe
+            }
+            AccessibilityOption::ColorFilter => {
+                let filters = ColorFilter::ALL.len() as i32;
+                let index = ColorFilter::ALL
+                    .iter()
+                    .position(|filter| *filter == self.color_filter)
+                    .unwrap_or(0) as i32;
+                self.color_filter = ColorFilter::ALL[(index + steps).rem_euclid(filters) as usize];
+            }
+        }
+        *self = self.clamped();
+    }
+
+    /// A setting's value as text for a menu, like `60` for the field of view or `On`.
+    pub fn display(&self, option: AccessibilityOption) -> String {
+        let switch = |on: bool| if on { "On" } else { "Off" }.to_string();
+        match option {
+            AccessibilityOption::Fov => format!("{}", self.fov.round()),
+            AccessibilityOption::HeadBob => switch(self.head_bob),
+            AccessibilityOption::ScreenShake => format!("{}%", (self.screen_shake * 100.0).round()),
+            AccessibilityOption::FlashReduction => {
+                format!("{}%", (self.flash_reduction * 100.0).round())
+            }
+            AccessibilityOption::HighContrastAutomap => switch(self.high_contrast_automap),
+            AccessibilityOption::ToggleCrouch => switch(self.toggle_crouch),
+            AccessibilityOption::ToggleSprint => switch(self.toggle_sprint),
+            AccessibilityOption::PhotosensitiveSafe => switch(self.photosensitive_safe),
+            AccessibilityOption::ColorFilter => match self.color_filter {
+                ColorFilter::None => "Off".to_string(),
+                ColorFilter::Simulate(kind) => format!("{kind:?} view"),
+                ColorFilter::Compensate(kind) => format!("{kind:?}"),
+            },
+        }
+    }
+
+    /// The settings with values out of range brought back into it.
+    pub(crate) fn clamped(self) -> Self {
+        Self {
+            fov: self.fov.clamp(40.0, 120.0),
+            screen_shake: self.screen_shake.clamp(0.0, 1.0),
+            flash_reduction: self.flash_reduction.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+}
+
+/// Everything a player can change from the [menus](crate::menu::Menus), saved together.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::config::Settings;
+///
+/// let mut settings = Settings::default();
+/// settings.accessibility.head_bob = false;
+///
+/// let saved = settings.to_ron().unwrap();
+/// assert_eq!(Settings::from_ron(&saved).unwrap(), settings);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub audio: AudioConfig,
+    pub accessibility: AccessibilityConfig,
+}
+
+impl Settings {
+    pub fn from_ron(source: &str) -> anyhow::Result<Self> {
+        Ok(ron::from_str(source)?)
+    }
+
+    pub fn to_ron(&self) -> anyhow::Result<String> {
+        Ok(ron::ser::to_string_pretty(self, Default::default())?)
+    }
+}
+
+/// Saves frames for trailers and videos. Native only.
+///
+/// While capturing, the simulation advances by `1 / (frame_rate * every_nth_frame)` seconds
+/// per rendered frame rather than by real time, so the saved frames play back smoothly at
+/// `frame_rate` however slowly they were rendered and encoded.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::config::{CaptureOutput, FrameCapture, RendererConfig};
+///
+/// let config = RendererConfig {
+///     capture: Some(FrameCapture {
+///         every_nth_frame: 2,
+///         frame_rate: 30,
+///         output: CaptureOutput::Png {
+///             directory: "capture".into(),
+///         },
+///     }),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameCapture {
+    /// Keeps one frame out of this many, starting with the first
+    pub every_nth_frame: u32,
+    /// Frames per second of the finished video
+    pub frame_rate: u32,
+    pub output: CaptureOutput,
+}
+
+/// Where captured frames go.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CaptureOutput {
+    /// Numbered PNGs, `frame_000000.png` onwards, in a directory that's created if needed.
+    Png { directory: PathBuf },
+    /// Raw frames piped into an `ffmpeg` process, which has to be on the `PATH`, encoding to
+    /// `path`. The container and codec follow from its extension.
+    Ffmpeg { path: PathBuf },
+}
+
+/// Which GPU to ask for when several are available.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerPreference {
+    HighPerformance,
+    LowPower,
+}
+
+/// The graphics API to render with. Forcing one the platform doesn't have fails at startup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// The platform's primary API: Vulkan, Metal, DX12 or WebGPU.
+    Auto,
+    Vulkan,
+    Metal,
+    Dx12,
+    /// OpenGL, or WebGL on the web.
+    Gl,
+    BrowserWebGpu,
+}
+
+/// How frames are presented. Modes the surface doesn't support fall back to `Fifo`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresentMode {
+    /// The first mode the surface reports.
+    Auto,
+    /// Vsync. Always supported.
+    Fifo,
+    /// Vsync without blocking, dropping stale frames.
+    Mailbox,
+    /// No vsync. May tear.
+    Immediate,
+}
+
+/// The kind of surface format to ask for. If the surface offers none, its first format is used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SurfaceFormatPreference {
+    /// An sRGB format, where the GPU applies gamma when writing.
+    Srgb,
+    /// A plain UNORM format, where the shader applies gamma itself. Avoids double gamma on
+    /// platforms that treat sRGB surfaces inconsistently.
+    Linear,
+    /// A 16-bit float or 10-bit format, so HDR displays aren't limited to 8 bits per channel.
+    HighBitDepth,
+}
+
+/// Lowers the main view's horizontal ray resolution under load. When casting rays and building
+/// the view takes longer than `frame_budget`, only every 2nd ray is cast, then every 4th, up to
+/// `max_ray_step`, and the columns in between are interpolated from their neighbours. Full
+/// resolution returns once there's headroom again. Monitors always cast every ray.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DynamicResolution {
+    /// CPU time per frame allowed for casting rays and building the view
+    pub frame_budget: Duration,
+    /// The most columns one ray may cover. Rounded down to a power of two.
+    pub max_ray_step: u32,
+}
+
+impl Default for DynamicResolution {
+    fn default() -> Self {
+        Self {
+            frame_budget: Duration::from_millis(8),
+            max_ray_step: 4,
+        }
+    }
+}
+
+/// Side-by-side stereo 3D, for 3D TVs and viewers and as a step towards VR. The view is drawn
+/// twice, from cameras `eye_separation` apart across the player's head, each squeezed into half
+/// its width with the left eye's on the left, as half side-by-side displays expect. Change it
+/// while running with [`Engine::set_stereo`](crate::engine::Engine::set_stereo).
+///
+/// Casts twice as many rays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stereo {
+    /// World units between the eyes. Stronger depth the wider apart, and uncomfortable much
+    /// past the default.
+    pub eye_separation: f32,
+}
+
+impl Default for Stereo {
+    fn default() -> Self {
+        Self {
+            eye_separation: 4.0,
+        }
+    }
+}
+
+/// A view of the map from straight above in place of the first-person one, for tactical
+/// views, debugging and games that mix the two. It shows the same map and entities: each tile's
+/// floor or wall texture, the sprites nearest the player as icons and the player as an arrow
+/// pointing the way they face. North is up and the view follows the player. Menus, captions
+/// and the performance overlay are still drawn over it.
+///
+/// V toggles it during play. Change it with
+/// [`Engine::set_top_down`](crate::engine::Engine::set_top_down).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TopDown {
+    /// How many tiles fit across the view
+    pub tiles_across: f32,
+}
+
+impl Default for TopDown {
+    fn default() -> Self {
+        Self { tiles_across: 16.0 }
+    }
+}
+
+/// A rear-view mirror: a second camera at the player's head facing backwards, drawn into an
+/// area over the view, for racing and horror games. The area is given in fractions of the
+/// view's width and height, so it keeps its place at any resolution. It's drawn as the camera
+/// sees it, not flipped like a real mirror, and hidden while a menu is open or the view is
+/// [top-down](TopDown). Change it with
+/// [`Engine::set_rear_view`](crate::engine::Engine::set_rear_view).
+///
+/// Casts as many rays again as the area is wide.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RearView {
+    /// Left edge, from 0.0 to 1.0
+    pub x: f32,
+    /// Top edge, from 0.0 to 1.0
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Degrees
+    pub fov: f32,
+}
+
+impl Default for RearView {
+    /// A wide strip at the top middle of the view, like a car's mirror.
+    fn default() -> Self {
+        Self {
+            x: 0.35,
+            y: 0.03,
+            width: 0.3,
+            height: 0.15,
+            fov: 70.0,
+        }
+    }
+}
+
+/// Anti-aliasing for the main view. Monitors are never anti-aliased.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AntiAliasing {
+    None,
+    /// Multisampling with the given sample count, usually 4. Smooths the top and bottom
+    /// edges of walls and sprite outlines at little cost. Counts the GPU doesn't support
+    /// fall back to the nearest supported one.
+    Msaa(u32),
+    /// Supersampling: renders at twice the internal resolution in each direction and
+    /// averages down. Smooths everything, including texture detail, but doubles the number
+    /// of rays cast.
+    Ssaa,
+}
@@ -0,0 +1,28 @@
+/// Cheats and overlays for testing levels. Only compiled into debug builds.
+///
+/// Set them up front through [`EngineConfig::debug`](crate::config::EngineConfig::debug), or
+/// toggle them while running: F1 for noclip, F2 for god mode and F3 for collision boxes. F4
+/// switches between normal speed and quarter speed slow motion.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::{config::EngineConfig, debug::DebugFlags};
+///
+/// let config = EngineConfig {
+///     debug: DebugFlags {
+///         noclip: true,
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DebugFlags {
+    /// Walk through walls.
+    pub noclip: bool,
+    /// Take no damage from hazard floors.
+    pub god_mode: bool,
+    /// Highlight the floor where the player's collision box touches a wall.
+    pub show_collision: bool,
+}
@@ -0,0 +1,144 @@
+use winit::{
+    event_loop::ActiveEventLoop,
+    monitor::{MonitorHandle, VideoModeHandle},
+    window,
+};
+
+use crate::config::Fullscreen;
+
+/// A connected monitor and the video modes it supports, for an options menu to offer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MonitorInfo {
+    /// Position in [`available_monitors`], which `monitor` in [`Fullscreen`] refers to
+    pub index: usize,
+    pub name: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub is_primary: bool,
+    pub video_modes: Vec<VideoMode>,
+}
+
+/// A resolution and refresh rate a monitor can switch to for exclusive fullscreen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VideoMode {
+    pub width: u32,
+    pub height: u32,
+    pub refresh_rate_millihertz: u32,
+    pub bit_depth: u16,
+}
+
+impl From<&VideoModeHandle> for VideoMode {
+    fn from(mode: &VideoModeHandle) -> Self {
+        Self {
+            width: mode.size().width,
+            height: mode.size().height,
+            refresh_rate_millihertz: mode.refresh_rate_millihertz(),
+            bit_depth: mode.bit_depth(),
+        }
+    }
+}
+
+/// Every connected monitor. Some platforms, like the web and Wayland, report no video modes.
+pub fn available_monitors(event_loop: &ActiveEventLoop) -> Vec<MonitorInfo> {
+    let primary = event_loop.primary_monitor();
+
+    event_loop
+        .available_monitors()
+        .enumerate()
+        .map(|(index, monitor)| MonitorInfo {
+            index,
+            name: monitor.name(),
+            width: monitor.size().width,
+            height: monitor.size().height,
+            is_primary: primary.as_ref() == Some(&monitor),
+            video_modes: monitor
+                .video_modes()
+                .map(|mode| VideoMode::from(&mode))
+                .collect(),
+        })
+        .collect()
+}
+
+/// The mode closest to the requested size, preferring the requested refresh rate, or the
+/// highest one if none was asked for. `None` if there are no modes.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::display::{VideoMode, closest_video_mode};
+///
+/// let mode = |width, height, hz: u32| VideoMode {
+///     width,
+///     height,
+///     refresh_rate_millihertz: hz * 1000,
+///     bit_depth: 32,
+/// };
+/// let modes = [mode(1920, 1080, 60), mode(1920, 1080, 144), mode(1280, 720, 60)];
+///
+/// assert_eq!(closest_video_mode(&modes, 1920, 1080, None), Some(modes[1]));
+/// assert_eq!(closest_video_mode(&modes, 1920, 1080, Some(60_000)), Some(modes[0]));
+/// assert_eq!(closest_video_mode(&modes, 1366, 768, None), Some(modes[2]));
+/// ```
+pub fn closest_video_mode(
+    modes: &[VideoMode],
+    width: u32,
+    height: u32,
+    refresh_rate_millihertz: Option<u32>,
+) -> Option<VideoMode> {
+    modes.iter().copied().min_by_key(|mode| {
+        let size_difference =
+            (mode.width as i64 * mode.height as i64 - width as i64 * height as i64).unsigned_abs();
+        let refresh_difference = match refresh_rate_millihertz {
+            Some(refresh_rate) => mode.refresh_rate_millihertz.abs_diff(refresh_rate),
+            None => u32::MAX - mode.refresh_rate_millihertz,
+        };
+        (
+            size_difference,
+            refresh_difference,
+            u16::MAX - mode.bit_depth,
+        )
+    })
+}
+
+/// Turns the configured fullscreen setting into winit's, finding the monitor and video mode.
+/// Exclusive fullscreen on a monitor without video modes falls back to borderless.
+pub(crate) fn window_fullscreen(
+    fullscreen: &Fullscreen,
+    event_loop: &ActiveEventLoop,
+) -> Option<window::Fullscreen> {
+    let find_monitor = |index: Option<usize>| -> Option<MonitorHandle> {
+        match index {
+            Some(index) => event_loop.available_monitors().nth(index),
+            None => event_loop.primary_monitor(),
+        }
+    };
+
+    match *fullscreen {
+        Fullscreen::Windowed => None,
+        Fullscreen::Borderless { monitor } => {
+            Some(window::Fullscreen::Borderless(find_monitor(monitor)))
+        }
+        Fullscreen::Exclusive {
+            monitor,
+            width,
+            height,
+            refresh_rate_millihertz,
+        } => {
+            let handle = find_monitor(monitor);
+            let video_mode = handle.as_ref().and_then(|handle| {
+                let modes: Vec<_> = handle.video_modes().collect();
+                let summaries: Vec<_> = modes.iter().map(VideoMode::from).collect();
+                let closest =
+                    closest_video_mode(&summaries, width, height, refresh_rate_millihertz)?;
+                let index = summaries.iter().position(|mode| *mode == closest)?;
+                println!("Fullscreen video mode: {closest:?}");
+                Some(modes[index].clone())
+            });
+
+            match video_mode {
+                Some(video_mode) => Some(window::Fullscreen::Exclusive(video_mode)),
+                None => Some(window::Fullscreen::Borderless(handle)),
+            }
+        }
+    }
+}
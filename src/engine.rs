@@ -0,0 +1,915 @@
+use std::{sync::Arc, time::Duration};
+
+use glam::Vec2;
+use image::RgbaImage;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    audio::{AudioBus, AudioOutput},
+    camera::Camera,
+    config::{AccessibilityConfig, AudioConfig, EngineConfig, RearView, Settings, Stereo, TopDown},
+    entity::{Body, Contact, Entity, EntityCulling, EntityDef, EntityId, Hostility},
+    gpu::DeviceReport,
+    inventory::{Inventory, ItemDef, Items},
+    map::{Exploration, Map, MapDiff, MapMetadata, Maps, Region},
+    menu::{MenuInput, Menus},
+    profiling::GpuTimings,
+    raycaster::Raycaster,
+    renderer::Renderer,
+    sequence::{Lightning, Sequence},
+    stats::LevelStats,
+    weapon::Weapon,
+};
+
+/// The engine without its event loop, for applications that already have a window and want
+/// to drive frames themselves. [`run_with_config`](crate::run_with_config) is built on the same
+/// pieces.
+///
+/// The window settings in [`EngineConfig`] are ignored since the window already exists. The
+/// host forwards resizes and calls [`step`](Self::step) once per frame with the input it has
+/// gathered.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn example(window: std::sync::Arc<winit::window::Window>, maps: raycasting_engine::map::Maps) -> anyhow::Result<()> {
+/// use raycasting_engine::{config::EngineConfig, engine::{Engine, InputState}};
+///
+/// let size = window.inner_size();
+/// let mut engine = Engine::from_window_handle(
+///     window.clone(),
+///     size.width,
+///     size.height,
+///     maps,
+///     "Map1",
+///     EngineConfig::default(),
+/// )
+/// .await?;
+///
+/// // Each time the window needs redrawing
+/// let frame = engine.step(&InputState {
+///     forward: true,
+///     ..Default::default()
+/// })?;
+/// window.set_title(&format!("{:.0} fps", frame.timing.smoothed_fps));
+/// if frame.next_frame_time.is_none() {
+///     window.request_redraw();
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Engine {
+    pub(crate) raycaster: Raycaster,
+}
+
+/// What the player is doing this frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InputState {
+    pub forward: bool,
+    pub back: bool,
+    pub left: bool,
+    pub right: bool,
+    /// Mouse movement since the last step, in pixels
+    pub look_delta: (f64, f64),
+    /// Held to fire the weapon
+    pub fire: bool,
+    /// Held to crouch, or pressed to start and stop with
+    /// [`toggle_crouch`](crate::config::AccessibilityConfig::toggle_crouch)
+    pub crouch: bool,
+    /// Held to sprint, or pressed to start and stop with
+    /// [`toggle_sprint`](crate::config::AccessibilityConfig::toggle_sprint)
+    pub sprint: bool,
+}
+
+/// What a [`step`](Engine::step) did.
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    pub timing: FrameTiming,
+    /// When the next frame is due if the frame rate is capped. `None` means step again as soon
+    /// as possible.
+    pub next_frame_time: Option<Instant>,
+}
+
+/// A [`Spawner`](crate::entity::Spawner)'s wave starting or finishing, by spawner name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WaveEvent {
+    Started(String),
+    Finished(String),
+}
+
+/// A checkpoint being reached or respawned at, by checkpoint name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckpointEvent {
+    Reached(String),
+    Respawned(String),
+}
+
+/// A noise spreading through the map, and the AI entities that heard it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoiseEvent {
+    pub position: Vec2,
+    pub radius: f32,
+    pub heard_by: Vec<EntityId>,
+}
+
+/// An AI entity noticing, losing or giving up on what it chases. See [`Ai`](crate::ai::Ai).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AiEvent {
+    /// It saw a hostile body after not seeing one the step before
+    Spotted { entity: EntityId, target: Body },
+    /// It lost sight of what it was chasing and started searching where it was last seen
+    Lost { entity: EntityId, last_seen: Vec2 },
+    /// It searched for as long as it remembers without finding anything
+    GaveUp { entity: EntityId },
+}
+
+/// A projectile or a hitscan weapon's shot hitting a body of a faction hostile to its own. The
+/// projectile has been despawned by the time the game sees this. Health is left to the game.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HitEvent {
+    /// `None` for a hitscan [`Weapon`](crate::weapon::Weapon)'s shot
+    pub projectile: Option<EntityId>,
+    pub target: Body,
+    pub damage: f32,
+}
+
+/// The player taking some of a pickup's item into their inventory. The pickup is despawned
+/// once it's all been taken.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PickupEvent {
+    pub entity: EntityId,
+    pub item: String,
+    pub count: u32,
+}
+
+/// The player firing their weapon, by weapon name, and the projectile it launched if it fires
+/// them. What a hitscan shot hits is reported as a [`HitEvent`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShotEvent {
+    pub weapon: String,
+    pub projectile: Option<EntityId>,
+}
+
+/// Progress on the current map, and the stats of each map left.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatsEvent {
+    SecretFound(String),
+    ObjectiveCompleted(String),
+    /// The stats of a map as they were when the player switched away from it, for a results
+    /// screen
+    LevelFinished(LevelStats),
+}
+
+/// A [`Sequence`] starting or finishing, by sequence name, or reaching one of its
+/// [`Step::Event`](crate::sequence::Step::Event)s, by event name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SequenceEvent {
+    Started(String),
+    Triggered(String),
+    Finished(String),
+}
+
+/// Something done in the [`Menus`](crate::menu::Menus) that the game may want to act on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MenuEvent {
+    /// A screen opened from another, by name
+    Opened(&'static str),
+    /// The last screen closed, so play carries on
+    Closed,
+    /// A [`MenuAction::Custom`](crate::menu::MenuAction::Custom) item chosen, by name
+    Selected(String),
+    /// Volumes or accessibility settings changed, for saving
+    SettingsChanged(Settings),
+    Quit,
+}
+
+/// The wall a ray cast with [`Engine::cast_ray`] hit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WallHit {
+    /// Distance along the ray, in world units
+    pub distance: f32,
+    pub position: Vec2,
+    /// The tile hit as (row, col), or `None` for a linedef
+    pub tile: Option<(usize, usize)>,
+}
+
+/// A texture or sound that failed to load. Textures are drawn as a magenta and black
+/// checkerboard instead and sounds are left silent, so a missing or broken file shows in the
+/// game rather than stopping it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssetEvent {
+    pub path: &'static str,
+    /// What went wrong, with its causes
+    pub error: String,
+}
+
+/// Damage the player took from a hazard floor. Health is left to the game.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DamageEvent {
+    pub amount: f32,
+    /// The map tile id of the hazard
+    pub tile_id: u8,
+}
+
+/// Timing of the frame just rendered, for game code that animates or shows a frame rate.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameTiming {
+    /// Real time since the previous frame, or the capture's frame interval while capturing.
+    /// Unaffected by pausing and the time scale.
+    pub delta_time: Duration,
+    /// Frames per second, smoothed over roughly the last ten frames
+    pub smoothed_fps: f32,
+    /// Frames rendered so far, including this one
+    pub frame_index: u64,
+    /// Every `delta_time` so far added together
+    pub elapsed: Duration,
+}
+
+impl FrameTiming {
+    /// Moves on to the next frame, which took `delta_time`.
+    pub(crate) fn advance(&mut self, delta_time: Duration) {
+        let seconds = delta_time.as_secs_f32();
+        let average = if self.frame_index == 0 || self.smoothed_fps <= 0.0 {
+            seconds
+        } else {
+            0.9 / self.smoothed_fps + 0.1 * seconds
+        };
+
+        self.delta_time = delta_time;
+        self.smoothed_fps = if average > 0.0 { 1.0 / average } else { 0.0 };
+        self.frame_index += 1;
+        self.elapsed += delta_time;
+    }
+}
+
+impl Engine {
+    /// Starts the engine rendering into an existing window. `width` and `height` are the
+    /// window's size in physical pixels.
+    pub async fn from_window_handle(
+        window: Arc<dyn wgpu::WindowHandle>,
+        width: u32,
+        height: u32,
+        maps: Maps,
+        current_map_key: &'static str,
+        config: EngineConfig,
+    ) -> anyhow::Result<Self> {
+        let current_map_key = crate::start_map_key(&config, &maps, current_map_key)?;
+        let maps = Arc::new(maps);
+        let renderer = Renderer::from_window_handle(
+            window,
+            PhysicalSize::new(width, height),
+            current_map_key,
+            &maps[current_map_key],
+            config.assets.clone(),
+            &config.renderer_with_preloads(),
+        )
+        .await?;
+
+        Self::from_renderer(renderer, maps, current_map_key, &config)
+    }
+
+    pub(crate) fn from_renderer(
+        renderer: Renderer,
+        maps: Arc<Maps>,
+        current_map_key: &'static str,
+        config: &EngineConfig,
+    ) -> anyhow::Result<Self> {
+        crate::profiling::start_cpu_profiler();
+
+        let mut raycaster = Raycaster::new(
+            renderer,
+            maps,
+            current_map_key,
+            config.simulation,
+            config.entity_types.clone(),
+            config.weapons.clone(),
+            config.preload_sounds.clone(),
+        )?;
+        raycaster.set_time_scale(config.time_scale);
+        raycaster.set_performance_overlay(config.renderer.performance_overlay);
+        raycaster.set_dynamic_resolution(config.renderer.dynamic_resolution);
+        raycaster.set_column_interleaving(config.renderer.column_interleaving);
+        raycaster.set_stereo(config.renderer.stereo)?;
+        raycaster.set_top_down(config.renderer.top_down);
+        raycaster.set_rear_view(config.renderer.rear_view)?;
+        raycaster.set_audio_config(config.audio);
+        raycaster.set_accessibility(config.accessibility)?;
+        raycaster.set_hostility(config.hostility.clone());
+        raycaster.set_entity_culling(config.entity_culling);
+        raycaster.set_items(config.items.clone());
+        *raycaster.inventory_mut() = config.inventory.clone();
+        if let Some(menus) = &config.menus {
+            raycaster.set_menus(menus.clone());
+        }
+        if config.editor {
+            raycaster.open_editor();
+        } else {
+            raycaster.open_menu(Menus::MAIN);
+        }
+        #[cfg(debug_assertions)]
+        raycaster.set_debug_flags(config.debug);
+
+        Ok(Self { raycaster })
+    }
+
+    /// Call when the window is resized, with its new size in physical pixels.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.raycaster.renderer().resize(width, height);
+    }
+
+    /// Applies the input, advances the simulation and renders a frame.
+    pub fn step(&mut self, input: &InputState) -> anyhow::Result<Frame> {
+        self.raycaster.set_input(input);
+        self.raycaster.update()?;
+
+        Ok(self.frame())
+    }
+
+    /// What the last frame did.
+    pub(crate) fn frame(&mut self) -> Frame {
+        Frame {
+            timing: self.raycaster.timing(),
+            next_frame_time: self.raycaster.renderer().next_frame_time(),
+        }
+    }
+
+    /// Moves to another map, reporting the stats of the one being left as a
+    /// [`StatsEvent::LevelFinished`].
+    pub fn switch_map(&mut self, map_key: &'static str) -> anyhow::Result<()> {
+        self.raycaster.switch_map(map_key)
+    }
+
+    /// The current map's name, par time and other details, for showing a title card or a
+    /// results screen.
+    pub fn map_metadata(&self) -> &MapMetadata {
+        self.raycaster.map_metadata()
+    }
+
+    /// The key of the map being played.
+    pub fn map_key(&self) -> &'static str {
+        self.raycaster.current_map().0
+    }
+
+    /// The map being played, as it was loaded with any diffs
+    /// [applied](Self::apply_map_diff) since. Doors, elevators and other moving parts aren't
+    /// reflected in it.
+    pub fn map(&self) -> &Map {
+        self.raycaster.current_map().1
+    }
+
+    /// The player's point of view, without screen shake or head bob.
+    pub fn camera(&self) -> Camera {
+        self.raycaster.camera()
+    }
+
+    /// World units per second the player is moving at.
+    pub fn player_velocity(&self) -> Vec2 {
+        self.raycaster.player_velocity()
+    }
+
+    /// Simulation steps taken so far. A client sends it with its input so its server can say
+    /// which step a [`reconcile_player`](Self::reconcile_player) is for.
+    pub fn tick(&self) -> u64 {
+        self.raycaster.tick()
+    }
+
+    /// Corrects the player's movement, which is predicted from local input, with where a
+    /// server says they were after step `tick`. The steps since are replayed from there, so
+    /// only a misprediction moves the player. Their facing stays as it is.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use raycasting_engine::prelude::*;
+    /// # fn receive() -> Option<(u64, Vec2, Vec2)> { None }
+    /// # fn send(_: u64, _: &InputState) {}
+    /// # fn step(engine: &mut Engine, input: &InputState) -> anyhow::Result<()> {
+    /// // On the client, every step
+    /// engine.step(input)?;
+    /// send(engine.tick(), input);
+    /// if let Some((tick, position, velocity)) = receive() {
+    ///     engine.reconcile_player(tick, position, velocity);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reconcile_player(&mut self, tick: u64, position: Vec2, velocity: Vec2) {
+        self.raycaster.reconcile_player(tick, position, velocity);
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.raycaster.set_paused(paused);
+    }
+
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.raycaster.set_time_scale(time_scale);
+    }
+
+    /// Timing of the most recent frame.
+    pub fn timing(&self) -> FrameTiming {
+        self.raycaster.timing()
+    }
+
+    /// Sends the elevators of tile type `tile_id` to their other level, as if the player had
+    /// stepped on. Returns whether `tile_id` is an elevator in the current map.
+    pub fn activate_elevator(&mut self, tile_id: u8) -> bool {
+        self.raycaster.activate_elevator(tile_id)
+    }
+
+    /// Opens the [door](crate::map::DoorData) at `position`, in world units, or holds it open
+    /// for longer if it already is. Returns whether there's a door there.
+    pub fn open_door(&mut self, position: Vec2) -> bool {
+        self.raycaster.open_door(position)
+    }
+
+    /// The doors that have started opening since this was last called, whether by
+    /// [`open_door`](Self::open_door) or by AI walking up to them, as a diff for other copies
+    /// of the map. A server sends it to its clients each step, with any tile changes it makes,
+    /// so they see the same world. Doors shut by themselves once their hold is up, so only
+    /// their opening is sent.
+    pub fn take_map_changes(&mut self) -> MapDiff {
+        self.raycaster.take_map_changes()
+    }
+
+    /// Applies a diff from another copy of the current map, like a server's
+    /// [`take_map_changes`](Self::take_map_changes) or a tile it changed. Changes to its tiles
+    /// rebuild its textures, which shows the loading screen while they load, and ones to its
+    /// entities, entity definitions or metadata take effect when it's next started.
+    ///
+    /// The engine doesn't decide which copy is right: a client applies what its server sends,
+    /// and a server applies its own changes before sending them.
+    pub fn apply_map_diff(&mut self, diff: &MapDiff) -> anyhow::Result<()> {
+        self.raycaster.apply_map_diff(diff)
+    }
+
+    /// Decodes a diff sent by another copy of the game, like its server's, for
+    /// [`apply_map_diff`](Self::apply_map_diff). Only paths the maps already use, or the
+    /// preloaded sprites, are accepted, as decoded paths are kept for the rest of the program
+    /// and a peer could otherwise grow it without bound. Use
+    /// [`MapDiff::from_bytes_with_migrations`] with
+    /// [`Migrations::with_allowed_paths`](crate::map::Migrations::with_allowed_paths) to allow
+    /// more.
+    pub fn map_diff_from_bytes(&self, bytes: &[u8]) -> anyhow::Result<MapDiff> {
+        self.raycaster.map_diff_from_bytes(bytes)
+    }
+
+    /// The diff that brings a freshly loaded copy of the current map to where this one is now,
+    /// with its changed tiles and every door that isn't shut, for a client that joins part way
+    /// through.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn send(_: Vec<u8>) {}
+    /// # fn sync(engine: &mut raycasting_engine::engine::Engine) -> anyhow::Result<()> {
+    /// // On the server, once a client has joined
+    /// send(engine.map_state()?.to_bytes());
+    ///
+    /// // Then every step
+    /// let changes = engine.take_map_changes();
+    /// if !changes.is_empty() {
+    ///     send(changes.to_bytes());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn map_state(&self) -> anyhow::Result<MapDiff> {
+        self.raycaster.map_state()
+    }
+
+    /// How much of the sky around the player is open to the map's
+    /// [`Weather`](crate::map::Weather), from 0 under a roof to 1 out in the open. It eases
+    /// as the player moves in and out of shelter, for muffling sounds or fading in a wet
+    /// screen effect. 0 on maps without weather.
+    pub fn weather_exposure(&self) -> f32 {
+        self.raycaster.weather_exposure()
+    }
+
+    /// Flashes lightning over the scene, with its thunder following as late as the strike is
+    /// far away. Its thunder is only heard if the sound is loaded, as it is when listed in
+    /// [`EngineConfig::preload_sounds`].
+    pub fn strike_lightning(&mut self, lightning: Lightning) {
+        self.raycaster.strike_lightning(lightning);
+    }
+
+    /// The time of day as a fraction of a day since midnight, so 0.25 is dawn and 0.5 is
+    /// noon. It only passes on maps with a [`DayNight`](crate::map::DayNight) cycle.
+    pub fn time_of_day(&self) -> f32 {
+        self.raycaster.time_of_day()
+    }
+
+    /// Jumps to a time of day, as in [`time_of_day`](Self::time_of_day). Values outside
+    /// `0.0..1.0` wrap around.
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        self.raycaster.set_time_of_day(time_of_day);
+    }
+
+    /// Adds a factory for entity definitions of this type. It's used from the next map switch
+    /// on, so types the first map needs belong in [`EngineConfig::entity_types`].
+    pub fn register_entity_type(
+        &mut self,
+        type_name: impl Into<String>,
+        factory: impl Fn(&EntityDef) -> Entity + Send + Sync + 'static,
+    ) {
+        self.raycaster
+            .register_entity_type(type_name.into(), factory);
+    }
+
+    /// Adds an entity to the current map. Entities are cleared when switching maps. Errors if
+    /// its sprite image isn't loaded with the map, see [`Entity::with_sprite`].
+    pub fn spawn(&mut self, entity: Entity) -> anyhow::Result<EntityId> {
+        self.raycaster.spawn(entity)
+    }
+
+    /// Removes an entity, returning it if it was still alive.
+    pub fn despawn(&mut self, id: EntityId) -> Option<Entity> {
+        self.raycaster.despawn(id)
+    }
+
+    pub fn entity(&self, id: EntityId) -> Option<&Entity> {
+        self.raycaster.entity(id)
+    }
+
+    pub fn entity_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        self.raycaster.entity_mut(id)
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = (EntityId, &Entity)> {
+        self.raycaster.entities()
+    }
+
+    /// Bodies that overlapped during the most recent step, such as a projectile reaching an
+    /// enemy. Each pair is listed once.
+    pub fn contacts(&self) -> &[Contact] {
+        self.raycaster.contacts()
+    }
+
+    /// Whether a straight line between two points in the current map, in world units, is
+    /// clear of walls. For deciding whether enemies notice the player, whether someone is
+    /// hidden, or whether a sound is muffled.
+    pub fn can_see(&self, a: Vec2, b: Vec2) -> bool {
+        self.raycaster.can_see(a, b)
+    }
+
+    /// The nearest wall along a ray from `origin` in `direction`, in world units, in the
+    /// current map. For aiming, placing decals, or measuring how far the player can move.
+    pub fn cast_ray(&self, origin: Vec2, direction: Vec2) -> Option<WallHit> {
+        if direction == Vec2::ZERO {
+            return None;
+        }
+        self.raycaster
+            .cast_ray(origin, direction.y.atan2(direction.x))
+    }
+
+    /// The engine's sound, for the game to play through its audio output.
+    pub fn audio_output(&self) -> AudioOutput {
+        self.raycaster.audio_output()
+    }
+
+    /// The current volumes, for saving from an options menu.
+    pub fn audio_config(&self) -> AudioConfig {
+        self.raycaster.audio_config()
+    }
+
+    /// Sets one bus's volume, from 0.0 to 1.0.
+    pub fn set_volume(&mut self, bus: AudioBus, volume: f32) {
+        let mut audio_config = self.raycaster.audio_config();
+        audio_config.set_volume(bus, volume);
+        self.raycaster.set_audio_config(audio_config);
+    }
+
+    /// Damage the player took during the most recent step. Damage isn't taken while paused.
+    pub fn damage_events(&self) -> &[DamageEvent] {
+        self.raycaster.damage_events()
+    }
+
+    /// Textures and sounds that failed to load during the most recent step. Textures are
+    /// replaced with a placeholder and sounds left silent. A map's textures load when it's
+    /// first entered, and its sounds each time it is.
+    pub fn asset_events(&self) -> &[AssetEvent] {
+        self.raycaster.asset_events()
+    }
+
+    /// Spawner waves that started or finished during the most recent step.
+    pub fn wave_events(&self) -> &[WaveEvent] {
+        self.raycaster.wave_events()
+    }
+
+    /// Starts a spawner's wave on the next step, for spawners with
+    /// [`SpawnTrigger::Manual`](crate::entity::SpawnTrigger::Manual) or to start one early.
+    /// Returns whether the current map has a spawner by that name that hasn't started yet.
+    pub fn trigger_spawner(&mut self, name: &str) -> bool {
+        self.raycaster.trigger_spawner(name)
+    }
+
+    /// Records a checkpoint where the player is now, as if they had walked into a map's
+    /// [`Checkpoint`](crate::map::Checkpoint). It holds their position and inventory and the
+    /// current map's elevators, entities and spawner waves. Replaces the last checkpoint.
+    pub fn set_checkpoint(&mut self, name: impl Into<String>) {
+        self.raycaster.save_checkpoint(name.into());
+    }
+
+    /// The name of the checkpoint [`respawn`](Self::respawn) goes back to, if one has been
+    /// reached.
+    pub fn checkpoint(&self) -> Option<&str> {
+        self.raycaster.checkpoint_name()
+    }
+
+    /// Puts the player and their map back as they were at the last checkpoint at the start of
+    /// the next step, for when the player dies. Returns whether a checkpoint has been reached.
+    pub fn respawn(&mut self) -> bool {
+        self.raycaster.request_respawn()
+    }
+
+    /// Shows the automap, the parts of the current map the player has seen from above, in
+    /// place of the view, or the view again. M toggles it too.
+    pub fn set_automap(&mut self, open: bool) {
+        self.raycaster.set_automap(open);
+    }
+
+    /// The tiles of a map the player has seen, if they've been on it. Save it with
+    /// [`Exploration::to_bytes`].
+    pub fn exploration(&self, map_key: &str) -> Option<&Exploration> {
+        self.raycaster.exploration(map_key)
+    }
+
+    /// Replaces what the player has seen of a map, such as with one from a save game. Fails if
+    /// there's no such map or the exploration is a different size.
+    pub fn set_exploration(
+        &mut self,
+        map_key: &'static str,
+        exploration: Exploration,
+    ) -> anyhow::Result<()> {
+        self.raycaster.set_exploration(map_key, exploration)
+    }
+
+    /// Makes a noise at `position`, like a gunshot, heard at the next step by AI entities up to
+    /// `radius` world units away. It travels through open tiles and around corners, so walls
+    /// keep it from the other side, and thin walls muffle it. Entities that hear it walk to
+    /// where it was made.
+    pub fn make_noise(&mut self, position: Vec2, radius: f32) {
+        self.raycaster.make_noise(position, radius);
+    }
+
+    /// Makes AI plan routes around `region` for `duration`, as though its tiles cost `cost`
+    /// times as much to cross, like a danger zone around a grenade or a fire. Entities already
+    /// inside still walk out. See [`Map::with_path_cost`](crate::map::Map::with_path_cost) for
+    /// lasting costs. Lifted on switching maps.
+    pub fn stamp_path_cost(&mut self, region: Region, cost: f32, duration: Duration) {
+        self.raycaster.stamp_path_cost(region, cost, duration);
+    }
+
+    /// Lifts every cost laid with [`stamp_path_cost`](Self::stamp_path_cost).
+    pub fn clear_path_stamps(&mut self) {
+        self.raycaster.clear_path_stamps();
+    }
+
+    /// Noises made during the most recent step, by the game or the player's footsteps on
+    /// noisy floors.
+    pub fn noise_events(&self) -> &[NoiseEvent] {
+        self.raycaster.noise_events()
+    }
+
+    /// AI entities spotting, losing and giving up on targets during the current frame.
+    pub fn ai_events(&self) -> &[AiEvent] {
+        self.raycaster.ai_events()
+    }
+
+    pub fn entity_culling(&self) -> Option<EntityCulling> {
+        self.raycaster.entity_culling()
+    }
+
+    /// Stops updating entities far from the player, or updates them all again with `None`.
+    pub fn set_entity_culling(&mut self, entity_culling: Option<EntityCulling>) {
+        self.raycaster.set_entity_culling(entity_culling);
+    }
+
+    /// Changes which factions fight each other.
+    pub fn set_hostility(&mut self, hostility: Hostility) {
+        self.raycaster.set_hostility(hostility);
+    }
+
+    /// Projectile hits during the most recent step. See [`Entity::with_damage`].
+    pub fn hit_events(&self) -> &[HitEvent] {
+        self.raycaster.hit_events()
+    }
+
+    /// Adds or replaces an item pickups can give.
+    pub fn register_item(&mut self, name: impl Into<String>, def: ItemDef) {
+        self.raycaster.register_item(name.into(), def);
+    }
+
+    pub fn items(&self) -> &Items {
+        self.raycaster.items()
+    }
+
+    /// The player's items. It's kept across map switches and put back on respawning.
+    pub fn inventory(&self) -> &Inventory {
+        self.raycaster.inventory()
+    }
+
+    /// For using up items, or giving or taking them for the game's own reasons. Adding needs
+    /// [`items`](Self::items).
+    pub fn inventory_mut(&mut self) -> &mut Inventory {
+        self.raycaster.inventory_mut()
+    }
+
+    /// Pickups the player took during the most recent step. See [`Entity::with_pickup`].
+    pub fn pickup_events(&self) -> &[PickupEvent] {
+        self.raycaster.pickup_events()
+    }
+
+    /// Holds the weapon with this name, from [`EngineConfig::weapons`]. Returns whether there is
+    /// one.
+    pub fn select_weapon(&mut self, name: &str) -> bool {
+        self.raycaster.select_weapon(name)
+    }
+
+    /// The weapon the player is holding, if they have any.
+    pub fn weapon(&self) -> Option<&Weapon> {
+        self.raycaster.weapon()
+    }
+
+    /// Shots the player fired during the most recent step.
+    pub fn shot_events(&self) -> &[ShotEvent] {
+        self.raycaster.shot_events()
+    }
+
+    /// Time, kills, secrets, counters and objectives on the current map.
+    pub fn stats(&self) -> &LevelStats {
+        self.raycaster.stats()
+    }
+
+    /// Secrets found, objectives completed and maps left during the most recent step.
+    pub fn stats_events(&self) -> &[StatsEvent] {
+        self.raycaster.stats_events()
+    }
+
+    /// Removes an entity the player killed, counting it in the stats. Returns the entity if it
+    /// was still alive.
+    pub fn kill(&mut self, id: EntityId) -> Option<Entity> {
+        self.raycaster.kill(id)
+    }
+
+    /// Adds to one of the game's own counters on the current map, like coins collected.
+    /// `amount` can be negative.
+    pub fn add_to_counter(&mut self, name: &str, amount: i64) {
+        self.raycaster.add_to_counter(name, amount);
+    }
+
+    /// Sets a goal for the current map. Adding one with the name of an existing one replaces
+    /// it.
+    pub fn add_objective(&mut self, name: impl Into<String>, description: impl Into<String>) {
+        self.raycaster
+            .add_objective(name.into(), description.into());
+    }
+
+    /// Marks an objective complete. Returns whether there was an incomplete one by that name.
+    pub fn complete_objective(&mut self, name: &str) -> bool {
+        self.raycaster.complete_objective(name)
+    }
+
+    /// Runs a sequence, once any already running or waiting have finished.
+    pub fn play_sequence(&mut self, sequence: Sequence) {
+        self.raycaster.play_sequence(sequence);
+    }
+
+    /// Runs the current map's sequence with this name, whatever its trigger, like
+    /// [`play_sequence`](Self::play_sequence). Returns whether the map has one.
+    pub fn start_sequence(&mut self, name: &str) -> bool {
+        self.raycaster.start_sequence(name)
+    }
+
+    /// Ends the running sequence straight away, for a player who doesn't want to watch. The
+    /// events it had left are still reported, so the story carries on as if it had played out.
+    pub fn skip_sequence(&mut self) {
+        self.raycaster.skip_sequence();
+    }
+
+    pub fn sequence_playing(&self) -> bool {
+        self.raycaster.sequence_playing()
+    }
+
+    /// Sequences started and finished, and their events, during the most recent step.
+    pub fn sequence_events(&self) -> &[SequenceEvent] {
+        self.raycaster.sequence_events()
+    }
+
+    pub fn accessibility(&self) -> AccessibilityConfig {
+        self.raycaster.accessibility()
+    }
+
+    /// Changes the accessibility settings while running. Fails if the view can't be rebuilt for
+    /// a new field of view.
+    pub fn set_accessibility(&mut self, accessibility: AccessibilityConfig) -> anyhow::Result<()> {
+        self.raycaster.set_accessibility(accessibility)
+    }
+
+    /// The volumes and accessibility settings together, as the menus save them.
+    pub fn settings(&self) -> Settings {
+        self.raycaster.settings()
+    }
+
+    pub fn set_settings(&mut self, settings: Settings) -> anyhow::Result<()> {
+        self.raycaster.set_settings(settings)
+    }
+
+    /// Shakes the view `amplitude` degrees each way, dying down over `duration`, for
+    /// explosions and heavy landings. Scaled by the player's
+    /// [`screen_shake`](AccessibilityConfig::screen_shake) setting. A stronger shake replaces
+    /// a weaker one.
+    pub fn shake(&mut self, amplitude: f32, duration: Duration) {
+        self.raycaster.shake(amplitude, duration);
+    }
+
+    /// The menus from [`EngineConfig::menus`], and which screen is open.
+    pub fn menus(&self) -> &Menus {
+        self.raycaster.menus()
+    }
+
+    /// Opens a menu screen over any already open, stopping the simulation. Returns whether
+    /// there is one by that name.
+    pub fn open_menu(&mut self, name: &str) -> bool {
+        self.raycaster.open_menu(name)
+    }
+
+    /// Closes every open menu screen, carrying on play.
+    pub fn close_menu(&mut self) {
+        self.raycaster.close_menu();
+    }
+
+    /// Moves through the open menus, for a host reading its own keys or a gamepad. Volumes
+    /// changed are applied straight away. Quitting is left to the host.
+    pub fn navigate_menu(&mut self, input: MenuInput) -> Option<MenuEvent> {
+        self.raycaster.navigate_menu(input)
+    }
+
+    /// Checkpoints reached and respawns during the most recent step.
+    pub fn checkpoint_events(&self) -> &[CheckpointEvent] {
+        self.raycaster.checkpoint_events()
+    }
+
+    pub fn set_performance_overlay(&mut self, performance_overlay: bool) {
+        self.raycaster.set_performance_overlay(performance_overlay);
+    }
+
+    pub fn rear_view(&self) -> Option<RearView> {
+        self.raycaster.rear_view()
+    }
+
+    /// Shows what's behind the player in an area over the view, with `Some`, or stops. Fails
+    /// if the mirror's view can't be built.
+    pub fn set_rear_view(&mut self, rear_view: Option<RearView>) -> anyhow::Result<()> {
+        self.raycaster.set_rear_view(rear_view)
+    }
+
+    pub fn top_down(&self) -> Option<TopDown> {
+        self.raycaster.top_down()
+    }
+
+    /// Draws the map from above in place of the first-person view, with `Some`, or goes back
+    /// to it. The game carries on either way.
+    pub fn set_top_down(&mut self, top_down: Option<TopDown>) {
+        self.raycaster.set_top_down(top_down);
+    }
+
+    pub fn stereo(&self) -> Option<Stereo> {
+        self.raycaster.stereo()
+    }
+
+    /// Turns side-by-side stereo on, with `Some`, or off. Fails if the second view can't be
+    /// built.
+    pub fn set_stereo(&mut self, stereo: Option<Stereo>) -> anyhow::Result<()> {
+        self.raycaster.set_stereo(stereo)
+    }
+
+    /// Shows `image` in a [`SecondaryWindow`](crate::config::SecondaryWindow) with
+    /// [`WindowContent::Image`](crate::config::WindowContent::Image), by its position in
+    /// [`EngineConfig::windows`](crate::config::EngineConfig::windows). Kept until replaced.
+    pub fn set_window_image(&mut self, index: usize, image: &RgbaImage) {
+        self.raycaster.renderer().set_window_image(index, image);
+    }
+
+    /// The adapter the engine is rendering with, its limits and what it supports, as printed
+    /// at startup.
+    pub fn device_report(&self) -> &DeviceReport {
+        self.raycaster.device_report()
+    }
+
+    /// How long the GPU spent on each stage of a recent frame. `None` unless
+    /// [`gpu_profiling`](crate::config::RendererConfig::gpu_profiling) is on and the adapter
+    /// supports it, and until the first measurement comes back.
+    pub fn gpu_timings(&self) -> Option<GpuTimings> {
+        self.raycaster.gpu_timings()
+    }
+
+    /// Saves the next frame as a PNG at `path`. Native only.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn screenshot(&mut self, path: impl Into<std::path::PathBuf>) {
+        self.raycaster.renderer().request_screenshot(path.into());
+    }
+}
@@ -0,0 +1,789 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    ops::{BitOr, BitOrAssign},
+    sync::Arc,
+    time::Duration,
+};
+
+use glam::Vec2;
+
+use crate::{ai::Ai, inventory::ItemStack, map::Region};
+
+/// Groups of things that can collide, as bits. An entity is in some groups (its layers) and
+/// collides with some groups (its mask). Two bodies only collide if each is in a group the
+/// other collides with, so projectiles masked to [`ENEMY`](Self::ENEMY) pass through allies.
+///
+/// Games can define their own groups with [`bit`](Self::bit), above the built-in ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CollisionLayers(pub u32);
+
+impl CollisionLayers {
+    pub const NONE: Self = Self(0);
+    pub const PLAYER: Self = Self(1);
+    pub const ENEMY: Self = Self(1 << 1);
+    pub const ALLY: Self = Self(1 << 2);
+    pub const PROJECTILE: Self = Self(1 << 3);
+    pub const ALL: Self = Self(u32::MAX);
+
+    /// A group for the game's own use. Bits 0 to 3 are the built-in groups.
+    pub const fn bit(bit: u32) -> Self {
+        Self(1 << bit)
+    }
+
+    pub const fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl BitOr for CollisionLayers {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for CollisionLayers {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+/// A side entities fight for. Whether two factions fight is up to the [`Hostility`] table,
+/// which settles which projectiles hurt which bodies and which bodies AI goes after.
+///
+/// Games can number their own factions above the built-in ones, like `Faction(3)`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Faction(pub u16);
+
+impl Faction {
+    pub const NEUTRAL: Self = Self(0);
+    /// The player's faction, and their projectiles'
+    pub const PLAYER: Self = Self(1);
+    pub const ENEMY: Self = Self(2);
+}
+
+/// Which factions are hostile to each other, both ways. The default has the player and the
+/// enemies hostile to each other and nothing else.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::entity::{Faction, Hostility};
+///
+/// const MUTANTS: Faction = Faction(3);
+///
+/// // Enemies and mutants fight the player and each other
+/// let hostility = Hostility::default()
+///     .with_hostile(Faction::PLAYER, MUTANTS)
+///     .with_hostile(Faction::ENEMY, MUTANTS);
+/// assert!(hostility.is_hostile(MUTANTS, Faction::ENEMY));
+/// assert!(!hostility.is_hostile(Faction::ENEMY, Faction::ENEMY));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hostility {
+    /// Pairs of hostile factions, the lower one first
+    pairs: HashSet<(Faction, Faction)>,
+}
+
+impl Default for Hostility {
+    fn default() -> Self {
+        Self::new().with_hostile(Faction::PLAYER, Faction::ENEMY)
+    }
+}
+
+impl Hostility {
+    /// Nobody is hostile to anybody.
+    pub fn new() -> Self {
+        Self {
+            pairs: HashSet::new(),
+        }
+    }
+
+    /// Makes `a` and `b` hostile to each other. A faction can be hostile to itself, for a free
+    /// for all.
+    pub fn with_hostile(mut self, a: Faction, b: Faction) -> Self {
+        self.pairs.insert((a.min(b), a.max(b)));
+        self
+    }
+
+    pub fn is_hostile(&self, a: Faction, b: Faction) -> bool {
+        self.pairs.contains(&(a.min(b), a.max(b)))
+    }
+}
+
+/// Puts entities far from the player to sleep so large maps stay fast: a dormant entity isn't
+/// steered by its AI, moved or collided with. An entity goes dormant once it's more than
+/// `radius + hysteresis` from the player and wakes when it comes back within `radius`, so
+/// those near the edge don't flicker between the two. It keeps its velocity and AI state while
+/// asleep. See [`EngineConfig`](crate::config::EngineConfig).
+///
+/// Projectiles that fly out of range stop too, so give them a lifetime or despawn them
+/// further out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EntityCulling {
+    /// World units from the player
+    pub radius: f32,
+    /// World units past `radius`
+    pub hysteresis: f32,
+}
+
+impl Default for EntityCulling {
+    /// 32 tiles of 64 units, with 4 more before going dormant.
+    fn default() -> Self {
+        Self {
+            radius: 2048.0,
+            hysteresis: 256.0,
+        }
+    }
+}
+
+/// Refers to a spawned entity. Ids aren't reused, so an id for a despawned entity stays
+/// invalid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId(u32);
+
+/// Something that moves around the map, like an enemy or a projectile. Entities are circles
+/// that slide along walls and push each other apart, and are drawn as a billboard if they have
+/// a sprite.
+///
+/// An entity's sprite image has to be loaded with the map, so it must also be used by one of
+/// the map's sprites, starting entities or entity definitions. See
+/// [`Map::with_entities`](crate::map::Map::with_entities).
+///
+/// # Example
+///
+/// ```
+/// use glam::vec2;
+/// use raycasting_engine::entity::{CollisionLayers, Entity, Faction};
+///
+/// let guard = Entity::new(vec2(160.0, 96.0))
+///     .with_sprite("sprites/guard.png")
+///     .with_collision(CollisionLayers::ENEMY, CollisionLayers::ALL);
+///
+/// // Hits enemies for 10 damage, flies through the player and allies, and doesn't push
+/// // anything
+/// let bolt = Entity::new(vec2(96.0, 96.0))
+///     .with_radius(4.0)
+///     .with_velocity(vec2(400.0, 0.0))
+///     .with_collision(CollisionLayers::PROJECTILE, CollisionLayers::ENEMY)
+///     .sensor()
+///     .with_faction(Faction::PLAYER)
+///     .with_damage(10.0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entity {
+    pub(crate) position: Vec2,
+    /// World units per second
+    pub(crate) velocity: Vec2,
+    pub(crate) radius: f32,
+    pub(crate) layers: CollisionLayers,
+    pub(crate) mask: CollisionLayers,
+    /// Reports contacts without pushing or being pushed
+    pub(crate) sensor: bool,
+    pub(crate) texture_path: Option<&'static str>,
+    pub(crate) faction: Faction,
+    /// Dealt to the first hostile body a sensor touches
+    pub(crate) damage: f32,
+    /// Added to the player's inventory when they touch it
+    pub(crate) pickup: Option<ItemStack>,
+    /// Steers the entity by setting its velocity each step
+    pub(crate) ai: Option<Ai>,
+    /// Set while [`EntityCulling`] has put it to sleep
+    pub(crate) dormant: bool,
+}
+
+impl Entity {
+    /// A solid entity 16 world units in radius, in the enemy group and faction and colliding
+    /// with everything.
+    pub fn new(position: Vec2) -> Self {
+        Self {
+            position,
+            velocity: Vec2::ZERO,
+            radius: 16.0,
+            layers: CollisionLayers::ENEMY,
+            mask: CollisionLayers::ALL,
+            sensor: false,
+            texture_path: None,
+            faction: Faction::ENEMY,
+            damage: 0.0,
+            pickup: None,
+            ai: None,
+            dormant: false,
+        }
+    }
+
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius.max(0.0);
+        self
+    }
+
+    pub fn with_velocity(mut self, velocity: Vec2) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    /// The groups the entity is in and the groups it collides with.
+    pub fn with_collision(mut self, layers: CollisionLayers, mask: CollisionLayers) -> Self {
+        self.layers = layers;
+        self.mask = mask;
+        self
+    }
+
+    /// Makes the entity report contacts without pushing anything apart, like a projectile or
+    /// a pickup.
+    pub fn sensor(mut self) -> Self {
+        self.sensor = true;
+        self
+    }
+
+    /// Draws the entity as a billboard of this image.
+    pub fn with_sprite(mut self, texture_path: &'static str) -> Self {
+        self.texture_path = Some(texture_path);
+        self
+    }
+
+    pub fn with_faction(mut self, faction: Faction) -> Self {
+        self.faction = faction;
+        self
+    }
+
+    /// Makes a sensor entity a projectile: the first body it touches of a faction hostile to
+    /// its own takes `damage`, reported as a [`HitEvent`](crate::engine::HitEvent), and the
+    /// entity is despawned. Bodies of other factions are passed through.
+    pub fn with_damage(mut self, damage: f32) -> Self {
+        self.damage = damage.max(0.0);
+        self
+    }
+
+    /// Makes a sensor entity a pickup: when the player touches it, as many of `count` of `item`
+    /// as fit go into their [`Inventory`](crate::inventory::Inventory), reported as a
+    /// [`PickupEvent`](crate::engine::PickupEvent). The entity is despawned once it's all
+    /// been taken.
+    pub fn with_pickup(mut self, item: impl Into<String>, count: u32) -> Self {
+        self.pickup = Some(ItemStack::new(item, count));
+        self
+    }
+
+    /// Lets the engine move the entity, patrolling and chasing hostile bodies. Its velocity is
+    /// replaced each step.
+    pub fn with_ai(mut self, ai: Ai) -> Self {
+        self.ai = Some(ai);
+        self
+    }
+
+    pub fn position(&self) -> Vec2 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Vec2) {
+        self.position = position;
+    }
+
+    pub fn velocity(&self) -> Vec2 {
+        self.velocity
+    }
+
+    pub fn set_velocity(&mut self, velocity: Vec2) {
+        self.velocity = velocity;
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn faction(&self) -> Faction {
+        self.faction
+    }
+
+    pub fn damage(&self) -> f32 {
+        self.damage
+    }
+
+    /// What's left of the item the entity gives the player, if it's a pickup.
+    pub fn pickup(&self) -> Option<&ItemStack> {
+        self.pickup.as_ref()
+    }
+
+    pub fn ai(&self) -> Option<&Ai> {
+        self.ai.as_ref()
+    }
+
+    /// Whether the entity is too far from the player to be updated. See [`EntityCulling`].
+    pub fn is_dormant(&self) -> bool {
+        self.dormant
+    }
+}
+
+/// An entity placed in a map by type name, so maps can say what goes where and the game
+/// decides what each type is. Each time the map is switched to, the factory registered for the
+/// type in [`EntityTypes`] builds the entity from this.
+///
+/// A sprite image the factory gives the entity has to be loaded with the map, so name it with
+/// [`with_sprite`](Self::with_sprite).
+///
+/// # Example
+///
+/// ```
+/// use glam::vec2;
+/// use raycasting_engine::entity::{Entity, EntityDef, EntityTypes};
+///
+/// let guard = EntityDef::new("guard", vec2(160.0, 96.0))
+///     .with_rotation(90.0)
+///     .with_sprite("sprites/guard.png")
+///     .with_property("speed", "80");
+///
+/// let types = EntityTypes::new().with_type("guard", |def| {
+///     let speed: f32 = def.property("speed").and_then(|speed| speed.parse().ok()).unwrap_or(60.0);
+///     let facing = glam::Vec2::from_angle(def.rotation().to_radians());
+///     let mut entity = Entity::new(def.position()).with_velocity(facing * speed);
+///     if let Some(sprite) = def.sprite() {
+///         entity = entity.with_sprite(sprite);
+///     }
+///     entity
+/// });
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntityDef {
+    pub(crate) type_name: String,
+    pub(crate) position: Vec2,
+    /// Degrees clockwise from +x, like the camera's
+    pub(crate) rotation: f32,
+    pub(crate) texture_path: Option<&'static str>,
+    pub(crate) properties: BTreeMap<String, String>,
+}
+
+impl EntityDef {
+    pub fn new(type_name: impl Into<String>, position: Vec2) -> Self {
+        Self {
+            type_name: type_name.into(),
+            position,
+            rotation: 0.0,
+            texture_path: None,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    /// Which way the entity faces, in degrees clockwise from +x.
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// An image loaded with the map for the factory to draw the entity with.
+    pub fn with_sprite(mut self, texture_path: &'static str) -> Self {
+        self.texture_path = Some(texture_path);
+        self
+    }
+
+    /// A setting for the factory, like a health or a patrol speed.
+    pub fn with_property(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.properties.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    pub fn position(&self) -> Vec2 {
+        self.position
+    }
+
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    pub fn sprite(&self) -> Option<&'static str> {
+        self.texture_path
+    }
+
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(String::as_str)
+    }
+
+    pub fn properties(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.properties
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+/// Builds an entity from its definition in a map.
+pub type EntityFactory = dyn Fn(&EntityDef) -> Entity + Send + Sync;
+
+/// The factories for the entity types maps name in their [`EntityDef`]s. Definitions of a type
+/// with no factory are skipped. See [`EngineConfig`](crate::config::EngineConfig).
+#[derive(Clone, Default)]
+pub struct EntityTypes {
+    factories: HashMap<String, Arc<EntityFactory>>,
+}
+
+impl EntityTypes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_type(
+        mut self,
+        type_name: impl Into<String>,
+        factory: impl Fn(&EntityDef) -> Entity + Send + Sync + 'static,
+    ) -> Self {
+        self.register(type_name, factory);
+        self
+    }
+
+    pub(crate) fn register(
+        &mut self,
+        type_name: impl Into<String>,
+        factory: impl Fn(&EntityDef) -> Entity + Send + Sync + 'static,
+    ) {
+        self.factories.insert(type_name.into(), Arc::new(factory));
+    }
+
+    /// The entity for a definition, or `None` if its type isn't registered.
+    pub(crate) fn build(&self, def: &EntityDef) -> Option<Entity> {
+        self.factories
+            .get(&def.type_name)
+            .map(|factory| factory(def))
+    }
+}
+
+impl fmt::Debug for EntityTypes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut type_names: Vec<&String> = self.factories.keys().collect();
+        type_names.sort_unstable();
+        f.debug_set().entries(type_names).finish()
+    }
+}
+
+/// What starts a [`Spawner`]'s wave.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpawnTrigger {
+    /// As soon as the map is switched to
+    MapStart,
+    /// When the player first enters the region
+    PlayerEnters(Region),
+    /// When the wave of the spawner with this name finishes
+    AfterWave(String),
+    /// When the game calls [`Engine::trigger_spawner`](crate::engine::Engine::trigger_spawner)
+    Manual,
+}
+
+/// Spawns a wave of entities once its trigger fires: `count` of them built from `entity`,
+/// `interval` apart, at the definition's position. The wave finishes when all of them have
+/// spawned and been despawned. Each wave runs once each time the map is switched to, and its
+/// start and finish are reported as [`WaveEvent`](crate::engine::WaveEvent)s.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use glam::vec2;
+/// use raycasting_engine::{
+///     entity::{EntityDef, SpawnTrigger, Spawner},
+///     map::Region,
+/// };
+///
+/// let courtyard = Region::Rect {
+///     min: vec2(512.0, 512.0),
+///     max: vec2(1024.0, 1024.0),
+/// };
+/// let first = Spawner::new("first", EntityDef::new("zombie", vec2(768.0, 600.0)), 5)
+///     .with_interval(Duration::from_millis(1500))
+///     .with_trigger(SpawnTrigger::PlayerEnters(courtyard));
+/// let second = Spawner::new("second", EntityDef::new("brute", vec2(768.0, 600.0)), 2)
+///     .with_trigger(SpawnTrigger::AfterWave("first".into()));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spawner {
+    pub(crate) name: String,
+    pub(crate) entity: EntityDef,
+    pub(crate) count: u32,
+    pub(crate) interval: Duration,
+    pub(crate) trigger: SpawnTrigger,
+}
+
+impl Spawner {
+    /// A spawner started with the map, a second between spawns.
+    pub fn new(name: impl Into<String>, entity: EntityDef, count: u32) -> Self {
+        Self {
+            name: name.into(),
+            entity,
+            count,
+            interval: Duration::from_secs(1),
+            trigger: SpawnTrigger::MapStart,
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_trigger(mut self, trigger: SpawnTrigger) -> Self {
+        self.trigger = trigger;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn entity(&self) -> &EntityDef {
+        &self.entity
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn trigger(&self) -> &SpawnTrigger {
+        &self.trigger
+    }
+}
+
+/// One of the two things in a [`Contact`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Body {
+    Player,
+    Entity(EntityId),
+}
+
+/// Two bodies that overlapped during a step, after any pushing apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Contact {
+    pub a: Body,
+    pub b: Body,
+}
+
+/// The live entities of the current map.
+#[derive(Clone, Default)]
+pub(crate) struct Entities {
+    entities: Vec<(EntityId, Entity)>,
+    next_id: u32,
+}
+
+impl Entities {
+    pub fn spawn(&mut self, entity: Entity) -> EntityId {
+        let id = EntityId(self.next_id);
+        self.next_id += 1;
+        self.entities.push((id, entity));
+        id
+    }
+
+    /// Returns the entity if it was still alive.
+    pub fn despawn(&mut self, id: EntityId) -> Option<Entity> {
+        let index = self.entities.iter().position(|(other, _)| *other == id)?;
+        Some(self.entities.remove(index).1)
+    }
+
+    pub fn clear(&mut self) {
+        self.entities.clear();
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity> {
+        self.iter()
+            .find(|(other, _)| *other == id)
+            .map(|(_, entity)| entity)
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        self.entities
+            .iter_mut()
+            .find(|(other, _)| *other == id)
+            .map(|(_, entity)| entity)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &Entity)> {
+        self.entities.iter().map(|(id, entity)| (*id, entity))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut Entity)> {
+        self.entities.iter_mut().map(|(id, entity)| (*id, entity))
+    }
+
+    /// Puts entities that have strayed out of range to sleep and wakes those back in range,
+    /// or wakes them all without `culling`.
+    pub fn update_dormancy(&mut self, culling: Option<&EntityCulling>, player: Vec2) {
+        for (_, entity) in &mut self.entities {
+            let Some(culling) = culling else {
+                entity.dormant = false;
+                continue;
+            };
+            let distance = entity.position.distance(player);
+            if entity.dormant {
+                entity.dormant = distance > culling.radius;
+            } else {
+                entity.dormant = distance > culling.radius + culling.hysteresis;
+            }
+        }
+    }
+}
+
+/// A body taking part in collision resolution.
+pub(crate) struct Collider {
+    pub body: Body,
+    pub position: Vec2,
+    pub radius: f32,
+    pub layers: CollisionLayers,
+    pub mask: CollisionLayers,
+    pub sensor: bool,
+}
+
+impl Collider {
+    pub fn from_entity(id: EntityId, entity: &Entity) -> Self {
+        Self {
+            body: Body::Entity(id),
+            position: entity.position,
+            radius: entity.radius,
+            layers: entity.layers,
+            mask: entity.mask,
+            sensor: entity.sensor,
+        }
+    }
+
+    fn interacts(&self, other: &Collider) -> bool {
+        self.layers.intersects(other.mask) && other.layers.intersects(self.mask)
+    }
+}
+
+/// Indices of points bucketed by the grid cell they're in, so a query only looks at the points
+/// in the cells it touches instead of all of them.
+pub(crate) struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialHash {
+    /// Buckets each point by its index. Queries are quickest with cells about as big as their
+    /// radius.
+    pub fn new(cell_size: f32, points: impl IntoIterator<Item = Vec2>) -> Self {
+        let mut hash = Self {
+            cell_size: cell_size.max(1.0),
+            cells: HashMap::new(),
+        };
+        for (index, point) in points.into_iter().enumerate() {
+            hash.cells.entry(hash.cell(point)).or_default().push(index);
+        }
+        hash
+    }
+
+    fn cell(&self, point: Vec2) -> (i32, i32) {
+        let cell = (point / self.cell_size).floor();
+        (cell.x as i32, cell.y as i32)
+    }
+
+    /// The indices of the points in the cells within `radius` of `center`, which includes every
+    /// point within `radius` and some a little further.
+    pub fn query(&self, center: Vec2, radius: f32) -> impl Iterator<Item = usize> + '_ {
+        let (min_x, min_y) = self.cell(center - radius);
+        let (max_x, max_y) = self.cell(center + radius);
+        let span = (max_x as i64 - min_x as i64 + 1) * (max_y as i64 - min_y as i64 + 1);
+
+        // A query wider than the occupied cells walks them instead of every cell it covers
+        let cells: Box<dyn Iterator<Item = &Vec<usize>>> = if span > self.cells.len() as i64 {
+            Box::new(
+                self.cells
+                    .iter()
+                    .filter(move |((x, y), _)| {
+                        (min_x..=max_x).contains(x) && (min_y..=max_y).contains(y)
+                    })
+                    .map(|(_, indices)| indices),
+            )
+        } else {
+            Box::new(
+                (min_y..=max_y)
+                    .flat_map(move |y| (min_x..=max_x).map(move |x| (x, y)))
+                    .filter_map(|cell| self.cells.get(&cell)),
+            )
+        };
+        cells.flatten().copied()
+    }
+}
+
+/// Runs `update` for the index of every point, spread across a thread pool by the cell of a
+/// [`SpatialHash`] the point is in with the `rayon` feature, so nearby entities are updated on
+/// the same thread. The results come back in index order whichever thread finished first, so
+/// runs stay deterministic. Without the feature, or in a pool of one thread, they're worked
+/// out in order on this thread.
+pub(crate) fn update_partitioned<T: Send>(
+    cell_size: f32,
+    points: &[Vec2],
+    update: impl Fn(usize) -> T + Sync,
+) -> Vec<T> {
+    #[cfg(feature = "rayon")]
+    if rayon::current_num_threads() > 1 {
+        use rayon::prelude::*;
+
+        let hash = SpatialHash::new(cell_size, points.iter().copied());
+        let cells: Vec<&Vec<usize>> = hash.cells.values().collect();
+        let mut results: Vec<(usize, T)> = cells
+            .par_iter()
+            .flat_map_iter(|indices| indices.iter().map(|index| (*index, update(*index))))
+            .collect();
+        results.sort_unstable_by_key(|(index, _)| *index);
+        return results.into_iter().map(|(_, result)| result).collect();
+    }
+
+    let _ = cell_size;
+    (0..points.len()).map(update).collect()
+}
+
+/// Finds overlapping pairs and returns how far to move each collider to separate the solid
+/// ones, splitting each overlap evenly between the two. Only colliders in neighbouring cells of
+/// a [`SpatialHash`] are compared.
+pub(crate) fn resolve_collisions(colliders: &[Collider], contacts: &mut Vec<Contact>) -> Vec<Vec2> {
+    let mut pushes = vec![Vec2::ZERO; colliders.len()];
+    // Cells as wide as the biggest body, so overlapping bodies are at most a cell apart
+    let max_radius = colliders
+        .iter()
+        .map(|collider| collider.radius)
+        .fold(0.0, f32::max);
+    let hash = SpatialHash::new(
+        max_radius * 2.0,
+        colliders.iter().map(|collider| collider.position),
+    );
+
+    for (i, a) in colliders.iter().enumerate() {
+        let mut nearby: Vec<usize> = hash
+            .query(a.position, a.radius + max_radius)
+            .filter(|j| *j > i)
+            .collect();
+        // In index order, so contacts and pushes come out the same as comparing every pair
+        nearby.sort_unstable();
+        for j in nearby {
+            let b = &colliders[j];
+            if !a.interacts(b) {
+                continue;
+            }
+
+            let offset = b.position - a.position;
+            let overlap = a.radius + b.radius - offset.length();
+            if overlap <= 0.0 {
+                continue;
+            }
+
+            contacts.push(Contact {
+                a: a.body,
+                b: b.body,
+            });
+            if a.sensor || b.sensor {
+                continue;
+            }
+
+            // Bodies exactly on top of each other are split along x
+            let direction = offset.try_normalize().unwrap_or(Vec2::X);
+            pushes[i] -= direction * overlap / 2.0;
+            pushes[j] += direction * overlap / 2.0;
+        }
+    }
+
+    pushes
+}
@@ -0,0 +1,141 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use winit::event_loop::EventLoopProxy;
+use winit::keyboard::KeyCode;
+
+use crate::raycaster::Raycaster;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    UserEvent,
+    engine::{Engine, Frame},
+};
+
+/// Game logic run after every frame on the game thread. See
+/// [`run_on_game_thread`](crate::run_on_game_thread).
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) type GameLoop = Box<dyn FnMut(&mut Engine, &Frame) -> anyhow::Result<()> + Send>;
+
+/// What the event loop tells the game about its windows, handed over directly or through the
+/// game thread's channel.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Input {
+    Key(KeyCode, bool),
+    /// Raw mouse movement, in pixels
+    CursorMove((f64, f64)),
+    Fire(bool),
+    /// The main window's new size, in physical pixels
+    Resize(u32, u32),
+    /// One of the game's windows gained focus
+    Focused,
+    /// The game's windows all lost focus
+    Unfocused {
+        pause: bool,
+        frame_cap: Option<u32>,
+    },
+    /// One of the other windows, by index, and its new size
+    ResizeWindow(usize, u32, u32),
+    CloseWindow(usize),
+}
+
+impl Input {
+    /// Returns whether it asked to quit the game.
+    pub fn apply(self, raycaster: &mut Raycaster) -> bool {
+        match self {
+            Input::Key(code, is_pressed) => return raycaster.handle_key(code, is_pressed),
+            Input::CursorMove(delta) => raycaster.handle_cursor_move(delta),
+            Input::Fire(fire) => raycaster.set_fire(fire),
+            Input::Resize(width, height) => raycaster.renderer().resize(width, height),
+            Input::Focused => {
+                raycaster.set_focus_paused(false);
+                raycaster.renderer().set_background_frame_cap(None);
+            }
+            Input::Unfocused { pause, frame_cap } => {
+                raycaster.release_keys();
+                raycaster.set_focus_paused(pause);
+                raycaster.renderer().set_background_frame_cap(frame_cap);
+            }
+            Input::ResizeWindow(index, width, height) => {
+                raycaster.renderer().resize_window(index, width, height)
+            }
+            Input::CloseWindow(index) => raycaster.renderer().close_window(index),
+        }
+        false
+    }
+}
+
+/// The engine running on its own thread. It renders and presents by itself, only taking input
+/// from the event loop, so slow game logic doesn't hold up the windows.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct GameThread {
+    sender: Sender<Input>,
+    handle: JoinHandle<anyhow::Result<()>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl GameThread {
+    /// Wakes the event loop with [`UserEvent::GameStopped`] when the game quits or fails.
+    pub fn spawn(mut engine: Engine, mut game: GameLoop, proxy: EventLoopProxy<UserEvent>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let handle = thread::Builder::new()
+            .name("game".into())
+            .spawn(move || {
+                let result = run(&mut engine, &mut game, &receiver);
+                let _ = proxy.send_event(UserEvent::GameStopped);
+                result
+            })
+            .expect("failed to start the game thread");
+
+        Self { sender, handle }
+    }
+
+    pub fn send(&self, input: Input) {
+        // A stopped game has already told the event loop
+        let _ = self.sender.send(input);
+    }
+
+    /// Stops the game if it's still running and waits for it. Returns what stopped it.
+    pub fn join(self) -> anyhow::Result<()> {
+        drop(self.sender);
+        self.handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("the game thread panicked"))?
+    }
+}
+
+/// Takes input until the next frame is due, then steps the engine and the game. Stops when
+/// the event loop hangs up or a key quits.
+#[cfg(not(target_arch = "wasm32"))]
+fn run(engine: &mut Engine, game: &mut GameLoop, receiver: &Receiver<Input>) -> anyhow::Result<()> {
+    loop {
+        let next_frame_time = engine.raycaster.renderer().next_frame_time();
+        loop {
+            let input = match next_frame_time {
+                Some(time) => {
+                    match receiver.recv_timeout(time.saturating_duration_since(Instant::now())) {
+                        Ok(input) => input,
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                    }
+                }
+                None => match receiver.try_recv() {
+                    Ok(input) => input,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                },
+            };
+            if input.apply(&mut engine.raycaster) {
+                return Ok(());
+            }
+        }
+
+        engine.raycaster.update()?;
+        let frame = engine.frame();
+        game(engine, &frame)?;
+    }
+}
@@ -0,0 +1,99 @@
+use std::fmt;
+
+use crate::config::PresentMode;
+
+/// What the GPU the engine is rendering with can do, gathered at startup and printed as one
+/// line, for diagnosing rendering that differs between machines. Read it with
+/// [`Engine::device_report`](crate::engine::Engine::device_report).
+///
+/// The limits are the ones in effect, which the engine raises to the adapter's for texture
+/// sizes and array layers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceReport {
+    pub adapter_name: String,
+    /// The graphics API, such as Vulkan or Metal
+    pub backend: String,
+    /// Integrated, discrete, virtual or software
+    pub device_type: String,
+    /// The driver's name and version, where the platform reports them
+    pub driver: String,
+    /// The modes the window's surface can present with, of those the engine offers
+    pub present_modes: Vec<PresentMode>,
+    /// Width and height of the largest 2D texture, in pixels
+    pub max_texture_size: u32,
+    /// The most layers a texture array can have, which caps how many distinct images of each
+    /// kind a map can use
+    pub max_texture_array_layers: u32,
+    pub max_bind_groups: u32,
+    /// Bytes
+    pub max_buffer_size: u64,
+    /// Whether the GPU can time passes, which
+    /// [`gpu_profiling`](crate::config::RendererConfig::gpu_profiling) needs
+    pub timestamp_queries: bool,
+}
+
+impl DeviceReport {
+    pub(crate) fn new(
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        surface_caps: &wgpu::SurfaceCapabilities,
+        timestamp_features: wgpu::Features,
+    ) -> Self {
+        let info = adapter.get_info();
+        let limits = device.limits();
+        let driver = [info.driver, info.driver_info]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        Self {
+            adapter_name: info.name,
+            backend: format!("{:?}", info.backend),
+            device_type: format!("{:?}", info.device_type),
+            driver,
+            present_modes: surface_caps
+                .present_modes
+                .iter()
+                .filter_map(|mode| match mode {
+                    wgpu::PresentMode::Fifo => Some(PresentMode::Fifo),
+                    wgpu::PresentMode::Mailbox => Some(PresentMode::Mailbox),
+                    wgpu::PresentMode::Immediate => Some(PresentMode::Immediate),
+                    _ => None,
+                })
+                .collect(),
+            max_texture_size: limits.max_texture_dimension_2d,
+            max_texture_array_layers: limits.max_texture_array_layers,
+            max_bind_groups: limits.max_bind_groups,
+            max_buffer_size: limits.max_buffer_size,
+            timestamp_queries: adapter.features().contains(timestamp_features),
+        }
+    }
+}
+
+impl fmt::Display for DeviceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "GPU: {} ({}, {}",
+            self.adapter_name, self.device_type, self.backend
+        )?;
+        if !self.driver.is_empty() {
+            write!(f, ", {}", self.driver)?;
+        }
+        write!(
+            f,
+            "); textures up to {}px with {} array layers, {} bind groups, buffers up to {} MB; \
+             present modes {:?}; timestamp queries {}",
+            self.max_texture_size,
+            self.max_texture_array_layers,
+            self.max_bind_groups,
+            self.max_buffer_size / (1024 * 1024),
+            self.present_modes,
+            if self.timestamp_queries {
+                "supported"
+            } else {
+                "unsupported"
+            },
+        )
+    }
+}
@@ -0,0 +1,227 @@
+use crate::stats::{LevelStats, Stat};
+
+/// A rectangle in surface pixels, measured from the top left corner.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A status bar docked to the bottom of the window, like the one in Doom. The 3D view shrinks
+/// to the space above it, so fewer rows are drawn and, with a smaller `view_size`, fewer rays
+/// are cast.
+///
+/// The bar is split left to right into named slots, sized by weight, for the game to draw its
+/// widgets (face, health, ammo and so on) into. Slots can be bound to a [`Stat`] for the game
+/// to draw as text with [`stat_texts`](StatusBar::stat_texts).
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::hud::StatusBar;
+///
+/// let bar = StatusBar::new(0.2)
+///     .with_slot("health", 2.0)
+///     .with_slot("face", 1.0)
+///     .with_slot("ammo", 2.0);
+///
+/// let layout = bar.layout(1000, 500);
+/// assert_eq!(layout.view.height, 400);
+/// assert_eq!(layout.bar.y, 400);
+/// assert_eq!(layout.slot("face").unwrap().x, 400);
+/// assert_eq!(layout.slot("face").unwrap().width, 200);
+/// ```
+#[derive(Clone, Debug)]
+pub struct StatusBar {
+    height: f32,
+    view_size: f32,
+    color: [f32; 3],
+    slots: Vec<StatusBarSlot>,
+}
+
+/// A named part of the [`StatusBar`], `weight` relative to the other slots.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StatusBarSlot {
+    pub name: &'static str,
+    pub weight: f32,
+    /// The figure the slot shows, if it's bound to one
+    pub stat: Option<Stat>,
+}
+
+/// Where the 3D view, the status bar and each of its slots go on a surface of a given size.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HudLayout {
+    pub view: Rect,
+    pub bar: Rect,
+    pub slots: Vec<(&'static str, Rect)>,
+}
+
+impl StatusBar {
+    /// A bar taking up `height` of the window, as a fraction from 0.0 to 0.9.
+    pub fn new(height: f32) -> Self {
+        Self {
+            height: height.clamp(0.0, 0.9),
+            view_size: 1.0,
+            color: [0.25, 0.25, 0.25],
+            slots: Vec::new(),
+        }
+    }
+
+    /// Doom's layout: a bar 16% of the window tall with ammo, health, arms, face, armor and
+    /// keys slots.
+    pub fn classic() -> Self {
+        Self::new(0.16)
+            .with_slot("ammo", 1.5)
+            .with_slot("health", 1.5)
+            .with_slot("arms", 1.0)
+            .with_slot("face", 1.0)
+            .with_slot("armor", 1.5)
+            .with_slot("keys", 0.5)
+    }
+
+    /// How much of the space above the bar the 3D view fills, from 0.1 to 1.0. Smaller views
+    /// are centered, with a border in the bar's color, and cast fewer rays.
+    pub fn with_view_size(mut self, view_size: f32) -> Self {
+        self.view_size = view_size.clamp(0.1, 1.0);
+        self
+    }
+
+    /// Linear RGB color of the bar and the border around a shrunken view.
+    pub fn with_color(mut self, color: [f32; 3]) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Adds a slot to the right of the existing ones.
+    pub fn with_slot(mut self, name: &'static str, weight: f32) -> Self {
+        self.slots.push(StatusBarSlot {
+            name,
+            weight: weight.max(0.0),
+            stat: None,
+        });
+        self
+    }
+
+    /// Adds a slot to the right of the existing ones, showing `stat`.
+    pub fn with_stat_slot(mut self, name: &'static str, weight: f32, stat: Stat) -> Self {
+        self = self.with_slot(name, weight);
+        if let Some(slot) = self.slots.last_mut() {
+            slot.stat = Some(stat);
+        }
+        self
+    }
+
+    pub fn color(&self) -> [f32; 3] {
+        self.color
+    }
+
+    pub fn slots(&self) -> &[StatusBarSlot] {
+        &self.slots
+    }
+
+    pub fn layout(&self, width: u32, height: u32) -> HudLayout {
+        let bar_height = ((height as f32 * self.height).round() as u32).min(height);
+        let above = height - bar_height;
+
+        let view_width = ((width as f32 * self.view_size).round() as u32).clamp(1, width.max(1));
+        let view_height = ((above as f32 * self.view_size).round() as u32).clamp(1, above.max(1));
+        let view = Rect {
+            x: (width - view_width.min(width)) / 2,
+            y: (above - view_height.min(above)) / 2,
+            width: view_width,
+            height: view_height,
+        };
+
+        let bar = Rect {
+            x: 0,
+            y: above,
+            width,
+            height: bar_height,
+        };
+
+        let total_weight: f32 = self.slots.iter().map(|slot| slot.weight).sum();
+        let mut start_weight = 0.0;
+        let slots = self
+            .slots
+            .iter()
+            .map(|slot| {
+                let edge = |weight: f32| {
+                    if total_weight > 0.0 {
+                        (width as f32 * weight / total_weight).round() as u32
+                    } else {
+                        0
+                    }
+                };
+                let x = edge(start_weight);
+                start_weight += slot.weight;
+                let rect = Rect {
+                    x,
+                    y: bar.y,
+                    width: edge(start_weight) - x,
+                    height: bar_height,
+                };
+                (slot.name, rect)
+            })
+            .collect();
+
+        HudLayout { view, bar, slots }
+    }
+
+    /// The text of each slot bound to a stat, and where it goes in `layout`.
+    ///
+    /// ```
+    /// use raycasting_engine::{hud::StatusBar, stats::{LevelStats, Stat}};
+    ///
+    /// let bar = StatusBar::new(0.2)
+    ///     .with_slot("face", 1.0)
+    ///     .with_stat_slot("time", 1.0, Stat::Time);
+    /// let layout = bar.layout(1000, 500);
+    ///
+    /// let texts = bar.stat_texts(&layout, &LevelStats::default());
+    /// assert_eq!(texts, vec![(layout.slot("time").unwrap(), "0:00".to_string())]);
+    /// ```
+    pub fn stat_texts(&self, layout: &HudLayout, stats: &LevelStats) -> Vec<(Rect, String)> {
+        self.slots
+            .iter()
+            .zip(&layout.slots)
+            .filter_map(|(slot, (_, rect))| Some((*rect, stats.display(slot.stat?))))
+            .collect()
+    }
+}
+
+impl HudLayout {
+    /// The rectangle of the slot with the given name.
+    pub fn slot(&self, name: &str) -> Option<Rect> {
+        self.slots
+            .iter()
+            .find(|(slot_name, _)| *slot_name == name)
+            .map(|(_, rect)| *rect)
+    }
+
+    /// The slot with the given name split left to right into `count` cells of equal width,
+    /// such as one per [`Inventory`](crate::inventory::Inventory) stack to draw its icon in.
+    ///
+    /// ```
+    /// use raycasting_engine::hud::StatusBar;
+    ///
+    /// let layout = StatusBar::new(0.2).with_slot("items", 1.0).layout(1000, 500);
+    /// let cells = layout.cells("items", 4).unwrap();
+    /// assert_eq!(cells[1].x, 250);
+    /// assert_eq!(cells[1].width, 250);
+    /// ```
+    pub fn cells(&self, name: &str, count: u32) -> Option<Vec<Rect>> {
+        let slot = self.slot(name)?;
+        let edge = |index: u32| (slot.width as f32 * index as f32 / count as f32).round() as u32;
+        Some(
+            (0..count)
+                .map(|index| Rect {
+                    x: slot.x + edge(index),
+                    width: edge(index + 1) - edge(index),
+                    ..slot
+                })
+                .collect(),
+        )
+    }
+}
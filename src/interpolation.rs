@@ -0,0 +1,108 @@
+use std::{collections::VecDeque, time::Duration};
+
+use glam::Vec2;
+
+/// How many positions an [`Interpolator`] keeps. Older ones are dropped.
+const MAX_SAMPLES: usize = 32;
+
+/// Smooths the movement of something another machine simulates, like another player or an
+/// enemy on a server, from the positions it sends. Positions are drawn `delay` behind the
+/// time asked for, so there's usually a later one to blend towards even when they arrive
+/// late or unevenly. Once past the newest, it keeps moving at its last speed for up to
+/// [`with_max_extrapolation`](Self::with_max_extrapolation), then stops until more arrive.
+///
+/// Times are on the sender's clock, such as how long its game has been running.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use glam::vec2;
+/// use raycasting_engine::interpolation::Interpolator;
+///
+/// let ms = Duration::from_millis;
+/// let mut remote = Interpolator::new(ms(100));
+/// remote.push(ms(0), vec2(0.0, 0.0));
+/// remote.push(ms(100), vec2(10.0, 0.0));
+///
+/// // Half way between the two
+/// assert_eq!(remote.position(ms(150)), Some(vec2(5.0, 0.0)));
+/// // Carried on past the newest at the same speed
+/// assert_eq!(remote.position(ms(250)), Some(vec2(15.0, 0.0)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Interpolator {
+    /// Oldest first
+    samples: VecDeque<(Duration, Vec2)>,
+    delay: Duration,
+    max_extrapolation: Duration,
+}
+
+impl Interpolator {
+    /// Extrapolates for up to 250 ms.
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            samples: VecDeque::new(),
+            delay,
+            max_extrapolation: Duration::from_millis(250),
+        }
+    }
+
+    /// How long it keeps moving past the newest position. Zero holds it there.
+    pub fn with_max_extrapolation(mut self, max_extrapolation: Duration) -> Self {
+        self.max_extrapolation = max_extrapolation;
+        self
+    }
+
+    /// Adds where it was at `time`. Positions from before the newest arrived out of order and
+    /// are ignored.
+    pub fn push(&mut self, time: Duration, position: Vec2) {
+        if self
+            .samples
+            .back()
+            .is_some_and(|(newest, _)| time <= *newest)
+        {
+            return;
+        }
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((time, position));
+    }
+
+    /// Forgets every position, as when it teleports or respawns, so it isn't seen sliding
+    /// across the map.
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Where to draw it at `now`. `None` until a position has arrived.
+    pub fn position(&self, now: Duration) -> Option<Vec2> {
+        let time = now.saturating_sub(self.delay);
+        let &(first_time, first) = self.samples.front()?;
+        if time <= first_time {
+            return Some(first);
+        }
+
+        let after = self.samples.iter().position(|(t, _)| *t > time);
+        let Some(after) = after else {
+            return Some(self.extrapolate(time));
+        };
+        let (from_time, from) = self.samples[after - 1];
+        let (to_time, to) = self.samples[after];
+        let t = (time - from_time).as_secs_f32() / (to_time - from_time).as_secs_f32();
+        Some(from.lerp(to, t))
+    }
+
+    /// Carries on from the newest position at the speed it was last moving.
+    fn extrapolate(&self, time: Duration) -> Vec2 {
+        let (newest_time, newest) = self.samples[self.samples.len() - 1];
+        let Some(&(previous_time, previous)) = self.samples.iter().rev().nth(1) else {
+            return newest;
+        };
+        let velocity = (newest - previous) / (newest_time - previous_time).as_secs_f32();
+        let ahead = (time - newest_time).min(self.max_extrapolation);
+        newest + velocity * ahead.as_secs_f32()
+    }
+}
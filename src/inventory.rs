@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+/// What a kind of item is: the icon the HUD shows for it, how many fit in one inventory slot
+/// and tags games look items up by, like `"key"` or `"ammo"`.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::inventory::{ItemDef, Items};
+///
+/// let shells = ItemDef::new()
+///     .with_icon("icons/shells.png")
+///     .with_stack_size(50)
+///     .with_tag("ammo");
+/// let red_key = ItemDef::new().with_icon("icons/red_key.png").with_tag("key");
+///
+/// let items = Items::new()
+///     .with_item("shells", shells)
+///     .with_item("red_key", red_key);
+/// assert!(items.has_tag("red_key", "key"));
+/// assert_eq!(items.get("shells").unwrap().stack_size(), 50);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ItemDef {
+    icon: Option<&'static str>,
+    stack_size: u32,
+    tags: Vec<String>,
+}
+
+impl Default for ItemDef {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ItemDef {
+    /// An item without an icon or tags that doesn't stack.
+    pub fn new() -> Self {
+        Self {
+            icon: None,
+            stack_size: 1,
+            tags: Vec::new(),
+        }
+    }
+
+    /// An image for the game to draw the item with in its HUD.
+    pub fn with_icon(mut self, icon: &'static str) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    /// How many of the item fit in one slot, at least 1.
+    pub fn with_stack_size(mut self, stack_size: u32) -> Self {
+        self.stack_size = stack_size.max(1);
+        self
+    }
+
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn icon(&self) -> Option<&'static str> {
+        self.icon
+    }
+
+    pub fn stack_size(&self) -> u32 {
+        self.stack_size
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags.iter().map(String::as_str)
+    }
+}
+
+/// The items games can put in an [`Inventory`], by name. Pickups of items that aren't
+/// registered are left where they are. See [`EngineConfig`](crate::config::EngineConfig).
+#[derive(Clone, Debug, Default)]
+pub struct Items {
+    defs: HashMap<String, ItemDef>,
+}
+
+impl Items {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_item(mut self, name: impl Into<String>, def: ItemDef) -> Self {
+        self.register(name, def);
+        self
+    }
+
+    pub(crate) fn register(&mut self, name: impl Into<String>, def: ItemDef) {
+        self.defs.insert(name.into(), def);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ItemDef> {
+        self.defs.get(name)
+    }
+
+    /// Whether the item is registered with the tag.
+    pub fn has_tag(&self, name: &str, tag: &str) -> bool {
+        self.get(name)
+            .is_some_and(|def| def.tags().any(|other| other == tag))
+    }
+}
+
+/// Some number of one item, like a slot of an [`Inventory`] or what a pickup gives.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ItemStack {
+    pub item: String,
+    pub count: u32,
+}
+
+impl ItemStack {
+    pub fn new(item: impl Into<String>, count: u32) -> Self {
+        Self {
+            item: item.into(),
+            count,
+        }
+    }
+}
+
+/// The player's items, in slots that each hold a stack of one item up to its
+/// [`stack_size`](ItemDef::stack_size). Touching an entity made with
+/// [`Entity::with_pickup`](crate::entity::Entity::with_pickup) adds to it.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::inventory::{Inventory, ItemDef, Items};
+///
+/// let items = Items::new()
+///     .with_item("shells", ItemDef::new().with_stack_size(50).with_tag("ammo"))
+///     .with_item("red_key", ItemDef::new().with_tag("key"));
+///
+/// let mut inventory = Inventory::new().with_slots(2);
+/// assert_eq!(inventory.add(&items, "shells", 80), 80);
+/// // Both slots are taken by shells
+/// assert_eq!(inventory.add(&items, "red_key", 1), 0);
+/// assert_eq!(inventory.add(&items, "shells", 40), 20);
+///
+/// assert_eq!(inventory.remove("shells", 30), 30);
+/// assert_eq!(inventory.count("shells"), 70);
+/// assert_eq!(inventory.count_tagged(&items, "ammo"), 70);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Inventory {
+    stacks: Vec<ItemStack>,
+    slots: Option<usize>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inventory {
+    /// An empty inventory with as many slots as it needs.
+    pub fn new() -> Self {
+        Self {
+            stacks: Vec::new(),
+            slots: None,
+        }
+    }
+
+    /// Limits the inventory to this many slots.
+    pub fn with_slots(mut self, slots: usize) -> Self {
+        self.slots = Some(slots);
+        self
+    }
+
+    /// Adds up to `count` of a registered item, topping up its stacks before taking new slots.
+    /// Returns how many fit, which is 0 if the item isn't registered.
+    pub fn add(&mut self, items: &Items, item: &str, count: u32) -> u32 {
+        let Some(def) = items.get(item) else {
+            return 0;
+        };
+
+        let mut left = count;
+        for stack in self.stacks.iter_mut().filter(|stack| stack.item == item) {
+            let added = left.min(def.stack_size.saturating_sub(stack.count));
+            stack.count += added;
+            left -= added;
+        }
+        while left > 0 && self.slots.is_none_or(|slots| self.stacks.len() < slots) {
+            let added = left.min(def.stack_size);
+            self.stacks.push(ItemStack::new(item, added));
+            left -= added;
+        }
+
+        count - left
+    }
+
+    /// Takes up to `count` of an item, from its last stacks first. Returns how many were taken.
+    pub fn remove(&mut self, item: &str, count: u32) -> u32 {
+        let mut left = count;
+        for stack in self
+            .stacks
+            .iter_mut()
+            .rev()
+            .filter(|stack| stack.item == item)
+        {
+            let removed = left.min(stack.count);
+            stack.count -= removed;
+            left -= removed;
+        }
+        self.stacks.retain(|stack| stack.count > 0);
+
+        count - left
+    }
+
+    /// How many of an item are held, across all its stacks.
+    pub fn count(&self, item: &str) -> u32 {
+        self.stacks
+            .iter()
+            .filter(|stack| stack.item == item)
+            .map(|stack| stack.count)
+            .sum()
+    }
+
+    /// How many items with the tag are held, like the keys or all the ammo.
+    pub fn count_tagged(&self, items: &Items, tag: &str) -> u32 {
+        self.stacks
+            .iter()
+            .filter(|stack| items.has_tag(&stack.item, tag))
+            .map(|stack| stack.count)
+            .sum()
+    }
+
+    /// The filled slots, in the order they were filled, for drawing into a
+    /// [`StatusBar`](crate::hud::StatusBar) slot with
+    /// [`HudLayout::cells`](crate::hud::HudLayout::cells).
+    pub fn stacks(&self) -> &[ItemStack] {
+        &self.stacks
+    }
+
+    /// The most slots the inventory can have, or `None` if it has no limit.
+    pub fn slots(&self) -> Option<usize> {
+        self.slots
+    }
+
+    pub fn clear(&mut self) {
+        self.stacks.clear();
+    }
+}
@@ -1,86 +1,366 @@
+pub mod ai;
+pub mod assets;
+pub mod audio;
+pub mod camera;
+pub mod config;
+#[cfg(debug_assertions)]
+pub mod debug;
+pub mod display;
+pub mod engine;
+pub mod entity;
+mod game_thread;
+pub mod gpu;
+pub mod hud;
+pub mod interpolation;
+pub mod inventory;
 pub mod map;
+pub mod menu;
+/// The types most games need, and the vector type the engine's positions are in, for glob
+/// importing. Everything is also reachable through its own module.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::prelude::*;
+///
+/// let config = EngineConfig {
+///     start_map: Some("Map1".into()),
+///     ..Default::default()
+/// };
+/// let sprite = Sprite::new(vec2(96.0, 96.0), "sprites/barrel.png");
+/// ```
+pub mod prelude;
+pub mod profiling;
 mod raycaster;
 mod renderer;
+pub mod rng;
+pub mod sequence;
+pub mod sprite;
+pub mod stats;
+pub mod text;
+pub mod wad;
+pub mod weapon;
 
 use std::{mem::take, sync::Arc};
 
-use anyhow::Ok;
+use anyhow::{Context, Ok};
 use winit::{
     application::ApplicationHandler,
-    event::{DeviceEvent, KeyEvent, WindowEvent},
-    event_loop::EventLoop,
+    event::{DeviceEvent, ElementState, KeyEvent, MouseButton, StartCause, WindowEvent},
+    event_loop::{ControlFlow, EventLoop, EventLoopProxy},
     keyboard::PhysicalKey,
-    window::{CursorIcon, Fullscreen, Window},
+    window::{CursorGrabMode, Window, WindowId},
 };
 
 use crate::{
-    map::{Map, Maps},
-    raycaster::Raycaster,
+    assets::AssetSource,
+    config::{EngineConfig, WindowContent},
+    engine::Engine,
+    game_thread::Input,
+    map::Maps,
     renderer::Renderer,
 };
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    engine::Frame,
+    game_thread::{GameLoop, GameThread},
+};
 
 struct State {
     window: Arc<Window>,
-    raycaster: Raycaster,
+    /// Windows opened alongside the main one, by their index in the renderer. Closed ones are
+    /// left empty.
+    windows: Vec<Option<Arc<Window>>>,
+    game: Game,
+}
+
+/// Where the engine runs.
+enum Game {
+    /// On the event loop's thread, stepped when the main window is redrawn
+    Local(Box<Engine>),
+    #[cfg(not(target_arch = "wasm32"))]
+    Thread(GameThread),
+}
+
+/// Sent to the event loop from other threads and tasks.
+enum UserEvent {
+    /// The state, created asynchronously on the web
+    #[cfg_attr(not(target_arch = "wasm32"), allow(unused))]
+    Ready(Box<State>),
+    /// The game thread quit or failed
+    #[cfg(not(target_arch = "wasm32"))]
+    GameStopped,
 }
 
 impl State {
-    fn new(
+    async fn new(
         window: Arc<Window>,
+        windows: Vec<(Arc<Window>, WindowContent)>,
         maps: Arc<Maps>,
         current_map_key: &'static str,
+        assets: Arc<dyn AssetSource>,
+        config: EngineConfig,
     ) -> anyhow::Result<Self> {
         let map = maps.get(current_map_key).unwrap();
-        let renderer = pollster::block_on(Renderer::new(&window, map))?;
-        let raycaster = Raycaster::new(renderer, maps.clone(), current_map_key)?;
+        let renderer = Renderer::new(
+            &window,
+            current_map_key,
+            map,
+            assets,
+            &config.renderer_with_preloads(),
+        )
+        .await?;
+        println!("{}", renderer.device_report());
+        println!("Surface format: {:?}", renderer.surface_format());
+        let mut engine = Engine::from_renderer(renderer, maps, current_map_key, &config)?;
+        for (window, content) in &windows {
+            let size = window.inner_size();
+            engine
+                .raycaster
+                .add_window(window.clone(), size.width, size.height, *content)?;
+        }
 
-        Ok(Self { window, raycaster })
+        Ok(Self {
+            window,
+            windows: windows
+                .into_iter()
+                .map(|(window, _)| Some(window))
+                .collect(),
+            game: Game::Local(Box::new(engine)),
+        })
+    }
+
+    /// Moves the engine onto its own thread, which runs `game` after every frame.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn spawn_game_thread(self, game: GameLoop, proxy: EventLoopProxy<UserEvent>) -> Self {
+        let Game::Local(engine) = self.game else {
+            return self;
+        };
+        Self {
+            game: Game::Thread(GameThread::spawn(*engine, game, proxy)),
+            ..self
+        }
+    }
+
+    /// The engine, unless it's running on its own thread.
+    fn engine(&mut self) -> Option<&mut Engine> {
+        match &mut self.game {
+            Game::Local(engine) => Some(engine),
+            #[cfg(not(target_arch = "wasm32"))]
+            Game::Thread(_) => None,
+        }
+    }
+
+    /// Hands input to the game, wherever it's running, and exits if it asks to quit.
+    fn send(&mut self, event_loop: &winit::event_loop::ActiveEventLoop, input: Input) {
+        match &mut self.game {
+            Game::Local(engine) => {
+                if input.apply(&mut engine.raycaster) {
+                    event_loop.exit();
+                }
+            }
+            // The thread tells the event loop itself when it quits
+            #[cfg(not(target_arch = "wasm32"))]
+            Game::Thread(thread) => thread.send(input),
+        }
+    }
+
+    /// The index of one of the other windows, or `None` for the main one.
+    fn window_index(&self, window_id: WindowId) -> Option<usize> {
+        self.windows.iter().position(|window| {
+            window
+                .as_ref()
+                .is_some_and(|window| window.id() == window_id)
+        })
+    }
+
+    /// Handles an event for one of the other windows. Keys still reach the game.
+    fn secondary_window_event(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        index: usize,
+        event: WindowEvent,
+    ) {
+        match event {
+            WindowEvent::CloseRequested => {
+                self.send(event_loop, Input::CloseWindow(index));
+                self.windows[index] = None;
+            }
+            WindowEvent::Resized(size) => self.send(
+                event_loop,
+                Input::ResizeWindow(index, size.width, size.height),
+            ),
+            // Moving between the game's own windows doesn't pause it
+            WindowEvent::Focused(true) => self.send(event_loop, Input::Focused),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code),
+                        state: key_state,
+                        ..
+                    },
+                ..
+            } => self.send(event_loop, Input::Key(code, key_state.is_pressed())),
+            _ => (),
+        }
     }
 }
 
 struct App {
+    /// Hands the asynchronously created state back to the event loop on the web, where
+    /// initialization can't block, and lets the game thread stop it.
+    proxy: Option<EventLoopProxy<UserEvent>>,
     state: Option<State>,
-    width: u32,
-    height: u32,
+    config: EngineConfig,
     maps: Arc<Maps>,
     current_map_key: &'static str,
+    /// Runs the engine on its own thread with this game logic
+    #[cfg(not(target_arch = "wasm32"))]
+    game: Option<GameLoop>,
 }
 
 impl App {
-    fn new(width: u32, height: u32, maps: Arc<Maps>, current_map_key: &'static str) -> Self {
+    fn new(
+        config: EngineConfig,
+        maps: Arc<Maps>,
+        current_map_key: &'static str,
+        event_loop: &EventLoop<UserEvent>,
+    ) -> Self {
         Self {
+            proxy: Some(event_loop.create_proxy()),
             state: None,
-            width,
-            height,
+            config,
             maps,
             current_map_key,
+            #[cfg(not(target_arch = "wasm32"))]
+            game: None,
         }
     }
 }
 
-impl ApplicationHandler for App {
+impl ApplicationHandler<UserEvent> for App {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
-        let window_attributes = Window::default_attributes()
-            .with_inner_size(winit::dpi::LogicalSize::new(self.width, self.height))
+        #[allow(unused_mut)]
+        let mut window_attributes = Window::default_attributes()
+            .with_inner_size(winit::dpi::LogicalSize::new(
+                self.config.window_width,
+                self.config.window_height,
+            ))
             .with_resizable(false)
-            // .with_fullscreen(Some(Fullscreen::Borderless(None)));
-            .with_fullscreen(None);
+            .with_fullscreen(display::window_fullscreen(
+                &self.config.fullscreen,
+                event_loop,
+            ));
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            use winit::platform::web::WindowAttributesExtWebSys;
+            window_attributes = window_attributes.with_append(true);
+        }
 
         let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
+        let windows: Vec<_> = self
+            .config
+            .windows
+            .iter()
+            .map(|secondary| {
+                #[allow(unused_mut)]
+                let mut attributes = Window::default_attributes()
+                    .with_title(secondary.title.clone())
+                    .with_inner_size(winit::dpi::LogicalSize::new(
+                        secondary.width,
+                        secondary.height,
+                    ));
+                #[cfg(target_arch = "wasm32")]
+                {
+                    use winit::platform::web::WindowAttributesExtWebSys;
+                    attributes = attributes.with_append(true);
+                }
+                let window = Arc::new(event_loop.create_window(attributes).unwrap());
+                (window, secondary.content)
+            })
+            .collect();
 
         // lock cursor
-        window
-            .set_cursor_grab(winit::window::CursorGrabMode::Locked)
-            .unwrap();
+        #[cfg(not(target_arch = "wasm32"))]
+        window.set_cursor_grab(CursorGrabMode::Locked).unwrap();
+        // Browsers only grant pointer lock after a click, which is retried in `window_event`
+        #[cfg(target_arch = "wasm32")]
+        let _ = window.set_cursor_grab(CursorGrabMode::Locked);
         window.set_cursor_visible(false);
 
-        self.state = Some(State::new(window, take(&mut self.maps), self.current_map_key).unwrap());
+        let maps = take(&mut self.maps);
+        let assets = self.config.assets.clone();
+        let config = self.config.clone();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut state = pollster::block_on(State::new(
+                window,
+                windows,
+                maps,
+                self.current_map_key,
+                assets,
+                config,
+            ))
+            .unwrap();
+            if let (Some(game), Some(proxy)) = (self.game.take(), self.proxy.clone()) {
+                state = state.spawn_game_thread(game, proxy);
+            }
+            self.state = Some(state);
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        if let Some(proxy) = self.proxy.take() {
+            let current_map_key = self.current_map_key;
+            wasm_bindgen_futures::spawn_local(async move {
+                let state = State::new(window, windows, maps, current_map_key, assets, config)
+                    .await
+                    .expect("Failed to initialize the engine");
+                assert!(proxy.send_event(UserEvent::Ready(Box::new(state))).is_ok());
+            });
+        }
+    }
+
+    fn user_event(&mut self, event_loop: &winit::event_loop::ActiveEventLoop, event: UserEvent) {
+        let mut state = match event {
+            UserEvent::Ready(state) => *state,
+            #[cfg(not(target_arch = "wasm32"))]
+            UserEvent::GameStopped => {
+                event_loop.exit();
+                return;
+            }
+        };
+        // The initial resize has usually been missed while the state was being created
+        let size = state.window.inner_size();
+        state.send(event_loop, Input::Resize(size.width, size.height));
+        state.window.request_redraw();
+
+        self.state = Some(state);
+    }
+
+    fn new_events(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop, cause: StartCause) {
+        if let (StartCause::ResumeTimeReached { .. }, Some(state)) = (cause, &self.state) {
+            state.window.request_redraw();
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        let next_frame_time = self
+            .state
+            .as_mut()
+            .and_then(State::engine)
+            .and_then(|engine| engine.raycaster.renderer().next_frame_time());
+
+        if let Some(next_frame_time) = next_frame_time {
+            event_loop.set_control_flow(ControlFlow::WaitUntil(next_frame_time));
+        }
     }
 
     fn device_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
-        device_id: winit::event::DeviceId,
+        _device_id: winit::event::DeviceId,
         event: DeviceEvent,
     ) {
         let state = match &mut self.state {
@@ -88,24 +368,28 @@ impl ApplicationHandler for App {
             None => return,
         };
 
-        match event {
-            DeviceEvent::MouseMotion { delta } => {
-                state.raycaster.handle_cursor_move(delta);
-            }
-            _ => (),
+        // Raw mouse motion still arrives while another window has focus
+        if let DeviceEvent::MouseMotion { delta } = event
+            && state.window.has_focus()
+        {
+            state.send(event_loop, Input::CursorMove(delta));
         }
     }
 
     fn window_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
-        _window_id: winit::window::WindowId,
+        window_id: WindowId,
         event: winit::event::WindowEvent,
     ) {
         let state = match &mut self.state {
             Some(canvas) => canvas,
             None => return,
         };
+        if let Some(index) = state.window_index(window_id) {
+            state.secondary_window_event(event_loop, index, event);
+            return;
+        }
 
         match event {
             WindowEvent::CloseRequested => {
@@ -113,10 +397,46 @@ impl ApplicationHandler for App {
                 event_loop.exit();
             }
             WindowEvent::RedrawRequested => {
-                state.raycaster.update().unwrap();
+                // The game thread draws frames by itself
+                let Some(engine) = state.engine() else {
+                    return;
+                };
+                engine.raycaster.update().unwrap();
+                // With a frame cap the event loop wakes up for the next frame instead
+                if engine.raycaster.renderer().next_frame_time().is_none() {
+                    state.window.request_redraw();
+                }
             }
             WindowEvent::Resized(size) => {
-                state.raycaster.renderer().resize(size.width, size.height)
+                state.send(event_loop, Input::Resize(size.width, size.height))
+            }
+            WindowEvent::Focused(focused) => {
+                if focused {
+                    let _ = state.window.set_cursor_grab(CursorGrabMode::Locked);
+                    state.window.set_cursor_visible(false);
+                    state.send(event_loop, Input::Focused);
+                } else {
+                    let _ = state.window.set_cursor_grab(CursorGrabMode::None);
+                    state.window.set_cursor_visible(true);
+                    state.send(
+                        event_loop,
+                        Input::Unfocused {
+                            pause: self.config.pause_when_unfocused,
+                            frame_cap: self.config.background_frame_cap,
+                        },
+                    );
+                }
+                state.window.request_redraw();
+            }
+            WindowEvent::MouseInput {
+                state: button_state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                if button_state == ElementState::Pressed {
+                    let _ = state.window.set_cursor_grab(CursorGrabMode::Locked);
+                }
+                state.send(event_loop, Input::Fire(button_state.is_pressed()));
             }
             WindowEvent::KeyboardInput {
                 event:
@@ -126,9 +446,7 @@ impl ApplicationHandler for App {
                         ..
                     },
                 ..
-            } => state
-                .raycaster
-                .handle_key(event_loop, code, key_state.is_pressed()),
+            } => state.send(event_loop, Input::Key(code, key_state.is_pressed())),
             _ => (),
         }
     }
@@ -140,10 +458,95 @@ pub fn run(
     maps: Maps,
     current_map_key: &'static str,
 ) -> anyhow::Result<()> {
+    let config = EngineConfig {
+        window_width,
+        window_height,
+        ..Default::default()
+    };
+    run_with_config(config, maps, current_map_key)
+}
+
+pub fn run_with_config(
+    config: EngineConfig,
+    maps: Maps,
+    current_map_key: &'static str,
+) -> anyhow::Result<()> {
+    let current_map_key = start_map_key(&config, &maps, current_map_key)?;
+
     let maps = Arc::new(maps);
-    let event_loop = EventLoop::new().unwrap();
-    let mut app = App::new(window_width, window_height, maps, current_map_key);
+    let event_loop = EventLoop::with_user_event().build()?;
+    #[allow(unused_mut)]
+    let mut app = App::new(config, maps, current_map_key, &event_loop);
+
+    #[cfg(not(target_arch = "wasm32"))]
     event_loop.run_app(&mut app)?;
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::platform::web::EventLoopExtWebSys;
+        event_loop.spawn_app(app);
+    }
 
     Ok(())
 }
+
+/// Like [`run_with_config`], but with the engine on a thread of its own, which calls `game`
+/// with it after every frame. The event loop's thread only forwards input to it over a
+/// channel, so heavy game logic doesn't make the window unresponsive. Returns when the window
+/// is closed or the game quits, with the error `game` or a frame failed with, if any.
+///
+/// Not available on the web, which has no threads.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn example(maps: raycasting_engine::map::Maps) -> anyhow::Result<()> {
+/// use raycasting_engine::config::EngineConfig;
+///
+/// raycasting_engine::run_on_game_thread(EngineConfig::default(), maps, "Map1", |engine, frame| {
+///     if frame.timing.frame_index % 600 == 0 {
+///         engine.set_time_of_day(engine.time_of_day() + 0.1);
+///     }
+///     Ok(())
+/// })
+/// # }
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run_on_game_thread(
+    config: EngineConfig,
+    maps: Maps,
+    current_map_key: &'static str,
+    game: impl FnMut(&mut Engine, &Frame) -> anyhow::Result<()> + Send + 'static,
+) -> anyhow::Result<()> {
+    let current_map_key = start_map_key(&config, &maps, current_map_key)?;
+
+    let maps = Arc::new(maps);
+    let event_loop = EventLoop::with_user_event().build()?;
+    let mut app = App::new(config, maps, current_map_key, &event_loop);
+    app.game = Some(Box::new(game));
+
+    event_loop.run_app(&mut app)?;
+
+    match app.state.map(|state| state.game) {
+        Some(Game::Thread(thread)) => thread.join(),
+        _ => Ok(()),
+    }
+}
+
+/// The map to start on: the config's `start_map` if set, otherwise `current_map_key`.
+pub(crate) fn start_map_key(
+    config: &EngineConfig,
+    maps: &Maps,
+    current_map_key: &'static str,
+) -> anyhow::Result<&'static str> {
+    match &config.start_map {
+        Some(name) => Ok(*maps
+            .keys()
+            .find(|key| **key == name.as_str())
+            .with_context(|| {
+                let mut keys: Vec<_> = maps.keys().collect();
+                keys.sort();
+                format!("no map named {name}; maps are {keys:?}")
+            })?),
+        None => Ok(current_map_key),
+    }
+}
@@ -1,6 +1,10 @@
+use glam::vec2;
 use raycasting_engine::{
-    map::{Map, Maps, TileData, TileType, TileTypes},
-    run,
+    camera::Camera,
+    config::EngineConfig,
+    map::{Map, Maps, MonitorData, TileData, TileType, TileTypes},
+    run_with_config,
+    text::TextLabel,
 };
 
 fn main() {
@@ -34,10 +38,28 @@ fn main() {
     let tile_types = TileTypes::from([
         (0, TileType::Wall(TileData::new("walls/wall1.png"))),
         (3, TileType::Wall(TileData::new("walls/wall3.png"))),
+        (
+            1,
+            TileType::Monitor(MonitorData::new(Camera::new(vec2(128.0, 128.0), 0.0))),
+        ),
+        (4, TileType::Empty),
+        (6, TileType::Empty),
+        (7, TileType::Empty),
+        (8, TileType::Empty),
+        (9, TileType::Empty),
     ]);
 
-    let map1 = Map::new(layout, tile_types);
+    let map1 = Map::new(layout, tile_types).with_labels(vec![
+        TextLabel::new(vec2(416.0, 128.0), "Welcome").with_z_offset(24.0),
+    ]);
     let maps = Maps::from([("Map1", map1)]);
 
-    run(1280, 720, maps, "Map1").unwrap();
+    let config = EngineConfig {
+        window_width: 1280,
+        window_height: 720,
+        ..Default::default()
+    }
+    .with_args(std::env::args().skip(1))
+    .unwrap();
+    run_with_config(config, maps, "Map1").unwrap();
 }
@@ -0,0 +1,1200 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use glam::{Vec2, vec2};
+
+use crate::{
+    ai::Ai,
+    audio::{AudioBus, ReverbPreset, ReverbZone, SoundEmitter},
+    camera::Camera,
+    entity::{CollisionLayers, Entity, EntityDef, Faction, SpawnTrigger, Spawner},
+    inventory::ItemStack,
+    map::{
+        Checkpoint, CloudLayer, DayNight, DoorData, ElevatorData, Exploration, Fog, HazardData,
+        Linedef, Map, MapChange, MapDiff, MapMetadata, Migrations, MonitorData, PatrolPath,
+        Precipitation, Region, ReliefLight, Secret, SlopeData, SlopeDirection, SurfaceData,
+        ThinWallAxis, ThinWallData, TileData, TileDataFC, TileType, TileTypes, WaterData, Weather,
+    },
+    sequence::{Lightning, Sequence, SequenceTrigger, Step},
+    sprite::Sprite,
+    text::TextLabel,
+};
+
+const MAGIC: &[u8; 4] = b"RCMP";
+const DIFF_MAGIC: &[u8; 4] = b"RCDF";
+const EXPLORATION_MAGIC: &[u8; 4] = b"RCEX";
+/// Shared by maps and diffs, which encode metadata and entities the same way.
+const VERSION: u16 = 23;
+/// The oldest version still read, so saves made before an update keep loading. When a version
+/// adds a field, leave this where it was and read the field only when
+/// [`Reader::version`] is at least that version, giving older files its default.
+pub(super) const OLDEST_VERSION: u16 = 21;
+
+impl Map {
+    /// Encodes the map in a compact binary format: a header, a table of the paths it uses,
+    /// the tile grid run-length encoded, then its tile types, metadata and everything placed in
+    /// it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use raycasting_engine::map::{Map, TileData, TileType, TileTypes};
+    ///
+    /// let tile_types = TileTypes::from([(0, TileType::Wall(TileData::new("wall.png")))]);
+    /// let map = Map::new(vec![vec![0; 64]; 64], tile_types);
+    ///
+    /// let bytes = map.to_bytes();
+    /// assert!(bytes.len() < 64 * 64);
+    /// assert_eq!(Map::from_bytes(&bytes).unwrap().size().cols(), 64);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.body().finish(MAGIC)
+    }
+
+    /// Every path the map refers to, once each, like the path table it's encoded with.
+    pub(crate) fn paths(&self) -> Vec<&'static str> {
+        self.body().paths
+    }
+
+    fn body(&self) -> Writer {
+        let mut body = Writer::default();
+        body.grid(&self.tiles);
+
+        let mut tile_ids: Vec<&u8> = self.tile_types.keys().collect();
+        tile_ids.sort_unstable();
+        body.u32(tile_ids.len() as u32);
+        for id in tile_ids {
+            body.u8(*id);
+            body.tile_type(&self.tile_types[id]);
+        }
+
+        body.metadata(&self.metadata);
+        body.list(&self.linedefs, |w, linedef| {
+            w.vec2(linedef.start);
+            w.vec2(linedef.end);
+            w.path(linedef.texture_path);
+        });
+        body.list(&self.sprites, |w, sprite| {
+            w.vec2(sprite.position);
+            w.path(sprite.texture_path);
+            w.f32(sprite.opacity);
+            w.bool(sprite.flat);
+            w.f32(sprite.z_offset);
+            w.f32(sprite.scale);
+        });
+        body.list(&self.labels, |w, label| {
+            w.vec2(label.position);
+            w.string(&label.text);
+            w.f32(label.z_offset);
+            w.f32(label.height);
+            label.color.iter().for_each(|c| w.f32(*c));
+        });
+        body.list(&self.sound_emitters, |w, emitter| {
+            w.vec2(emitter.position);
+            w.path(emitter.sound_path);
+            w.bool(emitter.radius.is_some());
+            w.f32(emitter.radius.unwrap_or(0.0));
+            w.bool(emitter.looping);
+            w.f32(emitter.volume);
+            w.u8(emitter.bus as u8);
+        });
+        body.list(&self.reverb_zones, |w, zone| {
+            w.region(zone.region);
+            w.u8(zone.preset as u8);
+        });
+        body.list(&self.entities, Writer::entity);
+        body.list(&self.entity_defs, Writer::entity_def);
+        body.list(&self.spawners, |w, spawner| {
+            w.string(&spawner.name);
+            w.entity_def(&spawner.entity);
+            w.u32(spawner.count);
+            w.f32(spawner.interval.as_secs_f32());
+            match &spawner.trigger {
+                SpawnTrigger::MapStart => w.u8(0),
+                SpawnTrigger::PlayerEnters(region) => {
+                    w.u8(1);
+                    w.region(*region);
+                }
+                SpawnTrigger::AfterWave(name) => {
+                    w.u8(2);
+                    w.string(name);
+                }
+                SpawnTrigger::Manual => w.u8(3),
+            }
+        });
+        body.list(&self.checkpoints, |w, checkpoint| {
+            w.string(&checkpoint.name);
+            w.region(checkpoint.region);
+        });
+        body.list(&self.secrets, |w, secret| {
+            w.string(&secret.name);
+            w.region(secret.region);
+        });
+        body.list(&self.sequences, Writer::sequence);
+        body.list(&self.patrol_paths, |w, path| {
+            w.string(&path.name);
+            w.list(&path.waypoints, |w, waypoint| w.vec2(*waypoint));
+        });
+        body.list(&self.path_costs, |w, (tile_id, cost)| {
+            w.u8(*tile_id);
+            w.f32(*cost);
+        });
+
+        body
+    }
+
+    /// Decodes a map written by [`to_bytes`](Self::to_bytes).
+    ///
+    /// Maps hold their paths for the rest of the program, so paths read are interned: each
+    /// distinct path is leaked the first time any file or diff uses it and shared after that.
+    /// Only decode trusted files this way. For data from elsewhere, limit the paths with
+    /// [`Migrations::with_allowed_paths`] so none are leaked.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        Self::from_bytes_with_migrations(bytes, &Migrations::default())
+    }
+
+    /// Decodes a map written by [`to_bytes`](Self::to_bytes) before the game changed its
+    /// paths or entity types. See [`Migrations`].
+    pub fn from_bytes_with_migrations(
+        bytes: &[u8],
+        migrations: &Migrations,
+    ) -> anyhow::Result<Self> {
+        let mut r = Reader::open(bytes, MAGIC, "map file", migrations)?;
+
+        let tiles = r.grid()?;
+        let mut tile_types = TileTypes::new();
+        for _ in 0..r.u32()? {
+            let id = r.u8()?;
+            tile_types.insert(id, r.tile_type()?);
+        }
+
+        let mut map = Map::new(tiles, tile_types);
+        map.metadata = r.metadata()?;
+        map.linedefs = r.list(|r| Ok(Linedef::new(r.vec2()?, r.vec2()?, r.path()?)))?;
+        map.sprites = r.list(|r| {
+            Ok(Sprite {
+                position: r.vec2()?,
+                texture_path: r.path()?,
+                opacity: r.f32()?,
+                flat: r.bool()?,
+                z_offset: r.f32()?,
+                scale: r.f32()?,
+            })
+        })?;
+        map.labels = r.list(|r| {
+            Ok(TextLabel {
+                position: r.vec2()?,
+                text: r.string()?,
+                z_offset: r.f32()?,
+                height: r.f32()?,
+                color: [r.f32()?, r.f32()?, r.f32()?, r.f32()?],
+            })
+        })?;
+        map.sound_emitters = r.list(|r| {
+            Ok(SoundEmitter {
+                position: r.vec2()?,
+                sound_path: r.path()?,
+                radius: {
+                    let positional = r.bool()?;
+                    let radius = r.f32()?;
+                    positional.then_some(radius)
+                },
+                looping: r.bool()?,
+                volume: r.f32()?,
+                bus: match r.u8()? {
+                    0 => AudioBus::Master,
+                    1 => AudioBus::Music,
+                    2 => AudioBus::Sfx,
+                    bus => anyhow::bail!("unknown audio bus {bus}"),
+                },
+            })
+        })?;
+        map.reverb_zones = r.list(|r| {
+            let region = r.region()?;
+            let preset = match r.u8()? {
+                0 => ReverbPreset::SmallRoom,
+                1 => ReverbPreset::Cavern,
+                2 => ReverbPreset::Outdoors,
+                preset => anyhow::bail!("unknown reverb preset {preset}"),
+            };
+            Ok(ReverbZone::new(region, preset))
+        })?;
+        map.entities = r.list(Reader::entity)?;
+        map.entity_defs = r.list(Reader::entity_def)?;
+        map.spawners = r.list(|r| {
+            Ok(Spawner {
+                name: r.string()?,
+                entity: r.entity_def()?,
+                count: r.u32()?,
+                interval: Duration::try_from_secs_f32(r.f32()?)?,
+                trigger: match r.u8()? {
+                    0 => SpawnTrigger::MapStart,
+                    1 => SpawnTrigger::PlayerEnters(r.region()?),
+                    2 => SpawnTrigger::AfterWave(r.string()?),
+                    3 => SpawnTrigger::Manual,
+                    trigger => anyhow::bail!("unknown spawn trigger {trigger}"),
+                },
+            })
+        })?;
+        map.checkpoints = r.list(|r| Ok(Checkpoint::new(r.string()?, r.region()?)))?;
+        map.secrets = r.list(|r| Ok(Secret::new(r.string()?, r.region()?)))?;
+        map.sequences = r.list(Reader::sequence)?;
+        map.patrol_paths = r.list(|r| Ok(PatrolPath::new(r.string()?, r.list(Reader::vec2)?)))?;
+        map.path_costs = r.list(|r| Ok((r.u8()?, r.f32()?)))?;
+
+        r.finish()?;
+        map.validate()?;
+        Ok(map)
+    }
+
+    /// Reads and decodes a map file written with [`to_bytes`](Self::to_bytes).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::from_bytes(&bytes).with_context(|| format!("failed to load map {}", path.display()))
+    }
+}
+
+impl MapDiff {
+    /// Encodes the diff in the same way as [`Map::to_bytes`], for sending it to other copies of
+    /// the map or saving it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use raycasting_engine::map::MapDiff;
+    ///
+    /// let diff = MapDiff::new().with_tile(3, 4, 1);
+    /// assert_eq!(MapDiff::from_bytes(&diff.to_bytes()).unwrap(), diff);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Writer::default();
+        body.list(&self.changes, |w, change| match change {
+            MapChange::Tile { row, col, tile_id } => {
+                w.u8(0);
+                w.u32(*row as u32);
+                w.u32(*col as u32);
+                w.u8(*tile_id);
+            }
+            MapChange::Entities(entities) => {
+                w.u8(1);
+                w.list(entities, Writer::entity);
+            }
+            MapChange::Metadata(metadata) => {
+                w.u8(2);
+                w.metadata(metadata);
+            }
+            MapChange::EntityDefs(entity_defs) => {
+                w.u8(3);
+                w.list(entity_defs, Writer::entity_def);
+            }
+            MapChange::Door {
+                row,
+                col,
+                open,
+                opening,
+            } => {
+                w.u8(4);
+                w.u32(*row as u32);
+                w.u32(*col as u32);
+                w.f32(*open);
+                w.bool(*opening);
+            }
+        });
+
+        body.finish(DIFF_MAGIC)
+    }
+
+    /// Decodes a diff written by [`to_bytes`](Self::to_bytes). Paths are interned the same way
+    /// as by [`Map::from_bytes`], so decoding the same diff again doesn't grow memory, but new
+    /// paths do. Decode diffs from other players with
+    /// [`Engine::map_diff_from_bytes`](crate::engine::Engine::map_diff_from_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        Self::from_bytes_with_migrations(bytes, &Migrations::default())
+    }
+
+    /// Decodes a diff saved before the game changed its paths or entity types. See
+    /// [`Migrations`].
+    pub fn from_bytes_with_migrations(
+        bytes: &[u8],
+        migrations: &Migrations,
+    ) -> anyhow::Result<Self> {
+        let mut r = Reader::open(bytes, DIFF_MAGIC, "map diff", migrations)?;
+        let changes = r.list(|r| {
+            Ok(match r.u8()? {
+                0 => MapChange::Tile {
+                    row: r.u32()? as usize,
+                    col: r.u32()? as usize,
+                    tile_id: r.u8()?,
+                },
+                1 => MapChange::Entities(r.list(Reader::entity)?),
+                2 => MapChange::Metadata(Box::new(r.metadata()?)),
+                3 => MapChange::EntityDefs(r.list(Reader::entity_def)?),
+                // Added in version 23
+                4 if r.version() >= 23 => MapChange::Door {
+                    row: r.u32()? as usize,
+                    col: r.u32()? as usize,
+                    open: r.f32()?,
+                    opening: r.bool()?,
+                },
+                tag => anyhow::bail!("unknown map change {tag}"),
+            })
+        })?;
+
+        r.finish()?;
+        Ok(Self { changes })
+    }
+}
+
+impl Exploration {
+    /// Encodes the explored tiles with the same header as [`Map::to_bytes`], for a save game.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use raycasting_engine::map::{Exploration, Map, TileData, TileType, TileTypes};
+    ///
+    /// let tile_types = TileTypes::from([(0, TileType::Floor(TileData::new("floor.png")))]);
+    /// let map = Map::new(vec![vec![0; 3]; 3], tile_types);
+    /// let mut exploration = Exploration::new(&map);
+    /// exploration.explore(2, 0);
+    ///
+    /// assert_eq!(Exploration::from_bytes(&exploration.to_bytes()).unwrap(), exploration);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Writer::default();
+        body.u32(self.cols as u32);
+        body.u32(self.rows as u32);
+        body.bytes.extend_from_slice(&self.bits);
+        body.finish(EXPLORATION_MAGIC)
+    }
+
+    /// Decodes explored tiles written by [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        // Explorations hold no paths or entities to migrate
+        let migrations = Migrations::default();
+        let mut r = Reader::open(bytes, EXPLORATION_MAGIC, "exploration", &migrations)?;
+        let cols = r.u32()? as usize;
+        let rows = r.u32()? as usize;
+        let len = cols
+            .checked_mul(rows)
+            .context("exploration is too large")?
+            .div_ceil(8);
+        let bits = r.take(len)?.to_vec();
+
+        r.finish()?;
+        Ok(Self { cols, rows, bits })
+    }
+}
+
+/// The body of a map file, with the paths it refers to collected separately.
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+    paths: Vec<&'static str>,
+    path_indices: HashMap<&'static str, u32>,
+}
+
+impl Writer {
+    /// The whole file: a header with `magic`, the version and the path table, then the body.
+    fn finish(self, magic: &[u8; 4]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.bytes.len());
+        bytes.extend_from_slice(magic);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.paths.len() as u32).to_le_bytes());
+        for path in &self.paths {
+            bytes.extend_from_slice(&(path.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(path.as_bytes());
+        }
+        bytes.extend_from_slice(&self.bytes);
+        bytes
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    fn vec2(&mut self, value: Vec2) {
+        self.f32(value.x);
+        self.f32(value.y);
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    /// A path, as its index in the path table.
+    fn path(&mut self, path: &'static str) {
+        let next = self.paths.len() as u32;
+        let index = *self.path_indices.entry(path).or_insert(next);
+        if index == next {
+            self.paths.push(path);
+        }
+        self.u32(index);
+    }
+
+    fn optional_path(&mut self, path: Option<&'static str>) {
+        match path {
+            Some(path) => self.path(path),
+            None => self.u32(u32::MAX),
+        }
+    }
+
+    fn optional_string(&mut self, value: Option<&str>) {
+        self.bool(value.is_some());
+        if let Some(value) = value {
+            self.string(value);
+        }
+    }
+
+    fn list<T>(&mut self, items: &[T], mut write: impl FnMut(&mut Self, &T)) {
+        self.u32(items.len() as u32);
+        for item in items {
+            write(self, item);
+        }
+    }
+
+    /// The grid's size, then runs of up to 255 equal tiles as (length, tile id) pairs, row
+    /// by row.
+    fn grid(&mut self, tiles: &[Vec<u8>]) {
+        self.u32(tiles.first().map_or(0, Vec::len) as u32);
+        self.u32(tiles.len() as u32);
+
+        let mut run: Option<(u8, u8)> = None;
+        for &tile in tiles.iter().flatten() {
+            run = match run {
+                Some((len, id)) if id == tile && len < u8::MAX => Some((len + 1, id)),
+                Some((len, id)) => {
+                    self.u8(len);
+                    self.u8(id);
+                    Some((1, tile))
+                }
+                None => Some((1, tile)),
+            };
+        }
+        if let Some((len, id)) = run {
+            self.u8(len);
+            self.u8(id);
+        }
+    }
+
+    fn metadata(&mut self, metadata: &MapMetadata) {
+        self.optional_string(metadata.name.as_deref());
+        self.optional_string(metadata.author.as_deref());
+        self.optional_path(metadata.music_path);
+        self.optional_path(metadata.sky_texture_path);
+        self.list(&metadata.clouds, |w, cloud| {
+            w.path(cloud.texture_path);
+            w.f32(cloud.speed);
+            w.f32(cloud.opacity);
+        });
+        self.bool(metadata.fog.is_some());
+        if let Some(fog) = metadata.fog {
+            fog.color.iter().for_each(|c| self.f32(*c));
+            self.f32(fog.start);
+            self.f32(fog.end);
+        }
+        self.bool(metadata.day_night.is_some());
+        if let Some(day_night) = metadata.day_night {
+            self.f32(day_night.period.as_secs_f32());
+            day_night.night_sky.iter().for_each(|c| self.f32(*c));
+            day_night.night_light.iter().for_each(|c| self.f32(*c));
+            self.bool(day_night.night_fog.is_some());
+            day_night
+                .night_fog
+                .unwrap_or_default()
+                .iter()
+                .for_each(|c| self.f32(*c));
+        }
+        self.bool(metadata.weather.is_some());
+        if let Some(weather) = metadata.weather {
+            self.bool(weather.precipitation == Precipitation::Snow);
+            self.path(weather.texture_path);
+            self.f32(weather.density);
+            self.f32(weather.fall_speed);
+            self.f32(weather.scale);
+            self.optional_path(weather.sound_path);
+            self.f32(weather.volume);
+        }
+        match metadata.relief_light {
+            ReliefLight::Player => self.u8(0),
+            ReliefLight::Directional { angle, elevation } => {
+                self.u8(1);
+                self.f32(angle);
+                self.f32(elevation);
+            }
+        }
+        self.bool(metadata.par_time.is_some());
+        self.f32(
+            metadata
+                .par_time
+                .map_or(0.0, |par_time| par_time.as_secs_f32()),
+        );
+        self.bool(metadata.player_start.is_some());
+        let (position, rotation) = metadata.player_start.unwrap_or_default();
+        self.vec2(position);
+        self.f32(rotation);
+    }
+
+    fn entity(&mut self, entity: &Entity) {
+        self.vec2(entity.position);
+        self.vec2(entity.velocity);
+        self.f32(entity.radius);
+        self.u32(entity.layers.0);
+        self.u32(entity.mask.0);
+        self.bool(entity.sensor);
+        self.optional_path(entity.texture_path);
+        self.u16(entity.faction.0);
+        self.f32(entity.damage);
+        self.bool(entity.pickup.is_some());
+        if let Some(pickup) = &entity.pickup {
+            self.string(&pickup.item);
+            self.u32(pickup.count);
+        }
+        self.bool(entity.ai.is_some());
+        if let Some(ai) = &entity.ai {
+            self.f32(ai.speed);
+            self.f32(ai.sight_range);
+            self.optional_string(ai.patrol.as_ref().map(|(path, _)| path.as_str()));
+            self.f32(ai.pause().as_secs_f32());
+            self.bool(ai.opens_doors);
+            self.f32(ai.memory);
+            self.f32(ai.search_radius);
+        }
+    }
+
+    fn entity_def(&mut self, def: &EntityDef) {
+        self.string(&def.type_name);
+        self.vec2(def.position);
+        self.f32(def.rotation);
+        self.optional_path(def.texture_path);
+        self.u32(def.properties.len() as u32);
+        for (key, value) in &def.properties {
+            self.string(key);
+            self.string(value);
+        }
+    }
+
+    fn region(&mut self, region: Region) {
+        match region {
+            Region::Rect { min, max } => {
+                self.u8(0);
+                self.vec2(min);
+                self.vec2(max);
+            }
+            Region::Circle { center, radius } => {
+                self.u8(1);
+                self.vec2(center);
+                self.f32(radius);
+            }
+        }
+    }
+
+    fn camera(&mut self, camera: &Camera) {
+        self.vec2(camera.position);
+        self.f32(camera.rotation);
+        self.u16(camera.height);
+        self.f32(camera.fov);
+        self.f32(camera.pitch);
+    }
+
+    fn sequence(&mut self, sequence: &Sequence) {
+        self.string(&sequence.name);
+        self.list(&sequence.steps, |w, step| match step {
+            Step::LockInput => w.u8(0),
+            Step::UnlockInput => w.u8(1),
+            Step::MoveCamera {
+                waypoints,
+                duration,
+            } => {
+                w.u8(2);
+                w.list(waypoints, Writer::camera);
+                w.f32(duration.as_secs_f32());
+            }
+            Step::ReturnCamera => w.u8(3),
+            Step::ShowText(text) => {
+                w.u8(4);
+                w.string(text);
+            }
+            Step::HideText => w.u8(5),
+            Step::Wait(duration) => {
+                w.u8(6);
+                w.f32(duration.as_secs_f32());
+            }
+            Step::Event(name) => {
+                w.u8(7);
+                w.string(name);
+            }
+            Step::Lightning(lightning) => {
+                w.u8(8);
+                w.f32(lightning.distance);
+                w.f32(lightning.brightness);
+                w.f32(lightning.duration.as_secs_f32());
+                w.optional_path(lightning.thunder_path);
+                w.f32(lightning.volume);
+            }
+        });
+        match &sequence.trigger {
+            SequenceTrigger::MapStart => self.u8(0),
+            SequenceTrigger::PlayerEnters(region) => {
+                self.u8(1);
+                self.region(*region);
+            }
+            SequenceTrigger::Manual => self.u8(2),
+        }
+    }
+
+    fn tile_data(&mut self, data: &TileData) {
+        self.path(data.texture_path);
+        self.optional_path(data.emissive_mask_path);
+        self.optional_path(data.normal_map_path);
+    }
+
+    fn tile_type(&mut self, tile_type: &TileType) {
+        match tile_type {
+            TileType::Empty => self.u8(12),
+            TileType::Wall(data) => {
+                self.u8(0);
+                self.tile_data(data);
+            }
+            TileType::Floor(data) => {
+                self.u8(1);
+                self.tile_data(data);
+            }
+            TileType::Ceiling(data) => {
+                self.u8(2);
+                self.tile_data(data);
+            }
+            TileType::FloorCeiling(data) => {
+                self.u8(3);
+                self.path(data.texture_path_f);
+                self.path(data.texture_path_c);
+                self.bool(data.ceiling_height.is_some());
+                self.f32(data.ceiling_height.unwrap_or(0.0));
+            }
+            TileType::Monitor(data) => {
+                self.u8(4);
+                self.camera(&data.camera);
+            }
+            TileType::Water(data) => {
+                self.u8(5);
+                self.path(data.texture_path);
+                self.f32(data.speed);
+                self.f32(data.depth);
+                data.tint.iter().for_each(|c| self.f32(*c));
+            }
+            TileType::Hazard(data) => {
+                self.u8(6);
+                self.path(data.texture_path);
+                self.f32(data.damage);
+                self.f32(data.interval.as_secs_f32());
+                data.flash.iter().for_each(|c| self.f32(*c));
+            }
+            TileType::Surface(data) => {
+                self.u8(7);
+                self.path(data.texture_path);
+                self.vec2(data.push);
+                self.f32(data.friction);
+                self.f32(data.footstep_noise);
+                self.f32(data.reflection);
+            }
+            TileType::Elevator(data) => {
+                self.u8(8);
+                self.path(data.texture_path);
+                self.f32(data.low);
+                self.f32(data.high);
+                self.f32(data.speed);
+            }
+            TileType::Slope(data) => {
+                self.u8(9);
+                self.path(data.texture_path);
+                self.f32(data.from);
+                self.f32(data.to);
+                self.u8(data.direction as u8);
+            }
+            TileType::Outdoor(data) => {
+                self.u8(10);
+                self.path(data.texture_path);
+            }
+            TileType::ThinWall(data) => {
+                self.u8(11);
+                self.path(data.texture_path);
+                self.optional_path(data.floor_texture_path);
+                self.u8(data.axis as u8);
+                self.f32(data.offset);
+                self.bool(data.door.is_some());
+                if let Some(door) = data.door {
+                    self.f32(door.speed);
+                    self.f32(door.hold);
+                }
+            }
+        }
+    }
+}
+
+/// The `&'static` copy of `path`, leaking it only the first time it's seen in the process.
+/// Maps keep their paths as `&'static str`, and interning stops repeated decodes of the same
+/// file from leaking its paths again. Decoding with allowed paths doesn't get here.
+pub(crate) fn intern(path: &str) -> &'static str {
+    static PATHS: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    let mut paths = PATHS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match paths.get(path) {
+        Some(interned) => interned,
+        None => {
+            let leaked: &'static str = Box::leak(path.into());
+            paths.insert(leaked);
+            leaked
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    paths: Vec<&'static str>,
+    /// What's being read, for errors
+    kind: &'static str,
+    migrations: &'a Migrations,
+    /// The format version the bytes were written in
+    version: u16,
+}
+
+impl<'a> Reader<'a> {
+    /// Checks the header written by [`Writer::finish`] and reads the path table.
+    fn open(
+        bytes: &'a [u8],
+        magic: &[u8; 4],
+        kind: &'static str,
+        migrations: &'a Migrations,
+    ) -> anyhow::Result<Self> {
+        let mut r = Reader {
+            bytes,
+            offset: 0,
+            paths: Vec::new(),
+            kind,
+            migrations,
+            version: VERSION,
+        };
+        anyhow::ensure!(r.take(4)? == magic, "not a {kind}");
+        let version = r.u16()?;
+        anyhow::ensure!(
+            (OLDEST_VERSION..=VERSION).contains(&version),
+            "{kind} is version {version}, but only versions {OLDEST_VERSION} to {VERSION} can \
+             be read"
+        );
+        r.version = version;
+
+        // Each path takes at least its length, so a count beyond that is corrupt or hostile
+        let path_count = r.u32()? as usize;
+        anyhow::ensure!(
+            path_count <= r.remaining() / 4,
+            "{kind} has {path_count} paths, more than its {} bytes can hold",
+            r.remaining()
+        );
+        r.paths = (0..path_count)
+            .map(|_| migrations.path(&r.string()?))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(r)
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    /// The format version being read. A field added in version `N` is read only when this is
+    /// at least `N`, and given its default otherwise.
+    fn version(&self) -> u16 {
+        self.version
+    }
+
+    fn finish(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.offset == self.bytes.len(),
+            "{} has {} bytes left over",
+            self.kind,
+            self.bytes.len() - self.offset
+        );
+        Ok(())
+    }
+
+    fn take(&mut self, len: usize) -> anyhow::Result<&[u8]> {
+        let kind = self.kind;
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset.saturating_add(len))
+            .with_context(|| format!("{kind} ends early"))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn f32(&mut self) -> anyhow::Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn bool(&mut self) -> anyhow::Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn vec2(&mut self) -> anyhow::Result<Vec2> {
+        Ok(vec2(self.f32()?, self.f32()?))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let len = self.u32()? as usize;
+        anyhow::ensure!(
+            len <= self.remaining(),
+            "{} has a {len} byte string but only {} bytes left",
+            self.kind,
+            self.remaining()
+        );
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+
+    fn path(&mut self) -> anyhow::Result<&'static str> {
+        let index = self.u32()?;
+        self.paths
+            .get(index as usize)
+            .copied()
+            .with_context(|| format!("{} refers to missing path {index}", self.kind))
+    }
+
+    fn optional_path(&mut self) -> anyhow::Result<Option<&'static str>> {
+        if self.bytes.get(self.offset..self.offset + 4) == Some(&u32::MAX.to_le_bytes()) {
+            self.offset += 4;
+            return Ok(None);
+        }
+        Ok(Some(self.path()?))
+    }
+
+    fn optional_string(&mut self) -> anyhow::Result<Option<String>> {
+        Ok(if self.bool()? {
+            Some(self.string()?)
+        } else {
+            None
+        })
+    }
+
+    fn list<T>(
+        &mut self,
+        mut read: impl FnMut(&mut Self) -> anyhow::Result<T>,
+    ) -> anyhow::Result<Vec<T>> {
+        (0..self.u32()?).map(|_| read(self)).collect()
+    }
+
+    fn grid(&mut self) -> anyhow::Result<Vec<Vec<u8>>> {
+        let cols = self.u32()? as usize;
+        let rows = self.u32()? as usize;
+        anyhow::ensure!(cols > 0 && rows > 0, "map file has an empty grid");
+        let len = cols.checked_mul(rows).context("map file grid is too big")?;
+
+        let mut tiles = Vec::new();
+        while tiles.len() < len {
+            let run = self.u8()? as usize;
+            let id = self.u8()?;
+            anyhow::ensure!(
+                run > 0 && tiles.len() + run <= len,
+                "map file grid runs don't add up to its size"
+            );
+            tiles.extend(std::iter::repeat_n(id, run));
+        }
+        Ok(tiles.chunks_exact(cols).map(<[u8]>::to_vec).collect())
+    }
+
+    fn metadata(&mut self) -> anyhow::Result<MapMetadata> {
+        Ok(MapMetadata {
+            name: self.optional_string()?,
+            author: self.optional_string()?,
+            music_path: self.optional_path()?,
+            sky_texture_path: self.optional_path()?,
+            clouds: self.list(|r| {
+                Ok(CloudLayer {
+                    texture_path: r.path()?,
+                    speed: r.f32()?,
+                    opacity: r.f32()?,
+                })
+            })?,
+            fog: if self.bool()? {
+                Some(Fog {
+                    color: [self.f32()?, self.f32()?, self.f32()?],
+                    start: self.f32()?,
+                    end: self.f32()?,
+                })
+            } else {
+                None
+            },
+            day_night: if self.bool()? {
+                Some(DayNight {
+                    period: Duration::try_from_secs_f32(self.f32()?)?,
+                    night_sky: [self.f32()?, self.f32()?, self.f32()?],
+                    night_light: [self.f32()?, self.f32()?, self.f32()?],
+                    night_fog: {
+                        let fogged = self.bool()?;
+                        let night_fog = [self.f32()?, self.f32()?, self.f32()?];
+                        fogged.then_some(night_fog)
+                    },
+                })
+            } else {
+                None
+            },
+            weather: if self.bool()? {
+                Some(Weather {
+                    precipitation: if self.bool()? {
+                        Precipitation::Snow
+                    } else {
+                        Precipitation::Rain
+                    },
+                    texture_path: self.path()?,
+                    density: self.f32()?,
+                    fall_speed: self.f32()?,
+                    scale: self.f32()?,
+                    sound_path: self.optional_path()?,
+                    volume: self.f32()?,
+                })
+            } else {
+                None
+            },
+            relief_light: match self.u8()? {
+                0 => ReliefLight::Player,
+                1 => ReliefLight::Directional {
+                    angle: self.f32()?,
+                    elevation: self.f32()?,
+                },
+                light => anyhow::bail!("unknown relief light {light}"),
+            },
+            par_time: {
+                let timed = self.bool()?;
+                let par_time = Duration::try_from_secs_f32(self.f32()?)?;
+                timed.then_some(par_time)
+            },
+            player_start: {
+                let placed = self.bool()?;
+                let player_start = (self.vec2()?, self.f32()?);
+                placed.then_some(player_start)
+            },
+        })
+    }
+
+    fn entity(&mut self) -> anyhow::Result<Entity> {
+        Ok(Entity {
+            position: self.vec2()?,
+            velocity: self.vec2()?,
+            radius: self.f32()?,
+            layers: CollisionLayers(self.u32()?),
+            mask: CollisionLayers(self.u32()?),
+            sensor: self.bool()?,
+            texture_path: self.optional_path()?,
+            faction: Faction(self.u16()?),
+            damage: self.f32()?,
+            pickup: if self.bool()? {
+                Some(ItemStack::new(self.string()?, self.u32()?))
+            } else {
+                None
+            },
+            ai: if self.bool()? {
+                let ai = Ai::new(self.f32()?).with_sight_range(self.f32()?);
+                let path = self.optional_string()?;
+                let pause = Duration::try_from_secs_f32(self.f32()?)?;
+                let ai = ai
+                    .with_door_opening(self.bool()?)
+                    .with_memory(Duration::try_from_secs_f32(self.f32()?)?)
+                    .with_search_radius(self.f32()?);
+                Some(match path {
+                    Some(path) => ai.with_patrol(path, pause),
+                    None => ai,
+                })
+            } else {
+                None
+            },
+            // Worked out again on the next step
+            dormant: false,
+        })
+    }
+
+    fn entity_def(&mut self) -> anyhow::Result<EntityDef> {
+        let mut def = EntityDef::new(self.string()?, self.vec2()?).with_rotation(self.f32()?);
+        def.texture_path = self.optional_path()?;
+        for _ in 0..self.u32()? {
+            def.properties.insert(self.string()?, self.string()?);
+        }
+        self.migrations.entity_def(&mut def);
+        Ok(def)
+    }
+
+    fn region(&mut self) -> anyhow::Result<Region> {
+        Ok(match self.u8()? {
+            0 => Region::Rect {
+                min: self.vec2()?,
+                max: self.vec2()?,
+            },
+            1 => Region::Circle {
+                center: self.vec2()?,
+                radius: self.f32()?,
+            },
+            kind => anyhow::bail!("unknown region kind {kind}"),
+        })
+    }
+
+    fn camera(&mut self) -> anyhow::Result<Camera> {
+        let mut camera = Camera::new(self.vec2()?, self.f32()?);
+        camera.height = self.u16()?;
+        camera.fov = self.f32()?;
+        camera.pitch = self.f32()?;
+        Ok(camera)
+    }
+
+    fn sequence(&mut self) -> anyhow::Result<Sequence> {
+        let name = self.string()?;
+        let steps = self.list(|r| {
+            Ok(match r.u8()? {
+                0 => Step::LockInput,
+                1 => Step::UnlockInput,
+                2 => Step::MoveCamera {
+                    waypoints: r.list(Reader::camera)?,
+                    duration: Duration::try_from_secs_f32(r.f32()?)?,
+                },
+                3 => Step::ReturnCamera,
+                4 => Step::ShowText(r.string()?),
+                5 => Step::HideText,
+                6 => Step::Wait(Duration::try_from_secs_f32(r.f32()?)?),
+                7 => Step::Event(r.string()?),
+                8 => Step::Lightning(Lightning {
+                    distance: r.f32()?,
+                    brightness: r.f32()?,
+                    duration: Duration::try_from_secs_f32(r.f32()?)?,
+                    thunder_path: r.optional_path()?,
+                    volume: r.f32()?,
+                }),
+                step => anyhow::bail!("unknown sequence step {step}"),
+            })
+        })?;
+        let trigger = match self.u8()? {
+            0 => SequenceTrigger::MapStart,
+            1 => SequenceTrigger::PlayerEnters(self.region()?),
+            2 => SequenceTrigger::Manual,
+            trigger => anyhow::bail!("unknown sequence trigger {trigger}"),
+        };
+        Ok(Sequence::new(name, steps).with_trigger(trigger))
+    }
+
+    fn tile_data(&mut self) -> anyhow::Result<TileData> {
+        Ok(TileData {
+            texture_path: self.path()?,
+            emissive_mask_path: self.optional_path()?,
+            normal_map_path: self.optional_path()?,
+        })
+    }
+
+    fn tile_type(&mut self) -> anyhow::Result<TileType> {
+        Ok(match self.u8()? {
+            0 => TileType::Wall(self.tile_data()?),
+            1 => TileType::Floor(self.tile_data()?),
+            2 => TileType::Ceiling(self.tile_data()?),
+            3 => {
+                let data = TileDataFC::new(self.path()?, self.path()?);
+                let lowered = self.bool()?;
+                let ceiling_height = self.f32()?;
+                TileType::FloorCeiling(if lowered {
+                    data.with_ceiling_height(ceiling_height)
+                } else {
+                    data
+                })
+            }
+            4 => TileType::Monitor(MonitorData::new(self.camera()?)),
+            5 => TileType::Water(WaterData {
+                texture_path: self.path()?,
+                speed: self.f32()?,
+                depth: self.f32()?,
+                tint: [self.f32()?, self.f32()?, self.f32()?],
+            }),
+            6 => TileType::Hazard(HazardData {
+                texture_path: self.path()?,
+                damage: self.f32()?,
+                interval: Duration::try_from_secs_f32(self.f32()?)?,
+                flash: [self.f32()?, self.f32()?, self.f32()?],
+            }),
+            7 => TileType::Surface(SurfaceData {
+                texture_path: self.path()?,
+                push: self.vec2()?,
+                friction: self.f32()?,
+                footstep_noise: self.f32()?,
+                reflection: self.f32()?,
+            }),
+            8 => TileType::Elevator(ElevatorData {
+                texture_path: self.path()?,
+                low: self.f32()?,
+                high: self.f32()?,
+                speed: self.f32()?,
+            }),
+            9 => TileType::Slope(SlopeData {
+                texture_path: self.path()?,
+                from: self.f32()?,
+                to: self.f32()?,
+                direction: match self.u8()? {
+                    0 => SlopeDirection::North,
+                    1 => SlopeDirection::East,
+                    2 => SlopeDirection::South,
+                    3 => SlopeDirection::West,
+                    direction => anyhow::bail!("unknown slope direction {direction}"),
+                },
+            }),
+            10 => TileType::Outdoor(TileData::new(self.path()?)),
+            // Added in version 22
+            12 if self.version() >= 22 => TileType::Empty,
+            11 => TileType::ThinWall(ThinWallData {
+                texture_path: self.path()?,
+                floor_texture_path: self.optional_path()?,
+                axis: match self.u8()? {
+                    0 => ThinWallAxis::Horizontal,
+                    1 => ThinWallAxis::Vertical,
+                    axis => anyhow::bail!("unknown thin wall axis {axis}"),
+                },
+                offset: self.f32()?,
+                door: if self.bool()? {
+                    Some(DoorData::new(self.f32()?, self.f32()?))
+                } else {
+                    None
+                },
+            }),
+            kind => anyhow::bail!("unknown tile type {kind}"),
+        })
+    }
+}
@@ -0,0 +1,238 @@
+use std::mem;
+
+use crate::{
+    entity::{Entity, EntityDef},
+    map::{Map, MapMetadata},
+};
+
+/// How many edits [`Map::undo`] can go back through. Older ones are forgotten.
+const MAX_UNDO: usize = 256;
+
+/// One change in a [`MapDiff`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum MapChange {
+    /// Sets the tile id at a row and column.
+    Tile { row: usize, col: usize, tile_id: u8 },
+    /// Replaces the entities the map starts with.
+    Entities(Vec<Entity>),
+    /// Replaces the map's entity definitions.
+    EntityDefs(Vec<EntityDef>),
+    /// Replaces the map's metadata.
+    Metadata(Box<MapMetadata>),
+    /// Slides the door at a row and column to `open`, from 0 (shut) to 1 (all the way open),
+    /// and starts it opening for a whole hold if `opening`. Maps don't keep their doors'
+    /// state, so only a running [`Engine`](crate::engine::Engine) acts on it.
+    Door {
+        row: usize,
+        col: usize,
+        open: f32,
+        opening: bool,
+    },
+}
+
+/// Changes to a map's tiles, entities, entity definitions or metadata, applied in order. A diff only says what
+/// things become, so one made on a map can be applied to any copy of it, like one on another
+/// machine. See [`to_bytes`](Self::to_bytes) for sending it.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::map::{Map, MapDiff, TileData, TileType, TileTypes};
+///
+/// let tile_types = TileTypes::from([
+///     (0, TileType::Floor(TileData::new("floor.png"))),
+///     (1, TileType::Wall(TileData::new("wall.png"))),
+/// ]);
+/// let mut map = Map::new(vec![vec![0, 0], vec![0, 0]], tile_types);
+///
+/// map.edit(&MapDiff::new().with_tile(0, 1, 1)).unwrap();
+/// assert_eq!(map.tiles()[0][1], 1);
+///
+/// assert!(map.undo());
+/// assert_eq!(map.tiles()[0][1], 0);
+/// assert!(map.redo());
+/// assert_eq!(map.tiles()[0][1], 1);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MapDiff {
+    pub(crate) changes: Vec<MapChange>,
+}
+
+impl MapDiff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn with_tile(mut self, row: usize, col: usize, tile_id: u8) -> Self {
+        self.changes.push(MapChange::Tile { row, col, tile_id });
+        self
+    }
+    pub fn with_entities(mut self, entities: Vec<Entity>) -> Self {
+        self.changes.push(MapChange::Entities(entities));
+        self
+    }
+    pub fn with_entity_defs(mut self, entity_defs: Vec<EntityDef>) -> Self {
+        self.changes.push(MapChange::EntityDefs(entity_defs));
+        self
+    }
+    pub fn with_metadata(mut self, metadata: MapMetadata) -> Self {
+        self.changes.push(MapChange::Metadata(Box::new(metadata)));
+        self
+    }
+    pub fn with_door(mut self, row: usize, col: usize, open: f32, opening: bool) -> Self {
+        self.changes.push(MapChange::Door {
+            row,
+            col,
+            open,
+            opening,
+        });
+        self
+    }
+
+    /// The changes that turn `old` into `new`, which must be the same size. Sprites, labels,
+    /// sounds, spawners, checkpoints, secrets, patrol paths and linedefs aren't compared.
+    pub fn between(old: &Map, new: &Map) -> anyhow::Result<Self> {
+        let (old_size, new_size) = (old.size(), new.size());
+        anyhow::ensure!(
+            old_size.cols() == new_size.cols() && old_size.rows() == new_size.rows(),
+            "can't diff a {}x{} map against a {}x{} one",
+            old_size.cols(),
+            old_size.rows(),
+            new_size.cols(),
+            new_size.rows()
+        );
+
+        let mut diff = Self::new();
+        for (row, (old_tiles, new_tiles)) in old.tiles.iter().zip(&new.tiles).enumerate() {
+            for (col, (old_id, new_id)) in old_tiles.iter().zip(new_tiles).enumerate() {
+                if old_id != new_id {
+                    diff = diff.with_tile(row, col, *new_id);
+                }
+            }
+        }
+        if old.entities != new.entities {
+            diff = diff.with_entities(new.entities.clone());
+        }
+        if old.entity_defs != new.entity_defs {
+            diff = diff.with_entity_defs(new.entity_defs.clone());
+        }
+        if old.metadata != new.metadata {
+            diff = diff.with_metadata(new.metadata.clone());
+        }
+
+        Ok(diff)
+    }
+
+    pub fn changes(&self) -> &[MapChange] {
+        &self.changes
+    }
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Adds `other`'s changes after this diff's.
+    pub fn extend(&mut self, other: &MapDiff) {
+        self.changes.extend_from_slice(&other.changes);
+    }
+}
+
+/// Diffs that reverse a map's recent edits and ones that redo undone edits, latest last.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Journal {
+    undo: Vec<MapDiff>,
+    redo: Vec<MapDiff>,
+}
+
+impl Map {
+    /// Applies a diff without recording it for [`undo`](Self::undo), like one received from
+    /// another copy of the map. Returns the diff that reverses it. Nothing is changed if any
+    /// of its tiles or doors are outside the map, or a tile is set to an id without a tile
+    /// type. Door changes are left out, as the map has nothing to change for them.
+    pub fn apply(&mut self, diff: &MapDiff) -> anyhow::Result<MapDiff> {
+        let size = self.size();
+        for change in &diff.changes {
+            if let MapChange::Tile { row, col, .. } | MapChange::Door { row, col, .. } = change {
+                anyhow::ensure!(
+                    *row < size.rows() && *col < size.cols(),
+                    "tile {row}, {col} is outside the {}x{} map",
+                    size.cols(),
+                    size.rows()
+                );
+            }
+            if let MapChange::Tile { row, col, tile_id } = change {
+                anyhow::ensure!(
+                    self.tile_types.contains_key(tile_id),
+                    "tile id {tile_id} at ({row},{col}) has no tile type"
+                );
+            }
+        }
+
+        Ok(self.apply_unchecked(diff))
+    }
+
+    /// Applies a diff whose tiles are known to be inside the map.
+    fn apply_unchecked(&mut self, diff: &MapDiff) -> MapDiff {
+        let mut reverse: Vec<MapChange> =
+            diff.changes
+                .iter()
+                .filter_map(|change| match change {
+                    MapChange::Tile { row, col, tile_id } => Some(MapChange::Tile {
+                        row: *row,
+                        col: *col,
+                        tile_id: mem::replace(&mut self.tiles[*row][*col], *tile_id),
+                    }),
+                    MapChange::Entities(entities) => Some(MapChange::Entities(mem::replace(
+                        &mut self.entities,
+                        entities.clone(),
+                    ))),
+                    MapChange::EntityDefs(entity_defs) => Some(MapChange::EntityDefs(
+                        mem::replace(&mut self.entity_defs, entity_defs.clone()),
+                    )),
+                    MapChange::Metadata(metadata) => Some(MapChange::Metadata(Box::new(
+                        mem::replace(&mut self.metadata, (**metadata).clone()),
+                    ))),
+                    MapChange::Door { .. } => None,
+                })
+                .collect();
+        // Later changes may overwrite earlier ones, so they're undone first
+        reverse.reverse();
+
+        MapDiff { changes: reverse }
+    }
+
+    /// Applies a diff as an edit that [`undo`](Self::undo) can reverse. Anything undone can no
+    /// longer be redone.
+    pub fn edit(&mut self, diff: &MapDiff) -> anyhow::Result<()> {
+        let reverse = self.apply(diff)?;
+        if reverse.is_empty() {
+            return Ok(());
+        }
+
+        self.journal.undo.push(reverse);
+        if self.journal.undo.len() > MAX_UNDO {
+            self.journal.undo.remove(0);
+        }
+        self.journal.redo.clear();
+
+        Ok(())
+    }
+
+    /// Reverses the latest edit. Returns whether there was one.
+    pub fn undo(&mut self) -> bool {
+        let Some(diff) = self.journal.undo.pop() else {
+            return false;
+        };
+        let reverse = self.apply_unchecked(&diff);
+        self.journal.redo.push(reverse);
+        true
+    }
+
+    /// Applies the latest undone edit again. Returns whether there was one.
+    pub fn redo(&mut self) -> bool {
+        let Some(diff) = self.journal.redo.pop() else {
+            return false;
+        };
+        let reverse = self.apply_unchecked(&diff);
+        self.journal.undo.push(reverse);
+        true
+    }
+}
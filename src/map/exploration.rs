@@ -0,0 +1,107 @@
+use glam::Vec2;
+
+use crate::map::Map;
+
+/// Which of a map's tiles the player has seen, one bit per tile. The engine keeps one for each
+/// map, filled in from the tiles its rays pass over, and the automap only shows those.
+/// [`to_bytes`](Self::to_bytes) stores it in a save game.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::map::{Exploration, Map, TileData, TileType, TileTypes};
+///
+/// let tile_types = TileTypes::from([(0, TileType::Floor(TileData::new("floor.png")))]);
+/// let map = Map::new(vec![vec![0; 4]; 3], tile_types);
+///
+/// let mut exploration = Exploration::new(&map);
+/// exploration.explore(1, 2);
+/// assert!(exploration.is_explored(1, 2));
+/// assert!(!exploration.is_explored(2, 1));
+/// assert_eq!(exploration.explored_count(), 1);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Exploration {
+    pub(crate) cols: usize,
+    pub(crate) rows: usize,
+    /// Row by row, the lowest bit of each byte first
+    pub(crate) bits: Vec<u8>,
+}
+
+impl Exploration {
+    /// Nothing explored yet, sized for `map`.
+    pub fn new(map: &Map) -> Self {
+        let size = map.size();
+        Self {
+            cols: size.cols(),
+            rows: size.rows(),
+            bits: vec![0; (size.cols() * size.rows()).div_ceil(8)],
+        }
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Whether the tile has been seen. Tiles outside the map never have.
+    pub fn is_explored(&self, row: usize, col: usize) -> bool {
+        self.index(row, col)
+            .is_some_and(|index| self.bits[index / 8] & (1 << (index % 8)) != 0)
+    }
+
+    /// Marks the tile as seen. Tiles outside the map are ignored.
+    pub fn explore(&mut self, row: usize, col: usize) {
+        if let Some(index) = self.index(row, col) {
+            self.bits[index / 8] |= 1 << (index % 8);
+        }
+    }
+
+    pub fn explored_count(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum()
+    }
+
+    /// Whether this was made for a map the size of `map`.
+    pub fn fits(&self, map: &Map) -> bool {
+        let size = map.size();
+        self.cols == size.cols() && self.rows == size.rows()
+    }
+
+    fn index(&self, row: usize, col: usize) -> Option<usize> {
+        (row < self.rows && col < self.cols).then_some(row * self.cols + col)
+    }
+
+    /// Marks every tile a line passes through, from `start` to `end` in tiles.
+    pub(crate) fn explore_line(&mut self, start: Vec2, end: Vec2) {
+        let direction = end - start;
+        let step = Vec2::select(direction.cmpge(Vec2::ZERO), Vec2::ONE, Vec2::NEG_ONE);
+        // How far along the line one tile across and one tile down are
+        let t_delta = direction.recip().abs();
+
+        let mut tile = start.floor();
+        let mut t_max = Vec2::select(
+            direction.cmpge(Vec2::ZERO),
+            tile + 1.0 - start,
+            start - tile,
+        ) * t_delta;
+        let steps = (end.floor() - tile).abs().element_sum() as usize;
+
+        for _ in 0..=steps {
+            if tile.cmpge(Vec2::ZERO).all() {
+                self.explore(tile.y as usize, tile.x as usize);
+            }
+            if t_max.x < t_max.y {
+                t_max.x += t_delta.x;
+                tile.x += step.x;
+            } else {
+                t_max.y += t_delta.y;
+                tile.y += step.y;
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Context;
+
+use crate::entity::EntityDef;
+
+/// Changes a game has made to its maps' data, applied while decoding so map files and diffs
+/// saved before them, like those in players' save games, still load. See
+/// [`Map::from_bytes_with_migrations`](crate::map::Map::from_bytes_with_migrations).
+///
+/// The engine's own changes to the format are handled when decoding: files from the versions
+/// it can still read are upgraded, with anything added since given its default.
+///
+/// # Example
+///
+/// ```
+/// use glam::vec2;
+/// use raycasting_engine::{
+///     entity::EntityDef,
+///     map::{Map, Migrations, TileData, TileType, TileTypes},
+/// };
+///
+/// let tile_types = TileTypes::from([
+///     (0, TileType::Empty),
+///     (1, TileType::Wall(TileData::new("walls/wall1.png"))),
+/// ]);
+/// let old = Map::new(vec![vec![1, 0, 1]], tile_types).with_entity_defs(vec![
+///     EntityDef::new("imp", vec2(96.0, 32.0)).with_sprite("sprites/imp.png"),
+/// ]);
+///
+/// let migrations = Migrations::new()
+///     .with_path("sprites/imp.png", "sprites/demon.png")
+///     .with_entity_type("imp", "demon")
+///     .with_property_default("demon", "health", "60");
+/// let map = Map::from_bytes_with_migrations(&old.to_bytes(), &migrations).unwrap();
+///
+/// let def = &map.entity_defs()[0];
+/// assert_eq!(def.type_name(), "demon");
+/// assert_eq!(def.sprite(), Some("sprites/demon.png"));
+/// assert_eq!(def.property("health"), Some("60"));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Migrations {
+    paths: HashMap<String, &'static str>,
+    /// The only paths accepted, when set
+    allowed_paths: Option<HashSet<&'static str>>,
+    entity_types: HashMap<String, String>,
+    /// Type name, key and value
+    property_defaults: Vec<(String, String, String)>,
+}
+
+impl Migrations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An asset that moved, like a renamed texture, sprite or sound.
+    pub fn with_path(mut self, old: impl Into<String>, new: &'static str) -> Self {
+        self.paths.insert(old.into(), new);
+        self
+    }
+
+    /// Accepts only `paths` and the paths assets moved to, failing to decode anything that
+    /// refers to another. Decoded paths are kept for the rest of the program, so data from
+    /// elsewhere, like a diff from another player, should be decoded with the paths the game
+    /// already has. [`Engine::map_diff_from_bytes`](crate::engine::Engine::map_diff_from_bytes)
+    /// does this with its maps' paths.
+    pub fn with_allowed_paths(mut self, paths: impl IntoIterator<Item = &'static str>) -> Self {
+        self.allowed_paths
+            .get_or_insert_with(HashSet::new)
+            .extend(paths);
+        self
+    }
+
+    /// An entity type that was renamed. Spawners' entities are renamed too.
+    pub fn with_entity_type(mut self, old: impl Into<String>, new: impl Into<String>) -> Self {
+        self.entity_types.insert(old.into(), new.into());
+        self
+    }
+
+    /// A property entities of a type have gained, set to `value` where saved ones don't have
+    /// it. Renames come first, so `type_name` is the new name.
+    pub fn with_property_default(
+        mut self,
+        type_name: impl Into<String>,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.property_defaults
+            .push((type_name.into(), key.into(), value.into()));
+        self
+    }
+
+    /// The path `path` moved to, or else the allowed path it is, interning it if any path is
+    /// allowed.
+    pub(crate) fn path(&self, path: &str) -> anyhow::Result<&'static str> {
+        if let Some(new) = self.paths.get(path) {
+            return Ok(new);
+        }
+        match &self.allowed_paths {
+            Some(allowed) => allowed
+                .get(path)
+                .copied()
+                .with_context(|| format!("path {path} isn't one of the allowed paths")),
+            None => Ok(super::binary::intern(path)),
+        }
+    }
+
+    pub(crate) fn entity_def(&self, def: &mut EntityDef) {
+        if let Some(new) = self.entity_types.get(&def.type_name) {
+            def.type_name = new.clone();
+        }
+        for (type_name, key, value) in &self.property_defaults {
+            if *type_name == def.type_name {
+                def.properties
+                    .entry(key.clone())
+                    .or_insert_with(|| value.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,1546 @@
+mod binary;
+mod diff;
+mod exploration;
+mod migration;
+mod preflight;
+mod preview;
+#[cfg(test)]
+mod tests;
+
+use std::{collections::HashMap, time::Duration};
+
+use glam::Vec2;
+
+use crate::{
+    audio::{ReverbZone, SoundEmitter},
+    camera::Camera,
+    entity::{Entity, EntityDef, Spawner},
+    map::diff::Journal,
+    sequence::Sequence,
+    sprite::Sprite,
+    text::TextLabel,
+};
+
+pub use diff::{MapChange, MapDiff};
+pub use exploration::Exploration;
+pub use migration::Migrations;
+pub use preflight::{AssetProblem, Preflight, PreflightFuture};
+
+/// How wide and deep every tile is, in world units.
+pub const TILE_SIZE: u16 = 64;
+/// How costly AI finds walking across hazard tiles by default, so it goes well out of its way
+/// around lava. See [`Map::with_path_cost`].
+pub const HAZARD_PATH_COST: f32 = 8.0;
+/// How costly AI finds wading through water by default.
+pub const WATER_PATH_COST: f32 = 2.0;
+/// How costly AI that can open doors finds going through a door by default, for the wait
+/// while it opens.
+pub const DOOR_PATH_COST: f32 = 3.0;
+/// The cheapest a tile can be to walk across, so routes can't wander without end.
+const MIN_PATH_COST: f32 = 0.1;
+
+pub struct MapSize {
+    cols: usize,
+    rows: usize,
+}
+
+impl MapSize {
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+}
+
+/// An area of a map, in world units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Region {
+    Rect { min: Vec2, max: Vec2 },
+    Circle { center: Vec2, radius: f32 },
+}
+
+impl Region {
+    pub fn contains(&self, point: Vec2) -> bool {
+        match *self {
+            Region::Rect { min, max } => point.cmpge(min).all() && point.cmplt(max).all(),
+            Region::Circle { center, radius } => point.distance_squared(center) < radius * radius,
+        }
+    }
+}
+
+/// A named route of waypoints in world units, walked in order and back to the first by
+/// entities with an [`Ai`](crate::ai::Ai) patrolling it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PatrolPath {
+    pub(crate) name: String,
+    pub(crate) waypoints: Vec<Vec2>,
+}
+
+impl PatrolPath {
+    pub fn new(name: impl Into<String>, waypoints: Vec<Vec2>) -> Self {
+        Self {
+            name: name.into(),
+            waypoints,
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn waypoints(&self) -> &[Vec2] {
+        &self.waypoints
+    }
+}
+
+/// An area that records the player's progress when they walk into it, to
+/// [`respawn`](crate::engine::Engine::respawn) at later.
+///
+/// # Example
+///
+/// ```
+/// use glam::vec2;
+/// use raycasting_engine::map::{Checkpoint, Region};
+///
+/// let gate = Checkpoint::new(
+///     "gate",
+///     Region::Circle {
+///         center: vec2(320.0, 96.0),
+///         radius: 48.0,
+///     },
+/// );
+/// assert!(gate.region().contains(vec2(320.0, 100.0)));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub(crate) name: String,
+    pub(crate) region: Region,
+}
+
+impl Checkpoint {
+    pub fn new(name: impl Into<String>, region: Region) -> Self {
+        Self {
+            name: name.into(),
+            region,
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn region(&self) -> Region {
+        self.region
+    }
+}
+
+/// A hidden area counted as found the first time the player walks into it on each visit to
+/// the map. See [`LevelStats`](crate::stats::LevelStats).
+///
+/// # Example
+///
+/// ```
+/// use glam::vec2;
+/// use raycasting_engine::map::{Region, Secret};
+///
+/// let closet = Secret::new(
+///     "closet",
+///     Region::Rect {
+///         min: vec2(640.0, 64.0),
+///         max: vec2(704.0, 128.0),
+///     },
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Secret {
+    pub(crate) name: String,
+    pub(crate) region: Region,
+}
+
+impl Secret {
+    pub fn new(name: impl Into<String>, region: Region) -> Self {
+        Self {
+            name: name.into(),
+            region,
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn region(&self) -> Region {
+        self.region
+    }
+}
+
+/// Configuration for a wall, floor or ceiling tile.
+///
+/// * texture_path - the path to the image (from the 'res' directory) the tile is drawn with.
+/// * emissive_mask_path - for walls, an image marking the parts of the texture that give off
+///   their own light, like lamps, screens and signs. White parts are drawn full-bright whatever
+///   the fog or time of day, black parts are shaded as usual and grays are in between. It's
+///   stretched to the texture's size.
+/// * normal_map_path - for walls, an image of which way each part of the texture faces, as in
+///   common tangent-space normal maps: red is right, green is up and blue is out of the wall.
+///   Lit by the map's [`ReliefLight`], bricks and panels look raised without changing the
+///   wall. It's stretched to the texture's size.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::map::{TileData, TileType};
+///
+/// let sign =
+///     TileType::Wall(TileData::new("exit_sign.png").with_emissive_mask("exit_sign_glow.png"));
+/// let bricks = TileType::Wall(TileData::new("bricks.png").with_normal_map("bricks_normal.png"));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct TileData {
+    pub(crate) texture_path: &'static str,
+    pub(crate) emissive_mask_path: Option<&'static str>,
+    pub(crate) normal_map_path: Option<&'static str>,
+}
+impl TileData {
+    pub fn new(texture_path: &'static str) -> Self {
+        TileData {
+            texture_path,
+            emissive_mask_path: None,
+            normal_map_path: None,
+        }
+    }
+    pub fn with_emissive_mask(mut self, emissive_mask_path: &'static str) -> Self {
+        self.emissive_mask_path = Some(emissive_mask_path);
+        self
+    }
+    pub fn with_normal_map(mut self, normal_map_path: &'static str) -> Self {
+        self.normal_map_path = Some(normal_map_path);
+        self
+    }
+}
+
+/// Configuration for a tile with both a floor and a ceiling.
+///
+/// * texture_path_floor | texture_path_ceiling - the paths to the images (from the 'res'
+///   directory) used for the floor and the ceiling.
+/// * ceiling_height - how high the ceiling is, in world units. Defaults to the wall height,
+///   which is also the most it can be. Where a lower ceiling meets a higher one, its edge is
+///   drawn down to it.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::map::{TileDataFC, TileType};
+///
+/// let low_corridor = TileType::FloorCeiling(
+///     TileDataFC::new("floor.png", "ceiling.png").with_ceiling_height(40.0),
+/// );
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct TileDataFC {
+    pub(crate) texture_path_f: &'static str,
+    pub(crate) texture_path_c: &'static str,
+    pub(crate) ceiling_height: Option<f32>,
+}
+impl TileDataFC {
+    pub fn new(texture_path_floor: &'static str, texture_path_ceiling: &'static str) -> Self {
+        TileDataFC {
+            texture_path_f: texture_path_floor,
+            texture_path_c: texture_path_ceiling,
+            ceiling_height: None,
+        }
+    }
+    pub fn with_ceiling_height(mut self, ceiling_height: f32) -> Self {
+        self.ceiling_height = Some(ceiling_height.max(1.0));
+        self
+    }
+}
+
+/// Configuration for a wall tile whose texture is the live view of another camera, like a
+/// security monitor. The view is rendered at the resolution of the map's wall textures.
+#[derive(Clone, Copy, Debug)]
+pub struct MonitorData {
+    pub(crate) camera: Camera,
+}
+impl MonitorData {
+    pub fn new(camera: Camera) -> Self {
+        MonitorData { camera }
+    }
+}
+
+/// Configuration for a water floor tile. While the player stands in it they move slower, the
+/// camera sinks a little and the view is tinted and rippled.
+///
+/// * texture_path - the path to the image (from the 'res' directory) used for the water's surface.
+/// * speed - multiplies movement speed while in the water. Defaults to 0.5.
+/// * depth - how far the camera sinks, in world units. Defaults to 8.
+/// * tint - the color multiplied over the view while in the water. Defaults to a pale blue.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::map::{TileType, WaterData};
+///
+/// let tile_type = TileType::Water(WaterData::new("water.png").with_speed(0.4));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct WaterData {
+    pub(crate) texture_path: &'static str,
+    pub(crate) speed: f32,
+    pub(crate) depth: f32,
+    pub(crate) tint: [f32; 3],
+}
+impl WaterData {
+    pub fn new(texture_path: &'static str) -> Self {
+        WaterData {
+            texture_path,
+            speed: 0.5,
+            depth: 8.0,
+            tint: [0.55, 0.75, 1.0],
+        }
+    }
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed.max(0.0);
+        self
+    }
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth.max(0.0);
+        self
+    }
+    pub fn with_tint(mut self, tint: [f32; 3]) -> Self {
+        self.tint = tint;
+        self
+    }
+}
+
+/// Configuration for a floor tile that hurts the player standing on it, like lava or acid.
+/// Damage comes in ticks, each reported as a [`DamageEvent`](crate::engine::DamageEvent), with
+/// the view flashing as it lands. The first tick lands as the player steps on.
+///
+/// * texture_path - the path to the image (from the 'res' directory) used for the floor.
+/// * damage - damage per second.
+/// * interval - time between ticks. Defaults to half a second.
+/// * flash - the color the view flashes on each tick. Defaults to red.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::map::{HazardData, TileType};
+///
+/// let tile_type = TileType::Hazard(HazardData::new("lava.png", 20.0));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct HazardData {
+    pub(crate) texture_path: &'static str,
+    pub(crate) damage: f32,
+    pub(crate) interval: Duration,
+    pub(crate) flash: [f32; 3],
+}
+impl HazardData {
+    pub fn new(texture_path: &'static str, damage: f32) -> Self {
+        HazardData {
+            texture_path,
+            damage,
+            interval: Duration::from_millis(500),
+            flash: [1.0, 0.15, 0.1],
+        }
+    }
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval.max(Duration::from_millis(1));
+        self
+    }
+    pub fn with_flash(mut self, flash: [f32; 3]) -> Self {
+        self.flash = flash;
+        self
+    }
+}
+
+/// Configuration for a floor tile that changes how the player moves across it: a conveyor
+/// that carries them along, ice they slide on, or metal grating that rings underfoot. It can
+/// also mirror the walls, for wet or polished floors.
+///
+/// * texture_path - the path to the image (from the 'res' directory) used for the floor.
+/// * push - a velocity added to the player's own while on the tile, in world units per second.
+/// * friction - how quickly the player gets up to speed and stops, where 1 is instant, as on
+///   a normal floor, and lower values slide. Defaults to 1.
+/// * footstep_noise - how far the player's footsteps carry while they walk on the tile, in
+///   world units, alerting AI that hears them. Defaults to 0, silent.
+/// * reflection - how strongly the walls beyond the tile show in it, upside down and rippling,
+///   from 0 to 1. They fade out further from the wall. Defaults to 0, none.
+///
+/// # Example
+///
+/// ```
+/// use glam::vec2;
+/// use raycasting_engine::map::{SurfaceData, TileType};
+///
+/// let conveyor = TileType::Surface(SurfaceData::conveyor("belt.png", vec2(60.0, 0.0)));
+/// let ice = TileType::Surface(SurfaceData::ice("ice.png"));
+/// let grating = TileType::Surface(SurfaceData::metal("grating.png"));
+/// let wet = TileType::Surface(SurfaceData::new("flagstones.png").with_reflection(0.4));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct SurfaceData {
+    pub(crate) texture_path: &'static str,
+    pub(crate) push: Vec2,
+    pub(crate) friction: f32,
+    pub(crate) footstep_noise: f32,
+    pub(crate) reflection: f32,
+}
+impl SurfaceData {
+    pub fn new(texture_path: &'static str) -> Self {
+        SurfaceData {
+            texture_path,
+            push: Vec2::ZERO,
+            friction: 1.0,
+            footstep_noise: 0.0,
+            reflection: 0.0,
+        }
+    }
+    pub fn conveyor(texture_path: &'static str, push: Vec2) -> Self {
+        Self::new(texture_path).with_push(push)
+    }
+    pub fn ice(texture_path: &'static str) -> Self {
+        Self::new(texture_path).with_friction(0.05)
+    }
+    pub fn metal(texture_path: &'static str) -> Self {
+        Self::new(texture_path).with_footstep_noise(384.0)
+    }
+    pub fn with_push(mut self, push: Vec2) -> Self {
+        self.push = push;
+        self
+    }
+    pub fn with_friction(mut self, friction: f32) -> Self {
+        self.friction = friction.clamp(0.0, 1.0);
+        self
+    }
+    pub fn with_footstep_noise(mut self, footstep_noise: f32) -> Self {
+        self.footstep_noise = footstep_noise.max(0.0);
+        self
+    }
+    pub fn with_reflection(mut self, reflection: f32) -> Self {
+        self.reflection = reflection.clamp(0.0, 1.0);
+        self
+    }
+}
+
+/// Configuration for a floor tile that rises and falls between two levels, carrying the player.
+/// Stepping onto it sends it to its other level, as does
+/// [`Engine::activate_elevator`](crate::engine::Engine::activate_elevator) for switches. Every
+/// tile of the same type moves together.
+///
+/// Maps are still a single level: the floor is drawn flat and only the player's view rises
+/// with the platform, so `high` should stay below the wall height.
+///
+/// * texture_path - the path to the image (from the 'res' directory) used for the platform.
+/// * high - the raised floor height, in world units.
+/// * low - the lowered floor height, where it starts. Defaults to 0.
+/// * speed - how fast it moves, in world units per second. Defaults to 32.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::map::{ElevatorData, TileType};
+///
+/// let lift = TileType::Elevator(ElevatorData::new("lift.png", 24.0).with_speed(16.0));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ElevatorData {
+    pub(crate) texture_path: &'static str,
+    pub(crate) low: f32,
+    pub(crate) high: f32,
+    pub(crate) speed: f32,
+}
+impl ElevatorData {
+    pub fn new(texture_path: &'static str, high: f32) -> Self {
+        ElevatorData {
+            texture_path,
+            low: 0.0,
+            high,
+            speed: 32.0,
+        }
+    }
+    pub fn with_low(mut self, low: f32) -> Self {
+        self.low = low;
+        self
+    }
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed.max(0.0);
+        self
+    }
+}
+
+/// The way a ramp climbs across its tile. See [`SlopeData::ramp`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlopeDirection {
+    /// Towards -y
+    North,
+    /// Towards +x
+    East,
+    /// Towards +y
+    South,
+    /// Towards -x
+    West,
+}
+
+/// Configuration for a raised floor tile: a flat step or a ramp between two heights. Lay steps
+/// of increasing height side by side for a staircase. The player walks up anything that rises
+/// no more than a step at a time and is blocked by taller edges, and their view follows the
+/// floor.
+///
+/// Heights are in world units above the base floor and should stay below the wall height.
+///
+/// * texture_path - the path to the image (from the 'res' directory) used for the floor and
+///   the sides of the step.
+/// * from | to - the floor height at the low and high ends of a ramp.
+/// * direction - the way a ramp climbs.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::map::{SlopeData, SlopeDirection, TileType};
+///
+/// let step = TileType::Slope(SlopeData::step("stone.png", 16.0));
+/// let ramp = TileType::Slope(SlopeData::ramp("stone.png", 0.0, 32.0, SlopeDirection::East));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct SlopeData {
+    pub(crate) texture_path: &'static str,
+    pub(crate) from: f32,
+    pub(crate) to: f32,
+    pub(crate) direction: SlopeDirection,
+}
+impl SlopeData {
+    pub fn step(texture_path: &'static str, height: f32) -> Self {
+        Self::ramp(texture_path, height, height, SlopeDirection::East)
+    }
+    pub fn ramp(texture_path: &'static str, from: f32, to: f32, direction: SlopeDirection) -> Self {
+        SlopeData {
+            texture_path,
+            from: from.max(0.0),
+            to: to.max(0.0),
+            direction,
+        }
+    }
+    /// The floor as a plane over the tile: its height at the tile's top left corner, then how
+    /// much it rises across the tile in x and in y.
+    pub(crate) fn plane(&self) -> [f32; 3] {
+        let rise = self.to - self.from;
+        match self.direction {
+            SlopeDirection::East => [self.from, rise, 0.0],
+            SlopeDirection::West => [self.to, -rise, 0.0],
+            SlopeDirection::South => [self.from, 0.0, rise],
+            SlopeDirection::North => [self.to, 0.0, -rise],
+        }
+    }
+    /// The floor height at a point within the tile, where (0, 0) is its top left corner and
+    /// (1, 1) its bottom right.
+    pub(crate) fn height_at(&self, uv: Vec2) -> f32 {
+        let [base, rise_x, rise_y] = self.plane();
+        base + rise_x * uv.x + rise_y * uv.y
+    }
+    pub(crate) fn max_height(&self) -> f32 {
+        self.from.max(self.to)
+    }
+}
+
+/// Which way a thin wall runs through its tile. See [`ThinWallData`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThinWallAxis {
+    /// Along x, facing north and south
+    Horizontal,
+    /// Along y, facing east and west
+    Vertical,
+}
+
+/// Configuration for a wall that's a single plane through its tile instead of filling it, like
+/// a fence down the middle of a yard or an office partition. It blocks movement and sight only
+/// along that plane, so the rest of the tile can be walked into.
+///
+/// * texture_path - the path to the image (from the 'res' directory) used for both faces.
+/// * axis - which way the wall runs.
+/// * offset - how far across the tile the wall sits, from 0 (the top or left edge) to 1 (the
+///   bottom or right edge). Defaults to 0.5.
+/// * floor_texture_path - the path to the image used for the floor on either side. Without
+///   one, the floor is left empty.
+/// * door - makes the wall a door that slides open along itself. See [`DoorData`].
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::map::{ThinWallAxis, ThinWallData, TileType};
+///
+/// let fence = TileType::ThinWall(
+///     ThinWallData::new("fence.png", ThinWallAxis::Horizontal).with_floor("grass.png"),
+/// );
+/// let partition = TileType::ThinWall(
+///     ThinWallData::new("partition.png", ThinWallAxis::Vertical).with_offset(0.25),
+/// );
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ThinWallData {
+    pub(crate) texture_path: &'static str,
+    pub(crate) floor_texture_path: Option<&'static str>,
+    pub(crate) axis: ThinWallAxis,
+    pub(crate) offset: f32,
+    pub(crate) door: Option<DoorData>,
+}
+impl ThinWallData {
+    pub fn new(texture_path: &'static str, axis: ThinWallAxis) -> Self {
+        ThinWallData {
+            texture_path,
+            floor_texture_path: None,
+            axis,
+            offset: 0.5,
+            door: None,
+        }
+    }
+    pub fn with_door(mut self, door: DoorData) -> Self {
+        self.door = Some(door);
+        self
+    }
+    pub fn with_offset(mut self, offset: f32) -> Self {
+        self.offset = offset.clamp(0.0, 1.0);
+        self
+    }
+    pub fn with_floor(mut self, floor_texture_path: &'static str) -> Self {
+        self.floor_texture_path = Some(floor_texture_path);
+        self
+    }
+    /// The axis the wall faces along: 1 (y) for a horizontal wall, 0 (x) for a vertical one.
+    pub(crate) fn normal_axis(&self) -> usize {
+        match self.axis {
+            ThinWallAxis::Horizontal => 1,
+            ThinWallAxis::Vertical => 0,
+        }
+    }
+    /// The wall's ends in world units, for the tile at `row` and `col`.
+    pub(crate) fn segment(&self, row: usize, col: usize, tile_size: f32) -> (Vec2, Vec2) {
+        let corner = Vec2::new(col as f32, row as f32) * tile_size;
+        match self.axis {
+            ThinWallAxis::Horizontal => {
+                let y = corner.y + self.offset * tile_size;
+                (Vec2::new(corner.x, y), Vec2::new(corner.x + tile_size, y))
+            }
+            ThinWallAxis::Vertical => {
+                let x = corner.x + self.offset * tile_size;
+                (Vec2::new(x, corner.y), Vec2::new(x, corner.y + tile_size))
+            }
+        }
+    }
+}
+
+/// Configuration for a thin wall that's a door. Each door tile opens on its own, when
+/// [`Engine::open_door`](crate::engine::Engine::open_door) is called on it or when AI that
+/// [can open doors](crate::ai::Ai::with_door_opening) walks up to it, and shuts again once
+/// it's been left open a while with nothing in the doorway. It only lets things through once
+/// it's all the way open.
+///
+/// * speed - how many tiles' widths it slides per second.
+/// * hold - how long it stays open before shutting, in seconds.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::map::{DoorData, ThinWallAxis, ThinWallData, TileType};
+///
+/// let door = TileType::ThinWall(
+///     ThinWallData::new("door.png", ThinWallAxis::Vertical).with_door(DoorData::new(1.5, 3.0)),
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DoorData {
+    pub(crate) speed: f32,
+    pub(crate) hold: f32,
+}
+impl DoorData {
+    pub fn new(speed: f32, hold: f32) -> Self {
+        DoorData {
+            speed: speed.max(0.01),
+            hold: hold.max(0.0),
+        }
+    }
+}
+
+/// A wall along any line segment, in world units, placed on top of the tile grid like a Doom
+/// linedef. Linedefs block movement and sight like walls, are full wall height, and repeat
+/// their texture every tile's width along their length. Both sides are drawn.
+///
+/// # Example
+///
+/// ```
+/// use glam::vec2;
+/// use raycasting_engine::map::Linedef;
+///
+/// let diagonal = Linedef::new(vec2(64.0, 64.0), vec2(192.0, 160.0), "brick.png");
+/// // A round pillar from sixteen segments
+/// let pillar = Linedef::arc(vec2(320.0, 320.0), 24.0, 0.0, 360.0, 16, "stone.png");
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Linedef {
+    pub(crate) start: Vec2,
+    pub(crate) end: Vec2,
+    pub(crate) texture_path: &'static str,
+}
+impl Linedef {
+    pub fn new(start: Vec2, end: Vec2, texture_path: &'static str) -> Self {
+        Linedef {
+            start,
+            end,
+            texture_path,
+        }
+    }
+    /// Segments approximating a circular arc from `start_angle` to `end_angle`, in degrees
+    /// clockwise from +x like the camera's rotation.
+    pub fn arc(
+        center: Vec2,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        segments: usize,
+        texture_path: &'static str,
+    ) -> Vec<Self> {
+        let segments = segments.max(1);
+        let point = |i: usize| {
+            let t = i as f32 / segments as f32;
+            let angle = (start_angle + (end_angle - start_angle) * t).to_radians();
+            center + Vec2::from_angle(angle) * radius
+        };
+        (0..segments)
+            .map(|i| Linedef::new(point(i), point(i + 1), texture_path))
+            .collect()
+    }
+}
+
+/// The type of a tile's 3D representation. Contains configuration data for the tile.
+///
+/// * wall_texture_path | ceiling_texture_path - the path to the image (from the 'res' directory) that will be applied to the 3D representation as a texture.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::map::{TileData, TileType};
+///
+/// let tile_type = TileType::Wall(TileData::new("wall.png"));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub enum TileType {
+    /// Open floor with no textures of its own, for walking through under the plain floor and
+    /// ceiling.
+    Empty,
+    Wall(TileData),
+    Floor(TileData),
+    Ceiling(TileData),
+    FloorCeiling(TileDataFC),
+    Monitor(MonitorData),
+    Water(WaterData),
+    Hazard(HazardData),
+    Surface(SurfaceData),
+    Elevator(ElevatorData),
+    Slope(SlopeData),
+    /// A floor open to the sky, for courtyards and other outdoor areas. See
+    /// [`Map::with_sky`].
+    Outdoor(TileData),
+    ThinWall(ThinWallData),
+}
+
+/// Distance fog, blending everything towards a color between two distances from the camera,
+/// in world units. The sky isn't fogged. The color is in the renderer's
+/// [`ColorSpace`](crate::config::ColorSpace), sRGB unless configured otherwise.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::map::Fog;
+///
+/// // Clear up close, gone into the murk by eight tiles away
+/// let fog = Fog::new([0.3, 0.32, 0.35], 64.0, 512.0);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fog {
+    pub(crate) color: [f32; 3],
+    pub(crate) start: f32,
+    pub(crate) end: f32,
+}
+impl Fog {
+    pub fn new(color: [f32; 3], start: f32, end: f32) -> Self {
+        let start = start.max(0.0);
+        Fog {
+            color,
+            start,
+            end: end.max(start + 1.0),
+        }
+    }
+    /// How much of the fog color covers something this far away, from 0.0 to 1.0.
+    pub(crate) fn amount(&self, distance: f32) -> f32 {
+        ((distance - self.start) / (self.end - self.start)).clamp(0.0, 1.0)
+    }
+}
+
+/// The most cloud layers drawn over a map's sky. Any past these are ignored.
+pub const MAX_CLOUD_LAYERS: usize = 3;
+
+/// An image drawn over the sky, drifting around the player. Its alpha says where the clouds
+/// are. Cloud images are stretched to the sky image's size.
+///
+/// * speed - how far it drifts each second, as a fraction of its width. Negative values drift
+///   the other way.
+/// * opacity - how much it covers the sky, from 0.0 to 1.0.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::map::CloudLayer;
+///
+/// // A slow, faint high layer under a quicker, thicker low one
+/// let high = CloudLayer::new("sky/cirrus.png").with_speed(0.002).with_opacity(0.5);
+/// let low = CloudLayer::new("sky/cumulus.png").with_speed(0.01);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CloudLayer {
+    pub(crate) texture_path: &'static str,
+    pub(crate) speed: f32,
+    pub(crate) opacity: f32,
+}
+impl CloudLayer {
+    pub fn new(texture_path: &'static str) -> Self {
+        Self {
+            texture_path,
+            speed: 0.005,
+            opacity: 1.0,
+        }
+    }
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self
+    }
+    pub fn texture_path(&self) -> &'static str {
+        self.texture_path
+    }
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+}
+
+/// A day passing while the map is played, darkening it towards night and back. Days are
+/// brightest at noon and darkest at midnight, blending smoothly in between. The time of day
+/// carries over between maps and can be set with
+/// [`Engine::set_time_of_day`](crate::engine::Engine::set_time_of_day).
+///
+/// * period - how long a whole day lasts.
+/// * night_sky - multiplies the sky and clouds at midnight.
+/// * night_light - multiplies walls, floors, ceilings and sprites at midnight.
+/// * night_fog - the fog's color at midnight. Without it the fog is darkened like the light.
+///   Has no effect on maps without [`Fog`].
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use raycasting_engine::map::DayNight;
+///
+/// // A day every ten minutes, with moonlit blue nights
+/// let day_night = DayNight::new(Duration::from_secs(600))
+///     .with_night_sky([0.05, 0.07, 0.2])
+///     .with_night_light([0.25, 0.3, 0.45]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DayNight {
+    pub(crate) period: Duration,
+    pub(crate) night_sky: [f32; 3],
+    pub(crate) night_light: [f32; 3],
+    pub(crate) night_fog: Option<[f32; 3]>,
+}
+impl DayNight {
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period,
+            night_sky: [0.05, 0.05, 0.12],
+            night_light: [0.3, 0.3, 0.4],
+            night_fog: None,
+        }
+    }
+    pub fn with_night_sky(mut self, night_sky: [f32; 3]) -> Self {
+        self.night_sky = night_sky;
+        self
+    }
+    pub fn with_night_light(mut self, night_light: [f32; 3]) -> Self {
+        self.night_light = night_light;
+        self
+    }
+    pub fn with_night_fog(mut self, night_fog: [f32; 3]) -> Self {
+        self.night_fog = Some(night_fog);
+        self
+    }
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+    pub fn night_sky(&self) -> [f32; 3] {
+        self.night_sky
+    }
+    pub fn night_light(&self) -> [f32; 3] {
+        self.night_light
+    }
+    pub fn night_fog(&self) -> Option<[f32; 3]> {
+        self.night_fog
+    }
+}
+
+/// What falls in a map's [`Weather`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precipitation {
+    /// Falls fast and straight
+    Rain,
+    /// Falls slowly, drifting from side to side
+    Snow,
+}
+
+/// Rain or snow falling around the player, only over outdoor tiles so it stops at the edge of
+/// a roof. Each drop or flake is a sprite using `texture_path`.
+///
+/// * density - drops or flakes per outdoor tile near the player.
+/// * fall_speed - world units per second.
+/// * scale - the size of each drop or flake, as for [`Sprite::with_scale`].
+/// * sound_path - a sound looped while the weather's heard, louder the more of the sky is open
+///   around the player. See also
+///   [`Engine::weather_exposure`](crate::engine::Engine::weather_exposure).
+/// * volume - the sound's volume out in the open.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::map::Weather;
+///
+/// let storm = Weather::rain("sprites/raindrop.png")
+///     .with_density(3.0)
+///     .with_sound("sounds/rain.wav");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weather {
+    pub(crate) precipitation: Precipitation,
+    pub(crate) texture_path: &'static str,
+    pub(crate) density: f32,
+    pub(crate) fall_speed: f32,
+    pub(crate) scale: f32,
+    pub(crate) sound_path: Option<&'static str>,
+    pub(crate) volume: f32,
+}
+impl Weather {
+    pub fn rain(texture_path: &'static str) -> Self {
+        Self {
+            precipitation: Precipitation::Rain,
+            texture_path,
+            density: 2.0,
+            fall_speed: 480.0,
+            scale: 0.15,
+            sound_path: None,
+            volume: 1.0,
+        }
+    }
+    pub fn snow(texture_path: &'static str) -> Self {
+        Self {
+            precipitation: Precipitation::Snow,
+            texture_path,
+            density: 1.5,
+            fall_speed: 40.0,
+            scale: 0.05,
+            sound_path: None,
+            volume: 1.0,
+        }
+    }
+    pub fn with_density(mut self, density: f32) -> Self {
+        self.density = density.max(0.0);
+        self
+    }
+    pub fn with_fall_speed(mut self, fall_speed: f32) -> Self {
+        self.fall_speed = fall_speed.max(0.0);
+        self
+    }
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale.max(0.0);
+        self
+    }
+    pub fn with_sound(mut self, sound_path: &'static str) -> Self {
+        self.sound_path = Some(sound_path);
+        self
+    }
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume.max(0.0);
+        self
+    }
+    pub fn precipitation(&self) -> Precipitation {
+        self.precipitation
+    }
+    pub fn texture_path(&self) -> &'static str {
+        self.texture_path
+    }
+    pub fn density(&self) -> f32 {
+        self.density
+    }
+    pub fn fall_speed(&self) -> f32 {
+        self.fall_speed
+    }
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+    pub fn sound_path(&self) -> Option<&'static str> {
+        self.sound_path
+    }
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+}
+
+/// Where the light comes from that picks out the relief of wall textures with normal maps. See
+/// [`TileData::with_normal_map`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ReliefLight {
+    /// Shines from the player, like a headlamp
+    #[default]
+    Player,
+    /// Shines from the same direction everywhere, like the sun. `angle` is the heading it
+    /// comes from in degrees, as for a [`Camera`]'s rotation, and `elevation` how far above the
+    /// horizon it is in degrees.
+    Directional { angle: f32, elevation: f32 },
+}
+
+/// Information about a map for the game to show and settings the engine applies while it's
+/// the current map.
+///
+/// * name | author - for the game to show, like on a title card or the pause menu.
+/// * music_path - the path to a sound (from the 'res' directory) looped on the music bus.
+/// * sky_texture_path - see [`Map::with_sky`].
+/// * clouds - drawn over the sky, in order. See [`CloudLayer`].
+/// * day_night - see [`DayNight`].
+/// * weather - see [`Weather`].
+/// * relief_light - see [`ReliefLight`].
+/// * fog - see [`Fog`].
+/// * par_time - how long the map should take, for the game to compare against.
+/// * player_start - where the player is placed when the map is entered, in world units, and
+///   which way they face, in degrees. Without it they stay where they are.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use raycasting_engine::map::{Fog, MapMetadata};
+///
+/// let metadata = MapMetadata::new()
+///     .with_name("The Flooded Cellars")
+///     .with_author("jtkyber")
+///     .with_music("music/cellars.wav")
+///     .with_fog(Fog::new([0.1, 0.12, 0.1], 128.0, 768.0))
+///     .with_par_time(Duration::from_secs(90));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MapMetadata {
+    pub(crate) name: Option<String>,
+    pub(crate) author: Option<String>,
+    pub(crate) music_path: Option<&'static str>,
+    pub(crate) sky_texture_path: Option<&'static str>,
+    pub(crate) clouds: Vec<CloudLayer>,
+    pub(crate) day_night: Option<DayNight>,
+    pub(crate) weather: Option<Weather>,
+    pub(crate) relief_light: ReliefLight,
+    pub(crate) fog: Option<Fog>,
+    pub(crate) par_time: Option<Duration>,
+    pub(crate) player_start: Option<(Vec2, f32)>,
+}
+impl MapMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+    pub fn with_music(mut self, music_path: &'static str) -> Self {
+        self.music_path = Some(music_path);
+        self
+    }
+    pub fn with_sky(mut self, texture_path: &'static str) -> Self {
+        self.sky_texture_path = Some(texture_path);
+        self
+    }
+    pub fn with_clouds(mut self, clouds: Vec<CloudLayer>) -> Self {
+        self.clouds = clouds;
+        self
+    }
+    pub fn with_day_night(mut self, day_night: DayNight) -> Self {
+        self.day_night = Some(day_night);
+        self
+    }
+    pub fn with_weather(mut self, weather: Weather) -> Self {
+        self.weather = Some(weather);
+        self
+    }
+    pub fn with_relief_light(mut self, relief_light: ReliefLight) -> Self {
+        self.relief_light = relief_light;
+        self
+    }
+    pub fn with_fog(mut self, fog: Fog) -> Self {
+        self.fog = Some(fog);
+        self
+    }
+    pub fn with_par_time(mut self, par_time: Duration) -> Self {
+        self.par_time = Some(par_time);
+        self
+    }
+    pub fn with_player_start(mut self, position: Vec2, rotation: f32) -> Self {
+        self.player_start = Some((position, rotation));
+        self
+    }
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+    pub fn music_path(&self) -> Option<&'static str> {
+        self.music_path
+    }
+    pub fn sky_texture_path(&self) -> Option<&'static str> {
+        self.sky_texture_path
+    }
+    pub fn clouds(&self) -> &[CloudLayer] {
+        &self.clouds
+    }
+    pub fn day_night(&self) -> Option<DayNight> {
+        self.day_night
+    }
+    pub fn weather(&self) -> Option<Weather> {
+        self.weather
+    }
+    pub fn relief_light(&self) -> ReliefLight {
+        self.relief_light
+    }
+    pub fn fog(&self) -> Option<Fog> {
+        self.fog
+    }
+    pub fn par_time(&self) -> Option<Duration> {
+        self.par_time
+    }
+    pub fn player_start(&self) -> Option<(Vec2, f32)> {
+        self.player_start
+    }
+}
+
+/// Holds a map's tile data, where the key is the number used to
+pub type TileTypes = HashMap<u8, TileType>;
+
+#[derive(Clone)]
+pub struct Map {
+    tiles: Vec<Vec<u8>>,
+    tile_types: TileTypes,
+    sprites: Vec<Sprite>,
+    labels: Vec<TextLabel>,
+    sound_emitters: Vec<SoundEmitter>,
+    reverb_zones: Vec<ReverbZone>,
+    entities: Vec<Entity>,
+    entity_defs: Vec<EntityDef>,
+    spawners: Vec<Spawner>,
+    checkpoints: Vec<Checkpoint>,
+    secrets: Vec<Secret>,
+    sequences: Vec<Sequence>,
+    patrol_paths: Vec<PatrolPath>,
+    /// Route costs by tile id, for tiles that don't use the default for their type
+    path_costs: Vec<(u8, f32)>,
+    metadata: MapMetadata,
+    linedefs: Vec<Linedef>,
+    journal: Journal,
+}
+
+pub type Maps = HashMap<&'static str, Map>;
+
+impl Map {
+    pub fn new(tiles: Vec<Vec<u8>>, tile_types: TileTypes) -> Self {
+        Self {
+            tiles,
+            tile_types,
+            sprites: Vec::new(),
+            labels: Vec::new(),
+            sound_emitters: Vec::new(),
+            reverb_zones: Vec::new(),
+            entities: Vec::new(),
+            entity_defs: Vec::new(),
+            spawners: Vec::new(),
+            checkpoints: Vec::new(),
+            secrets: Vec::new(),
+            sequences: Vec::new(),
+            patrol_paths: Vec::new(),
+            path_costs: Vec::new(),
+            metadata: MapMetadata::default(),
+            linedefs: Vec::new(),
+            journal: Journal::default(),
+        }
+    }
+    /// Places floating text in the map.
+    pub fn with_labels(mut self, labels: Vec<TextLabel>) -> Self {
+        self.labels = labels;
+        self
+    }
+    /// Places billboards in the map.
+    pub fn with_sprites(mut self, sprites: Vec<Sprite>) -> Self {
+        self.sprites = sprites;
+        self
+    }
+    /// Places sounds in the map, which start playing when it loads.
+    pub fn with_sound_emitters(mut self, sound_emitters: Vec<SoundEmitter>) -> Self {
+        self.sound_emitters = sound_emitters;
+        self
+    }
+    /// Marks out areas where sounds echo.
+    pub fn with_reverb_zones(mut self, reverb_zones: Vec<ReverbZone>) -> Self {
+        self.reverb_zones = reverb_zones;
+        self
+    }
+    /// Places entities in the map, spawned each time it's switched to. Their sprite images are
+    /// loaded with the map, along with those of its sprites.
+    pub fn with_entities(mut self, entities: Vec<Entity>) -> Self {
+        self.entities = entities;
+        self
+    }
+    /// Places entities by type name, built by the game's [`EntityTypes`](crate::entity::EntityTypes)
+    /// each time the map is switched to. Their sprite images are loaded with the map.
+    pub fn with_entity_defs(mut self, entity_defs: Vec<EntityDef>) -> Self {
+        self.entity_defs = entity_defs;
+        self
+    }
+    /// Adds waves of entities, started by triggers. Their sprite images are loaded with the
+    /// map.
+    pub fn with_spawners(mut self, spawners: Vec<Spawner>) -> Self {
+        self.spawners = spawners;
+        self
+    }
+    /// Marks out areas that record a checkpoint when the player enters them.
+    pub fn with_checkpoints(mut self, checkpoints: Vec<Checkpoint>) -> Self {
+        self.checkpoints = checkpoints;
+        self
+    }
+    /// Marks out hidden areas, counted in the level's stats when the player finds them.
+    pub fn with_secrets(mut self, secrets: Vec<Secret>) -> Self {
+        self.secrets = secrets;
+        self
+    }
+    /// Adds scripted sequences, each run once per visit to the map when its trigger fires.
+    pub fn with_sequences(mut self, sequences: Vec<Sequence>) -> Self {
+        self.sequences = sequences;
+        self
+    }
+    /// Adds routes for entities to patrol, looked up by name.
+    pub fn with_patrol_paths(mut self, patrol_paths: Vec<PatrolPath>) -> Self {
+        self.patrol_paths = patrol_paths;
+        self
+    }
+    /// Sets how costly AI finds walking across tiles with this id when it plans a route,
+    /// relative to 1.0 for an ordinary floor: below 1 for roads it would rather take, above for
+    /// mud it goes around if that's shorter than wading through. `f32::INFINITY` keeps it off
+    /// them altogether. Hazards default to [`HAZARD_PATH_COST`] and water to
+    /// [`WATER_PATH_COST`]. Costs below 0.1 count as 0.1.
+    pub fn with_path_cost(mut self, tile_id: u8, cost: f32) -> Self {
+        let cost = cost.max(MIN_PATH_COST);
+        match self.path_costs.iter_mut().find(|(id, _)| *id == tile_id) {
+            Some((_, existing)) => *existing = cost,
+            None => self.path_costs.push((tile_id, cost)),
+        }
+        self
+    }
+    /// Shows an image above outdoor tiles, wrapped once around the player. The horizon is
+    /// halfway down the image.
+    pub fn with_sky(mut self, texture_path: &'static str) -> Self {
+        self.metadata.sky_texture_path = Some(texture_path);
+        self
+    }
+    /// Names the map and sets its music, sky and fog. A sky set in the metadata replaces one
+    /// from [`with_sky`](Self::with_sky).
+    pub fn with_metadata(mut self, metadata: MapMetadata) -> Self {
+        let sky_texture_path = metadata.sky_texture_path.or(self.metadata.sky_texture_path);
+        self.metadata = MapMetadata {
+            sky_texture_path,
+            ..metadata
+        };
+        self
+    }
+    /// Adds walls along arbitrary line segments, on top of the grid. They should stay inside
+    /// the map.
+    pub fn with_linedefs(mut self, linedefs: Vec<Linedef>) -> Self {
+        self.linedefs = linedefs;
+        self
+    }
+    pub fn size(&self) -> MapSize {
+        MapSize {
+            cols: self.tiles[0].len(),
+            rows: self.tiles.len(),
+        }
+    }
+    pub fn tiles(&self) -> &Vec<Vec<u8>> {
+        &self.tiles
+    }
+    pub fn tile_id(&self, row: usize, col: usize) -> Option<u8> {
+        Some(self.tiles[row][col])
+    }
+    pub fn tile_type(&self, tile_id: u8) -> Option<TileType> {
+        // println!("{:?}", tile_id);
+        self.tile_types.get(&tile_id).copied()
+    }
+    pub fn tile_types(&self) -> &TileTypes {
+        &self.tile_types
+    }
+    /// The tile under a point in world units, as (row, col), if it's inside the map.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use glam::vec2;
+    /// use raycasting_engine::map::{Map, Region, TileData, TileType};
+    ///
+    /// let map = Map::new(
+    ///     vec![vec![1, 1, 1], vec![1, 0, 1], vec![1, 1, 1]],
+    ///     HashMap::from([(1, TileType::Wall(TileData::new("wall1.png")))]),
+    /// );
+    ///
+    /// assert_eq!(map.world_to_cell(vec2(100.0, 70.0)), Some((1, 1)));
+    /// assert_eq!(map.cell_to_world_center(1, 1), vec2(96.0, 96.0));
+    /// assert!(map.cell_rect(1, 1).contains(vec2(100.0, 70.0)));
+    /// assert_eq!(map.walls().count(), 8);
+    /// ```
+    pub fn world_to_cell(&self, position: Vec2) -> Option<(usize, usize)> {
+        let tile = (position / TILE_SIZE as f32).floor();
+        let size = self.size();
+        if tile.x < 0.0 || tile.y < 0.0 {
+            return None;
+        }
+        let (row, col) = (tile.y as usize, tile.x as usize);
+        (row < size.rows() && col < size.cols()).then_some((row, col))
+    }
+    /// The middle of a tile, in world units.
+    pub fn cell_to_world_center(&self, row: usize, col: usize) -> Vec2 {
+        (Vec2::new(col as f32, row as f32) + 0.5) * TILE_SIZE as f32
+    }
+    /// The area a tile covers, in world units.
+    pub fn cell_rect(&self, row: usize, col: usize) -> Region {
+        let min = Vec2::new(col as f32, row as f32) * TILE_SIZE as f32;
+        Region::Rect {
+            min,
+            max: min + TILE_SIZE as f32,
+        }
+    }
+    /// Every tile as (row, col, tile id), row by row.
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize, u8)> + '_ {
+        self.tiles.iter().enumerate().flat_map(|(row, tiles)| {
+            tiles
+                .iter()
+                .enumerate()
+                .map(move |(col, &tile_id)| (row, col, tile_id))
+        })
+    }
+    /// The tiles that block movement and sight all the way across, walls and monitors, as
+    /// (row, col, tile id).
+    pub fn walls(&self) -> impl Iterator<Item = (usize, usize, u8)> + '_ {
+        self.cells().filter(|&(_, _, tile_id)| {
+            matches!(
+                self.tile_type(tile_id),
+                Some(TileType::Wall(_) | TileType::Monitor(_))
+            )
+        })
+    }
+    /// Checks that the map can be played: its grid has tiles, every row is as long as the
+    /// first, every tile id in it has a tile type, declared [`TileType::Empty`] for open floor,
+    /// and everything placed in it is at a finite position. Maps decoded with
+    /// [`from_bytes`](Self::from_bytes) have already been checked, and the engine checks the
+    /// rest when it starts.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use raycasting_engine::map::Map;
+    ///
+    /// let map = Map::new(vec![vec![1, 1, 1], vec![1, 0]], HashMap::new());
+    /// assert!(map.validate().is_err());
+    /// ```
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let cols = self.tiles.first().map_or(0, Vec::len);
+        anyhow::ensure!(cols > 0, "map has no tiles");
+        for (row, tiles) in self.tiles.iter().enumerate() {
+            anyhow::ensure!(
+                tiles.len() == cols,
+                "map row {row} has {} tiles, but the first has {cols}",
+                tiles.len()
+            );
+        }
+        if let Some((row, col, tile_id)) = self
+            .cells()
+            .find(|(_, _, tile_id)| !self.tile_types.contains_key(tile_id))
+        {
+            anyhow::bail!("tile id {tile_id} at ({row},{col}) has no tile type");
+        }
+
+        let positions = self
+            .linedefs
+            .iter()
+            .flat_map(|linedef| [("linedef", linedef.start), ("linedef", linedef.end)])
+            .chain(
+                self.sprites
+                    .iter()
+                    .map(|sprite| ("sprite", sprite.position)),
+            )
+            .chain(self.labels.iter().map(|label| ("label", label.position)))
+            .chain(
+                self.sound_emitters
+                    .iter()
+                    .map(|emitter| ("sound emitter", emitter.position)),
+            )
+            .chain(
+                self.entities
+                    .iter()
+                    .map(|entity| ("entity", entity.position)),
+            )
+            .chain(self.entity_defs.iter().map(|def| ("entity", def.position)))
+            .chain(
+                self.patrol_paths
+                    .iter()
+                    .flat_map(|path| path.waypoints.iter().map(|&point| ("patrol path", point))),
+            )
+            .chain(
+                self.metadata
+                    .player_start
+                    .map(|(position, _)| ("player start", position)),
+            );
+        for (what, position) in positions {
+            anyhow::ensure!(
+                position.is_finite(),
+                "map has a {what} at {position}, which isn't a finite position"
+            );
+        }
+        Ok(())
+    }
+    pub fn sprites(&self) -> &[Sprite] {
+        &self.sprites
+    }
+    pub fn labels(&self) -> &[TextLabel] {
+        &self.labels
+    }
+    pub fn sound_emitters(&self) -> &[SoundEmitter] {
+        &self.sound_emitters
+    }
+    pub fn reverb_zones(&self) -> &[ReverbZone] {
+        &self.reverb_zones
+    }
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+    pub fn entity_defs(&self) -> &[EntityDef] {
+        &self.entity_defs
+    }
+    pub fn spawners(&self) -> &[Spawner] {
+        &self.spawners
+    }
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+    pub fn secrets(&self) -> &[Secret] {
+        &self.secrets
+    }
+    pub fn sequences(&self) -> &[Sequence] {
+        &self.sequences
+    }
+    pub fn patrol_paths(&self) -> &[PatrolPath] {
+        &self.patrol_paths
+    }
+    pub fn patrol_path(&self, name: &str) -> Option<&PatrolPath> {
+        self.patrol_paths.iter().find(|path| path.name == name)
+    }
+    /// The costs set with [`with_path_cost`](Self::with_path_cost), by tile id.
+    pub fn path_costs(&self) -> &[(u8, f32)] {
+        &self.path_costs
+    }
+    /// How costly AI finds walking across a tile with this id. Infinite for walls. Doors
+    /// default to [`DOOR_PATH_COST`] for AI that can open them.
+    pub fn path_cost(&self, tile_id: u8) -> f32 {
+        if let Some((_, cost)) = self.path_costs.iter().find(|(id, _)| *id == tile_id) {
+            return *cost;
+        }
+        match self.tile_type(tile_id) {
+            Some(TileType::ThinWall(data)) if data.door.is_some() => DOOR_PATH_COST,
+            Some(TileType::Wall(_) | TileType::Monitor(_) | TileType::ThinWall(_)) => f32::INFINITY,
+            Some(TileType::Hazard(_)) => HAZARD_PATH_COST,
+            Some(TileType::Water(_)) => WATER_PATH_COST,
+            _ => 1.0,
+        }
+    }
+    pub fn sky_texture_path(&self) -> Option<&'static str> {
+        self.metadata.sky_texture_path
+    }
+    pub fn metadata(&self) -> &MapMetadata {
+        &self.metadata
+    }
+    pub fn linedefs(&self) -> &[Linedef] {
+        &self.linedefs
+    }
+    /// The height of the tallest step or ramp, or 0 if the floor is flat everywhere.
+    pub(crate) fn max_floor_height(&self) -> f32 {
+        self.tile_types
+            .values()
+            .filter_map(|tile_type| match tile_type {
+                TileType::Slope(data) => Some(data.max_height()),
+                _ => None,
+            })
+            .fold(0.0, f32::max)
+    }
+    /// Whether the tile under a point, in world units, is open to the sky.
+    pub(crate) fn is_outdoor(&self, position: Vec2, tile_size: f32) -> bool {
+        let tile = (position / tile_size).floor();
+        let size = self.size();
+        if tile.x < 0.0 || tile.y < 0.0 {
+            return false;
+        }
+        let (row, col) = (tile.y as usize, tile.x as usize);
+        row < size.rows()
+            && col < size.cols()
+            && matches!(
+                self.tile_type(self.tiles[row][col]),
+                Some(TileType::Outdoor(_))
+            )
+    }
+    /// Whether any tile has a lowered ceiling or none at all.
+    pub(crate) fn ceilings_vary(&self) -> bool {
+        self.tile_types.values().any(|tile_type| match tile_type {
+            TileType::FloorCeiling(data) => data.ceiling_height.is_some(),
+            TileType::Outdoor(_) => true,
+            _ => false,
+        })
+    }
+    pub fn img_path_count(&self) -> u16 {
+        let mut count = 0;
+        for v in self.tile_types.values() {
+            match v {
+                TileType::Wall(_) => count += 1,
+                TileType::Ceiling(_) => count += 1,
+                TileType::FloorCeiling(_) => count += 2,
+                TileType::Floor(_) => count += 1,
+                TileType::Water(_) => count += 1,
+                TileType::Hazard(_) => count += 1,
+                TileType::Surface(_) => count += 1,
+                TileType::Elevator(_) => count += 1,
+                TileType::Slope(_) => count += 1,
+                TileType::Outdoor(_) => count += 1,
+                TileType::ThinWall(data) => count += 1 + data.floor_texture_path.is_some() as u16,
+                TileType::Empty | TileType::Monitor(_) => (),
+            };
+        }
+
+        count
+    }
+}
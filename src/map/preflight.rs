@@ -0,0 +1,90 @@
+use std::{collections::BTreeMap, fmt, future::Future, pin::Pin};
+
+use crate::{
+    assets::{self, AssetSource},
+    map::Maps,
+    renderer::texture_paths,
+};
+
+pub type PreflightFuture<'a> = Pin<Box<dyn Future<Output = Vec<AssetProblem>> + 'a>>;
+
+/// A file maps use that's missing or can't be decoded, found by [`Preflight::preflight`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssetProblem {
+    pub path: &'static str,
+    /// The maps that use it, sorted
+    pub maps: Vec<&'static str>,
+    /// What went wrong, with its causes
+    pub error: String,
+}
+
+impl fmt::Display for AssetProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (used by {}): {}",
+            self.path,
+            self.maps.join(", "),
+            self.error
+        )
+    }
+}
+
+/// Checks every asset a set of maps uses before the game starts.
+pub trait Preflight {
+    /// Loads and decodes every texture the maps use from `assets`, and returns each one that's
+    /// missing or unreadable, sorted by path. The engine only finds them as maps are entered,
+    /// and draws a placeholder for each, so this gives level authors the whole list at once.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use raycasting_engine::{
+    ///     assets::FileSystemAssets,
+    ///     map::{Map, Maps, Preflight, TileData, TileType, TileTypes},
+    /// };
+    ///
+    /// let tile_types = TileTypes::from([
+    ///     (0, TileType::Empty),
+    ///     (1, TileType::Wall(TileData::new("walls/wall1.png"))),
+    ///     (2, TileType::Wall(TileData::new("walls/missing.png"))),
+    /// ]);
+    /// let maps = Maps::from([("E1M1", Map::new(vec![vec![1, 0, 2]], tile_types))]);
+    ///
+    /// let problems = pollster::block_on(maps.preflight(&FileSystemAssets::new()));
+    /// assert_eq!(problems.len(), 1);
+    /// assert_eq!(problems[0].path, "walls/missing.png");
+    /// assert_eq!(problems[0].maps, ["E1M1"]);
+    /// ```
+    fn preflight<'a>(&'a self, assets: &'a dyn AssetSource) -> PreflightFuture<'a>;
+}
+
+impl Preflight for Maps {
+    fn preflight<'a>(&'a self, assets: &'a dyn AssetSource) -> PreflightFuture<'a> {
+        let mut users: BTreeMap<&'static str, Vec<&'static str>> = BTreeMap::new();
+        for (key, map) in self {
+            for path in texture_paths(map) {
+                users.entry(path).or_default().push(key);
+            }
+        }
+
+        Box::pin(async move {
+            let mut problems = Vec::new();
+            for (path, mut maps) in users {
+                let result = match assets.load(path).await {
+                    Ok(bytes) => assets::decode_image(&bytes).map(|_| ()),
+                    Err(error) => Err(error),
+                };
+                if let Err(error) = result {
+                    maps.sort_unstable();
+                    problems.push(AssetProblem {
+                        path,
+                        maps,
+                        error: format!("{error:#}"),
+                    });
+                }
+            }
+            problems
+        })
+    }
+}
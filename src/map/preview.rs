@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use glam::Vec2;
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    assets::{self, AssetSource},
+    map::{Exploration, Map, TileType},
+};
+
+/// World units per tile, as in the raycaster.
+const TILE_SIZE: f32 = 64.0;
+/// Empty tiles, and where there's no tile type for a tile id.
+const EMPTY: Rgba<u8> = Rgba([0, 0, 0, 255]);
+/// Thin walls and linedefs.
+const LINE: Rgba<u8> = Rgba([230, 230, 230, 255]);
+
+impl Map {
+    /// Draws the map from above, `scale` pixels to a tile, with a plain color for each kind of
+    /// tile. Save it with [`RgbaImage::save`] for a PNG.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use raycasting_engine::map::{Map, TileData, TileType, TileTypes};
+    ///
+    /// let tile_types = TileTypes::from([
+    ///     (0, TileType::Wall(TileData::new("wall.png"))),
+    ///     (1, TileType::Floor(TileData::new("floor.png"))),
+    /// ]);
+    /// let map = Map::new(vec![vec![0, 0, 0], vec![0, 1, 0], vec![0, 0, 0]], tile_types);
+    ///
+    /// let preview = map.render_preview(8);
+    /// assert_eq!(preview.dimensions(), (24, 24));
+    /// ```
+    pub fn render_preview(&self, scale: u32) -> RgbaImage {
+        self.render_preview_with(scale, &HashMap::new())
+    }
+
+    /// Like [`render_preview`](Self::render_preview), with colors for some tile ids taking the
+    /// place of the plain ones, such as from [`tile_colors`](Self::tile_colors).
+    pub fn render_preview_with(&self, scale: u32, colors: &HashMap<u8, Rgba<u8>>) -> RgbaImage {
+        let scale = scale.max(1);
+        let size = self.size();
+        let mut image = RgbaImage::new(size.cols() as u32 * scale, size.rows() as u32 * scale);
+
+        for (row, tiles) in self.tiles.iter().enumerate() {
+            for (col, tile_id) in tiles.iter().enumerate() {
+                let color = colors
+                    .get(tile_id)
+                    .copied()
+                    .unwrap_or_else(|| self.tile_color(*tile_id));
+                for y in 0..scale {
+                    for x in 0..scale {
+                        image.put_pixel(col as u32 * scale + x, row as u32 * scale + y, color);
+                    }
+                }
+
+                if let Some(TileType::ThinWall(data)) = self.tile_type(*tile_id) {
+                    let (start, end) = data.segment(row, col, TILE_SIZE);
+                    draw_line(&mut image, start, end, scale);
+                }
+            }
+        }
+
+        for linedef in &self.linedefs {
+            draw_line(&mut image, linedef.start, linedef.end, scale);
+        }
+
+        image
+    }
+
+    /// Like [`render_preview`](Self::render_preview), with the tiles the player hasn't seen
+    /// left black.
+    pub fn render_explored(&self, scale: u32, exploration: &Exploration) -> RgbaImage {
+        let mut image = self.render_preview(scale);
+        let scale = scale.max(1);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            if !exploration.is_explored((y / scale) as usize, (x / scale) as usize) {
+                *pixel = EMPTY;
+            }
+        }
+        image
+    }
+
+    /// The plain color a tile id is drawn with in previews.
+    pub(crate) fn tile_color(&self, tile_id: u8) -> Rgba<u8> {
+        self.tile_type(tile_id).map_or(EMPTY, default_color)
+    }
+
+    /// The average color of each tile type's texture, as seen from above: the floor where
+    /// there is one, otherwise the wall.
+    pub async fn tile_colors(
+        &self,
+        assets: &dyn AssetSource,
+    ) -> anyhow::Result<HashMap<u8, Rgba<u8>>> {
+        let mut averages: HashMap<&'static str, Rgba<u8>> = HashMap::new();
+        let mut colors = HashMap::new();
+
+        for (tile_id, tile_type) in &self.tile_types {
+            let Some(path) = top_texture_path(tile_type) else {
+                continue;
+            };
+            let color = match averages.get(path) {
+                Some(color) => *color,
+                None => {
+                    let bytes = assets.load(path).await?;
+                    let image = assets::decode_image(&bytes)
+                        .with_context(|| format!("failed to load texture {path}"))?;
+                    let color = average_color(&image.to_rgba8());
+                    averages.insert(path, color);
+                    color
+                }
+            };
+            colors.insert(*tile_id, color);
+        }
+
+        Ok(colors)
+    }
+}
+
+fn default_color(tile_type: TileType) -> Rgba<u8> {
+    Rgba(match tile_type {
+        TileType::Empty => return EMPTY,
+        TileType::Wall(_) => [90, 90, 100, 255],
+        TileType::Monitor(_) => [60, 110, 140, 255],
+        TileType::Floor(_) | TileType::Ceiling(_) | TileType::FloorCeiling(_) => {
+            [170, 160, 140, 255]
+        }
+        TileType::Water(_) => [50, 100, 200, 255],
+        TileType::Hazard(_) => [200, 60, 40, 255],
+        TileType::Surface(_) => [150, 190, 200, 255],
+        TileType::Elevator(_) => [200, 180, 60, 255],
+        TileType::Slope(_) => [140, 130, 110, 255],
+        TileType::Outdoor(_) => [110, 160, 90, 255],
+        TileType::ThinWall(_) => [170, 160, 140, 255],
+    })
+}
+
+fn top_texture_path(tile_type: &TileType) -> Option<&'static str> {
+    match tile_type {
+        TileType::Empty => None,
+        TileType::Wall(data)
+        | TileType::Floor(data)
+        | TileType::Ceiling(data)
+        | TileType::Outdoor(data) => Some(data.texture_path),
+        TileType::FloorCeiling(data) => Some(data.texture_path_f),
+        TileType::Water(data) => Some(data.texture_path),
+        TileType::Hazard(data) => Some(data.texture_path),
+        TileType::Surface(data) => Some(data.texture_path),
+        TileType::Elevator(data) => Some(data.texture_path),
+        TileType::Slope(data) => Some(data.texture_path),
+        TileType::ThinWall(data) => data.floor_texture_path,
+        TileType::Monitor(_) => None,
+    }
+}
+
+fn average_color(image: &RgbaImage) -> Rgba<u8> {
+    let mut sum = [0u64; 3];
+    for pixel in image.pixels() {
+        for (total, channel) in sum.iter_mut().zip(pixel.0) {
+            *total += channel as u64;
+        }
+    }
+    let count = (image.width() as u64 * image.height() as u64).max(1);
+    Rgba([
+        (sum[0] / count) as u8,
+        (sum[1] / count) as u8,
+        (sum[2] / count) as u8,
+        255,
+    ])
+}
+
+/// Draws a line between two points in world units, a pixel wide.
+fn draw_line(image: &mut RgbaImage, start: Vec2, end: Vec2, scale: u32) {
+    let start = start / TILE_SIZE * scale as f32;
+    let end = end / TILE_SIZE * scale as f32;
+    let steps = (end - start).abs().max_element().ceil().max(1.0) as u32;
+    for i in 0..=steps {
+        let point = start.lerp(end, i as f32 / steps as f32);
+        let (x, y) = (point.x as u32, point.y as u32);
+        if point.x >= 0.0 && point.y >= 0.0 && x < image.width() && y < image.height() {
+            image.put_pixel(x, y, LINE);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::Context;
+
+use super::*;
+
+#[test]
+fn a_tile_id_without_a_tile_type_is_named_where_it_is() {
+    let tile_types = TileTypes::from([
+        (0, TileType::Empty),
+        (1, TileType::Wall(TileData::new("wall.png"))),
+    ]);
+    let map = Map::new(
+        vec![vec![1, 1, 1, 1], vec![1, 0, 5, 1], vec![1, 5, 0, 1]],
+        tile_types,
+    );
+
+    let error = map
+        .validate()
+        .context("map \"Map1\" is invalid")
+        .unwrap_err();
+    assert_eq!(
+        format!("{error:#}"),
+        "map \"Map1\" is invalid: tile id 5 at (1,2) has no tile type"
+    );
+
+    let mut typed = map.clone();
+    typed.tile_types.insert(5, TileType::Empty);
+    assert!(typed.validate().is_ok());
+}
+
+/// A map using most of what the oldest readable format can hold.
+fn fixture_map() -> Map {
+    let tile_types = TileTypes::from([
+        (1, TileType::Wall(TileData::new("walls/wall1.png"))),
+        (
+            2,
+            TileType::FloorCeiling(
+                TileDataFC::new("floors/floor1.png", "ceilings/ceiling1.png")
+                    .with_ceiling_height(48.0),
+            ),
+        ),
+        (
+            3,
+            TileType::ThinWall(
+                ThinWallData::new("walls/door.png", ThinWallAxis::Vertical)
+                    .with_door(DoorData::new(64.0, 3.0)),
+            ),
+        ),
+    ]);
+    let tiles = vec![
+        vec![1, 1, 1, 1, 1],
+        vec![1, 2, 3, 2, 1],
+        vec![1, 1, 1, 1, 1],
+    ];
+    Map::new(tiles, tile_types)
+        .with_sprites(vec![Sprite::new(
+            Vec2::new(96.0, 96.0),
+            "sprites/barrel.png",
+        )])
+        .with_entity_defs(vec![
+            EntityDef::new("imp", Vec2::new(224.0, 96.0))
+                .with_rotation(180.0)
+                .with_property("health", "60"),
+        ])
+        .with_metadata(
+            MapMetadata::new()
+                .with_name("Fixture")
+                .with_par_time(Duration::from_secs(90))
+                .with_player_start(Vec2::new(96.0, 96.0), 0.0),
+        )
+}
+
+#[test]
+fn a_map_written_in_the_oldest_version_still_reads() {
+    let bytes = include_bytes!("golden/v21.map");
+    assert_eq!(
+        u16::from_le_bytes([bytes[4], bytes[5]]),
+        binary::OLDEST_VERSION
+    );
+
+    let map = Map::from_bytes(bytes).unwrap();
+    assert_eq!(map.to_bytes(), fixture_map().to_bytes());
+}
+
+#[test]
+fn decoding_a_map_twice_shares_its_paths() {
+    let bytes = fixture_map().to_bytes();
+    let first = Map::from_bytes(&bytes).unwrap();
+    let second = Map::from_bytes(&bytes).unwrap();
+    assert!(std::ptr::eq(
+        first.sprites()[0].texture_path,
+        second.sprites()[0].texture_path
+    ));
+}
+
+#[test]
+fn a_path_table_longer_than_the_file_is_rejected() {
+    let mut bytes = fixture_map().to_bytes();
+    // The path count follows the magic and version
+    bytes[6..10].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(Map::from_bytes(&bytes).is_err());
+}
+
+#[test]
+fn decoding_a_diff_twice_shares_its_paths() {
+    let diff = MapDiff::new().with_entity_defs(vec![
+        EntityDef::new("imp", Vec2::new(224.0, 96.0)).with_sprite("sprites/imp.png"),
+    ]);
+    let bytes = diff.to_bytes();
+    let sprite = |diff: &MapDiff| match &diff.changes()[0] {
+        MapChange::EntityDefs(defs) => defs[0].sprite().unwrap(),
+        change => panic!("expected entity definitions, got {change:?}"),
+    };
+
+    let first = MapDiff::from_bytes(&bytes).unwrap();
+    let second = MapDiff::from_bytes(&bytes).unwrap();
+    assert!(std::ptr::eq(sprite(&first), sprite(&second)));
+}
+
+#[test]
+fn a_diff_setting_a_tile_id_without_a_tile_type_is_rejected() {
+    let tile_types = TileTypes::from([
+        (0, TileType::Empty),
+        (1, TileType::Wall(TileData::new("wall.png"))),
+    ]);
+    let mut map = Map::new(
+        vec![vec![1, 1, 1], vec![1, 0, 1], vec![1, 1, 1]],
+        tile_types,
+    );
+
+    let error = map.apply(&MapDiff::new().with_tile(1, 1, 5)).unwrap_err();
+    assert_eq!(error.to_string(), "tile id 5 at (1,1) has no tile type");
+    assert_eq!(map.tile_id(1, 1), Some(0));
+
+    // Empty tiles survive encoding
+    let decoded = Map::from_bytes(&map.to_bytes()).unwrap();
+    assert!(matches!(decoded.tile_type(0), Some(TileType::Empty)));
+}
+
+#[test]
+fn only_allowed_paths_are_decoded() {
+    let diff = MapDiff::new().with_entity_defs(vec![
+        EntityDef::new("imp", Vec2::new(224.0, 96.0)).with_sprite("sprites/unknown.png"),
+    ]);
+    let imp: &'static str = "sprites/imp.png";
+    let allowed = Migrations::new().with_allowed_paths([imp]);
+
+    let error = MapDiff::from_bytes_with_migrations(&diff.to_bytes(), &allowed).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "path sprites/unknown.png isn't one of the allowed paths"
+    );
+
+    // A copy of the allowed path decodes to the allowed one rather than a new leak
+    let diff = MapDiff::new().with_entity_defs(vec![
+        EntityDef::new("imp", Vec2::new(224.0, 96.0)).with_sprite(String::from(imp).leak()),
+    ]);
+    let decoded = MapDiff::from_bytes_with_migrations(&diff.to_bytes(), &allowed).unwrap();
+    match &decoded.changes()[0] {
+        MapChange::EntityDefs(defs) => assert!(std::ptr::eq(defs[0].sprite().unwrap(), imp)),
+        change => panic!("expected entity definitions, got {change:?}"),
+    }
+}
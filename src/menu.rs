@@ -0,0 +1,344 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    audio::AudioBus,
+    config::{AccessibilityOption, Settings},
+    engine::MenuEvent,
+};
+
+/// A press that moves through [`Menus`]. The arrow keys or WASD, Enter or Space and Escape or
+/// Backspace are read from the keyboard; a game with a gamepad passes its presses to
+/// [`Engine::navigate_menu`](crate::engine::Engine::navigate_menu).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuInput {
+    Up,
+    Down,
+    /// Turns a setting down
+    Left,
+    /// Turns a setting up
+    Right,
+    Select,
+    Back,
+}
+
+/// What choosing a [`MenuItem`] does.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MenuAction {
+    /// Opens the screen with this name over the current one
+    Open(&'static str),
+    /// Goes back to the screen underneath, closing the menus from the first one
+    Back,
+    /// Closes the menus and carries on playing
+    Resume,
+    /// A volume, shown as a percentage and changed with left and right
+    Volume(AudioBus),
+    /// An accessibility setting, shown with its value and changed with left and right.
+    /// Switches can also be selected.
+    Accessibility(AccessibilityOption),
+    /// Reported to the game as [`MenuEvent::Selected`] with this name, for its own choices
+    /// like loading a save
+    Custom(String),
+    Quit,
+}
+
+/// One line of a [`MenuScreen`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MenuItem {
+    pub(crate) label: String,
+    pub(crate) action: MenuAction,
+}
+
+impl MenuItem {
+    pub fn new(label: impl Into<String>, action: MenuAction) -> Self {
+        Self {
+            label: label.into(),
+            action,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn action(&self) -> &MenuAction {
+        &self.action
+    }
+}
+
+/// A titled list of items, one of them selected at a time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MenuScreen {
+    pub(crate) title: String,
+    pub(crate) items: Vec<MenuItem>,
+}
+
+impl MenuScreen {
+    pub fn new(title: impl Into<String>, items: Vec<MenuItem>) -> Self {
+        Self {
+            title: title.into(),
+            items,
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn items(&self) -> &[MenuItem] {
+        &self.items
+    }
+}
+
+/// Named menu screens and which are open, drawn as text over the view. The simulation stops
+/// while a screen is open.
+///
+/// The engine opens the [`MAIN`](Self::MAIN) screen, if there is one, on starting, and the
+/// [`PAUSE`](Self::PAUSE) screen when Escape is pressed during play. [`Settings`] changed from
+/// the menus are saved to the [settings path](Self::with_settings_path), if there is one, and
+/// reported as [`MenuEvent::SettingsChanged`] for the game to save them itself otherwise.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::{
+///     config::Settings,
+///     engine::MenuEvent,
+///     menu::{MenuAction, MenuInput, MenuItem, MenuScreen, Menus},
+/// };
+///
+/// let mut menus = Menus::standard().with_screen(
+///     Menus::MAIN,
+///     MenuScreen::new(
+///         "MY GAME",
+///         vec![
+///             MenuItem::new("New game", MenuAction::Resume),
+///             MenuItem::new("Load game", MenuAction::Custom("load".into())),
+///             MenuItem::new("Options", MenuAction::Open(Menus::OPTIONS)),
+///             MenuItem::new("Quit", MenuAction::Quit),
+///         ],
+///     ),
+/// );
+/// let mut settings = Settings::default();
+///
+/// assert!(menus.open(Menus::MAIN));
+/// menus.navigate(MenuInput::Down, &mut settings);
+/// assert_eq!(
+///     menus.navigate(MenuInput::Select, &mut settings),
+///     Some(MenuEvent::Selected("load".into()))
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Menus {
+    screens: Vec<(&'static str, MenuScreen)>,
+    /// The open screens, the last one shown, each with its selected item
+    open: Vec<(&'static str, usize)>,
+    settings_path: Option<PathBuf>,
+}
+
+impl Menus {
+    /// The screen shown on starting.
+    pub const MAIN: &'static str = "main";
+    /// The screen Escape opens during play.
+    pub const PAUSE: &'static str = "pause";
+    pub const OPTIONS: &'static str = "options";
+    pub const ACCESSIBILITY: &'static str = "accessibility";
+
+    /// No screens, for a game to add all of its own.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A main menu, a pause menu, an options screen with the volumes and an accessibility
+    /// screen, for a game to replace any of with [`with_screen`](Self::with_screen).
+    pub fn standard() -> Self {
+        Self::new()
+            .with_screen(
+                Self::MAIN,
+                MenuScreen::new(
+                    "MAIN MENU",
+                    vec![
+                        MenuItem::new("Start", MenuAction::Resume),
+                        MenuItem::new("Options", MenuAction::Open(Self::OPTIONS)),
+                        MenuItem::new("Quit", MenuAction::Quit),
+                    ],
+                ),
+            )
+            .with_screen(
+                Self::PAUSE,
+                MenuScreen::new(
+                    "PAUSED",
+                    vec![
+                        MenuItem::new("Resume", MenuAction::Resume),
+                        MenuItem::new("Options", MenuAction::Open(Self::OPTIONS)),
+                        MenuItem::new("Quit", MenuAction::Quit),
+                    ],
+                ),
+            )
+            .with_screen(
+                Self::OPTIONS,
+                MenuScreen::new(
+                    "OPTIONS",
+                    vec![
+                        MenuItem::new("Volume", MenuAction::Volume(AudioBus::Master)),
+                        MenuItem::new("Music", MenuAction::Volume(AudioBus::Music)),
+                        MenuItem::new("Effects", MenuAction::Volume(AudioBus::Sfx)),
+                        MenuItem::new("Accessibility", MenuAction::Open(Self::ACCESSIBILITY)),
+                        MenuItem::new("Back", MenuAction::Back),
+                    ],
+                ),
+            )
+            .with_screen(
+                Self::ACCESSIBILITY,
+                MenuScreen::new(
+                    "ACCESSIBILITY",
+                    [
+                        ("Field of view", AccessibilityOption::Fov),
+                        ("Head bob", AccessibilityOption::HeadBob),
+                        ("Screen shake", AccessibilityOption::ScreenShake),
+                        ("Flash reduction", AccessibilityOption::FlashReduction),
+                        ("Contrast map", AccessibilityOption::HighContrastAutomap),
+                        ("Toggle crouch", AccessibilityOption::ToggleCrouch),
+                        ("Toggle sprint", AccessibilityOption::ToggleSprint),
+                        ("Safe flashes", AccessibilityOption::PhotosensitiveSafe),
+                        ("Colors", AccessibilityOption::ColorFilter),
+                    ]
+                    .into_iter()
+                    .map(|(label, option)| MenuItem::new(label, MenuAction::Accessibility(option)))
+                    .chain([MenuItem::new("Back", MenuAction::Back)])
+                    .collect(),
+                ),
+            )
+    }
+
+    /// Adds a screen, replacing any with the same name.
+    pub fn with_screen(mut self, name: &'static str, screen: MenuScreen) -> Self {
+        match self.screens.iter_mut().find(|(key, _)| *key == name) {
+            Some((_, existing)) => *existing = screen,
+            None => self.screens.push((name, screen)),
+        }
+        self
+    }
+
+    /// Saves the [`Settings`] as RON to `path` whenever they're changed from the menus, and
+    /// loads them from it on starting if it exists, in place of those in
+    /// [`EngineConfig`](crate::config::EngineConfig). Native only.
+    pub fn with_settings_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.settings_path = Some(path.into());
+        self
+    }
+
+    pub fn screen(&self, name: &str) -> Option<&MenuScreen> {
+        self.screens
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, screen)| screen)
+    }
+
+    pub fn settings_path(&self) -> Option<&Path> {
+        self.settings_path.as_deref()
+    }
+
+    /// Opens a screen over any already open, with its first item selected. Returns whether
+    /// there is one by that name.
+    pub fn open(&mut self, name: &str) -> bool {
+        let Some((name, _)) = self.screens.iter().find(|(key, _)| *key == name) else {
+            return false;
+        };
+        self.open.push((name, 0));
+        true
+    }
+
+    /// Closes every open screen.
+    pub fn close(&mut self) {
+        self.open.clear();
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.open.is_empty()
+    }
+
+    /// The screen being shown and the index of its selected item.
+    pub fn current(&self) -> Option<(&MenuScreen, usize)> {
+        let (name, selected) = self.open.last()?;
+        Some((self.screen(name)?, *selected))
+    }
+
+    /// Moves the selection or acts on the selected item, changing `settings`. Volumes are
+    /// changed by a tenth at a time.
+    pub fn navigate(&mut self, input: MenuInput, settings: &mut Settings) -> Option<MenuEvent> {
+        let (name, selected) = *self.open.last()?;
+        let items = &self.screen(name)?.items;
+        let len = items.len();
+        if len == 0 && input != MenuInput::Back {
+            return None;
+        }
+        let action = items.get(selected).map(|item| item.action.clone());
+
+        match (input, action) {
+            (MenuInput::Up, _) => {
+                self.select((selected + len - 1) % len);
+                None
+            }
+            (MenuInput::Down, _) => {
+                self.select((selected + 1) % len);
+                None
+            }
+            (MenuInput::Left | MenuInput::Right, Some(MenuAction::Volume(bus))) => {
+                let step = if input == MenuInput::Left { -0.1 } else { 0.1 };
+                // Rounded so repeated steps land back on whole tenths
+                let volume = ((settings.audio.volume(bus) + step) * 10.0).round() / 10.0;
+                settings.audio.set_volume(bus, volume);
+                Some(MenuEvent::SettingsChanged(*settings))
+            }
+            (
+                MenuInput::Left | MenuInput::Right | MenuInput::Select,
+                Some(MenuAction::Accessibility(option)),
+            ) => {
+                let steps = if input == MenuInput::Left { -1 } else { 1 };
+                if input == MenuInput::Select && option.is_number() {
+                    return None;
+                }
+                settings.accessibility.adjust(option, steps);
+                Some(MenuEvent::SettingsChanged(*settings))
+            }
+            (MenuInput::Back, _) | (MenuInput::Select, Some(MenuAction::Back)) => {
+                self.open.pop();
+                (!self.is_open()).then_some(MenuEvent::Closed)
+            }
+            (MenuInput::Select, Some(MenuAction::Open(screen))) => {
+                self.open(screen).then_some(MenuEvent::Opened(screen))
+            }
+            (MenuInput::Select, Some(MenuAction::Resume)) => {
+                self.close();
+                Some(MenuEvent::Closed)
+            }
+            (MenuInput::Select, Some(MenuAction::Custom(name))) => Some(MenuEvent::Selected(name)),
+            (MenuInput::Select, Some(MenuAction::Quit)) => Some(MenuEvent::Quit),
+            _ => None,
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        if let Some((_, selected)) = self.open.last_mut() {
+            *selected = index;
+        }
+    }
+
+    /// An item's line as it's drawn, with the value of the setting it changes if it changes
+    /// one.
+    pub(crate) fn item_text(item: &MenuItem, settings: &Settings) -> String {
+        match item.action {
+            MenuAction::Volume(bus) => format!(
+                "{} < {}% >",
+                item.label,
+                (settings.audio.volume(bus) * 100.0).round()
+            ),
+            MenuAction::Accessibility(option) => format!(
+                "{} < {} >",
+                item.label,
+                settings.accessibility.display(option)
+            ),
+            _ => item.label.clone(),
+        }
+    }
+}
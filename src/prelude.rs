@@ -0,0 +1,26 @@
+pub use glam::{Vec2, vec2};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::run_on_game_thread;
+pub use crate::{
+    assets::AssetSource,
+    audio::{AudioBus, SoundEmitter},
+    camera::Camera,
+    config::{
+        AccessibilityConfig, AudioConfig, EngineConfig, Fullscreen, RendererConfig,
+        SecondaryWindow, Settings, SimulationMode, WindowContent,
+    },
+    engine::{
+        AiEvent, AssetEvent, CheckpointEvent, DamageEvent, Engine, Frame, FrameTiming, HitEvent,
+        InputState, MenuEvent, NoiseEvent, PickupEvent, SequenceEvent, ShotEvent, StatsEvent,
+        WallHit, WaveEvent,
+    },
+    entity::{Body, Entity, EntityDef, EntityId, Faction, Hostility, Spawner},
+    inventory::{Inventory, ItemDef, Items},
+    map::{Map, MapMetadata, Maps, Preflight, Region, TileType},
+    menu::{MenuInput, Menus},
+    run, run_with_config,
+    sequence::{Lightning, Sequence},
+    sprite::{Animation, Sprite, SpriteSheet},
+    weapon::Weapon,
+};
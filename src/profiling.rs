@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use crate::{
+    camera::Camera,
+    map::{Map, TILE_SIZE},
+};
+
+/// The puffin the engine records into, for serving its scopes at the same version.
+#[cfg(feature = "puffin")]
+pub use ::profiling::puffin;
+
+/// How long the GPU spent on each part of the main view, measured with timestamp queries.
+/// Results arrive a frame or two after the frame they measure. See
+/// [`RendererConfig::gpu_profiling`](crate::config::RendererConfig::gpu_profiling).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GpuTimings {
+    /// Casting the floor and ceiling
+    pub floor: Duration,
+    /// Drawing the wall columns
+    pub walls: Duration,
+    /// Drawing sprites and text labels
+    pub sprites: Duration,
+    /// Scaling the view onto the window, or `None` when it's drawn straight to the window
+    pub post_process: Option<Duration>,
+}
+
+impl GpuTimings {
+    pub fn total(&self) -> Duration {
+        self.floor + self.walls + self.sprites + self.post_process.unwrap_or_default()
+    }
+}
+
+/// Connects the CPU profiler the engine was built for, so its scopes show up. With the `tracy`
+/// feature, this starts the Tracy client for the Tracy profiler to connect to. With `puffin`,
+/// it turns puffin's scopes on; the host serves them to puffin_viewer, for example with
+/// `puffin_http::Server`, using the re-exported [`puffin`]. Without either feature the scopes compile to nothing.
+pub(crate) fn start_cpu_profiler() {
+    #[cfg(feature = "tracy")]
+    let _ = ::profiling::tracy_client::Client::start();
+    #[cfg(feature = "puffin")]
+    ::profiling::puffin::set_scopes_on(true);
+}
+
+/// Casts and projects the main view of `map` from `camera`, `width` by `height` pixels, the
+/// way the engine does each frame but without a window or GPU. For timing the CPU side of a
+/// frame in benchmarks and tests, where a slowdown in the caster shows up on its own.
+pub fn draw_view_on_cpu(map: &Map, camera: &Camera, width: u32, height: u32) -> anyhow::Result<()> {
+    // Walls are a tile high, as in the engine
+    crate::raycaster::draw_view_headless(map, camera, width, height, TILE_SIZE, TILE_SIZE)?;
+    Ok(())
+}
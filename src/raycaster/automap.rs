@@ -0,0 +1,99 @@
+use glam::Vec2;
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    camera::Camera,
+    map::{Exploration, Map, TileType},
+};
+
+/// Pixels to a tile in the automap.
+const SCALE: u32 = 8;
+const PLAYER: Rgba<u8> = Rgba([255, 255, 255, 255]);
+/// The player in the high contrast automap, where walls are white.
+const HIGH_CONTRAST_PLAYER: Rgba<u8> = Rgba([255, 220, 0, 255]);
+const HIGH_CONTRAST_WALL: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const HIGH_CONTRAST_FLOOR: Rgba<u8> = Rgba([40, 40, 40, 255]);
+
+/// The automap: the parts of the map the player has seen, from above, with the player on it.
+#[derive(Default)]
+pub(crate) struct Automap {
+    /// The map, the player's pixel and heading and how many tiles were explored in the last
+    /// image, to skip drawing one that would look the same
+    drawn: Option<(&'static str, (i32, i32), i32, usize)>,
+    high_contrast: bool,
+}
+
+impl Automap {
+    /// The explored tiles of `map` with the player on them. `None` if nothing has changed since
+    /// the last one.
+    pub fn take_image(
+        &mut self,
+        map_key: &'static str,
+        map: &Map,
+        exploration: &Exploration,
+        camera: &Camera,
+        tile_size: f32,
+        high_contrast: bool,
+    ) -> Option<RgbaImage> {
+        let player = camera.position / tile_size * SCALE as f32;
+        let drawn = (
+            map_key,
+            (player.x as i32, player.y as i32),
+            camera.rotation.round() as i32,
+            exploration.explored_count(),
+        );
+        if self.drawn == Some(drawn) && self.high_contrast == high_contrast {
+            return None;
+        }
+        self.drawn = Some(drawn);
+        self.high_contrast = high_contrast;
+
+        let (mut image, color) = if high_contrast {
+            (render_high_contrast(map, exploration), HIGH_CONTRAST_PLAYER)
+        } else {
+            (map.render_explored(SCALE, exploration), PLAYER)
+        };
+        let facing = Vec2::from_angle(camera.rotation.to_radians());
+        for i in 0..=SCALE {
+            fill_point(&mut image, player + facing * i as f32, 0, color);
+        }
+        fill_point(&mut image, player, 2, color);
+
+        Some(image)
+    }
+}
+
+/// The explored tiles in two tones, walls white and everything else dark, for players who
+/// struggle to tell the usual colors apart.
+fn render_high_contrast(map: &Map, exploration: &Exploration) -> RgbaImage {
+    let size = map.size();
+    let mut image = RgbaImage::new(size.cols() as u32 * SCALE, size.rows() as u32 * SCALE);
+    for (row, col, tile_id) in map.cells() {
+        if !exploration.is_explored(row, col) {
+            continue;
+        }
+        let color = match map.tile_type(tile_id) {
+            Some(TileType::Wall(_) | TileType::Monitor(_)) => HIGH_CONTRAST_WALL,
+            Some(_) => HIGH_CONTRAST_FLOOR,
+            None => continue,
+        };
+        for y in 0..SCALE {
+            for x in 0..SCALE {
+                image.put_pixel(col as u32 * SCALE + x, row as u32 * SCALE + y, color);
+            }
+        }
+    }
+    image
+}
+
+/// Fills a square `radius` pixels out from a point in pixels.
+fn fill_point(image: &mut RgbaImage, center: Vec2, radius: i32, color: Rgba<u8>) {
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let (x, y) = (center.x as i32 + dx, center.y as i32 + dy);
+            if x >= 0 && y >= 0 && (x as u32) < image.width() && (y as u32) < image.height() {
+                image.put_pixel(x as u32, y as u32, color);
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{
+    raycaster::WallInstance,
+    renderer::{Renderer, TextureCategory},
+};
+
+/// What casting and projecting a view needs from the renderer: the texture layers walls and
+/// sprites are drawn with, and somewhere to put the main view's walls. [`Renderer`] draws them
+/// with the GPU, and [`HeadlessBackend`] just keeps them.
+pub(crate) trait RenderBackend {
+    fn wall_layer(&self, tile_id: u8) -> anyhow::Result<u32>;
+    fn linedef_layer(&self, path: &str) -> anyhow::Result<u32>;
+    fn sprite_layer(&self, path: &str) -> anyhow::Result<u32>;
+    /// Sets the wall drawn in a column of the main view.
+    fn set_wall_instance(&mut self, index: usize, instance: WallInstance) -> anyhow::Result<()>;
+}
+
+impl RenderBackend for Renderer {
+    fn wall_layer(&self, tile_id: u8) -> anyhow::Result<u32> {
+        Ok(self.get_texture_index(tile_id, &TextureCategory::Wall)? as u32)
+    }
+
+    fn linedef_layer(&self, path: &str) -> anyhow::Result<u32> {
+        Renderer::linedef_layer(self, path)
+    }
+
+    fn sprite_layer(&self, path: &str) -> anyhow::Result<u32> {
+        Renderer::sprite_layer(self, path)
+    }
+
+    fn set_wall_instance(&mut self, index: usize, instance: WallInstance) -> anyhow::Result<()> {
+        Renderer::set_wall_instance(self, index, instance)
+    }
+}
+
+/// A backend without a GPU, giving each wall tile its id as its texture layer and keeping the
+/// walls it's handed. For timing and testing the caster on its own.
+#[derive(Default)]
+pub(crate) struct HeadlessBackend {
+    pub(crate) walls: Vec<WallInstance>,
+}
+
+impl RenderBackend for HeadlessBackend {
+    fn wall_layer(&self, tile_id: u8) -> anyhow::Result<u32> {
+        Ok(tile_id as u32)
+    }
+
+    fn linedef_layer(&self, _path: &str) -> anyhow::Result<u32> {
+        Ok(0)
+    }
+
+    fn sprite_layer(&self, _path: &str) -> anyhow::Result<u32> {
+        Ok(0)
+    }
+
+    fn set_wall_instance(&mut self, index: usize, instance: WallInstance) -> anyhow::Result<()> {
+        if index >= self.walls.len() {
+            self.walls.resize(index + 1, WallInstance::default());
+        }
+        self.walls[index] = instance;
+        Ok(())
+    }
+}
@@ -0,0 +1,25 @@
+use glam::Vec2;
+
+use crate::{
+    entity::Entities,
+    inventory::Inventory,
+    raycaster::{doors::Doors, elevator::Elevators, spawners::Spawners},
+    stats::LevelStats,
+};
+
+/// The player and the state of their map as they were at a checkpoint, to respawn into.
+#[derive(Clone)]
+pub(crate) struct Snapshot {
+    pub name: String,
+    pub map_key: &'static str,
+    pub position: Vec2,
+    pub rotation: f32,
+    pub player_floor: f32,
+    pub elevator_tile: Option<u8>,
+    pub elevators: Elevators,
+    pub doors: Doors,
+    pub entities: Entities,
+    pub spawners: Spawners,
+    pub inventory: Inventory,
+    pub stats: LevelStats,
+}
@@ -0,0 +1,235 @@
+use glam::Vec2;
+
+use crate::{
+    map::{Map, TileType},
+    raycaster::{MAX_STEP_HEIGHT, WALL_GAP, cast_ray, doors::Doors, linedefs::LinedefIndex},
+};
+
+/// What bodies on the current map bump into and what blocks sight across it: its tiles, doors
+/// and linedefs. Borrowed apart from the rest of the engine so work spread across threads only
+/// shares what it reads.
+#[derive(Clone, Copy)]
+pub(crate) struct Terrain<'a> {
+    pub map: &'a Map,
+    pub doors: &'a Doors,
+    pub linedefs: &'a LinedefIndex,
+    pub tile_size: u16,
+}
+
+impl Terrain<'_> {
+    /// Whether a straight line from `a` to `b` clears every wall.
+    pub fn can_see(&self, a: Vec2, b: Vec2) -> bool {
+        let offset = b - a;
+        if offset == Vec2::ZERO {
+            return true;
+        }
+
+        let angle = offset.y.atan2(offset.x);
+        cast_ray(
+            a,
+            angle,
+            self.map,
+            self.linedefs,
+            self.doors,
+            self.tile_size,
+        )
+        .is_none_or(|hit| hit.dist >= offset.length())
+    }
+
+    /// The floor height of any step or ramp under a point, or 0.
+    pub fn slope_height_at(&self, position: Vec2) -> f32 {
+        let tile = (position / self.tile_size as f32).floor();
+        self.slope_height(tile.y as i32, tile.x as i32, position)
+    }
+
+    /// The floor height of a tile at a point, taking the nearest point on the tile if it's
+    /// outside. 0 for anything but a step or ramp.
+    fn slope_height(&self, row: i32, col: i32, position: Vec2) -> f32 {
+        let size = self.map.size();
+        if row < 0 || col < 0 || row as usize >= size.rows() || col as usize >= size.cols() {
+            return 0.0;
+        }
+
+        let tile_type = self
+            .map
+            .tile_id(row as usize, col as usize)
+            .and_then(|tile_id| self.map.tile_type(tile_id));
+        match tile_type {
+            Some(TileType::Slope(data)) => {
+                let uv = position / self.tile_size as f32 - Vec2::new(col as f32, row as f32);
+                data.height_at(uv.clamp(Vec2::ZERO, Vec2::ONE))
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Moves a body with a square collision box `radius` across by `step` one axis at a time, so
+    /// it slides along walls instead of stopping dead. A body already stuck in a wall can always
+    /// move, to get out. Each axis is swept through every tile the box crosses, so a step longer than a tile
+    /// still stops at the first wall instead of passing through. Against linedefs the body is a
+    /// circle, pushed back out of any it ends up overlapping.
+    pub fn slide_body(&self, position: Vec2, step: Vec2, radius: f32) -> Vec2 {
+        if step == Vec2::ZERO || self.collides(position, radius) {
+            return position + step;
+        }
+
+        // Linedefs push bodies out rather than stopping them, so long steps are split up to
+        // keep from passing through one
+        let substeps = if self.linedefs.is_empty() {
+            1
+        } else {
+            (step.length() / radius.max(1.0)).ceil() as usize
+        };
+        let step = step / substeps as f32;
+
+        let mut position = position;
+        for _ in 0..substeps {
+            position.x += self.sweep_axis(position, step.x, radius, 0);
+            position.y += self.sweep_axis(position, step.y, radius, 1);
+            position = self.linedefs.push_out(position, radius);
+        }
+        position
+    }
+
+    /// How far a collision box can move along one axis (0 for x, 1 for y), up to `distance`,
+    /// before it touches a wall or a step too tall to climb. Walks the tiles its leading edge
+    /// enters in order.
+    fn sweep_axis(&self, position: Vec2, distance: f32, radius: f32, axis: usize) -> f32 {
+        let tile_size = self.tile_size as f32;
+        let across = 1 - axis;
+        let first_across = ((position[across] - radius) / tile_size).floor() as i32;
+        let last_across = ((position[across] + radius) / tile_size).floor() as i32;
+        let floor = self.slope_height_at(position);
+        let blocked = |along: i32| {
+            // Where the body's middle would cross into the tile
+            let mut entry = position;
+            entry[axis] = if distance > 0.0 {
+                along as f32 * tile_size
+            } else {
+                (along + 1) as f32 * tile_size
+            };
+            (first_across..=last_across).any(|across_tile| {
+                let (row, col) = match axis {
+                    0 => (across_tile, along),
+                    _ => (along, across_tile),
+                };
+                // A thin wall running the way the body moves blocks it where it ends
+                let straddles_thin_wall =
+                    self.thin_wall_plane(row, col, across).is_some_and(|plane| {
+                        plane > position[across] - radius && plane < position[across] + radius
+                    });
+                self.is_solid_tile(row, col)
+                    || straddles_thin_wall
+                    || self.slope_height(row, col, entry) > floor + MAX_STEP_HEIGHT
+            })
+        };
+
+        if distance > 0.0 {
+            let edge = position[axis] + radius;
+            let first = (edge / tile_size).floor() as i32 + 1;
+            let last = ((edge + distance) / tile_size).floor() as i32;
+            if let Some(wall) = (first..=last).find(|along| blocked(*along)) {
+                // Stop just short, as a box reaching the boundary already overlaps the tile
+                return (wall as f32 * tile_size - WALL_GAP - edge).max(0.0);
+            }
+        } else if distance < 0.0 {
+            let edge = position[axis] - radius;
+            let first = (edge / tile_size).floor() as i32 - 1;
+            let last = ((edge + distance) / tile_size).floor() as i32;
+            if let Some(wall) = (last..=first).rev().find(|along| blocked(*along)) {
+                return ((wall + 1) as f32 * tile_size - edge).min(0.0);
+            }
+        }
+
+        self.stop_at_thin_walls(position, distance, radius, axis)
+    }
+
+    /// Shortens a move along one axis so a collision box stops at any thin wall facing that
+    /// way that it would pass through.
+    fn stop_at_thin_walls(&self, position: Vec2, distance: f32, radius: f32, axis: usize) -> f32 {
+        let tile_size = self.tile_size as f32;
+        let mut min = position - radius;
+        let mut max = position + radius;
+        min[axis] += distance.min(0.0);
+        max[axis] += distance.max(0.0);
+        let (min, max) = ((min / tile_size).floor(), (max / tile_size).floor());
+
+        let mut distance = distance;
+        for row in min.y as i32..=max.y as i32 {
+            for col in min.x as i32..=max.x as i32 {
+                let Some(plane) = self.thin_wall_plane(row, col, axis) else {
+                    continue;
+                };
+                if distance > 0.0 {
+                    let edge = position[axis] + radius;
+                    if edge <= plane && edge + distance > plane {
+                        distance = (plane - WALL_GAP - edge).max(0.0);
+                    }
+                } else if distance < 0.0 {
+                    let edge = position[axis] - radius;
+                    if edge >= plane && edge + distance < plane {
+                        distance = (plane + WALL_GAP - edge).min(0.0);
+                    }
+                }
+            }
+        }
+
+        distance
+    }
+
+    /// Where a tile's thin wall crosses `axis`, if it has one facing along it.
+    fn thin_wall_plane(&self, row: i32, col: i32, axis: usize) -> Option<f32> {
+        let size = self.map.size();
+        if row < 0 || col < 0 || row as usize >= size.rows() || col as usize >= size.cols() {
+            return None;
+        }
+
+        let tile_type = self
+            .map
+            .tile_id(row as usize, col as usize)
+            .and_then(|tile_id| self.map.tile_type(tile_id));
+        match tile_type {
+            Some(TileType::ThinWall(data))
+                if data.normal_axis() == axis
+                    && !self.doors.is_open((row as usize, col as usize)) =>
+            {
+                let (start, _) = data.segment(row as usize, col as usize, self.tile_size as f32);
+                Some(start[axis])
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a collision box `radius` across at `position` overlaps a wall or leaves the
+    /// map.
+    pub fn collides(&self, position: Vec2, radius: f32) -> bool {
+        let tile_size = self.tile_size as f32;
+        let min = ((position - radius) / tile_size).floor();
+        let max = ((position + radius) / tile_size).floor();
+        let overlaps = |row, col| {
+            self.is_solid_tile(row, col)
+                || (0..2).any(|axis| {
+                    self.thin_wall_plane(row, col, axis).is_some_and(|plane| {
+                        plane > position[axis] - radius && plane < position[axis] + radius
+                    })
+                })
+        };
+
+        (min.y as i32..=max.y as i32)
+            .any(|row| (min.x as i32..=max.x as i32).any(|col| overlaps(row, col)))
+    }
+
+    /// Whether a tile blocks movement. Everything outside the map does.
+    fn is_solid_tile(&self, row: i32, col: i32) -> bool {
+        let size = self.map.size();
+        if row < 0 || col < 0 || row as usize >= size.rows() || col as usize >= size.cols() {
+            return true;
+        }
+
+        let tile_type = self
+            .map
+            .tile_id(row as usize, col as usize)
+            .and_then(|tile_id| self.map.tile_type(tile_id));
+        matches!(tile_type, Some(TileType::Wall(_) | TileType::Monitor(_)))
+    }
+}
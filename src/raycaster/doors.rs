@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use crate::map::{DoorData, Map, TileType};
+
+/// How far one door tile has slid open and whether it's opening or shutting.
+#[derive(Clone)]
+struct Door {
+    data: DoorData,
+    /// From 0 (shut) to 1 (all the way open)
+    open: f32,
+    opening: bool,
+    /// Seconds left before it starts shutting once it's all the way open
+    hold_left: f32,
+}
+
+/// The current map's door tiles, by row and column.
+#[derive(Clone, Default)]
+pub(crate) struct Doors {
+    doors: HashMap<(usize, usize), Door>,
+}
+
+impl Doors {
+    /// Shuts every door in the map.
+    pub fn load_map(&mut self, map: &Map) {
+        self.doors = Self::shut_doors(map);
+    }
+
+    /// Picks up doors added to or removed from the map since it was loaded, leaving the ones
+    /// still there as they are.
+    pub fn reload_map(&mut self, map: &Map) {
+        let mut doors = Self::shut_doors(map);
+        for (tile, door) in &mut doors {
+            if let Some(old) = self.doors.remove(tile) {
+                door.open = old.open;
+                door.opening = old.opening;
+                door.hold_left = old.hold_left;
+            }
+        }
+        self.doors = doors;
+    }
+
+    fn shut_doors(map: &Map) -> HashMap<(usize, usize), Door> {
+        let size = map.size();
+        (0..size.rows())
+            .flat_map(|row| (0..size.cols()).map(move |col| (row, col)))
+            .filter_map(|(row, col)| {
+                let tile_id = map.tile_id(row, col)?;
+                match map.tile_type(tile_id) {
+                    Some(TileType::ThinWall(data)) => data.door.map(|data| {
+                        (
+                            (row, col),
+                            Door {
+                                data,
+                                open: 0.0,
+                                opening: false,
+                                hold_left: 0.0,
+                            },
+                        )
+                    }),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_door(&self, tile: (usize, usize)) -> bool {
+        self.doors.contains_key(&tile)
+    }
+
+    /// Starts a door opening, or keeps it open for another hold if it already is. Returns
+    /// whether there's a door at `tile`.
+    pub fn open(&mut self, tile: (usize, usize)) -> bool {
+        let Some(door) = self.doors.get_mut(&tile) else {
+            return false;
+        };
+        door.opening = true;
+        door.hold_left = door.data.hold;
+        true
+    }
+
+    pub fn is_opening(&self, tile: (usize, usize)) -> bool {
+        self.doors.get(&tile).is_some_and(|door| door.opening)
+    }
+
+    /// Puts a door where another copy of the map says it is: `open` from 0 to 1, and
+    /// sliding open for a whole hold if `opening`. Returns whether there's a door at `tile`.
+    pub fn set(&mut self, tile: (usize, usize), open: f32, opening: bool) -> bool {
+        let Some(door) = self.doors.get_mut(&tile) else {
+            return false;
+        };
+        door.open = if open.is_nan() {
+            0.0
+        } else {
+            open.clamp(0.0, 1.0)
+        };
+        door.opening = opening;
+        door.hold_left = if opening { door.data.hold } else { 0.0 };
+        true
+    }
+
+    /// Every door that isn't shut and still, as `(tile, open, opening)`.
+    pub fn moving(&self) -> impl Iterator<Item = ((usize, usize), f32, bool)> + '_ {
+        self.doors
+            .iter()
+            .filter(|(_, door)| door.opening || door.open > 0.0)
+            .map(|(tile, door)| (*tile, door.open, door.opening))
+    }
+
+    /// Slides the doors, shutting the ones whose hold is up unless `occupied` says something
+    /// is standing in their tile. Shutting doors open again for anything that steps in.
+    /// Returns whether any moved.
+    pub fn update(&mut self, delta_time: f32, occupied: impl Fn((usize, usize)) -> bool) -> bool {
+        let mut moved = false;
+        for (tile, door) in &mut self.doors {
+            if !door.opening && door.open > 0.0 && occupied(*tile) {
+                door.opening = true;
+                door.hold_left = door.data.hold;
+            }
+            if door.opening && door.open >= 1.0 {
+                door.hold_left -= delta_time;
+                if door.hold_left <= 0.0 && !occupied(*tile) {
+                    door.opening = false;
+                }
+            }
+
+            let change = door.data.speed * delta_time;
+            let previous = door.open;
+            door.open = if door.opening {
+                (door.open + change).min(1.0)
+            } else {
+                (door.open - change).max(0.0)
+            };
+            moved |= door.open != previous;
+        }
+        moved
+    }
+
+    /// How far the door at `tile` has slid open, from 0 to 1. 0 for anything but a door.
+    pub fn openness(&self, tile: (usize, usize)) -> f32 {
+        self.doors.get(&tile).map_or(0.0, |door| door.open)
+    }
+
+    /// Whether things can pass through the tile's door, which is only once it's all the way
+    /// open.
+    pub fn is_open(&self, tile: (usize, usize)) -> bool {
+        self.openness(tile) >= 1.0
+    }
+}
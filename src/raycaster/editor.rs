@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use glam::Vec2;
+use image::{Rgba, RgbaImage};
+use winit::keyboard::KeyCode;
+
+use crate::{
+    entity::Entity,
+    map::{Map, MapDiff},
+};
+
+/// Pixels to a tile in the editor's image.
+const SCALE: u32 = 16;
+const CURSOR: Rgba<u8> = Rgba([255, 220, 0, 255]);
+const ENTITY: Rgba<u8> = Rgba([230, 40, 40, 255]);
+const PLAYER_START: Rgba<u8> = Rgba([40, 220, 80, 255]);
+const PLAYER: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// The level editor: a copy of the current map, edited a tile at a time from above and put
+/// in place of the map when play resumes.
+///
+/// * Arrow keys or WASD - move the cursor.
+/// * Space - paint the tile under the cursor with the selected tile type.
+/// * Q | E - select the previous or next tile type. Enter selects the one under the cursor.
+/// * N - place an entity on the cursor, a copy of the map's first one if it has any.
+/// * Backspace | Delete - remove the entities and entity definitions on the cursor's tile.
+/// * P - make the cursor the player start, facing the way the player does.
+/// * Z | Y - undo or redo an edit.
+pub(crate) struct Editor {
+    map: Map,
+    tile_size: f32,
+    /// Row and column of the tile being edited
+    cursor: (usize, usize),
+    /// The map's tile ids in order, to paint with
+    palette: Vec<u8>,
+    selected: usize,
+    /// Whether the map differs from the one being played
+    changed: bool,
+    /// Whether the image has to be drawn again
+    redraw: bool,
+}
+
+impl Editor {
+    /// Starts editing `map` with the cursor on the tile at `position`.
+    pub fn new(map: &Map, position: Vec2, tile_size: f32) -> Self {
+        let size = map.size();
+        let cursor = (
+            ((position.y / tile_size).max(0.0) as usize).min(size.rows() - 1),
+            ((position.x / tile_size).max(0.0) as usize).min(size.cols() - 1),
+        );
+        let mut palette: Vec<u8> = map.tile_types().keys().copied().collect();
+        palette.sort_unstable();
+
+        Self {
+            map: map.clone(),
+            tile_size,
+            cursor,
+            palette,
+            selected: 0,
+            changed: false,
+            redraw: true,
+        }
+    }
+
+    /// The edited map, if anything was changed.
+    pub fn changed_map(&self) -> Option<&Map> {
+        self.changed.then_some(&self.map)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
+    /// The center of the cursor's tile, in world units.
+    pub fn cursor_position(&self) -> Vec2 {
+        (Vec2::new(self.cursor.1 as f32, self.cursor.0 as f32) + 0.5) * self.tile_size
+    }
+
+    /// Applies a key press. `rotation` is the player's, for the player start.
+    pub fn handle_key(&mut self, code: KeyCode, rotation: f32) {
+        match code {
+            KeyCode::ArrowUp | KeyCode::KeyW => self.move_cursor(-1, 0),
+            KeyCode::ArrowDown | KeyCode::KeyS => self.move_cursor(1, 0),
+            KeyCode::ArrowLeft | KeyCode::KeyA => self.move_cursor(0, -1),
+            KeyCode::ArrowRight | KeyCode::KeyD => self.move_cursor(0, 1),
+            KeyCode::KeyQ => self.cycle_palette(self.palette.len().saturating_sub(1)),
+            KeyCode::KeyE => self.cycle_palette(1),
+            KeyCode::Enter => {
+                let (row, col) = self.cursor;
+                let tile_id = self.map.tiles()[row][col];
+                if let Some(index) = self.palette.iter().position(|id| *id == tile_id) {
+                    self.selected = index;
+                    self.redraw = true;
+                }
+            }
+            KeyCode::Space => {
+                let (row, col) = self.cursor;
+                if let Some(tile_id) = self.palette.get(self.selected) {
+                    self.edit(MapDiff::new().with_tile(row, col, *tile_id));
+                }
+            }
+            KeyCode::KeyN => {
+                let position = self.cursor_position();
+                let mut entity = self
+                    .map
+                    .entities()
+                    .first()
+                    .cloned()
+                    .unwrap_or_else(|| Entity::new(position));
+                entity.position = position;
+                let mut entities = self.map.entities().to_vec();
+                entities.push(entity);
+                self.edit(MapDiff::new().with_entities(entities));
+            }
+            KeyCode::Backspace | KeyCode::Delete => {
+                let off_cursor = |position: Vec2| {
+                    let tile = (position / self.tile_size).floor();
+                    (tile.y as usize, tile.x as usize) != self.cursor
+                };
+                let mut diff = MapDiff::new();
+                let entities = self.map.entities();
+                if !entities.iter().all(|entity| off_cursor(entity.position)) {
+                    diff = diff.with_entities(
+                        entities
+                            .iter()
+                            .filter(|entity| off_cursor(entity.position))
+                            .cloned()
+                            .collect(),
+                    );
+                }
+                let entity_defs = self.map.entity_defs();
+                if !entity_defs.iter().all(|def| off_cursor(def.position)) {
+                    diff = diff.with_entity_defs(
+                        entity_defs
+                            .iter()
+                            .filter(|def| off_cursor(def.position))
+                            .cloned()
+                            .collect(),
+                    );
+                }
+                if !diff.is_empty() {
+                    self.edit(diff);
+                }
+            }
+            KeyCode::KeyP => {
+                let metadata = self
+                    .map
+                    .metadata()
+                    .clone()
+                    .with_player_start(self.cursor_position(), rotation);
+                self.edit(MapDiff::new().with_metadata(metadata));
+            }
+            KeyCode::KeyZ if self.map.undo() => self.changed(),
+            KeyCode::KeyY if self.map.redo() => self.changed(),
+            _ => (),
+        }
+    }
+
+    fn move_cursor(&mut self, rows: isize, cols: isize) {
+        let size = self.map.size();
+        self.cursor = (
+            self.cursor
+                .0
+                .saturating_add_signed(rows)
+                .min(size.rows() - 1),
+            self.cursor
+                .1
+                .saturating_add_signed(cols)
+                .min(size.cols() - 1),
+        );
+        self.redraw = true;
+    }
+
+    fn cycle_palette(&mut self, step: usize) {
+        if !self.palette.is_empty() {
+            self.selected = (self.selected + step) % self.palette.len();
+            self.redraw = true;
+        }
+    }
+
+    fn edit(&mut self, diff: MapDiff) {
+        self.map
+            .edit(&diff)
+            .expect("the cursor stays inside the map");
+        self.changed();
+    }
+
+    fn changed(&mut self) {
+        self.changed = true;
+        self.redraw = true;
+    }
+
+    /// The map from above with the cursor, entities, spawners and player start over it, and
+    /// the palette along the bottom with the selected tile type outlined. `None` if nothing
+    /// has changed since the last one.
+    pub fn take_image(&mut self, player: Vec2) -> Option<RgbaImage> {
+        if !self.redraw {
+            return None;
+        }
+        self.redraw = false;
+
+        let colors: HashMap<u8, Rgba<u8>> = self
+            .palette
+            .iter()
+            .map(|tile_id| (*tile_id, self.palette_color(*tile_id)))
+            .collect();
+        let preview = self.map.render_preview_with(SCALE, &colors);
+
+        // The palette goes a tile below the map, wrapping onto more rows if it's wider
+        let per_row = (preview.width() / SCALE) as usize;
+        let palette_rows = self.palette.len().div_ceil(per_row) as u32;
+        let mut image = RgbaImage::from_pixel(
+            preview.width(),
+            preview.height() + (palette_rows + 1) * SCALE,
+            Rgba([0, 0, 0, 255]),
+        );
+        image::imageops::replace(&mut image, &preview, 0, 0);
+
+        let entity_positions = self.map.entities().iter().map(|entity| entity.position);
+        let def_positions = self.map.entity_defs().iter().map(|def| def.position);
+        let spawner_positions = self
+            .map
+            .spawners()
+            .iter()
+            .map(|spawner| spawner.entity().position);
+        for position in entity_positions
+            .chain(def_positions)
+            .chain(spawner_positions)
+        {
+            self.fill_point(&mut image, position, 2, ENTITY);
+        }
+        if let Some((position, rotation)) = self.map.metadata().player_start() {
+            self.fill_point(&mut image, position, 3, PLAYER_START);
+            let facing = Vec2::from_angle(rotation.to_radians()) * self.tile_size * 0.5;
+            self.fill_point(&mut image, position + facing, 1, PLAYER_START);
+        }
+        self.fill_point(&mut image, player, 2, PLAYER);
+        outline(&mut image, self.cursor.1 as u32, self.cursor.0 as u32);
+
+        let top = preview.height() / SCALE + 1;
+        for (index, tile_id) in self.palette.iter().enumerate() {
+            let (x, y) = ((index % per_row) as u32, top + (index / per_row) as u32);
+            for py in 1..SCALE - 1 {
+                for px in 1..SCALE - 1 {
+                    image.put_pixel(x * SCALE + px, y * SCALE + py, colors[tile_id]);
+                }
+            }
+            if index == self.selected {
+                outline(&mut image, x, y);
+            }
+        }
+
+        Some(image)
+    }
+
+    /// A tile id's preview color, shaded by the id so tile types of the same kind can be told
+    /// apart.
+    fn palette_color(&self, tile_id: u8) -> Rgba<u8> {
+        let Rgba([r, g, b, a]) = self.map.tile_color(tile_id);
+        let shade = 0.75 + 0.5 * ((tile_id as u32 * 5) % 8) as f32 / 7.0;
+        let scaled = |channel: u8| (channel as f32 * shade).min(255.0) as u8;
+        Rgba([scaled(r), scaled(g), scaled(b), a])
+    }
+
+    /// Fills a square `radius` pixels out from a point in world units.
+    fn fill_point(&self, image: &mut RgbaImage, position: Vec2, radius: i32, color: Rgba<u8>) {
+        let center = position / self.tile_size * SCALE as f32;
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let (x, y) = (center.x as i32 + dx, center.y as i32 + dy);
+                if x >= 0 && y >= 0 && (x as u32) < image.width() && (y as u32) < image.height() {
+                    image.put_pixel(x as u32, y as u32, color);
+                }
+            }
+        }
+    }
+}
+
+/// Outlines the tile at column `x`, row `y` of the image.
+fn outline(image: &mut RgbaImage, x: u32, y: u32) {
+    for i in 0..SCALE {
+        image.put_pixel(x * SCALE + i, y * SCALE, CURSOR);
+        image.put_pixel(x * SCALE + i, y * SCALE + SCALE - 1, CURSOR);
+        image.put_pixel(x * SCALE, y * SCALE + i, CURSOR);
+        image.put_pixel(x * SCALE + SCALE - 1, y * SCALE + i, CURSOR);
+    }
+}
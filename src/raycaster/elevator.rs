@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use crate::map::{ElevatorData, Map, TileType};
+
+/// Where one elevator tile type's floor is and where it's headed. Every tile of the type moves
+/// together.
+#[derive(Clone)]
+struct Elevator {
+    data: ElevatorData,
+    height: f32,
+    raised: bool,
+}
+
+/// The floor heights of the current map's elevators.
+#[derive(Clone, Default)]
+pub(crate) struct Elevators {
+    elevators: HashMap<u8, Elevator>,
+}
+
+impl Elevators {
+    /// Puts every elevator in the map back at its low level.
+    pub fn load_map(&mut self, map: &Map) {
+        self.elevators = map
+            .tile_types()
+            .iter()
+            .filter_map(|(tile_id, tile_type)| match tile_type {
+                TileType::Elevator(data) => Some((
+                    *tile_id,
+                    Elevator {
+                        data: *data,
+                        height: data.low,
+                        raised: false,
+                    },
+                )),
+                _ => None,
+            })
+            .collect();
+    }
+
+    /// Sends an elevator to its other level, turning it around if it's moving. Returns whether
+    /// `tile_id` is an elevator.
+    pub fn activate(&mut self, tile_id: u8) -> bool {
+        let Some(elevator) = self.elevators.get_mut(&tile_id) else {
+            return false;
+        };
+        elevator.raised = !elevator.raised;
+        true
+    }
+
+    /// Moves the elevators towards their levels. Returns whether any moved.
+    pub fn update(&mut self, delta_time: f32) -> bool {
+        let mut moved = false;
+        for elevator in self.elevators.values_mut() {
+            let target = if elevator.raised {
+                elevator.data.high
+            } else {
+                elevator.data.low
+            };
+            let change = elevator.data.speed * delta_time;
+            let previous = elevator.height;
+            elevator.height = if elevator.height < target {
+                (elevator.height + change).min(target)
+            } else {
+                (elevator.height - change).max(target)
+            };
+            moved |= elevator.height != previous;
+        }
+        moved
+    }
+
+    /// The floor height of a tile, which is 0 for anything but an elevator.
+    pub fn floor_height(&self, tile_id: Option<u8>) -> f32 {
+        tile_id
+            .and_then(|tile_id| self.elevators.get(&tile_id))
+            .map_or(0.0, |elevator| elevator.height)
+    }
+}
@@ -0,0 +1,109 @@
+use crate::sequence::Lightning;
+
+/// The brightest photosensitive safe mode lets lightning make the scene, as extra brightness.
+const SAFE_BRIGHTNESS: f32 = 0.25;
+/// How quickly lightning may change the scene's brightness in photosensitive safe mode, per
+/// second. A strike swells and fades over about a second rather than flickering.
+const SAFE_RATE: f32 = 0.5;
+
+/// A strike still lighting the scene.
+#[derive(Clone)]
+struct Flash {
+    brightness: f32,
+    /// Seconds
+    duration: f32,
+    elapsed: f32,
+}
+
+/// Thunder on its way to the player.
+#[derive(Clone)]
+struct Thunder {
+    sound_path: &'static str,
+    volume: f32,
+    /// Seconds left before it's heard
+    delay: f32,
+}
+
+/// The flashes and thunder of lightning struck in the current map.
+#[derive(Clone, Default)]
+pub(crate) struct LightningState {
+    flashes: Vec<Flash>,
+    thunder: Vec<Thunder>,
+    /// Extra brightness the scene is lit with, added to 1
+    brightness: f32,
+}
+
+impl LightningState {
+    pub fn load_map(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn strike(&mut self, lightning: Lightning) {
+        self.flashes.push(Flash {
+            brightness: lightning.brightness,
+            duration: lightning.duration.as_secs_f32(),
+            elapsed: 0.0,
+        });
+        if let Some(sound_path) = lightning.thunder_path {
+            self.thunder.push(Thunder {
+                sound_path,
+                volume: lightning.thunder_volume(),
+                delay: lightning.thunder_delay(),
+            });
+        }
+    }
+
+    /// Flickers the flashes, weakened by `flash_reduction` and kept dim and slow when
+    /// `photosensitive_safe`. Returns the thunder that has arrived, as sounds and volumes.
+    pub fn update(
+        &mut self,
+        delta_time: f32,
+        flash_reduction: f32,
+        photosensitive_safe: bool,
+    ) -> Vec<(&'static str, f32)> {
+        for flash in &mut self.flashes {
+            flash.elapsed += delta_time;
+        }
+        self.flashes.retain(|flash| flash.elapsed < flash.duration);
+        let target = self
+            .flashes
+            .iter()
+            .map(|flash| flash.brightness * flicker(flash.elapsed / flash.duration))
+            .sum::<f32>()
+            * (1.0 - flash_reduction);
+        self.brightness = if photosensitive_safe {
+            let step = SAFE_RATE * delta_time;
+            let target = target.min(SAFE_BRIGHTNESS);
+            self.brightness + (target - self.brightness).clamp(-step, step)
+        } else {
+            target
+        };
+
+        let mut arrived = Vec::new();
+        self.thunder.retain_mut(|thunder| {
+            thunder.delay -= delta_time;
+            if thunder.delay > 0.0 {
+                return true;
+            }
+            arrived.push((thunder.sound_path, thunder.volume));
+            false
+        });
+        arrived
+    }
+
+    /// Extra brightness the scene is lit with, where 1 doubles it.
+    pub fn brightness(&self) -> f32 {
+        self.brightness
+    }
+}
+
+/// How bright a flash is `t` of the way through, from 0 to 1: the stroke, a dip, a return
+/// stroke, then a fade.
+fn flicker(t: f32) -> f32 {
+    match t {
+        t if t < 0.2 => 1.0,
+        t if t < 0.35 => 0.2,
+        t if t < 0.5 => 0.8,
+        t => 0.8 * (1.0 - t) / 0.5,
+    }
+}
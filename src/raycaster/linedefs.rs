@@ -0,0 +1,171 @@
+use glam::Vec2;
+
+use crate::map::{Linedef, Map};
+
+/// A linedef a ray hit. See [`LinedefIndex::cast`].
+pub(crate) struct LinedefHit {
+    /// Distance along the ray
+    pub dist: f32,
+    pub position: Vec2,
+    /// Index into the map's linedefs
+    pub index: usize,
+    /// Horizontal texture coordinate, repeating every tile along the linedef
+    pub tex_u: f32,
+}
+
+/// The current map's linedefs, bucketed by the tiles their bounding boxes cover so a ray only
+/// tests the ones near the tiles it passes through.
+#[derive(Default)]
+pub(crate) struct LinedefIndex {
+    linedefs: Vec<Linedef>,
+    /// Linedef indices per tile, row by row
+    cells: Vec<Vec<u32>>,
+    cols: usize,
+    rows: usize,
+    tile_size: f32,
+}
+
+impl LinedefIndex {
+    pub fn load_map(&mut self, map: &Map, tile_size: u16) {
+        let size = map.size();
+        self.linedefs = map.linedefs().to_vec();
+        self.cols = size.cols();
+        self.rows = size.rows();
+        self.tile_size = tile_size as f32;
+        self.cells = vec![Vec::new(); self.cols * self.rows];
+
+        for (index, linedef) in self.linedefs.iter().enumerate() {
+            let min = self.tile_of(linedef.start.min(linedef.end));
+            let max = self.tile_of(linedef.start.max(linedef.end));
+            for row in min.1..=max.1 {
+                for col in min.0..=max.0 {
+                    self.cells[row * self.cols + col].push(index as u32);
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.linedefs.is_empty()
+    }
+
+    /// The tile under a point as (col, row), clamped into the map.
+    fn tile_of(&self, position: Vec2) -> (usize, usize) {
+        let tile = (position / self.tile_size).floor();
+        (
+            (tile.x.max(0.0) as usize).min(self.cols.saturating_sub(1)),
+            (tile.y.max(0.0) as usize).min(self.rows.saturating_sub(1)),
+        )
+    }
+
+    /// Finds the nearest linedef along a ray from `origin` at `angle` radians, no further than
+    /// `max_dist`. Walks the tiles along the ray and stops at the first one holding a hit.
+    pub fn cast(&self, origin: Vec2, angle: f32, max_dist: f32) -> Option<LinedefHit> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let direction = Vec2::from_angle(angle);
+        let mut tile = (origin / self.tile_size).floor();
+        let step = direction.signum();
+        let inverse = direction.abs().max(Vec2::splat(1e-6)).recip();
+        let along_delta = self.tile_size * inverse;
+        let next_edge = (tile + step.max(Vec2::ZERO)) * self.tile_size;
+        let mut next_along = (next_edge - origin).abs() * inverse;
+        let mut along = 0.0;
+
+        while along < max_dist {
+            if tile.x < 0.0
+                || tile.y < 0.0
+                || tile.x as usize >= self.cols
+                || tile.y as usize >= self.rows
+            {
+                return None;
+            }
+
+            // A linedef in this tile can be hit beyond it, so only a hit inside counts yet
+            let exit = next_along.min_element().min(max_dist);
+            let cell = &self.cells[tile.y as usize * self.cols + tile.x as usize];
+            let nearest = cell
+                .iter()
+                .filter_map(|&index| self.intersect(index as usize, origin, direction))
+                .filter(|hit| hit.dist <= exit)
+                .min_by(|a, b| a.dist.total_cmp(&b.dist));
+            if nearest.is_some() {
+                return nearest;
+            }
+
+            along = exit;
+            if next_along.x < next_along.y {
+                tile.x += step.x;
+                next_along.x += along_delta.x;
+            } else {
+                tile.y += step.y;
+                next_along.y += along_delta.y;
+            }
+        }
+
+        None
+    }
+
+    fn intersect(&self, index: usize, origin: Vec2, direction: Vec2) -> Option<LinedefHit> {
+        let linedef = &self.linedefs[index];
+        let segment = linedef.end - linedef.start;
+        let denom = direction.perp_dot(segment);
+        if denom == 0.0 {
+            return None;
+        }
+
+        let to_start = linedef.start - origin;
+        let dist = to_start.perp_dot(segment) / denom;
+        let t = to_start.perp_dot(direction) / denom;
+        if dist < 0.0 || !(0.0..=1.0).contains(&t) {
+            return None;
+        }
+
+        let along_linedef = t * segment.length();
+        Some(LinedefHit {
+            dist,
+            position: origin + direction * dist,
+            index,
+            tex_u: (along_linedef / self.tile_size).fract(),
+        })
+    }
+
+    pub fn texture_path(&self, index: usize) -> &'static str {
+        self.linedefs[index].texture_path
+    }
+
+    /// Moves a circle `radius` across out of any linedefs it overlaps.
+    pub fn push_out(&self, position: Vec2, radius: f32) -> Vec2 {
+        if self.is_empty() {
+            return position;
+        }
+
+        let min = self.tile_of(position - radius);
+        let max = self.tile_of(position + radius);
+        let mut position = position;
+        for row in min.1..=max.1 {
+            for col in min.0..=max.0 {
+                for &index in &self.cells[row * self.cols + col] {
+                    let linedef = &self.linedefs[index as usize];
+                    let segment = linedef.end - linedef.start;
+                    let t = ((position - linedef.start).dot(segment)
+                        / segment.length_squared().max(f32::EPSILON))
+                    .clamp(0.0, 1.0);
+                    let offset = position - (linedef.start + segment * t);
+                    let distance = offset.length();
+                    if distance < radius {
+                        // Exactly on the line, push out to the linedef's right
+                        let normal = offset
+                            .try_normalize()
+                            .unwrap_or_else(|| segment.perp().normalize_or_zero());
+                        position += normal * (radius - distance);
+                    }
+                }
+            }
+        }
+
+        position
+    }
+}
@@ -1,6 +1,11 @@
 use std::ops::{Add, Rem, Sub};
 
-use crate::raycaster::{Position, TileSide};
+use glam::Vec2;
+
+use crate::{
+    map::{ThinWallAxis, ThinWallData},
+    raycaster::{Position, TileSide},
+};
 
 pub(crate) trait CustomMath {
     fn keep_in_range(&self, min: Self, max: Self) -> Self;
@@ -16,6 +21,7 @@ where
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn ray_line_intersection(
     rx1: f32,
     ry1: f32,
@@ -53,89 +59,37 @@ pub(crate) struct IntersectionData {
     pub side: TileSide,
 }
 
-pub(crate) fn ray_tile_intersection(
-    rx1: f32,
-    ry1: f32,
+/// Where a ray meets a thin wall, if it does, and which face it sees. `open` is how far a door
+/// has slid along itself, from 0 to 1, leaving a gap at its start.
+pub(crate) fn ray_thin_wall_intersection(
+    origin: Vec2,
+    angle: f32,
+    data: &ThinWallData,
+    open: f32,
     row: usize,
     col: usize,
     tile_size: u16,
-    ray_angle: f32,
-    sides: [TileSide; 2],
 ) -> Option<IntersectionData> {
-    let x1: f32 = (col as u16 * tile_size).into();
-    let y1: f32 = (row as u16 * tile_size).into();
-
-    let x2 = x1 + tile_size as f32;
-    let y2 = y1;
-
-    let x3 = x2;
-    let y3 = y1 + tile_size as f32;
-
-    let x4 = x1;
-    let y4 = y3;
-
-    let mut record = f32::INFINITY;
-    let mut closest: Option<Position> = None;
-    let mut side = TileSide::Top;
-
-    let mut tx1;
-    let mut ty1;
-    let mut tx2;
-    let mut ty2;
-
-    for i in 0..2 {
-        match sides[i] {
-            TileSide::Top => {
-                tx1 = x1;
-                ty1 = y1;
-                tx2 = x2;
-                ty2 = y2;
-            }
-            TileSide::Right => {
-                tx1 = x2;
-                ty1 = y2;
-                tx2 = x3;
-                ty2 = y3;
-            }
-            TileSide::Bottom => {
-                tx1 = x3;
-                ty1 = y3;
-                tx2 = x4;
-                ty2 = y4;
-            }
-            TileSide::Left => {
-                tx1 = x4;
-                ty1 = y4;
-                tx2 = x1;
-                ty2 = y1;
-            }
-        }
-
-        let intersection = ray_line_intersection(rx1, ry1, 1.0, ray_angle, tx1, ty1, tx2, ty2);
-
-        if let Some(pos) = intersection {
-            let dx = (rx1 - pos.x).abs();
-            let dy = (ry1 - pos.y).abs();
-            let d = (dx * dx + dy * dy).sqrt();
-
-            record = d.min(record);
-            if d <= record {
-                record = d;
-                closest = intersection;
-                side = sides[i];
-            }
-        }
-    }
-
-    if let Some(pos) = closest {
-        return Some(IntersectionData {
-            dist: record,
-            intersection: pos,
-            side,
-        });
+    if open >= 1.0 {
+        return None;
     }
-
-    None
+    let (start, end) = data.segment(row, col, tile_size as f32);
+    let start = start + (end - start) * open;
+    let intersection = ray_line_intersection(
+        origin.x, origin.y, 1.0, angle, start.x, start.y, end.x, end.y,
+    )?;
+    let side = match data.axis {
+        ThinWallAxis::Horizontal if angle.sin() > 0.0 => TileSide::Top,
+        ThinWallAxis::Horizontal => TileSide::Bottom,
+        ThinWallAxis::Vertical if angle.cos() > 0.0 => TileSide::Left,
+        ThinWallAxis::Vertical => TileSide::Right,
+    };
+
+    Some(IntersectionData {
+        dist: origin.distance(Vec2::new(intersection.x, intersection.y)),
+        intersection,
+        side,
+    })
 }
 
 #[cfg(test)]
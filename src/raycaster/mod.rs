@@ -1,401 +1,4251 @@
 #[cfg(test)]
 mod tests;
-use std::{f32::consts::PI, sync::Arc, vec};
+use std::{
+    collections::{HashMap, VecDeque},
+    f32::consts::PI,
+    sync::Arc,
+    time::Duration,
+    vec,
+};
+mod automap;
+mod backend;
+mod checkpoint;
+mod collision;
+mod doors;
+mod editor;
+mod elevator;
+mod lightning;
+mod linedefs;
 mod math;
-use anyhow::Ok;
+mod noise;
+mod pathfinding;
+mod ray_step;
+mod rays;
+mod sequencer;
+mod sky;
+mod spawners;
+mod weapons;
+mod weather;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+use anyhow::{Context, Ok};
 use glam::Vec2;
-use winit::{
-    dpi::PhysicalPosition,
-    event_loop::{self, ActiveEventLoop},
-    keyboard::KeyCode,
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+use winit::keyboard::KeyCode;
+
+#[cfg(debug_assertions)]
+use crate::debug::DebugFlags;
+use crate::{
+    ai::{Ai, AiState},
+    audio::{Audio, AudioOutput, SoundEmitter},
+    camera::Camera,
+    config::{
+        AccessibilityConfig, AudioConfig, DynamicResolution, FIXED_TIMESTEP, RearView, Settings,
+        SimulationMode, Stereo, TopDown, WindowContent,
+    },
+    engine::{
+        AiEvent, AssetEvent, CheckpointEvent, DamageEvent, FrameTiming, HitEvent, InputState,
+        MenuEvent, NoiseEvent, PickupEvent, SequenceEvent, ShotEvent, StatsEvent,
+        WallHit as PublicWallHit, WaveEvent,
+    },
+    entity::{
+        Body, Collider, CollisionLayers, Contact, Entities, Entity, EntityCulling, EntityDef,
+        EntityId, EntityTypes, Faction, Hostility, SpatialHash, resolve_collisions,
+        update_partitioned,
+    },
+    gpu::DeviceReport,
+    inventory::{Inventory, ItemDef, Items},
+    map::{
+        Exploration, Fog, MAX_CLOUD_LAYERS, Map, MapChange, MapDiff, MapMetadata, Maps, Migrations,
+        Region, ReliefLight, TILE_SIZE, TileDataFC, TileType, WaterData,
+    },
+    menu::{MenuInput, Menus},
+    profiling::GpuTimings,
+    raycaster::{
+        automap::Automap,
+        backend::{HeadlessBackend, RenderBackend},
+        checkpoint::Snapshot,
+        collision::Terrain,
+        doors::Doors,
+        editor::Editor,
+        elevator::Elevators,
+        lightning::LightningState,
+        linedefs::LinedefIndex,
+        math::{CustomMath, ray_thin_wall_intersection},
+        pathfinding::PathStamp,
+        ray_step::RayStepController,
+        rays::{RayHit, Rays},
+        sequencer::Sequencer,
+        sky::Sky,
+        spawners::Spawners,
+        weapons::Arsenal,
+        weather::WeatherState,
+    },
+    renderer::{self, MAX_FLAT_SPRITES, PostEffect, Renderer, TopDownCamera},
+    rng::Rng,
+    sequence::{Lightning, Sequence},
+    sprite::Sprite,
+    stats::{LevelStats, Objective},
+    text::TextLabel,
+    weapon::{FireMode, Weapon},
 };
 
-use crate::{
-    map::{Map, Maps, TileType},
-    raycaster::math::{CustomMath, ray_tile_intersection},
-    renderer::{self, Renderer},
-};
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TileSide {
+    Top,    // 0
+    Left,   // 1
+    Bottom, // 2
+    Right,  // 3
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Position {
+    x: f32,
+    y: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct WallInstance {
+    screen_x: f32,
+    top: f32,
+    height: f32,
+    tex_u: f32,
+    tex_layer: u32,
+    /// Screen row below which a step or ramp in front hides the wall
+    floor_clip: f32,
+    /// Screen row above which a lower ceiling in front hides the wall
+    ceiling_clip: f32,
+    /// The fog color, and in alpha how much of it covers the wall
+    fog: [f32; 4],
+    /// Direction to the light normal maps are lit by: across the texture, up it and out of
+    /// the wall
+    relief_light: [f32; 3],
+}
+
+impl Default for WallInstance {
+    fn default() -> Self {
+        Self {
+            screen_x: 0.0,
+            top: 0.0,
+            height: 0.0,
+            tex_u: 0.0,
+            tex_layer: 0,
+            floor_clip: f32::MAX,
+            ceiling_clip: f32::MIN,
+            fog: [0.0; 4],
+            relief_light: [0.0; 3],
+        }
+    }
+}
+
+/// One screen column of a sprite or of a text label's glyph.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct SpriteInstance {
+    screen_x: f32,
+    top: f32,
+    height: f32,
+    tex_u: f32,
+    tex_layer: u32,
+    /// Multiplies the texel color. Alpha is the opacity.
+    tint: [f32; 4],
+    /// The fog color, and in alpha how much of it covers the sprite
+    fog: [f32; 4],
+}
+
+/// Camera uniform for the floor pass. Matches `FloorCamera` in floor.wgsl.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct FloorCamera {
+    position: [f32; 2],
+    rotation: f32,
+    fov: f32,
+    height: f32,
+    horizon: f32,
+    dist_to_projection_plane: f32,
+    tile_size: f32,
+    wall_height: f32,
+    flat_sprite_count: u32,
+    /// Above zero, floor within this distance of a solid tile is highlighted
+    collision_radius: f32,
+    /// The tallest step or ramp, or 0 when the floor is flat everywhere
+    max_floor_height: f32,
+    /// Set when some tiles have lowered ceilings or are open to the sky
+    ceilings_vary: u32,
+    has_sky: u32,
+    /// Fog starts and ends at these distances. Both are 0 without fog.
+    fog_start: f32,
+    fog_end: f32,
+    fog_color: [f32; 3],
+    /// Seconds since the engine started, which ripples reflective floors
+    time: f32,
+    /// Multiplies the sky and clouds
+    sky_tint: [f32; 3],
+    cloud_count: u32,
+    /// Per cloud layer: how far it's drifted, then its opacity
+    clouds: [[f32; 4]; MAX_CLOUD_LAYERS],
+}
+
+/// A sprite lying on the floor. Matches `FlatSprite` in floor.wgsl.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct FlatSpriteInstance {
+    position: [f32; 2],
+    half_size: f32,
+    opacity: f32,
+    layer: u32,
+    _pad: [u32; 3],
+}
+
+impl WallInstance {
+    /// Where the column's wall is on screen and what it shows, for the floor pass to mirror:
+    /// top, height, texture coordinate, then layer.
+    pub(crate) fn reflected_column(&self) -> [f32; 4] {
+        [self.top, self.height, self.tex_u, self.tex_layer as f32]
+    }
+
+    /// Blends towards `other`, for a column between two cast rays on the same wall face.
+    fn lerp(&self, other: &WallInstance, t: f32, column: usize) -> WallInstance {
+        WallInstance {
+            screen_x: column as f32,
+            top: self.top + (other.top - self.top) * t,
+            height: self.height + (other.height - self.height) * t,
+            tex_u: self.tex_u + (other.tex_u - self.tex_u) * t,
+            tex_layer: self.tex_layer,
+            floor_clip: self.floor_clip,
+            ceiling_clip: self.ceiling_clip,
+            fog: self.fog,
+            relief_light: self.relief_light,
+        }
+    }
+}
+
+/// The fog color with how much of it covers something `distance` away, for the shaders.
+fn fog_color(fog: Fog, distance: f32) -> [f32; 4] {
+    let [r, g, b] = fog.color;
+    [r, g, b, fog.amount(distance)]
+}
+
+/// Where a hit lies across the tile face, as a texture coordinate.
+fn face_u(hit: Position, side: TileSide, tile_size: u16) -> f32 {
+    let use_x_for_offset = matches!(side, TileSide::Top | TileSide::Bottom);
+
+    // Tile-local offset for texture column start
+    let offset_temp = if use_x_for_offset {
+        (hit.x.floor() as i32).rem_euclid(tile_size as i32)
+    } else {
+        (hit.y.floor() as i32).rem_euclid(tile_size as i32)
+    };
+
+    let offset = if use_x_for_offset {
+        // Mirror
+        (tile_size as i32) - offset_temp - 1
+    } else {
+        offset_temp
+    } as f32;
+
+    (offset + 0.5) / (tile_size as f32)
+}
+
+/// The direction to `light` from a wall face hit at `position`, across the face in the way
+/// its texture runs, up and out of it. The player's light is taken at the middle of the wall.
+fn face_light(
+    light: ReliefLight,
+    camera: &Camera,
+    position: Vec2,
+    side: TileSide,
+    wall_height: f32,
+) -> [f32; 3] {
+    let out = match side {
+        TileSide::Top => Vec2::NEG_Y,
+        TileSide::Bottom => Vec2::Y,
+        TileSide::Left => Vec2::NEG_X,
+        TileSide::Right => Vec2::X,
+    };
+    // Top and bottom faces are mirrored, so their textures run towards -x
+    let across = if out.x == 0.0 { Vec2::NEG_X } else { Vec2::Y };
+    let toward = match light {
+        ReliefLight::Player => (camera.position - position)
+            .extend(camera.height as f32 - wall_height / 2.0)
+            .normalize_or_zero(),
+        ReliefLight::Directional { angle, elevation } => {
+            let elevation = elevation.to_radians();
+            (Vec2::from_angle(angle.to_radians()) * elevation.cos()).extend(elevation.sin())
+        }
+    };
+    [
+        toward.truncate().dot(across),
+        toward.z,
+        toward.truncate().dot(out),
+    ]
+}
+
+/// Where a wall's texture comes from.
+enum WallSurface {
+    Tile(u8),
+    Linedef(&'static str),
+}
+
+/// The wall a ray hit. See [`cast_ray`].
+struct WallHit {
+    /// Distance along the ray
+    dist: f32,
+    intersection: Position,
+    side: TileSide,
+    /// Tiles by index, then linedefs after the last tile
+    tile_index: usize,
+    surface: WallSurface,
+    tex_u: f32,
+}
+
+/// Finds the nearest wall or linedef along a ray from `origin` at `angle` radians, if it hits
+/// one.
+fn cast_ray(
+    origin: Vec2,
+    angle: f32,
+    current_map: &Map,
+    linedefs: &LinedefIndex,
+    doors: &Doors,
+    tile_size: u16,
+) -> Option<WallHit> {
+    let map_size = current_map.size();
+    let map_cols = map_size.cols();
+    let map_rows = map_size.rows();
+
+    let angle = angle.keep_in_range(0.0, 2.0 * PI);
+    let size = tile_size as f32;
+    let dir = Vec2::from_angle(angle);
+    let sign = |dir: f32| {
+        if dir > 0.0 {
+            1
+        } else if dir < 0.0 {
+            -1
+        } else {
+            0
+        }
+    };
+    let step = (sign(dir.x), sign(dir.y));
+    // Distance along the ray to the first tile edge crossed on each axis, and between edges
+    let first_edge = |origin: f32, dir: f32| {
+        let tile = (origin / size).floor();
+        if dir > 0.0 {
+            ((tile + 1.0) * size - origin) / dir
+        } else if dir < 0.0 {
+            (tile * size - origin) / dir
+        } else {
+            f32::INFINITY
+        }
+    };
+    let mut next_edge = Vec2::new(first_edge(origin.x, dir.x), first_edge(origin.y, dir.y));
+    let edge_spacing = Vec2::new(size / dir.x.abs(), size / dir.y.abs());
+
+    let mut col = (origin.x / size).floor() as i32;
+    let mut row = (origin.y / size).floor() as i32;
+    // Set once the ray has stepped into a tile, to the distance and side it came in by
+    let mut entered: Option<(f32, TileSide)> = None;
+    let mut closest: Option<WallHit> = None;
+    loop {
+        let (cols, rows) = (map_cols as i32, map_rows as i32);
+        if col < 0 || row < 0 || col >= cols || row >= rows {
+            // Past the edge and heading further out, so there's nothing left to hit
+            if (col < 0 && step.0 <= 0)
+                || (col >= cols && step.0 >= 0)
+                || (row < 0 && step.1 <= 0)
+                || (row >= rows && step.1 >= 0)
+            {
+                break;
+            }
+        } else {
+            let (r, c) = (row as usize, col as usize);
+            let tile_id = current_map.tile_id(r, c).unwrap();
+            let hit = match (current_map.tile_type(tile_id), entered) {
+                // Solid tiles are hit on the edge the ray came in by. The ray starting inside
+                // one sees out of it.
+                (Some(TileType::Wall(_) | TileType::Monitor(_)), Some((dist, side))) => {
+                    let mut intersection = origin + dir * dist;
+                    match side {
+                        TileSide::Left => intersection.x = c as f32 * size,
+                        TileSide::Right => intersection.x = (c + 1) as f32 * size,
+                        TileSide::Top => intersection.y = r as f32 * size,
+                        TileSide::Bottom => intersection.y = (r + 1) as f32 * size,
+                    }
+                    Some((dist, intersection, side, 0.0))
+                }
+                (Some(TileType::ThinWall(data)), _) => {
+                    let open = doors.openness((r, c));
+                    ray_thin_wall_intersection(origin, angle, &data, open, r, c, tile_size).map(
+                        |data| {
+                            let intersection = Vec2::new(data.intersection.x, data.intersection.y);
+                            // The texture slides with a door
+                            (data.dist, intersection, data.side, open * size)
+                        },
+                    )
+                }
+                _ => None,
+            };
+
+            if let Some((dist, intersection, side, slide)) = hit {
+                closest = Some(WallHit {
+                    dist,
+                    intersection: Position {
+                        x: intersection.x,
+                        y: intersection.y,
+                    },
+                    side,
+                    tile_index: r * map_cols + c,
+                    surface: WallSurface::Tile(tile_id),
+                    tex_u: face_u(
+                        Position {
+                            x: intersection.x - slide,
+                            y: intersection.y - slide,
+                        },
+                        side,
+                        tile_size,
+                    ),
+                });
+                break;
+            }
+        }
+
+        if next_edge.x < next_edge.y {
+            entered = Some((
+                next_edge.x,
+                if step.0 > 0 {
+                    TileSide::Left
+                } else {
+                    TileSide::Right
+                },
+            ));
+            next_edge.x += edge_spacing.x;
+            col += step.0;
+        } else {
+            entered = Some((
+                next_edge.y,
+                if step.1 > 0 {
+                    TileSide::Top
+                } else {
+                    TileSide::Bottom
+                },
+            ));
+            next_edge.y += edge_spacing.y;
+            row += step.1;
+        }
+    }
+
+    let max_dist = closest
+        .as_ref()
+        .map_or(f32::INFINITY, |closest| closest.dist);
+    if let Some(hit) = linedefs.cast(origin, angle, max_dist) {
+        closest = Some(WallHit {
+            dist: hit.dist,
+            intersection: Position {
+                x: hit.position.x,
+                y: hit.position.y,
+            },
+            side: TileSide::Top,
+            tile_index: map_rows * map_cols + hit.index,
+            surface: WallSurface::Linedef(linedefs.texture_path(hit.index)),
+            tex_u: hit.tex_u,
+        });
+    }
+
+    closest
+}
+
+/// The columns a ray is cast for when each covers `step` columns: every `step`th and the last.
+/// The velocity that takes a body at `speed` from `position` towards `target`, and whether it
+/// lands on the target this step.
+fn steer(position: Vec2, target: Vec2, speed: f32, delta_time: f32) -> (Vec2, bool) {
+    let offset = target - position;
+    if offset.length() > speed * delta_time {
+        return (offset.normalize() * speed, false);
+    }
+    let velocity = if delta_time > 0.0 {
+        offset / delta_time
+    } else {
+        Vec2::ZERO
+    };
+    (velocity, true)
+}
+
+/// The nearest hostile body an awake entity with an AI can see, and where it is.
+fn sighting(
+    terrain: Terrain<'_>,
+    hostility: &Hostility,
+    id: EntityId,
+    entity: &Entity,
+    bodies: &[(Body, Vec2, Faction)],
+    hash: &SpatialHash,
+) -> Option<(Body, Vec2)> {
+    let ai = entity.ai.as_ref().filter(|_| !entity.dormant)?;
+
+    // In body order, so equally near targets are picked the same way every run
+    let mut nearby: Vec<usize> = hash.query(entity.position, ai.sight_range).collect();
+    nearby.sort_unstable();
+    let mut targets: Vec<(Body, Vec2, f32)> = nearby
+        .into_iter()
+        .map(|index| &bodies[index])
+        .filter(|(body, _, faction)| {
+            *body != Body::Entity(id) && hostility.is_hostile(entity.faction, *faction)
+        })
+        .map(|(body, position, _)| (*body, *position, position.distance(entity.position)))
+        .filter(|(_, _, distance)| *distance <= ai.sight_range)
+        .collect();
+    targets.sort_by(|a, b| a.2.total_cmp(&b.2));
+    targets
+        .into_iter()
+        .find(|(_, position, _)| terrain.can_see(entity.position, *position))
+        .map(|(body, position, _)| (body, position))
+}
+
+/// Steps every awake entity's AI, in entity order. Looking for targets casts a ray each and
+/// following routes plans them, the costly parts with many entities, so both are done for each
+/// entity on its own, on worker threads with the `rayon` feature.
+fn plan_ai(
+    terrain: Terrain<'_>,
+    hostility: &Hostility,
+    path_stamps: &[PathStamp],
+    entities: &Entities,
+    player: Vec2,
+    delta_time: f32,
+) -> Vec<Option<AiStep>> {
+    // Anything solid can be chased, projectiles and pickups can't
+    let bodies: Vec<(Body, Vec2, Faction)> =
+        std::iter::once((Body::Player, player, Faction::PLAYER))
+            .chain(
+                entities
+                    .iter()
+                    .filter(|(_, entity)| !entity.sensor)
+                    .map(|(id, entity)| (Body::Entity(id), entity.position, entity.faction)),
+            )
+            .collect();
+    let tile_size = terrain.tile_size as f32;
+    let hash = SpatialHash::new(
+        AI_CELL_TILES * tile_size,
+        bodies.iter().map(|(_, position, _)| *position),
+    );
+
+    let list: Vec<(EntityId, &Entity)> = entities.iter().collect();
+    let positions: Vec<Vec2> = list.iter().map(|(_, entity)| entity.position).collect();
+    let sightings = update_partitioned(UPDATE_CELL_TILES * tile_size, &positions, |index| {
+        let (id, entity) = list[index];
+        sighting(terrain, hostility, id, entity, &bodies, &hash)
+    });
+    update_partitioned(UPDATE_CELL_TILES * tile_size, &positions, |index| {
+        let (id, entity) = list[index];
+        step_ai(
+            terrain,
+            path_stamps,
+            id,
+            entity,
+            sightings[index],
+            delta_time,
+        )
+    })
+}
+
+/// What stepping an entity's AI comes to, to be merged back in entity order.
+struct AiStep {
+    ai: Ai,
+    velocity: Vec2,
+    events: Vec<AiEvent>,
+    /// Where it last saw its target, when it reached its search point and needs another
+    search_from: Option<Vec2>,
+}
+
+/// Steps an awake entity's AI on its own: chasing what it `seen`, searching where it lost it,
+/// heading for a noise or walking its patrol.
+fn step_ai(
+    terrain: Terrain<'_>,
+    path_stamps: &[PathStamp],
+    id: EntityId,
+    entity: &Entity,
+    seen: Option<(Body, Vec2)>,
+    delta_time: f32,
+) -> Option<AiStep> {
+    let mut ai = entity.ai.clone().filter(|_| !entity.dormant)?;
+    let mut events = Vec::new();
+    let step = |ai: Ai, events: Vec<AiEvent>, velocity: Vec2| AiStep {
+        ai,
+        velocity,
+        events,
+        search_from: None,
+    };
+
+    if let Some((body, position)) = seen {
+        if ai.state != AiState::Chasing {
+            events.push(AiEvent::Spotted {
+                entity: id,
+                target: body,
+            });
+        }
+        let velocity = (position - entity.position).normalize_or_zero() * ai.speed;
+        ai.target = Some(body);
+        ai.last_seen = Some(position);
+        ai.state = AiState::Chasing;
+        return Some(step(ai, events, velocity));
+    }
+    ai.target = None;
+
+    if ai.state == AiState::Chasing
+        && let Some(last_seen) = ai.last_seen
+        && ai.memory > 0.0
+    {
+        ai.state = AiState::Searching;
+        ai.search_left = ai.memory;
+        ai.search_point = Some(last_seen);
+        events.push(AiEvent::Lost {
+            entity: id,
+            last_seen,
+        });
+    }
+    if ai.state == AiState::Searching {
+        ai.search_left -= delta_time;
+        if let Some(last_seen) = ai.last_seen
+            && ai.search_left > 0.0
+        {
+            let point = ai.search_point.unwrap_or(last_seen);
+            let (velocity, arrived) = follow_route(
+                terrain,
+                path_stamps,
+                &mut ai,
+                entity.position,
+                point,
+                delta_time,
+            );
+            return Some(AiStep {
+                search_from: arrived.then_some(last_seen),
+                ..step(ai, events, velocity)
+            });
+        }
+        // Back to the patrol or a noise it heard, from this step
+        ai.last_seen = None;
+        ai.search_point = None;
+        ai.state = AiState::Idle;
+        events.push(AiEvent::GaveUp { entity: id });
+    } else if ai.state == AiState::Chasing {
+        // Lost sight with no memory to search with
+        ai.last_seen = None;
+        ai.state = AiState::Idle;
+    }
+
+    if let Some(noise) = ai.heard {
+        let (velocity, arrived) = follow_route(
+            terrain,
+            path_stamps,
+            &mut ai,
+            entity.position,
+            noise,
+            delta_time,
+        );
+        if arrived {
+            // Back to the patrol, if there is one, from the next step
+            ai.heard = None;
+            ai.state = AiState::Idle;
+        } else {
+            ai.state = AiState::Investigating;
+        }
+        return Some(step(ai, events, velocity));
+    }
+
+    let Some((pause, path)) = ai.patrol.as_ref().and_then(|(name, pause)| {
+        terrain
+            .map
+            .patrol_path(name)
+            .filter(|path| !path.waypoints.is_empty())
+            .map(|path| (*pause, path))
+    }) else {
+        ai.state = AiState::Idle;
+        return Some(step(ai, events, Vec2::ZERO));
+    };
+
+    if ai.state == AiState::Pausing {
+        ai.pause_left -= delta_time;
+        if ai.pause_left > 0.0 {
+            return Some(step(ai, events, Vec2::ZERO));
+        }
+        ai.waypoint += 1;
+    }
+    ai.state = AiState::Patrolling;
+    ai.waypoint %= path.waypoints.len();
+
+    let waypoint = path.waypoints[ai.waypoint];
+    let (velocity, arrived) = follow_route(
+        terrain,
+        path_stamps,
+        &mut ai,
+        entity.position,
+        waypoint,
+        delta_time,
+    );
+    if arrived {
+        ai.state = AiState::Pausing;
+        ai.pause_left = pause.as_secs_f32();
+    }
+    Some(step(ai, events, velocity))
+}
+
+/// Steers an AI towards `goal` along the cheapest route across the map's tiles, planned
+/// again when the goal changes and every [`ROUTE_REFRESH`] seconds so it keeps out of
+/// newly stamped costs. Heads straight for the goal when there's no route to it. Returns
+/// the velocity and whether it lands on the goal this step.
+fn follow_route(
+    terrain: Terrain<'_>,
+    path_stamps: &[PathStamp],
+    ai: &mut Ai,
+    position: Vec2,
+    goal: Vec2,
+    delta_time: f32,
+) -> (Vec2, bool) {
+    ai.route_age += delta_time;
+    if ai.route_goal != Some(goal) || ai.route_age >= ROUTE_REFRESH {
+        let path = pathfinding::find_path(
+            terrain.map,
+            path_stamps,
+            terrain.doors,
+            ai.opens_doors,
+            position,
+            goal,
+            terrain.tile_size as f32,
+        )
+        .unwrap_or_default();
+        // Reversed without the goal itself, so waypoints are popped off as they're reached
+        ai.route = path.into_iter().rev().skip(1).collect();
+        ai.route_goal = Some(goal);
+        ai.route_age = 0.0;
+    }
+
+    while let Some(waypoint) = ai.route.last() {
+        let (velocity, arrived) = steer(position, *waypoint, ai.speed, delta_time);
+        if !arrived {
+            return (velocity, false);
+        }
+        ai.route.pop();
+    }
+    steer(position, goal, ai.speed, delta_time)
+}
+
+fn cast_columns(width: usize, step: usize) -> impl Iterator<Item = usize> {
+    let last = width.saturating_sub(1);
+    (0..width)
+        .step_by(step)
+        .chain((!last.is_multiple_of(step)).then_some(last))
+}
+
+/// The player's movement on one simulation step, kept so it can be replayed from a corrected
+/// position.
+#[derive(Clone, Copy)]
+struct PlayerStep {
+    tick: u64,
+    delta_time: f32,
+    /// Unit length, or zero when standing still
+    direction: Vec2,
+    /// The crouching or sprinting speed factor
+    stance: f32,
+}
+
+/// The player's movement on the latest steps, oldest first, kept so it can be replayed from
+/// a corrected position. Holds at most [`MAX_PLAYER_STEPS`].
+#[derive(Default)]
+struct PlayerSteps(VecDeque<PlayerStep>);
+
+impl PlayerSteps {
+    /// Keeps a step, dropping the oldest one when the history is full.
+    fn push(&mut self, step: PlayerStep) {
+        if self.0.len() == MAX_PLAYER_STEPS {
+            self.0.pop_front();
+        }
+        self.0.push_back(step);
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Drops the steps up to and including `tick`, then replays the rest from where a server
+    /// says the player was after it. Returns where that leaves the player and their velocity.
+    fn reconcile(
+        &mut self,
+        terrain: Terrain<'_>,
+        noclip: bool,
+        tick: u64,
+        mut position: Vec2,
+        mut velocity: Vec2,
+    ) -> (Vec2, Vec2) {
+        while self.0.front().is_some_and(|step| step.tick <= tick) {
+            self.0.pop_front();
+        }
+        for step in &self.0 {
+            move_player(terrain, noclip, &mut position, &mut velocity, *step);
+        }
+        (position, velocity)
+    }
+}
+
+/// Accelerates the player along a step's direction and slides them through the map, passing
+/// through walls with `noclip`. Returns how far they moved.
+fn move_player(
+    terrain: Terrain<'_>,
+    noclip: bool,
+    position: &mut Vec2,
+    velocity: &mut Vec2,
+    step: PlayerStep,
+) -> f32 {
+    let tile_type = terrain
+        .map
+        .world_to_cell(*position)
+        .and_then(|(row, col)| terrain.map.tile_id(row, col))
+        .and_then(|tile_id| terrain.map.tile_type(tile_id));
+    let water_speed = match tile_type {
+        Some(TileType::Water(water)) => water.speed,
+        _ => 1.0,
+    };
+    let (push, friction) = match tile_type {
+        Some(TileType::Surface(surface)) => (surface.push, surface.friction),
+        _ => (Vec2::ZERO, 1.0),
+    };
+
+    let target = step.direction * MOVE_SPEED * water_speed * step.stance;
+    let traction = (friction * TRACTION_RATE * step.delta_time).min(1.0);
+    *velocity += (target - *velocity) * traction;
+
+    let offset = (*velocity + push) * step.delta_time;
+    if offset == Vec2::ZERO {
+        return 0.0;
+    }
+    let slid = slide_player(terrain, noclip, *position, offset);
+    // Running into a wall stops the player on that axis, instead of sliding on ice
+    // pressed up against it
+    let moved = slid - *position;
+    if moved.x == 0.0 && offset.x != 0.0 {
+        velocity.x = 0.0;
+    }
+    if moved.y == 0.0 && offset.y != 0.0 {
+        velocity.y = 0.0;
+    }
+    *position = slid;
+    moved.length()
+}
+
+/// Moves the player by `step` one axis at a time, so they slide along walls instead of
+/// stopping dead, or straight through them with `noclip`. A player already stuck in a wall
+/// can always move, to get out.
+fn slide_player(terrain: Terrain<'_>, noclip: bool, position: Vec2, step: Vec2) -> Vec2 {
+    if noclip {
+        return position + step;
+    }
+    terrain.slide_body(position, step, PLAYER_RADIUS)
+}
+
+/// Why the simulation is stopped. The game's pause and the one for losing focus are kept apart,
+/// so focus coming and going doesn't undo a pause the game set.
+#[derive(Clone, Copy, Debug, Default)]
+struct Pause {
+    paused: bool,
+    focus_paused: bool,
+}
+
+impl Pause {
+    fn is_paused(self) -> bool {
+        self.paused || self.focus_paused
+    }
+}
+
+#[derive(Default)]
+struct PlayerController {
+    key_forward: bool,
+    key_back: bool,
+    key_left: bool,
+    key_right: bool,
+    key_fire: bool,
+    key_crouch: bool,
+    key_sprint: bool,
+}
+
+/// The rays and projection parameters for a single render target.
+struct View {
+    height: u32,
+    dist_to_projection_plane: f32,
+    rays: Rays,
+    /// Camera position and rotation the rays were last cast from
+    cast_from: Option<(Vec2, f32)>,
+    /// The camera and ray step every column is up to date for, so a frame from the same place
+    /// can keep them. An interleaved cast only gets there on its second frame in one place.
+    settled: Option<(Camera, usize)>,
+    /// Which columns the next interleaved cast covers, 0 for even or 1 for odd
+    interleave_parity: usize,
+}
+
+impl View {
+    fn new(width: u32, height: u32, fov: f32) -> anyhow::Result<Self> {
+        let dist_to_projection_plane = width as f32 / 2.0 / (fov.to_radians() / 2.0).tan();
+        let ray_angles = get_ray_angles(fov, width)?;
+
+        Ok(Self {
+            height,
+            dist_to_projection_plane,
+            rays: Rays::new(ray_angles),
+            cast_from: None,
+            settled: None,
+            interleave_parity: 0,
+        })
+    }
+
+    /// Finds the nearest wall along a column's ray from the camera.
+    #[allow(clippy::too_many_arguments)]
+    fn cast_column(
+        &mut self,
+        column: usize,
+        camera: &Camera,
+        current_map: &Map,
+        linedefs: &LinedefIndex,
+        doors: &Doors,
+        tile_size: u16,
+        renderer: &impl RenderBackend,
+    ) -> anyhow::Result<()> {
+        let angle = self.rays.angles[column] + camera.rotation.to_radians();
+        let hit = match cast_ray(
+            camera.position,
+            angle,
+            current_map,
+            linedefs,
+            doors,
+            tile_size,
+        ) {
+            Some(hit) => Some(RayHit {
+                len: hit.dist,
+                position: Vec2::new(hit.intersection.x, hit.intersection.y),
+                tile_index: hit.tile_index,
+                side: hit.side,
+                layer: match hit.surface {
+                    WallSurface::Tile(tile_id) => renderer.wall_layer(tile_id)?,
+                    WallSurface::Linedef(path) => renderer.linedef_layer(path)?,
+                },
+                tex_u: hit.tex_u,
+            }),
+            None => None,
+        };
+        self.rays.set(column, hit);
+
+        Ok(())
+    }
+
+    /// Casts a ray for every `step`th column and the last. The columns in between take the hit
+    /// of the nearer cast column.
+    #[allow(clippy::too_many_arguments)]
+    fn cast_rays(
+        &mut self,
+        camera: &Camera,
+        current_map: &Map,
+        linedefs: &LinedefIndex,
+        doors: &Doors,
+        tile_size: u16,
+        renderer: &impl RenderBackend,
+        step: usize,
+    ) -> anyhow::Result<()> {
+        for column in cast_columns(self.rays.len(), step) {
+            self.cast_column(
+                column,
+                camera,
+                current_map,
+                linedefs,
+                doors,
+                tile_size,
+                renderer,
+            )?;
+        }
+        self.cast_from = Some((camera.position, camera.rotation));
+        self.settled = Some((*camera, step));
+
+        if step > 1 {
+            let cast: Vec<usize> = cast_columns(self.rays.len(), step).collect();
+            for pair in cast.windows(2) {
+                let (left, right) = (pair[0], pair[1]);
+                for column in left + 1..right {
+                    let nearest = if column - left <= right - column {
+                        left
+                    } else {
+                        right
+                    };
+                    self.rays.copy_hit(column, nearest);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Casts half the columns, alternating between even and odd each call, and reprojects the
+    /// rest from the previous cast: each stale column takes the hit of the old column that
+    /// looked the same way, shifted by how far the camera turned, with its distance measured
+    /// again from the camera's new position. Columns turned into view from off screen are cast.
+    /// Falls back to casting every column after a jump in position.
+    fn cast_rays_interleaved(
+        &mut self,
+        camera: &Camera,
+        current_map: &Map,
+        linedefs: &LinedefIndex,
+        doors: &Doors,
+        tile_size: u16,
+        renderer: &impl RenderBackend,
+    ) -> anyhow::Result<()> {
+        let Some((position, rotation)) = self.cast_from else {
+            return self.cast_rays(camera, current_map, linedefs, doors, tile_size, renderer, 1);
+        };
+        if camera.position.distance(position) > tile_size as f32 / 4.0 || self.rays.len() < 2 {
+            return self.cast_rays(camera, current_map, linedefs, doors, tile_size, renderer, 1);
+        }
+
+        let column_angle = self.rays.angles[1] - self.rays.angles[0];
+        let turned = (camera.rotation - rotation)
+            .keep_in_range(-180.0, 180.0)
+            .to_radians();
+        let shift = (turned / column_angle).round() as isize;
+        let stayed = (position, rotation) == (camera.position, camera.rotation);
+
+        let previous = self.rays.clone();
+        let parity = self.interleave_parity;
+        for column in 0..self.rays.len() {
+            let source = column as isize + shift;
+            if column % 2 == parity || source < 0 || source >= previous.len() as isize {
+                self.cast_column(
+                    column,
+                    camera,
+                    current_map,
+                    linedefs,
+                    doors,
+                    tile_size,
+                    renderer,
+                )?;
+            } else {
+                self.rays
+                    .reproject(column, &previous, source as usize, camera.position);
+            }
+        }
+
+        self.interleave_parity = 1 - parity;
+        self.cast_from = Some((camera.position, camera.rotation));
+        self.settled = stayed.then_some((*camera, 1));
+
+        Ok(())
+    }
+
+    /// The walls of every column, clipped by steps and lowered ceilings in front of them and
+    /// faded into the fog, when rays were cast `step` columns apart.
+    fn walls(
+        &self,
+        camera: &Camera,
+        current_map: &Map,
+        fog: Option<Fog>,
+        tile_size: u16,
+        wall_height: u16,
+        step: usize,
+    ) -> Vec<WallInstance> {
+        let relief_light = current_map.metadata().relief_light();
+        let mut instances = self.wall_instances(camera, wall_height, relief_light, step);
+        self.clip_walls(&mut instances, camera, current_map, tile_size, wall_height);
+        self.fog_walls(&mut instances, fog, wall_height);
+        instances
+    }
+
+    /// Hands the main view's walls to the backend. See [`walls`](Self::walls).
+    #[allow(clippy::too_many_arguments)]
+    fn draw_walls(
+        &self,
+        camera: &Camera,
+        current_map: &Map,
+        fog: Option<Fog>,
+        tile_size: u16,
+        wall_height: u16,
+        step: usize,
+        backend: &mut impl RenderBackend,
+    ) -> anyhow::Result<()> {
+        let instances = self.walls(camera, current_map, fog, tile_size, wall_height, step);
+        for (i, instance) in instances.into_iter().enumerate() {
+            backend.set_wall_instance(i, instance)?;
+        }
+
+        Ok(())
+    }
+
+    /// Wall instances for every column when rays were cast `step` columns apart. Columns
+    /// between two cast rays that hit the same wall face are interpolated; the rest copy the
+    /// nearer cast column.
+    fn wall_instances(
+        &self,
+        camera: &Camera,
+        wall_height: u16,
+        relief_light: ReliefLight,
+        step: usize,
+    ) -> Vec<WallInstance> {
+        let cast: Vec<usize> = cast_columns(self.rays.len(), step).collect();
+        let mut instances = vec![WallInstance::default(); self.rays.len()];
+        for &column in &cast {
+            instances[column] = self.wall_instance(column, camera, wall_height, relief_light);
+        }
+
+        for pair in cast.windows(2) {
+            let (left, right) = (pair[0], pair[1]);
+            let same_face = self.rays.same_face(left, right);
+
+            for column in left + 1..right {
+                let t = (column - left) as f32 / (right - left) as f32;
+                instances[column] = if same_face {
+                    instances[left].lerp(&instances[right], t, column)
+                } else {
+                    let nearest = if t <= 0.5 { left } else { right };
+                    WallInstance {
+                        screen_x: column as f32,
+                        ..instances[nearest]
+                    }
+                };
+            }
+        }
+
+        instances
+    }
+
+    fn wall_instance(
+        &self,
+        column: usize,
+        camera: &Camera,
+        wall_height: u16,
+        relief_light: ReliefLight,
+    ) -> WallInstance {
+        let (Some((position, _)), Some(side)) = (self.rays.hit(column), self.rays.side(column))
+        else {
+            return WallInstance::default();
+        };
+
+        let dist = self.rays.distances[column];
+        let y_center = self.height as f32 / 2.0 + camera.pitch;
+
+        let ratio = self.dist_to_projection_plane / dist;
+        let scale = (self.dist_to_projection_plane * wall_height as f32) / dist;
+        let wall_bottom = ratio * camera.height as f32 + y_center;
+        let wall_top = wall_bottom - scale;
+
+        WallInstance {
+            screen_x: column as f32,
+            top: wall_top,
+            height: wall_bottom - wall_top,
+            tex_u: self.rays.tex_u[column],
+            tex_layer: self.rays.layers[column],
+            floor_clip: f32::MAX,
+            ceiling_clip: f32::MIN,
+            fog: [0.0; 4],
+            relief_light: face_light(relief_light, camera, position, side, wall_height as f32),
+        }
+    }
+
+    /// Fades walls into the fog by their distance, which their projected height gives back.
+    fn fog_walls(&self, instances: &mut [WallInstance], fog: Option<Fog>, wall_height: u16) {
+        let Some(fog) = fog else {
+            return;
+        };
+        for instance in instances
+            .iter_mut()
+            .filter(|instance| instance.height > 0.0)
+        {
+            let dist = self.dist_to_projection_plane * wall_height as f32 / instance.height;
+            instance.fog = fog_color(fog, dist);
+        }
+    }
+
+    /// Hides the bottoms of walls behind any steps and ramps in front of them, and their tops
+    /// behind any lower ceilings.
+    fn clip_walls(
+        &self,
+        instances: &mut [WallInstance],
+        camera: &Camera,
+        current_map: &Map,
+        tile_size: u16,
+        wall_height: u16,
+    ) {
+        if current_map.max_floor_height() <= 0.0 && !current_map.ceilings_vary() {
+            return;
+        }
+
+        for (column, instance) in instances.iter_mut().enumerate() {
+            if self.rays.is_hit(column) {
+                (instance.floor_clip, instance.ceiling_clip) =
+                    self.clips(column, camera, current_map, tile_size, wall_height);
+            }
+        }
+    }
+
+    /// The highest screen row a step or ramp reaches and the lowest a lowered ceiling reaches
+    /// between the camera and a column's wall. Walks the tiles along the ray, projecting the
+    /// floor and ceiling where the ray enters and leaves each one.
+    fn clips(
+        &self,
+        column: usize,
+        camera: &Camera,
+        current_map: &Map,
+        tile_size: u16,
+        wall_height: u16,
+    ) -> (f32, f32) {
+        let column_angle = self.rays.angles[column];
+        let direction = Vec2::from_angle(column_angle + camera.rotation.to_radians());
+        let length = self.rays.distances[column] / column_angle.cos();
+        let y_center = self.height as f32 / 2.0 + camera.pitch;
+        let tile_size = tile_size as f32;
+        let size = current_map.size();
+        // The screen row of a point `t` along the ray at `height` above the floor
+        let row = |t: f32, height: f32| {
+            y_center
+                + self.dist_to_projection_plane * (camera.height as f32 - height)
+                    / (t * column_angle.cos())
+        };
+
+        let mut floor_clip = f32::MAX;
+        let mut ceiling_clip = f32::MIN;
+        let mut tile = (camera.position / tile_size).floor();
+        let step = direction.signum();
+        let inverse = direction.abs().max(Vec2::splat(1e-6)).recip();
+        let along_delta = tile_size * inverse;
+        let next_edge = (tile + step.max(Vec2::ZERO)) * tile_size;
+        let mut next_along = (next_edge - camera.position).abs() * inverse;
+        let mut along = 0.0;
+
+        while along < length {
+            if tile.x < 0.0
+                || tile.y < 0.0
+                || tile.y as usize >= size.rows()
+                || tile.x as usize >= size.cols()
+            {
+                break;
+            }
+            let exit = next_along.min_element().min(length);
+            let tile_id = current_map.tile_id(tile.y as usize, tile.x as usize);
+            for t in [along, exit] {
+                if t <= 0.0 {
+                    continue;
+                }
+                match tile_id.and_then(|id| current_map.tile_type(id)) {
+                    Some(TileType::Slope(data)) => {
+                        let point = camera.position + direction * t;
+                        let uv = (point / tile_size - tile).clamp(Vec2::ZERO, Vec2::ONE);
+                        floor_clip = floor_clip.min(row(t, data.height_at(uv)));
+                    }
+                    Some(TileType::FloorCeiling(TileDataFC {
+                        ceiling_height: Some(ceiling_height),
+                        ..
+                    })) if ceiling_height < wall_height as f32 => {
+                        ceiling_clip = ceiling_clip.max(row(t, ceiling_height));
+                    }
+                    _ => (),
+                }
+            }
+
+            along = exit;
+            if next_along.x < next_along.y {
+                tile.x += step.x;
+                next_along.x += along_delta.x;
+            } else {
+                tile.y += step.y;
+                next_along.y += along_delta.y;
+            }
+        }
+
+        (floor_clip, ceiling_clip)
+    }
+
+    /// The floor pass's camera uniform for this view.
+    #[allow(clippy::too_many_arguments)]
+    fn floor_camera(
+        &self,
+        camera: &Camera,
+        current_map: &Map,
+        sky: &Sky,
+        tile_size: u16,
+        wall_height: u16,
+        flat_sprite_count: usize,
+        collision_radius: f32,
+        time: f32,
+    ) -> FloorCamera {
+        let fog = sky.fog();
+        let (clouds, cloud_count) = sky.clouds(current_map.metadata());
+        FloorCamera {
+            position: camera.position.into(),
+            rotation: camera.rotation.to_radians(),
+            fov: camera.fov.to_radians(),
+            height: camera.height as f32,
+            horizon: self.height as f32 / 2.0 + camera.pitch,
+            dist_to_projection_plane: self.dist_to_projection_plane,
+            tile_size: tile_size as f32,
+            wall_height: wall_height as f32,
+            flat_sprite_count: flat_sprite_count.min(MAX_FLAT_SPRITES) as u32,
+            collision_radius,
+            max_floor_height: current_map.max_floor_height(),
+            ceilings_vary: current_map.ceilings_vary() as u32,
+            has_sky: current_map.sky_texture_path().is_some() as u32,
+            fog_start: fog.map_or(0.0, |fog| fog.start),
+            fog_end: fog.map_or(0.0, |fog| fog.end),
+            fog_color: fog.map_or([0.0; 3], |fog| fog.color),
+            time,
+            sky_tint: sky.tint(),
+            cloud_count,
+            clouds,
+        }
+    }
+
+    /// Projects sprites into per-column strips, leaving out columns hidden behind walls. Strips
+    /// are ordered back to front so translucent sprites blend over whatever is behind them.
+    fn sprite_instances(
+        &self,
+        camera: &Camera,
+        sprites: &[Sprite],
+        tile_size: u16,
+        wall_height: u16,
+        sky: &Sky,
+        renderer: &impl RenderBackend,
+    ) -> anyhow::Result<Vec<SpriteInstance>> {
+        let width = self.rays.len();
+        let y_center = self.height as f32 / 2.0 + camera.pitch;
+
+        let mut visible: Vec<(&Sprite, f32, f32)> = sprites
+            .iter()
+            .filter(|sprite| !sprite.flat)
+            .filter_map(|sprite| {
+                let offset = sprite.position - camera.position;
+                let angle = (offset.y.atan2(offset.x) - camera.rotation.to_radians())
+                    .keep_in_range(-PI, PI);
+                // Distance along the view direction, matching the fisheye corrected walls
+                let depth = offset.length() * angle.cos();
+
+                (depth > 1.0 && sprite.opacity > 0.0 && sprite.scale > 0.0)
+                    .then_some((sprite, angle, depth))
+            })
+            .collect();
+        visible.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+        let mut instances = Vec::new();
+        for (sprite, angle, depth) in visible {
+            // Same projection as walls, with the bottom raised by the z-offset
+            let ratio = self.dist_to_projection_plane / depth;
+            let sprite_height = ratio * wall_height as f32 * sprite.scale;
+            let sprite_width = ratio * tile_size as f32 * sprite.scale;
+            let bottom = ratio * (camera.height as f32 - sprite.z_offset) + y_center;
+
+            // Columns are evenly spaced in angle, like the rays
+            let center_x = (angle.to_degrees() + camera.fov / 2.0) / camera.fov * width as f32;
+            let left = center_x - sprite_width / 2.0;
+            let first = left.max(0.0) as usize;
+            let last = ((left + sprite_width).ceil().max(0.0) as usize).min(width);
+            if first >= last {
+                continue;
+            }
+
+            let tex_layer = renderer.sprite_layer(sprite.texture_path)?;
+            let fog = sky.fog().map_or([0.0; 4], |fog| fog_color(fog, depth));
+            let [r, g, b] = sky.light();
+
+            for column in first..last {
+                if self.rays.distances[column] <= depth {
+                    continue;
+                }
+
+                instances.push(SpriteInstance {
+                    screen_x: column as f32,
+                    top: bottom - sprite_height,
+                    height: sprite_height,
+                    tex_u: ((column as f32 + 0.5 - left) / sprite_width).clamp(0.0, 1.0),
+                    tex_layer,
+                    tint: [r, g, b, sprite.opacity],
+                    fog,
+                });
+            }
+        }
+
+        Ok(instances)
+    }
+
+    /// Projects text labels into per-column glyph strips the same way as sprites: hidden
+    /// behind walls and ordered back to front.
+    fn label_instances(
+        &self,
+        camera: &Camera,
+        labels: &[TextLabel],
+        fog: Option<Fog>,
+    ) -> Vec<SpriteInstance> {
+        let width = self.rays.len();
+        let y_center = self.height as f32 / 2.0 + camera.pitch;
+
+        let mut visible: Vec<(&TextLabel, f32, f32)> = labels
+            .iter()
+            .filter_map(|label| {
+                let offset = label.position - camera.position;
+                let angle = (offset.y.atan2(offset.x) - camera.rotation.to_radians())
+                    .keep_in_range(-PI, PI);
+                let depth = offset.length() * angle.cos();
+
+                (depth > 1.0 && label.height > 0.0 && !label.text.is_empty())
+                    .then_some((label, angle, depth))
+            })
+            .collect();
+        visible.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+        let mut instances = Vec::new();
+        for (label, angle, depth) in visible {
+            let ratio = self.dist_to_projection_plane / depth;
+            let glyph_size = ratio * label.height;
+            let bottom = ratio * (camera.height as f32 - label.z_offset) + y_center;
+
+            let glyphs: Vec<u32> = label.text.chars().map(renderer::glyph_layer).collect();
+            let text_width = glyph_size * glyphs.len() as f32;
+            let fog = fog.map_or([0.0; 4], |fog| fog_color(fog, depth));
+
+            let center_x = (angle.to_degrees() + camera.fov / 2.0) / camera.fov * width as f32;
+            let left = center_x - text_width / 2.0;
+            let first = left.max(0.0) as usize;
+            let last = ((left + text_width).ceil().max(0.0) as usize).min(width);
+
+            for column in first..last {
+                if self.rays.distances[column] <= depth {
+                    continue;
+                }
+
+                let x = (column as f32 + 0.5 - left) / glyph_size;
+                let glyph = glyphs[(x as usize).min(glyphs.len() - 1)];
+                if glyph == ' ' as u32 {
+                    continue;
+                }
+
+                instances.push(SpriteInstance {
+                    screen_x: column as f32,
+                    top: bottom - glyph_size,
+                    height: glyph_size,
+                    tex_u: x.fract(),
+                    tex_layer: glyph,
+                    tint: label.color,
+                    fog,
+                });
+            }
+        }
+
+        instances
+    }
+
+    /// Lays out the held weapon's view sprite as square strips at the bottom middle of the
+    /// view, `scale` of its height. It's drawn over everything in the world.
+    fn viewmodel_instances(&self, tex_layer: u32, scale: f32) -> Vec<SpriteInstance> {
+        let size = self.height as f32 * scale;
+        let width = self.rays.len();
+        let left = (width as f32 - size) / 2.0;
+        let first = left.max(0.0) as usize;
+        let last = ((left + size).ceil().max(0.0) as usize).min(width);
+
+        (first..last)
+            .map(|column| SpriteInstance {
+                screen_x: column as f32,
+                top: self.height as f32 - size,
+                height: size,
+                tex_u: ((column as f32 + 0.5 - left) / size).clamp(0.0, 1.0),
+                tex_layer,
+                tint: [1.0; 4],
+                fog: [0.0; 4],
+            })
+            .collect()
+    }
+
+    /// Lays out lines of text in the top left corner as glyph strips, at a whole multiple of
+    /// the font's size so it stays crisp.
+    fn overlay_instances(&self, lines: &[String]) -> Vec<SpriteInstance> {
+        let glyph_size = ((self.height / 240).max(1) * 8) as f32;
+        let width = self.rays.len();
+
+        let mut instances = Vec::new();
+        for (row, line) in lines.iter().enumerate() {
+            let top = glyph_size * (row as f32 + 0.5);
+            for (index, c) in line.chars().enumerate() {
+                if c == ' ' {
+                    continue;
+                }
+                let left = glyph_size * (index as f32 + 0.5);
+                for offset in 0..glyph_size as usize {
+                    let column = left as usize + offset;
+                    if column >= width {
+                        break;
+                    }
+                    instances.push(SpriteInstance {
+                        screen_x: column as f32,
+                        top,
+                        height: glyph_size,
+                        tex_u: (offset as f32 + 0.5) / glyph_size,
+                        tex_layer: renderer::glyph_layer(c),
+                        tint: [1.0, 1.0, 0.3, 1.0],
+                        fog: [0.0; 4],
+                    });
+                }
+            }
+        }
+
+        instances
+    }
+
+    /// Lays out a line of text centered near the bottom of the view as glyph strips, twice the
+    /// overlay's size.
+    fn caption_instances(&self, text: &str) -> Vec<SpriteInstance> {
+        let glyph_size = ((self.height / 240).max(1) * 16) as f32;
+        let top = self.height as f32 - glyph_size * 2.5;
+        self.centered_line(text, top, glyph_size, [1.0; 4])
+    }
+
+    /// Lays out a menu screen in the middle of the view: its title, then its items with the
+    /// selected one highlighted.
+    fn menu_instances(
+        &self,
+        title: &str,
+        items: &[String],
+        selected: usize,
+    ) -> Vec<SpriteInstance> {
+        let glyph_size = ((self.height / 240).max(1) * 16) as f32;
+        let line_height = glyph_size * 1.5;
+        let lines = items.len() as f32 + 2.0;
+        let top = (self.height as f32 - line_height * lines) / 2.0;
+
+        let mut instances = self.centered_line(title, top, glyph_size, [1.0, 0.3, 0.3, 1.0]);
+        for (index, item) in items.iter().enumerate() {
+            let tint = if index == selected {
+                [1.0, 1.0, 0.3, 1.0]
+            } else {
+                [0.8, 0.8, 0.8, 1.0]
+            };
+            let top = top + line_height * (index as f32 + 2.0);
+            instances.extend(self.centered_line(item, top, glyph_size, tint));
+        }
+
+        instances
+    }
+
+    /// Lays out a line of text centered across the view as glyph strips.
+    fn centered_line(
+        &self,
+        text: &str,
+        top: f32,
+        glyph_size: f32,
+        tint: [f32; 4],
+    ) -> Vec<SpriteInstance> {
+        let width = self.rays.len();
+        let text_width = glyph_size * text.chars().count() as f32;
+        let left = (width as f32 - text_width) / 2.0;
+
+        let mut instances = Vec::new();
+        for (index, c) in text.chars().enumerate() {
+            if c == ' ' {
+                continue;
+            }
+            let glyph_left = left + glyph_size * index as f32;
+            for offset in 0..glyph_size as usize {
+                let column = glyph_left + offset as f32;
+                if column < 0.0 || column >= width as f32 {
+                    continue;
+                }
+                instances.push(SpriteInstance {
+                    screen_x: column.floor(),
+                    top,
+                    height: glyph_size,
+                    tex_u: (offset as f32 + 0.5) / glyph_size,
+                    tex_layer: renderer::glyph_layer(c),
+                    tint,
+                    fog: [0.0; 4],
+                });
+            }
+        }
+
+        instances
+    }
+}
+
+/// A render-to-texture surface, drawn by its own camera into a wall texture layer.
+struct Monitor {
+    camera: Camera,
+    view: View,
+}
+
+/// The right eye's view in side-by-side stereo. The main view is the left eye's.
+struct StereoView {
+    eye_separation: f32,
+    view: View,
+}
+
+/// A view looking backwards from the player's head, drawn over a corner of the main view.
+struct RearViewMirror {
+    area: RearView,
+    view: View,
+}
+
+pub(crate) struct Raycaster {
+    renderer: Renderer,
+    tile_size: u16,
+    wall_height: u16,
+    camera: Camera,
+    view: View,
+    monitors: Vec<Monitor>,
+    stereo: Option<StereoView>,
+    rear_view: Option<RearViewMirror>,
+    /// Set while the map is drawn from above in place of the first-person view
+    top_down: Option<TopDown>,
+    maps: Arc<Maps>,
+    /// The maps as the game started, for working out how far the current one has changed
+    original_maps: Arc<Maps>,
+    current_map_key: &'static str,
+    player_controller: PlayerController,
+    time_scale: f32,
+    simulation: SimulationMode,
+    /// The source of all simulation randomness
+    rng: Rng,
+    /// Simulation time not yet stepped through in deterministic mode
+    fixed_step_remainder: f32,
+    /// Simulation steps taken so far
+    tick: u64,
+    player_steps: PlayerSteps,
+    pause: Pause,
+    /// Frame and GPU timings drawn in the corner of the view
+    performance_overlay: bool,
+    /// Set when the main view's ray resolution adapts to load
+    ray_step_controller: Option<RayStepController>,
+    /// Whether the main view casts half its columns each frame and reprojects the rest
+    column_interleaving: bool,
+    timing: FrameTiming,
+    /// The water the player last stood in, kept while the effects fade out
+    water: Option<WaterData>,
+    /// How far into `water` the player has sunk, from 0 to 1
+    submersion: f32,
+    /// Seconds until the hazard being stood on deals damage again
+    hazard_cooldown: f32,
+    /// Damage taken during the current frame
+    damage_events: Vec<DamageEvent>,
+    /// The color of the last damage flash and how much of it is left, from 0 to 1
+    damage_flash: ([f32; 3], f32),
+    /// How far the view shakes in degrees, and seconds left and in total
+    shake: (f32, f32, f32),
+    /// How far through a step the view's bob is, in radians
+    bob_phase: f32,
+    crouching: bool,
+    sprinting: bool,
+    accessibility: AccessibilityConfig,
+    /// The player's own velocity, in world units per second, without any push from the floor
+    velocity: Vec2,
+    elevators: Elevators,
+    doors: Doors,
+    sky: Sky,
+    weather: WeatherState,
+    lightning: LightningState,
+    linedefs: LinedefIndex,
+    /// Whether a door or elevator moved this frame, so the view's walls have to be cast again
+    walls_moved: bool,
+    /// The elevator tile the player is standing on, so stepping on activates it only once
+    elevator_tile: Option<u8>,
+    /// Height of the floor under the player, which they fall to when stepping off a raised one
+    player_floor: f32,
+    entities: Entities,
+    /// Builds the entities maps place by type name
+    entity_types: EntityTypes,
+    spawners: Spawners,
+    /// Waves that started or finished during the current frame
+    wave_events: Vec<WaveEvent>,
+    /// The last checkpoint reached, which the player respawns at
+    checkpoint: Option<Snapshot>,
+    /// The index of the map checkpoint the player is standing in, so it's only reached once
+    /// per visit
+    checkpoint_region: Option<usize>,
+    /// Set to respawn at the start of the next frame
+    respawn_pending: bool,
+    /// Checkpoints reached and respawns during the current frame
+    checkpoint_events: Vec<CheckpointEvent>,
+    /// Noises made since the last step, by position and radius
+    noises: Vec<(Vec2, f32)>,
+    /// Noises spread during the current frame
+    noise_events: Vec<NoiseEvent>,
+    /// AI spotting, losing and giving up on targets during the current frame
+    ai_events: Vec<AiEvent>,
+    /// Temporary route costs over parts of the current map
+    path_stamps: Vec<PathStamp>,
+    /// Seconds until the player's footsteps can be heard again
+    footstep_timer: f32,
+    /// Which factions fight each other
+    hostility: Hostility,
+    entity_culling: Option<EntityCulling>,
+    /// Projectile hits during the current frame
+    hit_events: Vec<HitEvent>,
+    /// The items pickups can give
+    items: Items,
+    inventory: Inventory,
+    /// Pickups taken during the current frame
+    pickup_events: Vec<PickupEvent>,
+    arsenal: Arsenal,
+    /// Shots fired during the current frame
+    shot_events: Vec<ShotEvent>,
+    stats: LevelStats,
+    /// Secrets found, objectives completed and levels finished during the current frame
+    stats_events: Vec<StatsEvent>,
+    sequencer: Sequencer,
+    /// Sequences started, finished and their events during the current frame
+    sequence_events: Vec<SequenceEvent>,
+    /// Textures and sounds that failed to load during the current frame
+    asset_events: Vec<AssetEvent>,
+    /// Doors that started opening since [`take_map_changes`](Self::take_map_changes)
+    map_changes: MapDiff,
+    /// Stop the simulation while a screen is open
+    menus: Menus,
+    /// Bodies that overlapped during the current frame
+    contacts: Vec<Contact>,
+    audio: Audio,
+    /// Set while the level editor is open, which stops the simulation
+    editor: Option<Editor>,
+    /// The tiles seen on each map visited, for the automap
+    explored: HashMap<&'static str, Exploration>,
+    /// Set while the automap is shown in place of the view
+    automap: Option<Automap>,
+    /// What each window besides the main one shows, with the automap drawn for those that
+    /// show it
+    windows: Vec<(WindowContent, Automap)>,
+    #[cfg(debug_assertions)]
+    debug_flags: DebugFlags,
+}
+
+/// Half the width of the player's square collision box, in world units.
+const PLAYER_RADIUS: f32 = 16.0;
+/// How far short of a wall a swept move stops, in world units.
+const WALL_GAP: f32 = 0.001;
+/// The tallest rise the player and entities can walk up, in world units.
+const MAX_STEP_HEIGHT: f32 = 24.0;
+/// How fast the player drops off a raised floor, in world units per second.
+const FALL_SPEED: f32 = 256.0;
+/// How quickly the player sinks into and rises out of water, in full depths per second.
+const SUBMERSION_RATE: f32 = 4.0;
+/// How quickly a damage flash fades, in full flashes per second.
+const DAMAGE_FLASH_FADE_RATE: f32 = 3.0;
+/// How far the view bobs up and down while walking, in world units.
+const BOB_HEIGHT: f32 = 3.0;
+/// How far the player walks for each bob, in world units.
+const BOB_STRIDE: f32 = 48.0;
+/// How much lower the view is while crouching, in world units.
+const CROUCH_HEIGHT: f32 = 14.0;
+/// Movement speed while crouching, relative to walking.
+const CROUCH_SPEED: f32 = 0.5;
+/// Movement speed while sprinting, relative to walking.
+const SPRINT_SPEED: f32 = 1.6;
+/// The player's walking speed, in world units per second.
+const MOVE_SPEED: f32 = 150.0;
+/// Seconds between the player's footsteps on noisy floors.
+const FOOTSTEP_INTERVAL: f32 = 0.4;
+/// Width of the cells AI looks for targets in, in tiles.
+const AI_CELL_TILES: f32 = 8.0;
+/// Width of the cells entities are split between threads by, in tiles.
+const UPDATE_CELL_TILES: f32 = 8.0;
+/// Seconds between AI planning its route again on the way to the same place.
+const ROUTE_REFRESH: f32 = 0.5;
+/// How many random spots AI tries for the next place to search before settling on where it
+/// last saw its target.
+const SEARCH_POINT_TRIES: usize = 4;
+/// How near AI that can open doors has to get to a door on its route to open it, in tiles.
+const DOOR_REACH: f32 = 1.5;
+/// How quickly the player's velocity follows their input at a friction of 1, per second. At
+/// this rate a normal floor responds within a frame.
+const TRACTION_RATE: f32 = 60.0;
+/// How many of the player's steps are kept for [`Raycaster::reconcile_player`], which is two
+/// seconds at [`FIXED_TIMESTEP`].
+const MAX_PLAYER_STEPS: usize = 120;
+
+impl Raycaster {
+    pub fn new(
+        renderer: Renderer,
+        maps: Arc<Maps>,
+        current_map_key: &'static str,
+        simulation: SimulationMode,
+        entity_types: EntityTypes,
+        weapons: Vec<Weapon>,
+        preload_sounds: Vec<&'static str>,
+    ) -> anyhow::Result<Raycaster> {
+        for (key, map) in maps.iter() {
+            map.validate()
+                .with_context(|| format!("map \"{key}\" is invalid"))?;
+        }
+        let (width, height) = renderer.internal_size();
+
+        let camera = match maps[current_map_key].metadata().player_start() {
+            Some((position, rotation)) => Camera::new(position, rotation),
+            None => Camera::new(glam::vec2(100.0, 100.0), 10.0),
+        };
+        let view = View::new(width, height, camera.fov)?;
+        let tile_size = TILE_SIZE;
+
+        let mut audio = Audio::new();
+        let mut sounds = preload_sounds;
+        for sound in weapons.iter().filter_map(|weapon| weapon.sound) {
+            if !sounds.contains(&sound) {
+                sounds.push(sound);
+            }
+        }
+        audio.set_preload(sounds);
+        audio.load_map(&maps[current_map_key], renderer.assets());
+
+        let mut elevators = Elevators::default();
+        elevators.load_map(&maps[current_map_key]);
+
+        let mut doors = Doors::default();
+        doors.load_map(&maps[current_map_key]);
+
+        let mut sky = Sky::new(renderer.color_space());
+        sky.apply(maps[current_map_key].metadata());
+
+        let mut linedefs = LinedefIndex::default();
+        linedefs.load_map(&maps[current_map_key], tile_size);
+
+        let mut entities = Entities::default();
+        spawn_map_entities(
+            &mut entities,
+            &renderer,
+            &maps[current_map_key],
+            &entity_types,
+        );
+
+        let mut spawners = Spawners::default();
+        spawners.load_map(&maps[current_map_key]);
+
+        let stats = level_stats(current_map_key, &maps[current_map_key]);
+
+        let mut sequencer = Sequencer::default();
+        sequencer.load_map(&maps[current_map_key]);
+
+        Ok(Self {
+            renderer,
+            tile_size,
+            wall_height: 64,
+            camera,
+            view,
+            monitors: Vec::new(),
+            stereo: None,
+            rear_view: None,
+            top_down: None,
+            original_maps: maps.clone(),
+            maps,
+            current_map_key,
+
+            player_controller: PlayerController::default(),
+            time_scale: 1.0,
+            simulation,
+            rng: match simulation {
+                SimulationMode::Realtime => Rng::from_time(),
+                SimulationMode::Deterministic { seed } => Rng::new(seed),
+            },
+            fixed_step_remainder: 0.0,
+            tick: 0,
+            player_steps: PlayerSteps::default(),
+            pause: Pause::default(),
+            performance_overlay: false,
+            ray_step_controller: None,
+            column_interleaving: false,
+            timing: FrameTiming::default(),
+            water: None,
+            submersion: 0.0,
+            hazard_cooldown: 0.0,
+            damage_events: Vec::new(),
+            damage_flash: ([0.0; 3], 0.0),
+            shake: (0.0, 0.0, 0.0),
+            bob_phase: 0.0,
+            crouching: false,
+            sprinting: false,
+            accessibility: AccessibilityConfig::default(),
+            velocity: Vec2::ZERO,
+            elevators,
+            sky,
+            weather: WeatherState::default(),
+            lightning: LightningState::default(),
+            doors,
+            linedefs,
+            walls_moved: false,
+            elevator_tile: None,
+            player_floor: 0.0,
+            entities,
+            entity_types,
+            spawners,
+            wave_events: Vec::new(),
+            checkpoint: None,
+            checkpoint_region: None,
+            respawn_pending: false,
+            checkpoint_events: Vec::new(),
+            noises: Vec::new(),
+            path_stamps: Vec::new(),
+            noise_events: Vec::new(),
+            ai_events: Vec::new(),
+            footstep_timer: 0.0,
+            hostility: Hostility::default(),
+            entity_culling: None,
+            hit_events: Vec::new(),
+            items: Items::default(),
+            inventory: Inventory::default(),
+            pickup_events: Vec::new(),
+            arsenal: Arsenal::new(weapons),
+            shot_events: Vec::new(),
+            stats,
+            stats_events: Vec::new(),
+            sequencer,
+            sequence_events: Vec::new(),
+            asset_events: Vec::new(),
+            map_changes: MapDiff::new(),
+            menus: Menus::default(),
+            contacts: Vec::new(),
+            audio,
+            editor: None,
+            explored: HashMap::new(),
+            automap: None,
+            windows: Vec::new(),
+            #[cfg(debug_assertions)]
+            debug_flags: DebugFlags::default(),
+        })
+    }
+
+    pub fn update(&mut self) -> anyhow::Result<()> {
+        if self.renderer.is_device_lost() {
+            self.recover_device()?;
+        }
+
+        if let Some(editor) = &mut self.editor {
+            if let Some(image) = editor.take_image(self.camera.position) {
+                self.renderer.show_overhead(&image);
+            }
+            self.renderer.render()?;
+            self.timing.advance(self.renderer.delta_time());
+            return Ok(());
+        }
+
+        if self.renderer.is_loading() {
+            if !self.renderer.poll_textures()? {
+                self.renderer.render()?;
+                self.timing.advance(self.renderer.delta_time());
+                return Ok(());
+            }
+            self.monitors = self.create_monitors()?;
+        }
+
+        self.damage_events.clear();
+        self.wave_events.clear();
+        self.checkpoint_events.clear();
+        self.noise_events.clear();
+        self.ai_events.clear();
+        self.hit_events.clear();
+        self.pickup_events.clear();
+        self.shot_events.clear();
+        self.stats_events.clear();
+        self.sequence_events.clear();
+        self.asset_events = self.renderer.take_failed_assets();
+        self.contacts.clear();
+        self.walls_moved = false;
+        if self.respawn_pending {
+            self.respawn_pending = false;
+            self.respawn()?;
+        }
+        self.simulate()?;
+        self.audio
+            .update(self.camera.position, self.camera.rotation);
+        self.asset_events.append(&mut self.audio.take_failed());
+        // Walls are fogged as they're cast, so they're cast again when the fog's color changes
+        self.walls_moved |= self.sky.apply(self.maps[self.current_map_key].metadata());
+        self.renderer.set_ambient_light(self.sky.light());
+
+        let view_start = Instant::now();
+        match self.top_down {
+            Some(top_down) => {
+                self.view.settled = None;
+                self.update_top_down(top_down)?;
+            }
+            None => {
+                // Walls look the same from where they were last cast while nothing's moved, like
+                // when the player stands still, so they're kept as they are
+                if self.walls_moved
+                    || self.view.cast_from.is_none()
+                    || self.view.settled != Some((self.view_camera(), self.ray_step()))
+                {
+                    self.update_rays()?;
+                    self.explore_view();
+                    self.update_quads()?;
+                }
+                self.update_sprites()?;
+                self.update_stereo()?;
+                self.update_rear_view()?;
+            }
+        }
+        self.update_post_effect();
+        if let Some(controller) = &mut self.ray_step_controller {
+            controller.update(view_start.elapsed());
+        }
+
+        self.update_monitors()?;
+
+        if let Some(automap) = &mut self.automap
+            && let Some(exploration) = self.explored.get(self.current_map_key)
+            && let Some(image) = automap.take_image(
+                self.current_map_key,
+                &self.maps[self.current_map_key],
+                exploration,
+                &self.camera,
+                self.tile_size as f32,
+                self.accessibility.high_contrast_automap,
+            )
+        {
+            self.renderer.show_overhead(&image);
+        }
+        self.update_windows();
+
+        self.renderer.render()?;
+        self.timing.advance(self.renderer.delta_time());
+
+        Ok(())
+    }
+
+    /// Marks the tiles the main view's rays passed over, and the walls they hit, as explored.
+    fn explore_view(&mut self) {
+        if self.sequencer.camera().is_some() {
+            return;
+        }
+        let map = &self.maps[self.current_map_key];
+        let exploration = self
+            .explored
+            .entry(self.current_map_key)
+            .or_insert_with(|| Exploration::new(map));
+        if !exploration.fits(map) {
+            *exploration = Exploration::new(map);
+        }
+        let tile_size = self.tile_size as f32;
+        let start = self.camera.position / tile_size;
+        // Rays that hit nothing are followed out of the map
+        let reach = (exploration.cols() + exploration.rows()) as f32;
+
+        let rays = &self.view.rays;
+        for column in 0..rays.len() {
+            match rays.hit(column) {
+                Some((position, tile_index)) => {
+                    exploration.explore_line(start, position / tile_size);
+                    exploration.explore(
+                        tile_index / exploration.cols(),
+                        tile_index % exploration.cols(),
+                    );
+                }
+                None => {
+                    let angle = rays.angles[column] + self.camera.rotation.to_radians();
+                    exploration.explore_line(start, start + Vec2::from_angle(angle) * reach);
+                }
+            }
+        }
+    }
+
+    /// Makes the renderer again on a new device, then casts the view again into it.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn recover_device(&mut self) -> anyhow::Result<()> {
+        self.renderer.recover(&self.maps[self.current_map_key])?;
+        if !self.renderer.is_loading() {
+            self.monitors = self.create_monitors()?;
+        }
+        // The new device's instance buffers start empty
+        self.view.cast_from = None;
+        for (_, automap) in &mut self.windows {
+            *automap = Automap::default();
+        }
+        Ok(())
+    }
+
+    /// The web has no way to block on opening a new device.
+    #[cfg(target_arch = "wasm32")]
+    fn recover_device(&mut self) -> anyhow::Result<()> {
+        anyhow::bail!("the GPU device was lost")
+    }
+
+    /// Pairs each monitor texture layer with its camera. Needs the map's textures to be loaded.
+    fn create_monitors(&self) -> anyhow::Result<Vec<Monitor>> {
+        let current_map = self.maps.get(self.current_map_key).unwrap();
+        let (monitor_width, monitor_height) = self.renderer.monitor_size()?;
+
+        let mut monitors = Vec::new();
+        for tile_id in self.renderer.monitor_tile_ids()? {
+            if let Some(TileType::Monitor(data)) = current_map.tile_type(tile_id) {
+                monitors.push(Monitor {
+                    camera: data.camera,
+                    view: View::new(monitor_width, monitor_height, data.camera.fov)?,
+                });
+            }
+        }
+
+        Ok(monitors)
+    }
+
+    #[profiling::function]
+    fn update_rays(&mut self) -> anyhow::Result<()> {
+        let current_map = self.maps.get(self.current_map_key).unwrap();
+        let camera = self.view_camera();
+
+        let step = self.ray_step();
+        if self.column_interleaving && step == 1 {
+            self.view.cast_rays_interleaved(
+                &camera,
+                current_map,
+                &self.linedefs,
+                &self.doors,
+                self.tile_size,
+                &self.renderer,
+            )
+        } else {
+            self.view.cast_rays(
+                &camera,
+                current_map,
+                &self.linedefs,
+                &self.doors,
+                self.tile_size,
+                &self.renderer,
+                step,
+            )
+        }
+    }
+
+    #[profiling::function]
+    fn update_quads(&mut self) -> anyhow::Result<()> {
+        let current_map = self.maps.get(self.current_map_key).unwrap();
+        let camera = self.view_camera();
+        self.view.draw_walls(
+            &camera,
+            current_map,
+            self.sky.fog(),
+            self.tile_size,
+            self.wall_height,
+            self.ray_step(),
+            &mut self.renderer,
+        )
+    }
+
+    #[profiling::function]
+    fn update_sprites(&mut self) -> anyhow::Result<()> {
+        let current_map = self.maps.get(self.current_map_key).unwrap();
+        let camera = self.view_camera();
+        let instances = self.screen_sprite_instances(&self.view, &camera)?;
+        self.renderer.set_sprite_instances(&instances)?;
+        let label_instances = self.screen_label_instances(&self.view, &camera);
+        self.renderer.set_label_instances(&label_instances)?;
+
+        let flat_sprites = self.flat_sprite_instances(current_map.sprites())?;
+        let floor_camera = self.view.floor_camera(
+            &camera,
+            current_map,
+            &self.sky,
+            self.tile_size,
+            self.wall_height,
+            flat_sprites.len(),
+            self.collision_radius(),
+            self.timing.elapsed.as_secs_f32(),
+        );
+        self.renderer.set_floor(&floor_camera, &flat_sprites)?;
+
+        Ok(())
+    }
+
+    /// Draws the map from above, following the view camera, with the nearest sprites as icons.
+    #[profiling::function]
+    fn update_top_down(&mut self, top_down: TopDown) -> anyhow::Result<()> {
+        let camera = self.head_camera();
+        let tile_size = self.tile_size as f32;
+        let (width, _) = self.renderer.internal_size();
+
+        let mut billboards = self.billboards();
+        billboards.sort_by(|a, b| {
+            let distance = |sprite: &Sprite| sprite.position.distance_squared(camera.position);
+            distance(a).total_cmp(&distance(b))
+        });
+        billboards.truncate(MAX_FLAT_SPRITES);
+        // Drawn in order, so the nearest go on top
+        let icons = billboards
+            .iter()
+            .rev()
+            .map(|sprite| {
+                Ok(FlatSpriteInstance {
+                    position: sprite.position.into(),
+                    half_size: tile_size * sprite.scale / 2.0,
+                    opacity: sprite.opacity,
+                    layer: self.renderer.sprite_layer(sprite.texture_path)?,
+                    _pad: [0; 3],
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        self.renderer.show_top_down(
+            &TopDownCamera {
+                center: camera.position.into(),
+                player: self.camera.position.into(),
+                player_rotation: self.camera.rotation.to_radians(),
+                units_per_pixel: top_down.tiles_across * tile_size / width as f32,
+                tile_size,
+                icon_count: icons.len() as u32,
+            },
+            &icons,
+        );
+        let label_instances = self.overlay_label_instances(&self.view);
+        self.renderer.set_label_instances(&label_instances)?;
+
+        Ok(())
+    }
+
+    /// Passes the water tint, damage flashes and color filter to the post-processing pass.
+    fn update_post_effect(&mut self) {
+        let effect = match self.water {
+            Some(water) if self.submersion > 0.0 => PostEffect::underwater(
+                water.tint,
+                self.submersion,
+                self.timing.elapsed.as_secs_f32(),
+            ),
+            _ => PostEffect::default(),
+        };
+        let (flash_color, flash) = self.damage_flash;
+        let flash = flash * (1.0 - self.accessibility.flash_reduction);
+        self.renderer.set_post_effect(
+            effect
+                .with_flash(flash_color, flash)
+                .with_filter(self.accessibility.color_filter.matrix()),
+        );
+    }
+
+    /// Casts and builds the right eye's view in stereo, the same way as the main view's.
+    #[profiling::function]
+    fn update_stereo(&mut self) -> anyhow::Result<()> {
+        let Some(eye_separation) = self.stereo.as_ref().map(|stereo| stereo.eye_separation) else {
+            return Ok(());
+        };
+        let current_map = self.maps.get(self.current_map_key).unwrap();
+        let camera = self.eye_camera(eye_separation / 2.0);
+        let step = self.ray_step();
+
+        if let Some(stereo) = &mut self.stereo {
+            stereo.view.cast_rays(
+                &camera,
+                current_map,
+                &self.linedefs,
+                &self.doors,
+                self.tile_size,
+                &self.renderer,
+                step,
+            )?;
+        }
+        let Some(stereo) = &self.stereo else {
+            return Ok(());
+        };
+
+        let instances = stereo.view.walls(
+            &camera,
+            current_map,
+            self.sky.fog(),
+            self.tile_size,
+            self.wall_height,
+            step,
+        );
+        for (i, instance) in instances.into_iter().enumerate() {
+            self.renderer.set_stereo_instance(i, instance);
+        }
+
+        let sprite_instances = self.screen_sprite_instances(&stereo.view, &camera)?;
+        self.renderer.set_stereo_sprite_instances(&sprite_instances);
+        let label_instances = self.screen_label_instances(&stereo.view, &camera);
+        self.renderer.set_stereo_label_instances(&label_instances);
+
+        let flat_sprites = self.flat_sprite_instances(current_map.sprites())?;
+        let floor_camera = stereo.view.floor_camera(
+            &camera,
+            current_map,
+            &self.sky,
+            self.tile_size,
+            self.wall_height,
+            flat_sprites.len(),
+            self.collision_radius(),
+            self.timing.elapsed.as_secs_f32(),
+        );
+        self.renderer.set_stereo_floor(&floor_camera, &flat_sprites);
+
+        Ok(())
+    }
+
+    /// Casts and builds the rear-view mirror from behind the player's head, without the weapon
+    /// or any overlays.
+    #[profiling::function]
+    fn update_rear_view(&mut self) -> anyhow::Result<()> {
+        let Some(fov) = self.rear_view.as_ref().map(|mirror| mirror.area.fov) else {
+            return Ok(());
+        };
+        self.renderer.show_rear_view(!self.menus.is_open());
+        let current_map = self.maps.get(self.current_map_key).unwrap();
+        let head = self.head_camera();
+        let camera = Camera {
+            rotation: (head.rotation + 180.0).rem_euclid(360.0),
+            fov,
+            // The pitch is in the main view's pixels
+            pitch: 0.0,
+            ..head
+        };
+
+        if let Some(mirror) = &mut self.rear_view {
+            mirror.view.cast_rays(
+                &camera,
+                current_map,
+                &self.linedefs,
+                &self.doors,
+                self.tile_size,
+                &self.renderer,
+                1,
+            )?;
+        }
+        let Some(mirror) = &self.rear_view else {
+            return Ok(());
+        };
+
+        let instances = mirror.view.walls(
+            &camera,
+            current_map,
+            self.sky.fog(),
+            self.tile_size,
+            self.wall_height,
+            1,
+        );
+        for (i, instance) in instances.into_iter().enumerate() {
+            self.renderer.set_rear_view_instance(i, instance);
+        }
+
+        let sprite_instances = mirror.view.sprite_instances(
+            &camera,
+            &self.billboards_in_front(&camera),
+            self.tile_size,
+            self.wall_height,
+            &self.sky,
+            &self.renderer,
+        )?;
+        self.renderer
+            .set_rear_view_sprite_instances(&sprite_instances);
+        let label_instances =
+            mirror
+                .view
+                .label_instances(&camera, current_map.labels(), self.sky.fog());
+        self.renderer
+            .set_rear_view_label_instances(&label_instances);
+
+        let flat_sprites = self.flat_sprite_instances(current_map.sprites())?;
+        let floor_camera = mirror.view.floor_camera(
+            &camera,
+            current_map,
+            &self.sky,
+            self.tile_size,
+            self.wall_height,
+            flat_sprites.len(),
+            0.0,
+            self.timing.elapsed.as_secs_f32(),
+        );
+        self.renderer
+            .set_rear_view_floor(&floor_camera, &flat_sprites);
+
+        Ok(())
+    }
+
+    /// The sprite columns of a view of the player's surroundings, with the held weapon over
+    /// them.
+    fn screen_sprite_instances(
+        &self,
+        view: &View,
+        camera: &Camera,
+    ) -> anyhow::Result<Vec<SpriteInstance>> {
+        let mut instances = view.sprite_instances(
+            camera,
+            &self.billboards_in_front(camera),
+            self.tile_size,
+            self.wall_height,
+            &self.sky,
+            &self.renderer,
+        )?;
+        if let (Some(weapon), Some(sprite)) = (self.arsenal.current(), self.arsenal.sprite()) {
+            instances.extend(
+                view.viewmodel_instances(self.renderer.sprite_layer(sprite)?, weapon.scale),
+            );
+        }
+
+        Ok(instances)
+    }
+
+    /// The map's labels in a view of the player's surroundings, with the
+    /// [overlays](Self::overlay_label_instances) over them.
+    fn screen_label_instances(&self, view: &View, camera: &Camera) -> Vec<SpriteInstance> {
+        let current_map = self.maps.get(self.current_map_key).unwrap();
+        let mut instances = view.label_instances(camera, current_map.labels(), self.sky.fog());
+        instances.extend(self.overlay_label_instances(view));
+        instances
+    }
+
+    /// The performance overlay, any caption and any open menu, laid out for a view.
+    fn overlay_label_instances(&self, view: &View) -> Vec<SpriteInstance> {
+        let mut instances = Vec::new();
+        if self.performance_overlay {
+            instances.extend(view.overlay_instances(&self.performance_lines()));
+        }
+        if let Some(text) = self.sequencer.text() {
+            instances.extend(view.caption_instances(text));
+        }
+        if let Some((screen, selected)) = self.menus.current() {
+            let settings = self.settings();
+            let items: Vec<String> = screen
+                .items
+                .iter()
+                .map(|item| Menus::item_text(item, &settings))
+                .collect();
+            instances.extend(view.menu_instances(&screen.title, &items, selected));
+        }
+
+        instances
+    }
+
+    /// The radius of the player's collision box to outline on the floor, or 0 when it isn't
+    /// shown.
+    fn collision_radius(&self) -> f32 {
+        #[cfg(debug_assertions)]
+        if self.debug_flags.show_collision {
+            return PLAYER_RADIUS;
+        }
+
+        0.0
+    }
+
+    /// The camera the main view is drawn from: the left eye's in stereo, otherwise
+    /// [`head_camera`](Self::head_camera).
+    fn view_camera(&self) -> Camera {
+        let eye_separation = self
+            .stereo
+            .as_ref()
+            .map_or(0.0, |stereo| stereo.eye_separation);
+        self.eye_camera(-eye_separation / 2.0)
+    }
+
+    /// The head camera moved `offset` to its right, for one eye in stereo.
+    fn eye_camera(&self, offset: f32) -> Camera {
+        let camera = self.head_camera();
+        let right = Vec2::from_angle((camera.rotation + 90.0).to_radians());
+        Camera {
+            position: camera.position + right * offset,
+            ..camera
+        }
+    }
+
+    /// The player's camera as the main view sees it, raised by the floor they're standing on
+    /// and sunk into any water they're in, or a sequence's camera while it has the view.
+    fn head_camera(&self) -> Camera {
+        if let Some(camera) = self.sequencer.camera() {
+            // The view's rays are spread for the player's field of view
+            return Camera {
+                fov: self.camera.fov,
+                ..camera
+            };
+        }
+        let sink = self
+            .water
+            .map_or(0.0, |water| water.depth * self.submersion);
+        let crouch = if self.crouching { CROUCH_HEIGHT } else { 0.0 };
+        let bob = if self.accessibility.head_bob {
+            let pace = (self.velocity.length() / MOVE_SPEED).min(1.0);
+            self.bob_phase.sin().abs() * BOB_HEIGHT * pace
+        } else {
+            0.0
+        };
+        let height = self.camera.height as f32 + self.player_floor - sink - crouch + bob;
+
+        let (amplitude, left, duration) = self.shake;
+        let shake = if left > 0.0 && duration > 0.0 {
+            amplitude * self.accessibility.screen_shake * left / duration
+        } else {
+            0.0
+        };
+        // Fixed frequencies rather than the simulation's randomness, so shaking can't change a
+        // deterministic run
+        let time = self.timing.elapsed.as_secs_f32();
+        let turn = shake * (time * 47.0).sin();
+        let tilt = shake * (time * 61.0).cos() * self.view.height as f32 / self.camera.fov;
+
+        Camera {
+            height: height.max(0.0) as u16,
+            rotation: self.camera.rotation + turn,
+            pitch: self.camera.pitch + tilt,
+            ..self.camera
+        }
+    }
+
+    /// Whether a straight line from `a` to `b` clears every wall.
+    pub fn can_see(&self, a: Vec2, b: Vec2) -> bool {
+        self.terrain().can_see(a, b)
+    }
+
+    /// The nearest wall along a ray from `origin` at `angle` radians, if it hits one.
+    pub fn cast_ray(&self, origin: Vec2, angle: f32) -> Option<PublicWallHit> {
+        let current_map = self.maps.get(self.current_map_key).unwrap();
+        let hit = cast_ray(
+            origin,
+            angle,
+            current_map,
+            &self.linedefs,
+            &self.doors,
+            self.tile_size,
+        )?;
+        let cols = current_map.size().cols();
+        let tiles = current_map.size().rows() * cols;
+        Some(PublicWallHit {
+            distance: hit.dist,
+            position: Vec2::new(hit.intersection.x, hit.intersection.y),
+            tile: (hit.tile_index < tiles).then(|| (hit.tile_index / cols, hit.tile_index % cols)),
+        })
+    }
+
+    /// The tile type under a point, if it's inside the map.
+    fn tile_type_at(&self, position: Vec2) -> Option<TileType> {
+        let current_map = self.maps.get(self.current_map_key).unwrap();
+        self.tile_id_at(position)
+            .and_then(|tile_id| current_map.tile_type(tile_id))
+    }
+
+    /// The id of the tile under a point, if it's inside the map.
+    fn tile_id_at(&self, position: Vec2) -> Option<u8> {
+        let current_map = self.maps.get(self.current_map_key).unwrap();
+        let (row, col) = current_map.world_to_cell(position)?;
+        current_map.tile_id(row, col)
+    }
+
+    /// The map's sprites and a sprite for each entity that has one, standing on any step or
+    /// ramp under them.
+    fn billboards(&self) -> Vec<Sprite> {
+        let current_map = self.maps.get(self.current_map_key).unwrap();
+        let entity_sprites = self.entities.iter().filter_map(|(_, entity)| {
+            entity
+                .texture_path
+                .map(|texture_path| Sprite::new(entity.position, texture_path))
+        });
+        current_map
+            .sprites()
+            .iter()
+            .copied()
+            .chain(entity_sprites)
+            .map(|sprite| Sprite {
+                z_offset: sprite.z_offset + self.terrain().slope_height_at(sprite.position),
+                ..sprite
+            })
+            .collect()
+    }
+
+    /// The [billboards](Self::billboards) that aren't wholly behind `camera`, so those out of
+    /// sight aren't sorted and clipped against the view.
+    fn billboards_in_front(&self, camera: &Camera) -> Vec<Sprite> {
+        let forward = Vec2::from_angle(camera.rotation.to_radians());
+        let tile_size = self.tile_size as f32;
+        let mut billboards = self.billboards();
+        if let Some(weather) = self.maps[self.current_map_key].metadata().weather() {
+            billboards.extend(self.weather.sprites(weather));
+        }
+        billboards.retain(|sprite| {
+            (sprite.position - camera.position).dot(forward) > -tile_size * sprite.scale / 2.0
+        });
+        billboards
+    }
+
+    /// The map's flat sprites, in map order. They don't depend on the camera, so every view
+    /// shares them.
+    fn flat_sprite_instances(&self, sprites: &[Sprite]) -> anyhow::Result<Vec<FlatSpriteInstance>> {
+        sprites
+            .iter()
+            .filter(|sprite| sprite.flat)
+            .take(MAX_FLAT_SPRITES)
+            .map(|sprite| {
+                Ok(FlatSpriteInstance {
+                    position: sprite.position.into(),
+                    half_size: self.tile_size as f32 * sprite.scale / 2.0,
+                    opacity: sprite.opacity,
+                    layer: self.renderer.sprite_layer(sprite.texture_path)?,
+                    _pad: [0; 3],
+                })
+            })
+            .collect()
+    }
+
+    #[profiling::function]
+    fn update_monitors(&mut self) -> anyhow::Result<()> {
+        let current_map = self.maps.get(self.current_map_key).unwrap();
+        let flat_sprites = self.flat_sprite_instances(current_map.sprites())?;
+        let billboards = self.billboards();
+
+        for (m, monitor) in self.monitors.iter_mut().enumerate() {
+            monitor.view.cast_rays(
+                &monitor.camera,
+                current_map,
+                &self.linedefs,
+                &self.doors,
+                self.tile_size,
+                &self.renderer,
+                1,
+            )?;
+
+            let instances = monitor.view.walls(
+                &monitor.camera,
+                current_map,
+                self.sky.fog(),
+                self.tile_size,
+                self.wall_height,
+                1,
+            );
+            for (i, instance) in instances.into_iter().enumerate() {
+                self.renderer.set_monitor_instance(m, i, instance)?;
+            }
+
+            let sprite_instances = monitor.view.sprite_instances(
+                &monitor.camera,
+                &billboards,
+                self.tile_size,
+                self.wall_height,
+                &self.sky,
+                &self.renderer,
+            )?;
+            self.renderer
+                .set_monitor_sprite_instances(m, &sprite_instances)?;
+
+            let label_instances =
+                monitor
+                    .view
+                    .label_instances(&monitor.camera, current_map.labels(), self.sky.fog());
+            self.renderer
+                .set_monitor_label_instances(m, &label_instances)?;
+
+            let floor_camera = monitor.view.floor_camera(
+                &monitor.camera,
+                current_map,
+                &self.sky,
+                self.tile_size,
+                self.wall_height,
+                flat_sprites.len(),
+                0.0,
+                self.timing.elapsed.as_secs_f32(),
+            );
+            self.renderer
+                .set_monitor_floor(m, &floor_camera, &flat_sprites)?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves to another map, reporting the stats of the one being left. Its textures are
+    /// loaded on demand if they aren't cached.
+    pub fn switch_map(&mut self, map_key: &'static str) -> anyhow::Result<()> {
+        anyhow::ensure!(self.maps.contains_key(map_key), "no map named {map_key}");
+        let stats = std::mem::take(&mut self.stats);
+        self.stats_events.push(StatsEvent::LevelFinished(stats));
+        self.load_map(map_key)
+    }
+
+    /// Moves to another map, or starts the current one over, with fresh stats.
+    fn load_map(&mut self, map_key: &'static str) -> anyhow::Result<()> {
+        let map = self
+            .maps
+            .get(map_key)
+            .with_context(|| format!("no map named {map_key}"))?;
+
+        self.current_map_key = map_key;
+        self.renderer.switch_map(map_key, map);
+        self.audio.load_map(map, self.renderer.assets());
+        self.elevators.load_map(map);
+        self.doors.load_map(map);
+        self.sky.apply(map.metadata());
+        self.weather.load_map();
+        self.lightning.load_map();
+        self.linedefs.load_map(map, self.tile_size);
+        self.elevator_tile = None;
+        self.player_floor = 0.0;
+        self.player_steps.clear();
+        if let Some((position, rotation)) = map.metadata().player_start() {
+            self.camera.position = position;
+            self.camera.rotation = rotation;
+        }
+        self.entities.clear();
+        spawn_map_entities(&mut self.entities, &self.renderer, map, &self.entity_types);
+        self.spawners.load_map(map);
+        self.checkpoint_region = None;
+        self.stats = level_stats(map_key, map);
+        self.sequencer.load_map(map);
+        self.noises.clear();
+        self.path_stamps.clear();
+        // The last cast's hits belong to the old map
+        self.view.cast_from = None;
+
+        // Monitors are rebuilt once the textures are in if they have to load first
+        self.monitors = if self.renderer.is_loading() {
+            Vec::new()
+        } else {
+            self.create_monitors()?
+        };
+
+        Ok(())
+    }
+
+    /// Opens another window onto the game, showing `content`. Returns its index for
+    /// [`Renderer::set_window_image`].
+    pub fn add_window(
+        &mut self,
+        window: Arc<dyn wgpu::WindowHandle>,
+        width: u32,
+        height: u32,
+        content: WindowContent,
+    ) -> anyhow::Result<usize> {
+        let index = self.renderer.add_window(window, width, height)?;
+        self.windows.push((content, Automap::default()));
+        Ok(index)
+    }
+
+    /// Draws the automap again for the windows showing it, if it's changed.
+    fn update_windows(&mut self) {
+        let Some(exploration) = self.explored.get(self.current_map_key) else {
+            return;
+        };
+        for (index, (content, automap)) in self.windows.iter_mut().enumerate() {
+            if *content == WindowContent::Automap
+                && let Some(image) = automap.take_image(
+                    self.current_map_key,
+                    &self.maps[self.current_map_key],
+                    exploration,
+                    &self.camera,
+                    self.tile_size as f32,
+                    self.accessibility.high_contrast_automap,
+                )
+            {
+                self.renderer.set_window_image(index, &image);
+            }
+        }
+    }
+
+    /// Shows the automap in place of the view, or the view again. The game keeps running
+    /// underneath.
+    pub fn set_automap(&mut self, open: bool) {
+        if !open && self.automap.take().is_some() {
+            self.renderer.hide_overhead();
+        } else if open && self.automap.is_none() && self.editor.is_none() {
+            self.automap = Some(Automap::default());
+        }
+    }
+
+    pub fn exploration(&self, map_key: &str) -> Option<&Exploration> {
+        self.explored.get(map_key)
+    }
+
+    /// Replaces what's been explored of a map, such as with one from a save game.
+    pub fn set_exploration(
+        &mut self,
+        map_key: &'static str,
+        exploration: Exploration,
+    ) -> anyhow::Result<()> {
+        let map = self
+            .maps
+            .get(map_key)
+            .with_context(|| format!("no map named {map_key}"))?;
+        anyhow::ensure!(
+            exploration.fits(map),
+            "a {}x{} exploration doesn't fit map {map_key}",
+            exploration.cols(),
+            exploration.rows()
+        );
+        self.explored.insert(map_key, exploration);
+        Ok(())
+    }
+
+    /// Opens the level editor on the current map, with the cursor where the player is.
+    pub fn open_editor(&mut self) {
+        if self.editor.is_none() {
+            self.set_automap(false);
+            self.release_keys();
+            self.editor = Some(Editor::new(
+                &self.maps[self.current_map_key],
+                self.camera.position,
+                self.tile_size as f32,
+            ));
+        }
+    }
+
+    /// Closes the level editor and plays from its cursor. An edited map replaces the current
+    /// one, with its textures rebuilt and its entities spawned again. If the edited map can't
+    /// be played, the editor stays open with its edits.
+    pub fn close_editor(&mut self) -> anyhow::Result<()> {
+        let Some(editor) = &self.editor else {
+            return Ok(());
+        };
+
+        let map_key = self.current_map_key;
+        let position = editor.cursor_position();
+        if let Some(map) = editor.changed_map() {
+            map.validate()
+                .with_context(|| format!("map \"{map_key}\" is invalid"))?;
+            let map = map.clone();
+            let previous = Arc::make_mut(&mut self.maps).insert(map_key, map);
+            self.renderer.forget_map(map_key);
+            if let Err(error) = self.load_map(map_key) {
+                // The map being played goes back, so the edits can be fixed and tried again
+                if let Some(previous) = previous {
+                    Arc::make_mut(&mut self.maps).insert(map_key, previous);
+                }
+                self.renderer.forget_map(map_key);
+                return Err(error);
+            }
+        }
+        self.editor = None;
+        self.renderer.hide_overhead();
+        self.camera.position = position;
+        self.velocity = Vec2::ZERO;
+
+        Ok(())
+    }
+
+    /// Writes the map being edited to `maps/<key>.rcmap` in the map file format. See
+    /// [`Map::to_bytes`].
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_editor_map(&self) -> anyhow::Result<()> {
+        let Some(editor) = &self.editor else {
+            return Ok(());
+        };
+        let path = std::path::Path::new("maps").join(format!("{}.rcmap", self.current_map_key));
+        std::fs::create_dir_all("maps")?;
+        std::fs::write(&path, editor.map().to_bytes())
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        println!("saved {}", path.display());
+
+        Ok(())
+    }
+
+    pub fn map_metadata(&self) -> &MapMetadata {
+        self.maps[self.current_map_key].metadata()
+    }
+
+    pub fn current_map(&self) -> (&'static str, &Map) {
+        (self.current_map_key, &self.maps[self.current_map_key])
+    }
+
+    pub fn camera(&self) -> Camera {
+        self.camera
+    }
+
+    pub fn renderer(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+
+    fn move_dir(&self) -> f32 {
+        let PlayerController {
+            key_forward,
+            key_back,
+            key_left,
+            key_right,
+            ..
+        } = self.player_controller;
+        let rotation = self.camera.rotation;
+
+        if key_forward && !key_right && !key_left {
+            // forward
+            rotation
+        } else if key_back && !key_right && !key_left {
+            // backwards
+            rotation + 180.0
+        } else if key_right && !key_forward && !key_back {
+            // right
+            rotation + 90.0
+        } else if key_left && !key_forward && !key_back {
+            // left
+            rotation - 90.0
+        } else if key_forward && key_right {
+            // forward-right
+            rotation + 45.0
+        } else if key_forward && key_left {
+            // forward-left
+            rotation - 45.0
+        } else if key_back && key_right {
+            // backwards-right
+            rotation + 135.0
+        } else if key_back && key_left {
+            // backwards-left
+            rotation - 135.0
+        } else {
+            rotation
+        }
+    }
+
+    /// Advances the simulation by this frame's delta time. Deterministic runs take as many
+    /// fixed steps as fit, carrying the rest over to the next frame.
+    #[profiling::function]
+    fn simulate(&mut self) -> anyhow::Result<()> {
+        if self.pause.is_paused() || self.menus.is_open() {
+            return Ok(());
+        }
+
+        match self.simulation {
+            SimulationMode::Realtime => self.update_positions(self.delta_time()),
+            SimulationMode::Deterministic { .. } => {
+                self.fixed_step_remainder += self.delta_time();
+                while self.fixed_step_remainder >= FIXED_TIMESTEP {
+                    self.update_positions(FIXED_TIMESTEP)?;
+                    self.fixed_step_remainder -= FIXED_TIMESTEP;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The simulation's random number generator. AI, particles and procedural generation
+    /// should draw from this, never from their own, so deterministic runs stay repeatable.
+    #[allow(dead_code)]
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    pub fn update_positions(&mut self, delta_time: f32) -> anyhow::Result<()> {
+        self.sequencer
+            .update(delta_time, self.camera.position, &mut self.sequence_events);
+
+        let in_water = match self.tile_type_at(self.camera.position) {
+            Some(TileType::Water(water)) => {
+                self.water = Some(water);
+                true
+            }
+            _ => false,
+        };
+        let target = if in_water { 1.0 } else { 0.0 };
+        let change = delta_time * SUBMERSION_RATE;
+        self.submersion = if self.submersion < target {
+            (self.submersion + change).min(target)
+        } else {
+            (self.submersion - change).max(target)
+        };
+
+        self.update_hazard(delta_time);
+
+        let stance = if self.crouching {
+            CROUCH_SPEED
+        } else if self.sprinting {
+            SPRINT_SPEED
+        } else {
+            1.0
+        };
+        let moving = self.player_controller.key_forward
+            || self.player_controller.key_back
+            || self.player_controller.key_left
+            || self.player_controller.key_right;
+        let direction = if moving && !self.sequencer.input_locked() {
+            let move_dir = self.move_dir().keep_in_range(0.0, 360.0).to_radians();
+            Vec2::new(move_dir.cos(), move_dir.sin())
+        } else {
+            Vec2::ZERO
+        };
+        self.tick += 1;
+        let step = PlayerStep {
+            tick: self.tick,
+            delta_time,
+            direction,
+            stance,
+        };
+        self.player_steps.push(step);
+
+        let mut position = self.camera.position;
+        let mut velocity = self.velocity;
+        let moved = move_player(
+            self.terrain(),
+            self.noclip(),
+            &mut position,
+            &mut velocity,
+            step,
+        );
+        self.camera.position = position;
+        self.velocity = velocity;
+        self.bob_phase = (self.bob_phase + moved / BOB_STRIDE * PI) % (2.0 * PI);
+
+        let (amplitude, left, duration) = self.shake;
+        self.shake = (amplitude, (left - delta_time).max(0.0), duration);
+
+        self.update_elevators(delta_time);
+        self.update_doors(delta_time);
+        self.sky
+            .advance(delta_time, self.maps[self.current_map_key].metadata());
+        self.update_weather(delta_time);
+        self.update_lightning(delta_time);
+        self.update_weapon(delta_time);
+        self.update_noise(delta_time);
+        self.entities
+            .update_dormancy(self.entity_culling.as_ref(), self.camera.position);
+        self.update_path_stamps(delta_time);
+        self.update_ai(delta_time);
+        self.update_entities(delta_time);
+        let map = &self.maps[self.current_map_key];
+        self.spawners.update(
+            delta_time,
+            self.camera.position,
+            &mut self.entities,
+            &self.entity_types,
+            |entity| check_sprite(&self.renderer, map, entity),
+            &mut self.wave_events,
+        );
+        self.update_checkpoints();
+        self.update_secrets();
+        self.stats.time += Duration::from_secs_f32(delta_time);
+
+        Ok(())
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn player_velocity(&self) -> Vec2 {
+        self.velocity
+    }
+
+    /// Moves the player to where a server says they were after step `tick`, then replays
+    /// their movement on every step since, so input the server hasn't seen yet isn't lost.
+    /// Steps that are too old to have been kept are skipped.
+    pub fn reconcile_player(&mut self, tick: u64, position: Vec2, velocity: Vec2) {
+        let noclip = self.noclip();
+        // Built from the fields, as the steps are borrowed mutably alongside it
+        let terrain = Terrain {
+            map: &self.maps[self.current_map_key],
+            doors: &self.doors,
+            linedefs: &self.linedefs,
+            tile_size: self.tile_size,
+        };
+        (self.camera.position, self.velocity) = self
+            .player_steps
+            .reconcile(terrain, noclip, tick, position, velocity);
+    }
+
+    /// Counts the secrets the player has just found.
+    fn update_secrets(&mut self) {
+        let position = self.camera.position;
+        for secret in self.maps[self.current_map_key].secrets() {
+            if secret.region.contains(position) && !self.stats.secrets_found.contains(&secret.name)
+            {
+                self.stats.secrets_found.push(secret.name.clone());
+                self.stats_events
+                    .push(StatsEvent::SecretFound(secret.name.clone()));
+            }
+        }
+    }
+
+    /// Reaches a map checkpoint the player has just walked into.
+    fn update_checkpoints(&mut self) {
+        let region = self.checkpoint_region_at(self.camera.position);
+        if let Some(index) = region
+            && self.checkpoint_region != Some(index)
+        {
+            let name = self.maps[self.current_map_key].checkpoints()[index]
+                .name
+                .clone();
+            self.save_checkpoint(name.clone());
+            self.checkpoint_events.push(CheckpointEvent::Reached(name));
+        }
+        self.checkpoint_region = region;
+    }
+
+    /// The index of the first of the current map's checkpoints containing `position`.
+    fn checkpoint_region_at(&self, position: Vec2) -> Option<usize> {
+        self.maps[self.current_map_key]
+            .checkpoints()
+            .iter()
+            .position(|checkpoint| checkpoint.region.contains(position))
+    }
+
+    /// Records the player and the current map as they are now, replacing the last checkpoint.
+    pub fn save_checkpoint(&mut self, name: String) {
+        self.checkpoint = Some(Snapshot {
+            name,
+            map_key: self.current_map_key,
+            position: self.camera.position,
+            rotation: self.camera.rotation,
+            player_floor: self.player_floor,
+            elevator_tile: self.elevator_tile,
+            elevators: self.elevators.clone(),
+            doors: self.doors.clone(),
+            entities: self.entities.clone(),
+            spawners: self.spawners.clone(),
+            inventory: self.inventory.clone(),
+            stats: self.stats.clone(),
+        });
+    }
+
+    pub fn checkpoint_name(&self) -> Option<&str> {
+        self.checkpoint
+            .as_ref()
+            .map(|snapshot| snapshot.name.as_str())
+    }
+
+    /// Respawns at the last checkpoint at the start of the next frame. Returns whether there
+    /// is one.
+    pub fn request_respawn(&mut self) -> bool {
+        self.respawn_pending = self.checkpoint.is_some();
+        self.respawn_pending
+    }
+
+    /// Puts the player and their map back as they were at the last checkpoint, switching maps
+    /// if it was reached on another one.
+    fn respawn(&mut self) -> anyhow::Result<()> {
+        let Some(snapshot) = self.checkpoint.clone() else {
+            return Ok(());
+        };
+        if snapshot.map_key != self.current_map_key {
+            self.load_map(snapshot.map_key)?;
+        }
+
+        self.camera.position = snapshot.position;
+        self.camera.rotation = snapshot.rotation;
+        self.velocity = Vec2::ZERO;
+        self.player_steps.clear();
+        self.player_floor = snapshot.player_floor;
+        self.elevator_tile = snapshot.elevator_tile;
+        self.elevators = snapshot.elevators;
+        self.doors = snapshot.doors;
+        self.entities = snapshot.entities;
+        self.spawners = snapshot.spawners;
+        self.inventory = snapshot.inventory;
+        self.stats = LevelStats {
+            time: self.stats.time,
+            ..snapshot.stats
+        };
+        self.hazard_cooldown = 0.0;
+        self.damage_flash.1 = 0.0;
+        self.checkpoint_region = self.checkpoint_region_at(snapshot.position);
+        self.view.cast_from = None;
+
+        self.checkpoint_events
+            .push(CheckpointEvent::Respawned(snapshot.name));
+        Ok(())
+    }
+
+    /// Moves the elevators, activates one the player has just stepped onto, and carries the
+    /// player with the floor under them.
+    fn update_elevators(&mut self, delta_time: f32) {
+        let tile_id = self.tile_id_at(self.camera.position);
+        let elevator_tile = tile_id.filter(|_| {
+            matches!(
+                self.tile_type_at(self.camera.position),
+                Some(TileType::Elevator(_))
+            )
+        });
+        if let Some(tile_id) = elevator_tile
+            && self.elevator_tile != Some(tile_id)
+        {
+            self.elevators.activate(tile_id);
+        }
+        self.elevator_tile = elevator_tile;
+        self.walls_moved |= self.elevators.update(delta_time);
+
+        // Rising floors lift the player straight away, and they fall off raised ones
+        let floor = self.elevators.floor_height(tile_id)
+            + self.terrain().slope_height_at(self.camera.position);
+        self.player_floor = if floor >= self.player_floor {
+            floor
+        } else {
+            (self.player_floor - FALL_SPEED * delta_time).max(floor)
+        };
+    }
+
+    /// Lets rain or snow fall around the player, with its sound as loud as the sky is open.
+    fn update_weather(&mut self, delta_time: f32) {
+        let current_map = &self.maps[self.current_map_key];
+        self.weather.update(
+            delta_time,
+            current_map,
+            self.camera.position,
+            self.tile_size as f32,
+            self.wall_height as f32,
+            &mut self.rng,
+        );
+        if let Some(weather) = current_map.metadata().weather()
+            && let Some(sound_path) = weather.sound_path
+        {
+            self.audio
+                .set_ambient_volume(sound_path, weather.volume * self.weather.exposure());
+        }
+    }
+
+    /// Strikes the lightning sequences called for, lights the scene with the flashes and plays
+    /// thunder that has arrived.
+    fn update_lightning(&mut self, delta_time: f32) {
+        for lightning in self.sequencer.take_lightning() {
+            self.lightning.strike(lightning);
+        }
+        let thunder = self.lightning.update(
+            delta_time,
+            self.accessibility.flash_reduction,
+            self.accessibility.photosensitive_safe,
+        );
+        for (sound_path, volume) in thunder {
+            self.audio
+                .play(SoundEmitter::ambient(sound_path).once().with_volume(volume));
+        }
+        self.sky.set_flash(self.lightning.brightness());
+    }
+
+    /// Slides the doors, holding open any with the player or an entity in the doorway.
+    fn update_doors(&mut self, delta_time: f32) {
+        let tile_size = self.tile_size as f32;
+        let tile_of = |position: Vec2| {
+            let tile = (position / tile_size).floor();
+            (tile.y as usize, tile.x as usize)
+        };
+        let occupied: Vec<(usize, usize)> = std::iter::once(self.camera.position)
+            .chain(self.entities.iter().map(|(_, entity)| entity.position))
+            .filter(|position| position.x >= 0.0 && position.y >= 0.0)
+            .map(tile_of)
+            .collect();
+        self.walls_moved |= self
+            .doors
+            .update(delta_time, |tile| occupied.contains(&tile));
+    }
+
+    /// Steers each awake entity with an AI: at the player while it can see them, around where
+    /// it last saw them for a while after, otherwise to a noise it heard or along its patrol
+    /// path.
+    fn update_ai(&mut self, delta_time: f32) {
+        let mut entities = std::mem::take(&mut self.entities);
+        let steps = plan_ai(
+            self.terrain(),
+            &self.hostility,
+            &self.path_stamps,
+            &entities,
+            self.camera.position,
+            delta_time,
+        );
+
+        // Search points draw from the engine's RNG, so they're picked here, in entity order
+        for ((_, entity), step) in entities.iter_mut().zip(steps) {
+            let Some(mut step) = step else {
+                continue;
+            };
+            if let Some(last_seen) = step.search_from {
+                step.ai.search_point = Some(self.search_point(last_seen, &step.ai, entity.radius));
+            }
+            entity.ai = Some(step.ai);
+            entity.velocity = step.velocity;
+            self.ai_events.extend(step.events);
+        }
+
+        // Doors along the way open as AI that can open them walks up
+        let doors_reached: Vec<(usize, usize)> = entities
+            .iter()
+            .filter(|(_, entity)| !entity.dormant)
+            .filter_map(|(_, entity)| {
+                let ai = entity.ai.as_ref().filter(|ai| ai.opens_doors)?;
+                self.door_ahead(ai, entity.position)
+            })
+            .collect();
+        for tile in doors_reached {
+            self.open_door_tile(tile);
+        }
+
+        self.entities = entities;
+    }
+
+    /// Somewhere open within an AI's search radius of where it last saw its target, for it to
+    /// look next. Where it was last seen if a few tries all land in walls.
+    fn search_point(&mut self, last_seen: Vec2, ai: &Ai, radius: f32) -> Vec2 {
+        for _ in 0..SEARCH_POINT_TRIES {
+            let angle = self.rng.range(0.0, 2.0 * PI);
+            let distance = self.rng.range(0.0, ai.search_radius);
+            let point = last_seen + Vec2::from_angle(angle) * distance;
+            if !self.terrain().collides(point, radius) {
+                return point;
+            }
+        }
+        last_seen
+    }
+
+    /// The shut door tile an AI's route takes it into next, if it's within reach.
+    fn door_ahead(&self, ai: &Ai, position: Vec2) -> Option<(usize, usize)> {
+        let tile_size = self.tile_size as f32;
+        let next = ai.route.last().copied().or(ai.route_goal)?;
+        if next.x < 0.0 || next.y < 0.0 || position.distance(next) > DOOR_REACH * tile_size {
+            return None;
+        }
+        let tile = (next / tile_size).floor();
+        let tile = (tile.y as usize, tile.x as usize);
+        (self.doors.is_door(tile) && !self.doors.is_open(tile)).then_some(tile)
+    }
+
+    /// Makes AI plan its routes as though the tiles with their centers in `region` cost `cost`
+    /// times as much to cross, for `duration`. Stamps over the same tile multiply.
+    pub fn stamp_path_cost(&mut self, region: Region, cost: f32, duration: Duration) {
+        self.path_stamps.push(PathStamp {
+            region,
+            cost: cost.max(0.1),
+            left: duration.as_secs_f32(),
+        });
+    }
+
+    pub fn clear_path_stamps(&mut self) {
+        self.path_stamps.clear();
+    }
+
+    fn update_path_stamps(&mut self, delta_time: f32) {
+        for stamp in &mut self.path_stamps {
+            stamp.left -= delta_time;
+        }
+        self.path_stamps.retain(|stamp| stamp.left > 0.0);
+    }
+
+    /// Queues a noise for the next step. See [`update_noise`](Self::update_noise).
+    pub fn make_noise(&mut self, position: Vec2, radius: f32) {
+        self.noises.push((position, radius));
+    }
+
+    /// Makes the player's footsteps heard on noisy floors, then spreads each noise through the
+    /// map and alerts the AI entities it reaches.
+    fn update_noise(&mut self, delta_time: f32) {
+        let footstep_noise = match self.tile_type_at(self.camera.position) {
+            Some(TileType::Surface(surface)) => surface.footstep_noise,
+            _ => 0.0,
+        };
+        self.footstep_timer = (self.footstep_timer - delta_time).max(0.0);
+        if footstep_noise > 0.0
+            && self.velocity.length() > MOVE_SPEED * 0.5
+            && self.footstep_timer == 0.0
+        {
+            self.noises.push((self.camera.position, footstep_noise));
+            self.footstep_timer = FOOTSTEP_INTERVAL;
+        }
+
+        let current_map = &self.maps[self.current_map_key];
+        let tile_size = self.tile_size as f32;
+        for (position, radius) in std::mem::take(&mut self.noises) {
+            let reached = noise::propagate(current_map, position, radius, tile_size);
+            let mut heard_by = Vec::new();
+            for (id, entity) in self.entities.iter_mut() {
+                let tile = (entity.position / tile_size).floor();
+                if let Some(ai) = &mut entity.ai
+                    && tile.cmpge(Vec2::ZERO).all()
+                    && reached.contains_key(&(tile.y as usize, tile.x as usize))
+                {
+                    ai.heard = Some(position);
+                    heard_by.push(id);
+                }
+            }
+            self.noise_events.push(NoiseEvent {
+                position,
+                radius,
+                heard_by,
+            });
+        }
+    }
+
+    /// Fires the held weapon while fire is held and it's ready, if there's ammo for it.
+    fn update_weapon(&mut self, delta_time: f32) {
+        self.arsenal.update(delta_time);
+        if !self.player_controller.key_fire
+            || self.sequencer.input_locked()
+            || !self.arsenal.ready()
+        {
+            return;
+        }
+        let Some(weapon) = self.arsenal.current() else {
+            return;
+        };
+        if let Some((item, per_shot)) = &weapon.ammo {
+            if self.inventory.count(item) < *per_shot {
+                return;
+            }
+            self.inventory.remove(item, *per_shot);
+        }
+
+        let weapon = weapon.clone();
+        self.arsenal.fired();
+        let origin = self.camera.position;
+        let direction = Vec2::from_angle(self.camera.rotation.to_radians());
+        let projectile = match weapon.mode {
+            FireMode::Hitscan { range, damage } => {
+                if let Some(target) = self.hitscan(origin, direction, range) {
+                    self.hit_events.push(HitEvent {
+                        projectile: None,
+                        target: Body::Entity(target),
+                        damage,
+                    });
+                }
+                None
+            }
+            FireMode::Projectile { entity, speed } => {
+                // Far enough ahead not to start out touching the player
+                let position = origin + direction * (PLAYER_RADIUS + entity.radius + 1.0);
+                Some(self.entities.spawn(Entity {
+                    position,
+                    velocity: direction * speed,
+                    ..*entity
+                }))
+            }
+        };
+
+        if let Some(sound) = weapon.sound {
+            self.audio.play(SoundEmitter::ambient(sound).once());
+        }
+        if weapon.noise > 0.0 {
+            self.noises.push((origin, weapon.noise));
+        }
+        self.shot_events.push(ShotEvent {
+            weapon: weapon.name,
+            projectile,
+        });
+    }
+
+    /// The first solid entity a shot from `origin` along `direction` reaches within `range`,
+    /// if it's hostile to the player and not behind a wall.
+    fn hitscan(&self, origin: Vec2, direction: Vec2, range: f32) -> Option<EntityId> {
+        let mut reached: Vec<(EntityId, f32, Faction)> = self
+            .entities
+            .iter()
+            .filter(|(_, entity)| !entity.sensor)
+            .filter_map(|(id, entity)| {
+                let along = (entity.position - origin).dot(direction);
+                let off = (origin + direction * along).distance(entity.position);
+                if along < 0.0 || off > entity.radius {
+                    return None;
+                }
+                // Where the shot enters the entity's circle
+                let entry = (along - (entity.radius * entity.radius - off * off).sqrt()).max(0.0);
+                (entry <= range).then_some((id, entry, entity.faction))
+            })
+            .collect();
+        reached.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        let (id, _, faction) = reached
+            .into_iter()
+            .find(|(_, entry, _)| self.can_see(origin, origin + direction * *entry))?;
+        self.hostility
+            .is_hostile(Faction::PLAYER, faction)
+            .then_some(id)
+    }
+
+    /// Spends each projectile touching a body of a faction hostile to its own on the first such
+    /// body, reporting the hit.
+    fn resolve_hits(&mut self, contacts: &[Contact]) {
+        let mut spent = Vec::new();
+        for contact in contacts {
+            for (projectile, target) in [(contact.a, contact.b), (contact.b, contact.a)] {
+                let Body::Entity(id) = projectile else {
+                    continue;
+                };
+                let Some(entity) = self.entities.get(id) else {
+                    continue;
+                };
+                if !entity.sensor || entity.damage <= 0.0 || spent.contains(&id) {
+                    continue;
+                }
+                let target_faction = match target {
+                    Body::Player => Some(Faction::PLAYER),
+                    Body::Entity(target) => self
+                        .entities
+                        .get(target)
+                        .filter(|target| !target.sensor)
+                        .map(|target| target.faction),
+                };
+                if target_faction
+                    .is_some_and(|faction| self.hostility.is_hostile(entity.faction, faction))
+                {
+                    self.hit_events.push(HitEvent {
+                        projectile: Some(id),
+                        target,
+                        damage: entity.damage,
+                    });
+                    spent.push(id);
+                }
+            }
+        }
+
+        for id in spent {
+            self.entities.despawn(id);
+        }
+    }
+
+    /// Puts what the player can carry of each pickup they touch into their inventory,
+    /// despawning the pickups they've taken all of.
+    fn resolve_pickups(&mut self, contacts: &[Contact]) {
+        for contact in contacts {
+            let id = match (contact.a, contact.b) {
+                (Body::Player, Body::Entity(id)) | (Body::Entity(id), Body::Player) => id,
+                _ => continue,
+            };
+            let Some(pickup) = self
+                .entities
+                .get_mut(id)
+                .and_then(|entity| entity.pickup.as_mut())
+            else {
+                continue;
+            };
+
+            let count = self.inventory.add(&self.items, &pickup.item, pickup.count);
+            if count == 0 {
+                continue;
+            }
+            pickup.count -= count;
+            self.pickup_events.push(PickupEvent {
+                entity: id,
+                item: pickup.item.clone(),
+                count,
+            });
+            if pickup.count == 0 {
+                self.entities.despawn(id);
+            }
+        }
+    }
+
+    /// Moves the awake entities, then pushes overlapping bodies apart, records the contacts and
+    /// resolves projectile hits and pickups.
+    fn update_entities(&mut self, delta_time: f32) {
+        let cell_size = UPDATE_CELL_TILES * self.tile_size as f32;
+        let awake: Vec<&Entity> = self
+            .entities
+            .iter()
+            .filter(|(_, entity)| !entity.dormant)
+            .map(|(_, entity)| entity)
+            .collect();
+        let positions: Vec<Vec2> = awake.iter().map(|entity| entity.position).collect();
+        let terrain = self.terrain();
+        let moved = update_partitioned(cell_size, &positions, |index| {
+            let entity = awake[index];
+            terrain.slide_body(entity.position, entity.velocity * delta_time, entity.radius)
+        });
+        for ((_, entity), position) in self.awake_entities_mut().zip(moved) {
+            entity.position = position;
+        }
+
+        let mut colliders = vec![Collider {
+            body: Body::Player,
+            position: self.camera.position,
+            radius: PLAYER_RADIUS,
+            layers: CollisionLayers::PLAYER,
+            mask: CollisionLayers::ALL,
+            sensor: false,
+        }];
+        colliders.extend(
+            self.entities
+                .iter()
+                .filter(|(_, entity)| !entity.dormant)
+                .map(|(id, entity)| Collider::from_entity(id, entity)),
+        );
+
+        let mut contacts = Vec::new();
+        let pushes = resolve_collisions(&colliders, &mut contacts);
+        self.camera.position = slide_player(
+            self.terrain(),
+            self.noclip(),
+            self.camera.position,
+            pushes[0],
+        );
+        let awake: Vec<&Entity> = self
+            .entities
+            .iter()
+            .filter(|(_, entity)| !entity.dormant)
+            .map(|(_, entity)| entity)
+            .collect();
+        let positions: Vec<Vec2> = awake.iter().map(|entity| entity.position).collect();
+        let terrain = self.terrain();
+        let pushed = update_partitioned(cell_size, &positions, |index| {
+            let entity = awake[index];
+            terrain.slide_body(entity.position, pushes[index + 1], entity.radius)
+        });
+        for ((_, entity), position) in self.awake_entities_mut().zip(pushed) {
+            entity.position = position;
+        }
+
+        self.resolve_hits(&contacts);
+        self.resolve_pickups(&contacts);
+        // Deterministic frames resolve several steps into the same list
+        for contact in contacts {
+            if !self.contacts.contains(&contact) {
+                self.contacts.push(contact);
+            }
+        }
+    }
+
+    fn awake_entities_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut Entity)> {
+        self.entities
+            .iter_mut()
+            .filter(|(_, entity)| !entity.dormant)
+    }
+
+    /// Deals a hazard floor's damage in ticks while the player stands on it, and fades the
+    /// flash from the last tick.
+    fn update_hazard(&mut self, delta_time: f32) {
+        self.damage_flash.1 = (self.damage_flash.1 - delta_time * DAMAGE_FLASH_FADE_RATE).max(0.0);
+
+        let current_map = self.maps.get(self.current_map_key).unwrap();
+        let hazard = self.tile_id_at(self.camera.position).and_then(|tile_id| {
+            match current_map.tile_type(tile_id) {
+                Some(TileType::Hazard(hazard)) => Some((tile_id, hazard)),
+                _ => None,
+            }
+        });
+        let Some((tile_id, hazard)) = hazard else {
+            // Stepping back on damages straight away
+            self.hazard_cooldown = 0.0;
+            return;
+        };
+
+        self.hazard_cooldown -= delta_time;
+        while self.hazard_cooldown <= 0.0 {
+            let interval = hazard.interval.as_secs_f32();
+            self.hazard_cooldown += interval;
+
+            #[cfg(debug_assertions)]
+            if self.debug_flags.god_mode {
+                continue;
+            }
+            self.damage_events.push(DamageEvent {
+                amount: hazard.damage * interval,
+                tile_id,
+            });
+            self.damage_flash = (hazard.flash, 1.0);
+        }
+    }
+
+    /// Whether the player passes through walls, which only debug builds allow.
+    fn noclip(&self) -> bool {
+        cfg!(debug_assertions) && self.debug_flags.noclip
+    }
+
+    /// The current map's walls, doors and linedefs, apart from the rest of the engine.
+    fn terrain(&self) -> Terrain<'_> {
+        Terrain {
+            map: self.maps.get(self.current_map_key).unwrap(),
+            doors: &self.doors,
+            linedefs: &self.linedefs,
+            tile_size: self.tile_size,
+        }
+    }
+
+    pub fn set_dynamic_resolution(&mut self, dynamic_resolution: Option<DynamicResolution>) {
+        self.ray_step_controller = dynamic_resolution.map(RayStepController::new);
+    }
+
+    pub fn timing(&self) -> FrameTiming {
+        self.timing
+    }
+
+    pub fn set_column_interleaving(&mut self, column_interleaving: bool) {
+        self.column_interleaving = column_interleaving;
+    }
+
+    /// How many columns each of the main view's rays covers.
+    fn ray_step(&self) -> usize {
+        self.ray_step_controller
+            .as_ref()
+            .map_or(1, RayStepController::step)
+    }
+
+    /// Draws the view side by side for each eye, or goes back to a single view with `None`.
+    pub fn set_stereo(&mut self, stereo: Option<Stereo>) -> anyhow::Result<()> {
+        self.stereo = match stereo {
+            Some(stereo) => {
+                let (width, height) = self.renderer.internal_size();
+                Some(StereoView {
+                    eye_separation: stereo.eye_separation,
+                    view: View::new(width, height, self.camera.fov)?,
+                })
+            }
+            None => None,
+        };
+        self.renderer.set_stereo(self.stereo.is_some());
+        Ok(())
+    }
+
+    /// Shows what's behind the player in an area over the view, or stops with `None`.
+    pub fn set_rear_view(&mut self, rear_view: Option<RearView>) -> anyhow::Result<()> {
+        self.renderer.set_rear_view(rear_view);
+        self.rear_view = match (rear_view, self.renderer.rear_view_size()) {
+            (Some(area), Some((width, height))) => Some(RearViewMirror {
+                area,
+                view: View::new(width, height, area.fov)?,
+            }),
+            _ => None,
+        };
+        Ok(())
+    }
+
+    pub fn rear_view(&self) -> Option<RearView> {
+        self.rear_view.as_ref().map(|mirror| mirror.area)
+    }
+
+    /// Draws the map from above in place of the first-person view, or goes back to it with
+    /// `None`.
+    pub fn set_top_down(&mut self, top_down: Option<TopDown>) {
+        self.top_down = top_down;
+        if top_down.is_none() {
+            self.renderer.hide_top_down();
+        }
+    }
+
+    pub fn top_down(&self) -> Option<TopDown> {
+        self.top_down
+    }
+
+    pub fn stereo(&self) -> Option<Stereo> {
+        self.stereo.as_ref().map(|stereo| Stereo {
+            eye_separation: stereo.eye_separation,
+        })
+    }
+
+    pub fn set_performance_overlay(&mut self, performance_overlay: bool) {
+        self.performance_overlay = performance_overlay;
+    }
+
+    pub fn audio_output(&self) -> AudioOutput {
+        self.audio.output()
+    }
+
+    pub fn audio_config(&self) -> AudioConfig {
+        self.audio.volumes()
+    }
+
+    pub fn set_audio_config(&mut self, audio_config: AudioConfig) {
+        self.audio.set_volumes(audio_config);
+    }
+
+    pub fn activate_elevator(&mut self, tile_id: u8) -> bool {
+        self.elevators.activate(tile_id)
+    }
+
+    pub fn open_door(&mut self, position: Vec2) -> bool {
+        let tile = (position / self.tile_size as f32).floor();
+        tile.x >= 0.0 && tile.y >= 0.0 && self.open_door_tile((tile.y as usize, tile.x as usize))
+    }
+
+    /// Opens a door, adding it to the map changes if it wasn't already opening.
+    fn open_door_tile(&mut self, tile: (usize, usize)) -> bool {
+        let was_opening = self.doors.is_opening(tile);
+        if !self.doors.open(tile) {
+            return false;
+        }
+        if !was_opening {
+            self.map_changes.changes.push(MapChange::Door {
+                row: tile.0,
+                col: tile.1,
+                open: self.doors.openness(tile),
+                opening: true,
+            });
+        }
+        true
+    }
+
+    pub fn take_map_changes(&mut self) -> MapDiff {
+        std::mem::take(&mut self.map_changes)
+    }
+
+    /// Applies a diff from another copy of the current map. Changes to its tiles rebuild its
+    /// textures and doors, and ones to its entities, entity definitions or metadata take
+    /// effect when it's next started. Door changes where there's no door are skipped.
+    pub fn apply_map_diff(&mut self, diff: &MapDiff) -> anyhow::Result<()> {
+        let map_key = self.current_map_key;
+        let changes_map = diff
+            .changes()
+            .iter()
+            .any(|change| !matches!(change, MapChange::Door { .. }));
+        if changes_map {
+            let map = Arc::make_mut(&mut self.maps)
+                .get_mut(map_key)
+                .with_context(|| format!("no map named {map_key}"))?;
+            map.apply(diff)?;
+
+            let map = &self.maps[map_key];
+            self.renderer.forget_map(map_key);
+            self.renderer.switch_map(map_key, map);
+            self.doors.reload_map(map);
+        }
+
+        for change in diff.changes() {
+            if let MapChange::Door {
+                row,
+                col,
+                open,
+                opening,
+            } = change
+            {
+                self.doors.set((*row, *col), *open, *opening);
+            }
+        }
+        self.view.cast_from = None;
+        Ok(())
+    }
+
+    /// Decodes a diff accepting only paths the maps, as started or as changed since, or the
+    /// preloaded sprites use.
+    pub fn map_diff_from_bytes(&self, bytes: &[u8]) -> anyhow::Result<MapDiff> {
+        let paths = self
+            .original_maps
+            .values()
+            .chain(self.maps.values())
+            .flat_map(Map::paths)
+            .chain(self.renderer.preload_sprites().iter().copied());
+        MapDiff::from_bytes_with_migrations(bytes, &Migrations::new().with_allowed_paths(paths))
+    }
+
+    /// The changes that bring a fresh copy of the current map to where this one is now: its
+    /// tiles, entities, entity definitions and metadata, then every door that isn't shut.
+    pub fn map_state(&self) -> anyhow::Result<MapDiff> {
+        let map_key = self.current_map_key;
+        let mut diff = MapDiff::between(&self.original_maps[map_key], &self.maps[map_key])?;
+
+        let mut doors: Vec<_> = self.doors.moving().collect();
+        doors.sort_unstable_by_key(|(tile, ..)| *tile);
+        for ((row, col), open, opening) in doors {
+            diff = diff.with_door(row, col, open, opening);
+        }
+        Ok(diff)
+    }
+
+    pub fn weather_exposure(&self) -> f32 {
+        self.weather.exposure()
+    }
+
+    pub fn strike_lightning(&mut self, lightning: Lightning) {
+        self.lightning.strike(lightning);
+    }
+
+    pub fn time_of_day(&self) -> f32 {
+        self.sky.time_of_day()
+    }
+
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        self.sky.set_time_of_day(time_of_day);
+    }
+
+    pub fn register_entity_type(
+        &mut self,
+        type_name: String,
+        factory: impl Fn(&EntityDef) -> Entity + Send + Sync + 'static,
+    ) {
+        self.entity_types.register(type_name, factory);
+    }
+
+    pub fn spawn(&mut self, entity: Entity) -> anyhow::Result<EntityId> {
+        check_sprite(&self.renderer, &self.maps[self.current_map_key], &entity)?;
+        Ok(self.entities.spawn(entity))
+    }
+
+    pub fn despawn(&mut self, id: EntityId) -> Option<Entity> {
+        self.entities.despawn(id)
+    }
+
+    pub fn entity(&self, id: EntityId) -> Option<&Entity> {
+        self.entities.get(id)
+    }
+
+    pub fn entity_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        self.entities.get_mut(id)
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = (EntityId, &Entity)> {
+        self.entities.iter()
+    }
+
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
 
-const BYTES_PER_PIXEL: u8 = 4;
+    pub fn damage_events(&self) -> &[DamageEvent] {
+        &self.damage_events
+    }
 
-enum AngleQuadrant {
-    BottomRight,
-    BottomLeft,
-    TopLeft,
-    TopRight,
-}
+    pub fn wave_events(&self) -> &[WaveEvent] {
+        &self.wave_events
+    }
 
-#[derive(Clone, Copy, Debug)]
-enum TileSide {
-    Top,    // 0
-    Left,   // 1
-    Bottom, // 2
-    Right,  // 3
-}
+    pub fn checkpoint_events(&self) -> &[CheckpointEvent] {
+        &self.checkpoint_events
+    }
 
-#[derive(Clone, Copy, Debug)]
-struct Position {
-    x: f32,
-    y: f32,
-}
+    pub fn noise_events(&self) -> &[NoiseEvent] {
+        &self.noise_events
+    }
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub(crate) struct WallInstance {
-    screen_x: f32,
-    top: f32,
-    height: f32,
-    tex_u: f32,
-    tex_layer: u32,
-    // _pad: [u32; 3],
-}
+    pub fn ai_events(&self) -> &[AiEvent] {
+        &self.ai_events
+    }
 
-impl Default for WallInstance {
-    fn default() -> Self {
-        Self {
-            screen_x: 0.0,
-            top: 0.0,
-            height: 0.0,
-            tex_u: 0.0,
-            tex_layer: 0,
-        }
+    pub fn set_hostility(&mut self, hostility: Hostility) {
+        self.hostility = hostility;
     }
-}
 
-#[derive(Debug)]
-struct Ray {
-    len: f32,
-    angle: f32,
-    fisheye_correction: f32,
-    tile_index: Option<usize>,
-    tile_intersection: Option<Position>,
-    tile_id: Option<u8>,
-    tile_type: Option<TileType>,
-    tile_side: Option<TileSide>,
-    tile_image_index: Option<usize>,
-}
+    pub fn entity_culling(&self) -> Option<EntityCulling> {
+        self.entity_culling
+    }
 
-impl Ray {
-    fn update_intersection(
-        &mut self,
-        len: f32,
-        tile_index: Option<usize>,
-        tile_intersection: Option<Position>,
-        tile_type: Option<TileType>,
-        tile_side: Option<TileSide>,
-        tile_id: Option<u8>,
-        tile_image_index: Option<usize>,
-    ) {
-        self.len = len;
-        self.tile_index = tile_index;
-        self.tile_intersection = tile_intersection;
-        self.tile_type = tile_type;
-        self.tile_side = tile_side;
-        self.tile_id = tile_id;
-        self.tile_image_index = tile_image_index;
+    /// Takes effect from the next step, waking every entity without culling.
+    pub fn set_entity_culling(&mut self, entity_culling: Option<EntityCulling>) {
+        self.entity_culling = entity_culling;
     }
-}
 
-struct PlayerController {
-    key_forward: bool,
-    key_back: bool,
-    key_left: bool,
-    key_right: bool,
-}
+    pub fn hit_events(&self) -> &[HitEvent] {
+        &self.hit_events
+    }
 
-pub(crate) struct Raycaster {
-    renderer: Renderer,
-    projection_plane_width: u32,
-    projection_plane_height: u32,
-    projection_plane_y_center: f32,
-    tile_size: u16,
-    wall_height: u16,
-    fov: f32,
-    rays: Vec<Ray>,
-    player_position: Position,
-    player_rotation: f32,
-    player_move_dir: f32,
-    player_height: u16,
-    player_dist_to_projection_plane: f32,
-    maps: Arc<Maps>,
-    current_map_key: &'static str,
-    player_controller: PlayerController,
-}
+    pub fn set_items(&mut self, items: Items) {
+        self.items = items;
+    }
 
-impl Raycaster {
-    pub fn new(
-        renderer: Renderer,
-        maps: Arc<Maps>,
-        current_map_key: &'static str,
-    ) -> anyhow::Result<Raycaster> {
-        let config = renderer.config().clone();
+    pub fn register_item(&mut self, name: String, def: ItemDef) {
+        self.items.register(name, def);
+    }
 
-        let fov: f32 = 60.0;
-        let player_dist_to_projection_plane =
-            config.width as f32 / 2.0 / (fov.to_radians() / 2.0).tan();
-        let ray_angles = get_ray_angles(fov, config.width)?;
-        let fish_table = get_fish_table(config.width)?;
+    pub fn items(&self) -> &Items {
+        &self.items
+    }
 
-        Ok(Self {
-            renderer,
-            projection_plane_width: config.width,
-            projection_plane_height: config.height,
-            projection_plane_y_center: config.height as f32 / 2.0,
-            tile_size: 64,
-            wall_height: 64,
-            fov,
-            rays: ray_angles
-                .iter()
-                .enumerate()
-                .map(|(i, a)| Ray {
-                    len: f32::INFINITY,
-                    angle: *a,
-                    fisheye_correction: fish_table[i],
-                    tile_index: None,
-                    tile_intersection: None,
-                    tile_id: None,
-                    tile_type: None,
-                    tile_side: None,
-                    tile_image_index: None,
-                })
-                .collect(),
-            player_position: Position { x: 100.0, y: 100.0 },
-            player_rotation: 10.0,
-            player_move_dir: 10.0,
-            player_height: 32,
-            player_dist_to_projection_plane,
-            maps: maps,
-            current_map_key,
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
 
-            player_controller: PlayerController {
-                key_forward: false,
-                key_back: false,
-                key_left: false,
-                key_right: false,
-            },
-        })
+    pub fn inventory_mut(&mut self) -> &mut Inventory {
+        &mut self.inventory
     }
 
-    pub fn update(&mut self) -> anyhow::Result<()> {
-        self.update_positions()?;
+    pub fn pickup_events(&self) -> &[PickupEvent] {
+        &self.pickup_events
+    }
 
-        self.update_rays()?;
-        self.update_quads()?;
+    /// Holds the weapon with this name. Returns whether there is one.
+    pub fn select_weapon(&mut self, name: &str) -> bool {
+        self.arsenal
+            .weapons()
+            .iter()
+            .position(|weapon| weapon.name == name)
+            .is_some_and(|index| self.arsenal.select(index))
+    }
 
-        self.renderer.render()?;
+    pub fn weapon(&self) -> Option<&Weapon> {
+        self.arsenal.current()
+    }
 
-        Ok(())
+    pub fn shot_events(&self) -> &[ShotEvent] {
+        &self.shot_events
     }
 
-    fn update_rays(&mut self) -> anyhow::Result<()> {
-        let current_map = &self.maps.get(self.current_map_key).unwrap();
-        let map_size = current_map.size();
-        let map_cols = map_size.cols();
-        let map_rows = map_size.rows();
+    pub fn stats(&self) -> &LevelStats {
+        &self.stats
+    }
 
-        for ray in &mut self.rays {
-            let mut adjusted_angle = ray.angle + self.player_rotation.to_radians();
-            adjusted_angle = adjusted_angle.keep_in_range(0.0, 2.0 * PI);
+    pub fn stats_events(&self) -> &[StatsEvent] {
+        &self.stats_events
+    }
 
-            let mut closest: Option<Position> = None;
-            let mut record = f32::INFINITY;
+    pub fn asset_events(&self) -> &[AssetEvent] {
+        &self.asset_events
+    }
 
-            let ray_angle_quadrant = get_angle_quadrant(adjusted_angle);
+    /// Despawns an entity, counting it as a kill if it was alive.
+    pub fn kill(&mut self, id: EntityId) -> Option<Entity> {
+        let entity = self.entities.despawn(id)?;
+        self.stats.kills += 1;
+        Some(entity)
+    }
 
-            let sides_to_check: [TileSide; 2] = match ray_angle_quadrant {
-                AngleQuadrant::BottomRight => [TileSide::Top, TileSide::Left],
-                AngleQuadrant::BottomLeft => [TileSide::Top, TileSide::Right],
-                AngleQuadrant::TopLeft => [TileSide::Right, TileSide::Bottom],
-                AngleQuadrant::TopRight => [TileSide::Bottom, TileSide::Left],
-            };
+    pub fn add_to_counter(&mut self, name: &str, amount: i64) {
+        *self.stats.counters.entry(name.to_string()).or_default() += amount;
+    }
 
-            let mut tile_index: Option<usize> = None;
-            let mut tile_id: Option<u8> = None;
-            let mut tile_type: Option<TileType> = None;
-            let mut tile_side: Option<TileSide> = None;
-            for row in 0..map_rows {
-                for col in 0..map_cols {
-                    let tile_id_temp = current_map.tile_id(row, col);
-                    let tile_type_temp = current_map.tile_type(tile_id_temp.unwrap());
-
-                    match tile_type_temp {
-                        Some(TileType::Wall(_)) => (),
-                        _ => continue,
-                    }
+    /// Adds an objective, or puts one of the same name back to incomplete with a new
+    /// description.
+    pub fn add_objective(&mut self, name: String, description: String) {
+        self.stats
+            .objectives
+            .retain(|objective| objective.name != name);
+        self.stats.objectives.push(Objective {
+            name,
+            description,
+            complete: false,
+        });
+    }
 
-                    let tile_intersection = ray_tile_intersection(
-                        self.player_position.x,
-                        self.player_position.y,
-                        row,
-                        col,
-                        self.tile_size,
-                        adjusted_angle,
-                        sides_to_check,
-                    );
+    /// Returns whether there's an incomplete objective by that name.
+    pub fn complete_objective(&mut self, name: &str) -> bool {
+        let Some(objective) = self
+            .stats
+            .objectives
+            .iter_mut()
+            .find(|objective| objective.name == name && !objective.complete)
+        else {
+            return false;
+        };
+        objective.complete = true;
+        self.stats_events
+            .push(StatsEvent::ObjectiveCompleted(name.to_string()));
+        true
+    }
 
-                    if let Some(data) = tile_intersection {
-                        if data.dist < record {
-                            record = data.dist;
-                            closest = Some(data.intersection);
-                            tile_side = Some(data.side);
-                            tile_index = Some(row * map_cols + col);
-                            tile_id = tile_id_temp;
-                            tile_type = tile_type_temp;
-                        }
-                    }
-                }
+    pub fn play_sequence(&mut self, sequence: Sequence) {
+        self.sequencer.play(sequence);
+    }
+
+    pub fn start_sequence(&mut self, name: &str) -> bool {
+        self.sequencer.start(name)
+    }
+
+    pub fn skip_sequence(&mut self) {
+        self.sequencer.skip(&mut self.sequence_events);
+    }
+
+    pub fn sequence_playing(&self) -> bool {
+        self.sequencer.playing()
+    }
+
+    pub fn sequence_events(&self) -> &[SequenceEvent] {
+        &self.sequence_events
+    }
+
+    /// Takes the game's menus, loading the volumes saved from them if there are any.
+    pub fn accessibility(&self) -> AccessibilityConfig {
+        self.accessibility
+    }
+
+    /// Applies accessibility settings, rebuilding the view if the field of view changed.
+    pub fn set_accessibility(&mut self, accessibility: AccessibilityConfig) -> anyhow::Result<()> {
+        let accessibility = accessibility.clamped();
+        if accessibility.fov != self.camera.fov {
+            let (width, height) = self.renderer.internal_size();
+            self.view = View::new(width, height, accessibility.fov)?;
+            if let Some(stereo) = &mut self.stereo {
+                stereo.view = View::new(width, height, accessibility.fov)?;
             }
+            self.camera.fov = accessibility.fov;
+        }
+        if !accessibility.toggle_crouch {
+            self.crouching = self.player_controller.key_crouch;
+        }
+        if !accessibility.toggle_sprint {
+            self.sprinting = self.player_controller.key_sprint;
+        }
+        self.renderer
+            .set_photosensitive_safe(accessibility.photosensitive_safe);
+        self.accessibility = accessibility;
+        Ok(())
+    }
 
-            if let (Some(intersection), Some(t_index), Some(t_id), Some(t_type), Some(t_side)) =
-                (closest, tile_index, tile_id, tile_type, tile_side)
-            {
-                let texture_index = self
-                    .renderer
-                    .get_texture_index(t_id, &renderer::TextureCategory::Wall)?;
-
-                ray.update_intersection(
-                    record.floor(),
-                    Some(t_index),
-                    Some(intersection),
-                    Some(t_type),
-                    Some(t_side),
-                    Some(t_id),
-                    Some(texture_index),
-                );
-            } else {
-                ray.update_intersection(record.floor(), None, None, None, None, None, None);
+    pub fn settings(&self) -> Settings {
+        Settings {
+            audio: self.audio_config(),
+            accessibility: self.accessibility,
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: Settings) -> anyhow::Result<()> {
+        self.set_audio_config(settings.audio);
+        self.set_accessibility(settings.accessibility)
+    }
+
+    /// Shakes the view `amplitude` degrees each way, dying down over `duration`, scaled by
+    /// [`screen_shake`](AccessibilityConfig::screen_shake). A stronger shake replaces a
+    /// weaker one.
+    pub fn shake(&mut self, amplitude: f32, duration: Duration) {
+        let (current, left, total) = self.shake;
+        let remaining = if total > 0.0 {
+            current * left / total
+        } else {
+            0.0
+        };
+        if amplitude >= remaining {
+            let duration = duration.as_secs_f32();
+            self.shake = (amplitude, duration, duration);
+        }
+    }
+
+    pub fn set_menus(&mut self, menus: Menus) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = menus.settings_path()
+            && path.exists()
+            && let Err(err) = self.load_settings(path)
+        {
+            eprintln!("{err:#}");
+        }
+        self.menus = menus;
+    }
+
+    pub fn menus(&self) -> &Menus {
+        &self.menus
+    }
+
+    pub fn open_menu(&mut self, name: &str) -> bool {
+        self.menus.open(name)
+    }
+
+    pub fn close_menu(&mut self) {
+        self.menus.close();
+    }
+
+    /// Moves through the open menus, applying and saving any settings changed.
+    pub fn navigate_menu(&mut self, input: MenuInput) -> Option<MenuEvent> {
+        let mut settings = self.settings();
+        let event = self.menus.navigate(input, &mut settings);
+        if let Some(MenuEvent::SettingsChanged(settings)) = event {
+            if let Err(err) = self.set_settings(settings) {
+                eprintln!("{err:#}");
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Err(err) = self.save_settings(settings) {
+                eprintln!("{err:#}");
             }
         }
+        event
+    }
 
-        Ok(())
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_settings(&mut self, path: &std::path::Path) -> anyhow::Result<()> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        self.set_settings(Settings::from_ron(&source)?)
     }
 
-    fn update_quads(&mut self) -> anyhow::Result<()> {
-        for (i, ray) in self.rays.iter().enumerate() {
-            if let (Some(intersection), Some(tile_side), Some(tile_id)) =
-                (ray.tile_intersection, ray.tile_side, ray.tile_id)
-            {
-                let dist = ray.len / ray.fisheye_correction;
-
-                let ratio = self.player_dist_to_projection_plane / dist;
-                let scale = (self.player_dist_to_projection_plane * self.wall_height as f32) / dist;
-                let wall_bottom =
-                    ratio * self.player_height as f32 + self.projection_plane_y_center as f32;
-                let wall_top = wall_bottom - scale;
-                let wall_height = wall_bottom - wall_top;
-
-                // let adjusted_angle = ray.angle + self.player_rotation.to_radians();
-                // let adjusted_angle = adjusted_angle.keep_in_range(0.0, 2.0 * PI);
-
-                // let mut offset = match ray.tile_side {
-                //
-                // }
-
-                let use_x_for_offset =
-                    matches!(tile_side, TileSide::Top) || matches!(tile_side, TileSide::Bottom);
-
-                // Tile-local offset for texture column start
-                let offset = if use_x_for_offset {
-                    let offset_temp =
-                        (intersection.x.floor() as i32).rem_euclid(self.tile_size as i32);
-                    // Mirror
-                    (self.tile_size as i32) - offset_temp - 1
-                } else {
-                    (intersection.y.floor() as i32).rem_euclid(self.tile_size as i32)
-                } as f32;
-
-                let tex_u = (offset + 0.5) / (self.tile_size as f32);
-
-                // if tile_id != 0 { println!("{}", tile_id);
-                let tex_layer = self
-                    .renderer
-                    .get_texture_index(tile_id, &renderer::TextureCategory::Wall)?;
-
-                let instance = WallInstance {
-                    screen_x: i as f32,
-                    top: wall_top as f32,
-                    height: wall_height as f32,
-                    tex_u,
-                    tex_layer: tex_layer as u32,
-                    // _pad: [0u32; 3],
-                };
+    /// Writes the settings to the menus' settings path, if they have one.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_settings(&self, settings: Settings) -> anyhow::Result<()> {
+        let Some(path) = self.menus.settings_path() else {
+            return Ok(());
+        };
+        std::fs::write(path, settings.to_ron()?)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
 
-                self.renderer.set_wall_instance(i, instance)?;
-            } else {
-                self.renderer
-                    .set_wall_instance(i, WallInstance::default())?;
-            };
+    /// Holds fire down or lets it go, for the mouse button.
+    pub fn set_fire(&mut self, fire: bool) {
+        self.player_controller.key_fire = fire;
+    }
+
+    pub fn trigger_spawner(&mut self, name: &str) -> bool {
+        self.spawners.trigger(name)
+    }
+
+    pub fn gpu_timings(&self) -> Option<GpuTimings> {
+        self.renderer.gpu_timings()
+    }
+
+    pub fn device_report(&self) -> &DeviceReport {
+        self.renderer.device_report()
+    }
+
+    /// The performance overlay's text: the last frame's time, then the GPU's stages if they're
+    /// being measured.
+    fn performance_lines(&self) -> Vec<String> {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+        let mut lines = vec![
+            format!("frame  {:6.2} ms", ms(self.timing.delta_time)),
+            format!("fps    {:6.1}", self.timing.smoothed_fps),
+        ];
+        if self.ray_step_controller.is_some() {
+            lines.push(format!("rays   1/{}", self.ray_step()));
+        }
+        if let Some(timings) = self.renderer.gpu_timings() {
+            lines.push(format!("floor  {:6.2} ms", ms(timings.floor)));
+            lines.push(format!("walls  {:6.2} ms", ms(timings.walls)));
+            lines.push(format!("sprite {:6.2} ms", ms(timings.sprites)));
+            if let Some(post_process) = timings.post_process {
+                lines.push(format!("post   {:6.2} ms", ms(post_process)));
+            }
+            lines.push(format!("gpu    {:6.2} ms", ms(timings.total())));
         }
+        lines
+    }
 
-        Ok(())
+    /// Stops the simulation while still drawing frames.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.pause.paused = paused;
     }
 
-    pub fn renderer(&mut self) -> &mut Renderer {
-        &mut self.renderer
+    /// Stops the simulation while the windows are unfocused, apart from any pause the game set
+    /// with [`set_paused`](Self::set_paused).
+    pub(crate) fn set_focus_paused(&mut self, focus_paused: bool) {
+        self.pause.focus_paused = focus_paused;
     }
 
-    fn move_dir(&self) -> f32 {
-        let PlayerController {
-            key_forward,
-            key_back,
-            key_left,
-            key_right,
-        } = self.player_controller;
+    /// Takes movement and mouse look from a host application instead of window events.
+    pub fn set_input(&mut self, input: &InputState) {
+        self.player_controller.key_forward = input.forward;
+        self.player_controller.key_back = input.back;
+        self.player_controller.key_left = input.left;
+        self.player_controller.key_right = input.right;
+        self.player_controller.key_fire = input.fire;
+        self.set_crouch_key(input.crouch);
+        self.set_sprint_key(input.sprint);
+        self.handle_cursor_move(input.look_delta);
+    }
 
-        if key_forward && !key_right && !key_left {
-            // forward
-            self.player_rotation
-        } else if key_back && !key_right && !key_left {
-            // backwards
-            self.player_rotation + 180.0
-        } else if key_right && !key_forward && !key_back {
-            // right
-            self.player_rotation + 90.0
-        } else if key_left && !key_forward && !key_back {
-            // left
-            self.player_rotation - 90.0
-        } else if key_forward && key_right {
-            // forward-right
-            self.player_rotation + 45.0
-        } else if key_forward && key_left {
-            // forward-left
-            self.player_rotation - 45.0
-        } else if key_back && key_right {
-            // backwards-right
-            self.player_rotation + 135.0
-        } else if key_back && key_left {
-            // backwards-left
-            self.player_rotation - 135.0
-        } else {
-            self.player_rotation
+    /// Lets go of every movement key, for when key releases can't be seen, like while the
+    /// window is unfocused.
+    pub fn release_keys(&mut self) {
+        self.set_crouch_key(false);
+        self.set_sprint_key(false);
+        self.player_controller = PlayerController::default();
+    }
+
+    /// Crouches while the key is held, or each time it's pressed with
+    /// [`toggle_crouch`](AccessibilityConfig::toggle_crouch).
+    fn set_crouch_key(&mut self, held: bool) {
+        if held == self.player_controller.key_crouch {
+            return;
+        }
+        self.player_controller.key_crouch = held;
+        if !self.accessibility.toggle_crouch {
+            self.crouching = held;
+        } else if held {
+            self.crouching = !self.crouching;
         }
     }
 
-    pub fn update_positions(&mut self) -> anyhow::Result<()> {
-        let delta_time = self.renderer.delta_time().as_secs_f32();
-        let move_speed = 150.0 * delta_time;
+    /// Sprints while the key is held, or each time it's pressed with
+    /// [`toggle_sprint`](AccessibilityConfig::toggle_sprint).
+    fn set_sprint_key(&mut self, held: bool) {
+        if held == self.player_controller.key_sprint {
+            return;
+        }
+        self.player_controller.key_sprint = held;
+        if !self.accessibility.toggle_sprint {
+            self.sprinting = held;
+        } else if held {
+            self.sprinting = !self.sprinting;
+        }
+    }
 
-        let move_dir = self.move_dir().keep_in_range(0.0, 360.0).to_radians();
+    /// Speeds up or slows down the simulation. Negative scales are treated as 0.0, which
+    /// freezes it. Input is still read every frame, so looking around stays responsive.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
 
-        if self.player_controller.key_forward
-            || self.player_controller.key_back
-            || self.player_controller.key_left
-            || self.player_controller.key_right
-        {
-            self.player_position.x += move_speed * move_dir.cos();
-            self.player_position.y += move_speed * move_dir.sin();
-        };
+    /// Seconds of simulation to advance this frame.
+    fn delta_time(&self) -> f32 {
+        self.timing.delta_time.as_secs_f32() * self.time_scale
+    }
 
-        Ok(())
+    #[cfg(debug_assertions)]
+    pub fn set_debug_flags(&mut self, debug_flags: DebugFlags) {
+        self.debug_flags = debug_flags;
     }
 
-    pub fn handle_key(&mut self, event_loop: &ActiveEventLoop, code: KeyCode, is_pressed: bool) {
-        let delta_time = self.renderer.delta_time().as_secs_f32();
+    /// Returns whether the key asked to quit the game.
+    pub fn handle_key(&mut self, code: KeyCode, is_pressed: bool) -> bool {
+        if let Some(editor) = &mut self.editor
+            && !matches!(code, KeyCode::Escape | KeyCode::F5 | KeyCode::F8)
+        {
+            if is_pressed {
+                editor.handle_key(code, self.camera.rotation);
+            }
+            return false;
+        }
+
+        if self.menus.is_open() {
+            return menu_input(code)
+                .filter(|_| is_pressed)
+                .is_some_and(|input| self.navigate_menu(input) == Some(MenuEvent::Quit));
+        }
 
         match (code, is_pressed) {
             (KeyCode::Escape, true) => {
-                println!("App Closed via Esc key");
-                event_loop.exit();
+                if self.menus.open(Menus::PAUSE) {
+                    self.release_keys();
+                } else {
+                    println!("App Closed via Esc key");
+                    return true;
+                }
             }
             // Forward
             (KeyCode::KeyW, true) => {
@@ -425,17 +4275,169 @@ impl Raycaster {
             (KeyCode::KeyA, false) => {
                 self.player_controller.key_left = false;
             }
+            (KeyCode::KeyC | KeyCode::ControlLeft, _) => self.set_crouch_key(is_pressed),
+            (KeyCode::ShiftLeft, _) => self.set_sprint_key(is_pressed),
+            #[cfg(not(target_arch = "wasm32"))]
+            (KeyCode::F12, true) => {
+                let seconds = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |time| time.as_secs());
+                self.renderer
+                    .request_screenshot(format!("screenshots/screenshot_{seconds}.png").into());
+            }
+            (KeyCode::F9, true) => {
+                self.performance_overlay = !self.performance_overlay;
+            }
+            (KeyCode::KeyM, true) => {
+                self.set_automap(self.automap.is_none());
+            }
+            (KeyCode::KeyV, true) => {
+                let top_down = self.top_down.is_none().then(TopDown::default);
+                self.set_top_down(top_down);
+            }
+            // Weapons, in the order they were given
+            (
+                KeyCode::Digit1
+                | KeyCode::Digit2
+                | KeyCode::Digit3
+                | KeyCode::Digit4
+                | KeyCode::Digit5
+                | KeyCode::Digit6
+                | KeyCode::Digit7
+                | KeyCode::Digit8
+                | KeyCode::Digit9,
+                true,
+            ) => {
+                let index = code as usize - KeyCode::Digit1 as usize;
+                self.arsenal.select(index);
+            }
+            // Level editor
+            (KeyCode::F8, true) => {
+                if self.editor.is_some() {
+                    if let Err(err) = self.close_editor() {
+                        eprintln!("{err:#}");
+                    }
+                } else {
+                    self.open_editor();
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            (KeyCode::F5, true) => {
+                if let Err(err) = self.save_editor_map() {
+                    eprintln!("{err:#}");
+                }
+            }
+            // Debug toggles
+            #[cfg(debug_assertions)]
+            (KeyCode::F1, true) => {
+                self.debug_flags.noclip = !self.debug_flags.noclip;
+                println!("noclip: {}", self.debug_flags.noclip);
+            }
+            #[cfg(debug_assertions)]
+            (KeyCode::F2, true) => {
+                self.debug_flags.god_mode = !self.debug_flags.god_mode;
+                println!("god mode: {}", self.debug_flags.god_mode);
+            }
+            #[cfg(debug_assertions)]
+            (KeyCode::F3, true) => {
+                self.debug_flags.show_collision = !self.debug_flags.show_collision;
+                println!("show collision: {}", self.debug_flags.show_collision);
+            }
+            #[cfg(debug_assertions)]
+            (KeyCode::F4, true) => {
+                let time_scale = if self.time_scale == 1.0 { 0.25 } else { 1.0 };
+                self.set_time_scale(time_scale);
+                println!("time scale: {}", self.time_scale);
+            }
 
             _ => (),
         }
+        false
     }
 
     pub fn handle_cursor_move(&mut self, delta: (f64, f64)) {
-        self.player_rotation += delta.0 as f32 / 40.0;
-        self.projection_plane_y_center -= delta.1 as f32 / 4.0;
+        if self.editor.is_some() || self.menus.is_open() || self.sequencer.input_locked() {
+            return;
+        }
+        self.camera.rotation += delta.0 as f32 / 40.0;
+        self.camera.pitch -= delta.1 as f32 / 4.0;
+    }
+}
+
+/// The menu press a key makes.
+fn menu_input(code: KeyCode) -> Option<MenuInput> {
+    Some(match code {
+        KeyCode::ArrowUp | KeyCode::KeyW => MenuInput::Up,
+        KeyCode::ArrowDown | KeyCode::KeyS => MenuInput::Down,
+        KeyCode::ArrowLeft | KeyCode::KeyA => MenuInput::Left,
+        KeyCode::ArrowRight | KeyCode::KeyD => MenuInput::Right,
+        KeyCode::Enter | KeyCode::Space => MenuInput::Select,
+        KeyCode::Escape | KeyCode::Backspace => MenuInput::Back,
+        _ => return None,
+    })
+}
+
+/// Fresh stats for starting on a map.
+fn level_stats(map_key: &'static str, map: &Map) -> LevelStats {
+    LevelStats::new(map_key, map.metadata().par_time(), map.secrets().len())
+}
+
+/// Spawns a map's starting entities, then builds and spawns its entity definitions. Built
+/// entities whose sprite isn't loaded are left out.
+fn spawn_map_entities(
+    entities: &mut Entities,
+    renderer: &Renderer,
+    map: &Map,
+    entity_types: &EntityTypes,
+) {
+    for entity in map.entities() {
+        entities.spawn(entity.clone());
+    }
+    for def in map.entity_defs() {
+        match entity_types.build(def) {
+            Some(entity) => {
+                if let Err(error) = check_sprite(renderer, map, &entity) {
+                    eprintln!("entity type {}: {error}", def.type_name());
+                } else {
+                    entities.spawn(entity);
+                }
+            }
+            None => eprintln!("no entity type named {}", def.type_name()),
+        }
+    }
+}
+
+/// Errors if the entity's sprite image isn't loaded with `map`, since it couldn't be drawn.
+fn check_sprite(renderer: &Renderer, map: &Map, entity: &Entity) -> anyhow::Result<()> {
+    match entity.texture_path {
+        Some(path) if !renderer.sprite_loaded(map, path) => anyhow::bail!(
+            "sprite image {path} isn't loaded with the map. Use it in the map, or add it to \
+             RendererConfig::preload_sprites"
+        ),
+        _ => Ok(()),
     }
 }
 
+/// Casts and projects a view `width` by `height` pixels from `camera` on the CPU alone, as the
+/// main view is each frame, and returns the walls it drew.
+pub(crate) fn draw_view_headless(
+    map: &Map,
+    camera: &Camera,
+    width: u32,
+    height: u32,
+    tile_size: u16,
+    wall_height: u16,
+) -> anyhow::Result<Vec<WallInstance>> {
+    let mut view = View::new(width, height, camera.fov)?;
+    let mut backend = HeadlessBackend::default();
+    let mut linedefs = LinedefIndex::default();
+    linedefs.load_map(map, tile_size);
+    let doors = Doors::default();
+    view.cast_rays(camera, map, &linedefs, &doors, tile_size, &backend, 1)?;
+    view.draw_walls(camera, map, None, tile_size, wall_height, 1, &mut backend)?;
+    Ok(backend.walls)
+}
+
 fn get_ray_angles(fov: f32, width: u32) -> anyhow::Result<Vec<f32>> {
     let ray_inc: f32 = fov / width as f32;
     let mut angle: f32 = 0.0;
@@ -449,28 +4451,3 @@ fn get_ray_angles(fov: f32, width: u32) -> anyhow::Result<Vec<f32>> {
 
     Ok(ray_angles)
 }
-
-fn get_fish_table(width: u32) -> anyhow::Result<Vec<f32>> {
-    let width = width as f32;
-    let half_neg: i32 = (-width / 2.0).floor() as i32;
-    let half: i32 = (width / 2.0).floor() as i32;
-    let mut fish_table: Vec<f32> = vec![0.0; width as usize];
-
-    for n in half_neg..half {
-        let radian: f32 = (n as f32 * PI) / (width * 3.0);
-        fish_table[(n + half) as usize] = 1.0 / radian.cos();
-    }
-
-    Ok(fish_table)
-}
-
-fn get_angle_quadrant(angle: f32) -> AngleQuadrant {
-    let ray_angle_quadrant_id: u8 = (angle / (PI / 2.0)).floor() as u8;
-    match ray_angle_quadrant_id {
-        0 => AngleQuadrant::BottomRight,
-        1 => AngleQuadrant::BottomLeft,
-        2 => AngleQuadrant::TopLeft,
-        3 => AngleQuadrant::TopRight,
-        _ => AngleQuadrant::BottomRight,
-    }
-}
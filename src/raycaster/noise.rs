@@ -0,0 +1,67 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use glam::Vec2;
+
+use crate::map::{Map, TileType};
+
+/// How many times further noise counts as having travelled when it passes through a thin
+/// wall's tile, the nearest thing maps have to a door.
+const THIN_WALL_MUFFLING: f32 = 3.0;
+
+/// How far noise made at `source` travels to reach each tile it gets to within `radius`,
+/// keyed by row and column. Noise flows through open tiles and around corners, is muffled by
+/// thin walls and is stopped by solid walls and the edge of the map.
+pub(crate) fn propagate(
+    map: &Map,
+    source: Vec2,
+    radius: f32,
+    tile_size: f32,
+) -> HashMap<(usize, usize), f32> {
+    let size = map.size();
+    let mut distances = HashMap::new();
+    let tile = (source / tile_size).floor();
+    if tile.x < 0.0 || tile.y < 0.0 {
+        return distances;
+    }
+    let start = (tile.y as usize, tile.x as usize);
+    if start.0 >= size.rows() || start.1 >= size.cols() {
+        return distances;
+    }
+
+    // Distances are never negative, so their bits sort the same way they do
+    let mut queue = BinaryHeap::from([Reverse((0.0f32.to_bits(), start))]);
+    distances.insert(start, 0.0);
+    while let Some(Reverse((distance, (row, col)))) = queue.pop() {
+        let distance = f32::from_bits(distance);
+        if distance > distances[&(row, col)] {
+            continue;
+        }
+
+        let neighbors = [
+            (row.wrapping_sub(1), col),
+            (row + 1, col),
+            (row, col.wrapping_sub(1)),
+            (row, col + 1),
+        ];
+        for (row, col) in neighbors {
+            if row >= size.rows() || col >= size.cols() {
+                continue;
+            }
+            let muffling = match map.tile_id(row, col).and_then(|id| map.tile_type(id)) {
+                Some(TileType::Wall(_) | TileType::Monitor(_)) => continue,
+                Some(TileType::ThinWall(_)) => THIN_WALL_MUFFLING,
+                _ => 1.0,
+            };
+            let next = distance + tile_size * muffling;
+            if next <= radius && distances.get(&(row, col)).is_none_or(|known| next < *known) {
+                distances.insert((row, col), next);
+                queue.push(Reverse((next.to_bits(), (row, col))));
+            }
+        }
+    }
+
+    distances
+}
@@ -0,0 +1,159 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    f32::consts::SQRT_2,
+};
+
+use glam::Vec2;
+
+use crate::{
+    map::{Map, Region},
+    raycaster::doors::Doors,
+};
+
+/// The most tiles a search settles before giving up, so an unreachable goal in a big map
+/// doesn't hold up the step.
+const MAX_SETTLED_TILES: usize = 4096;
+
+/// A cost laid over part of the map for a while, like a danger zone AI should keep out of.
+/// Multiplies the cost of the tiles whose centers it covers.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PathStamp {
+    pub region: Region,
+    pub cost: f32,
+    /// Seconds until it's lifted
+    pub left: f32,
+}
+
+/// The cost of walking onto a tile, or `None` if it can't be walked onto. Shut doors can only
+/// be walked onto by AI that `opens_doors`.
+fn tile_cost(
+    map: &Map,
+    stamps: &[PathStamp],
+    doors: &Doors,
+    opens_doors: bool,
+    (row, col): (usize, usize),
+    tile_size: f32,
+) -> Option<f32> {
+    let size = map.size();
+    if row >= size.rows() || col >= size.cols() {
+        return None;
+    }
+    if !opens_doors && doors.is_door((row, col)) && !doors.is_open((row, col)) {
+        return None;
+    }
+    let center = (Vec2::new(col as f32, row as f32) + 0.5) * tile_size;
+    let cost = stamps
+        .iter()
+        .filter(|stamp| stamp.region.contains(center))
+        .fold(
+            map.tile_id(row, col).map_or(1.0, |id| map.path_cost(id)),
+            |cost, stamp| cost * stamp.cost,
+        );
+
+    cost.is_finite().then_some(cost)
+}
+
+/// The cheapest route from `from` to `to` across the map's tiles by their
+/// [path costs](Map::path_cost) and any stamps over them: the center of each tile after the
+/// first, then `to`. Moves diagonally only where both tiles beside the corner are open.
+/// `None` when `to` can't be reached, or is too far to search for.
+pub(crate) fn find_path(
+    map: &Map,
+    stamps: &[PathStamp],
+    doors: &Doors,
+    opens_doors: bool,
+    from: Vec2,
+    to: Vec2,
+    tile_size: f32,
+) -> Option<Vec<Vec2>> {
+    let tile_of = |point: Vec2| {
+        let tile = (point / tile_size).floor();
+        (tile.x >= 0.0 && tile.y >= 0.0).then_some((tile.y as usize, tile.x as usize))
+    };
+    let start = tile_of(from)?;
+    let goal = tile_of(to)?;
+    tile_cost(map, stamps, doors, opens_doors, goal, tile_size)?;
+
+    // The cheapest any tile can be, so the estimate never overshoots
+    let cheapest = map
+        .path_costs()
+        .iter()
+        .map(|(_, cost)| *cost)
+        .chain(stamps.iter().map(|stamp| stamp.cost))
+        .fold(1.0f32, f32::min)
+        .max(0.0);
+    let estimate = |(row, col): (usize, usize)| {
+        let offset = Vec2::new(col as f32 - goal.1 as f32, row as f32 - goal.0 as f32);
+        offset.length() * tile_size * cheapest
+    };
+
+    let mut costs = HashMap::from([(start, 0.0f32)]);
+    let mut came_from = HashMap::new();
+    // Costs are never negative, so their bits sort the same way they do
+    let mut queue = BinaryHeap::from([Reverse((estimate(start).to_bits(), start))]);
+    let mut settled = HashSet::new();
+    while let Some(Reverse((_, tile))) = queue.pop() {
+        if tile == goal {
+            break;
+        }
+        // Tiles are queued again when a cheaper way to them turns up
+        if !settled.insert(tile) {
+            continue;
+        }
+        if settled.len() > MAX_SETTLED_TILES {
+            return None;
+        }
+
+        let (row, col) = tile;
+        let open = |row: usize, col: usize| {
+            tile_cost(map, stamps, doors, opens_doors, (row, col), tile_size)
+        };
+        for (d_row, d_col) in [
+            (-1, 0),
+            (1, 0),
+            (0, -1),
+            (0, 1),
+            (-1, -1),
+            (-1, 1),
+            (1, -1),
+            (1, 1),
+        ] {
+            let next = (
+                row.wrapping_add_signed(d_row),
+                col.wrapping_add_signed(d_col),
+            );
+            let Some(cost) = open(next.0, next.1) else {
+                continue;
+            };
+            let diagonal = d_row != 0 && d_col != 0;
+            if diagonal && (open(next.0, col).is_none() || open(row, next.1).is_none()) {
+                continue;
+            }
+
+            let length = if diagonal { SQRT_2 } else { 1.0 } * tile_size;
+            let total = costs[&tile] + length * cost;
+            if costs.get(&next).is_none_or(|known| total < *known) {
+                costs.insert(next, total);
+                came_from.insert(next, tile);
+                queue.push(Reverse(((total + estimate(next)).to_bits(), next)));
+            }
+        }
+    }
+    if !costs.contains_key(&goal) {
+        return None;
+    }
+
+    let mut path = vec![to];
+    let mut tile = goal;
+    while let Some(previous) = came_from.get(&tile) {
+        if *previous != start {
+            let (row, col) = *previous;
+            path.push((Vec2::new(col as f32, row as f32) + 0.5) * tile_size);
+        }
+        tile = *previous;
+    }
+    path.reverse();
+
+    Some(path)
+}
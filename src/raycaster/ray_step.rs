@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use crate::config::DynamicResolution;
+
+/// Frames to wait after changing the step before changing it again, so the average settles.
+const COOLDOWN_FRAMES: u32 = 30;
+/// Full resolution returns when the average is below this fraction of the budget. Halving the
+/// step roughly doubles the work, so this leaves room for that without bouncing straight back.
+const HEADROOM: f32 = 0.6;
+
+/// Picks how many columns each cast ray covers from how long recent frames took to cast.
+pub(crate) struct RayStepController {
+    config: DynamicResolution,
+    step: usize,
+    /// Smoothed seconds per frame spent casting rays and building the view
+    average: Option<f32>,
+    cooldown: u32,
+}
+
+impl RayStepController {
+    pub fn new(config: DynamicResolution) -> Self {
+        Self {
+            config,
+            step: 1,
+            average: None,
+            cooldown: 0,
+        }
+    }
+
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    /// Records how long this frame's view took and adjusts the step for the next frame.
+    pub fn update(&mut self, view_time: Duration) {
+        let seconds = view_time.as_secs_f32();
+        let average = match self.average {
+            Some(average) => average * 0.9 + seconds * 0.1,
+            None => seconds,
+        };
+        self.average = Some(average);
+
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+            return;
+        }
+
+        let budget = self.config.frame_budget.as_secs_f32();
+        let max_step = 1 << self.config.max_ray_step.max(1).ilog2();
+
+        let step = if average > budget && self.step < max_step {
+            self.step * 2
+        } else if average < budget * HEADROOM && self.step > 1 {
+            self.step / 2
+        } else {
+            return;
+        };
+
+        // The new step changes the work per frame, so the old average no longer applies
+        self.step = step;
+        self.average = None;
+        self.cooldown = COOLDOWN_FRAMES;
+    }
+}
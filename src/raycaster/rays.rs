@@ -0,0 +1,158 @@
+use glam::Vec2;
+
+use crate::raycaster::TileSide;
+
+/// Set in a column's flags when its ray hit a wall.
+const HIT: u8 = 1;
+/// The side hit is stored in the flags above [`HIT`].
+const SIDE_SHIFT: u8 = 1;
+
+/// The result of casting one ray, before it's stored.
+pub(crate) struct RayHit {
+    /// Distance along the ray
+    pub len: f32,
+    pub position: Vec2,
+    pub tile_index: usize,
+    pub side: TileSide,
+    pub layer: u32,
+    pub tex_u: f32,
+}
+
+/// Ray results for every column of a view, one array per field. Building the wall instances
+/// reads straight down the arrays without unwrapping anything per column.
+#[derive(Clone)]
+pub(crate) struct Rays {
+    /// Each column's angle from the view direction, in radians
+    pub angles: Vec<f32>,
+    /// Divides a length along the ray into the perpendicular distance, which removes fisheye
+    fisheye_correction: Vec<f32>,
+    /// Perpendicular distance to the wall hit, or infinity when nothing was hit
+    pub distances: Vec<f32>,
+    /// Where the ray hit, in world units
+    positions: Vec<Vec2>,
+    /// Index of the tile hit
+    tile_indices: Vec<u32>,
+    /// Wall texture layer of the tile hit
+    pub layers: Vec<u32>,
+    /// Horizontal texture coordinate across the face hit
+    pub tex_u: Vec<f32>,
+    /// Whether the ray hit, and which side of the tile
+    flags: Vec<u8>,
+}
+
+impl Rays {
+    pub fn new(angles: Vec<f32>) -> Self {
+        let width = angles.len();
+        Self {
+            fisheye_correction: angles.iter().map(|angle| 1.0 / angle.cos()).collect(),
+            angles,
+            distances: vec![f32::INFINITY; width],
+            positions: vec![Vec2::ZERO; width],
+            tile_indices: vec![0; width],
+            layers: vec![0; width],
+            tex_u: vec![0.0; width],
+            flags: vec![0; width],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.angles.len()
+    }
+
+    pub fn is_hit(&self, column: usize) -> bool {
+        self.flags[column] & HIT != 0
+    }
+
+    /// Where the column's ray hit and the index of the tile hit, if it hit anything.
+    pub fn hit(&self, column: usize) -> Option<(Vec2, usize)> {
+        self.is_hit(column)
+            .then(|| (self.positions[column], self.tile_indices[column] as usize))
+    }
+
+    /// The side of the tile the column's ray hit, if it hit anything.
+    pub fn side(&self, column: usize) -> Option<TileSide> {
+        if !self.is_hit(column) {
+            return None;
+        }
+        Some(match self.flags[column] >> SIDE_SHIFT {
+            0 => TileSide::Top,
+            1 => TileSide::Left,
+            2 => TileSide::Bottom,
+            _ => TileSide::Right,
+        })
+    }
+
+    /// Whether both columns hit the same face of the same tile.
+    pub fn same_face(&self, a: usize, b: usize) -> bool {
+        self.is_hit(a)
+            && self.flags[a] == self.flags[b]
+            && self.tile_indices[a] == self.tile_indices[b]
+    }
+
+    pub fn set(&mut self, column: usize, hit: Option<RayHit>) {
+        let Some(hit) = hit else {
+            self.distances[column] = f32::INFINITY;
+            self.flags[column] = 0;
+            return;
+        };
+
+        self.distances[column] = hit.len.floor() / self.fisheye_correction[column];
+        self.positions[column] = hit.position;
+        self.tile_indices[column] = hit.tile_index as u32;
+        self.layers[column] = hit.layer;
+        self.tex_u[column] = hit.tex_u;
+        self.flags[column] = HIT | (hit.side as u8) << SIDE_SHIFT;
+    }
+
+    /// Takes another column's hit as this column's, at the same perpendicular distance.
+    pub fn copy_hit(&mut self, column: usize, from: usize) {
+        self.copy_hit_from(column, &self.clone_column(from));
+    }
+
+    /// Takes a column's hit from another set of rays, such as the previous frame's, measuring
+    /// the distance again from `camera_position`.
+    pub fn reproject(
+        &mut self,
+        column: usize,
+        previous: &Rays,
+        from: usize,
+        camera_position: Vec2,
+    ) {
+        let mut hit = previous.clone_column(from);
+        if hit.flags & HIT != 0 {
+            hit.distance =
+                hit.position.distance(camera_position).floor() / self.fisheye_correction[column];
+        }
+        self.copy_hit_from(column, &hit);
+    }
+
+    fn clone_column(&self, column: usize) -> ColumnHit {
+        ColumnHit {
+            distance: self.distances[column],
+            position: self.positions[column],
+            tile_index: self.tile_indices[column],
+            layer: self.layers[column],
+            tex_u: self.tex_u[column],
+            flags: self.flags[column],
+        }
+    }
+
+    fn copy_hit_from(&mut self, column: usize, hit: &ColumnHit) {
+        self.distances[column] = hit.distance;
+        self.positions[column] = hit.position;
+        self.tile_indices[column] = hit.tile_index;
+        self.layers[column] = hit.layer;
+        self.tex_u[column] = hit.tex_u;
+        self.flags[column] = hit.flags;
+    }
+}
+
+/// One column's stored hit, for moving between columns.
+struct ColumnHit {
+    distance: f32,
+    position: Vec2,
+    tile_index: u32,
+    layer: u32,
+    tex_u: f32,
+    flags: u8,
+}
@@ -0,0 +1,241 @@
+use std::collections::VecDeque;
+
+use glam::Vec2;
+
+use crate::{
+    camera::Camera,
+    engine::SequenceEvent,
+    map::Map,
+    sequence::{Lightning, Sequence, SequenceTrigger, Step},
+};
+
+/// A sequence being run and how far along it is.
+#[derive(Clone)]
+struct Playing {
+    sequence: Sequence,
+    step: usize,
+    /// Seconds spent on the current step, if it takes time
+    elapsed: f32,
+}
+
+/// The current map's sequences and the one running, along with what it has done to the view
+/// and the player's input.
+#[derive(Clone, Default)]
+pub(crate) struct Sequencer {
+    /// Each of the map's sequences, and whether it has run on this visit
+    placed: Vec<(Sequence, bool)>,
+    playing: Option<Playing>,
+    /// Sequences waiting for the one running to finish
+    queue: VecDeque<Sequence>,
+    input_locked: bool,
+    camera: Option<Camera>,
+    text: Option<String>,
+    /// Struck since the raycaster last took them
+    lightning: Vec<Lightning>,
+}
+
+impl Sequencer {
+    /// Stops whatever is running and sets every sequence in the map waiting for its trigger
+    /// again.
+    pub fn load_map(&mut self, map: &Map) {
+        *self = Self {
+            placed: map
+                .sequences()
+                .iter()
+                .map(|sequence| (sequence.clone(), false))
+                .collect(),
+            ..Default::default()
+        };
+    }
+
+    /// Runs a sequence once those started before it have finished.
+    pub fn play(&mut self, sequence: Sequence) {
+        self.queue.push_back(sequence);
+    }
+
+    /// Runs the map's sequence with this name, whatever its trigger. Returns whether there is
+    /// one.
+    pub fn start(&mut self, name: &str) -> bool {
+        let Some((sequence, run)) = self
+            .placed
+            .iter_mut()
+            .find(|(sequence, _)| sequence.name == name)
+        else {
+            return false;
+        };
+        *run = true;
+        self.queue.push_back(sequence.clone());
+        true
+    }
+
+    /// Ends the running sequence straight away, still reporting the events it had left.
+    pub fn skip(&mut self, events: &mut Vec<SequenceEvent>) {
+        let Some(playing) = self.playing.take() else {
+            return;
+        };
+        for step in &playing.sequence.steps[playing.step..] {
+            if let Step::Event(name) = step {
+                events.push(SequenceEvent::Triggered(name.clone()));
+            }
+        }
+        self.finish(playing.sequence.name, events);
+    }
+
+    /// Starts sequences whose trigger has fired and runs steps until one has to wait.
+    pub fn update(&mut self, delta_time: f32, player: Vec2, events: &mut Vec<SequenceEvent>) {
+        for (sequence, run) in &mut self.placed {
+            let fired = match &sequence.trigger {
+                SequenceTrigger::MapStart => true,
+                SequenceTrigger::PlayerEnters(region) => region.contains(player),
+                SequenceTrigger::Manual => false,
+            };
+            if fired && !*run {
+                *run = true;
+                self.queue.push_back(sequence.clone());
+            }
+        }
+
+        // Time left over from a step that finished, for the next one
+        let mut delta_time = delta_time;
+        loop {
+            if self.playing.is_none() {
+                let Some(sequence) = self.queue.pop_front() else {
+                    return;
+                };
+                events.push(SequenceEvent::Started(sequence.name.clone()));
+                self.playing = Some(Playing {
+                    sequence,
+                    step: 0,
+                    elapsed: 0.0,
+                });
+            }
+            let Some(playing) = &mut self.playing else {
+                return;
+            };
+            let Some(step) = playing.sequence.steps.get(playing.step) else {
+                let playing = self.playing.take().unwrap();
+                self.finish(playing.sequence.name, events);
+                continue;
+            };
+
+            let duration = match step {
+                Step::LockInput => {
+                    self.input_locked = true;
+                    None
+                }
+                Step::UnlockInput => {
+                    self.input_locked = false;
+                    None
+                }
+                Step::MoveCamera {
+                    waypoints,
+                    duration,
+                } => {
+                    let t = if duration.is_zero() {
+                        1.0
+                    } else {
+                        ((playing.elapsed + delta_time) / duration.as_secs_f32()).min(1.0)
+                    };
+                    self.camera = camera_along(waypoints, t).or(self.camera);
+                    Some(duration.as_secs_f32())
+                }
+                Step::ReturnCamera => {
+                    self.camera = None;
+                    None
+                }
+                Step::ShowText(text) => {
+                    self.text = Some(text.clone());
+                    None
+                }
+                Step::HideText => {
+                    self.text = None;
+                    None
+                }
+                Step::Wait(duration) => Some(duration.as_secs_f32()),
+                Step::Event(name) => {
+                    events.push(SequenceEvent::Triggered(name.clone()));
+                    None
+                }
+                Step::Lightning(lightning) => {
+                    self.lightning.push(*lightning);
+                    None
+                }
+            };
+
+            if let Some(duration) = duration {
+                playing.elapsed += delta_time;
+                if playing.elapsed < duration {
+                    return;
+                }
+                delta_time = playing.elapsed - duration;
+                playing.elapsed = 0.0;
+            }
+            playing.step += 1;
+        }
+    }
+
+    /// Reports a sequence as finished and undoes what it left behind.
+    fn finish(&mut self, name: String, events: &mut Vec<SequenceEvent>) {
+        events.push(SequenceEvent::Finished(name));
+        self.input_locked = false;
+        self.camera = None;
+        self.text = None;
+    }
+
+    pub fn playing(&self) -> bool {
+        self.playing.is_some()
+    }
+
+    pub fn input_locked(&self) -> bool {
+        self.input_locked
+    }
+
+    /// The camera to show in place of the player's, while a sequence has taken the view.
+    pub fn camera(&self) -> Option<Camera> {
+        self.camera
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+
+    /// The lightning struck since this was last called.
+    pub fn take_lightning(&mut self) -> Vec<Lightning> {
+        std::mem::take(&mut self.lightning)
+    }
+}
+
+/// The camera `t` of the way along a path through `waypoints`, from 0 to 1, moving at an even
+/// speed. Paths that only turn in place take the same time between each waypoint.
+fn camera_along(waypoints: &[Camera], t: f32) -> Option<Camera> {
+    let first = waypoints.first()?;
+    let lengths: Vec<f32> = waypoints
+        .windows(2)
+        .map(|pair| pair[0].position.distance(pair[1].position))
+        .collect();
+    let total: f32 = lengths.iter().sum();
+    let even = total == 0.0;
+
+    let mut along = t * if even { lengths.len() as f32 } else { total };
+    for (pair, length) in waypoints.windows(2).zip(lengths) {
+        let length = if even { 1.0 } else { length };
+        if along <= length {
+            let s = if length > 0.0 { along / length } else { 1.0 };
+            return Some(lerp_camera(&pair[0], &pair[1], s));
+        }
+        along -= length;
+    }
+    Some(*waypoints.last().unwrap_or(first))
+}
+
+/// Blends two cameras, turning whichever way round is shorter.
+fn lerp_camera(a: &Camera, b: &Camera, s: f32) -> Camera {
+    let turn = (b.rotation - a.rotation + 180.0).rem_euclid(360.0) - 180.0;
+    Camera {
+        position: a.position.lerp(b.position, s),
+        rotation: a.rotation + turn * s,
+        height: (a.height as f32 + (b.height as f32 - a.height as f32) * s).round() as u16,
+        fov: a.fov + (b.fov - a.fov) * s,
+        pitch: a.pitch + (b.pitch - a.pitch) * s,
+    }
+}
@@ -0,0 +1,151 @@
+use std::f32::consts::TAU;
+
+use crate::{
+    config::ColorSpace,
+    map::{Fog, MAX_CLOUD_LAYERS, MapMetadata},
+};
+
+/// Noon, for maps entered before the time of day has been set.
+const DEFAULT_TIME_OF_DAY: f32 = 0.5;
+
+/// The time of day, how far the clouds have drifted, and the colors they give the current map.
+#[derive(Clone)]
+pub(crate) struct Sky {
+    /// Fraction of a day since midnight
+    time_of_day: f32,
+    /// How far each cloud layer has drifted, as a fraction of its width
+    cloud_offsets: [f32; MAX_CLOUD_LAYERS],
+    light: [f32; 3],
+    tint: [f32; 3],
+    fog: Option<Fog>,
+    /// Extra brightness from lightning, added to 1
+    flash: f32,
+    /// How the map's colors are encoded
+    color_space: ColorSpace,
+}
+
+impl Sky {
+    pub fn new(color_space: ColorSpace) -> Self {
+        Self {
+            time_of_day: DEFAULT_TIME_OF_DAY,
+            cloud_offsets: [0.0; MAX_CLOUD_LAYERS],
+            light: [1.0; 3],
+            tint: [1.0; 3],
+            fog: None,
+            flash: 0.0,
+            color_space,
+        }
+    }
+
+    /// Moves the day on and drifts the clouds, for maps that have them.
+    pub fn advance(&mut self, delta_time: f32, metadata: &MapMetadata) {
+        if let Some(day_night) = metadata.day_night
+            && !day_night.period.is_zero()
+        {
+            self.time_of_day =
+                (self.time_of_day + delta_time / day_night.period.as_secs_f32()).rem_euclid(1.0);
+        }
+        for (offset, cloud) in self.cloud_offsets.iter_mut().zip(&metadata.clouds) {
+            *offset = (*offset + cloud.speed * delta_time).rem_euclid(1.0);
+        }
+    }
+
+    /// Works out the light, sky tint and fog for the time of day. Returns whether the fog
+    /// changed, which walls are shaded with.
+    pub fn apply(&mut self, metadata: &MapMetadata) -> bool {
+        let (light, tint, fog) = match metadata.day_night {
+            Some(day_night) => {
+                let daylight = self.daylight();
+                let blend = |night: [f32; 3], day: [f32; 3]| {
+                    std::array::from_fn(|i| night[i] + (day[i] - night[i]) * daylight)
+                };
+                let fog = metadata.fog.map(|fog| {
+                    let night_fog = day_night.night_fog.unwrap_or(std::array::from_fn(|i| {
+                        fog.color[i] * day_night.night_light[i]
+                    }));
+                    Fog {
+                        color: blend(night_fog, fog.color),
+                        ..fog
+                    }
+                });
+                (
+                    blend(day_night.night_light, [1.0; 3]),
+                    blend(day_night.night_sky, [1.0; 3]),
+                    fog,
+                )
+            }
+            None => ([1.0; 3], [1.0; 3], metadata.fog),
+        };
+        // Fog is blended with linear colors, so it has to be one too
+        let fog = fog.map(|fog| match self.color_space {
+            ColorSpace::Srgb => Fog {
+                color: fog.color.map(srgb_to_linear),
+                ..fog
+            },
+            ColorSpace::Linear => fog,
+        });
+        self.light = light;
+        self.tint = tint;
+        let changed = fog != self.fog;
+        self.fog = fog;
+        changed
+    }
+
+    pub fn time_of_day(&self) -> f32 {
+        self.time_of_day
+    }
+
+    /// Wrapped into `0.0..1.0`, so 1.25 is a quarter past midnight.
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        self.time_of_day = time_of_day.rem_euclid(1.0);
+    }
+
+    /// 0 at midnight rising to 1 at noon.
+    pub fn daylight(&self) -> f32 {
+        (1.0 - (self.time_of_day * TAU).cos()) / 2.0
+    }
+
+    /// Lights up the whole scene, sky included, for a lightning flash.
+    pub fn set_flash(&mut self, flash: f32) {
+        self.flash = flash;
+    }
+
+    /// Multiplies everything but the sky.
+    pub fn light(&self) -> [f32; 3] {
+        self.light.map(|light| light * (1.0 + self.flash))
+    }
+
+    /// Multiplies the sky and clouds.
+    pub fn tint(&self) -> [f32; 3] {
+        self.tint.map(|tint| tint * (1.0 + self.flash))
+    }
+
+    /// The map's fog, colored for the time of day, in linear space.
+    pub fn fog(&self) -> Option<Fog> {
+        self.fog
+    }
+
+    /// Per cloud layer drawn: how far it's drifted and its opacity, padded for the floor
+    /// pass's uniform. Then how many there are.
+    pub fn clouds(&self, metadata: &MapMetadata) -> ([[f32; 4]; MAX_CLOUD_LAYERS], u32) {
+        let mut clouds = [[0.0; 4]; MAX_CLOUD_LAYERS];
+        let mut count = 0;
+        for ((cloud, offset), layer) in clouds
+            .iter_mut()
+            .zip(self.cloud_offsets)
+            .zip(&metadata.clouds)
+        {
+            *cloud = [offset, layer.opacity, 0.0, 0.0];
+            count += 1;
+        }
+        (clouds, count)
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -0,0 +1,121 @@
+use glam::Vec2;
+
+use crate::{
+    engine::WaveEvent,
+    entity::{Entities, Entity, EntityId, EntityTypes, SpawnTrigger, Spawner},
+    map::Map,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum WaveState {
+    Waiting,
+    Spawning,
+    Finished,
+}
+
+/// One spawner's wave and the entities it has spawned that are still alive.
+#[derive(Clone)]
+struct Wave {
+    spawner: Spawner,
+    state: WaveState,
+    /// Set by the game or another wave finishing, to start on the next update
+    triggered: bool,
+    spawned: u32,
+    /// Seconds until the next spawn
+    timer: f32,
+    alive: Vec<EntityId>,
+}
+
+/// The waves of the current map's spawners.
+#[derive(Clone, Default)]
+pub(crate) struct Spawners {
+    waves: Vec<Wave>,
+}
+
+impl Spawners {
+    /// Sets every spawner in the map waiting for its trigger again.
+    pub fn load_map(&mut self, map: &Map) {
+        self.waves = map
+            .spawners()
+            .iter()
+            .map(|spawner| Wave {
+                spawner: spawner.clone(),
+                state: WaveState::Waiting,
+                triggered: false,
+                spawned: 0,
+                timer: 0.0,
+                alive: Vec::new(),
+            })
+            .collect();
+    }
+
+    /// Starts the named spawner's wave on the next update if it's still waiting. Returns
+    /// whether it was.
+    pub fn trigger(&mut self, name: &str) -> bool {
+        let Some(wave) = self
+            .waves
+            .iter_mut()
+            .find(|wave| wave.spawner.name == name && wave.state == WaveState::Waiting)
+        else {
+            return false;
+        };
+        wave.triggered = true;
+        true
+    }
+
+    /// Starts waves whose trigger has fired, spawns what's due and finishes waves that have
+    /// been cleared. Entities `check` rejects are left out of their wave.
+    pub fn update(
+        &mut self,
+        delta_time: f32,
+        player: Vec2,
+        entities: &mut Entities,
+        entity_types: &EntityTypes,
+        check: impl Fn(&Entity) -> anyhow::Result<()>,
+        events: &mut Vec<WaveEvent>,
+    ) {
+        for index in 0..self.waves.len() {
+            let wave = &mut self.waves[index];
+            if wave.state == WaveState::Waiting {
+                let fired = match &wave.spawner.trigger {
+                    SpawnTrigger::MapStart => true,
+                    SpawnTrigger::PlayerEnters(region) => region.contains(player),
+                    SpawnTrigger::AfterWave(_) | SpawnTrigger::Manual => wave.triggered,
+                };
+                if fired {
+                    wave.state = WaveState::Spawning;
+                    events.push(WaveEvent::Started(wave.spawner.name.clone()));
+                }
+            }
+            if wave.state != WaveState::Spawning {
+                continue;
+            }
+
+            wave.alive.retain(|id| entities.get(*id).is_some());
+            wave.timer -= delta_time;
+            while wave.spawned < wave.spawner.count && wave.timer <= 0.0 {
+                if let Some(entity) = entity_types.build(&wave.spawner.entity) {
+                    match check(&entity) {
+                        Ok(()) => wave.alive.push(entities.spawn(entity)),
+                        Err(error) => eprintln!("wave {}: {error}", wave.spawner.name),
+                    }
+                }
+                wave.spawned += 1;
+                wave.timer += wave.spawner.interval.as_secs_f32();
+            }
+
+            if wave.spawned == wave.spawner.count && wave.alive.is_empty() {
+                wave.state = WaveState::Finished;
+                events.push(WaveEvent::Finished(wave.spawner.name.clone()));
+
+                let name = wave.spawner.name.clone();
+                for next in &mut self.waves {
+                    if matches!(&next.spawner.trigger, SpawnTrigger::AfterWave(after) if *after == name)
+                    {
+                        next.triggered = true;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,3 +1,417 @@
-// use super::*;
+use std::fmt::Write;
 
-// #[test]
+use super::{backend::HeadlessBackend, *};
+use crate::map::TileData;
+
+const TILE_SIZE: u16 = 64;
+const WALL_HEIGHT: u16 = 64;
+/// Where the golden columns are kept. Run the tests with `UPDATE_GOLDEN` set to write them
+/// again after a change that's meant to move walls.
+const GOLDEN_COLUMNS: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/src/raycaster/golden/columns.txt"
+);
+/// The poses the golden columns are cast from: positions in tiles and rotations in degrees.
+const GOLDEN_POSES: [(f32, f32, f32); 4] = [
+    (1.5, 1.5, 45.0),
+    (3.5, 3.5, 0.0),
+    (6.2, 6.7, 200.0),
+    (4.0, 2.5, 290.0),
+];
+
+/// A `size` by `size` map walled around its edge, with a pillar every eight tiles so rays
+/// cross most of it before they hit anything.
+fn open_map(size: usize) -> Map {
+    let tiles = (0..size)
+        .map(|row| {
+            (0..size)
+                .map(|col| {
+                    let edge = row == 0 || col == 0 || row == size - 1 || col == size - 1;
+                    let pillar = row % 8 == 4 && col % 8 == 4;
+                    (edge || pillar) as u8
+                })
+                .collect()
+        })
+        .collect();
+    let tile_types = HashMap::from([
+        (0, TileType::Floor(TileData::new("floor.png"))),
+        (1, TileType::Wall(TileData::new("wall.png"))),
+    ]);
+    Map::new(tiles, tile_types)
+}
+
+/// Casts and projects a view `width` by `height` pixels from `camera`, as the main view is,
+/// and returns the walls it drew.
+fn draw_view(map: &Map, camera: Camera, width: u32, height: u32) -> Vec<WallInstance> {
+    draw_view_headless(map, &camera, width, height, TILE_SIZE, WALL_HEIGHT).unwrap()
+}
+
+#[test]
+fn ray_hits_the_nearest_face() {
+    let map = open_map(16);
+    let linedefs = LinedefIndex::default();
+    let doors = Doors::default();
+    // From the middle of the tile left of a pillar, looking right at it
+    let origin = Vec2::new(3.5, 4.5) * TILE_SIZE as f32;
+    let hit = cast_ray(origin, 0.0, &map, &linedefs, &doors, TILE_SIZE).unwrap();
+
+    assert_eq!(hit.side, TileSide::Left);
+    assert_eq!(hit.tile_index, 4 * 16 + 4);
+    assert!((hit.dist - TILE_SIZE as f32 / 2.0).abs() < 0.01);
+}
+
+/// Facing a wall square on, every column is the same distance from the view plane, so without
+/// fisheye the wall is one height across the view, centered on the horizon at eye level.
+#[test]
+fn a_wall_faced_square_on_projects_level() {
+    let map = open_map(4);
+    // Looking east across the room at the wall a tile and a half away
+    let camera = Camera::new(Vec2::splat(1.5) * TILE_SIZE as f32, 0.0)
+        .with_fov(30.0)
+        .with_height(WALL_HEIGHT / 2);
+    let (width, height) = (64, 48);
+    let walls = draw_view(&map, camera, width, height);
+
+    let dist_to_projection_plane = width as f32 / 2.0 / 15f32.to_radians().tan();
+    let expected = dist_to_projection_plane * WALL_HEIGHT as f32 / (1.5 * TILE_SIZE as f32);
+    assert_eq!(walls.len(), width as usize);
+    for wall in &walls {
+        assert!(
+            (wall.height - expected).abs() < expected * 0.02,
+            "column {} is {} high, not {expected}",
+            wall.screen_x,
+            wall.height
+        );
+        assert!((wall.top + wall.height / 2.0 - height as f32 / 2.0).abs() < 1.0);
+    }
+}
+
+#[test]
+fn textures_run_left_to_right_across_a_face() {
+    let map = open_map(4);
+    let camera = Camera::new(Vec2::splat(1.5) * TILE_SIZE as f32, 0.0).with_fov(30.0);
+    let walls = draw_view(&map, camera, 64, 48);
+
+    assert!(walls.iter().all(|wall| wall.tex_layer == 1));
+    assert!(walls.windows(2).all(|pair| pair[0].tex_u <= pair[1].tex_u));
+    // The middle column hits the middle of the tile's face
+    assert!((walls[32].tex_u - 0.5).abs() < 2.0 / TILE_SIZE as f32);
+}
+
+/// A body moving more than a tile a step toward the pillar in the middle of a small room, from
+/// each side along each axis, stops against the face it meets instead of passing through.
+#[test]
+fn a_long_step_stops_flush_against_a_one_tile_wall() {
+    let map = open_map(9);
+    let doors = Doors::default();
+    let linedefs = LinedefIndex::default();
+    let terrain = Terrain {
+        map: &map,
+        doors: &doors,
+        linedefs: &linedefs,
+        tile_size: TILE_SIZE,
+    };
+    let tile = TILE_SIZE as f32;
+    let radius = 16.0;
+    let step = 2.5 * tile;
+
+    // Where the body starts, in tiles, which way it moves and the pillar face it should reach
+    let cases = [
+        (Vec2::new(1.5, 4.5), Vec2::X, 4.0 * tile - radius),
+        (Vec2::new(7.5, 4.5), Vec2::NEG_X, 5.0 * tile + radius),
+        (Vec2::new(4.5, 1.5), Vec2::Y, 4.0 * tile - radius),
+        (Vec2::new(4.5, 7.5), Vec2::NEG_Y, 5.0 * tile + radius),
+    ];
+    for (start, direction, expected) in cases {
+        let moved = terrain.slide_body(start * tile, direction * step, radius);
+        let along = moved.dot(direction.abs());
+        assert!(
+            (along - expected).abs() < 0.01,
+            "moving {direction} from {start} ended at {moved}, not against the face at {expected}"
+        );
+        assert!(!terrain.collides(moved, radius));
+    }
+}
+
+/// A room with pillars of a second wall type scattered through it, so rays from the golden
+/// poses land on every side of both at many distances.
+fn golden_map() -> Map {
+    let tiles = [
+        "11111111", "10000001", "10200201", "10000001", "10002201", "10200001", "10000001",
+        "11111111",
+    ]
+    .map(|row| row.bytes().map(|tile| tile - b'0').collect())
+    .to_vec();
+    let tile_types = HashMap::from([
+        (0, TileType::Floor(TileData::new("floor.png"))),
+        (1, TileType::Wall(TileData::new("wall.png"))),
+        (2, TileType::Wall(TileData::new("pillar.png"))),
+    ]);
+    Map::new(tiles, tile_types)
+}
+
+/// Each golden pose, then per column the distance, side, texture coordinate and layer of the
+/// wall its ray hit.
+fn golden_columns() -> String {
+    let map = golden_map();
+    let (linedefs, doors) = (LinedefIndex::default(), Doors::default());
+    let mut columns = String::new();
+    for (x, y, rotation) in GOLDEN_POSES {
+        let camera = Camera::new(Vec2::new(x, y) * TILE_SIZE as f32, rotation);
+        let mut view = View::new(24, 16, camera.fov).unwrap();
+        let backend = HeadlessBackend::default();
+        view.cast_rays(&camera, &map, &linedefs, &doors, TILE_SIZE, &backend, 1)
+            .unwrap();
+
+        writeln!(columns, "pose {x} {y} {rotation}").unwrap();
+        let rays = &view.rays;
+        for column in 0..rays.len() {
+            match rays.side(column) {
+                Some(side) => writeln!(
+                    columns,
+                    "{column} {:.3} {side:?} {:.4} {}",
+                    rays.distances[column], rays.tex_u[column], rays.layers[column]
+                ),
+                None => writeln!(columns, "{column} miss"),
+            }
+            .unwrap();
+        }
+    }
+    columns
+}
+
+#[test]
+fn columns_match_the_golden_data() {
+    let columns = golden_columns();
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(GOLDEN_COLUMNS, &columns).unwrap();
+        return;
+    }
+
+    let golden = std::fs::read_to_string(GOLDEN_COLUMNS).unwrap();
+    assert_eq!(columns.lines().count(), golden.lines().count());
+    for (actual, expected) in columns.lines().zip(golden.lines()) {
+        // Numbers may differ in the last place between platforms
+        let matches = actual.split(' ').count() == expected.split(' ').count()
+            && actual.split(' ').zip(expected.split(' ')).all(|(a, b)| {
+                a == b
+                    || a.parse::<f32>()
+                        .ok()
+                        .zip(b.parse::<f32>().ok())
+                        .is_some_and(|(a, b)| (a - b).abs() <= 1e-3)
+            });
+        assert!(
+            matches,
+            "column `{actual}` doesn't match golden `{expected}`"
+        );
+    }
+}
+
+/// Stepping AI across a thread pool comes out the same as stepping it on one thread, since
+/// results are merged in entity order. Some entities patrol and some chase the player, so
+/// both the sighting and the route planning passes are compared.
+#[cfg(feature = "rayon")]
+#[test]
+fn ai_stepped_in_parallel_moves_entities_as_serially() {
+    use std::time::Duration;
+
+    use crate::{
+        ai::Ai,
+        entity::{Entities, Hostility},
+        map::PatrolPath,
+    };
+
+    let tile = TILE_SIZE as f32;
+    let map = open_map(48).with_patrol_paths(vec![PatrolPath::new(
+        "loop",
+        vec![
+            Vec2::new(2.5, 2.5) * tile,
+            Vec2::new(45.5, 2.5) * tile,
+            Vec2::new(45.5, 45.5) * tile,
+            Vec2::new(2.5, 45.5) * tile,
+        ],
+    )]);
+    let doors = Doors::default();
+    let linedefs = LinedefIndex::default();
+    let terrain = Terrain {
+        map: &map,
+        doors: &doors,
+        linedefs: &linedefs,
+        tile_size: TILE_SIZE,
+    };
+    let player = Vec2::new(24.5, 24.5) * tile;
+
+    let run = |threads: usize| -> Vec<Vec2> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        pool.install(|| {
+            let mut entities = Entities::default();
+            for i in 0..400 {
+                let position = Vec2::new(1.5 + (i % 46) as f32, 1.5 + (i / 46 * 5) as f32) * tile;
+                let ai = if i % 2 == 0 {
+                    Ai::new(96.0).with_patrol("loop", Duration::from_millis(250))
+                } else {
+                    Ai::new(128.0).with_sight_range(30.0 * tile)
+                };
+                entities.spawn(Entity::new(position).with_ai(ai));
+            }
+
+            let delta_time = 1.0 / 60.0;
+            for _ in 0..60 {
+                let steps = plan_ai(
+                    terrain,
+                    &Hostility::default(),
+                    &[],
+                    &entities,
+                    player,
+                    delta_time,
+                );
+                for ((_, entity), step) in entities.iter_mut().zip(steps) {
+                    let Some(step) = step else {
+                        continue;
+                    };
+                    entity.ai = Some(step.ai);
+                    entity.position = terrain.slide_body(
+                        entity.position,
+                        step.velocity * delta_time,
+                        entity.radius,
+                    );
+                }
+            }
+            entities.iter().map(|(_, entity)| entity.position).collect()
+        })
+    };
+
+    assert_eq!(run(1), run(4));
+}
+
+/// Losing and regaining focus only touches the focus pause, so a pause the game set holds
+/// through it, whether losing focus pauses or not.
+#[test]
+fn a_game_pause_survives_focus_coming_and_going() {
+    for pause_when_unfocused in [true, false] {
+        let mut pause = Pause {
+            paused: true,
+            ..Default::default()
+        };
+        pause.focus_paused = pause_when_unfocused;
+        assert!(pause.is_paused());
+        pause.focus_paused = false;
+        assert!(pause.is_paused());
+    }
+
+    let mut pause = Pause {
+        focus_paused: true,
+        ..Default::default()
+    };
+    assert!(pause.is_paused());
+    pause.focus_paused = false;
+    assert!(!pause.is_paused());
+}
+
+/// `count` steps walking right at a walk, from tick 1.
+fn walking_steps(count: u64) -> Vec<PlayerStep> {
+    (1..=count)
+        .map(|tick| PlayerStep {
+            tick,
+            delta_time: FIXED_TIMESTEP,
+            direction: Vec2::X,
+            stance: 1.0,
+        })
+        .collect()
+}
+
+/// Takes `steps` from `position` at `velocity`, keeping them as the engine does, and returns
+/// where the player is and how fast they move after each one.
+fn predict(
+    terrain: Terrain<'_>,
+    history: &mut PlayerSteps,
+    steps: &[PlayerStep],
+    mut position: Vec2,
+    mut velocity: Vec2,
+) -> Vec<(Vec2, Vec2)> {
+    steps
+        .iter()
+        .map(|step| {
+            history.push(*step);
+            move_player(terrain, false, &mut position, &mut velocity, *step);
+            (position, velocity)
+        })
+        .collect()
+}
+
+/// A server agreeing with where the client predicted the player was after some tick leaves
+/// the player where they are, and one that disagrees moves them by the difference, as the
+/// steps since are replayed on top of it.
+#[test]
+fn reconciling_replays_the_steps_since_the_server_tick() {
+    let map = open_map(16);
+    let doors = Doors::default();
+    let linedefs = LinedefIndex::default();
+    let terrain = Terrain {
+        map: &map,
+        doors: &doors,
+        linedefs: &linedefs,
+        tile_size: TILE_SIZE,
+    };
+    let start = Vec2::new(2.5, 8.5) * TILE_SIZE as f32;
+    let steps = walking_steps(30);
+
+    let mut history = PlayerSteps::default();
+    let predicted = predict(terrain, &mut history, &steps, start, Vec2::ZERO);
+    let (now, velocity_now) = predicted[29];
+    let (then, velocity_then) = predicted[9];
+    let (position, velocity) = history.reconcile(terrain, false, 10, then, velocity_then);
+    assert!(position.distance(now) < 0.001, "{position} isn't {now}");
+    assert!(velocity.distance(velocity_now) < 0.001);
+
+    let mut history = PlayerSteps::default();
+    predict(terrain, &mut history, &steps, start, Vec2::ZERO);
+    let offset = Vec2::new(-12.0, 20.0);
+    let (position, _) = history.reconcile(terrain, false, 10, then + offset, velocity_then);
+    assert!(
+        position.distance(now + offset) < 0.001,
+        "{position} isn't {now} moved by {offset}"
+    );
+}
+
+/// A server tick from before the oldest kept step replays only the steps that were kept,
+/// not the ones already dropped.
+#[test]
+fn reconciling_to_a_tick_older_than_the_history_skips_dropped_steps() {
+    let map = open_map(16);
+    let doors = Doors::default();
+    let linedefs = LinedefIndex::default();
+    let terrain = Terrain {
+        map: &map,
+        doors: &doors,
+        linedefs: &linedefs,
+        tile_size: TILE_SIZE,
+    };
+    let start = Vec2::new(2.5, 8.5) * TILE_SIZE as f32;
+    let dropped = 10;
+    let steps = walking_steps((MAX_PLAYER_STEPS + dropped) as u64);
+
+    let mut history = PlayerSteps::default();
+    let predicted = predict(terrain, &mut history, &steps, start, Vec2::ZERO);
+    let (position, velocity) = history.reconcile(terrain, false, 0, start, Vec2::ZERO);
+
+    // Only the kept steps, taken from where the server put the player
+    let kept = predict(
+        terrain,
+        &mut PlayerSteps::default(),
+        &steps[dropped..],
+        start,
+        Vec2::ZERO,
+    );
+    let (expected, expected_velocity) = *kept.last().unwrap();
+    assert!(
+        position.distance(expected) < 0.001,
+        "{position} isn't {expected}"
+    );
+    assert!(velocity.distance(expected_velocity) < 0.001);
+    let (every_step, _) = *predicted.last().unwrap();
+    assert!(position.distance(every_step) > 1.0);
+}
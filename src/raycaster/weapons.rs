@@ -0,0 +1,88 @@
+use crate::weapon::Weapon;
+
+/// The player's weapons, which one is held and how far along its firing is.
+#[derive(Clone, Default)]
+pub(crate) struct Arsenal {
+    weapons: Vec<Weapon>,
+    current: Option<usize>,
+    /// Seconds until the held weapon can fire again
+    cooldown: f32,
+    /// Seconds since the held weapon last fired, while its fire sprites are playing
+    since_shot: Option<f32>,
+}
+
+impl Arsenal {
+    /// Holds the first of the weapons, if there are any.
+    pub fn new(weapons: Vec<Weapon>) -> Self {
+        Self {
+            current: (!weapons.is_empty()).then_some(0),
+            weapons,
+            cooldown: 0.0,
+            since_shot: None,
+        }
+    }
+
+    pub fn weapons(&self) -> &[Weapon] {
+        &self.weapons
+    }
+
+    pub fn current(&self) -> Option<&Weapon> {
+        self.current.map(|index| &self.weapons[index])
+    }
+
+    /// Switches to the weapon at `index`. Returns whether there is one. The cooldown from the
+    /// last shot keeps running, so switching back and forth doesn't fire any sooner.
+    pub fn select(&mut self, index: usize) -> bool {
+        if index >= self.weapons.len() {
+            return false;
+        }
+        if self.current != Some(index) {
+            self.current = Some(index);
+            self.since_shot = None;
+        }
+        true
+    }
+
+    /// Runs down the cooldown and the fire animation.
+    pub fn update(&mut self, delta_time: f32) {
+        self.cooldown = (self.cooldown - delta_time).max(0.0);
+        let Some(weapon) = self.current() else {
+            return;
+        };
+        let animation = weapon.frame_time.as_secs_f32() * weapon.fire_sprites.len() as f32;
+        self.since_shot = self
+            .since_shot
+            .map(|since_shot| since_shot + delta_time)
+            .filter(|since_shot| *since_shot < animation);
+    }
+
+    /// Whether the held weapon is ready to fire.
+    pub fn ready(&self) -> bool {
+        self.current.is_some() && self.cooldown == 0.0
+    }
+
+    /// Starts the held weapon's cooldown and fire animation.
+    pub fn fired(&mut self) {
+        if let Some(weapon) = self.current() {
+            self.cooldown = weapon.fire_interval.as_secs_f32();
+            self.since_shot = Some(0.0);
+        }
+    }
+
+    /// The view sprite to draw for the held weapon right now.
+    pub fn sprite(&self) -> Option<&'static str> {
+        let weapon = self.current()?;
+        let frame_time = weapon.frame_time.as_secs_f32();
+        Some(match self.since_shot {
+            Some(since_shot) if frame_time > 0.0 => {
+                let frame = (since_shot / frame_time) as usize;
+                weapon
+                    .fire_sprites
+                    .get(frame)
+                    .copied()
+                    .unwrap_or(weapon.idle_sprite)
+            }
+            _ => weapon.idle_sprite,
+        })
+    }
+}
@@ -0,0 +1,137 @@
+use glam::Vec2;
+
+use crate::{
+    map::{Map, Precipitation, Weather},
+    rng::Rng,
+    sprite::Sprite,
+};
+
+/// How far from the player weather falls, in tiles.
+const WEATHER_RADIUS: f32 = 6.0;
+/// How far from the player open sky is listened for, in tiles.
+const EXPOSURE_RADIUS: i32 = 3;
+/// The most drops or flakes kept falling at once, however dense the weather.
+const MAX_PARTICLES: usize = 1024;
+/// Places tried for a new drop before giving up until the next frame, so few fall near the
+/// player when they're mostly under a roof.
+const SPAWN_TRIES: usize = 4;
+/// How quickly the weather's sound follows the player in and out of shelter, per second
+const EXPOSURE_RATE: f32 = 2.0;
+/// How far snowflakes drift from side to side, in world units per second
+const SNOW_DRIFT: f32 = 12.0;
+
+/// One drop or flake.
+#[derive(Clone)]
+struct Particle {
+    position: Vec2,
+    /// Above the floor, in world units
+    height: f32,
+    /// Radians, so flakes don't all sway together
+    phase: f32,
+}
+
+/// The current map's falling weather around the player.
+#[derive(Clone, Default)]
+pub(crate) struct WeatherState {
+    particles: Vec<Particle>,
+    /// How much of the sky around the player is open, eased over time, from 0 to 1
+    exposure: f32,
+}
+
+impl WeatherState {
+    pub fn load_map(&mut self) {
+        self.particles.clear();
+        self.exposure = 0.0;
+    }
+
+    /// Drops the particles, replacing those that landed, drifted under a roof or were left
+    /// behind by the player with new ones over open tiles nearby.
+    pub fn update(
+        &mut self,
+        delta_time: f32,
+        map: &Map,
+        center: Vec2,
+        tile_size: f32,
+        wall_height: f32,
+        rng: &mut Rng,
+    ) {
+        let Some(weather) = map.metadata().weather() else {
+            self.particles.clear();
+            self.exposure = 0.0;
+            return;
+        };
+
+        let open = self.open_fraction(map, center, tile_size);
+        let change = EXPOSURE_RATE * delta_time;
+        self.exposure += (open - self.exposure).clamp(-change, change);
+
+        let radius = WEATHER_RADIUS * tile_size;
+        let drift = match weather.precipitation {
+            Precipitation::Rain => 0.0,
+            Precipitation::Snow => SNOW_DRIFT,
+        };
+        self.particles.retain_mut(|particle| {
+            particle.height -= weather.fall_speed * delta_time;
+            particle.phase += delta_time;
+            particle.position.x += particle.phase.cos() * drift * delta_time;
+            particle.height > 0.0
+                && (particle.position - center).abs().max_element() < radius
+                && map.is_outdoor(particle.position, tile_size)
+        });
+
+        let area = (WEATHER_RADIUS * 2.0).powi(2);
+        let wanted = ((weather.density * area) as usize).min(MAX_PARTICLES);
+        let missing = wanted.saturating_sub(self.particles.len());
+        // The first fill spreads them through the air, rather than all starting at the top
+        let filling = self.particles.is_empty();
+        for _ in 0..missing {
+            let position = (0..SPAWN_TRIES)
+                .map(|_| center + Vec2::new(rng.range(-radius, radius), rng.range(-radius, radius)))
+                .find(|position| map.is_outdoor(*position, tile_size));
+            let Some(position) = position else {
+                continue;
+            };
+            self.particles.push(Particle {
+                position,
+                height: if filling {
+                    rng.range(0.0, wall_height)
+                } else {
+                    wall_height
+                },
+                phase: rng.range(0.0, std::f32::consts::TAU),
+            });
+        }
+    }
+
+    /// How many of the tiles around the player are open to the sky, from 0 to 1.
+    fn open_fraction(&self, map: &Map, center: Vec2, tile_size: f32) -> f32 {
+        let mut open = 0;
+        for row in -EXPOSURE_RADIUS..=EXPOSURE_RADIUS {
+            for col in -EXPOSURE_RADIUS..=EXPOSURE_RADIUS {
+                let offset = Vec2::new(col as f32, row as f32) * tile_size;
+                open += map.is_outdoor(center + offset, tile_size) as u32;
+            }
+        }
+        open as f32 / ((EXPOSURE_RADIUS * 2 + 1) as f32).powi(2)
+    }
+
+    /// How much of the sky around the player is open, eased as they move in and out of
+    /// shelter. 0 without weather.
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    /// A billboard for each drop or flake.
+    pub fn sprites(&self, weather: Weather) -> impl Iterator<Item = Sprite> + '_ {
+        let opacity = match weather.precipitation {
+            Precipitation::Rain => 0.6,
+            Precipitation::Snow => 0.9,
+        };
+        self.particles.iter().map(move |particle| {
+            Sprite::new(particle.position, weather.texture_path)
+                .with_scale(weather.scale)
+                .with_z_offset(particle.height)
+                .with_opacity(opacity)
+        })
+    }
+}
@@ -0,0 +1,265 @@
+use glam::Mat3;
+use wgpu::util::DeviceExt;
+
+use crate::{hud::Rect, renderer::texture::Texture};
+
+/// The strongest tint the view is given in photosensitive safe mode.
+const SAFE_TINT_STRENGTH: f32 = 0.6;
+/// How quickly the tint may change in photosensitive safe mode, per second. Slow enough that
+/// the view can't flash more than about once a second, well under the 3 flashes a second
+/// accessibility guidelines allow.
+const SAFE_TINT_RATE: f32 = 1.2;
+
+/// Full-screen effects applied while scaling. Matches `PostEffect` in blit.wgsl. The default
+/// leaves the view untouched.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct PostEffect {
+    /// Multiplied over the view, blended in by `tint_strength`
+    tint: [f32; 3],
+    tint_strength: f32,
+    /// Amplitude of the ripple, as a fraction of the view
+    distortion: f32,
+    /// Seconds, to animate the ripple
+    time: f32,
+    /// How much of `color_matrix` is applied, 0 or 1
+    color_strength: f32,
+    _pad: f32,
+    /// A linear color matrix applied last, as padded columns
+    color_matrix: [[f32; 4]; 3],
+}
+
+impl PostEffect {
+    /// A tint and a rippling distortion, both scaled by `strength` from 0 to 1.
+    pub fn underwater(tint: [f32; 3], strength: f32, time: f32) -> Self {
+        Self {
+            tint,
+            tint_strength: strength * 0.6,
+            distortion: strength * 0.004,
+            time,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a flash of `color` over the effect, at `strength` from 0 to 1.
+    pub fn with_flash(mut self, color: [f32; 3], strength: f32) -> Self {
+        if strength <= 0.0 {
+            return self;
+        }
+        let weight = strength / (self.tint_strength + strength);
+        for (tint, color) in self.tint.iter_mut().zip(color) {
+            *tint += (color - *tint) * weight;
+        }
+        self.tint_strength = (self.tint_strength + strength * 0.5).min(1.0);
+        self
+    }
+
+    /// Passes the final colors through `matrix`, such as a
+    /// [`ColorFilter`](crate::config::ColorFilter)'s.
+    pub fn with_filter(mut self, matrix: Option<Mat3>) -> Self {
+        if let Some(matrix) = matrix {
+            self.color_matrix = [matrix.x_axis, matrix.y_axis, matrix.z_axis]
+                .map(|column| column.extend(0.0).to_array());
+            self.color_strength = 1.0;
+        }
+        self
+    }
+
+    /// The effect with its tint capped and eased towards from `shown`, the effect on screen,
+    /// so that it can't flash brightly or quickly.
+    pub fn limited(mut self, shown: &Self, delta_time: f32) -> Self {
+        let step = SAFE_TINT_RATE * delta_time;
+        let toward = |from: f32, to: f32| from + (to - from).clamp(-step, step);
+        // A tint fading out keeps its color, rather than jumping to the new one
+        if self.tint_strength > 0.0 {
+            for (tint, shown) in self.tint.iter_mut().zip(shown.tint) {
+                *tint = toward(shown, *tint);
+            }
+        } else {
+            self.tint = shown.tint;
+        }
+        self.tint_strength = toward(
+            shown.tint_strength,
+            self.tint_strength.min(SAFE_TINT_STRENGTH),
+        );
+        self
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.tint_strength == 0.0 && self.distortion == 0.0 && self.color_strength == 0.0
+    }
+}
+
+/// An internal-resolution color target and the pass that scales it onto the surface.
+pub(crate) struct Blit {
+    pub target: Texture,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    effect_buffer: wgpu::Buffer,
+}
+
+impl Blit {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        filter: wgpu::FilterMode,
+    ) -> Self {
+        let target =
+            Texture::create_render_target(device, width, height, format, "Internal Target");
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Blit Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Blit bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let effect_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Effect Buffer"),
+            contents: bytemuck::bytes_of(&PostEffect::default()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Blit Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&target.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: effect_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("blit.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blit Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Blit Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: Default::default(),
+        });
+
+        Self {
+            target,
+            pipeline,
+            bind_group,
+            effect_buffer,
+        }
+    }
+
+    pub fn set_effect(&self, queue: &wgpu::Queue, effect: &PostEffect) {
+        queue.write_buffer(&self.effect_buffer, 0, bytemuck::bytes_of(effect));
+    }
+
+    /// Scales the target onto `viewport` of the surface, or all of it. The rest is cleared to
+    /// `clear_color`, or kept as it was without one.
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        surface_view: &wgpu::TextureView,
+        viewport: Option<Rect>,
+        clear_color: Option<wgpu::Color>,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Blit Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: surface_view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: clear_color.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes,
+            multiview_mask: None,
+        });
+
+        if let Some(viewport) = viewport {
+            render_pass.set_viewport(
+                viewport.x as f32,
+                viewport.y as f32,
+                viewport.width as f32,
+                viewport.height as f32,
+                0.0,
+                1.0,
+            );
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
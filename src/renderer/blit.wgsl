@@ -0,0 +1,45 @@
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+
+struct PostEffect {
+    tint: vec3f,
+    tint_strength: f32,
+    distortion: f32,
+    time: f32,
+    color_strength: f32,
+    color_matrix: mat3x3f,
+};
+@group(0) @binding(2) var<uniform> effect: PostEffect;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2f,
+};
+
+// A single triangle that covers the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2f(f32((index << 1u) & 2u), f32(index & 2u));
+
+    out.clip_position = vec4f(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    // Ripple the lookup with crossing sine waves, pulling in from the edges so none of the
+    // view slides out of frame
+    let wave = vec2f(
+        sin(input.uv.y * 40.0 + effect.time * 3.0),
+        cos(input.uv.x * 30.0 + effect.time * 2.0) * 0.5,
+    );
+    let uv = mix(input.uv, vec2f(0.5), effect.distortion * 2.0) + wave * effect.distortion;
+
+    let color = textureSample(source, source_sampler, uv);
+    let tinted = mix(color.rgb, color.rgb * effect.tint, effect.tint_strength);
+    let filtered = mix(tinted, clamp(effect.color_matrix * tinted, vec3f(0.0), vec3f(1.0)), effect.color_strength);
+    return vec4f(filtered, color.a);
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use crate::renderer::MapTextures;
+
+struct CacheEntry {
+    textures: MapTextures,
+    last_used: u64,
+}
+
+/// Keeps the texture arrays of recently used maps resident, up to a memory budget. Maps that
+/// aren't active are dropped least recently used first once the budget is exceeded.
+pub(crate) struct TextureCache {
+    budget: u64,
+    entries: HashMap<&'static str, CacheEntry>,
+    clock: u64,
+}
+
+impl TextureCache {
+    pub fn new(budget: u64) -> Self {
+        Self {
+            budget,
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn get(&self, map_key: &str) -> Option<&MapTextures> {
+        self.entries.get(map_key).map(|entry| &entry.textures)
+    }
+
+    pub fn get_mut(&mut self, map_key: &str) -> Option<&mut MapTextures> {
+        self.entries
+            .get_mut(map_key)
+            .map(|entry| &mut entry.textures)
+    }
+
+    /// Marks a map as just used. Returns whether its textures are resident.
+    pub fn touch(&mut self, map_key: &str) -> bool {
+        self.clock += 1;
+        match self.entries.get_mut(map_key) {
+            Some(entry) => {
+                entry.last_used = self.clock;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds the textures for `map_key`, which becomes the active map, and evicts others to
+    /// make room.
+    pub fn insert(&mut self, map_key: &'static str, textures: MapTextures) {
+        self.clock += 1;
+        self.entries.insert(
+            map_key,
+            CacheEntry {
+                textures,
+                last_used: self.clock,
+            },
+        );
+        self.evict(map_key);
+    }
+
+    pub fn remove(&mut self, map_key: &str) {
+        self.entries.remove(map_key);
+    }
+
+    /// Drops inactive maps, least recently used first, until the cache fits its budget. The
+    /// active map is never dropped, even if it alone is over budget.
+    pub fn evict(&mut self, active_map_key: &str) {
+        while self.resident_bytes() > self.budget {
+            let lru = self
+                .entries
+                .iter()
+                .filter(|(key, _)| **key != active_map_key)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+
+            match lru {
+                Some(key) => {
+                    self.entries.remove(key);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// GPU memory used by every resident map's textures, in bytes.
+    pub fn resident_bytes(&self) -> u64 {
+        self.entries
+            .values()
+            .map(|entry| entry.textures.memory_size())
+            .sum()
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Child, Command, Stdio},
+    sync::mpsc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use image::RgbaImage;
+
+use crate::config::{CaptureOutput, FrameCapture};
+
+/// A frame copied into a buffer, on its way back from the GPU. Screenshots and frame capture
+/// both read frames through this.
+pub(crate) struct Readback {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    bgra: bool,
+}
+
+impl Readback {
+    /// Whether frames in `format` can be copied back: 8-bit RGBA or BGRA.
+    pub fn supports(format: wgpu::TextureFormat) -> bool {
+        use wgpu::TextureFormat::*;
+
+        matches!(
+            format,
+            Rgba8Unorm | Rgba8UnormSrgb | Bgra8Unorm | Bgra8UnormSrgb
+        )
+    }
+
+    /// Records a copy of `texture`, which needs `COPY_SRC` usage and an 8-bit RGBA or BGRA
+    /// format.
+    pub fn copy(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) -> anyhow::Result<Self> {
+        use wgpu::TextureFormat::*;
+
+        let bgra = match texture.format() {
+            Rgba8Unorm | Rgba8UnormSrgb => false,
+            Bgra8Unorm | Bgra8UnormSrgb => true,
+            format => anyhow::bail!("frames can't be read back from {format:?} surfaces"),
+        };
+
+        let (width, height) = (texture.width(), texture.height());
+        let padded_bytes_per_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+
+        Ok(Self {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            bgra,
+        })
+    }
+
+    /// Waits for the copy, after its commands have been submitted, and returns it as RGBA.
+    pub fn read(self, device: &wgpu::Device) -> anyhow::Result<RgbaImage> {
+        let slice = self.buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::PollType::wait_indefinitely())?;
+        receiver.recv()??;
+
+        let mut pixels = Vec::with_capacity((self.width * self.height * 4) as usize);
+        {
+            let mapped = slice.get_mapped_range();
+            for row in mapped.chunks(self.padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..(self.width * 4) as usize]);
+            }
+        }
+        self.buffer.unmap();
+
+        if self.bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        RgbaImage::from_raw(self.width, self.height, pixels)
+            .context("readback is smaller than the frame")
+    }
+}
+
+/// Writes every nth frame to numbered PNGs or an ffmpeg process.
+pub(crate) struct FrameCapturer {
+    capture: FrameCapture,
+    frame: u64,
+    saved: u64,
+    ffmpeg: Option<(Child, u32, u32)>,
+}
+
+impl FrameCapturer {
+    pub fn new(capture: FrameCapture) -> anyhow::Result<Self> {
+        if let CaptureOutput::Png { directory } = &capture.output {
+            fs::create_dir_all(directory)
+                .with_context(|| format!("failed to create {}", directory.display()))?;
+        }
+
+        Ok(Self {
+            capture,
+            frame: 0,
+            saved: 0,
+            ffmpeg: None,
+        })
+    }
+
+    /// Simulation time per rendered frame, so kept frames are evenly spaced at the capture's
+    /// frame rate however long rendering and encoding take.
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_secs_f64(
+            1.0 / (self.capture.frame_rate.max(1) * self.capture.every_nth_frame.max(1)) as f64,
+        )
+    }
+
+    /// Counts a rendered frame and says whether it should be kept.
+    pub fn next_frame(&mut self) -> bool {
+        let keep = self
+            .frame
+            .is_multiple_of(self.capture.every_nth_frame.max(1) as u64);
+        self.frame += 1;
+        keep
+    }
+
+    pub fn write(&mut self, image: &RgbaImage) -> anyhow::Result<()> {
+        match &self.capture.output {
+            CaptureOutput::Png { directory } => {
+                let path = directory.join(format!("frame_{:06}.png", self.saved));
+                image
+                    .save(&path)
+                    .with_context(|| format!("failed to write {}", path.display()))?;
+            }
+            CaptureOutput::Ffmpeg { path } => {
+                if self.ffmpeg.is_none() {
+                    let child =
+                        spawn_ffmpeg(path, image.width(), image.height(), self.capture.frame_rate)?;
+                    self.ffmpeg = Some((child, image.width(), image.height()));
+                }
+                let (child, width, height) = self.ffmpeg.as_mut().unwrap();
+                if (*width, *height) != image.dimensions() {
+                    anyhow::bail!("the frame size changed while capturing to ffmpeg");
+                }
+                child
+                    .stdin
+                    .as_mut()
+                    .context("ffmpeg's input is closed")?
+                    .write_all(image.as_raw())
+                    .context("failed to send a frame to ffmpeg")?;
+            }
+        }
+
+        self.saved += 1;
+        Ok(())
+    }
+}
+
+impl Drop for FrameCapturer {
+    fn drop(&mut self) {
+        // Closing its input lets ffmpeg finish the file
+        if let Some((mut child, ..)) = self.ffmpeg.take() {
+            drop(child.stdin.take());
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Starts ffmpeg reading raw RGBA frames from its input and encoding them to `path`.
+fn spawn_ffmpeg(path: &Path, width: u32, height: u32, frame_rate: u32) -> anyhow::Result<Child> {
+    Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-f",
+            "rawvideo",
+            "-pixel_format",
+            "rgba",
+            "-video_size",
+        ])
+        .arg(format!("{width}x{height}"))
+        .arg("-framerate")
+        .arg(frame_rate.max(1).to_string())
+        .args(["-i", "-", "-pix_fmt", "yuv420p"])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("failed to start ffmpeg; is it installed and on the PATH?")
+}
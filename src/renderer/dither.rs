@@ -0,0 +1,163 @@
+use wgpu::util::DeviceExt;
+
+use crate::rng::Rng;
+
+/// Width and height of the tiled noise. Big enough that the repeat isn't noticeable.
+const SIZE: usize = 64;
+/// Spread of the filter used to find clusters and voids, in pixels
+const SIGMA: f32 = 1.5;
+/// How much of the pattern is set before it's relaxed into the starting point
+const INITIAL_FILL: usize = SIZE * SIZE / 10;
+
+/// Blue noise thresholds, one byte per pixel in row order, made with the void-and-cluster
+/// method. Every value is about equally common and close values sit far apart, so offsetting
+/// colors by it breaks up banding without the clumps white noise leaves.
+fn blue_noise() -> Vec<u8> {
+    let count = SIZE * SIZE;
+    // The filter wraps around the edges so the noise tiles seamlessly
+    let filter: Vec<f32> = (0..count)
+        .map(|i| {
+            let wrap = |d: usize| d.min(SIZE - d) as f32;
+            let (dx, dy) = (wrap(i % SIZE), wrap(i / SIZE));
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+
+    let mut pattern = Pattern {
+        set: vec![false; count],
+        energy: vec![0.0; count],
+        filter,
+    };
+    let mut rng = Rng::new(0x5eed);
+    let mut filled = 0;
+    while filled < INITIAL_FILL {
+        let pixel = rng.below(count as u32) as usize;
+        if !pattern.set[pixel] {
+            pattern.toggle(pixel);
+            filled += 1;
+        }
+    }
+    // Move the tightest cluster into the largest void until that stops changing anything
+    loop {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; count];
+    // Rank the starting points by taking them away tightest first...
+    let start = pattern.set.clone();
+    let start_energy = pattern.energy.clone();
+    for rank in (0..filled).rev() {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        ranks[cluster] = rank;
+    }
+    // ...then fill in the rest from there, largest void first
+    pattern.set = start;
+    pattern.energy = start_energy;
+    for rank in filled..count {
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        ranks[void] = rank;
+    }
+
+    ranks
+        .into_iter()
+        .map(|rank| (rank * 256 / count) as u8)
+        .collect()
+}
+
+/// A binary pattern and, per pixel, how crowded its neighbourhood is.
+struct Pattern {
+    set: Vec<bool>,
+    energy: Vec<f32>,
+    filter: Vec<f32>,
+}
+
+impl Pattern {
+    fn toggle(&mut self, pixel: usize) {
+        self.set[pixel] = !self.set[pixel];
+        let sign = if self.set[pixel] { 1.0 } else { -1.0 };
+        let (px, py) = (pixel % SIZE, pixel / SIZE);
+        for (i, energy) in self.energy.iter_mut().enumerate() {
+            let dx = (i % SIZE + SIZE - px) % SIZE;
+            let dy = (i / SIZE + SIZE - py) % SIZE;
+            *energy += sign * self.filter[dy * SIZE + dx];
+        }
+    }
+
+    /// The set pixel with the most set pixels around it.
+    fn tightest_cluster(&self) -> usize {
+        self.extreme(true, |a, b| a > b)
+    }
+
+    /// The unset pixel with the fewest set pixels around it.
+    fn largest_void(&self) -> usize {
+        self.extreme(false, |a, b| a < b)
+    }
+
+    fn extreme(&self, set: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+        let mut best: Option<usize> = None;
+        for (i, energy) in self.energy.iter().enumerate() {
+            if self.set[i] == set && best.is_none_or(|best| better(*energy, self.energy[best])) {
+                best = Some(i);
+            }
+        }
+        best.unwrap_or(0)
+    }
+}
+
+/// The noise the scene shaders offset colors by before they're stored at 8 bits per channel.
+pub(crate) struct Dither {
+    /// A single black texel when dithering is off, since the view bind group always needs one
+    pub noise: wgpu::TextureView,
+    enabled: bool,
+}
+
+impl Dither {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, enabled: bool) -> Self {
+        let (size, data) = if enabled {
+            (SIZE as u32, blue_noise())
+        } else {
+            (1, vec![0])
+        };
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("Blue Noise Texture"),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &data,
+        );
+        Self {
+            noise: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            enabled,
+        }
+    }
+
+    /// Whether to dither what's drawn to `format`. Float targets keep the gradients as they
+    /// are.
+    pub fn applies_to(&self, format: wgpu::TextureFormat) -> bool {
+        self.enabled
+            && !matches!(
+                format,
+                wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rgba32Float
+            )
+    }
+}
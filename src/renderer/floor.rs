@@ -0,0 +1,490 @@
+use std::collections::HashMap;
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    raycaster::{FlatSpriteInstance, FloorCamera},
+    renderer::{
+        Textures,
+        texture::{Texture, grid_uniform},
+    },
+};
+
+/// Flat sprites past this many per view aren't drawn.
+pub(crate) const MAX_FLAT_SPRITES: usize = 64;
+
+/// Per view camera, decals and wall columns for the floor pass.
+pub(crate) struct FloorViewBuffers {
+    camera_buffer: wgpu::Buffer,
+    flat_sprite_buffer: wgpu::Buffer,
+    /// Per screen column: the top and height of its wall, then its texture coordinate and
+    /// layer, for mirroring it in reflective floors
+    columns: wgpu::Texture,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl FloorViewBuffers {
+    pub fn write(
+        &self,
+        queue: &wgpu::Queue,
+        camera: &FloorCamera,
+        flat_sprites: &[FlatSpriteInstance],
+    ) {
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(camera));
+
+        let count = flat_sprites.len().min(MAX_FLAT_SPRITES);
+        queue.write_buffer(
+            &self.flat_sprite_buffer,
+            0,
+            bytemuck::cast_slice(&flat_sprites[..count]),
+        );
+    }
+
+    pub fn write_columns(&self, queue: &wgpu::Queue, columns: &[[f32; 4]]) {
+        let width = columns.len().min(self.columns.width() as usize);
+        queue.write_texture(
+            self.columns.as_image_copy(),
+            bytemuck::cast_slice(&columns[..width]),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: None,
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: width as u32,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+/// Casts the floor and ceiling of every pixel on the GPU, along with sprites lying on the
+/// floor. Runs before the walls, which cover it where they're closer.
+pub(crate) struct FloorPass {
+    texture_layout: wgpu::BindGroupLayout,
+    view_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    offscreen_pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    /// Bound in place of arrays a map doesn't have
+    pub blank_array: Texture,
+}
+
+impl FloorPass {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        view_bind_group_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        offscreen_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let texture_array_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2Array,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let unfilterable_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Floor texture bind group layout"),
+            entries: &[
+                texture_array_entry(0),
+                texture_array_entry(1),
+                texture_array_entry(2),
+                texture_array_entry(6),
+                texture_array_entry(8),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                unfilterable_entry(7),
+                uniform_entry(9),
+            ],
+        });
+
+        let view_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Floor view bind group layout"),
+            entries: &[uniform_entry(0), uniform_entry(1), unfilterable_entry(2)],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("floor.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Floor Pipeline Layout"),
+            bind_group_layouts: &[&texture_layout, view_bind_group_layout, &view_layout],
+            immediate_size: 0,
+        });
+
+        let create_pipeline = |format, sample_count| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Floor Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview_mask: None,
+                cache: Default::default(),
+            })
+        };
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Floor Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            pipeline: create_pipeline(format, sample_count),
+            offscreen_pipeline: create_pipeline(offscreen_format, 1),
+            texture_layout,
+            view_layout,
+            sampler,
+            blank_array: Texture::from_image_array(device, queue, Vec::new(), 1, "Blank Array")
+                .unwrap()
+                .unwrap(),
+        }
+    }
+
+    /// Uploads which floor and ceiling layer each tile uses.
+    pub fn create_tile_layers(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tiles: &[Vec<u8>],
+        floor_image_map: &HashMap<usize, usize>,
+        ceiling_image_map: &HashMap<usize, usize>,
+        wall_image_map: &HashMap<usize, usize>,
+    ) -> Texture {
+        // Layer + 1, so 0 means the tile has none. The top bit marks solid tiles.
+        let layers: Vec<u32> = tiles
+            .iter()
+            .flatten()
+            .map(|tile_id| {
+                let floor = floor_image_map
+                    .get(&(*tile_id as usize))
+                    .map_or(0, |l| l + 1);
+                let ceiling = ceiling_image_map
+                    .get(&(*tile_id as usize))
+                    .map_or(0, |l| l + 1);
+                let solid = if wall_image_map.contains_key(&(*tile_id as usize)) {
+                    1 << 31
+                } else {
+                    0
+                };
+                (floor | ceiling << 16) as u32 | solid
+            })
+            .collect();
+
+        tile_texture(
+            device,
+            queue,
+            tiles,
+            wgpu::TextureFormat::R32Uint,
+            bytemuck::cast_slice(&layers),
+            "Tile Layers",
+        )
+    }
+
+    /// Uploads the floor plane and ceiling height of each tile.
+    pub fn create_tile_heights(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tiles: &[Vec<u8>],
+        tile_heights: &[[f32; 4]],
+    ) -> Texture {
+        tile_texture(
+            device,
+            queue,
+            tiles,
+            wgpu::TextureFormat::Rgba32Float,
+            bytemuck::cast_slice(tile_heights),
+            "Tile Heights",
+        )
+    }
+
+    /// Uploads how strongly each tile's floor mirrors the walls.
+    pub fn create_tile_reflections(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tiles: &[Vec<u8>],
+        tile_reflections: &[u8],
+    ) -> Texture {
+        tile_texture(
+            device,
+            queue,
+            tiles,
+            wgpu::TextureFormat::R8Unorm,
+            tile_reflections,
+            "Tile Reflections",
+        )
+    }
+
+    pub fn create_map_bind_group(
+        &self,
+        device: &wgpu::Device,
+        textures: &Textures,
+        tile_layers: &Texture,
+        tile_heights: &Texture,
+        tile_reflections: &Texture,
+    ) -> wgpu::BindGroup {
+        let blank = &self.blank_array;
+        let walls = textures.wall_texture_arr.as_ref().unwrap_or(blank);
+        let floor = textures.floor_texture_arr.as_ref().unwrap_or(blank);
+        let ceiling = textures.ceiling_texture_arr.as_ref().unwrap_or(blank);
+        let sprites = textures.sprite_texture_arr.as_ref().unwrap_or(blank);
+        let sky = textures.sky_texture_arr.as_ref().unwrap_or(blank);
+        let grids = grid_uniform(
+            device,
+            [floor.grid, ceiling.grid, sprites.grid, walls.grid],
+            "Floor Grid Buffer",
+        );
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Floor Bind Group"),
+            layout: &self.texture_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&floor.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&ceiling.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&sprites.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&tile_layers.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&tile_heights.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&sky.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::TextureView(&tile_reflections.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::TextureView(&walls.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: grids.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    pub fn create_view_buffers(
+        &self,
+        device: &wgpu::Device,
+        width: u32,
+        label: &str,
+    ) -> FloorViewBuffers {
+        let camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{label} Floor Camera Buffer")),
+            size: std::mem::size_of::<FloorCamera>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let flat_sprite_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{label} Flat Sprite Buffer")),
+            size: (std::mem::size_of::<FlatSpriteInstance>() * MAX_FLAT_SPRITES)
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let columns = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&format!("{label} Wall Columns")),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{label} Floor View Bind Group")),
+            layout: &self.view_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: flat_sprite_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        &columns.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+            ],
+        });
+
+        FloorViewBuffers {
+            camera_buffer,
+            flat_sprite_buffer,
+            columns,
+            bind_group,
+        }
+    }
+
+    /// Records the pass into an open render pass. `offscreen` picks the pipeline for monitor
+    /// targets.
+    pub fn draw(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        offscreen: bool,
+        map_bind_group: &wgpu::BindGroup,
+        view_bind_group: &wgpu::BindGroup,
+        floor_view: &FloorViewBuffers,
+    ) {
+        let pipeline = if offscreen {
+            &self.offscreen_pipeline
+        } else {
+            &self.pipeline
+        };
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, map_bind_group, &[]);
+        render_pass.set_bind_group(1, view_bind_group, &[]);
+        render_pass.set_bind_group(2, &floor_view.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// A texture with one texel per map tile.
+pub(crate) fn tile_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    tiles: &[Vec<u8>],
+    format: wgpu::TextureFormat,
+    data: &[u8],
+    label: &str,
+) -> Texture {
+    let size = wgpu::Extent3d {
+        width: tiles.first().map_or(0, |row| row.len()).max(1) as u32,
+        height: tiles.len().max(1) as u32,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        data,
+    );
+
+    Texture {
+        view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+        sampler: device.create_sampler(&wgpu::SamplerDescriptor::default()),
+        texture,
+        grid: 1,
+    }
+}
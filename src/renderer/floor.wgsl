@@ -0,0 +1,465 @@
+@group(0) @binding(0) var floor_atlas: texture_2d_array<f32>;
+@group(0) @binding(1) var ceiling_atlas: texture_2d_array<f32>;
+@group(0) @binding(2) var sprite_atlas: texture_2d_array<f32>;
+@group(0) @binding(3) var atlas_sampler: sampler;
+// Per tile: floor layer + 1 in the low 16 bits, ceiling layer + 1 in the next 15 and whether
+// the tile is solid in the top bit
+@group(0) @binding(4) var tile_layers: texture_2d<u32>;
+// Per tile: floor height at the tile's top left corner, then how much it rises across the tile
+// in x and in y, then the ceiling height. Zero for flat tiles.
+@group(0) @binding(5) var tile_heights: texture_2d<f32>;
+// Wrapped once around the view above outdoor tiles
+@group(0) @binding(6) var sky_atlas: texture_2d_array<f32>;
+// Per tile: how strongly its floor mirrors the walls
+@group(0) @binding(7) var tile_reflections: texture_2d<f32>;
+@group(0) @binding(8) var wall_atlas: texture_2d_array<f32>;
+
+// Images per side in each layer of the texture arrays
+struct AtlasGrids {
+    floor: u32,
+    ceiling: u32,
+    sprite: u32,
+    wall: u32,
+}
+
+@group(0) @binding(9) var<uniform> grids: AtlasGrids;
+
+struct ViewUniform {
+    screen_size: vec2f,
+    // Set when the target format doesn't gamma encode on write
+    encode_srgb: u32,
+    // Set when colors should be dithered before the target quantizes them
+    dither: u32,
+    // Multiplies everything but the sky, for the time of day
+    light: vec3f,
+    // How much walls, floors and ceilings darken where they meet
+    ambient_occlusion: f32,
+};
+
+@group(1) @binding(0) var<uniform> view: ViewUniform;
+// Tiled blue noise, one threshold per pixel
+@group(1) @binding(1) var blue_noise: texture_2d<f32>;
+
+struct FloorCamera {
+    position: vec2f,
+    // Radians
+    rotation: f32,
+    fov: f32,
+    height: f32,
+    // Screen row of the horizon
+    horizon: f32,
+    dist_to_projection_plane: f32,
+    tile_size: f32,
+    wall_height: f32,
+    flat_sprite_count: u32,
+    // Above zero, floor within this distance of a solid tile is highlighted
+    collision_radius: f32,
+    // The tallest step or ramp, or 0 when the floor is flat everywhere
+    max_floor_height: f32,
+    // Set when some tiles have lowered ceilings or are open to the sky
+    ceilings_vary: u32,
+    has_sky: u32,
+    // Fog starts and ends at these distances. Both are 0 without fog.
+    fog_start: f32,
+    fog_end: f32,
+    fog_color: vec3f,
+    // Seconds since the engine started, which ripples reflective floors
+    time: f32,
+    // Multiplies the sky and clouds
+    sky_tint: vec3f,
+    cloud_count: u32,
+    // Per cloud layer: how far it's drifted, then its opacity
+    clouds: array<vec4f, MAX_CLOUD_LAYERS>,
+};
+
+const MAX_CLOUD_LAYERS: u32 = 3u;
+
+struct FlatSprite {
+    position: vec2f,
+    half_size: f32,
+    opacity: f32,
+    layer: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+const MAX_FLAT_SPRITES: u32 = 64u;
+
+@group(2) @binding(0) var<uniform> camera: FloorCamera;
+@group(2) @binding(1) var<uniform> flat_sprites: array<FlatSprite, MAX_FLAT_SPRITES>;
+// Per screen column: the top and height of its wall, then its texture coordinate and layer
+@group(2) @binding(2) var wall_columns: texture_2d<f32>;
+
+// Shown where there's no floor or ceiling tile
+const BACKGROUND: vec3f = vec3f(0.1, 0.2, 0.3);
+const COLLISION_COLOR: vec3f = vec3f(1.0, 0.1, 0.1);
+const SOLID_BIT: u32 = 0x80000000u;
+// Tiles a floor ray walks through looking for a step before settling for the flat floor
+const MAX_FLOOR_STEPS: i32 = 64;
+// How much darker the sides of steps and the edges of lowered ceilings are
+const RISER_SHADE: f32 = 0.6;
+// Ceiling heights standing in for the wall height and for no ceiling at all
+const DEFAULT_CEILING: f32 = -1.0;
+const OPEN_SKY: f32 = 0.0;
+const TAU: f32 = 6.283185307;
+// How far reflections ripple up and down, as a fraction of the mirrored wall's height
+const RIPPLE: f32 = 0.02;
+// How far contact shadows reach from the bottom and top of the walls, as a fraction of their
+// height on screen
+const OCCLUSION_EXTENT: f32 = 0.15;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+// A single triangle that covers the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2f(f32((index << 1u) & 2u), f32(index & 2u));
+
+    out.clip_position = vec4f(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+
+    return out;
+}
+
+fn linear_to_srgb(color: vec3f) -> vec3f {
+    let lower = color * 12.92;
+    let higher = 1.055 * pow(color, vec3f(1.0 / 2.4)) - 0.055;
+    return select(higher, lower, color <= vec3f(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3f) -> vec3f {
+    let lower = color / 12.92;
+    let higher = pow((color + 0.055) / 1.055, vec3f(2.4));
+    return select(higher, lower, color <= vec3f(0.04045));
+}
+
+// Nudges the color by up to half a step of 8 bit output either way, in gamma space where the
+// steps are, so gradients come out as fine noise rather than bands
+fn dither(color: vec3f, pixel: vec2f) -> vec3f {
+    if view.dither == 0u {
+        return color;
+    }
+    let noise = textureLoad(blue_noise, vec2u(pixel) % textureDimensions(blue_noise), 0).r;
+    let encoded = linear_to_srgb(max(color, vec3f(0.0))) + (noise - 0.5) / 255.0;
+    return srgb_to_linear(max(encoded, vec3f(0.0)));
+}
+
+fn encode(color: vec3f, pixel: vec2f) -> vec4f {
+    let dithered = dither(color, pixel);
+    if view.encode_srgb != 0u {
+        return vec4f(linear_to_srgb(dithered), 1.0);
+    }
+    return vec4f(dithered, 1.0);
+}
+
+// Arrays with more images than the GPU allows layers pack them `grid` to a side in each
+// layer. Coordinates stay half a texel inside the image so filtering can't reach the next one.
+fn atlas_uv(uv: vec2f, index: u32, grid: u32, size: vec2u) -> vec2f {
+    let cell = index % (grid * grid);
+    let inset = 0.5 * f32(grid) / vec2f(size);
+    let inner = clamp(uv, inset, vec2f(1.0) - inset);
+    return (vec2f(f32(cell % grid), f32(cell / grid)) + inner) / f32(grid);
+}
+
+fn atlas_layer(index: u32, grid: u32) -> u32 {
+    return index / (grid * grid);
+}
+
+fn sample_atlas(atlas: texture_2d_array<f32>, uv: vec2f, index: u32, grid: u32) -> vec4f {
+    let size = textureDimensions(atlas);
+    return textureSampleLevel(atlas, atlas_sampler, atlas_uv(uv, index, grid, size), atlas_layer(index, grid), 0.0);
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = input.clip_position.xy;
+    let rows_from_horizon = pixel.y - camera.horizon;
+    if abs(rows_from_horizon) < 0.5 {
+        return encode(BACKGROUND, pixel);
+    }
+
+    // Columns are evenly spaced in angle, like the rays
+    let column_angle = (pixel.x / view.screen_size.x) * camera.fov - camera.fov / 2.0;
+    let angle = camera.rotation + column_angle;
+
+    // Invert the wall projection: the row a wall's bottom (or top) lands on gives its distance
+    let is_floor = rows_from_horizon > 0.0;
+    var plane_height = camera.height;
+    if !is_floor {
+        plane_height = camera.wall_height - camera.height;
+    }
+    let dist = camera.dist_to_projection_plane * plane_height / abs(rows_from_horizon);
+    let direction = vec2f(cos(angle), sin(angle));
+    let map_size = vec2i(textureDimensions(tile_layers));
+
+    // How far the line of sight drops per world unit along the ray
+    let drop = rows_from_horizon * cos(column_angle) / camera.dist_to_projection_plane;
+    var hit = PlaneHit(dist / cos(column_angle), -1, 0.0, false);
+    if is_floor && camera.max_floor_height > 0.0 {
+        hit = march_floor(direction, drop, map_size);
+    } else if !is_floor && camera.ceilings_vary != 0u {
+        hit = march_ceiling(direction, -drop, map_size);
+    }
+    if hit.sky {
+        if camera.has_sky == 0u {
+            return encode(BACKGROUND, pixel);
+        }
+        // The horizon is halfway down the image
+        let sky_uv = vec2f(fract(angle / TAU), 0.5 + rows_from_horizon / view.screen_size.y);
+        var sky = textureSampleLevel(sky_atlas, atlas_sampler, sky_uv, 0, 0.0).rgb;
+        // Cloud layers follow the sky in the atlas, drawn bottom up
+        for (var i = 0u; i < min(camera.cloud_count, MAX_CLOUD_LAYERS); i++) {
+            let cloud = camera.clouds[i];
+            let cloud_uv = vec2f(fract(sky_uv.x + cloud.x), sky_uv.y);
+            let texel = textureSampleLevel(sky_atlas, atlas_sampler, cloud_uv, i + 1u, 0.0);
+            sky = mix(sky, texel.rgb, texel.a * cloud.y);
+        }
+        return encode(sky * camera.sky_tint, pixel);
+    }
+    let world = camera.position + direction * hit.distance;
+    let fog = fog_amount(hit.distance);
+
+    var tile = vec2i(floor(world / camera.tile_size));
+    if hit.riser_axis >= 0 {
+        // The boundary belongs to the tile being stepped onto
+        tile = vec2i(floor(world / camera.tile_size + direction * 0.001));
+    }
+    if any(tile < vec2i(0)) || any(tile >= map_size) {
+        return encode(BACKGROUND, pixel);
+    }
+
+    let layers = textureLoad(tile_layers, tile, 0).r;
+    var tile_uv = fract(world / camera.tile_size);
+    if hit.riser_axis == 0 {
+        tile_uv = fract(vec2f(world.y, hit.height) / camera.tile_size);
+    } else if hit.riser_axis == 1 {
+        tile_uv = fract(vec2f(world.x, hit.height) / camera.tile_size);
+    }
+    var color = BACKGROUND;
+    if is_floor {
+        let layer = layers & 0xffffu;
+        if layer != 0u {
+            color = sample_atlas(floor_atlas, tile_uv, layer - 1u, grids.floor).rgb;
+        }
+        if hit.riser_axis >= 0 {
+            return encode(mix(color * RISER_SHADE * view.light, camera.fog_color, fog), pixel);
+        }
+
+        let reflection = textureLoad(tile_reflections, tile, 0).r;
+        if reflection > 0.0 {
+            color = reflect_wall(color, reflection, world, pixel);
+        }
+
+        if camera.collision_radius > 0.0 && near_solid(world, map_size) {
+            color = mix(color, COLLISION_COLOR, 0.4);
+        }
+
+        // Decals lie on the floor, drawn in map order
+        for (var i = 0u; i < min(camera.flat_sprite_count, MAX_FLAT_SPRITES); i++) {
+            let sprite = flat_sprites[i];
+            let offset = world - sprite.position;
+            if all(abs(offset) < vec2f(sprite.half_size)) {
+                let uv = offset / (sprite.half_size * 2.0) + 0.5;
+                let texel = sample_atlas(sprite_atlas, uv, sprite.layer, grids.sprite);
+                color = mix(color, texel.rgb, texel.a * sprite.opacity);
+            }
+        }
+    } else {
+        let layer = (layers >> 16u) & 0x7fffu;
+        if layer != 0u {
+            color = sample_atlas(ceiling_atlas, tile_uv, layer - 1u, grids.ceiling).rgb;
+        }
+        if hit.riser_axis >= 0 {
+            color *= RISER_SHADE;
+        }
+    }
+
+    color *= occlusion(pixel);
+    return encode(mix(color * view.light, camera.fog_color, fog), pixel);
+}
+
+// Darkens floor and ceiling close to the column's wall, fading out as they get further from it
+fn occlusion(pixel: vec2f) -> f32 {
+    if view.ambient_occlusion <= 0.0 {
+        return 1.0;
+    }
+    let column = textureLoad(wall_columns, vec2u(u32(pixel.x), 0u), 0);
+    let height = column.y;
+    if height <= 0.0 {
+        return 1.0;
+    }
+    let gap = max(pixel.y - (column.x + height), column.x - pixel.y) / height;
+    return 1.0 - view.ambient_occlusion * (1.0 - smoothstep(0.0, OCCLUSION_EXTENT, gap));
+}
+
+// Mirrors the column's wall about its bottom edge, rippling it and fading it out further below
+fn reflect_wall(color: vec3f, reflection: f32, world: vec2f, pixel: vec2f) -> vec3f {
+    let column = textureLoad(wall_columns, vec2u(u32(pixel.x), 0u), 0);
+    let top = column.x;
+    let height = column.y;
+    if height <= 0.0 {
+        return color;
+    }
+    let bottom = top + height;
+    let ripple = sin(dot(world, vec2f(0.15, 0.11)) + camera.time * 3.0) * RIPPLE * height;
+    let mirrored = 2.0 * bottom - pixel.y + ripple;
+    if mirrored < top || mirrored > bottom {
+        return color;
+    }
+    let v = (mirrored - top) / height;
+    let texel = sample_atlas(wall_atlas, vec2f(column.z, v), u32(column.w), grids.wall);
+    let fade = clamp(1.0 - (pixel.y - bottom) / height, 0.0, 1.0);
+    return mix(color, texel.rgb, reflection * fade);
+}
+
+// How much of the fog color covers something this far away
+fn fog_amount(distance: f32) -> f32 {
+    if camera.fog_end <= camera.fog_start {
+        return 0.0;
+    }
+    return clamp((distance - camera.fog_start) / (camera.fog_end - camera.fog_start), 0.0, 1.0);
+}
+
+struct PlaneHit {
+    // Along the ray from the camera
+    distance: f32,
+    // -1 on the floor or ceiling itself, otherwise the axis of the step side or ceiling edge
+    // the ray hit
+    riser_axis: i32,
+    // Height of the point hit on a step side or ceiling edge
+    height: f32,
+    // The ray rose above the walls without hitting a ceiling
+    sky: bool,
+};
+
+// The floor height of a tile at a point
+fn floor_height(tile: vec2i, world: vec2f) -> f32 {
+    let plane = textureLoad(tile_heights, tile, 0).xyz;
+    let uv = world / camera.tile_size - vec2f(tile);
+    return plane.x + plane.y * uv.x + plane.z * uv.y;
+}
+
+// Walks the tiles along a floor pixel's ray until the line of sight, falling `drop` per world
+// unit, goes under a tile's floor. Each tile's floor is a plane, so the crossing within it is
+// found from the heights where the ray enters and leaves. Where the line of sight is already
+// under the floor on entering, it hit the side of a step.
+fn march_floor(direction: vec2f, drop: f32, map_size: vec2i) -> PlaneHit {
+    let flat = camera.height / drop;
+    // Nothing rises above the tallest step, so start where the line of sight comes down to it
+    var along = max(0.0, (camera.height - camera.max_floor_height) / drop);
+    let start = camera.position + direction * along;
+
+    var tile = vec2i(floor(start / camera.tile_size));
+    let step = vec2i(sign(direction));
+    let inverse = 1.0 / max(abs(direction), vec2f(1e-6));
+    let next_edge = (vec2f(tile) + select(vec2f(0.0), vec2f(1.0), direction > vec2f(0.0))) * camera.tile_size;
+    var next_along = along + abs(next_edge - start) * inverse;
+    let along_delta = camera.tile_size * inverse;
+    var entered_axis = -1;
+
+    for (var i = 0; i < MAX_FLOOR_STEPS && along < flat; i++) {
+        let exit = min(min(next_along.x, next_along.y), flat);
+        if all(tile >= vec2i(0)) && all(tile < map_size) {
+            let entry_height = floor_height(tile, camera.position + direction * along);
+            let entry_gap = camera.height - drop * along - entry_height;
+            if entry_gap < 0.0 {
+                return PlaneHit(along, entered_axis, camera.height - drop * along, false);
+            }
+            let exit_gap = camera.height - drop * exit - floor_height(tile, camera.position + direction * exit);
+            if exit_gap <= 0.0 {
+                return PlaneHit(mix(along, exit, entry_gap / (entry_gap - exit_gap)), -1, 0.0, false);
+            }
+        }
+
+        along = exit;
+        if next_along.x < next_along.y {
+            next_along.x += along_delta.x;
+            tile.x += step.x;
+            entered_axis = 0;
+        } else {
+            next_along.y += along_delta.y;
+            tile.y += step.y;
+            entered_axis = 1;
+        }
+    }
+
+    return PlaneHit(flat, -1, 0.0, false);
+}
+
+// Walks the tiles along a ceiling pixel's ray until the line of sight, rising `rise` per world
+// unit, reaches a tile's ceiling. Where it's already above the ceiling on entering, it hit the
+// edge of a lowered ceiling. If it gets above the walls first, it can only be looking out at
+// the sky.
+fn march_ceiling(direction: vec2f, rise: f32, map_size: vec2i) -> PlaneHit {
+    let above_walls = (camera.wall_height - camera.height) / rise;
+    var along = 0.0;
+
+    var tile = vec2i(floor(camera.position / camera.tile_size));
+    let step = vec2i(sign(direction));
+    let inverse = 1.0 / max(abs(direction), vec2f(1e-6));
+    let next_edge = (vec2f(tile) + select(vec2f(0.0), vec2f(1.0), direction > vec2f(0.0))) * camera.tile_size;
+    var next_along = abs(next_edge - camera.position) * inverse;
+    let along_delta = camera.tile_size * inverse;
+    var entered_axis = -1;
+
+    for (var i = 0; i < MAX_FLOOR_STEPS && along < above_walls; i++) {
+        let exit = min(min(next_along.x, next_along.y), above_walls);
+        if all(tile >= vec2i(0)) && all(tile < map_size) {
+            let ceiling = textureLoad(tile_heights, tile, 0).w;
+            if ceiling != OPEN_SKY {
+                var height = min(ceiling, camera.wall_height);
+                if ceiling == DEFAULT_CEILING {
+                    height = camera.wall_height;
+                }
+                let entry_height = camera.height + rise * along;
+                if entry_height > height {
+                    return PlaneHit(along, entered_axis, entry_height, false);
+                }
+                if camera.height + rise * exit >= height {
+                    return PlaneHit((height - camera.height) / rise, -1, 0.0, false);
+                }
+            }
+        }
+
+        along = exit;
+        if next_along.x < next_along.y {
+            next_along.x += along_delta.x;
+            tile.x += step.x;
+            entered_axis = 0;
+        } else {
+            next_along.y += along_delta.y;
+            tile.y += step.y;
+            entered_axis = 1;
+        }
+    }
+
+    if along >= above_walls {
+        return PlaneHit(above_walls, -1, 0.0, true);
+    }
+    return PlaneHit(above_walls, -1, 0.0, false);
+}
+
+// Whether a circle of `collision_radius` at `world` overlaps a solid tile
+fn near_solid(world: vec2f, map_size: vec2i) -> bool {
+    let tile = vec2i(floor(world / camera.tile_size));
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbor = tile + vec2i(x, y);
+            if any(neighbor < vec2i(0)) || any(neighbor >= map_size) {
+                continue;
+            }
+            if (textureLoad(tile_layers, neighbor, 0).r & SOLID_BIT) == 0u {
+                continue;
+            }
+            let min_corner = vec2f(neighbor) * camera.tile_size;
+            let closest = clamp(world, min_corner, min_corner + camera.tile_size);
+            if distance(world, closest) < camera.collision_radius {
+                return true;
+            }
+        }
+    }
+    return false;
+}
@@ -0,0 +1,410 @@
+use std::sync::{Arc, mpsc};
+
+use anyhow::Context;
+use image::DynamicImage;
+
+use crate::{
+    assets::{self, AssetSource},
+    config::ColorSpace,
+    engine::AssetEvent,
+    map::{MAX_CLOUD_LAYERS, Map, TileType},
+    renderer::TextureCategory,
+};
+
+/// Stands in for a ceiling height where the ceiling is at the wall height.
+const DEFAULT_CEILING: f32 = -1.0;
+/// Stands in for a ceiling height where there's no ceiling.
+const OPEN_SKY: f32 = 0.0;
+
+/// One image to fetch and decode.
+struct TextureJob {
+    category: TextureCategory,
+    tile_id: u8,
+    path: &'static str,
+}
+
+/// A decoded image and what it's for.
+#[derive(Clone)]
+pub(crate) struct LoadedImage {
+    pub category: TextureCategory,
+    /// Unused for sprites, which are looked up by path
+    pub tile_id: u8,
+    pub path: &'static str,
+    /// Empty if it failed to load, to be filled with a placeholder the size of the others
+    pub image: DynamicImage,
+}
+
+/// A map's decoded images, ready to be uploaded.
+#[derive(Clone)]
+pub(crate) struct LoadedImages {
+    /// In the order the jobs were created
+    pub images: Vec<LoadedImage>,
+    pub monitor_tile_ids: Vec<u8>,
+    /// The map's tile ids, for looking up floor and ceiling layers per tile
+    pub tiles: Vec<Vec<u8>>,
+    /// Each tile's floor plane, as in [`SlopeData::plane`](crate::map::SlopeData), then its
+    /// ceiling height, [`DEFAULT_CEILING`] or [`OPEN_SKY`], row by row
+    pub tile_heights: Vec<[f32; 4]>,
+    /// How strongly each tile's floor mirrors the walls, row by row
+    pub tile_reflections: Vec<u8>,
+    /// The images that failed to load
+    pub failed: Vec<AssetEvent>,
+}
+
+/// Fetches and decodes a map's textures without blocking the event loop. Native builds use a
+/// small pool of worker threads; the web build decodes inside a spawned future.
+pub(crate) struct TextureLoader {
+    jobs: Vec<TextureJob>,
+    images: Vec<Option<DynamicImage>>,
+    loaded: usize,
+    monitor_tile_ids: Vec<u8>,
+    tiles: Vec<Vec<u8>>,
+    tile_heights: Vec<[f32; 4]>,
+    tile_reflections: Vec<u8>,
+    failed: Vec<AssetEvent>,
+    /// How the images are encoded. Linear ones are gamma encoded as they arrive, to match the
+    /// sRGB texture arrays.
+    color_space: ColorSpace,
+    receiver: mpsc::Receiver<(usize, anyhow::Result<DynamicImage>)>,
+}
+
+impl TextureLoader {
+    /// Starts loading the map's textures, with `preload_sprites` alongside its own sprites.
+    pub fn start(
+        map: &Map,
+        preload_sprites: &[&'static str],
+        assets: Arc<dyn AssetSource>,
+        color_space: ColorSpace,
+    ) -> Self {
+        let (jobs, monitor_tile_ids) = texture_jobs(map, preload_sprites);
+
+        let (sender, receiver) = mpsc::channel();
+        let paths: Vec<&'static str> = jobs.iter().map(|job| job.path).collect();
+        spawn_workers(paths, assets, sender);
+
+        Self {
+            images: jobs.iter().map(|_| None).collect(),
+            jobs,
+            loaded: 0,
+            monitor_tile_ids,
+            tiles: map.tiles().clone(),
+            tile_heights: tile_heights(map),
+            tile_reflections: tile_reflections(map),
+            failed: Vec::new(),
+            color_space,
+            receiver,
+        }
+    }
+
+    /// Fraction of images loaded, from 0.0 to 1.0.
+    pub fn progress(&self) -> f32 {
+        if self.jobs.is_empty() {
+            return 1.0;
+        }
+        self.loaded as f32 / self.jobs.len() as f32
+    }
+
+    /// Collects finished images without blocking. Returns every image once all have arrived.
+    pub fn poll(&mut self) -> Option<LoadedImages> {
+        while let Ok((index, image)) = self.receiver.try_recv() {
+            let job = &self.jobs[index];
+            let result = image.with_context(|| match job.category {
+                TextureCategory::Wall
+                | TextureCategory::Floor
+                | TextureCategory::Ceiling
+                | TextureCategory::EmissiveMask
+                | TextureCategory::NormalMap => format!(
+                    "failed to load {} texture {} for tile id {}",
+                    job.category.name(),
+                    job.path,
+                    job.tile_id
+                ),
+                _ => format!(
+                    "failed to load {} texture {}",
+                    job.category.name(),
+                    job.path
+                ),
+            });
+            let image = match result {
+                Ok(image) => image,
+                Err(error) => {
+                    self.failed.push(AssetEvent {
+                        path: job.path,
+                        error: format!("{error:#}"),
+                    });
+                    DynamicImage::new_rgba8(0, 0)
+                }
+            };
+            self.images[index] = Some(match self.color_space {
+                ColorSpace::Linear if job.category.holds_color() => encode_linear(image),
+                _ => image,
+            });
+            self.loaded += 1;
+        }
+
+        if self.loaded < self.jobs.len() {
+            return None;
+        }
+
+        let images = self
+            .jobs
+            .drain(..)
+            .zip(self.images.drain(..))
+            .map(|(job, image)| LoadedImage {
+                category: job.category,
+                tile_id: job.tile_id,
+                path: job.path,
+                image: image.unwrap(),
+            })
+            .collect();
+
+        Some(LoadedImages {
+            images,
+            monitor_tile_ids: std::mem::take(&mut self.monitor_tile_ids),
+            tiles: std::mem::take(&mut self.tiles),
+            tile_heights: std::mem::take(&mut self.tile_heights),
+            tile_reflections: std::mem::take(&mut self.tile_reflections),
+            failed: std::mem::take(&mut self.failed),
+        })
+    }
+}
+
+/// Every image the map's textures are built from, and its monitors' tile ids.
+fn texture_jobs(map: &Map, preload_sprites: &[&'static str]) -> (Vec<TextureJob>, Vec<u8>) {
+    let mut jobs = Vec::new();
+    let mut monitor_tile_ids = Vec::new();
+
+    for (k, v) in map.tile_types() {
+        let mut push = |category, path| {
+            jobs.push(TextureJob {
+                category,
+                tile_id: *k,
+                path,
+            })
+        };
+
+        match v {
+            TileType::Wall(data) => {
+                push(TextureCategory::Wall, data.texture_path);
+                if let Some(path) = data.emissive_mask_path {
+                    push(TextureCategory::EmissiveMask, path);
+                }
+                if let Some(path) = data.normal_map_path {
+                    push(TextureCategory::NormalMap, path);
+                }
+            }
+            TileType::Floor(data) => push(TextureCategory::Floor, data.texture_path),
+            TileType::Ceiling(data) => push(TextureCategory::Ceiling, data.texture_path),
+            TileType::FloorCeiling(data) => {
+                push(TextureCategory::Floor, data.texture_path_f);
+                push(TextureCategory::Ceiling, data.texture_path_c);
+            }
+            TileType::Water(data) => push(TextureCategory::Floor, data.texture_path),
+            TileType::Hazard(data) => push(TextureCategory::Floor, data.texture_path),
+            TileType::Surface(data) => push(TextureCategory::Floor, data.texture_path),
+            TileType::Elevator(data) => push(TextureCategory::Floor, data.texture_path),
+            TileType::Slope(data) => push(TextureCategory::Floor, data.texture_path),
+            TileType::Outdoor(data) => push(TextureCategory::Floor, data.texture_path),
+            TileType::ThinWall(data) => {
+                push(TextureCategory::Wall, data.texture_path);
+                if let Some(path) = data.floor_texture_path {
+                    push(TextureCategory::Floor, path);
+                }
+            }
+            TileType::Monitor(_) => monitor_tile_ids.push(*k),
+            TileType::Empty => (),
+        }
+    }
+
+    // Sprites sharing an image share a layer
+    for path in sprite_paths(map).chain(preload_sprites.iter().copied()) {
+        if !jobs
+            .iter()
+            .any(|job| job.category == TextureCategory::Sprite && job.path == path)
+        {
+            jobs.push(TextureJob {
+                category: TextureCategory::Sprite,
+                tile_id: 0,
+                path,
+            });
+        }
+    }
+
+    // Linedefs sharing an image share a layer
+    for linedef in map.linedefs() {
+        if !jobs
+            .iter()
+            .any(|job| job.category == TextureCategory::Linedef && job.path == linedef.texture_path)
+        {
+            jobs.push(TextureJob {
+                category: TextureCategory::Linedef,
+                tile_id: 0,
+                path: linedef.texture_path,
+            });
+        }
+    }
+
+    if let Some(path) = map.sky_texture_path() {
+        jobs.push(TextureJob {
+            category: TextureCategory::Sky,
+            tile_id: 0,
+            path,
+        });
+        // Drawn over the sky from the layers after it
+        for cloud in map.metadata().clouds().iter().take(MAX_CLOUD_LAYERS) {
+            jobs.push(TextureJob {
+                category: TextureCategory::Sky,
+                tile_id: 0,
+                path: cloud.texture_path(),
+            });
+        }
+    }
+
+    (jobs, monitor_tile_ids)
+}
+
+/// The sprite images a map uses itself: its sprites', entities', entity definitions' and
+/// weather's. Paths can repeat.
+pub(crate) fn sprite_paths(map: &Map) -> impl Iterator<Item = &'static str> + '_ {
+    let sprite_paths = map.sprites().iter().map(|sprite| sprite.texture_path);
+    let entity_paths = map
+        .entities()
+        .iter()
+        .filter_map(|entity| entity.texture_path);
+    let entity_def_paths = map
+        .entity_defs()
+        .iter()
+        .chain(map.spawners().iter().map(|spawner| &spawner.entity))
+        .filter_map(|def| def.texture_path);
+    let weather_path = map
+        .metadata()
+        .weather()
+        .map(|weather| weather.texture_path());
+    sprite_paths
+        .chain(entity_paths)
+        .chain(entity_def_paths)
+        .chain(weather_path)
+}
+
+/// The path of every image a map uses, once each.
+pub(crate) fn texture_paths(map: &Map) -> Vec<&'static str> {
+    let mut paths: Vec<&'static str> = texture_jobs(map, &[])
+        .0
+        .into_iter()
+        .map(|job| job.path)
+        .collect();
+    paths.sort_unstable();
+    paths.dedup();
+    paths
+}
+
+fn tile_heights(map: &Map) -> Vec<[f32; 4]> {
+    map.tiles()
+        .iter()
+        .flatten()
+        .map(|tile_id| match map.tile_type(*tile_id) {
+            Some(TileType::Slope(data)) => {
+                let [base, rise_x, rise_y] = data.plane();
+                [base, rise_x, rise_y, DEFAULT_CEILING]
+            }
+            Some(TileType::Outdoor(_)) => [0.0, 0.0, 0.0, OPEN_SKY],
+            Some(TileType::FloorCeiling(data)) => [
+                0.0,
+                0.0,
+                0.0,
+                data.ceiling_height.unwrap_or(DEFAULT_CEILING),
+            ],
+            _ => [0.0, 0.0, 0.0, DEFAULT_CEILING],
+        })
+        .collect()
+}
+
+fn tile_reflections(map: &Map) -> Vec<u8> {
+    map.tiles()
+        .iter()
+        .flatten()
+        .map(|tile_id| match map.tile_type(*tile_id) {
+            Some(TileType::Surface(data)) => (data.reflection * u8::MAX as f32).round() as u8,
+            _ => 0,
+        })
+        .collect()
+}
+
+/// Gamma encodes a linear image, so sampling it from an sRGB texture gives back the linear
+/// values it was authored with.
+fn encode_linear(image: DynamicImage) -> DynamicImage {
+    let table: Vec<u8> = (0..=u8::MAX)
+        .map(|value| {
+            let linear = value as f32 / u8::MAX as f32;
+            let encoded = if linear <= 0.0031308 {
+                linear * 12.92
+            } else {
+                1.055 * linear.powf(1.0 / 2.4) - 0.055
+            };
+            (encoded * u8::MAX as f32).round() as u8
+        })
+        .collect();
+    let mut image = image.into_rgba8();
+    for pixel in image.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            *channel = table[*channel as usize];
+        }
+    }
+    DynamicImage::ImageRgba8(image)
+}
+
+async fn load_image(assets: &dyn AssetSource, path: &str) -> anyhow::Result<DynamicImage> {
+    let bytes = assets.load(path).await?;
+    assets::decode_image(&bytes)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_workers(
+    paths: Vec<&'static str>,
+    assets: Arc<dyn AssetSource>,
+    sender: mpsc::Sender<(usize, anyhow::Result<DynamicImage>)>,
+) {
+    use std::sync::Mutex;
+
+    let worker_count = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .min(paths.len());
+    let queue = Arc::new(Mutex::new(paths.into_iter().enumerate()));
+
+    for _ in 0..worker_count {
+        let queue = queue.clone();
+        let assets = assets.clone();
+        let sender = sender.clone();
+
+        std::thread::spawn(move || {
+            loop {
+                let Some((index, path)) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                let image = pollster::block_on(load_image(assets.as_ref(), path));
+
+                // The renderer may have been dropped, in which case nobody is waiting
+                if sender.send((index, image)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn spawn_workers(
+    paths: Vec<&'static str>,
+    assets: Arc<dyn AssetSource>,
+    sender: mpsc::Sender<(usize, anyhow::Result<DynamicImage>)>,
+) {
+    wasm_bindgen_futures::spawn_local(async move {
+        for (index, path) in paths.into_iter().enumerate() {
+            let image = load_image(assets.as_ref(), path).await;
+            if sender.send((index, image)).is_err() {
+                break;
+            }
+        }
+    });
+}
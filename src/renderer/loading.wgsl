@@ -0,0 +1,45 @@
+struct LoadingUniform {
+    progress: f32,
+    aspect: f32,
+    _pad: vec2f,
+};
+
+@group(0) @binding(0) var<uniform> loading: LoadingUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2f,
+};
+
+const BAR_HALF_WIDTH: f32 = 0.3;
+const BAR_HALF_HEIGHT: f32 = 0.015;
+const BORDER: f32 = 0.004;
+
+// A single triangle that covers the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2f(f32((index << 1u) & 2u), f32(index & 2u));
+
+    out.clip_position = vec4f(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    // Centered coordinates with square pixels, so the border is equally thick on all sides
+    let p = vec2f((input.uv.x - 0.5) * loading.aspect, input.uv.y - 0.5);
+    let d = abs(p) - vec2f(BAR_HALF_WIDTH, BAR_HALF_HEIGHT);
+
+    if d.x > BORDER || d.y > BORDER {
+        return vec4f(0.02, 0.02, 0.02, 1.0);
+    }
+    if d.x > 0.0 || d.y > 0.0 {
+        return vec4f(0.6, 0.6, 0.6, 1.0);
+    }
+
+    let filled = (p.x + BAR_HALF_WIDTH) / (2.0 * BAR_HALF_WIDTH) <= loading.progress;
+    return select(vec4f(0.08, 0.08, 0.08, 1.0), vec4f(0.8, 0.8, 0.8, 1.0), filled);
+}
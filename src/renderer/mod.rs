@@ -1,38 +1,142 @@
+mod blit;
+mod cache;
+#[cfg(not(target_arch = "wasm32"))]
+mod capture;
+mod dither;
+mod floor;
+mod loader;
+mod loading;
+mod overhead;
+mod profiler;
+#[cfg(test)]
+mod tests;
+mod text;
 mod texture;
+mod top_down;
+mod window;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     mem,
-    sync::Arc,
-    time::{Duration, Instant},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
 };
 
-use anyhow::Ok;
+use anyhow::{Context, Ok};
 use glam::{Vec2, vec2};
-use wgpu::{util::DeviceExt, wgc::pipeline};
+use image::{DynamicImage, GenericImageView, RgbaImage};
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+use wgpu::util::DeviceExt;
 use winit::{dpi::PhysicalSize, window::Window};
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::renderer::capture::{FrameCapturer, Readback};
 use crate::{
-    map::{Map, TileData, TileType, TileTypes},
-    raycaster::WallInstance,
-    renderer::texture::{Texture, load_asset},
+    assets::AssetSource,
+    config::{
+        AntiAliasing, Backend, ColorSpace, PowerPreference, PresentMode, RearView, RendererConfig,
+        SurfaceFormatPreference,
+    },
+    engine::AssetEvent,
+    gpu::DeviceReport,
+    hud::{Rect, StatusBar},
+    map::Map,
+    profiling::GpuTimings,
+    raycaster::{FlatSpriteInstance, FloorCamera, SpriteInstance, WallInstance},
+    renderer::{
+        blit::Blit,
+        cache::TextureCache,
+        dither::Dither,
+        floor::{FloorPass, FloorViewBuffers},
+        loader::{LoadedImages, TextureLoader},
+        loading::LoadingScreen,
+        overhead::OverheadScreen,
+        profiler::{GpuProfiler, Timestamp},
+        text::GlyphAtlas,
+        texture::Texture,
+        top_down::TopDownPass,
+        window::WindowSurface,
+    },
 };
 
+pub(crate) use blit::PostEffect;
+pub(crate) use floor::MAX_FLAT_SPRITES;
+pub(crate) use loader::texture_paths;
+pub(crate) use text::glyph_layer;
+pub(crate) use top_down::TopDownCamera;
+
+/// Format of the offscreen targets used for render-to-texture surfaces. Matches the wall
+/// texture array so finished frames can be copied straight into a layer.
+const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Texture layers by tile id or path. Each map has its own, so maps can give the same tile id
+/// different textures.
 struct TileTextureMaps {
     wall_image_map: HashMap<usize, usize>,
     floor_image_map: HashMap<usize, usize>,
     ceiling_image_map: HashMap<usize, usize>,
+    /// Sprite layers by image path
+    sprite_image_map: HashMap<&'static str, usize>,
+    /// Wall layers of linedef images, by path
+    linedef_image_map: HashMap<&'static str, usize>,
+    /// The layers drawn for tiles without one of their own
+    missing_wall_layer: usize,
+    missing_floor_layer: usize,
+    missing_ceiling_layer: usize,
+    /// The tile ids and categories already warned about drawing a checkerboard for
+    warned_missing: Mutex<HashSet<(u8, &'static str)>>,
 }
 
 struct Textures {
     wall_texture_arr: Option<Texture>,
     floor_texture_arr: Option<Texture>,
     ceiling_texture_arr: Option<Texture>,
+    sprite_texture_arr: Option<Texture>,
+    sky_texture_arr: Option<Texture>,
+    /// A normal map per wall layer, flat where there isn't one
+    wall_normal_arr: Option<Texture>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(crate) enum TextureCategory {
     Wall,
     Floor,
     Ceiling,
+    Sprite,
+    Sky,
+    Linedef,
+    /// Which parts of a wall tile's texture are full-bright
+    EmissiveMask,
+    /// Which way the parts of a wall tile's texture face
+    NormalMap,
+}
+
+impl TextureCategory {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Wall => "wall",
+            Self::Floor => "floor",
+            Self::Ceiling => "ceiling",
+            Self::Sprite => "sprite",
+            Self::Sky => "sky",
+            Self::Linedef => "linedef",
+            Self::EmissiveMask => "emissive mask",
+            Self::NormalMap => "normal map",
+        }
+    }
+
+    /// Whether its images hold colors, which are decoded from sRGB when sampled, rather than
+    /// data like masks and normals.
+    fn holds_color(self) -> bool {
+        !matches!(self, Self::EmissiveMask | Self::NormalMap)
+    }
 }
 
 #[repr(C)]
@@ -63,30 +167,320 @@ const VERTICES: [Vertex; 4] = [
 
 const INDICES: [u16; 6] = [0, 1, 2, 2, 3, 0];
 
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ViewUniform {
+    screen_size: Vec2,
+    /// Non-zero when the target stores gamma encoded values without doing the encoding itself
+    encode_srgb: u32,
+    /// Non-zero when colors are offset by blue noise before the target quantizes them
+    dither: u32,
+    /// Multiplies everything but the sky, for the time of day
+    light: [f32; 3],
+    /// How much walls, floors and ceilings darken where they meet
+    ambient_occlusion: f32,
+}
+
+/// Instance data and uniforms for one render target (the screen or a monitor).
+struct ViewBuffers {
+    width: u32,
+    height: u32,
+    wall_instances: Vec<WallInstance>,
+    instance_buffer: wgpu::Buffer,
+    /// Visible sprite columns, sorted back to front
+    sprite_instances: Vec<SpriteInstance>,
+    sprite_buffer: wgpu::Buffer,
+    /// Visible columns of text labels, sorted back to front. Drawn after the sprites with the
+    /// sprite pipeline and the glyph atlas.
+    label_instances: Vec<SpriteInstance>,
+    label_buffer: wgpu::Buffer,
+    uniform: ViewUniform,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    floor: FloorViewBuffers,
+}
+
+impl ViewBuffers {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        floor_pass: &FloorPass,
+        dither: &Dither,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{label} Instance Buffer")),
+            size: (mem::size_of::<WallInstance>() * width as usize) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let sprite_buffer = create_sprite_buffer(device, width as usize, label);
+        let label_buffer = create_sprite_buffer(device, width as usize, label);
+
+        let uniform = ViewUniform {
+            screen_size: vec2(width as f32, height as f32),
+            encode_srgb: needs_srgb_encoding(format) as u32,
+            dither: dither.applies_to(format) as u32,
+            light: [1.0; 3],
+            ambient_occlusion: 0.0,
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{label} Uniform Buffer")),
+            contents: bytemuck::bytes_of(&uniform),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{label} Bind Group")),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&dither.noise),
+                },
+            ],
+        });
+
+        Self {
+            width,
+            height,
+            wall_instances: vec![WallInstance::default(); width as usize],
+            instance_buffer,
+            sprite_instances: Vec::new(),
+            sprite_buffer,
+            label_instances: Vec::new(),
+            label_buffer,
+            uniform,
+            uniform_buffer,
+            bind_group,
+            floor: floor_pass.create_view_buffers(device, width, label),
+        }
+    }
+
+    /// Replaces the sprite columns, growing the buffer if they don't fit.
+    fn set_sprite_instances(&mut self, device: &wgpu::Device, instances: &[SpriteInstance]) {
+        replace_instances(
+            device,
+            &mut self.sprite_buffer,
+            &mut self.sprite_instances,
+            instances,
+        );
+    }
+
+    /// Replaces the label columns, growing the buffer if they don't fit.
+    fn set_label_instances(&mut self, device: &wgpu::Device, instances: &[SpriteInstance]) {
+        replace_instances(
+            device,
+            &mut self.label_buffer,
+            &mut self.label_instances,
+            instances,
+        );
+    }
+}
+
+fn replace_instances(
+    device: &wgpu::Device,
+    buffer: &mut wgpu::Buffer,
+    current: &mut Vec<SpriteInstance>,
+    instances: &[SpriteInstance],
+) {
+    let capacity = buffer.size() as usize / mem::size_of::<SpriteInstance>();
+    if instances.len() > capacity {
+        *buffer = create_sprite_buffer(device, instances.len().next_power_of_two(), "Grown");
+    }
+
+    current.clear();
+    current.extend_from_slice(instances);
+}
+
+fn create_sprite_buffer(device: &wgpu::Device, capacity: usize, label: &str) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(&format!("{label} Sprite Buffer")),
+        size: (mem::size_of::<SpriteInstance>() * capacity.max(1)) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// A wall texture layer that is re-rendered every frame from a monitor camera.
+struct MonitorTarget {
+    tile_id: u8,
+    layer: u32,
+    target: Texture,
+    view: ViewBuffers,
+}
+
+/// The right eye's view in side-by-side stereo, drawn into its own target and scaled onto the
+/// right half of the view. The main view is the left eye's.
+struct StereoEye {
+    blit: Blit,
+    view: ViewBuffers,
+}
+
+/// The rear-view mirror, drawn into its own target the size of its area and scaled onto it
+/// over the view.
+struct RearViewTarget {
+    area: RearView,
+    blit: Blit,
+    view: ViewBuffers,
+    /// Multisampled color target the mirror resolves from when MSAA is on
+    msaa_view: Option<wgpu::TextureView>,
+    shown: bool,
+}
+
+/// `area`'s pixels within `viewport`, kept inside it.
+fn rear_view_rect(area: &RearView, viewport: Rect) -> Rect {
+    let x = area.x.clamp(0.0, 1.0);
+    let y = area.y.clamp(0.0, 1.0);
+    let width = area.width.clamp(0.0, 1.0 - x);
+    let height = area.height.clamp(0.0, 1.0 - y);
+    Rect {
+        x: viewport.x + (viewport.width as f32 * x) as u32,
+        y: viewport.y + (viewport.height as f32 * y) as u32,
+        width: ((viewport.width as f32 * width) as u32).max(1),
+        height: ((viewport.height as f32 * height) as u32).max(1),
+    }
+}
+
+/// Everything the renderer holds for the current map once its textures have loaded.
+struct MapTextures {
+    textures: Textures,
+    tile_texture_maps: TileTextureMaps,
+    bind_group: wgpu::BindGroup,
+    /// The wall normal maps, bound alongside the wall textures
+    normal_bind_group: wgpu::BindGroup,
+    /// `None` when the map has no sprites
+    sprite_bind_group: Option<wgpu::BindGroup>,
+    /// Floor layer and ceiling layer of each tile, for the floor pass
+    tile_layers: Texture,
+    /// Floor plane and ceiling height of each tile, for steps, ramps and low ceilings
+    tile_heights: Texture,
+    /// How strongly each tile's floor mirrors the walls
+    tile_reflections: Texture,
+    floor_bind_group: wgpu::BindGroup,
+    /// Wall layer of each tile, for the top-down pass
+    tile_walls: Texture,
+    top_down_bind_group: wgpu::BindGroup,
+    monitors: Vec<MonitorTarget>,
+}
+
+impl MapTextures {
+    fn memory_size(&self) -> u64 {
+        [
+            &self.textures.wall_texture_arr,
+            &self.textures.floor_texture_arr,
+            &self.textures.ceiling_texture_arr,
+            &self.textures.sprite_texture_arr,
+            &self.textures.sky_texture_arr,
+            &self.textures.wall_normal_arr,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(self.monitors.iter().map(|monitor| &monitor.target))
+        .chain([
+            &self.tile_layers,
+            &self.tile_heights,
+            &self.tile_reflections,
+            &self.tile_walls,
+        ])
+        .map(Texture::memory_size)
+        .sum()
+    }
+}
+
 pub(crate) struct Renderer {
-    window: Arc<Window>,
-    surface: wgpu::Surface<'static>,
+    /// Kept to open a new device on the same surface if the device is lost, and to make
+    /// surfaces for more windows
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    /// Shared with the renderer replacing this one after a device loss
+    surface: Arc<wgpu::Surface<'static>>,
     is_surface_configured: bool,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     render_pipeline: wgpu::RenderPipeline,
-    bind_group: wgpu::BindGroup,
+    offscreen_pipeline: wgpu::RenderPipeline,
+    sprite_pipeline: wgpu::RenderPipeline,
+    offscreen_sprite_pipeline: wgpu::RenderPipeline,
+    floor_pass: FloorPass,
+    top_down_pass: TopDownPass,
+    /// Set while the map is drawn from above in place of the main view
+    top_down: bool,
+    glyph_atlas: GlyphAtlas,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    view_bind_group_layout: wgpu::BindGroupLayout,
+    dither: Dither,
     quad_vertex_buffer: wgpu::Buffer,
     quad_index_buffer: wgpu::Buffer,
-    quad_instance_buffer: wgpu::Buffer,
-    textures: Textures,
-    tile_texture_maps: TileTextureMaps,
-    wall_instances: Vec<WallInstance>,
+    screen_view: ViewBuffers,
+    /// Set when the internal resolution differs from the surface's, or once a post effect
+    /// has been needed
+    blit: Option<Blit>,
+    post_effect: PostEffect,
+    stereo: Option<StereoEye>,
+    rear_view: Option<RearViewTarget>,
+    /// Whether post effects are limited so they can't flash brightly or quickly
+    photosensitive_safe: bool,
+    /// Multiplies everything in the views but the sky
+    ambient_light: [f32; 3],
+    /// How much walls, floors and ceilings darken where they meet, from 0 to 1
+    ambient_occlusion: f32,
+    /// Multisampled color target the main view resolves from when MSAA is on
+    msaa_view: Option<wgpu::TextureView>,
+    loading_screen: LoadingScreen,
+    /// Set while the level editor or automap is open, in place of the map's view
+    overhead_screen: Option<OverheadScreen>,
+    status_bar: Option<StatusBar>,
+    /// Windows besides the main one, by the index [`add_window`](Self::add_window) gave. Closed
+    /// ones are left empty so the others keep theirs.
+    windows: Vec<Option<WindowSurface>>,
+    /// Sprite images loaded with every map
+    preload_sprites: Vec<&'static str>,
+    /// How texture images are encoded
+    color_space: ColorSpace,
+    assets: Arc<dyn AssetSource>,
+    current_map_key: &'static str,
+    /// Set while the current map's textures are being fetched and decoded
+    loader: Option<TextureLoader>,
+    /// The decoded images of the map textures were last built for, kept to upload them again
+    /// after a device loss
+    #[cfg_attr(target_arch = "wasm32", allow(unused))]
+    map_images: Option<(&'static str, LoadedImages)>,
+    /// Textures that failed to load since they were last taken
+    failed_assets: Vec<AssetEvent>,
+    texture_cache: TextureCache,
     last_frame_time: Option<Instant>,
     delta_time: Duration,
+    frame_interval: Option<Duration>,
+    /// Replaces `frame_interval` while the window is in the background
+    background_frame_interval: Option<Duration>,
+    /// Set when GPU profiling is enabled and the device supports timestamp queries
+    profiler: Option<GpuProfiler>,
+    device_report: DeviceReport,
+    /// Set by the device when it's lost, such as on a driver reset or a switch between GPUs
+    device_lost: Arc<AtomicBool>,
+    /// The settings the renderer was made with, for making it again after a device loss
+    renderer_config: RendererConfig,
+    #[cfg(not(target_arch = "wasm32"))]
+    capturer: Option<FrameCapturer>,
+    #[cfg(not(target_arch = "wasm32"))]
+    screenshot_path: Option<PathBuf>,
+    /// Whether it was already said that frames can't be read back from the surface
+    #[cfg(not(target_arch = "wasm32"))]
+    readback_unsupported_logged: bool,
 }
 
 impl Renderer {
-    pub fn config(&self) -> &wgpu::SurfaceConfiguration {
-        &self.config
-    }
-
     pub fn resize(&mut self, width: u32, height: u32) {
         if width > 0 && height > 0 {
             self.config.width = width;
@@ -96,30 +490,89 @@ impl Renderer {
         }
     }
 
-    pub async fn new(window: &Arc<Window>, map: &Map) -> anyhow::Result<Self> {
-        // let tile_types: &TileTypes;
-        let window = window.clone();
+    /// The resolution rays are cast and walls are drawn at, before scaling to the surface.
+    pub fn internal_size(&self) -> (u32, u32) {
+        match &self.blit {
+            Some(blit) => (blit.target.texture.width(), blit.target.texture.height()),
+            None => (self.config.width, self.config.height),
+        }
+    }
+
+    pub async fn new(
+        window: &Arc<Window>,
+        map_key: &'static str,
+        map: &Map,
+        assets: Arc<dyn AssetSource>,
+        renderer_config: &RendererConfig,
+    ) -> anyhow::Result<Self> {
         let size = window.inner_size();
-        let (surface, device, queue, config) = wgpu_init(&window, size).await?;
+        Self::from_window_handle(window.clone(), size, map_key, map, assets, renderer_config).await
+    }
 
-        let (textures, tile_texture_maps) = load_textures(map, &device, &queue)?;
-        let wall_texture_arr = textures.wall_texture_arr.as_ref().unwrap();
-        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+    /// Renders into any window wgpu can create a surface for, such as one owned by the host
+    /// application. `size` is the window's size in physical pixels.
+    pub async fn from_window_handle(
+        window: Arc<dyn wgpu::WindowHandle>,
+        size: PhysicalSize<u32>,
+        map_key: &'static str,
+        map: &Map,
+        assets: Arc<dyn AssetSource>,
+        renderer_config: &RendererConfig,
+    ) -> anyhow::Result<Self> {
+        let instance = create_instance(renderer_config);
+        let surface = Arc::new(
+            instance
+                .create_surface(window)
+                .expect("Failed to create surface"),
+        );
+        let mut renderer =
+            Self::with_surface(instance, surface, size, map_key, assets, renderer_config).await?;
+        renderer.loader = Some(TextureLoader::start(
+            map,
+            &renderer_config.preload_sprites,
+            renderer.assets.clone(),
+            renderer_config.color_space,
+        ));
+        Ok(renderer)
+    }
 
-        let vertex_buffer_layouts = [
-            wgpu::VertexBufferLayout {
-                array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-                step_mode: wgpu::VertexStepMode::Vertex,
-                attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2],
-            },
-            wgpu::VertexBufferLayout {
-                array_stride: std::mem::size_of::<WallInstance>() as wgpu::BufferAddress,
-                step_mode: wgpu::VertexStepMode::Instance,
-                attributes: &wgpu::vertex_attr_array![2 => Float32, 3 => Float32, 4 => Float32, 5 => Float32, 6 => Uint32],
-            },
-        ];
+    /// Sets up everything but the map's textures on a new device presenting to `surface`.
+    async fn with_surface(
+        instance: wgpu::Instance,
+        surface: Arc<wgpu::Surface<'static>>,
+        size: PhysicalSize<u32>,
+        map_key: &'static str,
+        assets: Arc<dyn AssetSource>,
+        renderer_config: &RendererConfig,
+    ) -> anyhow::Result<Self> {
+        let (adapter, device, queue, config) =
+            wgpu_init(&instance, &surface, size, renderer_config).await?;
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let lost = device_lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // Dropping the device on purpose isn't a loss
+            if reason == wgpu::DeviceLostReason::Unknown {
+                println!("The GPU device was lost: {message}");
+                lost.store(true, Ordering::Relaxed);
+            }
+        });
+        let device_report = DeviceReport::new(
+            &adapter,
+            &device,
+            &surface.get_capabilities(&adapter),
+            GpuProfiler::FEATURES,
+        );
+
+        let profiler = if !renderer_config.gpu_profiling {
+            None
+        } else if device.features().contains(GpuProfiler::FEATURES) {
+            Some(GpuProfiler::new(&device, &queue))
+        } else {
+            println!("GPU profiling is unavailable: the adapter doesn't support timestamp queries");
+            None
+        };
 
-        let wall_instances = vec![WallInstance::default(); config.width as usize];
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
 
         let quad_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Quad Vertex Buffer"),
@@ -132,129 +585,288 @@ impl Renderer {
             usage: wgpu::BufferUsages::INDEX,
         });
 
-        let quad_instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Quad Instance Buffer"),
-            size: (mem::size_of::<WallInstance>() * config.width as usize) as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Texture bind group layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Texture bind group layout"),
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2Array,
-                        multisampled: false,
+        let view_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("View bind group layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
                     },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-        });
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Bind Group"),
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&wall_texture_arr.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&wall_texture_arr.sampler),
-                },
-            ],
+        // A status bar shrinks the view, and with it the number of rays
+        let view_rect = renderer_config.status_bar.as_ref().map_or(
+            Rect {
+                x: 0,
+                y: 0,
+                width: config.width,
+                height: config.height,
+            },
+            |bar| bar.layout(config.width, config.height).view,
+        );
+        // Supersampling renders at twice the size, which the blit averages down
+        let scale = match renderer_config.anti_aliasing {
+            AntiAliasing::Ssaa => renderer_config.render_scale * 2.0,
+            AntiAliasing::None | AntiAliasing::Msaa(_) => renderer_config.render_scale,
+        };
+        let max_size = device.limits().max_texture_dimension_2d;
+        let internal_width = ((view_rect.width as f32 * scale) as u32).clamp(1, max_size);
+        let internal_height = ((view_rect.height as f32 * scale) as u32).clamp(1, max_size);
+
+        let blit = (internal_width != config.width
+            || internal_height != config.height
+            || renderer_config.status_bar.is_some())
+        .then(|| {
+            // Keep pixels crisp when upscaling and average them when downscaling
+            let filter = if scale < 1.0 {
+                wgpu::FilterMode::Nearest
+            } else {
+                wgpu::FilterMode::Linear
+            };
+            Blit::new(
+                &device,
+                config.format,
+                internal_width,
+                internal_height,
+                filter,
+            )
         });
 
+        let dither = Dither::new(&device, &queue, renderer_config.dither);
+
+        let sample_count = match renderer_config.anti_aliasing {
+            AntiAliasing::Msaa(count) => supported_sample_count(&adapter, config.format, count),
+            AntiAliasing::None | AntiAliasing::Ssaa => 1,
+        };
+
+        let floor_pass = FloorPass::new(
+            &device,
+            &queue,
+            &view_bind_group_layout,
+            config.format,
+            OFFSCREEN_FORMAT,
+            sample_count,
+        );
+
+        let top_down_pass = TopDownPass::new(
+            &device,
+            &view_bind_group_layout,
+            config.format,
+            sample_count,
+        );
+
+        let screen_view = ViewBuffers::new(
+            &device,
+            &view_bind_group_layout,
+            &floor_pass,
+            &dither,
+            internal_width,
+            internal_height,
+            config.format,
+            "Screen",
+        );
+
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&bind_group_layout],
+                bind_group_layouts: &[&texture_bind_group_layout, &view_bind_group_layout],
                 immediate_size: 0,
             });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &vertex_buffer_layouts,
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState {
-                        color: wgpu::BlendComponent::REPLACE,
-                        alpha: wgpu::BlendComponent::REPLACE,
-                    }),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Cw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview_mask: None,
-            cache: Default::default(),
+        // Walls also take their normal maps
+        let wall_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Wall Pipeline Layout"),
+            bind_group_layouts: &[
+                &texture_bind_group_layout,
+                &view_bind_group_layout,
+                &texture_bind_group_layout,
+            ],
+            immediate_size: 0,
+        });
+
+        let msaa_view = (sample_count > 1).then(|| {
+            create_msaa_view(
+                &device,
+                config.format,
+                internal_width,
+                internal_height,
+                sample_count,
+            )
         });
 
+        let loading_screen = LoadingScreen::new(&device, config.format);
+        let glyph_atlas = GlyphAtlas::new(&device, &queue, &texture_bind_group_layout);
+
+        let sprite_shader = device.create_shader_module(wgpu::include_wgsl!("sprite.wgsl"));
+        let sprite_pipeline = create_sprite_pipeline(
+            &device,
+            &render_pipeline_layout,
+            &sprite_shader,
+            config.format,
+            sample_count,
+        );
+        let offscreen_sprite_pipeline = create_sprite_pipeline(
+            &device,
+            &render_pipeline_layout,
+            &sprite_shader,
+            OFFSCREEN_FORMAT,
+            1,
+        );
+
+        let render_pipeline = create_wall_pipeline(
+            &device,
+            &wall_pipeline_layout,
+            &shader,
+            config.format,
+            sample_count,
+        );
+        let offscreen_pipeline =
+            create_wall_pipeline(&device, &wall_pipeline_layout, &shader, OFFSCREEN_FORMAT, 1);
+
         Ok(Renderer {
-            window,
             surface,
             is_surface_configured: false,
             device,
             queue,
             config,
             render_pipeline,
-            bind_group,
+            offscreen_pipeline,
+            sprite_pipeline,
+            offscreen_sprite_pipeline,
+            floor_pass,
+            top_down_pass,
+            top_down: false,
+            glyph_atlas,
+            texture_bind_group_layout,
+            view_bind_group_layout,
+            dither,
             quad_vertex_buffer,
             quad_index_buffer,
-            quad_instance_buffer,
-            textures,
-            tile_texture_maps,
-            wall_instances,
+            screen_view,
+            blit,
+            post_effect: PostEffect::default(),
+            stereo: None,
+            rear_view: None,
+            photosensitive_safe: false,
+            ambient_light: [1.0; 3],
+            ambient_occlusion: renderer_config.ambient_occlusion.clamp(0.0, 1.0),
+            msaa_view,
+            loading_screen,
+            overhead_screen: None,
+            status_bar: renderer_config.status_bar.clone(),
+            windows: Vec::new(),
+            loader: None,
+            map_images: None,
+            failed_assets: Vec::new(),
+            preload_sprites: renderer_config.preload_sprites.clone(),
+            color_space: renderer_config.color_space,
+            assets,
+            current_map_key: map_key,
+            texture_cache: TextureCache::new(renderer_config.texture_budget),
             last_frame_time: Some(Instant::now()),
             delta_time: Duration::default(),
+            frame_interval: renderer_config
+                .frame_cap
+                .map(|fps| Duration::from_secs_f64(1.0 / fps.max(1) as f64)),
+            background_frame_interval: None,
+            profiler,
+            device_report,
+            instance,
+            adapter,
+            device_lost,
+            renderer_config: renderer_config.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            capturer: renderer_config
+                .capture
+                .clone()
+                .map(FrameCapturer::new)
+                .transpose()?,
+            #[cfg(not(target_arch = "wasm32"))]
+            screenshot_path: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            readback_unsupported_logged: false,
         })
     }
 
+    #[profiling::function]
     pub fn render(&mut self) -> anyhow::Result<()> {
-        self.window.request_redraw();
-
         let now = Instant::now();
         self.delta_time = Instant::now() - self.last_frame_time.unwrap_or(now);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(capturer) = &self.capturer {
+            self.delta_time = capturer.frame_duration();
+        }
 
         if !self.is_surface_configured {
             return Ok(());
         }
 
-        let output = self.surface.get_current_texture()?;
+        let measure_gpu = self
+            .profiler
+            .as_mut()
+            .is_some_and(|profiler| profiler.begin_frame(&self.device, self.blit.is_some()));
+        let profiler = self.profiler.as_ref().filter(|_| measure_gpu);
+
+        let output = match self.surface.get_current_texture() {
+            // Skipped frames: the surface is set up again for the next one
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(&self.device, &self.config);
+                return Ok(());
+            }
+            Err(wgpu::SurfaceError::Timeout) => return Ok(()),
+            result => result?,
+        };
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -265,53 +877,360 @@ impl Renderer {
                 label: Some("Render Encoder"),
             });
 
+        let map_textures = self.texture_cache.get(self.current_map_key);
+        if self.overhead_screen.is_some() || map_textures.is_none() {
+            match &self.overhead_screen {
+                Some(overhead_screen) => overhead_screen.draw(
+                    &self.queue,
+                    &mut encoder,
+                    &view,
+                    (self.config.width, self.config.height),
+                ),
+                None => {
+                    let progress = self.loader.as_ref().map_or(1.0, |loader| loader.progress());
+                    self.loading_screen.draw(
+                        &self.queue,
+                        &mut encoder,
+                        &view,
+                        self.config.width as f32 / self.config.height as f32,
+                        progress,
+                    );
+                }
+            }
+
+            self.queue.submit(std::iter::once(encoder.finish()));
+            output.present();
+            self.draw_windows()?;
+            profiling::finish_frame!();
+            self.last_frame_time = Some(now);
+
+            return Ok(());
+        }
+        let map_textures = map_textures.unwrap();
+
+        // Monitors are drawn first so the main pass samples this frame's views
+        for monitor in &map_textures.monitors {
+            self.draw_view(
+                &mut encoder,
+                &monitor.target.view,
+                None,
+                true,
+                map_textures,
+                &monitor.view,
+                None,
+            );
+
+            let wall_texture_arr = map_textures.textures.wall_texture_arr.as_ref().unwrap();
+            encoder.copy_texture_to_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &monitor.target.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::TexelCopyTextureInfo {
+                    texture: &wall_texture_arr.texture,
+                    mip_level: 0,
+                    origin: wall_texture_arr.cell_origin(monitor.layer),
+                    aspect: wgpu::TextureAspect::All,
+                },
+                monitor.target.texture.size(),
+            );
+        }
+
+        match &self.blit {
+            Some(blit) => {
+                blit.set_effect(&self.queue, &self.post_effect);
+                self.draw_view(
+                    &mut encoder,
+                    &blit.target.view,
+                    self.msaa_view.as_ref(),
+                    false,
+                    map_textures,
+                    &self.screen_view,
+                    profiler,
+                );
+                let stereo = self.stereo.as_ref().filter(|_| !self.top_down);
+                if let Some(eye) = stereo {
+                    eye.blit.set_effect(&self.queue, &self.post_effect);
+                    self.draw_view(
+                        &mut encoder,
+                        &eye.blit.target.view,
+                        self.msaa_view.as_ref(),
+                        false,
+                        map_textures,
+                        &eye.view,
+                        None,
+                    );
+                }
+                let (viewport, clear_color) = match &self.status_bar {
+                    Some(bar) => {
+                        let [r, g, b] = bar.color();
+                        (
+                            Some(bar.layout(self.config.width, self.config.height).view),
+                            wgpu::Color {
+                                r: r as f64,
+                                g: g as f64,
+                                b: b as f64,
+                                a: 1.0,
+                            },
+                        )
+                    }
+                    None => (None, wgpu::Color::BLACK),
+                };
+                let full_viewport = viewport.unwrap_or(Rect {
+                    x: 0,
+                    y: 0,
+                    width: self.config.width,
+                    height: self.config.height,
+                });
+                match stereo {
+                    Some(eye) => {
+                        let viewport = full_viewport;
+                        let half = viewport.width / 2;
+                        blit.draw(
+                            &mut encoder,
+                            &view,
+                            Some(Rect {
+                                width: half,
+                                ..viewport
+                            }),
+                            Some(clear_color),
+                            profiler.map(GpuProfiler::post_process_writes),
+                        );
+                        eye.blit.draw(
+                            &mut encoder,
+                            &view,
+                            Some(Rect {
+                                x: viewport.x + half,
+                                width: viewport.width - half,
+                                ..viewport
+                            }),
+                            None,
+                            None,
+                        );
+                    }
+                    None => blit.draw(
+                        &mut encoder,
+                        &view,
+                        viewport,
+                        Some(clear_color),
+                        profiler.map(GpuProfiler::post_process_writes),
+                    ),
+                }
+                if let Some(mirror) = self
+                    .rear_view
+                    .as_ref()
+                    .filter(|mirror| mirror.shown && !self.top_down)
+                {
+                    mirror.blit.set_effect(&self.queue, &self.post_effect);
+                    self.draw_view(
+                        &mut encoder,
+                        &mirror.blit.target.view,
+                        mirror.msaa_view.as_ref(),
+                        false,
+                        map_textures,
+                        &mirror.view,
+                        None,
+                    );
+                    mirror.blit.draw(
+                        &mut encoder,
+                        &view,
+                        Some(rear_view_rect(&mirror.area, full_viewport)),
+                        None,
+                        None,
+                    );
+                }
+            }
+            None => self.draw_view(
+                &mut encoder,
+                &view,
+                self.msaa_view.as_ref(),
+                false,
+                map_textures,
+                &self.screen_view,
+                profiler,
+            ),
+        }
+
+        if let Some(profiler) = profiler {
+            profiler.resolve(&mut encoder);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let readback = self.copy_frame(&mut encoder, &output.texture)?;
+
         {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    depth_slice: None,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-                multiview_mask: None,
-            });
+            profiling::scope!("submit");
+            self.queue.submit(std::iter::once(encoder.finish()));
+        }
 
-            self.queue.write_buffer(
-                &self.quad_instance_buffer,
-                0,
-                bytemuck::cast_slice(&self.wall_instances),
+        if measure_gpu && let Some(profiler) = &mut self.profiler {
+            profiler.finish_frame();
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some((readback, screenshot_path, keep_for_capture)) = readback {
+            self.save_frame(readback, screenshot_path, keep_for_capture)?;
+        }
+
+        {
+            profiling::scope!("present");
+            output.present();
+        }
+        self.draw_windows()?;
+        profiling::finish_frame!();
+
+        self.last_frame_time = Some(now);
+
+        Ok(())
+    }
+
+    /// Draws a view's floor and ceiling, then its wall columns, then its sprite columns into
+    /// `target`, or the map from above in their place while the top-down view is shown and
+    /// the target isn't offscreen. Labels go over either. Renders into `msaa_view` and resolves
+    /// into `target` when it's given.
+    /// `offscreen` selects the pipelines for monitor targets. `profiler` times each stage.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_view(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        msaa_view: Option<&wgpu::TextureView>,
+        offscreen: bool,
+        map_textures: &MapTextures,
+        view_buffers: &ViewBuffers,
+        profiler: Option<&GpuProfiler>,
+    ) {
+        let (wall_pipeline, sprite_pipeline) = if offscreen {
+            (&self.offscreen_pipeline, &self.offscreen_sprite_pipeline)
+        } else {
+            (&self.render_pipeline, &self.sprite_pipeline)
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: msaa_view.unwrap_or(target),
+                depth_slice: None,
+                resolve_target: msaa_view.map(|_| target),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.1,
+                        g: 0.2,
+                        b: 0.3,
+                        a: 1.0,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+            multiview_mask: None,
+        });
+        let timestamp = |render_pass: &mut wgpu::RenderPass, timestamp| {
+            if let Some(profiler) = profiler {
+                profiler.write(render_pass, timestamp);
+            }
+        };
+
+        self.queue.write_buffer(
+            &view_buffers.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&ViewUniform {
+                light: self.ambient_light,
+                ambient_occlusion: self.ambient_occlusion,
+                ..view_buffers.uniform
+            }),
+        );
+
+        timestamp(&mut render_pass, Timestamp::FloorStart);
+        if self.top_down && !offscreen {
+            self.top_down_pass.draw(
+                &mut render_pass,
+                &map_textures.top_down_bind_group,
+                &view_buffers.bind_group,
             );
+            timestamp(&mut render_pass, Timestamp::WallsStart);
+            timestamp(&mut render_pass, Timestamp::SpritesStart);
+        } else {
+            let columns: Vec<[f32; 4]> = view_buffers
+                .wall_instances
+                .iter()
+                .map(WallInstance::reflected_column)
+                .collect();
+            view_buffers.floor.write_columns(&self.queue, &columns);
+            self.floor_pass.draw(
+                &mut render_pass,
+                offscreen,
+                &map_textures.floor_bind_group,
+                &view_buffers.bind_group,
+                &view_buffers.floor,
+            );
+
+            {
+                profiling::scope!("upload wall instances");
+                self.queue.write_buffer(
+                    &view_buffers.instance_buffer,
+                    0,
+                    bytemuck::cast_slice(&view_buffers.wall_instances),
+                );
+            }
 
+            timestamp(&mut render_pass, Timestamp::WallsStart);
             render_pass.set_vertex_buffer(0, self.quad_vertex_buffer.slice(..));
-            render_pass.set_vertex_buffer(1, self.quad_instance_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, view_buffers.instance_buffer.slice(..));
             render_pass
                 .set_index_buffer(self.quad_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
 
-            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_pipeline(wall_pipeline);
+
+            render_pass.set_bind_group(0, &map_textures.bind_group, &[]);
+            render_pass.set_bind_group(1, &view_buffers.bind_group, &[]);
+            render_pass.set_bind_group(2, &map_textures.normal_bind_group, &[]);
+
+            render_pass.draw_indexed(0..6, 0, 0..view_buffers.width);
+
+            timestamp(&mut render_pass, Timestamp::SpritesStart);
 
-            render_pass.set_bind_group(0, &self.bind_group, &[]);
+            // Sprite columns are already clipped against the walls and sorted back to front, so
+            // blending them in order composites translucent ones correctly
+            if let Some(sprite_bind_group) = &map_textures.sprite_bind_group
+                && !view_buffers.sprite_instances.is_empty()
+            {
+                profiling::scope!("upload sprite instances");
+                self.queue.write_buffer(
+                    &view_buffers.sprite_buffer,
+                    0,
+                    bytemuck::cast_slice(&view_buffers.sprite_instances),
+                );
 
-            render_pass.draw_indexed(0..6, 0, 0..self.config.width);
+                render_pass.set_pipeline(sprite_pipeline);
+                render_pass.set_bind_group(0, sprite_bind_group, &[]);
+                render_pass.set_vertex_buffer(1, view_buffers.sprite_buffer.slice(..));
+
+                render_pass.draw_indexed(0..6, 0, 0..view_buffers.sprite_instances.len() as u32);
+            }
         }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        // Labels go over sprites, so names stay readable above the sprites they belong to
+        if !view_buffers.label_instances.is_empty() {
+            profiling::scope!("upload label instances");
+            self.queue.write_buffer(
+                &view_buffers.label_buffer,
+                0,
+                bytemuck::cast_slice(&view_buffers.label_instances),
+            );
 
-        self.last_frame_time = Some(now);
+            render_pass.set_pipeline(sprite_pipeline);
+            render_pass.set_bind_group(0, &self.glyph_atlas.bind_group, &[]);
+            render_pass.set_vertex_buffer(1, view_buffers.label_buffer.slice(..));
 
-        Ok(())
+            render_pass.draw_indexed(0..6, 0, 0..view_buffers.label_instances.len() as u32);
+        }
+
+        timestamp(&mut render_pass, Timestamp::ViewEnd);
     }
 
     pub fn set_wall_instance(
@@ -319,163 +1238,1389 @@ impl Renderer {
         index: usize,
         instance: WallInstance,
     ) -> anyhow::Result<()> {
-        self.wall_instances[index] = instance;
+        self.screen_view.wall_instances[index] = instance;
 
         Ok(())
     }
 
-    pub fn get_texture_index(
-        &self,
-        k: u8,
-        texture_category: &TextureCategory,
-    ) -> anyhow::Result<usize> {
-        match texture_category {
-            TextureCategory::Wall => Ok(*self
-                .tile_texture_maps
-                .wall_image_map
-                .get(&(k as usize))
-                .unwrap()),
-            TextureCategory::Floor => Ok(*self
-                .tile_texture_maps
-                .floor_image_map
-                .get(&(k as usize))
-                .unwrap()),
-            TextureCategory::Ceiling => Ok(*self
-                .tile_texture_maps
-                .ceiling_image_map
-                .get(&(k as usize))
-                .unwrap()),
-        }
+    pub fn set_sprite_instances(&mut self, instances: &[SpriteInstance]) -> anyhow::Result<()> {
+        self.screen_view
+            .set_sprite_instances(&self.device, instances);
+
+        Ok(())
     }
 
-    pub fn delta_time(&self) -> Duration {
-        self.delta_time
+    pub fn set_stereo_sprite_instances(&mut self, instances: &[SpriteInstance]) {
+        if let Some(eye) = &mut self.stereo {
+            eye.view.set_sprite_instances(&self.device, instances);
+        }
     }
-}
 
-async fn wgpu_init(
-    window: &Arc<Window>,
-    size: PhysicalSize<u32>,
-) -> anyhow::Result<(
-    wgpu::Surface<'static>,
-    wgpu::Device,
-    wgpu::Queue,
-    wgpu::SurfaceConfiguration,
-)> {
-    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::PRIMARY,
-        ..Default::default()
-    });
+    pub fn set_monitor_sprite_instances(
+        &mut self,
+        monitor: usize,
+        instances: &[SpriteInstance],
+    ) -> anyhow::Result<()> {
+        let device = self.device.clone();
+        self.map_textures_mut()?.monitors[monitor]
+            .view
+            .set_sprite_instances(&device, instances);
 
-    let surface = instance
-        .create_surface(window.clone())
-        .expect("Failed to create surface");
+        Ok(())
+    }
 
-    let adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            compatible_surface: Some(&surface),
-            force_fallback_adapter: false,
-        })
-        .await?;
+    pub fn set_label_instances(&mut self, instances: &[SpriteInstance]) -> anyhow::Result<()> {
+        self.screen_view
+            .set_label_instances(&self.device, instances);
 
-    let (device, queue) = adapter
-        .request_device(&wgpu::DeviceDescriptor {
-            label: None,
-            required_features: wgpu::Features::empty(),
-            experimental_features: wgpu::ExperimentalFeatures::disabled(),
-            required_limits: wgpu::Limits::default(),
-            memory_hints: Default::default(),
-            trace: wgpu::Trace::Off,
-        })
-        .await?;
+        Ok(())
+    }
 
-    let surface_caps = surface.get_capabilities(&adapter);
+    pub fn set_stereo_label_instances(&mut self, instances: &[SpriteInstance]) {
+        if let Some(eye) = &mut self.stereo {
+            eye.view.set_label_instances(&self.device, instances);
+        }
+    }
 
-    let surface_format = surface_caps
-        .formats
-        .iter()
-        .find(|f| f.is_srgb())
-        .copied()
-        .unwrap_or(surface_caps.formats[0]);
+    pub fn set_monitor_label_instances(
+        &mut self,
+        monitor: usize,
+        instances: &[SpriteInstance],
+    ) -> anyhow::Result<()> {
+        let device = self.device.clone();
+        self.map_textures_mut()?.monitors[monitor]
+            .view
+            .set_label_instances(&device, instances);
 
-    let config = wgpu::SurfaceConfiguration {
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-        format: surface_format,
+        Ok(())
+    }
+
+    pub fn set_floor(
+        &mut self,
+        camera: &FloorCamera,
+        flat_sprites: &[FlatSpriteInstance],
+    ) -> anyhow::Result<()> {
+        profiling::scope!("upload floor");
+        self.screen_view
+            .floor
+            .write(&self.queue, camera, flat_sprites);
+
+        Ok(())
+    }
+
+    pub fn set_stereo_floor(&mut self, camera: &FloorCamera, flat_sprites: &[FlatSpriteInstance]) {
+        if let Some(eye) = &self.stereo {
+            eye.view.floor.write(&self.queue, camera, flat_sprites);
+        }
+    }
+
+    pub fn set_monitor_floor(
+        &mut self,
+        monitor: usize,
+        camera: &FloorCamera,
+        flat_sprites: &[FlatSpriteInstance],
+    ) -> anyhow::Result<()> {
+        self.map_textures()?.monitors[monitor]
+            .view
+            .floor
+            .write(&self.queue, camera, flat_sprites);
+
+        Ok(())
+    }
+
+    pub fn set_rear_view_sprite_instances(&mut self, instances: &[SpriteInstance]) {
+        if let Some(mirror) = &mut self.rear_view {
+            mirror.view.set_sprite_instances(&self.device, instances);
+        }
+    }
+
+    pub fn set_rear_view_label_instances(&mut self, instances: &[SpriteInstance]) {
+        if let Some(mirror) = &mut self.rear_view {
+            mirror.view.set_label_instances(&self.device, instances);
+        }
+    }
+
+    pub fn set_rear_view_floor(
+        &mut self,
+        camera: &FloorCamera,
+        flat_sprites: &[FlatSpriteInstance],
+    ) {
+        if let Some(mirror) = &self.rear_view {
+            mirror.view.floor.write(&self.queue, camera, flat_sprites);
+        }
+    }
+
+    pub fn set_rear_view_instance(&mut self, index: usize, instance: WallInstance) {
+        if let Some(mirror) = &mut self.rear_view {
+            mirror.view.wall_instances[index] = instance;
+        }
+    }
+
+    pub fn set_stereo_instance(&mut self, index: usize, instance: WallInstance) {
+        if let Some(eye) = &mut self.stereo {
+            eye.view.wall_instances[index] = instance;
+        }
+    }
+
+    pub fn set_monitor_instance(
+        &mut self,
+        monitor: usize,
+        index: usize,
+        instance: WallInstance,
+    ) -> anyhow::Result<()> {
+        self.map_textures_mut()?.monitors[monitor]
+            .view
+            .wall_instances[index] = instance;
+
+        Ok(())
+    }
+
+    /// Where the renderer loads textures from, for loading the map's other assets from the
+    /// same place.
+    pub fn assets(&self) -> Arc<dyn AssetSource> {
+        self.assets.clone()
+    }
+
+    /// How texture images and map colors are encoded.
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Whether the current map's textures are still loading. Nothing but the loading screen
+    /// is drawn until they're done.
+    pub fn is_loading(&self) -> bool {
+        self.texture_cache.get(self.current_map_key).is_none()
+    }
+
+    /// Makes `map_key` the current map. Its textures are reused if they're still cached,
+    /// otherwise they start loading and the loading screen is shown until they're done.
+    pub fn switch_map(&mut self, map_key: &'static str, map: &Map) {
+        self.current_map_key = map_key;
+
+        if self.texture_cache.touch(map_key) {
+            self.loader = None;
+        } else {
+            self.loader = Some(TextureLoader::start(
+                map,
+                &self.preload_sprites,
+                self.assets.clone(),
+                self.color_space,
+            ));
+        }
+
+        self.texture_cache.evict(map_key);
+    }
+
+    /// Drops the map's cached textures, so they're built again from the map the next time it's
+    /// switched to. Needed after its tiles change.
+    pub fn forget_map(&mut self, map_key: &str) {
+        self.texture_cache.remove(map_key);
+    }
+
+    /// Draws `image` over the whole surface in place of the view, until
+    /// [`hide_overhead`](Self::hide_overhead).
+    pub fn show_overhead(&mut self, image: &RgbaImage) {
+        let overhead_screen = self.overhead_screen.get_or_insert_with(|| {
+            let image_format = if needs_srgb_encoding(self.config.format) {
+                wgpu::TextureFormat::Rgba8Unorm
+            } else {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            };
+            OverheadScreen::new(&self.device, self.config.format, image_format)
+        });
+        overhead_screen.upload(&self.device, &self.queue, image);
+    }
+
+    pub fn hide_overhead(&mut self) {
+        self.overhead_screen = None;
+    }
+
+    /// Uploads the map's textures once the loader has decoded all of them. Returns whether
+    /// they're ready.
+    pub fn poll_textures(&mut self) -> anyhow::Result<bool> {
+        if let Some(loader) = &mut self.loader
+            && let Some(mut images) = loader.poll()
+        {
+            self.loader = None;
+            for failed in &images.failed {
+                println!(
+                    "{} in map {}, drawing a placeholder instead",
+                    failed.error, self.current_map_key
+                );
+            }
+            self.failed_assets.append(&mut images.failed);
+            self.map_images = Some((self.current_map_key, images.clone()));
+            let map_textures = self.create_map_textures(images).with_context(|| {
+                format!("failed to set up textures for map {}", self.current_map_key)
+            })?;
+            self.texture_cache
+                .insert(self.current_map_key, map_textures);
+        }
+
+        Ok(!self.is_loading())
+    }
+
+    /// Textures that failed to load and were replaced with a placeholder since this was last
+    /// called.
+    pub fn take_failed_assets(&mut self) -> Vec<AssetEvent> {
+        std::mem::take(&mut self.failed_assets)
+    }
+
+    fn create_map_textures(&self, loaded: LoadedImages) -> anyhow::Result<MapTextures> {
+        let mut wall_image_map: HashMap<usize, usize> = HashMap::new();
+        let mut floor_image_map: HashMap<usize, usize> = HashMap::new();
+        let mut ceiling_image_map: HashMap<usize, usize> = HashMap::new();
+        let mut sprite_image_map: HashMap<&'static str, usize> = HashMap::new();
+        let mut linedef_image_map: HashMap<&'static str, usize> = HashMap::new();
+
+        let mut wall_images: Vec<DynamicImage> = Vec::new();
+        let mut floor_images: Vec<DynamicImage> = Vec::new();
+        let mut ceiling_images: Vec<DynamicImage> = Vec::new();
+        let mut sprite_images: Vec<DynamicImage> = Vec::new();
+        let mut sky_images: Vec<DynamicImage> = Vec::new();
+        let mut emissive_masks: HashMap<usize, DynamicImage> = HashMap::new();
+        let mut normal_maps: HashMap<usize, DynamicImage> = HashMap::new();
+
+        for loaded_image in loaded.images {
+            let (image_map, images) = match loaded_image.category {
+                TextureCategory::Wall => (&mut wall_image_map, &mut wall_images),
+                TextureCategory::Floor => (&mut floor_image_map, &mut floor_images),
+                TextureCategory::Ceiling => (&mut ceiling_image_map, &mut ceiling_images),
+                TextureCategory::Sprite => {
+                    sprite_image_map.insert(loaded_image.path, sprite_images.len());
+                    sprite_images.push(loaded_image.image);
+                    continue;
+                }
+                TextureCategory::Sky => {
+                    // Cloud layers follow the sky and are stretched to its size
+                    let image = match sky_images.first() {
+                        Some(sky)
+                            if sky.width() > 0
+                                && loaded_image.image.width() > 0
+                                && sky.dimensions() != loaded_image.image.dimensions() =>
+                        {
+                            let (width, height) = sky.dimensions();
+                            loaded_image.image.resize_exact(
+                                width,
+                                height,
+                                image::imageops::FilterType::Triangle,
+                            )
+                        }
+                        _ => loaded_image.image,
+                    };
+                    sky_images.push(image);
+                    continue;
+                }
+                TextureCategory::Linedef => {
+                    linedef_image_map.insert(loaded_image.path, wall_images.len());
+                    wall_images.push(loaded_image.image);
+                    continue;
+                }
+                // A mask or normal map that failed to load is left out, rather than drawn
+                _ if loaded_image.image.width() == 0 && !loaded_image.category.holds_color() => {
+                    continue;
+                }
+                TextureCategory::EmissiveMask => {
+                    emissive_masks.insert(loaded_image.tile_id as usize, loaded_image.image);
+                    continue;
+                }
+                TextureCategory::NormalMap => {
+                    normal_maps.insert(loaded_image.tile_id as usize, loaded_image.image);
+                    continue;
+                }
+            };
+            image_map.insert(loaded_image.tile_id as usize, images.len());
+            images.push(loaded_image.image);
+        }
+
+        for images in [
+            &mut wall_images,
+            &mut floor_images,
+            &mut ceiling_images,
+            &mut sprite_images,
+            &mut sky_images,
+        ] {
+            texture::fill_missing(images);
+        }
+
+        let mut masked_layers: HashMap<usize, DynamicImage> = emissive_masks
+            .into_iter()
+            .filter_map(|(tile_id, mask)| Some((*wall_image_map.get(&tile_id)?, mask)))
+            .collect();
+        let mut wall_images: Vec<DynamicImage> = wall_images
+            .into_iter()
+            .enumerate()
+            .map(|(layer, image)| with_shading(image, masked_layers.remove(&layer)))
+            .collect();
+
+        let [
+            missing_wall_layer,
+            missing_floor_layer,
+            missing_ceiling_layer,
+        ] = [&mut wall_images, &mut floor_images, &mut ceiling_images].map(|images| {
+            let (width, height) = images.first().map_or(
+                (texture::BLANK_LAYER_SIZE, texture::BLANK_LAYER_SIZE),
+                GenericImageView::dimensions,
+            );
+            images.push(texture::missing_texture(width, height));
+            images.len() - 1
+        });
+
+        // Monitor layers are blank until their first frame is rendered, and follow the images
+        for (i, tile_id) in loaded.monitor_tile_ids.iter().enumerate() {
+            wall_image_map.insert(*tile_id as usize, wall_images.len() + i);
+        }
+
+        let normal_images = normal_layers(
+            normal_maps
+                .into_iter()
+                .filter_map(|(tile_id, normals)| Some((*wall_image_map.get(&tile_id)?, normals)))
+                .collect(),
+            wall_images.first().map(|image| image.dimensions()),
+            wall_images.len() + loaded.monitor_tile_ids.len(),
+        );
+
+        let textures = Textures {
+            wall_texture_arr: Texture::from_image_array(
+                &self.device,
+                &self.queue,
+                wall_images,
+                loaded.monitor_tile_ids.len() as u32,
+                "Wall Texture Array",
+            )?,
+            floor_texture_arr: Texture::from_image_array(
+                &self.device,
+                &self.queue,
+                floor_images,
+                0,
+                "Floor Texture Array",
+            )?,
+            ceiling_texture_arr: Texture::from_image_array(
+                &self.device,
+                &self.queue,
+                ceiling_images,
+                0,
+                "Ceiling Texture Array",
+            )?,
+            sprite_texture_arr: Texture::from_image_array(
+                &self.device,
+                &self.queue,
+                sprite_images,
+                0,
+                "Sprite Texture Array",
+            )?,
+            sky_texture_arr: Texture::from_image_array(
+                &self.device,
+                &self.queue,
+                sky_images,
+                0,
+                "Sky Texture Array",
+            )?,
+            wall_normal_arr: Texture::from_data_list(
+                &self.device,
+                &self.queue,
+                &normal_images,
+                Some("Wall Normal Array"),
+            )
+            .ok(),
+        };
+        let wall_texture_arr = textures.wall_texture_arr.as_ref().unwrap();
+
+        let bind_group = self.create_texture_bind_group(wall_texture_arr, "Bind Group");
+        let normal_bind_group = self.create_texture_bind_group(
+            textures.wall_normal_arr.as_ref().unwrap(),
+            "Wall Normal Bind Group",
+        );
+        let sprite_bind_group = textures
+            .sprite_texture_arr
+            .as_ref()
+            .map(|texture| self.create_texture_bind_group(texture, "Sprite Bind Group"));
+
+        let tile_layers = self.floor_pass.create_tile_layers(
+            &self.device,
+            &self.queue,
+            &loaded.tiles,
+            &floor_image_map,
+            &ceiling_image_map,
+            &wall_image_map,
+        );
+        let tile_heights = self.floor_pass.create_tile_heights(
+            &self.device,
+            &self.queue,
+            &loaded.tiles,
+            &loaded.tile_heights,
+        );
+        let tile_reflections = self.floor_pass.create_tile_reflections(
+            &self.device,
+            &self.queue,
+            &loaded.tiles,
+            &loaded.tile_reflections,
+        );
+        let floor_bind_group = self.floor_pass.create_map_bind_group(
+            &self.device,
+            &textures,
+            &tile_layers,
+            &tile_heights,
+            &tile_reflections,
+        );
+
+        let tile_walls = self.top_down_pass.create_tile_walls(
+            &self.device,
+            &self.queue,
+            &loaded.tiles,
+            &wall_image_map,
+        );
+        let top_down_bind_group = self.top_down_pass.create_map_bind_group(
+            &self.device,
+            &textures,
+            &tile_layers,
+            &tile_walls,
+            &self.floor_pass.blank_array,
+        );
+
+        let (monitor_width, monitor_height) = wall_texture_arr.cell_size();
+        let monitors = loaded
+            .monitor_tile_ids
+            .into_iter()
+            .map(|tile_id| MonitorTarget {
+                tile_id,
+                layer: wall_image_map[&(tile_id as usize)] as u32,
+                target: Texture::create_render_target(
+                    &self.device,
+                    monitor_width,
+                    monitor_height,
+                    OFFSCREEN_FORMAT,
+                    "Monitor Target",
+                ),
+                view: ViewBuffers::new(
+                    &self.device,
+                    &self.view_bind_group_layout,
+                    &self.floor_pass,
+                    &self.dither,
+                    monitor_width,
+                    monitor_height,
+                    OFFSCREEN_FORMAT,
+                    "Monitor",
+                ),
+            })
+            .collect();
+
+        Ok(MapTextures {
+            textures,
+            tile_texture_maps: TileTextureMaps {
+                wall_image_map,
+                floor_image_map,
+                ceiling_image_map,
+                sprite_image_map,
+                linedef_image_map,
+                missing_wall_layer,
+                missing_floor_layer,
+                missing_ceiling_layer,
+                warned_missing: Mutex::default(),
+            },
+            bind_group,
+            normal_bind_group,
+            sprite_bind_group,
+            tile_layers,
+            tile_heights,
+            tile_reflections,
+            floor_bind_group,
+            tile_walls,
+            top_down_bind_group,
+            monitors,
+        })
+    }
+
+    fn create_texture_bind_group(&self, texture: &Texture, label: &str) -> wgpu::BindGroup {
+        let grid = texture::grid_uniform(
+            &self.device,
+            [texture.grid, 0, 0, 0],
+            &format!("{label} Grid Buffer"),
+        );
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout: &self.texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: grid.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    fn map_textures(&self) -> anyhow::Result<&MapTextures> {
+        self.texture_cache
+            .get(self.current_map_key)
+            .context("textures are still loading")
+    }
+
+    fn map_textures_mut(&mut self) -> anyhow::Result<&mut MapTextures> {
+        self.texture_cache
+            .get_mut(self.current_map_key)
+            .context("textures are still loading")
+    }
+
+    /// Tile ids of the map's monitors, in the order the raycaster should address them.
+    pub fn monitor_tile_ids(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(self
+            .map_textures()?
+            .monitors
+            .iter()
+            .map(|m| m.tile_id)
+            .collect())
+    }
+
+    /// Resolution monitors are rendered at, which is the size of a wall texture.
+    pub fn monitor_size(&self) -> anyhow::Result<(u32, u32)> {
+        Ok(self
+            .map_textures()?
+            .textures
+            .wall_texture_arr
+            .as_ref()
+            .unwrap()
+            .cell_size())
+    }
+
+    /// The layer holding a tile's texture. Tiles without one, like a tile changed to an id the
+    /// map's textures weren't built with, get a checkerboard rather than failing the frame,
+    /// with a warning naming the tile id, category and map the first time.
+    pub fn get_texture_index(
+        &self,
+        k: u8,
+        texture_category: &TextureCategory,
+    ) -> anyhow::Result<usize> {
+        let tile_texture_maps = &self.map_textures()?.tile_texture_maps;
+        let (image_map, missing_layer) = match texture_category {
+            TextureCategory::Wall => (
+                &tile_texture_maps.wall_image_map,
+                tile_texture_maps.missing_wall_layer,
+            ),
+            TextureCategory::Floor => (
+                &tile_texture_maps.floor_image_map,
+                tile_texture_maps.missing_floor_layer,
+            ),
+            TextureCategory::Ceiling => (
+                &tile_texture_maps.ceiling_image_map,
+                tile_texture_maps.missing_ceiling_layer,
+            ),
+            TextureCategory::Sprite => anyhow::bail!("sprite layers are looked up by path"),
+            TextureCategory::Sky => anyhow::bail!("the sky isn't a tile texture"),
+            TextureCategory::Linedef => anyhow::bail!("linedef layers are looked up by path"),
+            TextureCategory::EmissiveMask => {
+                anyhow::bail!("emissive masks are baked into wall layers")
+            }
+            TextureCategory::NormalMap => {
+                anyhow::bail!("normal maps share their wall's layer")
+            }
+        };
+
+        if let Some(layer) = image_map.get(&(k as usize)) {
+            return Ok(*layer);
+        }
+        let mut warned = tile_texture_maps
+            .warned_missing
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if warned.insert((k, texture_category.name())) {
+            eprintln!(
+                "tile id {k} has no {} texture in map {}, so it's drawn as a checkerboard",
+                texture_category.name(),
+                self.current_map_key
+            );
+        }
+        Ok(missing_layer)
+    }
+
+    /// The sprite array layer holding the image at `path`.
+    pub fn sprite_layer(&self, path: &str) -> anyhow::Result<u32> {
+        let layer = self
+            .map_textures()?
+            .tile_texture_maps
+            .sprite_image_map
+            .get(path)
+            .with_context(|| format!("sprite image {path} isn't loaded for this map"))?;
+
+        Ok(*layer as u32)
+    }
+
+    /// The sprite images loaded with every map.
+    pub fn preload_sprites(&self) -> &[&'static str] {
+        &self.preload_sprites
+    }
+
+    /// Whether the sprite image at `path` is loaded with `map`, because the map uses it or it's
+    /// preloaded with every map. Checked before spawning entities, as anything else can't be
+    /// drawn.
+    pub fn sprite_loaded(&self, map: &Map, path: &str) -> bool {
+        self.preload_sprites.contains(&path) || loader::sprite_paths(map).any(|p| p == path)
+    }
+
+    /// The wall array layer holding the linedef image at `path`.
+    pub fn linedef_layer(&self, path: &str) -> anyhow::Result<u32> {
+        let layer = self
+            .map_textures()?
+            .tile_texture_maps
+            .linedef_image_map
+            .get(path)
+            .with_context(|| format!("linedef image {path} isn't loaded for this map"))?;
+
+        Ok(*layer as u32)
+    }
+
+    pub fn delta_time(&self) -> Duration {
+        self.delta_time
+    }
+
+    /// The format the surface was configured with.
+    pub fn surface_format(&self) -> wgpu::TextureFormat {
+        self.config.format
+    }
+
+    /// When the next frame is due if the frame rate is capped.
+    pub fn next_frame_time(&self) -> Option<Instant> {
+        Some(self.last_frame_time? + self.frame_interval()?)
+    }
+
+    /// Caps the frame rate while the window is in the background, or lifts the cap with `None`
+    /// to go back to the configured one.
+    pub fn set_background_frame_cap(&mut self, frame_cap: Option<u32>) {
+        self.background_frame_interval =
+            frame_cap.map(|fps| Duration::from_secs_f64(1.0 / fps.max(1) as f64));
+    }
+
+    /// Saves the next rendered frame as a PNG.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn request_screenshot(&mut self, path: PathBuf) {
+        self.screenshot_path = Some(path);
+    }
+
+    /// Copies the frame back if a screenshot was asked for or the capture keeps it, with the
+    /// screenshot's path and whether the capture does. Surfaces frames can't be read back
+    /// from skip both, which is said once.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn copy_frame(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) -> anyhow::Result<Option<(Readback, Option<PathBuf>, bool)>> {
+        let keep_for_capture = self
+            .capturer
+            .as_mut()
+            .is_some_and(|capturer| capturer.next_frame());
+        // Taken even when the frame can't be read, so it isn't tried again every frame
+        let screenshot_path = self.screenshot_path.take();
+        if !keep_for_capture && screenshot_path.is_none() {
+            return Ok(None);
+        }
+        if !self.config.usage.contains(wgpu::TextureUsages::COPY_SRC)
+            || !Readback::supports(texture.format())
+        {
+            if !self.readback_unsupported_logged {
+                eprintln!(
+                    "Frames can't be read back from this {:?} surface, so screenshots and \
+                     captures are skipped",
+                    texture.format(),
+                );
+                self.readback_unsupported_logged = true;
+            }
+            return Ok(None);
+        }
+
+        let readback = Readback::copy(&self.device, encoder, texture)?;
+        Ok(Some((readback, screenshot_path, keep_for_capture)))
+    }
+
+    /// Saves the screenshot and hands the frame to the capturer. Neither stops the game when it
+    /// fails, like in a read-only directory or on a full disk: the error is logged and a failed
+    /// capture is stopped.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_frame(
+        &mut self,
+        readback: Readback,
+        screenshot_path: Option<PathBuf>,
+        keep_for_capture: bool,
+    ) -> anyhow::Result<()> {
+        let image = readback.read(&self.device)?;
+
+        if let Some(path) = screenshot_path {
+            if let Err(err) = save_screenshot(&image, &path) {
+                eprintln!("{err:#}");
+            } else {
+                println!("Saved screenshot to {}", path.display());
+            }
+        }
+
+        if keep_for_capture
+            && let Some(capturer) = &mut self.capturer
+            && let Err(err) = capturer.write(&image)
+        {
+            eprintln!("{err:#}, so capturing stopped");
+            self.capturer = None;
+        }
+
+        Ok(())
+    }
+
+    /// Sets the full-screen effect applied to the main view from the next frame on. The view
+    /// is drawn through a blit pass from then on if it wasn't already. In photosensitive safe
+    /// mode its tint is capped and eased towards over the frame's time.
+    /// Multiplies the colors of everything in the views but the sky, from the next frame.
+    pub fn set_ambient_light(&mut self, light: [f32; 3]) {
+        self.ambient_light = light;
+    }
+
+    pub fn set_post_effect(&mut self, effect: PostEffect) {
+        let effect = if self.photosensitive_safe {
+            effect.limited(&self.post_effect, self.delta_time.as_secs_f32())
+        } else {
+            effect
+        };
+        if self.blit.is_none() && !effect.is_none() {
+            self.blit = Some(Blit::new(
+                &self.device,
+                self.config.format,
+                self.screen_view.width,
+                self.screen_view.height,
+                wgpu::FilterMode::Linear,
+            ));
+        }
+        self.post_effect = effect;
+    }
+
+    /// Draws the view twice side by side, each squeezed into half its width: the main view's
+    /// instances on the left and the `set_stereo_*` ones on the right. The view is drawn
+    /// through a blit pass from then on if it wasn't already.
+    pub fn set_stereo(&mut self, stereo: bool) {
+        if !stereo {
+            self.stereo = None;
+            return;
+        }
+        if self.stereo.is_some() {
+            return;
+        }
+
+        let (width, height) = self.internal_size();
+        if self.blit.is_none() {
+            self.blit = Some(Blit::new(
+                &self.device,
+                self.config.format,
+                width,
+                height,
+                wgpu::FilterMode::Linear,
+            ));
+        }
+        self.stereo = Some(StereoEye {
+            blit: Blit::new(
+                &self.device,
+                self.config.format,
+                width,
+                height,
+                wgpu::FilterMode::Linear,
+            ),
+            view: ViewBuffers::new(
+                &self.device,
+                &self.view_bind_group_layout,
+                &self.floor_pass,
+                &self.dither,
+                width,
+                height,
+                self.config.format,
+                "Right Eye",
+            ),
+        });
+    }
+
+    /// Draws the `set_rear_view_*` instances into `area` over the view, or stops with `None`.
+    /// Its target is sized to the area of the internal resolution. The view is drawn through
+    /// a blit pass from then on if it wasn't already.
+    pub fn set_rear_view(&mut self, area: Option<RearView>) {
+        let Some(area) = area else {
+            self.rear_view = None;
+            return;
+        };
+
+        let (width, height) = self.internal_size();
+        if self.blit.is_none() {
+            self.blit = Some(Blit::new(
+                &self.device,
+                self.config.format,
+                width,
+                height,
+                wgpu::FilterMode::Linear,
+            ));
+        }
+        let Rect { width, height, .. } = rear_view_rect(
+            &area,
+            Rect {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            },
+        );
+        self.rear_view = Some(RearViewTarget {
+            area,
+            blit: Blit::new(
+                &self.device,
+                self.config.format,
+                width,
+                height,
+                wgpu::FilterMode::Linear,
+            ),
+            view: ViewBuffers::new(
+                &self.device,
+                &self.view_bind_group_layout,
+                &self.floor_pass,
+                &self.dither,
+                width,
+                height,
+                self.config.format,
+                "Rear View",
+            ),
+            msaa_view: self.msaa_view.as_ref().map(|msaa_view| {
+                create_msaa_view(
+                    &self.device,
+                    self.config.format,
+                    width,
+                    height,
+                    msaa_view.texture().sample_count(),
+                )
+            }),
+            shown: true,
+        });
+    }
+
+    /// The rear-view mirror's resolution, if there is one.
+    pub fn rear_view_size(&self) -> Option<(u32, u32)> {
+        self.rear_view
+            .as_ref()
+            .map(|mirror| (mirror.view.width, mirror.view.height))
+    }
+
+    /// Hides the rear-view mirror without dropping it, such as while a menu is open.
+    pub fn show_rear_view(&mut self, shown: bool) {
+        if let Some(mirror) = &mut self.rear_view {
+            mirror.shown = shown;
+        }
+    }
+
+    /// Draws the map from above with `camera` in place of the main view, with `icons` over it,
+    /// until [`hide_top_down`](Self::hide_top_down). Labels are still drawn over it.
+    pub fn show_top_down(&mut self, camera: &TopDownCamera, icons: &[FlatSpriteInstance]) {
+        self.top_down_pass.write(&self.queue, camera, icons);
+        self.top_down = true;
+    }
+
+    pub fn hide_top_down(&mut self) {
+        self.top_down = false;
+    }
+
+    pub fn set_photosensitive_safe(&mut self, photosensitive_safe: bool) {
+        self.photosensitive_safe = photosensitive_safe;
+    }
+
+    /// Opens another window onto the same device, showing nothing until
+    /// [`set_window_image`](Self::set_window_image). Returns the index the other window
+    /// methods take.
+    pub fn add_window(
+        &mut self,
+        window: Arc<dyn wgpu::WindowHandle>,
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<usize> {
+        let surface = self.instance.create_surface(window)?;
+        self.windows.push(Some(WindowSurface::new(
+            surface,
+            &self.adapter,
+            &self.device,
+            self.renderer_config.surface_format,
+            width,
+            height,
+        )));
+        Ok(self.windows.len() - 1)
+    }
+
+    pub fn resize_window(&mut self, index: usize, width: u32, height: u32) {
+        if let Some(Some(window)) = self.windows.get_mut(index) {
+            window.resize(&self.device, width, height);
+        }
+    }
+
+    /// Stops drawing to a window, before it's closed.
+    pub fn close_window(&mut self, index: usize) {
+        if let Some(window) = self.windows.get_mut(index) {
+            *window = None;
+        }
+    }
+
+    /// Replaces what a window shows, from the next frame.
+    pub fn set_window_image(&mut self, index: usize, image: &RgbaImage) {
+        if let Some(Some(window)) = self.windows.get_mut(index) {
+            window.set_image(&self.device, &self.queue, image);
+        }
+    }
+
+    fn draw_windows(&self) -> anyhow::Result<()> {
+        for window in self.windows.iter().flatten() {
+            window.draw(&self.device, &self.queue)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the GPU device has been lost, such as on a driver reset or a switch between
+    /// GPUs. Nothing is drawn until [`recover`](Self::recover) replaces it.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    /// Replaces a lost device with a new one presenting to the same window, and makes the
+    /// pipelines, targets and textures again. The current map's textures are uploaded from
+    /// the images kept from loading them, or loaded again if they hadn't finished; other maps'
+    /// are loaded again when they're next visited. Settings changed since startup carry over.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn recover(&mut self, map: &Map) -> anyhow::Result<()> {
+        let size = PhysicalSize::new(self.config.width, self.config.height);
+        let mut renderer = pollster::block_on(Self::with_surface(
+            self.instance.clone(),
+            self.surface.clone(),
+            size,
+            self.current_map_key,
+            self.assets.clone(),
+            &self.renderer_config,
+        ))?;
+        renderer.resize(size.width, size.height);
+
+        match self.map_images.take() {
+            Some((map_key, images)) if map_key == self.current_map_key => {
+                let map_textures = renderer.create_map_textures(images.clone())?;
+                renderer.texture_cache.insert(map_key, map_textures);
+                renderer.map_images = Some((map_key, images));
+            }
+            _ => {
+                renderer.loader = Some(TextureLoader::start(
+                    map,
+                    &self.preload_sprites,
+                    self.assets.clone(),
+                    self.color_space,
+                ));
+            }
+        }
+
+        renderer.set_photosensitive_safe(self.photosensitive_safe);
+        renderer.set_ambient_light(self.ambient_light);
+        renderer.set_post_effect(mem::take(&mut self.post_effect));
+        renderer.set_stereo(self.stereo.is_some());
+        if let Some(mirror) = &self.rear_view {
+            renderer.set_rear_view(Some(mirror.area));
+            renderer.show_rear_view(mirror.shown);
+        }
+        renderer.top_down = self.top_down;
+        renderer.background_frame_interval = self.background_frame_interval;
+        renderer.capturer = self.capturer.take();
+        // Their images are given again as they next change
+        renderer.windows = self
+            .windows
+            .drain(..)
+            .map(|window| {
+                window.map(|window| {
+                    let (width, height) = window.size();
+                    WindowSurface::new(
+                        window.surface,
+                        &renderer.adapter,
+                        &renderer.device,
+                        self.renderer_config.surface_format,
+                        width,
+                        height,
+                    )
+                })
+            })
+            .collect();
+        renderer.screenshot_path = self.screenshot_path.take();
+
+        *self = renderer;
+        println!("Recovered from the GPU device loss");
+        Ok(())
+    }
+
+    /// What the GPU can do, gathered at startup.
+    pub fn device_report(&self) -> &DeviceReport {
+        &self.device_report
+    }
+
+    /// The most recent GPU timings, if profiling is on and a measurement has come back.
+    pub fn gpu_timings(&self) -> Option<GpuTimings> {
+        self.profiler.as_ref().and_then(GpuProfiler::latest)
+    }
+
+    fn frame_interval(&self) -> Option<Duration> {
+        self.background_frame_interval.or(self.frame_interval)
+    }
+}
+
+fn create_instance(renderer_config: &RendererConfig) -> wgpu::Instance {
+    wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: match renderer_config.backend {
+            Backend::Auto => wgpu::Backends::PRIMARY,
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Dx12 => wgpu::Backends::DX12,
+            Backend::Gl => wgpu::Backends::GL,
+            Backend::BrowserWebGpu => wgpu::Backends::BROWSER_WEBGPU,
+        },
+        ..Default::default()
+    })
+}
+
+/// Opens a device on an adapter that can present to `surface`.
+async fn wgpu_init(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface<'static>,
+    size: PhysicalSize<u32>,
+    renderer_config: &RendererConfig,
+) -> anyhow::Result<(
+    wgpu::Adapter,
+    wgpu::Device,
+    wgpu::Queue,
+    wgpu::SurfaceConfiguration,
+)> {
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: match renderer_config.power_preference {
+                PowerPreference::HighPerformance => wgpu::PowerPreference::HighPerformance,
+                PowerPreference::LowPower => wgpu::PowerPreference::LowPower,
+            },
+            compatible_surface: Some(surface),
+            force_fallback_adapter: false,
+        })
+        .await?;
+
+    let (device, queue) = adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            label: None,
+            // Profiling is skipped on adapters without timestamp queries
+            required_features: if renderer_config.gpu_profiling {
+                adapter.features() & GpuProfiler::FEATURES
+            } else {
+                wgpu::Features::empty()
+            },
+            experimental_features: wgpu::ExperimentalFeatures::disabled(),
+            // Bigger and more textures per map where the GPU allows them
+            required_limits: wgpu::Limits {
+                max_texture_dimension_2d: adapter.limits().max_texture_dimension_2d,
+                max_texture_array_layers: adapter.limits().max_texture_array_layers,
+                ..wgpu::Limits::default()
+            },
+            memory_hints: Default::default(),
+            trace: wgpu::Trace::Off,
+        })
+        .await?;
+
+    let surface_caps = surface.get_capabilities(&adapter);
+
+    let surface_format =
+        choose_surface_format(renderer_config.surface_format, &surface_caps.formats);
+
+    // Reading frames back for screenshots and capture needs to copy from the surface
+    let usage = wgpu::TextureUsages::RENDER_ATTACHMENT
+        | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC);
+
+    let config = wgpu::SurfaceConfiguration {
+        usage,
+        format: surface_format,
         width: size.width,
         height: size.height,
-        present_mode: surface_caps.present_modes[0],
+        present_mode: choose_present_mode(
+            renderer_config.present_mode,
+            &surface_caps.present_modes,
+        ),
         alpha_mode: surface_caps.alpha_modes[0],
         view_formats: vec![],
         desired_maximum_frame_latency: 2,
     };
 
-    Ok((surface, device, queue, config))
+    Ok((adapter, device, queue, config))
 }
 
-pub fn load_textures(
-    map: &Map,
-    device: &wgpu::Device,
-    queue: &wgpu::Queue,
-) -> anyhow::Result<(Textures, TileTextureMaps)> {
-    let mut wall_image_map: HashMap<usize, usize> = HashMap::new();
-    let mut floor_image_map: HashMap<usize, usize> = HashMap::new();
-    let mut ceiling_image_map: HashMap<usize, usize> = HashMap::new();
-
-    let mut wall_byte_array: Vec<Vec<u8>> = Vec::new();
-    let mut floor_byte_array: Vec<Vec<u8>> = Vec::new();
-    let mut ceiling_byte_array: Vec<Vec<u8>> = Vec::new();
-
-    for (k, v) in map.tile_types() {
-        match v {
-            TileType::Wall(data) => {
-                wall_image_map.insert(*k as usize, wall_byte_array.len());
-
-                wall_byte_array.push(load_asset(data.texture_path)?);
-            }
-            TileType::Floor(data) => {
-                floor_image_map.insert(*k as usize, floor_byte_array.len());
+fn choose_surface_format(
+    preference: SurfaceFormatPreference,
+    supported: &[wgpu::TextureFormat],
+) -> wgpu::TextureFormat {
+    let preferred = match preference {
+        SurfaceFormatPreference::Srgb => supported.iter().find(|f| f.is_srgb()),
+        SurfaceFormatPreference::Linear => supported
+            .iter()
+            .find(|f| !f.is_srgb() && needs_srgb_encoding(**f)),
+        SurfaceFormatPreference::HighBitDepth => supported
+            .iter()
+            .find(|f| **f == wgpu::TextureFormat::Rgba16Float)
+            .or_else(|| {
+                supported
+                    .iter()
+                    .find(|f| **f == wgpu::TextureFormat::Rgb10a2Unorm)
+            }),
+    };
 
-                floor_byte_array.push(load_asset(data.texture_path)?);
-            }
-            TileType::Ceiling(data) => {
-                ceiling_image_map.insert(*k as usize, ceiling_byte_array.len());
+    preferred.copied().unwrap_or(supported[0])
+}
 
-                ceiling_byte_array.push(load_asset(data.texture_path)?);
-            }
-            TileType::FloorCeiling(data) => {
-                floor_image_map.insert(*k as usize, floor_byte_array.len());
-                ceiling_image_map.insert(*k as usize, ceiling_byte_array.len() + 1);
+/// Whether the shader has to gamma encode its output itself. sRGB formats encode on write
+/// and float formats hold linear values.
+fn needs_srgb_encoding(format: wgpu::TextureFormat) -> bool {
+    !format.is_srgb()
+        && !matches!(
+            format,
+            wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rgba32Float
+        )
+}
 
-                floor_byte_array.push(load_asset(data.texture_path_f)?);
-                ceiling_byte_array.push(load_asset(data.texture_path_c)?);
-            }
-        };
+fn choose_present_mode(
+    present_mode: PresentMode,
+    supported: &[wgpu::PresentMode],
+) -> wgpu::PresentMode {
+    let wanted = match present_mode {
+        PresentMode::Auto => return supported[0],
+        PresentMode::Fifo => wgpu::PresentMode::Fifo,
+        PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        PresentMode::Immediate => wgpu::PresentMode::Immediate,
+    };
+
+    if supported.contains(&wanted) {
+        wanted
+    } else {
+        wgpu::PresentMode::Fifo
+    }
+}
+
+/// The highest sample count up to `requested` that the format supports, or 1.
+fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    format: wgpu::TextureFormat,
+    requested: u32,
+) -> u32 {
+    let flags = adapter.get_texture_format_features(format).flags;
+    let count = [16, 8, 4, 2]
+        .into_iter()
+        .find(|count| *count <= requested && flags.sample_count_supported(*count))
+        .unwrap_or(1);
+
+    if count != requested {
+        println!("MSAA x{requested} is not supported for {format:?}, using x{count}");
     }
+    count
+}
+
+fn create_msaa_view(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    sample_count: u32,
+) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("MSAA Target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+fn create_sprite_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    let vertex_buffer_layouts = [
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2],
+        },
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SpriteInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &wgpu::vertex_attr_array![2 => Float32, 3 => Float32, 4 => Float32, 5 => Float32, 6 => Uint32, 7 => Float32x4, 8 => Float32x4],
+        },
+    ];
 
-    let wall_texture_arr =
-        texture::Texture::from_bytes_array(device, queue, &wall_byte_array, "Wall Texture Array");
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Sprite Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &vertex_buffer_layouts,
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Cw,
+            cull_mode: Some(wgpu::Face::Back),
+            unclipped_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview_mask: None,
+        cache: Default::default(),
+    })
+}
+
+/// One layer per wall layer for the wall normal array: the normal map given for the layer,
+/// stretched to the walls' `size`, or a flat one. Single pixels when no wall has a normal map,
+/// since they all face straight out.
+fn normal_layers(
+    mut normal_maps: HashMap<usize, DynamicImage>,
+    size: Option<(u32, u32)>,
+    layers: usize,
+) -> Vec<DynamicImage> {
+    let (width, height) = match size {
+        Some(size) if !normal_maps.is_empty() => size,
+        _ => (1, 1),
+    };
+    let flat = DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+        width,
+        height,
+        image::Rgba([128, 128, 255, 255]),
+    ));
+    (0..layers.max(1))
+        .map(|layer| match normal_maps.remove(&layer) {
+            Some(normals) if normals.dimensions() != (width, height) => {
+                normals.resize_exact(width, height, image::imageops::FilterType::Triangle)
+            }
+            Some(normals) => normals,
+            None => flat.clone(),
+        })
+        .collect()
+}
 
-    let floor_texture_arr =
-        texture::Texture::from_bytes_array(device, queue, &floor_byte_array, "Floor Texture Array");
+/// Stores in a wall image's alpha how much fog and light shade it: not at all where `mask` is
+/// white, for the texture's full-bright parts, and fully everywhere else. Walls are drawn
+/// opaque, so the channel is free.
+fn with_shading(image: DynamicImage, mask: Option<DynamicImage>) -> DynamicImage {
+    let mut image = image.into_rgba8();
+    let (width, height) = image.dimensions();
+    let mask = mask.map(|mask| {
+        if mask.dimensions() == (width, height) {
+            mask.into_luma8()
+        } else {
+            mask.resize_exact(width, height, image::imageops::FilterType::Triangle)
+                .into_luma8()
+        }
+    });
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        pixel[3] = mask
+            .as_ref()
+            .map_or(u8::MAX, |mask| u8::MAX - mask.get_pixel(x, y)[0]);
+    }
+    DynamicImage::ImageRgba8(image)
+}
 
-    let ceiling_texture_arr = texture::Texture::from_bytes_array(
-        device,
-        queue,
-        &ceiling_byte_array,
-        "Ceiling Texture Array",
-    );
+fn create_wall_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    let vertex_buffer_layouts = [
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2],
+        },
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<WallInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &wgpu::vertex_attr_array![2 => Float32, 3 => Float32, 4 => Float32, 5 => Float32, 6 => Uint32, 7 => Float32, 8 => Float32, 9 => Float32x4, 10 => Float32x3],
+        },
+    ];
 
-    Ok((
-        Textures {
-            wall_texture_arr,
-            floor_texture_arr,
-            ceiling_texture_arr,
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &vertex_buffer_layouts,
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent::REPLACE,
+                    alpha: wgpu::BlendComponent::REPLACE,
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Cw,
+            cull_mode: Some(wgpu::Face::Back),
+            unclipped_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
         },
-        TileTextureMaps {
-            wall_image_map,
-            floor_image_map,
-            ceiling_image_map,
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
         },
-    ))
+        multiview_mask: None,
+        cache: Default::default(),
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_screenshot(image: &RgbaImage, path: &std::path::Path) -> anyhow::Result<()> {
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)
+            .with_context(|| format!("failed to create {}", directory.display()))?;
+    }
+    image
+        .save(path)
+        .with_context(|| format!("failed to save screenshot {}", path.display()))
 }
@@ -0,0 +1,38 @@
+@group(0) @binding(0) var map_image: texture_2d<f32>;
+@group(0) @binding(1) var map_sampler: sampler;
+
+struct OverheadUniform {
+    // The fraction of the screen the image covers on each axis
+    scale: vec2f,
+    _pad: vec2f,
+};
+@group(0) @binding(2) var<uniform> overhead: OverheadUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2f,
+};
+
+// A single triangle that covers the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2f(f32((index << 1u) & 2u), f32(index & 2u));
+
+    out.clip_position = vec4f(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    // Centered on the screen, letterboxed to keep the map's tiles square
+    let uv = (input.uv - 0.5) / overhead.scale + 0.5;
+    let color = textureSample(map_image, map_sampler, uv);
+
+    if any(uv < vec2f(0.0)) || any(uv > vec2f(1.0)) {
+        return vec4f(0.02, 0.02, 0.02, 1.0);
+    }
+    return color;
+}
@@ -0,0 +1,149 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::profiling::GpuTimings;
+
+/// Points in a frame a timestamp is written at.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Timestamp {
+    FloorStart,
+    WallsStart,
+    SpritesStart,
+    ViewEnd,
+    PostProcessStart,
+    PostProcessEnd,
+}
+
+const TIMESTAMP_COUNT: u32 = 6;
+const BUFFER_SIZE: wgpu::BufferAddress = TIMESTAMP_COUNT as wgpu::BufferAddress * 8;
+
+/// Times the passes of the main view with timestamp queries. One frame is measured at a time;
+/// frames rendered while its results are still on their way back aren't measured.
+pub(crate) struct GpuProfiler {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    /// Set from the map callback once the readback buffer can be read
+    mapped: Arc<AtomicBool>,
+    in_flight: bool,
+    /// Whether the frame being measured had a post-processing pass
+    post_process: bool,
+    latest: Option<GpuTimings>,
+}
+
+impl GpuProfiler {
+    /// The device features profiling needs.
+    pub const FEATURES: wgpu::Features =
+        wgpu::Features::TIMESTAMP_QUERY.union(wgpu::Features::TIMESTAMP_QUERY_INSIDE_PASSES);
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Timestamp Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: TIMESTAMP_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Resolve Buffer"),
+            size: BUFFER_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Readback Buffer"),
+            size: BUFFER_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            mapped: Arc::new(AtomicBool::new(false)),
+            in_flight: false,
+            post_process: false,
+            latest: None,
+        }
+    }
+
+    /// Collects the previous measurement if it's back. Returns whether this frame can be
+    /// measured.
+    pub fn begin_frame(&mut self, device: &wgpu::Device, post_process: bool) -> bool {
+        if self.in_flight {
+            let _ = device.poll(wgpu::PollType::Poll);
+            if !self.mapped.swap(false, Ordering::Acquire) {
+                return false;
+            }
+
+            {
+                let data = self.readback_buffer.slice(..).get_mapped_range();
+                let ticks: &[u64] = bytemuck::cast_slice(&data);
+                let between = |start: Timestamp, end: Timestamp| {
+                    let ticks = ticks[end as usize].saturating_sub(ticks[start as usize]);
+                    Duration::from_nanos((ticks as f64 * self.period as f64) as u64)
+                };
+                self.latest = Some(GpuTimings {
+                    floor: between(Timestamp::FloorStart, Timestamp::WallsStart),
+                    walls: between(Timestamp::WallsStart, Timestamp::SpritesStart),
+                    sprites: between(Timestamp::SpritesStart, Timestamp::ViewEnd),
+                    post_process: self
+                        .post_process
+                        .then(|| between(Timestamp::PostProcessStart, Timestamp::PostProcessEnd)),
+                });
+            }
+            self.readback_buffer.unmap();
+            self.in_flight = false;
+        }
+
+        self.post_process = post_process;
+        true
+    }
+
+    pub fn write(&self, render_pass: &mut wgpu::RenderPass, timestamp: Timestamp) {
+        render_pass.write_timestamp(&self.query_set, timestamp as u32);
+    }
+
+    /// Timestamps for the start and end of the post-processing pass.
+    pub fn post_process_writes(&self) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(Timestamp::PostProcessStart as u32),
+            end_of_pass_write_index: Some(Timestamp::PostProcessEnd as u32),
+        }
+    }
+
+    /// Records copying the frame's timestamps to where they can be read.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.resolve_query_set(&self.query_set, 0..TIMESTAMP_COUNT, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            BUFFER_SIZE,
+        );
+    }
+
+    /// Starts reading the timestamps back, once the frame has been submitted.
+    pub fn finish_frame(&mut self) {
+        let mapped = self.mapped.clone();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                mapped.store(result.is_ok(), Ordering::Release);
+            });
+        self.in_flight = true;
+    }
+
+    pub fn latest(&self) -> Option<GpuTimings> {
+        self.latest
+    }
+}
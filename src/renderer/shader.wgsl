@@ -1,5 +1,32 @@
 @group(0) @binding(0) var wall_atlas: texture_2d_array<f32>;
 @group(0) @binding(1) var wall_sampler: sampler;
+// Images per side in each layer, in `x`. The normal maps are packed the same way.
+@group(0) @binding(2) var<uniform> wall_grid: vec4u;
+
+struct ViewUniform {
+    screen_size: vec2f,
+    // Set when the target format doesn't gamma encode on write
+    encode_srgb: u32,
+    // Set when colors should be dithered before the target quantizes them
+    dither: u32,
+    // Multiplies everything but the sky, for the time of day
+    light: vec3f,
+    // How much walls, floors and ceilings darken where they meet
+    ambient_occlusion: f32,
+};
+
+@group(1) @binding(0) var<uniform> view: ViewUniform;
+// Tiled blue noise, one threshold per pixel
+@group(1) @binding(1) var blue_noise: texture_2d<f32>;
+
+// A normal map per wall layer
+@group(2) @binding(0) var normal_atlas: texture_2d_array<f32>;
+@group(2) @binding(1) var normal_sampler: sampler;
+
+// How strongly normal maps brighten and darken the texture
+const RELIEF_STRENGTH: f32 = 0.8;
+// How far contact shadows reach from a wall's top and bottom, as a fraction of its height
+const OCCLUSION_EXTENT: f32 = 0.15;
 
 struct VertexInput {
     @location(0) position: vec2f,
@@ -9,35 +36,98 @@ struct VertexInput {
     @location(4) height: f32,
     @location(5) tex_u: f32,
     @location(6) tex_layer: u32,
+    // Screen row below which a step or ramp in front hides the wall
+    @location(7) floor_clip: f32,
+    // Screen row above which a lower ceiling in front hides the wall
+    @location(8) ceiling_clip: f32,
+    // The fog color, and in alpha how much of it covers the wall
+    @location(9) fog: vec4f,
+    // Direction to the relief light, across the texture, up it and out of the wall
+    @location(10) relief_light: vec3f,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2f,
     @location(1) tex_layer: u32,
+    @location(2) fog: vec4f,
+    @location(3) relief_light: vec3f,
 };
 
-const SCREEN_W = 1280;
-const SCREEN_H = 720;
-
 @vertex
 fn vs_main(input: VertexInput) -> VertexOutput {
     var out: VertexOutput;
+    // Clipping crops the texture rather than squashing it
+    let bottom = input.top + input.height;
+    let clipped_top = clamp(input.ceiling_clip, input.top, bottom);
+    let clipped_bottom = clamp(input.floor_clip, clipped_top, bottom);
     let pixel_x = input.screen_x + input.position.x;
-    let pixel_y = input.top + input.position.y * input.height;
-    let ndc_x = (pixel_x / SCREEN_W) * 2.0 - 1.0;
-    let ndc_y = 1.0 - (pixel_y / SCREEN_H) * 2.0;
+    let pixel_y = mix(clipped_top, clipped_bottom, input.position.y);
+    let ndc_x = (pixel_x / view.screen_size.x) * 2.0 - 1.0;
+    let ndc_y = 1.0 - (pixel_y / view.screen_size.y) * 2.0;
 
     out.clip_position = vec4f(ndc_x, ndc_y, 0.0, 1.0);
-    out.tex_coords = vec2f(input.tex_u, input.position.y);
+    out.tex_coords = vec2f(input.tex_u, (pixel_y - input.top) / max(input.height, 1e-6));
     out.tex_layer = input.tex_layer;
+    out.fog = input.fog;
+    out.relief_light = input.relief_light;
 
     return out;
 }
 
+fn linear_to_srgb(color: vec3f) -> vec3f {
+    let lower = color * 12.92;
+    let higher = 1.055 * pow(color, vec3f(1.0 / 2.4)) - 0.055;
+    return select(higher, lower, color <= vec3f(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3f) -> vec3f {
+    let lower = color / 12.92;
+    let higher = pow((color + 0.055) / 1.055, vec3f(2.4));
+    return select(higher, lower, color <= vec3f(0.04045));
+}
+
+// Nudges the color by up to half a step of 8 bit output either way, in gamma space where the
+// steps are, so gradients come out as fine noise rather than bands
+fn dither(color: vec3f, pixel: vec2f) -> vec3f {
+    if view.dither == 0u {
+        return color;
+    }
+    let noise = textureLoad(blue_noise, vec2u(pixel) % textureDimensions(blue_noise), 0).r;
+    let encoded = linear_to_srgb(max(color, vec3f(0.0))) + (noise - 0.5) / 255.0;
+    return srgb_to_linear(max(encoded, vec3f(0.0)));
+}
+
+// Arrays with more images than the GPU allows layers pack them `grid` to a side in each
+// layer. Coordinates stay half a texel inside the image so filtering can't reach the next one.
+fn atlas_uv(uv: vec2f, index: u32, grid: u32, size: vec2u) -> vec2f {
+    let cell = index % (grid * grid);
+    let inset = 0.5 * f32(grid) / vec2f(size);
+    let inner = clamp(uv, inset, vec2f(1.0) - inset);
+    return (vec2f(f32(cell % grid), f32(cell / grid)) + inner) / f32(grid);
+}
+
+fn atlas_layer(index: u32, grid: u32) -> u32 {
+    return index / (grid * grid);
+}
+
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-    let color = textureSample(wall_atlas, wall_sampler, input.tex_coords, input.tex_layer);
+    // Alpha is how much the texel is shaded, so emissive parts skip the fog and light
+    let uv = atlas_uv(input.tex_coords, input.tex_layer, wall_grid.x, textureDimensions(wall_atlas));
+    let layer = atlas_layer(input.tex_layer, wall_grid.x);
+    let texel = textureSample(wall_atlas, wall_sampler, uv, layer);
     // return vec4f(1.0, 0.0, 0.0, 1.0);
-    return vec4f(color.rgb, 1.0);
+    let normal = textureSample(normal_atlas, normal_sampler, uv, layer).rgb * 2.0 - 1.0;
+    // Only the difference from a flat wall counts, so walls without normal maps look the same
+    let relief = 1.0 + (dot(normal, input.relief_light) - input.relief_light.z) * RELIEF_STRENGTH;
+    let edge = min(input.tex_coords.y, 1.0 - input.tex_coords.y);
+    let occlusion = 1.0 - view.ambient_occlusion * (1.0 - smoothstep(0.0, OCCLUSION_EXTENT, edge));
+    let lit = texel.rgb * view.light * max(relief, 0.0) * occlusion;
+    let shaded = mix(lit, input.fog.rgb, input.fog.a);
+    let color = dither(mix(texel.rgb, shaded, texel.a), input.clip_position.xy);
+    if view.encode_srgb != 0u {
+        return vec4f(linear_to_srgb(color), 1.0);
+    }
+    return vec4f(color, 1.0);
 }
@@ -0,0 +1,110 @@
+@group(0) @binding(0) var sprite_atlas: texture_2d_array<f32>;
+@group(0) @binding(1) var sprite_sampler: sampler;
+// Images per side in each layer, in `x`
+@group(0) @binding(2) var<uniform> sprite_grid: vec4u;
+
+struct ViewUniform {
+    screen_size: vec2f,
+    // Set when the target format doesn't gamma encode on write
+    encode_srgb: u32,
+    // Set when colors should be dithered before the target quantizes them
+    dither: u32,
+    // Multiplies everything but the sky, for the time of day
+    light: vec3f,
+    // How much walls, floors and ceilings darken where they meet
+    ambient_occlusion: f32,
+};
+
+@group(1) @binding(0) var<uniform> view: ViewUniform;
+// Tiled blue noise, one threshold per pixel
+@group(1) @binding(1) var blue_noise: texture_2d<f32>;
+
+struct VertexInput {
+    @location(0) position: vec2f,
+    @location(1) uv: vec2f,
+    @location(2) screen_x: f32,
+    @location(3) top: f32,
+    @location(4) height: f32,
+    @location(5) tex_u: f32,
+    @location(6) tex_layer: u32,
+    @location(7) tint: vec4f,
+    // The fog color, and in alpha how much of it covers the sprite
+    @location(8) fog: vec4f,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2f,
+    @location(1) tex_layer: u32,
+    @location(2) tint: vec4f,
+    @location(3) fog: vec4f,
+};
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let pixel_x = input.screen_x + input.position.x;
+    let pixel_y = input.top + input.position.y * input.height;
+    let ndc_x = (pixel_x / view.screen_size.x) * 2.0 - 1.0;
+    let ndc_y = 1.0 - (pixel_y / view.screen_size.y) * 2.0;
+
+    out.clip_position = vec4f(ndc_x, ndc_y, 0.0, 1.0);
+    out.tex_coords = vec2f(input.tex_u, input.position.y);
+    out.tex_layer = input.tex_layer;
+    out.tint = input.tint;
+    out.fog = input.fog;
+
+    return out;
+}
+
+fn linear_to_srgb(color: vec3f) -> vec3f {
+    let lower = color * 12.92;
+    let higher = 1.055 * pow(color, vec3f(1.0 / 2.4)) - 0.055;
+    return select(higher, lower, color <= vec3f(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3f) -> vec3f {
+    let lower = color / 12.92;
+    let higher = pow((color + 0.055) / 1.055, vec3f(2.4));
+    return select(higher, lower, color <= vec3f(0.04045));
+}
+
+// Nudges the color by up to half a step of 8 bit output either way, in gamma space where the
+// steps are, so gradients come out as fine noise rather than bands
+fn dither(color: vec3f, pixel: vec2f) -> vec3f {
+    if view.dither == 0u {
+        return color;
+    }
+    let noise = textureLoad(blue_noise, vec2u(pixel) % textureDimensions(blue_noise), 0).r;
+    let encoded = linear_to_srgb(max(color, vec3f(0.0))) + (noise - 0.5) / 255.0;
+    return srgb_to_linear(max(encoded, vec3f(0.0)));
+}
+
+// Arrays with more images than the GPU allows layers pack them `grid` to a side in each
+// layer. Coordinates stay half a texel inside the image so filtering can't reach the next one.
+fn atlas_uv(uv: vec2f, index: u32, grid: u32, size: vec2u) -> vec2f {
+    let cell = index % (grid * grid);
+    let inset = 0.5 * f32(grid) / vec2f(size);
+    let inner = clamp(uv, inset, vec2f(1.0) - inset);
+    return (vec2f(f32(cell % grid), f32(cell / grid)) + inner) / f32(grid);
+}
+
+fn atlas_layer(index: u32, grid: u32) -> u32 {
+    return index / (grid * grid);
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = atlas_uv(input.tex_coords, input.tex_layer, sprite_grid.x, textureDimensions(sprite_atlas));
+    let color = textureSample(sprite_atlas, sprite_sampler, uv, atlas_layer(input.tex_layer, sprite_grid.x));
+    let alpha = color.a * input.tint.a;
+    let rgb = dither(mix(color.rgb * input.tint.rgb, input.fog.rgb, input.fog.a), input.clip_position.xy);
+    // Nothing to blend for the transparent parts of the image
+    if alpha < 0.004 {
+        discard;
+    }
+    if view.encode_srgb != 0u {
+        return vec4f(linear_to_srgb(rgb), alpha);
+    }
+    return vec4f(rgb, alpha);
+}
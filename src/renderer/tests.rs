@@ -0,0 +1,49 @@
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use super::*;
+
+/// `count` 4x4 wall images, each filled with a colour that says which one it is.
+fn numbered_walls(count: u32) -> Vec<DynamicImage> {
+    (0..count)
+        .map(|i| {
+            let colour = Rgba([(i % 256) as u8, (i / 256) as u8, 0, 255]);
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 4, colour))
+        })
+        .collect()
+}
+
+#[test]
+fn more_wall_images_than_layers_are_packed_into_a_grid() {
+    let walls = numbered_walls(300);
+    let grid = texture::atlas_grid(300, (4, 4), 256, 8192, "Walls").unwrap();
+    assert_eq!(grid, 2);
+
+    let layers = texture::pack_layers(&walls, grid);
+    assert_eq!(layers.len(), 75);
+    assert!(layers.iter().all(|layer| layer.dimensions() == (8, 8)));
+    // Row by row within a layer, four to a layer
+    for (image, layer, x, y) in [(0, 0, 0, 0), (5, 1, 4, 0), (298, 74, 0, 4), (299, 74, 4, 4)] {
+        assert_eq!(
+            layers[layer].get_pixel(x + 1, y + 2),
+            walls[image].get_pixel(0, 0),
+            "image {image}",
+        );
+    }
+}
+
+#[test]
+fn images_that_fit_as_layers_are_not_packed() {
+    assert_eq!(
+        texture::atlas_grid(256, (64, 64), 256, 8192, "Walls").unwrap(),
+        1
+    );
+}
+
+#[test]
+fn packing_past_the_largest_texture_is_an_error() {
+    let error = texture::atlas_grid(300, (8192, 8192), 256, 8192, "Walls").unwrap_err();
+    assert!(
+        error.to_string().contains("Walls needs 300 layers"),
+        "{error}"
+    );
+}
@@ -0,0 +1,79 @@
+use font8x8::legacy::BASIC_LEGACY;
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::renderer::texture::{Texture, grid_uniform};
+
+const GLYPH_SIZE: u32 = 8;
+
+/// The built-in ASCII font as a texture array, one glyph per layer.
+pub(crate) struct GlyphAtlas {
+    /// Kept alive for the bind group
+    #[allow(unused)]
+    texture: Texture,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl GlyphAtlas {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout) -> Self {
+        let glyphs: Vec<DynamicImage> = BASIC_LEGACY
+            .iter()
+            .map(|rows| {
+                // Each row is a byte with the leftmost pixel in the lowest bit
+                let image = RgbaImage::from_fn(GLYPH_SIZE, GLYPH_SIZE, |x, y| {
+                    let alpha = if rows[y as usize] & (1 << x) != 0 {
+                        255
+                    } else {
+                        0
+                    };
+                    Rgba([255, 255, 255, alpha])
+                });
+                DynamicImage::ImageRgba8(image)
+            })
+            .collect();
+
+        let texture =
+            Texture::from_image_list(device, queue, &glyphs, Some("Glyph Atlas")).unwrap();
+
+        // Nearest filtering keeps the pixel font crisp up close
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Glyph Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let grid = grid_uniform(device, [texture.grid, 0, 0, 0], "Glyph Grid Buffer");
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Glyph Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: grid.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            texture,
+            bind_group,
+        }
+    }
+}
+
+/// The atlas layer for a character, or `?` for characters the font doesn't have.
+pub(crate) fn glyph_layer(c: char) -> u32 {
+    if c.is_ascii() { c as u32 } else { '?' as u32 }
+}
@@ -1,18 +1,48 @@
-use std::{
-    fs,
-    path::{Path, PathBuf},
-};
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use wgpu::util::DeviceExt;
 
-use anyhow::Context;
-use image::{DynamicImage, GenericImageView};
+use crate::{assets, sprite::SpriteSheet};
 
 pub struct Texture {
     #[allow(unused)]
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    /// How many images each layer holds along each side. 1 unless an array had more images
+    /// than the GPU allows layers, and they were packed into a grid per layer instead.
+    pub grid: u32,
 }
 
+/// Size of blank layers when there are no images to take the size from.
+pub const BLANK_LAYER_SIZE: u32 = 64;
+
+/// Magenta and black squares, eight across, drawn in place of a texture that's missing so the
+/// gap is obvious.
+pub fn missing_texture(width: u32, height: u32) -> DynamicImage {
+    let square = (width.min(height) / 8).max(1);
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+        if (x / square + y / square).is_multiple_of(2) {
+            Rgba([255, 0, 255, 255])
+        } else {
+            Rgba([0, 0, 0, 255])
+        }
+    }))
+}
+
+/// Replaces the empty images left where textures failed to load with [`missing_texture`], at
+/// the size of the rest so they fit in the same array.
+pub fn fill_missing(images: &mut [DynamicImage]) {
+    let (width, height) = images
+        .iter()
+        .map(GenericImageView::dimensions)
+        .find(|&(width, height)| width > 0 && height > 0)
+        .unwrap_or((BLANK_LAYER_SIZE, BLANK_LAYER_SIZE));
+    for image in images.iter_mut().filter(|image| image.width() == 0) {
+        *image = missing_texture(width, height);
+    }
+}
+
+#[allow(unused)]
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
@@ -56,6 +86,77 @@ impl Texture {
             texture,
             view,
             sampler,
+            grid: 1,
+        }
+    }
+
+    /// A texture that can be drawn into and then copied out of, e.g. into a texture array layer.
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            grid: 1,
+        }
+    }
+
+    /// Approximate GPU memory used by the texture, in bytes.
+    pub fn memory_size(&self) -> u64 {
+        let size = self.texture.size();
+        let texel_size = self.texture.format().block_copy_size(None).unwrap_or(4) as u64;
+
+        size.width as u64 * size.height as u64 * size.depth_or_array_layers as u64 * texel_size
+    }
+
+    /// Size of one image in the array: a whole layer, unless they're packed into a grid.
+    pub fn cell_size(&self) -> (u32, u32) {
+        let size = self.texture.size();
+        (size.width / self.grid, size.height / self.grid)
+    }
+
+    /// Where image `index` of the array is: the corner of its cell, in the layer it's in.
+    pub fn cell_origin(&self, index: u32) -> wgpu::Origin3d {
+        let cells = self.grid * self.grid;
+        let cell = index % cells;
+        let (width, height) = self.cell_size();
+        wgpu::Origin3d {
+            x: cell % self.grid * width,
+            y: cell / self.grid * height,
+            z: index / cells,
         }
     }
 
@@ -65,26 +166,53 @@ impl Texture {
         bytes: &[u8],
         label: &str,
     ) -> anyhow::Result<Self> {
-        let img = image::load_from_memory(bytes)?;
+        let img = assets::decode_image(bytes)?;
         Self::from_image(device, queue, &img, Some(label))
     }
 
-    pub fn from_bytes_array(
+    /// Builds a texture array from decoded images, followed by `blank_layers` transparent
+    /// layers of the same size. `None` when there are no layers at all.
+    pub fn from_image_array(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        bytes_list: &Vec<Vec<u8>>,
+        mut imgs: Vec<DynamicImage>,
+        blank_layers: u32,
         label: &str,
-    ) -> Option<Self> {
-        let imgs: Vec<DynamicImage> = bytes_list
-            .iter()
-            .map(|bytes| image::load_from_memory(bytes).unwrap())
-            .collect();
+    ) -> anyhow::Result<Option<Self>> {
+        let (w, h) = imgs
+            .first()
+            .map(|img| img.dimensions())
+            .unwrap_or((BLANK_LAYER_SIZE, BLANK_LAYER_SIZE));
+        for _ in 0..blank_layers {
+            imgs.push(DynamicImage::new_rgba8(w, h));
+        }
 
         if imgs.is_empty() {
-            return None;
+            return Ok(None);
         }
 
-        Some(Self::from_image_list(device, queue, &imgs, Some(label)).unwrap())
+        Self::from_image_list(device, queue, &imgs, Some(label)).map(Some)
+    }
+
+    /// Slices a sprite sheet into frames and uploads them as an array, one layer per frame.
+    pub fn from_sprite_sheet(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        sheet: &SpriteSheet,
+        img: &DynamicImage,
+        label: &str,
+    ) -> anyhow::Result<Self> {
+        let (width, height) = img.dimensions();
+        sheet.validate(width, height)?;
+
+        let frames: Vec<DynamicImage> = (0..sheet.frame_count(width, height))
+            .map(|frame| {
+                let (x, y) = sheet.frame_origin(frame, width);
+                img.crop_imm(x, y, sheet.frame_width, sheet.frame_height)
+            })
+            .collect();
+
+        Self::from_image_list(device, queue, &frames, Some(label))
     }
 
     pub fn from_image(
@@ -143,16 +271,66 @@ impl Texture {
             texture,
             view,
             sampler,
+            grid: 1,
         })
     }
 
     pub fn from_image_list(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        imgs: &Vec<image::DynamicImage>,
+        imgs: &[image::DynamicImage],
+        label: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        Self::from_layers(
+            device,
+            queue,
+            imgs,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            label,
+        )
+    }
+
+    /// Builds a texture array from images holding data rather than colors, like normal maps,
+    /// so they're read back without gamma decoding.
+    pub fn from_data_list(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        imgs: &[image::DynamicImage],
+        label: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        Self::from_layers(device, queue, imgs, wgpu::TextureFormat::Rgba8Unorm, label)
+    }
+
+    fn from_layers(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        imgs: &[image::DynamicImage],
+        format: wgpu::TextureFormat,
         label: Option<&str>,
     ) -> anyhow::Result<Self> {
-        let size = get_img_size_if_all_equal(&imgs)?;
+        let size = get_img_size_if_all_equal(imgs)?;
+        // Checked here, since wgpu only reports it as a validation error
+        let limits = device.limits();
+        let grid = atlas_grid(
+            size.depth_or_array_layers,
+            (size.width, size.height),
+            limits.max_texture_array_layers,
+            limits.max_texture_dimension_2d,
+            label.unwrap_or("texture array"),
+        )?;
+        let packed;
+        let imgs = if grid > 1 {
+            println!(
+                "{} has more images than this GPU allows layers, so they're packed {grid} to a \
+                 side in each",
+                label.unwrap_or("texture array"),
+            );
+            packed = pack_layers(imgs, grid);
+            &packed
+        } else {
+            imgs
+        };
+        let size = get_img_size_if_all_equal(imgs)?;
         let layers = size.depth_or_array_layers;
 
         let texture = device.create_texture(&wgpu::TextureDescriptor {
@@ -161,7 +339,7 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING
                 | wgpu::TextureUsages::COPY_DST
                 | wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -219,11 +397,67 @@ impl Texture {
             texture,
             view,
             sampler,
+            grid,
         })
     }
 }
 
-fn get_img_size_if_all_equal(imgs: &Vec<image::DynamicImage>) -> anyhow::Result<wgpu::Extent3d> {
+/// How many images along each side every layer of an array holds so `images` of them fit in
+/// `max_layers` layers: 1 while each gets a layer of its own, and more once that would take
+/// too many. Errors when the grid that fits would make layers wider or taller than
+/// `max_dimension`.
+pub fn atlas_grid(
+    images: u32,
+    (width, height): (u32, u32),
+    max_layers: u32,
+    max_dimension: u32,
+    label: &str,
+) -> anyhow::Result<u32> {
+    let mut grid = 1;
+    while images.div_ceil(grid * grid) > max_layers {
+        grid += 1;
+    }
+    let (layer_width, layer_height) = (width * grid, height * grid);
+    if layer_width.max(layer_height) > max_dimension {
+        anyhow::bail!(
+            "{label} needs {images} layers, but this GPU allows at most {max_layers} in a texture \
+             array, and packing them {grid} to a side would make each {layer_width}x{layer_height}, \
+             over its limit of {max_dimension}. Use fewer distinct images of that kind in the \
+             map, or smaller ones.",
+        );
+    }
+    Ok(grid)
+}
+
+/// A uniform holding the [`grid`](Texture::grid) of up to four arrays, for shaders to find
+/// images in them.
+pub fn grid_uniform(device: &wgpu::Device, grids: [u32; 4], label: &str) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(label),
+        contents: bytemuck::cast_slice(&grids),
+        usage: wgpu::BufferUsages::UNIFORM,
+    })
+}
+
+/// Packs images `grid` to a side into each layer, left to right then top to bottom. The cells
+/// after the last image are left transparent.
+pub fn pack_layers(images: &[DynamicImage], grid: u32) -> Vec<DynamicImage> {
+    let (width, height) = images.first().map_or((0, 0), GenericImageView::dimensions);
+    images
+        .chunks((grid * grid) as usize)
+        .map(|cells| {
+            let mut layer = RgbaImage::new(width * grid, height * grid);
+            for (cell, image) in (0..).zip(cells) {
+                let x = (cell % grid) * width;
+                let y = (cell / grid) * height;
+                image::imageops::replace(&mut layer, &image.to_rgba8(), x.into(), y.into());
+            }
+            DynamicImage::ImageRgba8(layer)
+        })
+        .collect()
+}
+
+fn get_img_size_if_all_equal(imgs: &[image::DynamicImage]) -> anyhow::Result<wgpu::Extent3d> {
     if imgs.is_empty() {
         anyhow::bail!("Empty image list");
     }
@@ -241,57 +475,3 @@ fn get_img_size_if_all_equal(imgs: &Vec<image::DynamicImage>) -> anyhow::Result<
         depth_or_array_layers: imgs.len() as u32,
     })
 }
-
-pub fn load_asset(rel_path: &str) -> anyhow::Result<Vec<u8>> {
-    // Reject absolute paths to enforce assets rooted under `res/` by default.
-    let rel_path = Path::new(rel_path);
-    if rel_path.is_absolute() {
-        anyhow::bail!(
-            "expected relative asset path, got absolute: {}",
-            rel_path.display()
-        );
-    }
-
-    // Candidate roots in order (ASSETS_DIR from build.rs, then project res, exe-res, cwd/res)
-    let candidates: Vec<PathBuf> = vec![
-        option_env!("ASSETS_DIR").map(PathBuf::from),
-        Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("res")),
-        std::env::current_exe()
-            .ok()
-            .and_then(|exe| exe.parent().map(|p| p.join("res"))),
-        std::env::current_dir().ok().map(|cwd| cwd.join("res")),
-    ]
-    .into_iter()
-    .flatten()
-    .collect();
-
-    for root in candidates {
-        let full = root.join(rel_path);
-
-        // Skip if file doesn't exist at this root
-        if !full.exists() {
-            continue;
-        }
-
-        // canonicalize both root and file to protect against path traversal (..)
-        let canon_root = root
-            .canonicalize()
-            .with_context(|| format!("failed to canonicalize assets root {}", root.display()))?;
-        let canon_full = full
-            .canonicalize()
-            .with_context(|| format!("failed to canonicalize asset path {}", full.display()))?;
-
-        // Ensure the final path is inside the assets root
-        if !canon_full.starts_with(&canon_root) {
-            // This means rel_path tried to escape the assets dir.
-            continue;
-        }
-
-        // Finally read and return the bytes
-        let bytes = fs::read(&canon_full)
-            .with_context(|| format!("failed to read asset {}", canon_full.display()))?;
-        return Ok(bytes);
-    }
-
-    anyhow::bail!("asset not found: {}", rel_path.display());
-}
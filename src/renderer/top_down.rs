@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+
+use crate::{
+    raycaster::FlatSpriteInstance,
+    renderer::{
+        MAX_FLAT_SPRITES, Textures, floor,
+        texture::{Texture, grid_uniform},
+    },
+};
+
+/// Camera uniform for the top-down pass. Matches `TopDownCamera` in top_down.wgsl.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct TopDownCamera {
+    /// The world point at the middle of the view
+    pub center: [f32; 2],
+    pub player: [f32; 2],
+    /// Radians
+    pub player_rotation: f32,
+    pub units_per_pixel: f32,
+    pub tile_size: f32,
+    pub icon_count: u32,
+}
+
+/// Draws the map from straight above in place of the main view: each tile's floor or wall
+/// texture, sprites as icons and the player as an arrow.
+pub(crate) struct TopDownPass {
+    texture_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    camera_buffer: wgpu::Buffer,
+    icon_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+}
+
+impl TopDownPass {
+    pub fn new(
+        device: &wgpu::Device,
+        view_bind_group_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let texture_array_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2Array,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let tile_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Uint,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Top-down texture bind group layout"),
+            entries: &[
+                texture_array_entry(0),
+                texture_array_entry(1),
+                texture_array_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                tile_entry(4),
+                tile_entry(5),
+                uniform_entry(6),
+            ],
+        });
+
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Top-down camera bind group layout"),
+            entries: &[uniform_entry(0), uniform_entry(1)],
+        });
+
+        let camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Top-down Camera Buffer"),
+            size: std::mem::size_of::<TopDownCamera>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let icon_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Top-down Icon Buffer"),
+            size: (std::mem::size_of::<FlatSpriteInstance>() * MAX_FLAT_SPRITES)
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Top-down Camera Bind Group"),
+            layout: &camera_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: icon_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("top_down.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Top-down Pipeline Layout"),
+            bind_group_layouts: &[&texture_layout, view_bind_group_layout, &camera_layout],
+            immediate_size: 0,
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Top-down Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview_mask: None,
+            cache: Default::default(),
+        });
+
+        // Nearest, so tiles stay crisp when zoomed in
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Top-down Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture_layout,
+            pipeline,
+            sampler,
+            camera_buffer,
+            icon_buffer,
+            camera_bind_group,
+        }
+    }
+
+    /// Uploads which wall layer each tile uses.
+    pub fn create_tile_walls(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tiles: &[Vec<u8>],
+        wall_image_map: &HashMap<usize, usize>,
+    ) -> Texture {
+        // Layer + 1, so 0 means the tile has no walls
+        let layers: Vec<u32> = tiles
+            .iter()
+            .flatten()
+            .map(|tile_id| {
+                wall_image_map
+                    .get(&(*tile_id as usize))
+                    .map_or(0, |l| *l as u32 + 1)
+            })
+            .collect();
+
+        floor::tile_texture(
+            device,
+            queue,
+            tiles,
+            wgpu::TextureFormat::R32Uint,
+            bytemuck::cast_slice(&layers),
+            "Tile Walls",
+        )
+    }
+
+    /// `blank` is bound in place of arrays the map doesn't have.
+    pub fn create_map_bind_group(
+        &self,
+        device: &wgpu::Device,
+        textures: &Textures,
+        tile_layers: &Texture,
+        tile_walls: &Texture,
+        blank: &Texture,
+    ) -> wgpu::BindGroup {
+        let floor = textures.floor_texture_arr.as_ref().unwrap_or(blank);
+        let walls = textures.wall_texture_arr.as_ref().unwrap_or(blank);
+        let sprites = textures.sprite_texture_arr.as_ref().unwrap_or(blank);
+        // Laid out like the floor pass's, which has ceilings too
+        let grids = grid_uniform(
+            device,
+            [floor.grid, 1, sprites.grid, walls.grid],
+            "Top-down Grid Buffer",
+        );
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Top-down Bind Group"),
+            layout: &self.texture_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&floor.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&walls.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&sprites.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&tile_layers.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&tile_walls.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: grids.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    pub fn write(&self, queue: &wgpu::Queue, camera: &TopDownCamera, icons: &[FlatSpriteInstance]) {
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(camera));
+
+        let count = icons.len().min(MAX_FLAT_SPRITES);
+        queue.write_buffer(&self.icon_buffer, 0, bytemuck::cast_slice(&icons[..count]));
+    }
+
+    /// Records the pass into an open render pass.
+    pub fn draw(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        map_bind_group: &wgpu::BindGroup,
+        view_bind_group: &wgpu::BindGroup,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, map_bind_group, &[]);
+        render_pass.set_bind_group(1, view_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.camera_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
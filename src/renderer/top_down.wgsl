@@ -0,0 +1,170 @@
+@group(0) @binding(0) var floor_atlas: texture_2d_array<f32>;
+@group(0) @binding(1) var wall_atlas: texture_2d_array<f32>;
+@group(0) @binding(2) var sprite_atlas: texture_2d_array<f32>;
+@group(0) @binding(3) var atlas_sampler: sampler;
+// Per tile: floor layer + 1 in the low 16 bits, ceiling layer + 1 in the next 15 and whether
+// the tile is solid in the top bit
+@group(0) @binding(4) var tile_layers: texture_2d<u32>;
+// Per tile: wall layer + 1, or 0 for tiles without walls
+@group(0) @binding(5) var tile_walls: texture_2d<u32>;
+
+// Images per side in each layer of the texture arrays
+struct AtlasGrids {
+    floor: u32,
+    // Unused, the top-down view has no ceilings
+    ceiling: u32,
+    sprite: u32,
+    wall: u32,
+}
+
+@group(0) @binding(6) var<uniform> grids: AtlasGrids;
+
+struct ViewUniform {
+    screen_size: vec2f,
+    // Set when the target format doesn't gamma encode on write
+    encode_srgb: u32,
+    // Set when colors should be dithered before the target quantizes them
+    dither: u32,
+    // Multiplies everything but the sky, for the time of day
+    light: vec3f,
+};
+
+@group(1) @binding(0) var<uniform> view: ViewUniform;
+
+struct TopDownCamera {
+    // The world point at the middle of the view
+    center: vec2f,
+    player: vec2f,
+    // Radians
+    player_rotation: f32,
+    units_per_pixel: f32,
+    tile_size: f32,
+    icon_count: u32,
+};
+
+struct Icon {
+    position: vec2f,
+    half_size: f32,
+    opacity: f32,
+    layer: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+const MAX_ICONS: u32 = 64u;
+
+@group(2) @binding(0) var<uniform> camera: TopDownCamera;
+@group(2) @binding(1) var<uniform> icons: array<Icon, MAX_ICONS>;
+
+// Shown outside the map and where a tile has no floor
+const BACKGROUND: vec3f = vec3f(0.0, 0.0, 0.0);
+// How much darker walls are than floors, so they stand out from above
+const WALL_SHADE: f32 = 0.55;
+const PLAYER_COLOR: vec3f = vec3f(1.0, 0.85, 0.1);
+const OUTLINE_COLOR: vec3f = vec3f(0.0, 0.0, 0.0);
+// The player arrow's length, in tiles
+const ARROW_LENGTH: f32 = 0.5;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+// A single triangle that covers the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2f(f32((index << 1u) & 2u), f32(index & 2u));
+
+    out.clip_position = vec4f(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+
+    return out;
+}
+
+fn linear_to_srgb(color: vec3f) -> vec3f {
+    let lower = color * 12.92;
+    let higher = 1.055 * pow(color, vec3f(1.0 / 2.4)) - 0.055;
+    return select(higher, lower, color <= vec3f(0.0031308));
+}
+
+fn encode(color: vec3f) -> vec4f {
+    if view.encode_srgb != 0u {
+        return vec4f(linear_to_srgb(color), 1.0);
+    }
+    return vec4f(color, 1.0);
+}
+
+// Arrays with more images than the GPU allows layers pack them `grid` to a side in each
+// layer. Coordinates stay half a texel inside the image so filtering can't reach the next one.
+fn atlas_uv(uv: vec2f, index: u32, grid: u32, size: vec2u) -> vec2f {
+    let cell = index % (grid * grid);
+    let inset = 0.5 * f32(grid) / vec2f(size);
+    let inner = clamp(uv, inset, vec2f(1.0) - inset);
+    return (vec2f(f32(cell % grid), f32(cell / grid)) + inner) / f32(grid);
+}
+
+fn atlas_layer(index: u32, grid: u32) -> u32 {
+    return index / (grid * grid);
+}
+
+fn sample_atlas(atlas: texture_2d_array<f32>, uv: vec2f, index: u32, grid: u32) -> vec4f {
+    let size = textureDimensions(atlas);
+    return textureSampleLevel(atlas, atlas_sampler, atlas_uv(uv, index, grid, size), atlas_layer(index, grid), 0.0);
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = input.clip_position.xy;
+    let world = camera.center + (pixel - view.screen_size / 2.0) * camera.units_per_pixel;
+    let map_size = vec2i(textureDimensions(tile_layers));
+    let tile = vec2i(floor(world / camera.tile_size));
+    let tile_uv = fract(world / camera.tile_size);
+
+    var color = BACKGROUND;
+    if all(tile >= vec2i(0)) && all(tile < map_size) {
+        let wall_layer = textureLoad(tile_walls, tile, 0).r;
+        let floor_layer = textureLoad(tile_layers, tile, 0).r & 0xffffu;
+        if wall_layer != 0u {
+            color = sample_atlas(wall_atlas, tile_uv, wall_layer - 1u, grids.wall).rgb * WALL_SHADE;
+        } else if floor_layer != 0u {
+            color = sample_atlas(floor_atlas, tile_uv, floor_layer - 1u, grids.floor).rgb;
+        }
+    }
+
+    // Sprites and entities as icons over the tiles, in order
+    for (var i = 0u; i < min(camera.icon_count, MAX_ICONS); i++) {
+        let icon = icons[i];
+        let offset = world - icon.position;
+        if all(abs(offset) < vec2f(icon.half_size)) {
+            let uv = offset / (icon.half_size * 2.0) + 0.5;
+            let texel = sample_atlas(sprite_atlas, uv, icon.layer, grids.sprite);
+            color = mix(color, texel.rgb, texel.a * icon.opacity);
+        }
+    }
+
+    // The player as an arrow pointing the way they face, outlined so it shows on any floor
+    let facing = vec2f(cos(camera.player_rotation), sin(camera.player_rotation));
+    let side = vec2f(-facing.y, facing.x);
+    let local = vec2f(dot(world - camera.player, facing), dot(world - camera.player, side))
+        / (camera.tile_size * ARROW_LENGTH);
+    let outline = camera.units_per_pixel / (camera.tile_size * ARROW_LENGTH);
+    let inside = arrow_distance(local);
+    if inside < 0.0 {
+        color = PLAYER_COLOR;
+    } else if inside < outline * 1.5 {
+        color = OUTLINE_COLOR;
+    }
+
+    return encode(color);
+}
+
+// Roughly how far a point is outside an arrowhead with its tip at (0.5, 0) and its back corners
+// at (-0.5, ±0.35), in arrow lengths. Negative inside.
+fn arrow_distance(point: vec2f) -> f32 {
+    let back = -0.5 - point.x;
+    // Each slanted side, as a line through the tip
+    let slope = normalize(vec2f(0.35, 1.0));
+    let upper = dot(point - vec2f(0.5, 0.0), slope);
+    let lower = dot(point - vec2f(0.5, 0.0), vec2f(slope.x, -slope.y));
+    return max(back, max(upper, lower));
+}
@@ -0,0 +1,89 @@
+use image::RgbaImage;
+
+use crate::{
+    config::SurfaceFormatPreference,
+    renderer::{choose_surface_format, needs_srgb_encoding, overhead::OverheadScreen},
+};
+
+/// A window besides the main one, showing an image scaled to fit. Presented after the main
+/// window each frame.
+pub(crate) struct WindowSurface {
+    pub surface: wgpu::Surface<'static>,
+    config: wgpu::SurfaceConfiguration,
+    screen: OverheadScreen,
+}
+
+impl WindowSurface {
+    pub fn new(
+        surface: wgpu::Surface<'static>,
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        preference: SurfaceFormatPreference,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let caps = surface.get_capabilities(adapter);
+        let format = choose_surface_format(preference, &caps.formats);
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: caps.alpha_modes[0],
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        surface.configure(device, &config);
+
+        let image_format = if needs_srgb_encoding(format) {
+            wgpu::TextureFormat::Rgba8Unorm
+        } else {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        };
+        Self {
+            surface,
+            config,
+            screen: OverheadScreen::new(device, format, image_format),
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.config.width, self.config.height)
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.config.width = width;
+            self.config.height = height;
+            self.surface.configure(device, &self.config);
+        }
+    }
+
+    pub fn set_image(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, image: &RgbaImage) {
+        self.screen.upload(device, queue, image);
+    }
+
+    pub fn draw(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<()> {
+        let output = match self.surface.get_current_texture() {
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(device, &self.config);
+                return Ok(());
+            }
+            Err(wgpu::SurfaceError::Timeout) => return Ok(()),
+            result => result?,
+        };
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Window Encoder"),
+        });
+        self.screen.draw(queue, &mut encoder, &view, self.size());
+        queue.submit(std::iter::once(encoder.finish()));
+        output.present();
+
+        Ok(())
+    }
+}
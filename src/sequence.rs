@@ -0,0 +1,212 @@
+use std::time::Duration;
+
+use crate::{camera::Camera, map::Region};
+
+/// One thing a [`Sequence`] does. Steps run in order; only [`Wait`](Step::Wait) and
+/// [`MoveCamera`](Step::MoveCamera) take time, the rest happen at once.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    /// Stops the player moving, turning and firing until [`UnlockInput`](Step::UnlockInput) or
+    /// the end of the sequence
+    LockInput,
+    UnlockInput,
+    /// Shows the view from a camera travelling through `waypoints` at an even speed, taking
+    /// `duration`, turning whichever way is shorter. The view stays on the last waypoint until
+    /// [`ReturnCamera`](Step::ReturnCamera) or the end of the sequence. The player doesn't
+    /// move, and the view keeps the player's field of view.
+    MoveCamera {
+        waypoints: Vec<Camera>,
+        duration: Duration,
+    },
+    /// Gives the view back to the player
+    ReturnCamera,
+    /// Shows a line of text at the bottom of the view until
+    /// [`HideText`](Step::HideText), other text or the end of the sequence
+    ShowText(String),
+    HideText,
+    Wait(Duration),
+    /// Reported to the game as a
+    /// [`SequenceEvent::Triggered`](crate::engine::SequenceEvent::Triggered) with this name,
+    /// for it to do whatever the story needs
+    Event(String),
+    /// Strikes [`Lightning`], without waiting for its thunder
+    Lightning(Lightning),
+}
+
+/// The speed sound travels, in metres per second, for how long thunder takes to arrive.
+const SPEED_OF_SOUND: f32 = 343.0;
+
+/// A flash that brightens the whole scene, followed by thunder. Struck by a sequence's
+/// [`Step::Lightning`] or [`Engine::strike_lightning`](crate::engine::Engine::strike_lightning).
+/// Damage flash reduction weakens the flash, and photosensitive safe mode keeps it dim and
+/// slow (see [`AccessibilityConfig`](crate::config::AccessibilityConfig)).
+///
+/// * distance - how far away the strike is, in metres. The thunder arrives after the time sound
+///   takes to travel that far, and is quieter the further it has come.
+/// * brightness - how much brighter the scene gets at the flash's peak, where 1.0 doubles it.
+/// * duration - how long the flash flickers for.
+/// * thunder_path - the path to a sound (from the 'res' directory) played as thunder. Sounds of
+///   lightning in a map's sequences are loaded with the map; others have to be
+///   [preloaded](crate::config::EngineConfig) to be heard.
+/// * volume - the thunder's volume from a strike right overhead.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::sequence::{Lightning, Step};
+///
+/// // About three seconds between the flash and the thunder
+/// let step = Step::Lightning(Lightning::new(1000.0).with_thunder("sounds/thunder.wav"));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lightning {
+    pub(crate) distance: f32,
+    pub(crate) brightness: f32,
+    pub(crate) duration: Duration,
+    pub(crate) thunder_path: Option<&'static str>,
+    pub(crate) volume: f32,
+}
+
+impl Lightning {
+    pub fn new(distance: f32) -> Self {
+        Self {
+            distance: distance.max(0.0),
+            brightness: 1.5,
+            duration: Duration::from_millis(300),
+            thunder_path: None,
+            volume: 1.0,
+        }
+    }
+
+    pub fn with_brightness(mut self, brightness: f32) -> Self {
+        self.brightness = brightness.max(0.0);
+        self
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn with_thunder(mut self, thunder_path: &'static str) -> Self {
+        self.thunder_path = Some(thunder_path);
+        self
+    }
+
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume.max(0.0);
+        self
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    pub fn brightness(&self) -> f32 {
+        self.brightness
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn thunder_path(&self) -> Option<&'static str> {
+        self.thunder_path
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Seconds between the flash and the thunder.
+    pub fn thunder_delay(&self) -> f32 {
+        self.distance / SPEED_OF_SOUND
+    }
+
+    /// The thunder's volume once it has travelled `distance`, halving every kilometre.
+    pub fn thunder_volume(&self) -> f32 {
+        self.volume * 0.5f32.powf(self.distance / 1000.0)
+    }
+}
+
+/// What starts a [`Sequence`] placed in a map.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SequenceTrigger {
+    /// As soon as the map is switched to
+    MapStart,
+    /// When the player first enters the region
+    PlayerEnters(Region),
+    /// When the game calls [`Engine::start_sequence`](crate::engine::Engine::start_sequence)
+    Manual,
+}
+
+/// Scripted steps for intros and story beats: taking control from the player, flying the
+/// camera along a path, showing text, waiting and telling the game when to act. Sequences
+/// placed in a map with [`Map::with_sequences`](crate::map::Map::with_sequences) run once each
+/// time the map is switched to, when their trigger fires, and any sequence can be played with
+/// [`Engine::play_sequence`](crate::engine::Engine::play_sequence). One runs at a time; others
+/// started meanwhile wait their turn.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use glam::vec2;
+/// use raycasting_engine::{
+///     camera::Camera,
+///     sequence::{Sequence, SequenceTrigger, Step},
+/// };
+///
+/// let intro = Sequence::new(
+///     "intro",
+///     vec![
+///         Step::LockInput,
+///         Step::MoveCamera {
+///             waypoints: vec![
+///                 Camera::new(vec2(96.0, 96.0), 0.0),
+///                 Camera::new(vec2(480.0, 96.0), 90.0),
+///             ],
+///             duration: Duration::from_secs(4),
+///         },
+///         Step::ShowText("The gate is sealed.".into()),
+///         Step::Wait(Duration::from_secs(2)),
+///         Step::Event("open_gate".into()),
+///     ],
+/// )
+/// .with_trigger(SequenceTrigger::MapStart);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sequence {
+    pub(crate) name: String,
+    pub(crate) steps: Vec<Step>,
+    pub(crate) trigger: SequenceTrigger,
+}
+
+impl Sequence {
+    /// A sequence the game starts itself.
+    pub fn new(name: impl Into<String>, steps: Vec<Step>) -> Self {
+        Self {
+            name: name.into(),
+            steps,
+            trigger: SequenceTrigger::Manual,
+        }
+    }
+
+    pub fn with_trigger(mut self, trigger: SequenceTrigger) -> Self {
+        self.trigger = trigger;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    pub fn trigger(&self) -> &SequenceTrigger {
+        &self.trigger
+    }
+}
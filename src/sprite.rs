@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use glam::Vec2;
+use serde::Deserialize;
+
+use crate::assets::AssetSource;
+
+/// A billboard placed in a map: an image that always faces the camera, standing on the floor.
+/// Translucent sprites are blended over whatever is behind them. Flat sprites lie on the floor
+/// instead, like decals.
+///
+/// # Example
+///
+/// ```
+/// use glam::vec2;
+/// use raycasting_engine::sprite::Sprite;
+///
+/// let barrel = Sprite::new(vec2(160.0, 96.0), "sprites/barrel.png");
+/// let ghost = Sprite::new(vec2(224.0, 96.0), "sprites/ghost.png").with_opacity(0.5);
+/// let puddle = Sprite::new(vec2(96.0, 160.0), "sprites/puddle.png").flat();
+///
+/// // Half size, hanging from a 64 unit ceiling
+/// let lamp = Sprite::new(vec2(128.0, 128.0), "sprites/lamp.png")
+///     .with_scale(0.5)
+///     .with_z_offset(32.0);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Sprite {
+    pub(crate) position: Vec2,
+    pub(crate) texture_path: &'static str,
+    pub(crate) opacity: f32,
+    pub(crate) flat: bool,
+    pub(crate) z_offset: f32,
+    pub(crate) scale: f32,
+}
+
+impl Sprite {
+    pub fn new(position: Vec2, texture_path: &'static str) -> Self {
+        Self {
+            position,
+            texture_path,
+            opacity: 1.0,
+            flat: false,
+            z_offset: 0.0,
+            scale: 1.0,
+        }
+    }
+
+    /// How opaque the sprite is, from 0.0 (invisible) to 1.0. Multiplies the image's own alpha.
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Lays the sprite flat on the floor, centered on its position and `scale` tiles across.
+    /// Walls hide it like they hide the floor, and it's only visible where there's a floor tile
+    /// or empty space.
+    pub fn flat(mut self) -> Self {
+        self.flat = true;
+        self
+    }
+
+    /// Raises the bottom of the sprite this many world units above the floor. Ignored for flat
+    /// sprites.
+    pub fn with_z_offset(mut self, z_offset: f32) -> Self {
+        self.z_offset = z_offset;
+        self
+    }
+
+    /// Size relative to a tile. At 1.0 the sprite is one tile wide and one wall tall.
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale.max(0.0);
+        self
+    }
+
+    pub fn position(&self) -> Vec2 {
+        self.position
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    pub fn z_offset(&self) -> f32 {
+        self.z_offset
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+}
+
+/// Describes how an image is split into animation frames, so an animated entity can use one
+/// image instead of one file per frame. Usually loaded from a `.ron` or `.json` file next to
+/// the image.
+///
+/// Frames are cells of a `frame_width` x `frame_height` grid, numbered left to right, top to
+/// bottom. When the sheet is uploaded each frame becomes one layer of a texture array.
+///
+/// # Example
+///
+/// ```
+/// use raycasting_engine::sprite::SpriteSheet;
+///
+/// let sheet = SpriteSheet::from_ron(
+///     r#"(
+///         image: "sprites/guard.png",
+///         frame_width: 64,
+///         frame_height: 64,
+///         frame_count: Some(6),
+///         animations: {
+///             "idle": (frames: [0]),
+///             "walk": (frames: [1, 2, 3, 4], durations: [0.1, 0.1, 0.1, 0.1]),
+///             "die": (frames: [5], looping: false),
+///         },
+///     )"#,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(sheet.frame_at("walk", 0.25), Some(3));
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpriteSheet {
+    /// Path of the sheet's image, relative to the asset root
+    pub image: String,
+    pub frame_width: u32,
+    pub frame_height: u32,
+    /// Number of frames in the sheet. `None` uses every cell of the grid.
+    #[serde(default)]
+    pub frame_count: Option<u32>,
+    /// Seconds each frame is shown for when an animation doesn't give its own durations
+    #[serde(default = "default_frame_duration")]
+    pub frame_duration: f32,
+    #[serde(default)]
+    pub animations: HashMap<String, Animation>,
+}
+
+/// A named sequence of frames within a [`SpriteSheet`].
+#[derive(Clone, Debug, Deserialize)]
+pub struct Animation {
+    pub frames: Vec<u32>,
+    /// Seconds each frame is shown for. Empty uses the sheet's `frame_duration` for all of them.
+    #[serde(default)]
+    pub durations: Vec<f32>,
+    /// Whether the animation starts over after the last frame or holds it
+    #[serde(default = "default_looping")]
+    pub looping: bool,
+}
+
+fn default_frame_duration() -> f32 {
+    0.1
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+impl SpriteSheet {
+    pub fn from_ron(source: &str) -> anyhow::Result<Self> {
+        Ok(ron::from_str(source)?)
+    }
+
+    pub fn from_json(source: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(source)?)
+    }
+
+    /// Loads a descriptor, choosing the format from the file extension.
+    pub async fn load(assets: &dyn AssetSource, path: &str) -> anyhow::Result<Self> {
+        let bytes = assets.load(path).await?;
+        let source = std::str::from_utf8(&bytes)
+            .with_context(|| format!("sprite sheet {path} is not valid UTF-8"))?;
+
+        let sheet = if path.ends_with(".json") {
+            Self::from_json(source)
+        } else if path.ends_with(".ron") {
+            Self::from_ron(source)
+        } else {
+            anyhow::bail!("sprite sheet {path} should be a .ron or .json file")
+        };
+
+        sheet.with_context(|| format!("failed to parse sprite sheet {path}"))
+    }
+
+    /// Grid columns and rows for an image of the given size.
+    pub fn grid_size(&self, image_width: u32, image_height: u32) -> (u32, u32) {
+        (
+            image_width / self.frame_width.max(1),
+            image_height / self.frame_height.max(1),
+        )
+    }
+
+    /// Number of frames in an image of the given size.
+    pub fn frame_count(&self, image_width: u32, image_height: u32) -> u32 {
+        let (columns, rows) = self.grid_size(image_width, image_height);
+        self.frame_count
+            .unwrap_or(columns * rows)
+            .min(columns * rows)
+    }
+
+    /// Top left pixel of a frame in an image `image_width` pixels wide.
+    pub fn frame_origin(&self, frame: u32, image_width: u32) -> (u32, u32) {
+        let columns = (image_width / self.frame_width.max(1)).max(1);
+        (
+            frame % columns * self.frame_width,
+            frame / columns * self.frame_height,
+        )
+    }
+
+    /// The frame an animation shows `elapsed` seconds after it started, or `None` if there's
+    /// no animation with that name.
+    pub fn frame_at(&self, animation: &str, elapsed: f32) -> Option<u32> {
+        let animation = self.animations.get(animation)?;
+        Some(animation.frame_at(elapsed, self.frame_duration))
+    }
+
+    /// Checks that the frame size fits the image and that animations only use frames that exist.
+    pub fn validate(&self, image_width: u32, image_height: u32) -> anyhow::Result<()> {
+        if self.frame_width == 0 || self.frame_height == 0 {
+            anyhow::bail!("sprite sheet {} has a zero frame size", self.image);
+        }
+
+        let frame_count = self.frame_count(image_width, image_height);
+        if frame_count == 0 {
+            anyhow::bail!(
+                "sprite sheet {} is {image_width}x{image_height}, smaller than one {}x{} frame",
+                self.image,
+                self.frame_width,
+                self.frame_height
+            );
+        }
+
+        for (name, animation) in &self.animations {
+            if let Some(frame) = animation.frames.iter().find(|f| **f >= frame_count) {
+                anyhow::bail!(
+                    "animation {name} in {} uses frame {frame} but the sheet has {frame_count}",
+                    self.image
+                );
+            }
+            if !animation.durations.is_empty()
+                && animation.durations.len() != animation.frames.len()
+            {
+                anyhow::bail!(
+                    "animation {name} in {} has {} frames but {} durations",
+                    self.image,
+                    animation.frames.len(),
+                    animation.durations.len()
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Animation {
+    fn duration(&self, index: usize, default_duration: f32) -> f32 {
+        self.durations
+            .get(index)
+            .copied()
+            .unwrap_or(default_duration)
+            .max(f32::EPSILON)
+    }
+
+    /// The frame shown `elapsed` seconds after the animation started.
+    pub fn frame_at(&self, elapsed: f32, default_duration: f32) -> u32 {
+        let Some(last) = self.frames.last() else {
+            return 0;
+        };
+
+        let total: f32 = (0..self.frames.len())
+            .map(|i| self.duration(i, default_duration))
+            .sum();
+        let mut time = if self.looping {
+            elapsed.max(0.0) % total
+        } else {
+            elapsed.max(0.0)
+        };
+
+        for (i, frame) in self.frames.iter().enumerate() {
+            time -= self.duration(i, default_duration);
+            if time < 0.0 {
+                return *frame;
+            }
+        }
+
+        *last
+    }
+}
@@ -0,0 +1,126 @@
+use std::{collections::BTreeMap, time::Duration};
+
+/// A goal the game sets for the current map, shown in the level's summary.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Objective {
+    pub name: String,
+    pub description: String,
+    pub complete: bool,
+}
+
+/// A figure from [`LevelStats`] for a [`StatusBar`](crate::hud::StatusBar) slot to show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stat {
+    /// Time on the map, as minutes and seconds
+    Time,
+    Kills,
+    /// Secrets found out of the map's total
+    Secrets,
+    /// One of the game's own counters, by name
+    Counter(&'static str),
+}
+
+/// How the player is doing on the current map: time spent, kills, secrets found, the game's
+/// own counters and its objectives. Everything starts over when the map is switched, and the
+/// finished map's stats are reported as
+/// [`StatsEvent::LevelFinished`](crate::engine::StatsEvent::LevelFinished) for a results
+/// screen.
+///
+/// Time only passes while the simulation runs. Respawning puts kills, secrets, counters and
+/// objectives back as they were at the checkpoint, but not the time.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn example(engine: &mut raycasting_engine::engine::Engine) {
+/// use raycasting_engine::stats::Stat;
+///
+/// engine.add_objective("switch", "Open the floodgate");
+/// engine.add_to_counter("coins", 5);
+///
+/// let stats = engine.stats();
+/// println!("{} {}", stats.display(Stat::Time), stats.display(Stat::Counter("coins")));
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelStats {
+    pub(crate) map_key: &'static str,
+    pub(crate) time: Duration,
+    pub(crate) par_time: Option<Duration>,
+    pub(crate) kills: u32,
+    pub(crate) secrets_found: Vec<String>,
+    pub(crate) secrets_total: usize,
+    pub(crate) counters: BTreeMap<String, i64>,
+    pub(crate) objectives: Vec<Objective>,
+}
+
+impl LevelStats {
+    pub(crate) fn new(map_key: &'static str, par_time: Option<Duration>, secrets: usize) -> Self {
+        Self {
+            map_key,
+            par_time,
+            secrets_total: secrets,
+            ..Default::default()
+        }
+    }
+
+    pub fn map_key(&self) -> &'static str {
+        self.map_key
+    }
+
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// The map's [`par_time`](crate::map::MapMetadata::par_time), for comparing against.
+    pub fn par_time(&self) -> Option<Duration> {
+        self.par_time
+    }
+
+    pub fn kills(&self) -> u32 {
+        self.kills
+    }
+
+    /// The names of the secrets found, in the order they were found.
+    pub fn secrets_found(&self) -> &[String] {
+        &self.secrets_found
+    }
+
+    /// How many secrets the map has.
+    pub fn secrets_total(&self) -> usize {
+        self.secrets_total
+    }
+
+    /// A counter's value, 0 if nothing has been added to it.
+    pub fn counter(&self, name: &str) -> i64 {
+        self.counters.get(name).copied().unwrap_or(0)
+    }
+
+    pub fn counters(&self) -> impl Iterator<Item = (&str, i64)> {
+        self.counters
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+    }
+
+    pub fn objectives(&self) -> &[Objective] {
+        &self.objectives
+    }
+
+    /// Whether every objective has been completed. True when there are none.
+    pub fn objectives_complete(&self) -> bool {
+        self.objectives.iter().all(|objective| objective.complete)
+    }
+
+    /// A figure as text for the HUD, like `1:05` for the time or `2/3` for secrets.
+    pub fn display(&self, stat: Stat) -> String {
+        match stat {
+            Stat::Time => {
+                let seconds = self.time.as_secs();
+                format!("{}:{:02}", seconds / 60, seconds % 60)
+            }
+            Stat::Kills => self.kills.to_string(),
+            Stat::Secrets => format!("{}/{}", self.secrets_found.len(), self.secrets_total),
+            Stat::Counter(name) => self.counter(name).to_string(),
+        }
+    }
+}
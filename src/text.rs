@@ -0,0 +1,68 @@
+use glam::Vec2;
+
+/// Text floating in the world, such as a name, a line of dialogue or a damage number. Labels
+/// always face the camera, shrink with distance like everything else and are hidden behind
+/// walls.
+///
+/// Text is drawn with the engine's built-in 8x8 ASCII font. Other characters show as `?`.
+///
+/// # Example
+///
+/// ```
+/// use glam::vec2;
+/// use raycasting_engine::text::TextLabel;
+///
+/// let name = TextLabel::new(vec2(160.0, 96.0), "Guard")
+///     .with_z_offset(70.0)
+///     .with_color([1.0, 0.9, 0.2, 1.0]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct TextLabel {
+    pub(crate) position: Vec2,
+    pub(crate) text: String,
+    pub(crate) z_offset: f32,
+    pub(crate) height: f32,
+    pub(crate) color: [f32; 4],
+}
+
+impl TextLabel {
+    pub fn new(position: Vec2, text: impl Into<String>) -> Self {
+        Self {
+            position,
+            text: text.into(),
+            z_offset: 0.0,
+            height: 8.0,
+            color: [1.0; 4],
+        }
+    }
+
+    /// Raises the bottom of the text this many world units above the floor.
+    pub fn with_z_offset(mut self, z_offset: f32) -> Self {
+        self.z_offset = z_offset;
+        self
+    }
+
+    /// Height of a line of text in world units. Characters are as wide as they are tall.
+    pub fn with_height(mut self, height: f32) -> Self {
+        self.height = height.max(0.0);
+        self
+    }
+
+    /// Linear RGBA color. Alpha below 1.0 makes the text translucent.
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+    }
+
+    pub fn position(&self) -> Vec2 {
+        self.position
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
@@ -0,0 +1,600 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use glam::{Vec2, vec2};
+
+use crate::{
+    assets::AssetSource,
+    camera::Camera,
+    map::{Linedef, Map, MapMetadata, SlopeData, TileData, TileDataFC, TileType, TileTypes},
+};
+
+/// The side of a Doom map that tiles and linedefs are measured in, in world units.
+const TILE_SIZE: f32 = 64.0;
+/// Raised floors are clamped to this so they stay below the wall height.
+const MAX_FLOOR_HEIGHT: f32 = 48.0;
+/// Floor and ceiling heights are rounded to this, so sectors of nearly the same height share
+/// a tile type.
+const HEIGHT_QUANTUM: f32 = 4.0;
+/// The ceiling flat Doom draws as sky.
+const SKY_FLAT: &str = "F_SKY1";
+/// Sidedef number meaning a linedef has no back side.
+const NO_SIDEDEF: u16 = 0xFFFF;
+
+/// A lump in a WAD's directory.
+struct Lump {
+    name: String,
+    offset: usize,
+    size: usize,
+}
+
+/// A parsed Doom WAD file (IWAD or PWAD). Only Doom-format maps are read; Hexen-format maps
+/// and graphics lumps are not.
+///
+/// # Example
+///
+/// ```no_run
+/// use raycasting_engine::wad::{Wad, WadImport, WadMode, WadTextures};
+///
+/// let wad = Wad::from_bytes(std::fs::read("doom1.wad").unwrap()).unwrap();
+/// let textures = WadTextures::new("walls/wall1.png", "floors/floor1.png")
+///     .with_texture("STARTAN3", "walls/startan.png")
+///     .with_flat("FLOOR4_8", "floors/floor4_8.png")
+///     .with_sky("sky.png");
+/// let level = WadImport::new(textures)
+///     .with_mode(WadMode::Linedefs)
+///     .import(&wad, "E1M1")
+///     .unwrap();
+/// ```
+pub struct Wad {
+    bytes: Vec<u8>,
+    lumps: Vec<Lump>,
+}
+
+impl Wad {
+    pub fn from_bytes(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        anyhow::ensure!(bytes.len() >= 12, "WAD is too short for its header");
+        let magic = &bytes[0..4];
+        anyhow::ensure!(
+            magic == b"IWAD" || magic == b"PWAD",
+            "not a WAD file (no IWAD or PWAD header)"
+        );
+
+        let lump_count = u32_at(&bytes, 4) as usize;
+        let directory = u32_at(&bytes, 8) as usize;
+        anyhow::ensure!(
+            lump_count
+                .checked_mul(16)
+                .and_then(|len| len.checked_add(directory))
+                .is_some_and(|end| end <= bytes.len()),
+            "WAD directory runs past the end of the file"
+        );
+
+        let lumps = (0..lump_count)
+            .map(|i| {
+                let entry = directory + i * 16;
+                let lump = Lump {
+                    offset: u32_at(&bytes, entry) as usize,
+                    size: u32_at(&bytes, entry + 4) as usize,
+                    name: name_at(&bytes, entry + 8),
+                };
+                anyhow::ensure!(
+                    lump.offset.saturating_add(lump.size) <= bytes.len(),
+                    "lump {} runs past the end of the file",
+                    lump.name
+                );
+                Ok(lump)
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self { bytes, lumps })
+    }
+
+    pub async fn load(assets: &dyn AssetSource, path: &str) -> anyhow::Result<Self> {
+        let bytes = assets.load(path).await?;
+        Self::from_bytes(bytes).with_context(|| format!("failed to read WAD {path}"))
+    }
+
+    /// The names of the WAD's maps, like `E1M1` or `MAP01`, in the order they're stored.
+    pub fn map_names(&self) -> Vec<&str> {
+        self.lumps
+            .windows(2)
+            .filter(|pair| pair[1].name == "THINGS")
+            .map(|pair| pair[0].name.as_str())
+            .collect()
+    }
+
+    /// The data of one of a map's lumps, like `LINEDEFS`, which follow its marker.
+    fn map_lump(&self, map_name: &str, lump_name: &str) -> anyhow::Result<&[u8]> {
+        let marker = self
+            .lumps
+            .iter()
+            .position(|lump| lump.name.eq_ignore_ascii_case(map_name))
+            .with_context(|| format!("no map named {map_name} in the WAD"))?;
+        let lump = self.lumps[marker + 1..]
+            .iter()
+            .take(11)
+            .find(|lump| lump.name == lump_name)
+            .with_context(|| format!("map {map_name} has no {lump_name} lump"))?;
+        Ok(&self.bytes[lump.offset..lump.offset + lump.size])
+    }
+
+    fn has_map_lump(&self, map_name: &str, lump_name: &str) -> bool {
+        self.map_lump(map_name, lump_name).is_ok()
+    }
+}
+
+/// How a WAD map's walls are rebuilt.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WadMode {
+    /// Every wall is snapped to the tile grid. Diagonal walls become staircases.
+    #[default]
+    Grid,
+    /// Walls are kept as [`Linedef`]s at their exact angles. The grid only marks out the floor
+    /// and ceiling.
+    Linedefs,
+}
+
+/// Maps a WAD's texture and flat names to images, since WAD graphics aren't read. Names are
+/// matched ignoring case. Anything unmapped uses the default images.
+#[derive(Clone, Debug)]
+pub struct WadTextures {
+    wall: &'static str,
+    flat: &'static str,
+    sky: Option<&'static str>,
+    textures: HashMap<String, &'static str>,
+    flats: HashMap<String, &'static str>,
+}
+
+impl WadTextures {
+    pub fn new(default_wall: &'static str, default_flat: &'static str) -> Self {
+        Self {
+            wall: default_wall,
+            flat: default_flat,
+            sky: None,
+            textures: HashMap::new(),
+            flats: HashMap::new(),
+        }
+    }
+
+    /// An image for a wall texture, like `STARTAN3`.
+    pub fn with_texture(mut self, name: &str, texture_path: &'static str) -> Self {
+        self.textures
+            .insert(name.to_ascii_uppercase(), texture_path);
+        self
+    }
+
+    /// An image for a floor or ceiling flat, like `FLOOR4_8`.
+    pub fn with_flat(mut self, name: &str, texture_path: &'static str) -> Self {
+        self.flats.insert(name.to_ascii_uppercase(), texture_path);
+        self
+    }
+
+    /// Shows this image above sectors with a sky ceiling, which are otherwise given the
+    /// default flat.
+    pub fn with_sky(mut self, texture_path: &'static str) -> Self {
+        self.sky = Some(texture_path);
+        self
+    }
+
+    fn texture(&self, name: &str) -> &'static str {
+        self.textures.get(name).copied().unwrap_or(self.wall)
+    }
+
+    fn flat(&self, name: &str) -> &'static str {
+        self.flats.get(name).copied().unwrap_or(self.flat)
+    }
+}
+
+/// A map imported from a WAD.
+pub struct WadLevel {
+    pub map: Map,
+    /// Where player 1 starts, if the map places them
+    pub player_start: Option<Camera>,
+}
+
+/// Rebuilds a Doom map's walls, floors and ceilings as a [`Map`]. This is an approximation:
+///
+/// * Doom units are scaled so `cell_size` of them make a tile, and heights by `height_scale`.
+/// * Each tile takes the sector under its center. Raised floors become steps and low
+///   ceilings become lowered ceilings, but a tile can't have both, so the step wins.
+/// * Closed sectors, like shut doors, are solid. Doors, lifts, lighting and things other than
+///   the player start are ignored.
+///
+/// See [`Wad`] for an example.
+#[derive(Clone, Debug)]
+pub struct WadImport {
+    textures: WadTextures,
+    mode: WadMode,
+    cell_size: f32,
+    height_scale: f32,
+}
+
+impl WadImport {
+    /// Imports to the grid, with 32 Doom units to a tile and heights halved, which puts
+    /// Doom's usual 128 unit rooms at the wall height.
+    pub fn new(textures: WadTextures) -> Self {
+        Self {
+            textures,
+            mode: WadMode::Grid,
+            cell_size: 32.0,
+            height_scale: 0.5,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: WadMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// How many Doom units wide a tile is. Smaller cells follow the walls more closely but
+    /// make a bigger map.
+    pub fn with_cell_size(mut self, cell_size: f32) -> Self {
+        self.cell_size = cell_size.max(1.0);
+        self
+    }
+
+    /// World units per Doom unit for floor and ceiling heights.
+    pub fn with_height_scale(mut self, height_scale: f32) -> Self {
+        self.height_scale = height_scale.max(0.0);
+        self
+    }
+
+    pub fn import(&self, wad: &Wad, map_name: &str) -> anyhow::Result<WadLevel> {
+        anyhow::ensure!(
+            !wad.has_map_lump(map_name, "BEHAVIOR"),
+            "map {map_name} is in Hexen format, which isn't supported"
+        );
+        let level = DoomLevel::parse(wad, map_name)?;
+        anyhow::ensure!(!level.linedefs.is_empty(), "map {map_name} has no linedefs");
+
+        let (min, max) = level.bounds();
+        // A border of one tile keeps the edges solid
+        let cols = ((max.x - min.x) / self.cell_size).ceil() as usize + 2;
+        let rows = ((max.y - min.y) / self.cell_size).ceil() as usize + 2;
+        let scale = TILE_SIZE / self.cell_size;
+        // Doom's y points up, the map's down
+        let to_world = |point: Vec2| vec2(point.x - min.x, max.y - point.y) * scale + TILE_SIZE;
+        let to_doom = |world: Vec2| {
+            let doom = (world - TILE_SIZE) / scale;
+            vec2(doom.x + min.x, max.y - doom.y)
+        };
+        let lowest_floor = level
+            .sectors
+            .iter()
+            .map(|sector| sector.floor)
+            .fold(f32::MAX, f32::min);
+
+        let mut tiles = TileBuilder::default();
+        let mut layout = vec![vec![0; cols]; rows];
+        for (row, tile_row) in layout.iter_mut().enumerate() {
+            for (col, tile) in tile_row.iter_mut().enumerate() {
+                let corner = vec2(col as f32, row as f32) * TILE_SIZE;
+                let center = to_doom(corner + TILE_SIZE / 2.0);
+                let sector = match self.mode {
+                    WadMode::Grid => level.sector_at(center),
+                    // A tile the walls pass through stays open so it doesn't poke out of them
+                    WadMode::Linedefs => [vec2(0.5, 0.5), vec2(0.05, 0.05), vec2(0.95, 0.05)]
+                        .into_iter()
+                        .chain([vec2(0.05, 0.95), vec2(0.95, 0.95)])
+                        .find_map(|offset| level.sector_at(to_doom(corner + offset * TILE_SIZE))),
+                };
+
+                let tile_type = match sector {
+                    Some(sector) if self.mode == WadMode::Linedefs || !sector.is_closed() => {
+                        self.floor_tile(sector, lowest_floor)
+                    }
+                    _ => TileType::Wall(TileData::new(self.wall_texture(&level, center))),
+                };
+                *tile = tiles.id(tile_type)?;
+            }
+        }
+
+        let player_start = level.player_start.map(|(position, angle)| {
+            // Doom's angles turn counterclockwise with y up, the camera's clockwise with y down
+            Camera::new(to_world(position), (360.0 - angle) % 360.0)
+        });
+
+        let mut metadata = MapMetadata::new().with_name(map_name.to_ascii_uppercase());
+        if let Some(sky) = self.textures.sky {
+            metadata = metadata.with_sky(sky);
+        }
+        if let Some(camera) = player_start {
+            metadata = metadata.with_player_start(camera.position, camera.rotation);
+        }
+        let mut map = Map::new(layout, tiles.tile_types).with_metadata(metadata);
+        if self.mode == WadMode::Linedefs {
+            map = map.with_linedefs(
+                level
+                    .linedefs
+                    .iter()
+                    .filter(|linedef| level.blocks(linedef))
+                    .map(|linedef| {
+                        Linedef::new(
+                            to_world(level.vertices[linedef.start]),
+                            to_world(level.vertices[linedef.end]),
+                            self.textures.texture(level.texture_name(linedef)),
+                        )
+                    })
+                    .collect(),
+            );
+        }
+
+        Ok(WadLevel { map, player_start })
+    }
+
+    fn floor_tile(&self, sector: &Sector, lowest_floor: f32) -> TileType {
+        let quantize = |height: f32| (height / HEIGHT_QUANTUM).round() * HEIGHT_QUANTUM;
+        let floor = self.textures.flat(&sector.floor_flat);
+        let floor_height =
+            quantize((sector.floor - lowest_floor) * self.height_scale).min(MAX_FLOOR_HEIGHT);
+
+        if sector.ceiling_flat == SKY_FLAT && self.textures.sky.is_some() {
+            return TileType::Outdoor(TileData::new(floor));
+        }
+        if floor_height > 0.0 {
+            return TileType::Slope(SlopeData::step(floor, floor_height));
+        }
+
+        let ceiling = TileDataFC::new(floor, self.textures.flat(&sector.ceiling_flat));
+        let ceiling_height = quantize((sector.ceiling - sector.floor) * self.height_scale);
+        TileType::FloorCeiling(if ceiling_height < TILE_SIZE {
+            ceiling.with_ceiling_height(ceiling_height)
+        } else {
+            ceiling
+        })
+    }
+
+    /// The texture of the solid linedef nearest to a point.
+    fn wall_texture(&self, level: &DoomLevel, point: Vec2) -> &'static str {
+        level
+            .linedefs
+            .iter()
+            .filter(|linedef| level.blocks(linedef))
+            .map(|linedef| {
+                let start = level.vertices[linedef.start];
+                let segment = level.vertices[linedef.end] - start;
+                let t = ((point - start).dot(segment) / segment.length_squared().max(f32::EPSILON))
+                    .clamp(0.0, 1.0);
+                (point.distance_squared(start + segment * t), linedef)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map_or(self.textures.wall, |(_, linedef)| {
+                self.textures.texture(level.texture_name(linedef))
+            })
+    }
+}
+
+/// Assigns tile ids to distinct tile types.
+#[derive(Default)]
+struct TileBuilder {
+    tile_types: TileTypes,
+    ids: HashMap<String, u8>,
+}
+
+impl TileBuilder {
+    fn id(&mut self, tile_type: TileType) -> anyhow::Result<u8> {
+        // Tile types can't be hashed for their floats, but their debug output can
+        let key = format!("{tile_type:?}");
+        if let Some(id) = self.ids.get(&key) {
+            return Ok(*id);
+        }
+
+        let id = u8::try_from(self.ids.len())
+            .ok()
+            .context("the map has more than 256 distinct tiles; try fewer texture mappings")?;
+        self.ids.insert(key, id);
+        self.tile_types.insert(id, tile_type);
+        Ok(id)
+    }
+}
+
+struct DoomLinedef {
+    start: usize,
+    end: usize,
+    front: Option<usize>,
+    back: Option<usize>,
+}
+
+struct Sidedef {
+    upper: String,
+    lower: String,
+    middle: String,
+    sector: usize,
+}
+
+struct Sector {
+    floor: f32,
+    ceiling: f32,
+    floor_flat: String,
+    ceiling_flat: String,
+}
+
+impl Sector {
+    fn is_closed(&self) -> bool {
+        self.ceiling <= self.floor
+    }
+}
+
+/// A Doom-format map's geometry, in Doom units.
+struct DoomLevel {
+    vertices: Vec<Vec2>,
+    linedefs: Vec<DoomLinedef>,
+    sidedefs: Vec<Sidedef>,
+    sectors: Vec<Sector>,
+    /// Player 1's position and angle in degrees
+    player_start: Option<(Vec2, f32)>,
+}
+
+impl DoomLevel {
+    fn parse(wad: &Wad, map_name: &str) -> anyhow::Result<Self> {
+        let vertices = wad
+            .map_lump(map_name, "VERTEXES")?
+            .chunks_exact(4)
+            .map(|vertex| vec2(i16_at(vertex, 0) as f32, i16_at(vertex, 2) as f32))
+            .collect();
+        let sectors = wad
+            .map_lump(map_name, "SECTORS")?
+            .chunks_exact(26)
+            .map(|sector| Sector {
+                floor: i16_at(sector, 0) as f32,
+                ceiling: i16_at(sector, 2) as f32,
+                floor_flat: name_at(sector, 4),
+                ceiling_flat: name_at(sector, 12),
+            })
+            .collect();
+        let sidedefs = wad
+            .map_lump(map_name, "SIDEDEFS")?
+            .chunks_exact(30)
+            .map(|sidedef| Sidedef {
+                upper: name_at(sidedef, 4),
+                lower: name_at(sidedef, 12),
+                middle: name_at(sidedef, 20),
+                sector: u16_at(sidedef, 28) as usize,
+            })
+            .collect();
+        let side = |sidedef: u16| (sidedef != NO_SIDEDEF).then_some(sidedef as usize);
+        let linedefs = wad
+            .map_lump(map_name, "LINEDEFS")?
+            .chunks_exact(14)
+            .map(|linedef| DoomLinedef {
+                start: u16_at(linedef, 0) as usize,
+                end: u16_at(linedef, 2) as usize,
+                front: side(u16_at(linedef, 10)),
+                back: side(u16_at(linedef, 12)),
+            })
+            .collect();
+        let player_start = wad
+            .map_lump(map_name, "THINGS")?
+            .chunks_exact(10)
+            .find(|thing| u16_at(thing, 6) == 1)
+            .map(|thing| {
+                let position = vec2(i16_at(thing, 0) as f32, i16_at(thing, 2) as f32);
+                (position, i16_at(thing, 4) as f32)
+            });
+
+        let level = Self {
+            vertices,
+            linedefs,
+            sidedefs,
+            sectors,
+            player_start,
+        };
+        level.validate(map_name)?;
+        Ok(level)
+    }
+
+    fn validate(&self, map_name: &str) -> anyhow::Result<()> {
+        for linedef in &self.linedefs {
+            anyhow::ensure!(
+                linedef.start < self.vertices.len() && linedef.end < self.vertices.len(),
+                "a linedef in {map_name} uses a missing vertex"
+            );
+            anyhow::ensure!(
+                [linedef.front, linedef.back]
+                    .into_iter()
+                    .flatten()
+                    .all(|sidedef| sidedef < self.sidedefs.len()),
+                "a linedef in {map_name} uses a missing sidedef"
+            );
+        }
+        anyhow::ensure!(
+            self.sidedefs
+                .iter()
+                .all(|sidedef| sidedef.sector < self.sectors.len()),
+            "a sidedef in {map_name} uses a missing sector"
+        );
+        Ok(())
+    }
+
+    /// The smallest and largest corners around every linedef.
+    fn bounds(&self) -> (Vec2, Vec2) {
+        self.linedefs
+            .iter()
+            .flat_map(|linedef| [self.vertices[linedef.start], self.vertices[linedef.end]])
+            .fold((Vec2::MAX, Vec2::MIN), |(min, max), vertex| {
+                (min.min(vertex), max.max(vertex))
+            })
+    }
+
+    /// The sector containing a point, found from the nearest linedef to its right, the way
+    /// Doom's own point-in-sector test works before it has a BSP.
+    fn sector_at(&self, point: Vec2) -> Option<&Sector> {
+        let mut nearest: Option<(f32, &DoomLinedef)> = None;
+        for linedef in &self.linedefs {
+            let start = self.vertices[linedef.start];
+            let end = self.vertices[linedef.end];
+            if (start.y > point.y) == (end.y > point.y) {
+                continue;
+            }
+
+            let x = start.x + (point.y - start.y) / (end.y - start.y) * (end.x - start.x);
+            if x >= point.x && nearest.is_none_or(|(nearest_x, _)| x < nearest_x) {
+                nearest = Some((x, linedef));
+            }
+        }
+
+        let (_, linedef) = nearest?;
+        let start = self.vertices[linedef.start];
+        let end = self.vertices[linedef.end];
+        // Front sides face right of the linedef's direction
+        let sidedef = if (end - start).perp_dot(point - start) < 0.0 {
+            linedef.front
+        } else {
+            linedef.back
+        }?;
+        Some(&self.sectors[self.sidedefs[sidedef].sector])
+    }
+
+    /// Whether a linedef is a solid wall: one-sided, or between sectors where either is
+    /// closed.
+    fn blocks(&self, linedef: &DoomLinedef) -> bool {
+        match (linedef.front, linedef.back) {
+            (Some(front), Some(back)) => {
+                let front = &self.sectors[self.sidedefs[front].sector];
+                let back = &self.sectors[self.sidedefs[back].sector];
+                front.ceiling.min(back.ceiling) <= front.floor.max(back.floor)
+            }
+            _ => true,
+        }
+    }
+
+    /// The first texture set on either side, preferring the middle of a one-sided wall and the
+    /// upper texture of a closed door.
+    fn texture_name(&self, linedef: &DoomLinedef) -> &str {
+        let sides = [linedef.front, linedef.back]
+            .into_iter()
+            .flatten()
+            .map(|sidedef| &self.sidedefs[sidedef]);
+        let names: Vec<&str> = if linedef.back.is_none() {
+            sides.map(|sidedef| sidedef.middle.as_str()).collect()
+        } else {
+            sides
+                .flat_map(|sidedef| [&sidedef.upper, &sidedef.lower, &sidedef.middle])
+                .map(String::as_str)
+                .collect()
+        };
+        names.into_iter().find(|name| *name != "-").unwrap_or("-")
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn i16_at(bytes: &[u8], offset: usize) -> i16 {
+    i16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// An eight byte, zero padded lump or texture name, in upper case.
+fn name_at(bytes: &[u8], offset: usize) -> String {
+    bytes[offset..offset + 8]
+        .iter()
+        .take_while(|byte| **byte != 0)
+        .map(|byte| byte.to_ascii_uppercase() as char)
+        .collect()
+}
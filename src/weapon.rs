@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use crate::entity::Entity;
+
+/// How a [`Weapon`]'s shots reach what they hit.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FireMode {
+    /// Hits the nearest body along the view direction within `range` world units straight
+    /// away, if it's of a faction hostile to the player's and not behind a wall
+    Hitscan { range: f32, damage: f32 },
+    /// Launches a copy of `entity` from in front of the player, flying the way they face at
+    /// `speed` world units per second. Give it a faction and
+    /// [`Entity::with_damage`](crate::entity::Entity::with_damage) for it to hurt anything.
+    Projectile { entity: Box<Entity>, speed: f32 },
+}
+
+/// Something the player fires, described by data: the view sprites drawn at the bottom of the
+/// view, how often it can fire, how its shots hit, what ammo it uses and the sound it makes.
+/// Firing is the left mouse button or [`InputState::fire`](crate::engine::InputState::fire),
+/// and each shot is reported as a [`ShotEvent`](crate::engine::ShotEvent).
+///
+/// View sprites, projectile sprites and sounds are loaded with every map. Ammo is an item in the player's
+/// [`Inventory`](crate::inventory::Inventory), taken each shot.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use glam::Vec2;
+/// use raycasting_engine::{
+///     entity::{CollisionLayers, Entity, Faction},
+///     weapon::{FireMode, Weapon},
+/// };
+///
+/// let pistol = Weapon::new(
+///     "pistol",
+///     "sprites/pistol.png",
+///     FireMode::Hitscan {
+///         range: 1024.0,
+///         damage: 10.0,
+///     },
+/// )
+/// .with_fire_sprites(vec!["sprites/pistol_fire1.png", "sprites/pistol_fire2.png"])
+/// .with_fire_interval(Duration::from_millis(400))
+/// .with_ammo("bullets", 1)
+/// .with_sound("sounds/pistol.wav")
+/// .with_noise(512.0);
+///
+/// let bolt = Entity::new(Vec2::ZERO)
+///     .with_radius(4.0)
+///     .with_sprite("sprites/bolt.png")
+///     .with_collision(CollisionLayers::PROJECTILE, CollisionLayers::ENEMY)
+///     .sensor()
+///     .with_faction(Faction::PLAYER)
+///     .with_damage(25.0);
+/// let crossbow = Weapon::new(
+///     "crossbow",
+///     "sprites/crossbow.png",
+///     FireMode::Projectile {
+///         entity: Box::new(bolt),
+///         speed: 600.0,
+///     },
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Weapon {
+    pub(crate) name: String,
+    pub(crate) idle_sprite: &'static str,
+    /// Shown in turn after each shot
+    pub(crate) fire_sprites: Vec<&'static str>,
+    pub(crate) frame_time: Duration,
+    /// The shortest time between shots
+    pub(crate) fire_interval: Duration,
+    pub(crate) mode: FireMode,
+    /// The item taken from the inventory each shot, and how many
+    pub(crate) ammo: Option<(String, u32)>,
+    pub(crate) sound: Option<&'static str>,
+    /// How far away AI hears each shot, in world units, or 0 for silent
+    pub(crate) noise: f32,
+    /// The view sprite's size as a fraction of the view's height
+    pub(crate) scale: f32,
+}
+
+impl Weapon {
+    /// A weapon firing twice a second without ammo or a sound, its view sprite half the view
+    /// tall.
+    pub fn new(name: impl Into<String>, idle_sprite: &'static str, mode: FireMode) -> Self {
+        Self {
+            name: name.into(),
+            idle_sprite,
+            fire_sprites: Vec::new(),
+            frame_time: Duration::from_millis(100),
+            fire_interval: Duration::from_millis(500),
+            mode,
+            ammo: None,
+            sound: None,
+            noise: 0.0,
+            scale: 0.5,
+        }
+    }
+
+    /// View sprites to play through after each shot before going back to the idle one.
+    pub fn with_fire_sprites(mut self, fire_sprites: Vec<&'static str>) -> Self {
+        self.fire_sprites = fire_sprites;
+        self
+    }
+
+    /// How long each fire sprite is shown. Defaults to 100 ms.
+    pub fn with_frame_time(mut self, frame_time: Duration) -> Self {
+        self.frame_time = frame_time;
+        self
+    }
+
+    /// The shortest time between shots while fire is held.
+    pub fn with_fire_interval(mut self, fire_interval: Duration) -> Self {
+        self.fire_interval = fire_interval;
+        self
+    }
+
+    /// Takes `per_shot` of `item` from the inventory each shot, and won't fire without them.
+    pub fn with_ammo(mut self, item: impl Into<String>, per_shot: u32) -> Self {
+        self.ammo = Some((item.into(), per_shot));
+        self
+    }
+
+    pub fn with_sound(mut self, sound_path: &'static str) -> Self {
+        self.sound = Some(sound_path);
+        self
+    }
+
+    /// Makes each shot a noise AI hears up to `radius` world units away, like
+    /// [`Engine::make_noise`](crate::engine::Engine::make_noise).
+    pub fn with_noise(mut self, radius: f32) -> Self {
+        self.noise = radius.max(0.0);
+        self
+    }
+
+    /// How tall the view sprite is drawn, as a fraction of the view's height. Sprites are
+    /// square and sit at the bottom middle of the view.
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale.max(0.0);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn mode(&self) -> &FireMode {
+        &self.mode
+    }
+
+    pub fn fire_interval(&self) -> Duration {
+        self.fire_interval
+    }
+
+    /// The ammo item and how many each shot takes.
+    pub fn ammo(&self) -> Option<(&str, u32)> {
+        self.ammo
+            .as_ref()
+            .map(|(item, per_shot)| (item.as_str(), *per_shot))
+    }
+
+    /// Every image the weapon is drawn with, and its projectile's sprite.
+    pub(crate) fn sprites(&self) -> impl Iterator<Item = &'static str> + '_ {
+        let projectile = match &self.mode {
+            FireMode::Projectile { entity, .. } => entity.texture_path,
+            FireMode::Hitscan { .. } => None,
+        };
+        std::iter::once(self.idle_sprite)
+            .chain(self.fire_sprites.iter().copied())
+            .chain(projectile)
+    }
+}
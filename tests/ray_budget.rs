@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+use glam::Vec2;
+use raycasting_engine::{
+    camera::Camera,
+    map::{Map, TILE_SIZE, TileData, TileType, TileTypes},
+    profiling::draw_view_on_cpu,
+};
+
+/// How many times each pose is drawn. The median of them is timed, so one slow sample on a
+/// busy machine doesn't fail the test.
+const RUNS: usize = 9;
+
+/// A `size` by `size` map walled around its edge, with a pillar every eight tiles so rays
+/// cross most of it before they hit anything.
+fn open_map(size: usize) -> Map {
+    let tiles = (0..size)
+        .map(|row| {
+            (0..size)
+                .map(|col| {
+                    let edge = row == 0 || col == 0 || row == size - 1 || col == size - 1;
+                    let pillar = row % 8 == 4 && col % 8 == 4;
+                    (edge || pillar) as u8
+                })
+                .collect()
+        })
+        .collect();
+    let tile_types = TileTypes::from([
+        (0, TileType::Floor(TileData::new("floor.png"))),
+        (1, TileType::Wall(TileData::new("wall.png"))),
+    ]);
+    Map::new(tiles, tile_types)
+}
+
+/// The envelope the main view has to stay inside: casting and projecting 1280 columns on a
+/// 128 by 128 map, from a few places and headings, in well under a frame. Debug builds get
+/// more room.
+#[test]
+fn drawing_a_wide_view_on_a_large_map_stays_in_budget() {
+    let budget = if cfg!(debug_assertions) {
+        Duration::from_millis(200)
+    } else {
+        Duration::from_millis(8)
+    };
+    let map = open_map(128);
+    let tile = TILE_SIZE as f32;
+
+    let poses = [
+        (Vec2::new(64.0, 64.0) * tile, 0.0),
+        (Vec2::new(2.5, 2.5) * tile, 45.0),
+        (Vec2::new(125.5, 64.5) * tile, 180.0),
+        (Vec2::new(30.5, 110.5) * tile, 290.0),
+    ];
+    for (position, rotation) in poses {
+        let camera = Camera::new(position, rotation);
+        let mut times: Vec<Duration> = (0..RUNS)
+            .map(|_| {
+                let start = Instant::now();
+                draw_view_on_cpu(&map, &camera, 1280, 720).unwrap();
+                start.elapsed()
+            })
+            .collect();
+        times.sort();
+        let median = times[RUNS / 2];
+
+        assert!(
+            median < budget,
+            "drawing from {position} at {rotation} took {median:?}, over the {budget:?} budget"
+        );
+    }
+}